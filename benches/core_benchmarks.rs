@@ -16,7 +16,8 @@ use zeppelin::fts::types::FtsFieldConfig;
 use zeppelin::index::bitmap::build::build_cluster_bitmaps;
 use zeppelin::index::bitmap::evaluate::evaluate_filter_bitmap;
 use zeppelin::index::distance::{
-    compute_distance, cosine_distance, dot_product_distance, euclidean_distance,
+    compute_distance, compute_distances_batch, cosine_distance, dot_product_distance,
+    euclidean_distance,
 };
 use zeppelin::index::quantization::pq::PqCodebook;
 use zeppelin::index::quantization::sq::SqCalibration;
@@ -70,6 +71,38 @@ fn bench_distance(c: &mut Criterion) {
     }
 
     group.finish();
+
+    // Flat cluster scan: one query against a cluster-sized block of vectors,
+    // scored one at a time vs. in batches.
+    let mut group = c.benchmark_group("distance_scan");
+    for &dim in &[128, 768] {
+        let query = random_vector(dim);
+        let vectors = random_vectors(1000, dim);
+        group.throughput(Throughput::Elements(vectors.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("scalar_1k", dim), &dim, |bench, _| {
+            bench.iter(|| {
+                vectors
+                    .iter()
+                    .map(|v| compute_distance(black_box(&query), v, DistanceMetric::Cosine))
+                    .collect::<Vec<f32>>()
+            });
+        });
+
+        group.bench_with_input(BenchmarkId::new("batched_1k", dim), &dim, |bench, _| {
+            let mut out = vec![0.0f32; 64];
+            bench.iter(|| {
+                let mut all = Vec::with_capacity(vectors.len());
+                for chunk in vectors.chunks(64) {
+                    let out = &mut out[..chunk.len()];
+                    compute_distances_batch(black_box(&query), chunk, DistanceMetric::Cosine, out);
+                    all.extend_from_slice(out);
+                }
+                all
+            });
+        });
+    }
+    group.finish();
 }
 
 // ---------------------------------------------------------------------------
@@ -24,6 +24,12 @@ pub struct QueryRequest {
     pub nprobe: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rank_by: Option<serde_json::Value>,
+    /// "strong" or "eventual"; the server defaults to strong when omitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consistency: Option<String>,
+    /// Attribute names to return with each result.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_attributes: Option<Vec<String>>,
 }
 
 /// Search result entry.
@@ -186,3 +192,33 @@ impl BenchClient for ZeppelinClient {
         "zeppelin"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_request_serializes_consistency() {
+        let req = QueryRequest {
+            vector: Some(vec![0.0, 1.0]),
+            top_k: 5,
+            filter: None,
+            nprobe: None,
+            rank_by: None,
+            consistency: Some("eventual".to_string()),
+            include_attributes: Some(vec!["category".to_string()]),
+        };
+        let body = serde_json::to_value(&req).unwrap();
+        assert_eq!(body["consistency"], "eventual");
+        assert_eq!(body["include_attributes"], serde_json::json!(["category"]));
+
+        let req = QueryRequest {
+            consistency: None,
+            include_attributes: None,
+            ..req
+        };
+        let body = serde_json::to_value(&req).unwrap();
+        assert!(body.get("consistency").is_none());
+        assert!(body.get("include_attributes").is_none());
+    }
+}
@@ -5,6 +5,7 @@
 //! Usage:
 //!   cargo run -p zeppelin-bench -- --target http://localhost:8080 --scenario ingest
 //!   cargo run -p zeppelin-bench -- --target http://localhost:8080 --scenario query_latency
+//!   cargo run -p zeppelin-bench -- --target http://localhost:8080 --scenario fts_latency
 //!   cargo run -p zeppelin-bench -- --help
 
 mod client;
@@ -101,11 +102,14 @@ async fn main() -> anyhow::Result<()> {
         "compaction" => scenarios::compaction::run(&args, bench_client.as_ref()).await?,
         "scale_test" => scenarios::scale_test::run(&args, bench_client.as_ref()).await?,
         "bm25" => scenarios::bm25::run(&args, bench_client.as_ref()).await?,
+        "consistency" => scenarios::consistency::run(&args, bench_client.as_ref()).await?,
+        "fts_latency" => scenarios::fts_latency::run(&args, bench_client.as_ref()).await?,
         "index_comparison" => scenarios::index_comparison::run(&args, bench_client.as_ref()).await?,
         other => {
             eprintln!("Unknown scenario: {other}");
             eprintln!("Available: ingest, query_latency, query_throughput, filtered_query,");
-            eprintln!("           mixed_workload, compaction, scale_test, bm25, index_comparison");
+            eprintln!("           mixed_workload, compaction, scale_test, bm25, fts_latency,");
+            eprintln!("           index_comparison, consistency");
             std::process::exit(1);
         }
     };
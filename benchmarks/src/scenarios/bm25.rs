@@ -66,6 +66,8 @@ pub async fn run(args: &Args, client: &dyn BenchClient) -> Result<serde_json::Va
                 filter: None,
                 nprobe: None,
                 rank_by: Some(rank_by.clone()),
+                consistency: None,
+                include_attributes: None,
             };
 
             let t = Instant::now();
@@ -99,6 +101,8 @@ pub async fn run(args: &Args, client: &dyn BenchClient) -> Result<serde_json::Va
             filter: None,
             nprobe: None,
             rank_by: Some(serde_json::json!(["content", "BM25", query_text])),
+            consistency: None,
+            include_attributes: None,
         };
 
         let t = Instant::now();
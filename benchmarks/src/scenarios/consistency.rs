@@ -0,0 +1,89 @@
+//! Consistency level benchmark.
+//!
+//! Ingests vectors and immediately runs the same query set under
+//! `consistency: strong` and `consistency: eventual`. Strong queries scan
+//! uncompacted WAL fragments while eventual queries read the index only, so
+//! the gap between the two distributions is the cost of the WAL scan. Run
+//! against a server with a long compaction interval so the data stays in the
+//! WAL for the duration of the run.
+
+use std::time::Instant;
+
+use hdrhistogram::Histogram;
+
+use crate::client::{BenchClient, QueryRequest};
+use crate::datasets;
+use crate::results;
+use crate::Args;
+
+pub async fn run(args: &Args, client: &dyn BenchClient) -> Result<serde_json::Value, anyhow::Error> {
+    let ns = format!("bench-consistency-{}", rand::random::<u32>());
+
+    client
+        .create_namespace(&ns, args.dimensions, None)
+        .await
+        .map_err(|e| anyhow::anyhow!(e))?;
+
+    let vectors = datasets::random_vectors(args.vectors, args.dimensions, false);
+    eprintln!("  Ingesting {} vectors (no compaction wait)...", args.vectors);
+    for batch in vectors.chunks(args.batch_size) {
+        client
+            .upsert(&ns, batch)
+            .await
+            .map_err(|e| anyhow::anyhow!(e))?;
+    }
+
+    let n_queries = 500;
+    let queries: Vec<Vec<f32>> = (0..n_queries)
+        .map(|_| datasets::random_query(args.dimensions))
+        .collect();
+
+    let mut level_results = Vec::new();
+    let mut result_counts = Vec::new();
+
+    for level in ["strong", "eventual"] {
+        eprintln!("  Running {n_queries} queries with consistency={level}...");
+        let mut hist = Histogram::<u64>::new(3).unwrap();
+        let mut total_results = 0usize;
+
+        for q in &queries {
+            let req = QueryRequest {
+                vector: Some(q.clone()),
+                top_k: args.top_k,
+                filter: None,
+                nprobe: args.nprobe,
+                rank_by: None,
+                consistency: Some(level.to_string()),
+                include_attributes: None,
+            };
+
+            let t = Instant::now();
+            if let Ok(resp) = client.query(&ns, &req).await {
+                hist.record(t.elapsed().as_micros() as u64).ok();
+                total_results += resp.results.len();
+            }
+        }
+
+        let avg_results = total_results as f64 / hist.len().max(1) as f64;
+        eprintln!(
+            "    p50={:.1}ms p99={:.1}ms avg_results={:.1}",
+            hist.value_at_quantile(0.50) as f64 / 1000.0,
+            hist.value_at_quantile(0.99) as f64 / 1000.0,
+            avg_results,
+        );
+
+        let mut stats = results::latency_stats(&hist);
+        stats["consistency"] = serde_json::json!(level);
+        stats["total_results"] = serde_json::json!(total_results);
+        stats["avg_results"] = serde_json::json!(avg_results);
+        level_results.push(stats);
+        result_counts.push(total_results as i64);
+    }
+
+    let _ = client.delete_namespace(&ns).await;
+
+    Ok(serde_json::json!({
+        "levels": level_results,
+        "result_count_diff": result_counts[0] - result_counts[1],
+    }))
+}
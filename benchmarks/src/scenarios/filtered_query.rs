@@ -86,6 +86,8 @@ pub async fn run(args: &Args, client: &dyn BenchClient) -> Result<serde_json::Va
                 filter: Some(filter.clone()),
                 nprobe: args.nprobe,
                 rank_by: None,
+                consistency: None,
+                include_attributes: None,
             };
 
             let t = Instant::now();
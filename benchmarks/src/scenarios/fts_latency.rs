@@ -0,0 +1,176 @@
+//! Full-text search latency benchmark.
+//!
+//! Measures sequential BM25 query latency across query shapes (single term,
+//! multi-term, long) against an FTS-configured namespace. Unlike `bm25`, this
+//! scenario has no sustained throughput phase, so it finishes quickly.
+
+use std::time::Instant;
+
+use hdrhistogram::Histogram;
+
+use crate::client::{BenchClient, QueryRequest};
+use crate::datasets;
+use crate::results;
+use crate::Args;
+
+/// Queries per shape.
+const ITERATIONS: usize = 100;
+
+/// Query shapes, each a list of query strings cycled through.
+const SHAPES: &[(&str, &[&str])] = &[
+    ("single_term", &["vector", "database", "cache", "neural"]),
+    (
+        "multi_term",
+        &[
+            "vector search engine",
+            "machine learning neural network",
+            "distributed database system",
+        ],
+    ),
+    (
+        "long",
+        &[
+            "fast scalable distributed vector search engine with efficient memory cache",
+            "real time streaming batch processing on cloud native parallel system",
+        ],
+    ),
+];
+
+pub async fn run(args: &Args, client: &dyn BenchClient) -> Result<serde_json::Value, anyhow::Error> {
+    let ns = format!("bench-fts-latency-{}", rand::random::<u32>());
+
+    let fts_config = serde_json::json!({
+        "full_text_search": {
+            "content": {
+                "language": "english",
+                "stemming": true,
+                "remove_stopwords": true,
+            }
+        }
+    });
+
+    client
+        .create_namespace(&ns, args.dimensions, Some(fts_config))
+        .await
+        .map_err(|e| anyhow::anyhow!(e))?;
+
+    let n_docs = args.vectors.min(50_000);
+    let documents = datasets::random_documents(n_docs, args.dimensions);
+    eprintln!("  Ingesting {n_docs} documents...");
+    for batch in documents.chunks(args.batch_size) {
+        client
+            .upsert(&ns, batch)
+            .await
+            .map_err(|e| anyhow::anyhow!(e))?;
+    }
+
+    let mut overall = Histogram::<u64>::new(3).unwrap();
+    let mut shape_results = Vec::new();
+    let mut errors = 0u64;
+
+    for (shape, queries) in SHAPES {
+        eprintln!("  Querying {shape} x{ITERATIONS}...");
+        let mut hist = Histogram::<u64>::new(3).unwrap();
+
+        for i in 0..ITERATIONS {
+            let query_text = queries[i % queries.len()];
+            let req = QueryRequest {
+                vector: None,
+                top_k: args.top_k,
+                filter: None,
+                nprobe: None,
+                rank_by: Some(serde_json::json!(["content", "BM25", query_text])),
+                consistency: None,
+                include_attributes: None,
+            };
+
+            let t = Instant::now();
+            match client.query(&ns, &req).await {
+                Ok(_) => {
+                    let us = t.elapsed().as_micros() as u64;
+                    hist.record(us).ok();
+                    overall.record(us).ok();
+                }
+                Err(_) => errors += 1,
+            }
+        }
+
+        eprintln!(
+            "    p50={:.1}ms p99={:.1}ms",
+            hist.value_at_quantile(0.50) as f64 / 1000.0,
+            hist.value_at_quantile(0.99) as f64 / 1000.0,
+        );
+        let mut stats = results::latency_stats(&hist);
+        stats["shape"] = serde_json::json!(shape);
+        shape_results.push(stats);
+    }
+
+    let _ = client.delete_namespace(&ns).await;
+
+    Ok(serde_json::json!({
+        "documents": n_docs,
+        "shapes": shape_results,
+        "overall": results::latency_stats(&overall),
+        "errors": errors,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{QueryResponse, Vector};
+    use clap::Parser;
+
+    struct NoopClient;
+
+    #[async_trait::async_trait]
+    impl BenchClient for NoopClient {
+        async fn create_namespace(
+            &self,
+            _name: &str,
+            _dimensions: usize,
+            _extra: Option<serde_json::Value>,
+        ) -> Result<(), String> {
+            Ok(())
+        }
+
+        async fn upsert(&self, _namespace: &str, _vectors: &[Vector]) -> Result<(), String> {
+            Ok(())
+        }
+
+        async fn query(
+            &self,
+            _namespace: &str,
+            request: &QueryRequest,
+        ) -> Result<QueryResponse, String> {
+            assert!(request.rank_by.is_some());
+            Ok(QueryResponse { results: vec![] })
+        }
+
+        async fn delete_namespace(&self, _name: &str) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn name(&self) -> &str {
+            "noop"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fts_latency_smoke() {
+        let args = Args::parse_from([
+            "zeppelin-bench",
+            "--scenario",
+            "fts_latency",
+            "--vectors",
+            "20",
+            "--dimensions",
+            "4",
+        ]);
+        let out = run(&args, &NoopClient).await.unwrap();
+
+        assert_eq!(out["errors"], 0);
+        assert_eq!(out["shapes"].as_array().unwrap().len(), SHAPES.len());
+        assert_eq!(out["overall"]["count"], (ITERATIONS * SHAPES.len()) as u64);
+    }
+}
@@ -1,6 +1,12 @@
 //! Mixed workload benchmark.
 //!
 //! Simulates 80% reads / 20% writes and measures both.
+//!
+//! Reads use the default (strong) consistency, so their latency includes
+//! scanning the uncompacted WAL. That scan keeps only the best `top_k`
+//! vectors in a bounded heap rather than sorting every surviving one, so
+//! read latency should grow with the WAL backlog mostly through fragment
+//! fetch and scoring, not through ranking.
 
 use std::time::Instant;
 
@@ -53,6 +59,8 @@ pub async fn run(args: &Args, client: &dyn BenchClient) -> Result<serde_json::Va
                 filter: None,
                 nprobe: args.nprobe,
                 rank_by: None,
+                consistency: None,
+                include_attributes: None,
             };
             let t = Instant::now();
             if client.query(&ns, &req).await.is_ok() {
@@ -1,6 +1,8 @@
 pub mod bm25;
 pub mod compaction;
+pub mod consistency;
 pub mod filtered_query;
+pub mod fts_latency;
 pub mod index_comparison;
 pub mod ingest;
 pub mod mixed_workload;
@@ -58,6 +58,8 @@ pub async fn run(args: &Args, client: &dyn BenchClient) -> Result<serde_json::Va
                 filter: None,
                 nprobe: args.nprobe,
                 rank_by: None,
+                consistency: None,
+                include_attributes: None,
             };
             let t = Instant::now();
             if client.query(&ns, &req).await.is_ok() {
@@ -103,6 +103,12 @@ impl BenchClient for TurbopufferClient {
         if let Some(ref rank_by) = request.rank_by {
             body["rank_by"] = rank_by.clone();
         }
+        if let Some(ref level) = request.consistency {
+            body["consistency"] = serde_json::json!({ "level": level });
+        }
+        if let Some(ref attrs) = request.include_attributes {
+            body["include_attributes"] = serde_json::json!(attrs);
+        }
 
         let resp = self
             .http
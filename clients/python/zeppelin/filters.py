@@ -28,6 +28,14 @@ class Filter:
         """Not equal: ``{"op": "not_eq", "field": ..., "value": ...}``"""
         return {"op": "not_eq", "field": field, "value": value}
 
+    @staticmethod
+    def approx_eq(field: str, value: float, *, tolerance: float | None = None) -> dict:
+        """Numeric equality within ``tolerance`` (server default when omitted)."""
+        d: dict[str, Any] = {"op": "approx_eq", "field": field, "value": value}
+        if tolerance is not None:
+            d["tolerance"] = tolerance
+        return d
+
     @staticmethod
     def range(
         field: str,
@@ -11,6 +11,23 @@ use tracing::{debug, instrument};
 
 use crate::config::CacheConfig;
 use crate::error::{Result, ZeppelinError};
+use crate::storage::ZeppelinStore;
+
+/// Whether `key` exists, answered from the cache index when possible and
+/// otherwise with a `HEAD` against the store. Never reads object contents,
+/// and a store hit is not cached since there is no data to cache.
+pub async fn exists_with_cache(
+    cache: Option<&DiskCache>,
+    store: &ZeppelinStore,
+    key: &str,
+) -> Result<bool> {
+    if let Some(c) = cache {
+        if c.contains(key).await {
+            return Ok(true);
+        }
+    }
+    store.exists(key).await
+}
 
 /// Metadata for a cached entry.
 struct CacheEntry {
@@ -145,15 +162,13 @@ impl DiskCache {
                 Some(Bytes::from(data))
             }
             Err(_) => {
-                // File disappeared — remove from index
+                // File disappeared — remove from index. The miss is counted
+                // by `get_or_fetch`, like a key that was never cached.
                 let mut entries = self.entries.write().await;
                 if let Some(entry) = entries.remove(key) {
                     self.total_size.fetch_sub(entry.size, Ordering::Relaxed);
                     crate::metrics::CACHE_ENTRIES.dec();
                 }
-                crate::metrics::CACHE_HITS_TOTAL
-                    .with_label_values(&["miss"])
-                    .inc();
                 debug!("cache miss (file missing)");
                 None
             }
@@ -212,7 +227,15 @@ impl DiskCache {
         Ok(())
     }
 
+    /// Whether `key` is cached, without reading the file or touching its
+    /// LRU position.
+    pub async fn contains(&self, key: &str) -> bool {
+        self.entries.read().await.contains_key(key)
+    }
+
     /// Get a value from cache, or fetch it using the provided function if not cached.
+    ///
+    /// On a miss `fetch` runs exactly once and the miss is counted once.
     pub async fn get_or_fetch<F, Fut>(&self, key: &str, fetch: F) -> Result<Bytes>
     where
         F: FnOnce() -> Fut,
@@ -243,6 +266,20 @@ impl DiskCache {
         pinned.remove(key);
     }
 
+    /// Whether `key` is pinned.
+    pub async fn is_pinned(&self, key: &str) -> bool {
+        self.pinned.read().await.contains(key)
+    }
+
+    /// Unpin every key that starts with `prefix`, leaving the data cached.
+    /// Returns the number of keys unpinned.
+    pub async fn unpin_prefix(&self, prefix: &str) -> usize {
+        let mut pinned = self.pinned.write().await;
+        let before = pinned.len();
+        pinned.retain(|k| !k.starts_with(prefix));
+        before - pinned.len()
+    }
+
     /// Invalidate (remove) a single key from the cache.
     #[instrument(skip(self), fields(key = key))]
     pub async fn invalidate(&self, key: &str) -> Result<()> {
@@ -263,8 +300,9 @@ impl DiskCache {
     }
 
     /// Invalidate all keys that start with the given prefix.
+    /// Returns the number of entries removed.
     #[instrument(skip(self), fields(prefix = prefix))]
-    pub async fn invalidate_prefix(&self, prefix: &str) -> Result<()> {
+    pub async fn invalidate_prefix(&self, prefix: &str) -> Result<usize> {
         let mut entries = self.entries.write().await;
         let matching_keys: Vec<String> = entries
             .keys()
@@ -287,7 +325,7 @@ impl DiskCache {
         }
 
         debug!(removed = matching_keys.len(), "invalidated prefix");
-        Ok(())
+        Ok(matching_keys.len())
     }
 
     /// Get the total size of all cached data in bytes.
@@ -38,40 +38,6 @@ pub async fn compaction_loop(
 
         debug!(namespace_count = namespaces.len(), "compaction loop tick");
 
-        for ns in &namespaces {
-            match compactor.should_compact(&ns.name).await {
-                Ok(true) => {
-                    info!(namespace = %ns.name, "triggering compaction");
-                    match compactor
-                        .compact_with_fts(&ns.name, None, &ns.full_text_search)
-                        .await
-                    {
-                        Ok(result) => {
-                            crate::metrics::COMPACTIONS_TOTAL
-                                .with_label_values(&[&ns.name, "success"])
-                                .inc();
-                            info!(
-                                namespace = %ns.name,
-                                vectors_compacted = result.vectors_compacted,
-                                fragments_removed = result.fragments_removed,
-                                "compaction completed"
-                            );
-                        }
-                        Err(e) => {
-                            crate::metrics::COMPACTIONS_TOTAL
-                                .with_label_values(&[&ns.name, "failure"])
-                                .inc();
-                            warn!(namespace = %ns.name, error = %e, "compaction failed");
-                        }
-                    }
-                }
-                Ok(false) => {
-                    debug!(namespace = %ns.name, "compaction not needed");
-                }
-                Err(e) => {
-                    warn!(namespace = %ns.name, error = %e, "failed to check compaction status");
-                }
-            }
-        }
+        compactor.compact_all(&namespaces).await;
     }
 }
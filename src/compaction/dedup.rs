@@ -0,0 +1,115 @@
+//! Near-duplicate collapse for compaction.
+//!
+//! Vectors closer than `epsilon` (Euclidean) are collapsed to a single
+//! entry regardless of ID, keeping the most recently written one.
+
+use tracing::debug;
+
+use crate::index::distance::euclidean_distance;
+use crate::types::VectorEntry;
+
+/// Most neighbours by projection compared on each side of a vector.
+pub const MAX_WINDOW: usize = 256;
+
+/// Drop vectors within `epsilon` of a more recent vector.
+///
+/// `vectors` must be ordered oldest to newest. Vectors are visited newest
+/// first and kept unless a kept vector lies within `epsilon`. Candidates are
+/// found by sorting on a projection onto the all-ones direction: two vectors
+/// within `epsilon` of each other have projections within `epsilon` too, so
+/// only that window is compared exactly, nearest projections first and at
+/// most [`MAX_WINDOW`] on each side. Vectors crowded into one projection
+/// band therefore cost `O(n · MAX_WINDOW)` rather than `O(n²)`, at the price
+/// of missing duplicates further than `MAX_WINDOW` places away in that band.
+/// Output order is unspecified.
+pub fn collapse_near_duplicates(vectors: Vec<VectorEntry>, epsilon: f32) -> Vec<VectorEntry> {
+    let n = vectors.len();
+    if n < 2 || epsilon.is_nan() || epsilon <= 0.0 {
+        return vectors;
+    }
+
+    let projections: Vec<f32> = vectors
+        .iter()
+        .map(|v| v.values.iter().sum::<f32>() / (v.values.len().max(1) as f32).sqrt())
+        .collect();
+    let mut by_projection: Vec<usize> = (0..n).collect();
+    by_projection.sort_by(|&a, &b| projections[a].total_cmp(&projections[b]));
+    let mut position = vec![0; n];
+    for (pos, &j) in by_projection.iter().enumerate() {
+        position[j] = pos;
+    }
+
+    // `euclidean_distance` is squared.
+    let epsilon_sq = epsilon * epsilon;
+    let mut kept = vec![false; n];
+    for i in (0..n).rev() {
+        let p = projections[i];
+        let pos = position[i];
+        let below = by_projection[..pos]
+            .iter()
+            .rev()
+            .take_while(|&&j| projections[j] >= p - epsilon)
+            .take(MAX_WINDOW);
+        let above = by_projection[pos + 1..]
+            .iter()
+            .take_while(|&&j| projections[j] <= p + epsilon)
+            .take(MAX_WINDOW);
+        let is_duplicate = below.chain(above).any(|&j| {
+            kept[j]
+                && vectors[j].values.len() == vectors[i].values.len()
+                && euclidean_distance(&vectors[i].values, &vectors[j].values) < epsilon_sq
+        });
+        kept[i] = !is_duplicate;
+    }
+
+    let survivors: Vec<VectorEntry> = vectors
+        .into_iter()
+        .zip(kept)
+        .filter_map(|(v, keep)| keep.then_some(v))
+        .collect();
+    debug!(
+        before = n,
+        after = survivors.len(),
+        epsilon,
+        "collapsed near-duplicate vectors"
+    );
+    survivors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, values: Vec<f32>) -> VectorEntry {
+        VectorEntry {
+            id: id.to_string(),
+            values,
+            attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
+        }
+    }
+
+    #[test]
+    fn test_keeps_latest_of_near_duplicates() {
+        let vectors = vec![
+            entry("old", vec![1.0, 2.0, 3.0]),
+            entry("far", vec![-5.0, 0.0, 5.0]),
+            entry("new", vec![1.0, 2.0, 3.0001]),
+        ];
+        let mut ids: Vec<String> = collapse_near_duplicates(vectors, 0.01)
+            .into_iter()
+            .map(|v| v.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["far", "new"]);
+    }
+
+    #[test]
+    fn test_disabled_epsilon_is_noop() {
+        let vectors = vec![entry("a", vec![1.0]), entry("b", vec![1.0])];
+        assert_eq!(collapse_near_duplicates(vectors, 0.0).len(), 2);
+    }
+}
@@ -1,20 +1,33 @@
 pub mod background;
+pub mod dedup;
+pub mod verify;
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
+use serde::Serialize;
 use tracing::{debug, info, instrument, warn};
 use ulid::Ulid;
 
-use crate::config::{CompactionConfig, IndexingConfig};
+use crate::config::{ClusterBy, CompactionConfig, CompactionOverrides, IndexingConfig};
 use crate::error::{Result, ZeppelinError};
 use crate::fts::inverted_index::{fts_index_key, InvertedIndex};
 use crate::fts::types::FtsFieldConfig;
+use crate::index::distance::compute_distance;
 use crate::index::hierarchical::build::build_hierarchical;
+use crate::index::id_map::{segment_id_map_key, SegmentIdMap};
 use crate::index::ivf_flat::build::{
-    attrs_key, build_ivf_flat, cluster_key, deserialize_attrs, deserialize_cluster,
+    attrs_key, build_ivf_flat, build_ivf_flat_by_attribute, build_text_only, cluster_key,
+    deserialize_attrs, deserialize_cluster, docs_key,
 };
+use crate::index::ivf_flat::kmeans::training_metric;
+use crate::index::late_interaction::{token_matrix_key, TokenMatrix};
+use crate::index::named::{named_vectors_key, NamedVectorIndex};
+use crate::index::sparse::{sparse_index_key, SparseIndex};
+use crate::index::vamana::build::{build_vamana, load_vamana_vectors};
+use crate::index::versions::{versions_key, VersionIndex};
+use crate::namespace::manager::NamespaceMetadata;
 use crate::storage::ZeppelinStore;
-use crate::types::VectorEntry;
+use crate::types::{ConsistencyLevel, DistanceMetric, VectorEntry};
 use crate::wal::fragment::WalFragment;
 use crate::wal::manifest::{Manifest, ManifestVersion, SegmentRef};
 use crate::wal::WalReader;
@@ -33,6 +46,20 @@ pub struct CompactionResult {
     pub fragments_removed: usize,
     /// ID of the old segment that was replaced, if any.
     pub old_segment_removed: Option<String>,
+    /// Number of segments combined by a tiered merge after this run.
+    pub segments_merged: usize,
+}
+
+/// Outcome of compacting one namespace during [`Compactor::compact_all`].
+#[derive(Debug, Serialize)]
+pub struct NamespaceCompaction {
+    pub namespace: String,
+    pub segment_id: Option<String>,
+    pub vectors_compacted: usize,
+    pub fragments_removed: usize,
+    /// Why this namespace could not be checked or compacted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 /// Compacts WAL fragments into IVF-Flat segments on S3.
@@ -63,18 +90,119 @@ impl Compactor {
     }
 
     /// Check whether compaction should be triggered for a namespace.
-    #[instrument(skip(self), fields(namespace = namespace))]
     pub async fn should_compact(&self, namespace: &str) -> Result<bool> {
+        self.should_compact_with(namespace, &CompactionOverrides::default())
+            .await
+    }
+
+    /// Like [`should_compact`](Self::should_compact), with the namespace's
+    /// compaction overrides applied over the global config.
+    ///
+    /// Triggers when the uncompacted fragment count reaches
+    /// `max_wal_fragments_before_compact`, or when `max_fragment_age_secs`
+    /// is set and the oldest uncompacted fragment is at least that old.
+    #[instrument(skip(self, overrides), fields(namespace = namespace))]
+    pub async fn should_compact_with(
+        &self,
+        namespace: &str,
+        overrides: &CompactionOverrides,
+    ) -> Result<bool> {
+        let config = self.config.with_overrides(overrides);
         let manifest = Manifest::read(&self.store, namespace)
             .await?
             .unwrap_or_default();
-        let count = manifest.uncompacted_fragments().len();
+        let fragments = manifest.uncompacted_fragments();
+        // Fragment IDs are ULIDs, so the smallest one is the oldest write.
+        let oldest_age_secs = fragments
+            .iter()
+            .map(|f| f.id.timestamp_ms())
+            .min()
+            .map(|ms| {
+                let now_ms = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64;
+                now_ms.saturating_sub(ms) / 1000
+            });
         debug!(
-            fragment_count = count,
-            threshold = self.config.max_wal_fragments_before_compact,
+            fragment_count = fragments.len(),
+            threshold = config.max_wal_fragments_before_compact,
+            oldest_age_secs,
+            max_age_secs = config.max_fragment_age_secs,
             "checking compaction trigger"
         );
-        Ok(count >= self.config.max_wal_fragments_before_compact)
+        let too_old = matches!(
+            (oldest_age_secs, config.max_fragment_age_secs),
+            (Some(age), Some(max)) if age >= max
+        );
+        Ok(fragments.len() >= config.max_wal_fragments_before_compact || too_old)
+    }
+
+    /// Compact every namespace in `namespaces` that is over its compaction
+    /// thresholds (see [`should_compact_with`](Self::should_compact_with)),
+    /// one at a time. A failure is recorded in that namespace's
+    /// entry and does not stop the rest. Namespaces under the threshold are
+    /// left out of the returned list.
+    pub async fn compact_all(&self, namespaces: &[NamespaceMetadata]) -> Vec<NamespaceCompaction> {
+        let mut results = Vec::new();
+        for (i, ns) in namespaces.iter().enumerate() {
+            let failed = |e: ZeppelinError| NamespaceCompaction {
+                namespace: ns.name.clone(),
+                segment_id: None,
+                vectors_compacted: 0,
+                fragments_removed: 0,
+                error: Some(e.to_string()),
+            };
+            match self.should_compact_with(&ns.name, &ns.compaction).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    debug!(namespace = %ns.name, "compaction not needed");
+                    continue;
+                }
+                Err(e) => {
+                    warn!(namespace = %ns.name, error = %e, "failed to check compaction status");
+                    results.push(failed(e));
+                    continue;
+                }
+            }
+
+            info!(
+                namespace = %ns.name,
+                progress = %format!("{}/{}", i + 1, namespaces.len()),
+                "triggering compaction"
+            );
+            match self
+                .compact_with_fts(&ns.name, None, &ns.full_text_search)
+                .await
+            {
+                Ok(result) => {
+                    crate::metrics::COMPACTIONS_TOTAL
+                        .with_label_values(&[&ns.name, "success"])
+                        .inc();
+                    info!(
+                        namespace = %ns.name,
+                        vectors_compacted = result.vectors_compacted,
+                        fragments_removed = result.fragments_removed,
+                        "compaction completed"
+                    );
+                    results.push(NamespaceCompaction {
+                        namespace: ns.name.clone(),
+                        segment_id: result.segment_id,
+                        vectors_compacted: result.vectors_compacted,
+                        fragments_removed: result.fragments_removed,
+                        error: None,
+                    });
+                }
+                Err(e) => {
+                    crate::metrics::COMPACTIONS_TOTAL
+                        .with_label_values(&[&ns.name, "failure"])
+                        .inc();
+                    warn!(namespace = %ns.name, error = %e, "compaction failed");
+                    results.push(failed(e));
+                }
+            }
+        }
+        results
     }
 
     /// Compact all uncompacted WAL fragments into a new IVF-Flat segment.
@@ -145,13 +273,18 @@ impl Compactor {
             .unwrap_or_default();
 
         // 2. If no uncompacted fragments → no-op
+        // (tiered compaction may still have segments to merge).
         if manifest.uncompacted_fragments().is_empty() {
             debug!("no uncompacted fragments, skipping");
+            let segments_merged = self
+                .merge_tier(namespace, fencing_token, fts_configs)
+                .await?;
             return Ok(CompactionResult {
                 segment_id: None,
                 vectors_compacted: 0,
                 fragments_removed: 0,
                 old_segment_removed: None,
+                segments_merged,
             });
         }
 
@@ -162,14 +295,21 @@ impl Compactor {
         info!(fragment_count = fragments_removed, "starting compaction");
 
         // 3. Read fragments using snapshot refs (not re-reading manifest)
-        let fragments = self
+        let mut fragments = self
             .wal_reader
             .read_fragments_from_refs(namespace, &fragment_refs)
             .await?;
+        crate::query::resolve_patches(&self.store, namespace, &manifest, &mut fragments).await?;
+        for fragment in &mut fragments {
+            fragment.stamp_versions();
+        }
 
         // 4. Merge vectors: process in manifest order (sequence number), latest wins
         let mut latest_vectors: HashMap<String, VectorEntry> = HashMap::new();
         let mut deleted_ids: HashSet<String> = HashSet::new();
+        // Write order of each WAL vector; segment vectors count as oldest (0).
+        let mut write_order: HashMap<String, u64> = HashMap::new();
+        let mut sequence = 0u64;
 
         for fragment in &fragments {
             for del_id in &fragment.deletes {
@@ -179,23 +319,68 @@ impl Compactor {
             for vec in &fragment.vectors {
                 deleted_ids.remove(&vec.id);
                 latest_vectors.insert(vec.id.clone(), vec.clone());
+                sequence += 1;
+                write_order.insert(vec.id.clone(), sequence);
             }
         }
 
-        // 5. If existing active_segment: load vectors from it, merge
-        let old_segment_id = manifest.active_segment.clone();
-        if let Some(ref seg_id) = old_segment_id {
-            let existing_vecs = load_segment_vectors(&self.store, namespace, seg_id).await?;
-            for vec in existing_vecs {
-                // WAL overrides: only insert if not already in latest_vectors and not deleted
-                if !latest_vectors.contains_key(&vec.id) && !deleted_ids.contains(&vec.id) {
-                    latest_vectors.insert(vec.id.clone(), vec);
+        // 5. Tiered: the WAL batch becomes a segment of its own, and the IDs
+        // it rewrites or deletes are tombstoned in the live segments holding
+        // them. Otherwise every live segment is merged into the new one.
+        let merge_factor = self.config.merge_factor.map(|f| f.max(2));
+        let live_segments: Vec<SegmentRef> =
+            manifest.live_segments().into_iter().cloned().collect();
+        let replaced: Vec<String> = if merge_factor.is_some() {
+            Vec::new()
+        } else {
+            live_segments.iter().map(|s| s.id.clone()).collect()
+        };
+        let mut tombstones: HashMap<String, BTreeSet<String>> = HashMap::new();
+        if merge_factor.is_some() {
+            let written: HashSet<&String> = latest_vectors.keys().chain(&deleted_ids).collect();
+            for seg in &live_segments {
+                let hits: BTreeSet<String> = load_segment_ids(&self.store, namespace, seg)
+                    .await?
+                    .into_iter()
+                    .filter(|id| written.contains(id) && !seg.tombstones.contains(id))
+                    .collect();
+                if !hits.is_empty() {
+                    tombstones.insert(seg.id.clone(), hits);
+                }
+            }
+        } else {
+            for seg in &live_segments {
+                for vec in load_live_vectors(&self.store, namespace, seg).await? {
+                    // WAL overrides: only insert if not already in latest_vectors and not deleted
+                    if !latest_vectors.contains_key(&vec.id) && !deleted_ids.contains(&vec.id) {
+                        latest_vectors.insert(vec.id.clone(), vec);
+                    }
                 }
             }
         }
+        let old_segment_id = manifest
+            .active_segment
+            .clone()
+            .filter(|id| replaced.contains(id));
+
+        let meta = self.namespace_meta(namespace).await?;
+        let text_only = meta.as_ref().is_some_and(|m| m.is_text_only());
 
-        // 6. Collect surviving vectors
-        let vectors: Vec<VectorEntry> = latest_vectors.into_values().collect();
+        // 6. Collect surviving vectors, optionally collapsing near-duplicates.
+        // Text-only documents have no values, so every pair would collapse.
+        let mut vectors: Vec<VectorEntry> = latest_vectors.into_values().collect();
+        if let Some(epsilon) = self.config.dedup_epsilon.filter(|_| !text_only) {
+            vectors.sort_by(|a, b| {
+                let order = |v: &VectorEntry| write_order.get(&v.id).copied().unwrap_or(0);
+                order(a).cmp(&order(b)).then_with(|| a.id.cmp(&b.id))
+            });
+            let before = vectors.len();
+            vectors = dedup::collapse_near_duplicates(vectors, epsilon);
+            info!(
+                collapsed = before - vectors.len(),
+                epsilon, "near-duplicate dedup"
+            );
+        }
         let vectors_compacted = vectors.len();
 
         // Collect keys for deferred deletion
@@ -203,7 +388,7 @@ impl Compactor {
         for fref in &fragment_refs {
             deferred_deletes.push(WalFragment::s3_key(namespace, &fref.id));
         }
-        if let Some(ref seg_id) = old_segment_id {
+        for seg_id in &replaced {
             let prefix = format!("{namespace}/segments/{seg_id}/");
             if let Ok(keys) = self.store.list_prefix(&prefix).await {
                 deferred_deletes.extend(keys);
@@ -232,6 +417,8 @@ impl Compactor {
                     fresh_manifest.fencing_token = token;
                 }
 
+                apply_tombstones(&mut fresh_manifest, &tombstones);
+                fresh_manifest.remove_segments(&replaced);
                 fresh_manifest.remove_compacted_fragments(last_fragment_id);
                 fresh_manifest.pending_deletes = deferred_deletes.clone();
 
@@ -250,11 +437,15 @@ impl Compactor {
                             elapsed_ms = elapsed.as_millis(),
                             attempt, "compaction complete (all vectors deleted)"
                         );
+                        let segments_merged = self
+                            .merge_tier(namespace, fencing_token, fts_configs)
+                            .await?;
                         return Ok(CompactionResult {
                             segment_id: None,
                             vectors_compacted: 0,
                             fragments_removed,
                             old_segment_removed: old_segment_id,
+                            segments_merged,
                         });
                     }
                     Err(ZeppelinError::ManifestConflict { .. }) => {
@@ -276,33 +467,171 @@ impl Compactor {
         let segment_id = format!("seg_{}", Ulid::new());
 
         // 8. Build index (expensive, done once — NOT retried)
-        // Choose hierarchical or flat based on config.
+        let segment_ref = self
+            .build_segment(namespace, &segment_id, &vectors, meta.as_ref(), fts_configs)
+            .await?;
+
+        // 9. CAS loop: re-read manifest, apply changes, write conditionally
+        for attempt in 0..MAX_CAS_RETRIES {
+            let (mut fresh_manifest, version) =
+                match Manifest::read_versioned(&self.store, namespace).await? {
+                    Some(pair) => pair,
+                    None => (Manifest::default(), ManifestVersion(None)),
+                };
+
+            // Layer 1: Fencing check.
+            if let Some(token) = fencing_token {
+                if fresh_manifest.fencing_token > token {
+                    return Err(ZeppelinError::FencingTokenStale {
+                        namespace: namespace.to_string(),
+                        our_token: token,
+                        manifest_token: fresh_manifest.fencing_token,
+                    });
+                }
+                fresh_manifest.fencing_token = token;
+            }
+
+            apply_tombstones(&mut fresh_manifest, &tombstones);
+            if merge_factor.is_some() {
+                fresh_manifest.push_segment(segment_ref.clone());
+            } else {
+                fresh_manifest.replace_segments(&replaced, segment_ref.clone());
+            }
+            fresh_manifest.remove_compacted_fragments(last_fragment_id);
+            fresh_manifest.pending_deletes = deferred_deletes.clone();
+
+            // Layer 2: CAS.
+            match fresh_manifest
+                .write_conditional(&self.store, namespace, &version)
+                .await
+            {
+                Ok(()) => {
+                    let elapsed = start.elapsed();
+                    crate::metrics::COMPACTION_DURATION
+                        .with_label_values(&[namespace])
+                        .observe(elapsed.as_secs_f64());
+
+                    info!(
+                        segment_id = %segment_id,
+                        vectors_compacted,
+                        fragments_removed,
+                        elapsed_ms = elapsed.as_millis(),
+                        attempt,
+                        "compaction complete"
+                    );
+
+                    let segments_merged = self
+                        .merge_tier(namespace, fencing_token, fts_configs)
+                        .await?;
+                    return Ok(CompactionResult {
+                        segment_id: Some(segment_id),
+                        vectors_compacted,
+                        fragments_removed,
+                        old_segment_removed: old_segment_id,
+                        segments_merged,
+                    });
+                }
+                Err(ZeppelinError::ManifestConflict { .. }) => {
+                    warn!(attempt, "manifest CAS conflict in compactor, retrying");
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(ZeppelinError::ManifestConflict {
+            namespace: namespace.to_string(),
+        })
+    }
+
+    /// Build a segment's index, FTS indexes and ID map from `vectors`.
+    /// Returns the segment's manifest entry.
+    async fn build_segment(
+        &self,
+        namespace: &str,
+        segment_id: &str,
+        vectors: &[VectorEntry],
+        meta: Option<&NamespaceMetadata>,
+        fts_configs: &HashMap<String, FtsFieldConfig>,
+    ) -> Result<SegmentRef> {
+        let text_only = meta.is_some_and(|m| m.is_text_only());
+
+        // Resolve the namespace's index type into concrete build settings.
+        let indexing_config = self.indexing_config_for(meta);
+
+        // Text-only namespaces skip clustering entirely. Otherwise choose
+        // Vamana, hierarchical or flat based on config. `cluster_by` only
+        // applies to flat IVF segments.
         let build_start = std::time::Instant::now();
-        let (cluster_count, is_hierarchical, bitmap_fields) = if self.indexing_config.hierarchical {
-            let h_index = build_hierarchical(
-                &vectors,
-                &self.indexing_config,
+        let is_vamana = !text_only && indexing_config.vamana;
+        let (cluster_count, is_hierarchical, bitmap_fields) = if text_only {
+            let partitions = build_text_only(vectors, &self.store, namespace, segment_id).await?;
+            (partitions, false, Vec::new())
+        } else if is_vamana {
+            let index = build_vamana(
+                vectors,
+                &indexing_config,
                 &self.store,
                 namespace,
-                &segment_id,
+                segment_id,
             )
             .await?;
-            let bf = h_index.bitmap_fields.clone();
-            (h_index.num_leaf_clusters(), true, bf)
-        } else {
-            let index = build_ivf_flat(
-                &vectors,
-                &self.indexing_config,
+            (index.num_blocks(), false, Vec::new())
+        } else if indexing_config.hierarchical {
+            let h_index = build_hierarchical(
+                vectors,
+                &indexing_config,
                 &self.store,
                 namespace,
-                &segment_id,
+                segment_id,
             )
             .await?;
+            let bf = h_index.bitmap_fields.clone();
+            (h_index.num_leaf_clusters(), true, bf)
+        } else {
+            let index = match &self.config.cluster_by {
+                ClusterBy::Vector => {
+                    build_ivf_flat(
+                        vectors,
+                        &indexing_config,
+                        &self.store,
+                        namespace,
+                        segment_id,
+                    )
+                    .await?
+                }
+                ClusterBy::Attribute(field) => {
+                    build_ivf_flat_by_attribute(
+                        vectors,
+                        field,
+                        &indexing_config,
+                        &self.store,
+                        namespace,
+                        segment_id,
+                    )
+                    .await?
+                }
+            };
             let bf = index.bitmap_fields.clone();
             (index.num_clusters(), false, bf)
         };
         let build_elapsed = build_start.elapsed();
-        let index_type_label = if is_hierarchical {
+        let kmeans_metric = (!text_only
+            && !is_vamana
+            && !is_hierarchical
+            && self.config.cluster_by == ClusterBy::Vector)
+            .then(|| {
+                training_metric(
+                    indexing_config
+                        .kmeans_metric
+                        .unwrap_or(DistanceMetric::Euclidean),
+                )
+            });
+        let index_type_label = if text_only {
+            "text_only"
+        } else if is_vamana {
+            "vamana"
+        } else if is_hierarchical {
             "hierarchical"
         } else {
             "ivf_flat"
@@ -316,70 +645,238 @@ impl Compactor {
             "index build phase complete"
         );
 
-        // 8b. Build FTS inverted indexes (if FTS fields configured)
-        let fts_fields: Vec<String> = if !fts_configs.is_empty() && self.indexing_config.fts_index {
-            let fts_start = std::time::Instant::now();
-            let mut fts_field_names = Vec::new();
+        // Build FTS inverted indexes (if FTS fields configured). A
+        // text-only segment is only searchable through them, so it always
+        // gets them.
+        let fts_index = indexing_config.fts_index || text_only;
+        let fts_fields: Vec<String> = if !fts_configs.is_empty() && fts_index {
+            self.build_fts_indexes(namespace, segment_id, cluster_count, fts_configs)
+                .await?
+        } else {
+            Vec::new()
+        };
+
+        let id_map = self
+            .write_id_map(namespace, segment_id, cluster_count, text_only || is_vamana)
+            .await?;
+        let sparse = self
+            .write_sparse_index(namespace, segment_id, vectors)
+            .await?;
+        let named_vectors = self
+            .write_named_vectors(namespace, segment_id, vectors)
+            .await?;
+        let token_vectors = self
+            .write_token_matrices(namespace, segment_id, cluster_count, &id_map, vectors)
+            .await?;
+        let versions = self.write_versions(namespace, segment_id, vectors).await?;
+
+        Ok(SegmentRef {
+            id: segment_id.to_string(),
+            vector_count: vectors.len(),
+            cluster_count,
+            quantization: if text_only || is_vamana {
+                Default::default()
+            } else {
+                indexing_config.quantization
+            },
+            hierarchical: is_hierarchical,
+            vamana: is_vamana,
+            bitmap_fields,
+            fts_fields,
+            kmeans_metric,
+            text_only,
+            sparse,
+            named_vectors,
+            token_vectors,
+            versions,
+            tombstones: BTreeSet::new(),
+        })
+    }
 
-            // Phase 1: Parallel reads of cluster attributes.
-            let attr_keys: Vec<String> = (0..cluster_count)
-                .map(|i| attrs_key(namespace, &segment_id, i))
-                .collect();
-            let read_futs: Vec<_> = attr_keys.iter().map(|k| self.store.get(k)).collect();
-            let read_results = futures::future::join_all(read_futs).await;
-
-            // Phase 2: CPU — build inverted indexes.
-            let mut write_payloads = Vec::new();
-            for (cluster_idx, result) in read_results.into_iter().enumerate() {
-                let cluster_attrs = match result {
-                    Ok(data) => deserialize_attrs(&data)?,
-                    Err(_) => continue,
-                };
+    /// Write a segment's [`SegmentIdMap`]. The builders don't report which
+    /// partition each vector landed in, so the partitions' ID lists are read
+    /// back (in parallel). `docs` selects the ID-only partition blobs of
+    /// Vamana and text-only segments over IVF cluster blobs.
+    async fn write_id_map(
+        &self,
+        namespace: &str,
+        segment_id: &str,
+        partition_count: usize,
+        docs: bool,
+    ) -> Result<SegmentIdMap> {
+        let ids_key = if docs { docs_key } else { cluster_key };
+        let reads = futures::future::join_all((0..partition_count).map(|i| {
+            let key = ids_key(namespace, segment_id, i);
+            async move { self.store.get(&key).await }
+        }))
+        .await;
+        let partitions = reads
+            .into_iter()
+            .map(|data| Ok(deserialize_cluster(&data?)?.ids))
+            .collect::<Result<Vec<_>>>()?;
+        let id_map = SegmentIdMap::build(&partitions);
+        self.store
+            .put(
+                &segment_id_map_key(namespace, segment_id),
+                id_map.to_bytes()?,
+            )
+            .await?;
+        debug!(
+            segment_id,
+            ids = id_map.ids().len(),
+            "segment ID map written"
+        );
+        Ok(id_map)
+    }
 
-                let attr_refs: Vec<Option<&HashMap<String, crate::types::AttributeValue>>> =
-                    cluster_attrs.iter().map(|a| a.as_ref()).collect();
+    /// Write a segment's [`SparseIndex`] if any of its vectors has a sparse
+    /// representation. Returns whether one was written.
+    async fn write_sparse_index(
+        &self,
+        namespace: &str,
+        segment_id: &str,
+        vectors: &[VectorEntry],
+    ) -> Result<bool> {
+        let index = SparseIndex::build(vectors);
+        if index.is_empty() {
+            return Ok(false);
+        }
+        self.store
+            .put(&sparse_index_key(namespace, segment_id), index.to_bytes()?)
+            .await?;
+        debug!(segment_id, "sparse index written");
+        Ok(true)
+    }
 
-                let inv_index = InvertedIndex::build(&attr_refs, fts_configs);
+    /// Write a segment's [`NamedVectorIndex`] if any of its vectors has a
+    /// named vector. Returns whether one was written.
+    async fn write_named_vectors(
+        &self,
+        namespace: &str,
+        segment_id: &str,
+        vectors: &[VectorEntry],
+    ) -> Result<bool> {
+        let index = NamedVectorIndex::build(vectors);
+        if index.is_empty() {
+            return Ok(false);
+        }
+        self.store
+            .put(&named_vectors_key(namespace, segment_id), index.to_bytes()?)
+            .await?;
+        debug!(segment_id, "named vectors written");
+        Ok(true)
+    }
 
-                // Track which fields were indexed
-                for field_name in inv_index.fields.keys() {
-                    if !fts_field_names.contains(field_name) {
-                        fts_field_names.push(field_name.clone());
-                    }
-                }
+    /// Write a segment's [`VersionIndex`] if any of its vectors has a
+    /// version. Returns whether one was written.
+    async fn write_versions(
+        &self,
+        namespace: &str,
+        segment_id: &str,
+        vectors: &[VectorEntry],
+    ) -> Result<bool> {
+        let index = VersionIndex::build(vectors);
+        if index.is_empty() {
+            return Ok(false);
+        }
+        self.store
+            .put(&versions_key(namespace, segment_id), index.to_bytes()?)
+            .await?;
+        debug!(segment_id, "versions written");
+        Ok(true)
+    }
 
-                let fts_data = inv_index.to_bytes()?;
-                let fts_key = fts_index_key(namespace, &segment_id, cluster_idx);
-                write_payloads.push((fts_key, fts_data));
-            }
+    /// Pack the token vectors of a segment's vectors into one
+    /// [`TokenMatrix`] per partition, if any vector has them. Every
+    /// partition gets a matrix, possibly empty. Returns whether they were
+    /// written.
+    async fn write_token_matrices(
+        &self,
+        namespace: &str,
+        segment_id: &str,
+        partition_count: usize,
+        id_map: &SegmentIdMap,
+        vectors: &[VectorEntry],
+    ) -> Result<bool> {
+        if vectors.iter().all(|v| v.token_vectors.is_none()) {
+            return Ok(false);
+        }
+        let partition_count = partition_count.max(1);
+        let mut partitions: Vec<Vec<&VectorEntry>> = vec![Vec::new(); partition_count];
+        for vector in vectors.iter().filter(|v| v.token_vectors.is_some()) {
+            let partition = id_map.partition_of(&vector.id).unwrap_or(0);
+            partitions[partition.min(partition_count - 1)].push(vector);
+        }
+        let writes = partitions
+            .iter()
+            .enumerate()
+            .map(|(i, members)| async move {
+                let matrix = TokenMatrix::build(members.iter().copied());
+                self.store
+                    .put(
+                        &token_matrix_key(namespace, segment_id, i),
+                        matrix.to_bytes()?,
+                    )
+                    .await
+            });
+        futures::future::try_join_all(writes).await?;
+        debug!(
+            segment_id,
+            partitions = partitions.len(),
+            "token matrices written"
+        );
+        Ok(true)
+    }
 
-            // Phase 3: Parallel writes of FTS indexes.
-            let write_futs: Vec<_> = write_payloads
-                .iter()
-                .map(|(key, data)| self.store.put(key, data.clone()))
-                .collect();
-            let write_results = futures::future::join_all(write_futs).await;
-            for result in write_results {
-                result?;
-            }
+    /// Tiered compaction: merge the live segments picked by
+    /// [`pick_tier_merge`] into one segment, dropping their tombstoned IDs.
+    /// Returns the number of segments merged; a no-op (0) when compaction
+    /// is not tiered or no tier is full.
+    async fn merge_tier(
+        &self,
+        namespace: &str,
+        fencing_token: Option<u64>,
+        fts_configs: &HashMap<String, FtsFieldConfig>,
+    ) -> Result<usize> {
+        let Some(merge_factor) = self.config.merge_factor.map(|f| f.max(2)) else {
+            return Ok(0);
+        };
+        let manifest = Manifest::read(&self.store, namespace)
+            .await?
+            .unwrap_or_default();
+        let merged = pick_tier_merge(&manifest.live_segments(), merge_factor);
+        if merged.is_empty() {
+            return Ok(0);
+        }
+        let sources: Vec<SegmentRef> = manifest
+            .live_segments()
+            .into_iter()
+            .filter(|s| merged.contains(&s.id))
+            .cloned()
+            .collect();
+        info!(segments = ?merged, "starting tiered segment merge");
 
-            let fts_elapsed = fts_start.elapsed();
-            crate::metrics::FTS_INDEX_BUILD_DURATION
-                .with_label_values(&[namespace])
-                .observe(fts_elapsed.as_secs_f64());
-            debug!(
-                fts_fields = ?fts_field_names,
-                fts_build_duration_ms = fts_elapsed.as_millis() as u64,
-                clusters = cluster_count,
-                "FTS inverted index build complete"
-            );
+        let mut vectors = Vec::new();
+        let mut deferred_deletes = Vec::new();
+        for seg in &sources {
+            vectors.extend(load_live_vectors(&self.store, namespace, seg).await?);
+            let prefix = format!("{namespace}/segments/{}/", seg.id);
+            if let Ok(keys) = self.store.list_prefix(&prefix).await {
+                deferred_deletes.extend(keys);
+            }
+        }
 
-            fts_field_names
+        let segment_ref = if vectors.is_empty() {
+            None
         } else {
-            Vec::new()
+            let segment_id = format!("seg_{}", Ulid::new());
+            let meta = self.namespace_meta(namespace).await?;
+            Some(
+                self.build_segment(namespace, &segment_id, &vectors, meta.as_ref(), fts_configs)
+                    .await?,
+            )
         };
 
-        // 9. CAS loop: re-read manifest, apply changes, write conditionally
         for attempt in 0..MAX_CAS_RETRIES {
             let (mut fresh_manifest, version) =
                 match Manifest::read_versioned(&self.store, namespace).await? {
@@ -387,7 +884,6 @@ impl Compactor {
                     None => (Manifest::default(), ManifestVersion(None)),
                 };
 
-            // Layer 1: Fencing check.
             if let Some(token) = fencing_token {
                 if fresh_manifest.fencing_token > token {
                     return Err(ZeppelinError::FencingTokenStale {
@@ -399,47 +895,209 @@ impl Compactor {
                 fresh_manifest.fencing_token = token;
             }
 
-            fresh_manifest.add_segment(SegmentRef {
-                id: segment_id.clone(),
-                vector_count: vectors_compacted,
-                cluster_count,
-                quantization: self.indexing_config.quantization,
-                hierarchical: is_hierarchical,
-                bitmap_fields: bitmap_fields.clone(),
-                fts_fields: fts_fields.clone(),
-            });
-            fresh_manifest.remove_compacted_fragments(last_fragment_id);
-            fresh_manifest.pending_deletes = deferred_deletes.clone();
+            // Another compaction tombstoned or replaced a source segment in
+            // the meantime; the merged segment would resurrect its IDs.
+            let live = fresh_manifest.live_segments();
+            if !sources.iter().all(|s| live.contains(&s)) {
+                return Err(ZeppelinError::ManifestConflict {
+                    namespace: namespace.to_string(),
+                });
+            }
+
+            match &segment_ref {
+                Some(sref) => fresh_manifest.replace_segments(&merged, sref.clone()),
+                None => fresh_manifest.remove_segments(&merged),
+            }
+            fresh_manifest
+                .pending_deletes
+                .extend(deferred_deletes.iter().cloned());
 
-            // Layer 2: CAS.
             match fresh_manifest
                 .write_conditional(&self.store, namespace, &version)
                 .await
             {
                 Ok(()) => {
-                    let elapsed = start.elapsed();
-                    crate::metrics::COMPACTION_DURATION
-                        .with_label_values(&[namespace])
-                        .observe(elapsed.as_secs_f64());
-
                     info!(
-                        segment_id = %segment_id,
-                        vectors_compacted,
-                        fragments_removed,
-                        elapsed_ms = elapsed.as_millis(),
+                        segment_id = ?segment_ref.as_ref().map(|s| &s.id),
+                        segments_merged = merged.len(),
+                        vectors = vectors.len(),
                         attempt,
-                        "compaction complete"
+                        "tiered segment merge complete"
                     );
+                    return Ok(merged.len());
+                }
+                Err(ZeppelinError::ManifestConflict { .. }) => {
+                    warn!(attempt, "manifest CAS conflict in segment merge, retrying");
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+        }
 
-                    return Ok(CompactionResult {
-                        segment_id: Some(segment_id),
-                        vectors_compacted,
-                        fragments_removed,
-                        old_segment_removed: old_segment_id,
+        Err(ZeppelinError::ManifestConflict {
+            namespace: namespace.to_string(),
+        })
+    }
+}
+
+impl Compactor {
+    /// Build and write per-cluster FTS inverted indexes for a segment from
+    /// its stored cluster attributes. Returns the fields that were indexed.
+    async fn build_fts_indexes(
+        &self,
+        namespace: &str,
+        segment_id: &str,
+        cluster_count: usize,
+        fts_configs: &HashMap<String, FtsFieldConfig>,
+    ) -> Result<Vec<String>> {
+        let fts_start = std::time::Instant::now();
+        let mut fts_field_names = Vec::new();
+
+        // Phase 1: Parallel reads of cluster attributes.
+        let attr_keys: Vec<String> = (0..cluster_count)
+            .map(|i| attrs_key(namespace, segment_id, i))
+            .collect();
+        let read_futs: Vec<_> = attr_keys.iter().map(|k| self.store.get(k)).collect();
+        let read_results = futures::future::join_all(read_futs).await;
+
+        // Phase 2: CPU — build inverted indexes.
+        let mut write_payloads = Vec::new();
+        for (cluster_idx, result) in read_results.into_iter().enumerate() {
+            let cluster_attrs = match result {
+                Ok(data) => deserialize_attrs(&data)?,
+                Err(_) => continue,
+            };
+
+            let attr_refs: Vec<Option<&HashMap<String, crate::types::AttributeValue>>> =
+                cluster_attrs.iter().map(|a| a.as_ref()).collect();
+
+            let inv_index = InvertedIndex::build(&attr_refs, fts_configs);
+
+            // Track which fields were indexed
+            for field_name in inv_index.fields.keys() {
+                if !fts_field_names.contains(field_name) {
+                    fts_field_names.push(field_name.clone());
+                }
+            }
+
+            let fts_data = inv_index.to_bytes()?;
+            let fts_key = fts_index_key(namespace, segment_id, cluster_idx);
+            write_payloads.push((fts_key, fts_data));
+        }
+
+        // Phase 3: Parallel writes of FTS indexes.
+        let write_futs: Vec<_> = write_payloads
+            .iter()
+            .map(|(key, data)| self.store.put(key, data.clone()))
+            .collect();
+        let write_results = futures::future::join_all(write_futs).await;
+        for result in write_results {
+            result?;
+        }
+
+        let fts_elapsed = fts_start.elapsed();
+        crate::metrics::FTS_INDEX_BUILD_DURATION
+            .with_label_values(&[namespace])
+            .observe(fts_elapsed.as_secs_f64());
+        debug!(
+            fts_fields = ?fts_field_names,
+            fts_build_duration_ms = fts_elapsed.as_millis() as u64,
+            clusters = cluster_count,
+            "FTS inverted index build complete"
+        );
+
+        Ok(fts_field_names)
+    }
+
+    /// Rebuild only the FTS inverted indexes of the live segments for a new
+    /// field configuration, leaving vector clusters untouched.
+    ///
+    /// Reads each segment's stored attributes, overwrites its FTS index
+    /// artifacts and updates the segment's `fts_fields` in the manifest.
+    /// With an empty `fts_configs` the segments' FTS artifacts are removed.
+    /// Returns the fields indexed in any segment, or `None` if there is no
+    /// segment.
+    #[instrument(skip(self, fts_configs), fields(namespace = namespace))]
+    pub async fn rebuild_fts(
+        &self,
+        namespace: &str,
+        fts_configs: &HashMap<String, FtsFieldConfig>,
+    ) -> Result<Option<Vec<String>>> {
+        let manifest = Manifest::read(&self.store, namespace)
+            .await?
+            .unwrap_or_default();
+        let segments = manifest.live_segments();
+        if segments.is_empty() {
+            return Ok(None);
+        }
+
+        let fts_index = segments.iter().any(|s| s.text_only)
+            || self
+                .indexing_config_for(self.namespace_meta(namespace).await?.as_ref())
+                .fts_index;
+        let mut rebuilt: Vec<(String, Vec<String>)> = Vec::new();
+        for segment in segments {
+            let segment_id = segment.id.clone();
+            let cluster_count = segment.cluster_count;
+            let fts_fields = if !fts_configs.is_empty() && fts_index {
+                self.build_fts_indexes(namespace, &segment_id, cluster_count, fts_configs)
+                    .await?
+            } else {
+                Vec::new()
+            };
+            if fts_fields.is_empty() {
+                for cluster_idx in 0..cluster_count {
+                    let key = fts_index_key(namespace, &segment_id, cluster_idx);
+                    if let Err(e) = self.store.delete(&key).await {
+                        warn!(key = %key, error = %e, "failed to delete FTS index");
+                    }
+                }
+            }
+            rebuilt.push((segment_id, fts_fields));
+        }
+        let mut indexed: Vec<String> = Vec::new();
+        for field in rebuilt.iter().flat_map(|(_, fields)| fields) {
+            if !indexed.contains(field) {
+                indexed.push(field.clone());
+            }
+        }
+
+        for attempt in 0..MAX_CAS_RETRIES {
+            let (mut fresh_manifest, version) =
+                match Manifest::read_versioned(&self.store, namespace).await? {
+                    Some(pair) => pair,
+                    None => (Manifest::default(), ManifestVersion(None)),
+                };
+            // A compaction that replaced a segment in the meantime built
+            // its FTS indexes from the old config; the caller must retry.
+            for (segment_id, fts_fields) in &rebuilt {
+                let Some(seg_ref) = fresh_manifest
+                    .segments
+                    .iter_mut()
+                    .find(|s| &s.id == segment_id)
+                else {
+                    return Err(ZeppelinError::ManifestConflict {
+                        namespace: namespace.to_string(),
                     });
+                };
+                seg_ref.fts_fields = fts_fields.clone();
+            }
+
+            match fresh_manifest
+                .write_conditional(&self.store, namespace, &version)
+                .await
+            {
+                Ok(()) => {
+                    info!(
+                        segments = rebuilt.len(),
+                        fts_fields = ?indexed,
+                        attempt,
+                        "FTS rebuild complete"
+                    );
+                    return Ok(Some(indexed));
                 }
                 Err(ZeppelinError::ManifestConflict { .. }) => {
-                    warn!(attempt, "manifest CAS conflict in compactor, retrying");
+                    warn!(attempt, "manifest CAS conflict in FTS rebuild, retrying");
                     continue;
                 }
                 Err(e) => return Err(e),
@@ -450,6 +1108,119 @@ impl Compactor {
             namespace: namespace.to_string(),
         })
     }
+
+    /// The namespace's `meta.json`, or `None` when there is none (e.g.
+    /// manifests written without `meta.json`) or it can't be parsed. Read
+    /// errors are returned, so compaction never builds a segment with the
+    /// server defaults in place of the namespace's own settings.
+    async fn namespace_meta(&self, namespace: &str) -> Result<Option<NamespaceMetadata>> {
+        let data = match self.store.get(&NamespaceMetadata::s3_key(namespace)).await {
+            Ok(data) => data,
+            Err(ZeppelinError::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        match NamespaceMetadata::from_bytes(&data) {
+            Ok(meta) => Ok(Some(meta)),
+            Err(e) => {
+                warn!(error = %e, "failed to parse namespace metadata, using server indexing config");
+                Ok(None)
+            }
+        }
+    }
+
+    /// Indexing settings for a namespace, derived from its `index_type`.
+    ///
+    /// Falls back to the server-wide config without namespace metadata.
+    fn indexing_config_for(&self, meta: Option<&NamespaceMetadata>) -> IndexingConfig {
+        match meta {
+            Some(meta) => {
+                let mut config = self.indexing_config.for_index_type(meta.index_type);
+                config.kmeans_metric.get_or_insert(meta.distance_metric);
+                config
+            }
+            None => self.indexing_config.clone(),
+        }
+    }
+
+    /// Estimate recall@`top_k` of the namespace's live segments.
+    ///
+    /// Samples up to `sample_size` vectors from the segments (evenly spaced),
+    /// queries the namespace with each at `nprobe`, and compares the returned
+    /// IDs against a brute-force top-k over every live segment vector.
+    /// Returns the mean recall, or `None` if there is no segment or the
+    /// namespace is text-only.
+    #[instrument(skip(self), fields(namespace = namespace))]
+    pub async fn estimate_recall(
+        &self,
+        namespace: &str,
+        distance_metric: DistanceMetric,
+        nprobe: usize,
+        sample_size: usize,
+        top_k: usize,
+    ) -> Result<Option<f64>> {
+        let manifest = Manifest::read(&self.store, namespace)
+            .await?
+            .unwrap_or_default();
+        let segments = manifest.live_segments();
+        if segments.is_empty() || segments.iter().any(|s| s.text_only) {
+            return Ok(None);
+        }
+        let mut vectors = Vec::new();
+        for seg in segments {
+            vectors.extend(load_live_vectors(&self.store, namespace, seg).await?);
+        }
+        if vectors.is_empty() || sample_size == 0 || top_k == 0 {
+            return Ok(None);
+        }
+
+        let k = top_k.min(vectors.len());
+        let step = (vectors.len() / sample_size).max(1);
+        let mut total = 0.0;
+        let mut samples = 0;
+        for probe in vectors.iter().step_by(step).take(sample_size) {
+            let mut exact: Vec<(f32, &str)> = vectors
+                .iter()
+                .map(|v| {
+                    let d = compute_distance(&probe.values, &v.values, distance_metric);
+                    (d, v.id.as_str())
+                })
+                .collect();
+            exact.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let truth: HashSet<&str> = exact.iter().take(k).map(|(_, id)| *id).collect();
+
+            let response = crate::query::execute_query(
+                &self.store,
+                &self.wal_reader,
+                namespace,
+                &probe.values,
+                k,
+                nprobe,
+                None,
+                ConsistencyLevel::Eventual,
+                distance_metric,
+                self.indexing_config.oversample_factor,
+                None,
+            )
+            .await?;
+            let hits = response
+                .results
+                .iter()
+                .filter(|r| truth.contains(r.id.as_str()))
+                .count();
+            total += hits as f64 / k as f64;
+            samples += 1;
+        }
+
+        let recall = total / samples as f64;
+        info!(
+            recall,
+            samples,
+            top_k = k,
+            nprobe,
+            "estimated segment recall"
+        );
+        Ok(Some(recall))
+    }
 }
 
 /// Load all vectors from an existing IVF-Flat segment on S3.
@@ -480,6 +1251,10 @@ async fn load_segment_vectors(
                 id,
                 values: cluster.vectors[j].clone(),
                 attributes: attrs.get(j).cloned().flatten(),
+                sparse: None,
+                named_vectors: None,
+                token_vectors: None,
+                version: None,
             });
         }
     }
@@ -492,3 +1267,190 @@ async fn load_segment_vectors(
 
     Ok(vectors)
 }
+
+/// Load all documents from a text-only segment. Their `values` are empty.
+async fn load_text_only_docs(
+    store: &ZeppelinStore,
+    namespace: &str,
+    segment_id: &str,
+    partitions: usize,
+) -> Result<Vec<VectorEntry>> {
+    let mut docs = Vec::new();
+    for i in 0..partitions {
+        let ids = deserialize_cluster(&store.get(&docs_key(namespace, segment_id, i)).await?)?.ids;
+        let attrs = match store.get(&attrs_key(namespace, segment_id, i)).await {
+            Ok(data) => deserialize_attrs(&data)?,
+            Err(_) => vec![None; ids.len()],
+        };
+        for (j, id) in ids.into_iter().enumerate() {
+            docs.push(VectorEntry {
+                id,
+                values: Vec::new(),
+                attributes: attrs.get(j).cloned().flatten(),
+                sparse: None,
+                named_vectors: None,
+                token_vectors: None,
+                version: None,
+            });
+        }
+    }
+
+    debug!(
+        segment_id = segment_id,
+        docs_loaded = docs.len(),
+        "loaded documents from existing text-only segment"
+    );
+
+    Ok(docs)
+}
+
+/// Load every vector stored in a segment, whatever its index type.
+async fn load_vectors(
+    store: &ZeppelinStore,
+    namespace: &str,
+    seg: &SegmentRef,
+) -> Result<Vec<VectorEntry>> {
+    let mut vectors = if seg.text_only {
+        load_text_only_docs(store, namespace, &seg.id, seg.cluster_count).await?
+    } else if seg.vamana {
+        load_vamana_vectors(store, namespace, &seg.id).await?
+    } else {
+        load_segment_vectors(store, namespace, &seg.id).await?
+    };
+    if seg.sparse {
+        crate::query::attach_sparse(store, namespace, seg, &mut vectors).await?;
+    }
+    if seg.named_vectors {
+        crate::query::attach_named_vectors(store, namespace, seg, &mut vectors).await?;
+    }
+    if seg.token_vectors {
+        crate::query::attach_token_vectors(store, namespace, seg, None, &mut vectors).await?;
+    }
+    if seg.versions {
+        crate::query::attach_versions(store, namespace, seg, &mut vectors).await?;
+    }
+    Ok(vectors)
+}
+
+/// Load a segment's vectors minus its tombstoned IDs.
+async fn load_live_vectors(
+    store: &ZeppelinStore,
+    namespace: &str,
+    seg: &SegmentRef,
+) -> Result<Vec<VectorEntry>> {
+    let mut vectors = load_vectors(store, namespace, seg).await?;
+    vectors.retain(|v| !seg.tombstones.contains(&v.id));
+    Ok(vectors)
+}
+
+/// IDs stored in a segment, from its ID map when it has one.
+async fn load_segment_ids(
+    store: &ZeppelinStore,
+    namespace: &str,
+    seg: &SegmentRef,
+) -> Result<Vec<String>> {
+    match store.get(&segment_id_map_key(namespace, &seg.id)).await {
+        Ok(data) => Ok(SegmentIdMap::from_bytes(&data)?.ids().to_vec()),
+        // Segments written before ID maps existed.
+        Err(ZeppelinError::NotFound { .. }) => Ok(load_vectors(store, namespace, seg)
+            .await?
+            .into_iter()
+            .map(|v| v.id)
+            .collect()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Add new tombstones, keyed by segment ID, to the manifest's segments.
+fn apply_tombstones(manifest: &mut Manifest, tombstones: &HashMap<String, BTreeSet<String>>) {
+    for seg in &mut manifest.segments {
+        if let Some(ids) = tombstones.get(&seg.id) {
+            seg.tombstones.extend(ids.iter().cloned());
+        }
+    }
+}
+
+/// Pick the live segments a tiered merge should combine.
+///
+/// Segments fall into size tiers by live vector count: tier `t` holds
+/// counts in `[factor^t, factor^(t+1))`. The smallest tier with at least
+/// `merge_factor` segments is merged. Failing that, a segment that is at
+/// least half tombstones is rewritten alone. Empty when nothing qualifies.
+fn pick_tier_merge(segments: &[&SegmentRef], merge_factor: usize) -> Vec<String> {
+    let mut tiers: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    for seg in segments {
+        let live = seg.vector_count.saturating_sub(seg.tombstones.len());
+        tiers
+            .entry(live.max(1).ilog(merge_factor))
+            .or_default()
+            .push(seg.id.clone());
+    }
+    if let Some(ids) = tiers.into_values().find(|ids| ids.len() >= merge_factor) {
+        return ids;
+    }
+    segments
+        .iter()
+        .find(|s| !s.tombstones.is_empty() && s.tombstones.len() * 2 >= s.vector_count)
+        .map(|s| vec![s.id.clone()])
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(id: &str, vector_count: usize, tombstones: usize) -> SegmentRef {
+        SegmentRef {
+            id: id.to_string(),
+            vector_count,
+            cluster_count: 1,
+            quantization: Default::default(),
+            hierarchical: false,
+            vamana: false,
+            bitmap_fields: Vec::new(),
+            fts_fields: Vec::new(),
+            kmeans_metric: None,
+            text_only: false,
+            sparse: false,
+            named_vectors: false,
+            token_vectors: false,
+            versions: false,
+            tombstones: (0..tombstones).map(|i| format!("t{i}")).collect(),
+        }
+    }
+
+    #[test]
+    fn test_pick_tier_merge_waits_for_full_tier() {
+        let (a, b) = (segment("a", 1000, 0), segment("b", 40, 0));
+        assert!(pick_tier_merge(&[&a, &b], 3).is_empty());
+    }
+
+    #[test]
+    fn test_pick_tier_merge_takes_smallest_full_tier() {
+        let segs = [
+            segment("big1", 900, 0),
+            segment("big2", 1000, 0),
+            segment("big3", 1100, 0),
+            segment("s1", 10, 0),
+            segment("s2", 12, 0),
+            segment("s3", 20, 0),
+        ];
+        let refs: Vec<&SegmentRef> = segs.iter().collect();
+        assert_eq!(pick_tier_merge(&refs, 3), vec!["s1", "s2", "s3"]);
+    }
+
+    #[test]
+    fn test_pick_tier_merge_counts_live_vectors() {
+        // 100 vectors with 95 tombstones sit in the tier of 5.
+        let segs = [segment("a", 100, 95), segment("b", 4, 0)];
+        let refs: Vec<&SegmentRef> = segs.iter().collect();
+        assert_eq!(pick_tier_merge(&refs, 2), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_pick_tier_merge_rewrites_mostly_tombstoned_segment() {
+        let segs = [segment("a", 1000, 10), segment("b", 100, 60)];
+        let refs: Vec<&SegmentRef> = segs.iter().collect();
+        assert_eq!(pick_tier_merge(&refs, 4), vec!["b"]);
+    }
+}
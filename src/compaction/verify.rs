@@ -0,0 +1,257 @@
+//! Consistency check between a namespace's manifest and the objects on S3.
+//!
+//! The manifest is the source of truth, so every artifact of the live
+//! segments and every uncompacted fragment it references must exist, and
+//! every object under `wal/` or `segments/` must be referenced by it —
+//! either as live data or as a `pending_deletes` entry awaiting GC.
+
+use std::collections::HashSet;
+
+use serde::Serialize;
+use tracing::instrument;
+use ulid::Ulid;
+
+use crate::error::Result;
+use crate::fts::inverted_index::fts_index_key;
+use crate::index::bitmap::bitmap_key;
+use crate::index::hierarchical::tree_meta_key;
+use crate::index::ivf_flat::build::{
+    attrs_key, centroids_key, cluster_key, deserialize_cluster, docs_key,
+};
+use crate::index::late_interaction::token_matrix_key;
+use crate::index::named::named_vectors_key;
+use crate::index::quantization::pq::{pq_cluster_key, pq_codebook_key};
+use crate::index::quantization::sq::{sq_calibration_key, sq_cluster_key};
+use crate::index::quantization::QuantizationType;
+use crate::index::sparse::sparse_index_key;
+use crate::index::vamana::{graph_block_key, vamana_meta_key};
+use crate::storage::ZeppelinStore;
+use crate::wal::fragment::WalFragment;
+use crate::wal::manifest::{Manifest, SegmentRef};
+
+/// One disagreement between the manifest and S3.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Inconsistency {
+    /// An artifact of a live segment is missing.
+    MissingArtifact { key: String },
+    /// A cluster (or ID partition) exists but cannot be decoded.
+    UnreadableArtifact { key: String, error: String },
+    /// The IDs read from a live segment do not add up to the manifest's
+    /// `vector_count`.
+    VectorCountMismatch {
+        segment_id: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A fragment referenced by the manifest is missing.
+    MissingFragment { key: String },
+    /// A fragment the manifest does not reference. `compacted` is set when
+    /// it is at or below the compaction watermark, i.e. it should have
+    /// been deleted.
+    OrphanedFragment { key: String, compacted: bool },
+    /// An object under `segments/` of a segment that is neither live nor
+    /// pending deletion.
+    OrphanedSegmentObject { segment_id: String, key: String },
+}
+
+/// Result of [`verify_namespace`].
+#[derive(Debug, Serialize)]
+pub struct VerifyReport {
+    pub namespace: String,
+    pub active_segment: Option<String>,
+    /// Older live segments under tiered compaction.
+    pub tier_segments: Vec<String>,
+    /// IDs read back from the live segments' clusters.
+    pub segment_ids_read: usize,
+    pub fragments_checked: usize,
+    pub inconsistencies: Vec<Inconsistency>,
+}
+
+impl VerifyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.inconsistencies.is_empty()
+    }
+}
+
+/// Cross-check a namespace's manifest against the objects on S3.
+///
+/// Reads every cluster of the live segments, so the cost is that of a full
+/// segment scan. Objects written by a compaction that has not committed its
+/// manifest yet are reported as orphaned.
+#[instrument(skip(store), fields(namespace = namespace))]
+pub async fn verify_namespace(store: &ZeppelinStore, namespace: &str) -> Result<VerifyReport> {
+    let manifest = Manifest::read(store, namespace).await?.unwrap_or_default();
+    let pending: HashSet<&str> = manifest
+        .pending_deletes
+        .iter()
+        .map(String::as_str)
+        .collect();
+    let mut inconsistencies = Vec::new();
+
+    // 1. Live segments: every expected artifact exists and every ID is readable.
+    let live = manifest.live_segments();
+    let mut segment_ids_read = 0;
+    for seg in &live {
+        let present: HashSet<String> = store
+            .list_prefix(&format!("{namespace}/segments/{}/", seg.id))
+            .await?
+            .into_iter()
+            .collect();
+        for key in expected_artifacts(namespace, seg) {
+            if !present.contains(&key) {
+                inconsistencies.push(Inconsistency::MissingArtifact { key });
+            }
+        }
+        let mut ids_read = 0;
+        for i in 0..seg.cluster_count {
+            let key = if seg.text_only || seg.vamana {
+                docs_key(namespace, &seg.id, i)
+            } else {
+                cluster_key(namespace, &seg.id, i)
+            };
+            if !present.contains(&key) {
+                continue;
+            }
+            match store.get(&key).await.and_then(|d| deserialize_cluster(&d)) {
+                Ok(cluster) => ids_read += cluster.ids.len(),
+                Err(e) => inconsistencies.push(Inconsistency::UnreadableArtifact {
+                    key,
+                    error: e.to_string(),
+                }),
+            }
+        }
+        if ids_read != seg.vector_count {
+            inconsistencies.push(Inconsistency::VectorCountMismatch {
+                segment_id: seg.id.clone(),
+                expected: seg.vector_count,
+                found: ids_read,
+            });
+        }
+        segment_ids_read += ids_read;
+    }
+    // 2. Fragments: referenced ones exist, unreferenced ones are orphans.
+    let fragment_keys: HashSet<String> = store
+        .list_prefix(&format!("{namespace}/wal/"))
+        .await?
+        .into_iter()
+        .filter(|k| k.ends_with(".wal"))
+        .collect();
+    let mut referenced = HashSet::new();
+    for fref in &manifest.fragments {
+        let key = WalFragment::s3_key(namespace, &fref.id);
+        if !fragment_keys.contains(&key) {
+            inconsistencies.push(Inconsistency::MissingFragment { key: key.clone() });
+        }
+        referenced.insert(key);
+    }
+    let mut orphaned_fragments: Vec<&String> = fragment_keys
+        .iter()
+        .filter(|k| !referenced.contains(*k) && !pending.contains(k.as_str()))
+        .collect();
+    orphaned_fragments.sort();
+    for key in orphaned_fragments {
+        let compacted = match (fragment_id(key), manifest.compaction_watermark) {
+            (Some(id), Some(watermark)) => id <= watermark,
+            _ => false,
+        };
+        inconsistencies.push(Inconsistency::OrphanedFragment {
+            key: key.clone(),
+            compacted,
+        });
+    }
+
+    // 3. Segment objects outside the live segments and pending deletes.
+    let segments_prefix = format!("{namespace}/segments/");
+    let mut segment_keys = store.list_prefix(&segments_prefix).await?;
+    segment_keys.sort();
+    for key in segment_keys {
+        let Some(segment_id) = key
+            .strip_prefix(&segments_prefix)
+            .and_then(|rest| rest.split('/').next())
+        else {
+            continue;
+        };
+        if live.iter().any(|s| s.id == segment_id) || pending.contains(key.as_str()) {
+            continue;
+        }
+        inconsistencies.push(Inconsistency::OrphanedSegmentObject {
+            segment_id: segment_id.to_string(),
+            key,
+        });
+    }
+
+    Ok(VerifyReport {
+        namespace: namespace.to_string(),
+        active_segment: manifest.active_segment.clone(),
+        tier_segments: manifest.tier_segments.clone(),
+        segment_ids_read,
+        fragments_checked: manifest.fragments.len(),
+        inconsistencies,
+    })
+}
+
+/// Keys a segment described by `seg` must have on S3.
+fn expected_artifacts(namespace: &str, seg: &SegmentRef) -> Vec<String> {
+    let id = seg.id.as_str();
+    let mut keys = Vec::new();
+    if seg.text_only {
+        for i in 0..seg.cluster_count {
+            keys.push(docs_key(namespace, id, i));
+            keys.push(attrs_key(namespace, id, i));
+        }
+    } else if seg.vamana {
+        keys.push(vamana_meta_key(namespace, id));
+        for i in 0..seg.cluster_count {
+            keys.push(graph_block_key(namespace, id, i));
+            keys.push(docs_key(namespace, id, i));
+            keys.push(attrs_key(namespace, id, i));
+        }
+    } else {
+        keys.push(if seg.hierarchical {
+            tree_meta_key(namespace, id)
+        } else {
+            centroids_key(namespace, id)
+        });
+        match seg.quantization {
+            QuantizationType::Scalar => keys.push(sq_calibration_key(namespace, id)),
+            QuantizationType::Product => keys.push(pq_codebook_key(namespace, id)),
+            QuantizationType::None => {}
+        }
+        for i in 0..seg.cluster_count {
+            keys.push(cluster_key(namespace, id, i));
+            keys.push(attrs_key(namespace, id, i));
+            match seg.quantization {
+                QuantizationType::Scalar => keys.push(sq_cluster_key(namespace, id, i)),
+                QuantizationType::Product => keys.push(pq_cluster_key(namespace, id, i)),
+                QuantizationType::None => {}
+            }
+            if !seg.bitmap_fields.is_empty() {
+                keys.push(bitmap_key(namespace, id, i));
+            }
+        }
+    }
+    if !seg.fts_fields.is_empty() {
+        for i in 0..seg.cluster_count {
+            keys.push(fts_index_key(namespace, id, i));
+        }
+    }
+    if seg.sparse {
+        keys.push(sparse_index_key(namespace, id));
+    }
+    if seg.named_vectors {
+        keys.push(named_vectors_key(namespace, id));
+    }
+    if seg.token_vectors {
+        for i in 0..seg.cluster_count.max(1) {
+            keys.push(token_matrix_key(namespace, id, i));
+        }
+    }
+    keys
+}
+
+/// The fragment ULID in a `{ns}/wal/{id}.wal` key.
+fn fragment_id(key: &str) -> Option<Ulid> {
+    let name = key.rsplit('/').next()?.strip_suffix(".wal")?;
+    Ulid::from_string(name).ok()
+}
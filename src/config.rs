@@ -18,6 +18,12 @@ pub struct Config {
     pub consistency: ConsistencyConfig,
     #[serde(default)]
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub wal: WalConfig,
+    #[serde(default)]
+    pub query: QueryConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,18 +36,114 @@ pub struct ServerConfig {
     pub request_timeout_secs: u64,
     #[serde(default = "default_max_concurrent_queries")]
     pub max_concurrent_queries: usize,
+    /// Upserts allowed in flight per namespace; further concurrent upserts
+    /// get 429 instead of queueing. `0` disables the limit.
+    #[serde(default = "default_max_concurrent_upserts_per_ns")]
+    pub max_concurrent_upserts_per_ns: usize,
     #[serde(default = "default_max_batch_size")]
     pub max_batch_size: usize,
     #[serde(default = "default_max_top_k")]
     pub max_top_k: usize,
+    /// Most queries accepted by one `query_batch` request.
+    #[serde(default = "default_max_query_batch_size")]
+    pub max_query_batch_size: usize,
     #[serde(default = "default_shutdown_timeout_secs")]
     pub shutdown_timeout_secs: u64,
     #[serde(default = "default_max_dimensions")]
     pub max_dimensions: usize,
     #[serde(default = "default_max_vector_id_length")]
     pub max_vector_id_length: usize,
+    /// Characters allowed in upserted vector IDs. See [`VectorIdCharset`].
+    #[serde(default)]
+    pub vector_id_charset: VectorIdCharset,
     #[serde(default = "default_max_request_body_mb")]
     pub max_request_body_mb: usize,
+    /// Key casing for query and namespace response bodies: "snake"
+    /// (default) or "camel". Request bodies accept either.
+    #[serde(default)]
+    pub json_case: JsonCase,
+    /// Request rate allowed per API key and namespace for upserts, deletes
+    /// and patches. Disabled by default.
+    #[serde(default)]
+    pub write_rate_limit: RateLimit,
+    /// Request rate allowed per API key and namespace for queries and other
+    /// reads. Disabled by default.
+    #[serde(default)]
+    pub query_rate_limit: RateLimit,
+}
+
+/// A token bucket: `qps` requests per second on average, with bursts of up
+/// to `burst`. Requests over the rate get 429 with `Retry-After`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// `0` disables the limit.
+    #[serde(default)]
+    pub qps: f64,
+    /// `0` means `qps` rounded up.
+    #[serde(default)]
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub fn is_enabled(&self) -> bool {
+        self.qps > 0.0
+    }
+
+    /// Bucket capacity in requests, at least 1.
+    pub fn capacity(&self) -> f64 {
+        if self.burst > 0 {
+            f64::from(self.burst)
+        } else {
+            self.qps.ceil().max(1.0)
+        }
+    }
+}
+
+/// Characters allowed in vector IDs on upsert. Deletes accept any ID so
+/// vectors written under a looser policy can still be removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VectorIdCharset {
+    /// Any UTF-8 string.
+    Any,
+    /// Anything except control characters (newlines, NUL, escapes), which
+    /// break log lines and object key handling.
+    #[default]
+    Printable,
+    /// ASCII letters, digits and `-`, `_`, `.`, `~` only.
+    UrlSafe,
+}
+
+impl VectorIdCharset {
+    /// The first character of `id` this policy rejects, if any.
+    pub fn first_invalid(&self, id: &str) -> Option<char> {
+        match self {
+            VectorIdCharset::Any => None,
+            VectorIdCharset::Printable => id.chars().find(|c| c.is_control()),
+            VectorIdCharset::UrlSafe => id
+                .chars()
+                .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '~'))),
+        }
+    }
+}
+
+impl std::fmt::Display for VectorIdCharset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VectorIdCharset::Any => write!(f, "any"),
+            VectorIdCharset::Printable => write!(f, "printable"),
+            VectorIdCharset::UrlSafe => write!(f, "url_safe"),
+        }
+    }
+}
+
+/// Key casing applied to API response DTOs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonCase {
+    #[default]
+    Snake,
+    Camel,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,6 +174,12 @@ pub struct StorageConfig {
     pub azure_account: Option<String>,
     #[serde(default)]
     pub azure_access_key: Option<String>,
+
+    /// Times a strong query re-reads a manifest older than one this node
+    /// has committed, for backends with eventually consistent overwrites.
+    /// `0` disables the retry.
+    #[serde(default = "default_read_after_write_retries")]
+    pub read_after_write_retries: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -82,6 +190,11 @@ pub struct CacheConfig {
     pub max_size_gb: u64,
     #[serde(default = "default_eviction")]
     pub eviction: String,
+    /// Also pin cluster vector data, not just centroids, for namespaces
+    /// with `cache_pinned` set. Cluster data can be large, so this is off
+    /// by default.
+    #[serde(default)]
+    pub pin_clusters: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -106,8 +219,10 @@ pub struct IndexingConfig {
     #[serde(default = "default_pq_m")]
     pub pq_m: usize,
     /// Reranking factor: how many candidates to fetch with approximate
-    /// distances before reranking with full-precision vectors.
-    /// Only used when quantization is enabled. Default: 4.
+    /// distances before reranking with full-precision vectors, as a multiple
+    /// of the fetch size. Higher improves recall at the cost of latency.
+    /// Queries may override it. Only used when quantization is enabled.
+    /// Default: 4.
     #[serde(default = "default_rerank_factor")]
     pub rerank_factor: usize,
     /// Whether to use hierarchical (multi-level centroid tree) indexing.
@@ -124,6 +239,27 @@ pub struct IndexingConfig {
     /// (e.g., 5–10) in tests to force multi-level trees with small datasets.
     #[serde(default)]
     pub leaf_size: Option<usize>,
+    /// Whether to build a Vamana graph index instead of clusters. Takes
+    /// precedence over `hierarchical`; quantization does not apply.
+    /// Default: false.
+    #[serde(default)]
+    pub vamana: bool,
+    /// Maximum out-degree of a Vamana graph node. Default: 32.
+    #[serde(default = "default_vamana_max_degree")]
+    pub vamana_max_degree: usize,
+    /// Candidate list size for Vamana graph construction, and the minimum
+    /// list size at search time. Default: 64.
+    #[serde(default = "default_vamana_search_list_size")]
+    pub vamana_search_list_size: usize,
+    /// Pruning factor of the second Vamana build pass; larger keeps more
+    /// long-range edges. Default: 1.2.
+    #[serde(default = "default_vamana_alpha")]
+    pub vamana_alpha: f32,
+    /// Target size in bytes of a Vamana graph block on S3. Each block holds
+    /// as many fixed-size node records as fit, at least one.
+    /// Default: 256 KiB.
+    #[serde(default = "default_vamana_block_size_bytes")]
+    pub vamana_block_size_bytes: usize,
     /// Whether to build bitmap indexes for pre-filtering.
     /// When true, each cluster gets a roaring bitmap index per attribute field,
     /// enabling filter evaluation before distance computation.
@@ -132,6 +268,35 @@ pub struct IndexingConfig {
     /// Whether to build FTS inverted indexes during compaction.
     #[serde(default)]
     pub fts_index: bool,
+    /// Maximum vectors per probed cluster that get a full-precision distance.
+    /// Larger clusters are narrowed first by a cheap approximate distance,
+    /// bounding latency on skewed data at some cost in recall.
+    /// `None` (default) scans every vector. Not used by hierarchical search.
+    #[serde(default)]
+    pub max_candidates_per_cluster: Option<usize>,
+    /// Metric IVF-Flat k-means trains centroids under. `None` (default)
+    /// follows the namespace's distance metric; cosine trains spherical
+    /// k-means, the other metrics Euclidean.
+    #[serde(default)]
+    pub kmeans_metric: Option<crate::types::DistanceMetric>,
+    /// Seed for IVF-Flat k-means++ initialization, making index builds
+    /// over the same vectors reproducible. `None` (default) seeds randomly.
+    #[serde(default)]
+    pub kmeans_seed: Option<u64>,
+    /// Raise IVF-Flat `nprobe` for selective filters. The bitmaps of the
+    /// clusters about to be probed estimate what fraction of vectors pass the
+    /// filter, and `nprobe` grows until roughly `top_k` survivors are
+    /// expected, up to `max_nprobe`. Only applies to segments with bitmap
+    /// indexes. Default: false.
+    #[serde(default)]
+    pub adaptive_nprobe: bool,
+    /// Probe IVF-Flat clusters adaptively instead of a fixed `nprobe`: the
+    /// nearest cluster plus any whose centroid distance is within this
+    /// ratio of the nearest one's, up to `max_nprobe`. Queries inside a
+    /// cluster probe fewer clusters, queries near a boundary more. Must be
+    /// at least 1; queries may override it. `None` (default) uses `nprobe`.
+    #[serde(default)]
+    pub probe_gap_ratio: Option<f32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -140,8 +305,108 @@ pub struct CompactionConfig {
     pub interval_secs: u64,
     #[serde(default = "default_max_wal_fragments")]
     pub max_wal_fragments_before_compact: usize,
+    /// Also compact once the oldest uncompacted WAL fragment is at least
+    /// this many seconds old, however few fragments there are. `None`
+    /// (default) compacts on fragment count alone.
+    #[serde(default)]
+    pub max_fragment_age_secs: Option<u64>,
     #[serde(default = "default_retrain_threshold")]
     pub retrain_imbalance_threshold: f64,
+    /// How IVF-Flat segments group vectors into clusters: `"vector"`
+    /// (k-means, the default) or `"attribute:<field>"`.
+    #[serde(default)]
+    pub cluster_by: ClusterBy,
+    /// When set, compaction collapses vectors whose Euclidean distance is
+    /// below this value into the most recently written one, regardless of
+    /// ID. Collapsed IDs are removed from the namespace. Default: disabled.
+    #[serde(default)]
+    pub dedup_epsilon: Option<f32>,
+    /// When set, compaction is tiered: each run writes the WAL to a new
+    /// small segment instead of rewriting the active one, and once this
+    /// many live segments fall in the same size tier (powers of the
+    /// factor) they are merged into one, dropping tombstoned IDs. Must be
+    /// at least 2. `None` (default) rewrites a single segment every run.
+    #[serde(default)]
+    pub merge_factor: Option<usize>,
+}
+
+/// Per-namespace overrides of the compaction triggers in
+/// [`CompactionConfig`]. Unset fields fall back to the global config.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactionOverrides {
+    #[serde(default, alias = "maxWalFragmentsBeforeCompact")]
+    pub max_wal_fragments_before_compact: Option<usize>,
+    #[serde(default, alias = "maxFragmentAgeSecs")]
+    pub max_fragment_age_secs: Option<u64>,
+}
+
+impl CompactionOverrides {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+impl CompactionConfig {
+    /// This config with a namespace's overrides applied.
+    pub fn with_overrides(&self, overrides: &CompactionOverrides) -> Self {
+        Self {
+            max_wal_fragments_before_compact: overrides
+                .max_wal_fragments_before_compact
+                .unwrap_or(self.max_wal_fragments_before_compact),
+            max_fragment_age_secs: overrides
+                .max_fragment_age_secs
+                .or(self.max_fragment_age_secs),
+            ..self.clone()
+        }
+    }
+}
+
+/// Clustering strategy used when compaction builds an IVF-Flat segment.
+///
+/// `Attribute` sorts vectors by the named attribute and cuts the sorted run
+/// into contiguous clusters, so a range filter on that attribute lands in a
+/// handful of clusters. Centroids are the mean of each range, so vector
+/// search still works, but recall at a given `nprobe` is lower than with
+/// k-means because clusters are no longer spatially tight.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ClusterBy {
+    #[default]
+    Vector,
+    Attribute(String),
+}
+
+impl std::str::FromStr for ClusterBy {
+    type Err = ZeppelinError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            None if s == "vector" => Ok(ClusterBy::Vector),
+            Some(("attribute", field)) if !field.is_empty() => {
+                Ok(ClusterBy::Attribute(field.to_string()))
+            }
+            _ => Err(ZeppelinError::Config(format!(
+                "invalid cluster_by '{s}': expected \"vector\" or \"attribute:<field>\""
+            ))),
+        }
+    }
+}
+
+impl TryFrom<String> for ClusterBy {
+    type Error = ZeppelinError;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<ClusterBy> for String {
+    fn from(c: ClusterBy) -> Self {
+        match c {
+            ClusterBy::Vector => "vector".to_string(),
+            ClusterBy::Attribute(field) => format!("attribute:{field}"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -158,6 +423,97 @@ pub struct LoggingConfig {
     pub format: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalConfig {
+    /// Encoding for newly written WAL fragments: "row" (JSON, default) or
+    /// "columnar" (binary columns). Readers accept either.
+    #[serde(default)]
+    pub layout: crate::wal::WalLayout,
+    /// Hold small upserts and deletes to a namespace for up to this many
+    /// milliseconds and write them as one fragment. Each request is still
+    /// acknowledged only after its fragment is committed. Deletes by ID are
+    /// written alone, after flushing the held writes, since they check for
+    /// existing tombstones under the namespace lock. `None` (default)
+    /// writes one fragment per request.
+    #[serde(default)]
+    pub coalesce_window_ms: Option<u64>,
+    /// Flush a coalescing batch early once it holds this many operations.
+    /// Requests at least this large bypass coalescing.
+    #[serde(default = "default_coalesce_max_vectors")]
+    pub coalesce_max_vectors: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueryConfig {
+    /// Soft deadline for vector search, in milliseconds from the start of
+    /// the query. Once it passes, segment search stops probing further
+    /// clusters and returns what it has, flagged with a `partial_results`
+    /// warning. The closest cluster is always scanned, so `0` means
+    /// "one cluster". `None` (default) disables the deadline.
+    #[serde(default)]
+    pub soft_deadline_ms: Option<u64>,
+    /// For strong queries, resolve an ID returned by both the WAL and the
+    /// segment by write time instead of always preferring the WAL: a WAL
+    /// fragment older than the active segment's compaction watermark loses
+    /// to the segment. Off by default.
+    #[serde(default)]
+    pub dedupe_by_write_time: bool,
+}
+
+/// API key authentication. Disabled while `api_keys` is empty; once any key
+/// is configured, every request except health checks and `/metrics` must
+/// send one as `Authorization: Bearer <key>`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+}
+
+/// One API key and the roles it holds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyConfig {
+    /// Label for logs; the key itself is never logged.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub key: String,
+    pub grants: Vec<RoleGrant>,
+}
+
+/// A role on every namespace whose name starts with `prefix`. When several
+/// of a key's grants match a namespace, the longest prefix wins, so a
+/// broad grant can be narrowed for a subset. Admin endpoints that aren't
+/// tied to one namespace need an `admin` grant with an empty prefix.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoleGrant {
+    #[serde(default)]
+    pub prefix: String,
+    pub role: Role,
+}
+
+/// What an API key may do in a namespace. Each role includes the ones
+/// below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Queries and reads of vectors and namespace metadata.
+    Read,
+    /// Upserts, patches and deletes of vectors.
+    Write,
+    /// Creating, updating, compacting and deleting namespaces, and the
+    /// admin endpoints.
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Read => "read",
+            Role::Write => "write",
+            Role::Admin => "admin",
+        }
+    }
+}
+
 // Default value functions
 fn default_host() -> String {
     std::env::var("ZEPPELIN_HOST").unwrap_or_else(|_| "0.0.0.0".to_string())
@@ -180,6 +536,12 @@ fn default_max_concurrent_queries() -> usize {
         .and_then(|v| v.parse().ok())
         .unwrap_or(64)
 }
+fn default_max_concurrent_upserts_per_ns() -> usize {
+    std::env::var("ZEPPELIN_MAX_CONCURRENT_UPSERTS_PER_NS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(64)
+}
 fn default_max_batch_size() -> usize {
     std::env::var("ZEPPELIN_MAX_BATCH_SIZE")
         .ok()
@@ -192,6 +554,12 @@ fn default_max_top_k() -> usize {
         .and_then(|v| v.parse().ok())
         .unwrap_or(10_000)
 }
+fn default_max_query_batch_size() -> usize {
+    std::env::var("ZEPPELIN_MAX_QUERY_BATCH_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(64)
+}
 fn default_shutdown_timeout_secs() -> u64 {
     std::env::var("ZEPPELIN_SHUTDOWN_TIMEOUT_SECS")
         .ok()
@@ -222,6 +590,9 @@ fn default_backend() -> String {
 fn default_bucket() -> String {
     std::env::var("S3_BUCKET").unwrap_or_else(|_| "zeppelin".to_string())
 }
+fn default_read_after_write_retries() -> u32 {
+    3
+}
 fn default_cache_dir() -> PathBuf {
     std::env::var("ZEPPELIN_CACHE_DIR")
         .ok()
@@ -234,6 +605,9 @@ fn default_max_size_gb() -> u64 {
         .and_then(|v| v.parse().ok())
         .unwrap_or(50)
 }
+fn default_coalesce_max_vectors() -> usize {
+    1000
+}
 fn default_eviction() -> String {
     "lru".to_string()
 }
@@ -265,11 +639,27 @@ fn default_pq_m() -> usize {
     8
 }
 fn default_rerank_factor() -> usize {
-    4
+    crate::index::quantization::DEFAULT_RERANK_FACTOR
 }
 fn default_beam_width() -> usize {
     10
 }
+
+fn default_vamana_max_degree() -> usize {
+    32
+}
+
+fn default_vamana_search_list_size() -> usize {
+    64
+}
+
+fn default_vamana_alpha() -> f32 {
+    1.2
+}
+
+fn default_vamana_block_size_bytes() -> usize {
+    256 * 1024
+}
 fn default_bitmap_index() -> bool {
     true
 }
@@ -305,12 +695,18 @@ impl Default for ServerConfig {
             port: default_port(),
             request_timeout_secs: default_request_timeout(),
             max_concurrent_queries: default_max_concurrent_queries(),
+            max_concurrent_upserts_per_ns: default_max_concurrent_upserts_per_ns(),
             max_batch_size: default_max_batch_size(),
             max_top_k: default_max_top_k(),
+            max_query_batch_size: default_max_query_batch_size(),
             shutdown_timeout_secs: default_shutdown_timeout_secs(),
             max_dimensions: default_max_dimensions(),
             max_vector_id_length: default_max_vector_id_length(),
+            vector_id_charset: VectorIdCharset::default(),
             max_request_body_mb: default_max_request_body_mb(),
+            json_case: JsonCase::default(),
+            write_rate_limit: RateLimit::default(),
+            query_rate_limit: RateLimit::default(),
         }
     }
 }
@@ -337,6 +733,17 @@ impl Default for StorageConfig {
             azure_access_key: std::env::var("AZURE_ACCESS_KEY")
                 .ok()
                 .filter(|s| !s.is_empty()),
+            read_after_write_retries: default_read_after_write_retries(),
+        }
+    }
+}
+
+impl Default for WalConfig {
+    fn default() -> Self {
+        Self {
+            layout: crate::wal::WalLayout::default(),
+            coalesce_window_ms: None,
+            coalesce_max_vectors: default_coalesce_max_vectors(),
         }
     }
 }
@@ -347,6 +754,7 @@ impl Default for CacheConfig {
             dir: default_cache_dir(),
             max_size_gb: default_max_size_gb(),
             eviction: default_eviction(),
+            pin_clusters: false,
         }
     }
 }
@@ -366,18 +774,69 @@ impl Default for IndexingConfig {
             hierarchical: false,
             beam_width: default_beam_width(),
             leaf_size: None,
+            vamana: false,
+            vamana_max_degree: default_vamana_max_degree(),
+            vamana_search_list_size: default_vamana_search_list_size(),
+            vamana_alpha: default_vamana_alpha(),
+            vamana_block_size_bytes: default_vamana_block_size_bytes(),
             bitmap_index: default_bitmap_index(),
             fts_index: false,
+            max_candidates_per_cluster: None,
+            kmeans_metric: None,
+            kmeans_seed: None,
+            adaptive_nprobe: false,
+            probe_gap_ratio: None,
         }
     }
 }
 
+impl IndexingConfig {
+    /// Settings used to build segments for a namespace of the given index type.
+    ///
+    /// `IvfFlat` defers to this config as-is, so the server-wide
+    /// `quantization`, `hierarchical` and `vamana` flags keep working for namespaces
+    /// that never chose a type. The other variants pin the build path.
+    pub fn for_index_type(&self, index_type: crate::types::IndexType) -> IndexingConfig {
+        use crate::index::quantization::QuantizationType;
+        use crate::types::IndexType;
+
+        let mut config = self.clone();
+        match index_type {
+            IndexType::IvfFlat => {}
+            IndexType::IvfSq => {
+                config.quantization = QuantizationType::Scalar;
+                config.hierarchical = false;
+                config.vamana = false;
+            }
+            IndexType::IvfPq => {
+                config.quantization = QuantizationType::Product;
+                config.hierarchical = false;
+                config.vamana = false;
+            }
+            IndexType::Hierarchical => {
+                config.hierarchical = true;
+                config.vamana = false;
+            }
+            IndexType::Vamana => {
+                config.vamana = true;
+                config.hierarchical = false;
+                config.quantization = QuantizationType::None;
+            }
+        }
+        config
+    }
+}
+
 impl Default for CompactionConfig {
     fn default() -> Self {
         Self {
             interval_secs: default_compaction_interval(),
             max_wal_fragments_before_compact: default_max_wal_fragments(),
+            max_fragment_age_secs: None,
             retrain_imbalance_threshold: default_retrain_threshold(),
+            cluster_by: ClusterBy::default(),
+            dedup_epsilon: None,
+            merge_factor: None,
         }
     }
 }
@@ -418,6 +877,27 @@ impl Config {
         Ok(config)
     }
 
+    /// Copy of this config with credentials replaced by a placeholder,
+    /// safe to expose over the admin API or in logs.
+    pub fn redacted(&self) -> Config {
+        const REDACTED: &str = "<redacted>";
+        let mut config = self.clone();
+        let storage = &mut config.storage;
+        for secret in [
+            &mut storage.s3_access_key_id,
+            &mut storage.s3_secret_access_key,
+            &mut storage.azure_access_key,
+        ] {
+            if secret.is_some() {
+                *secret = Some(REDACTED.to_string());
+            }
+        }
+        for api_key in &mut config.auth.api_keys {
+            api_key.key = REDACTED.to_string();
+        }
+        config
+    }
+
     /// Apply environment variable overrides on top of file/default values.
     /// This ensures env vars always take priority over TOML settings.
     fn apply_env_overrides(&mut self) {
@@ -443,6 +923,12 @@ impl Config {
         {
             self.server.max_concurrent_queries = v;
         }
+        if let Some(v) = std::env::var("ZEPPELIN_MAX_CONCURRENT_UPSERTS_PER_NS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            self.server.max_concurrent_upserts_per_ns = v;
+        }
         if let Some(v) = std::env::var("ZEPPELIN_MAX_BATCH_SIZE")
             .ok()
             .and_then(|v| v.parse().ok())
@@ -455,6 +941,12 @@ impl Config {
         {
             self.server.max_top_k = v;
         }
+        if let Some(v) = std::env::var("ZEPPELIN_MAX_QUERY_BATCH_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            self.server.max_query_batch_size = v;
+        }
         if let Some(v) = std::env::var("ZEPPELIN_SHUTDOWN_TIMEOUT_SECS")
             .ok()
             .and_then(|v| v.parse().ok())
@@ -473,12 +965,27 @@ impl Config {
         {
             self.server.max_vector_id_length = v;
         }
+        if let Ok(v) = std::env::var("ZEPPELIN_VECTOR_ID_CHARSET") {
+            match v.to_lowercase().as_str() {
+                "any" => self.server.vector_id_charset = VectorIdCharset::Any,
+                "printable" => self.server.vector_id_charset = VectorIdCharset::Printable,
+                "url_safe" => self.server.vector_id_charset = VectorIdCharset::UrlSafe,
+                _ => tracing::warn!("Unknown ZEPPELIN_VECTOR_ID_CHARSET value: {v}"),
+            }
+        }
         if let Some(v) = std::env::var("ZEPPELIN_MAX_REQUEST_BODY_MB")
             .ok()
             .and_then(|v| v.parse().ok())
         {
             self.server.max_request_body_mb = v;
         }
+        if let Ok(v) = std::env::var("ZEPPELIN_JSON_CASE") {
+            match v.to_lowercase().as_str() {
+                "snake" => self.server.json_case = JsonCase::Snake,
+                "camel" => self.server.json_case = JsonCase::Camel,
+                _ => tracing::warn!("Unknown ZEPPELIN_JSON_CASE value: {v}"),
+            }
+        }
 
         // Storage
         if let Ok(v) = std::env::var("STORAGE_BACKEND") {
@@ -520,6 +1027,12 @@ impl Config {
         {
             self.storage.azure_access_key = Some(v);
         }
+        if let Some(v) = std::env::var("ZEPPELIN_READ_AFTER_WRITE_RETRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            self.storage.read_after_write_retries = v;
+        }
 
         // Cache
         if let Ok(v) = std::env::var("ZEPPELIN_CACHE_DIR") {
@@ -531,6 +1044,9 @@ impl Config {
         {
             self.cache.max_size_gb = v;
         }
+        if let Ok(v) = std::env::var("ZEPPELIN_CACHE_PIN_CLUSTERS") {
+            self.cache.pin_clusters = v == "true";
+        }
 
         // Indexing
         if let Some(v) = std::env::var("ZEPPELIN_DEFAULT_NUM_CENTROIDS")
@@ -569,6 +1085,36 @@ impl Config {
         if let Ok(v) = std::env::var("ZEPPELIN_FTS_INDEX") {
             self.indexing.fts_index = v == "true";
         }
+        if let Some(v) = std::env::var("ZEPPELIN_MAX_CANDIDATES_PER_CLUSTER")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            self.indexing.max_candidates_per_cluster = Some(v);
+        }
+        if let Some(v) = std::env::var("ZEPPELIN_RERANK_FACTOR")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            self.indexing.rerank_factor = v;
+        }
+        if let Ok(v) = std::env::var("ZEPPELIN_ADAPTIVE_NPROBE") {
+            self.indexing.adaptive_nprobe = v == "true";
+        }
+        if let Some(v) = std::env::var("ZEPPELIN_PROBE_GAP_RATIO")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            self.indexing.probe_gap_ratio = Some(v);
+        }
+        if let Ok(v) = std::env::var("ZEPPELIN_KMEANS_METRIC") {
+            use crate::types::DistanceMetric;
+            match v.to_lowercase().as_str() {
+                "cosine" => self.indexing.kmeans_metric = Some(DistanceMetric::Cosine),
+                "euclidean" => self.indexing.kmeans_metric = Some(DistanceMetric::Euclidean),
+                "dot_product" => self.indexing.kmeans_metric = Some(DistanceMetric::DotProduct),
+                _ => tracing::warn!("Unknown ZEPPELIN_KMEANS_METRIC value: {v}"),
+            }
+        }
         // Hierarchical indexing
         if let Ok(v) = std::env::var("ZEPPELIN_HIERARCHICAL") {
             self.indexing.hierarchical = v == "true";
@@ -585,6 +1131,22 @@ impl Config {
         {
             self.indexing.leaf_size = Some(v);
         }
+        // Vamana graph indexing
+        if let Ok(v) = std::env::var("ZEPPELIN_VAMANA") {
+            self.indexing.vamana = v == "true";
+        }
+        if let Some(v) = std::env::var("ZEPPELIN_VAMANA_MAX_DEGREE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            self.indexing.vamana_max_degree = v;
+        }
+        if let Some(v) = std::env::var("ZEPPELIN_VAMANA_SEARCH_LIST_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            self.indexing.vamana_search_list_size = v;
+        }
 
         // Compaction
         if let Some(v) = std::env::var("ZEPPELIN_COMPACTION_INTERVAL_SECS")
@@ -599,10 +1161,64 @@ impl Config {
         {
             self.compaction.max_wal_fragments_before_compact = v;
         }
+        if let Some(v) = std::env::var("ZEPPELIN_MAX_FRAGMENT_AGE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            self.compaction.max_fragment_age_secs = Some(v);
+        }
+        if let Ok(v) = std::env::var("ZEPPELIN_COMPACTION_CLUSTER_BY") {
+            match v.parse() {
+                Ok(cluster_by) => self.compaction.cluster_by = cluster_by,
+                Err(e) => tracing::warn!("Ignoring ZEPPELIN_COMPACTION_CLUSTER_BY: {e}"),
+            }
+        }
+        if let Some(v) = std::env::var("ZEPPELIN_COMPACTION_DEDUP_EPSILON")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            self.compaction.dedup_epsilon = Some(v);
+        }
+        if let Some(v) = std::env::var("ZEPPELIN_COMPACTION_MERGE_FACTOR")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            self.compaction.merge_factor = Some(v);
+        }
 
         // Logging
         if let Ok(v) = std::env::var("ZEPPELIN_LOG_FORMAT") {
             self.logging.format = v;
         }
+
+        // WAL
+        if let Some(v) = std::env::var("ZEPPELIN_QUERY_SOFT_DEADLINE_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            self.query.soft_deadline_ms = Some(v);
+        }
+        if let Ok(v) = std::env::var("ZEPPELIN_QUERY_DEDUPE_BY_WRITE_TIME") {
+            self.query.dedupe_by_write_time = v == "true";
+        }
+        if let Ok(v) = std::env::var("ZEPPELIN_WAL_LAYOUT") {
+            match v.to_lowercase().as_str() {
+                "row" => self.wal.layout = crate::wal::WalLayout::Row,
+                "columnar" => self.wal.layout = crate::wal::WalLayout::Columnar,
+                _ => tracing::warn!("Unknown ZEPPELIN_WAL_LAYOUT value: {v}"),
+            }
+        }
+        if let Some(v) = std::env::var("ZEPPELIN_WAL_COALESCE_WINDOW_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            self.wal.coalesce_window_ms = Some(v);
+        }
+        if let Some(v) = std::env::var("ZEPPELIN_WAL_COALESCE_MAX_VECTORS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            self.wal.coalesce_max_vectors = v;
+        }
     }
 }
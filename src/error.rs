@@ -50,13 +50,46 @@ pub enum ZeppelinError {
     #[error("namespace already exists: {namespace}")]
     NamespaceAlreadyExists { namespace: String },
 
+    #[error("vector {id} not found in namespace {namespace}")]
+    VectorNotFound { namespace: String, id: String },
+
+    #[error("upsert condition failed in namespace {namespace} for ids {ids:?}")]
+    UpsertConditionFailed { namespace: String, ids: Vec<String> },
+
+    #[error(
+        "idempotency key {key:?} in namespace {namespace} was already used for a different request"
+    )]
+    IdempotencyKeyReused { namespace: String, key: String },
+
+    #[error("too many concurrent upserts to namespace {namespace} (limit {limit}), retry")]
+    TooManyConcurrentUpserts { namespace: String, limit: usize },
+
+    #[error("rate limit exceeded for namespace {namespace:?}, retry after {retry_after_secs}s")]
+    RateLimited {
+        namespace: String,
+        retry_after_secs: u64,
+    },
+
+    // Auth errors
+    #[error("missing or unknown API key")]
+    Unauthorized,
+
+    #[error("API key lacks the {role} role on namespace {namespace:?}")]
+    Forbidden { namespace: String, role: String },
+
     // Index errors
     #[error("index error: {0}")]
     Index(String),
 
+    #[error("index updating for namespace {namespace}, retry")]
+    IndexUpdating { namespace: String },
+
     #[error("k-means failed to converge after {iterations} iterations")]
     KMeansConvergence { iterations: usize },
 
+    #[error("coalesced WAL write failed: {0}")]
+    CoalescedWrite(String),
+
     // Validation errors
     #[error("dimension mismatch: expected {expected}, got {actual}")]
     DimensionMismatch { expected: usize, actual: usize },
@@ -64,6 +97,9 @@ pub enum ZeppelinError {
     #[error("validation error: {0}")]
     Validation(String),
 
+    #[error("unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+
     // Config errors
     #[error("config error: {0}")]
     Config(String),
@@ -97,18 +133,33 @@ impl ZeppelinError {
         match self {
             ZeppelinError::NotFound { .. }
             | ZeppelinError::NamespaceNotFound { .. }
-            | ZeppelinError::ManifestNotFound { .. } => 404,
+            | ZeppelinError::ManifestNotFound { .. }
+            | ZeppelinError::VectorNotFound { .. } => 404,
 
             ZeppelinError::NamespaceAlreadyExists { .. }
             | ZeppelinError::ManifestConflict { .. }
             | ZeppelinError::LeaseHeld { .. }
             | ZeppelinError::LeaseExpired { .. }
-            | ZeppelinError::FencingTokenStale { .. } => 409,
+            | ZeppelinError::FencingTokenStale { .. }
+            | ZeppelinError::UpsertConditionFailed { .. } => 409,
 
             ZeppelinError::DimensionMismatch { .. }
             | ZeppelinError::Validation(_)
             | ZeppelinError::FtsFieldNotConfigured { .. } => 400,
 
+            ZeppelinError::Unauthorized => 401,
+
+            ZeppelinError::Forbidden { .. } => 403,
+
+            ZeppelinError::UnsupportedMediaType(_) => 415,
+
+            ZeppelinError::IdempotencyKeyReused { .. } => 422,
+
+            ZeppelinError::TooManyConcurrentUpserts { .. } => 429,
+            ZeppelinError::RateLimited { .. } => 429,
+
+            ZeppelinError::IndexUpdating { .. } => 503,
+
             _ => 500,
         }
     }
@@ -142,6 +193,15 @@ mod tests {
         assert_eq!(err.status_code(), 404);
     }
 
+    #[test]
+    fn test_vector_not_found_status_code() {
+        let err = ZeppelinError::VectorNotFound {
+            namespace: "ns".into(),
+            id: "v1".into(),
+        };
+        assert_eq!(err.status_code(), 404);
+    }
+
     #[test]
     fn test_namespace_already_exists_status_code() {
         let err = ZeppelinError::NamespaceAlreadyExists {
@@ -150,6 +210,36 @@ mod tests {
         assert_eq!(err.status_code(), 409);
     }
 
+    #[test]
+    fn test_upsert_condition_failed_status_code() {
+        let err = ZeppelinError::UpsertConditionFailed {
+            namespace: "ns".into(),
+            ids: vec!["v1".into()],
+        };
+        assert_eq!(err.status_code(), 409);
+        assert!(err.to_string().contains("v1"));
+    }
+
+    #[test]
+    fn test_idempotency_key_reused_status_code() {
+        let err = ZeppelinError::IdempotencyKeyReused {
+            namespace: "ns".into(),
+            key: "req-1".into(),
+        };
+        assert_eq!(err.status_code(), 422);
+    }
+
+    #[test]
+    fn test_auth_status_codes() {
+        assert_eq!(ZeppelinError::Unauthorized.status_code(), 401);
+        let err = ZeppelinError::Forbidden {
+            namespace: "prod-events".into(),
+            role: "write".into(),
+        };
+        assert_eq!(err.status_code(), 403);
+        assert!(err.to_string().contains("write role"), "{err}");
+    }
+
     #[test]
     fn test_dimension_mismatch_status_code() {
         let err = ZeppelinError::DimensionMismatch {
@@ -165,6 +255,39 @@ mod tests {
         assert_eq!(err.status_code(), 400);
     }
 
+    #[test]
+    fn test_unsupported_media_type_status_code() {
+        let err = ZeppelinError::UnsupportedMediaType("text/plain".into());
+        assert_eq!(err.status_code(), 415);
+    }
+
+    #[test]
+    fn test_too_many_concurrent_upserts_status_code() {
+        let err = ZeppelinError::TooManyConcurrentUpserts {
+            namespace: "ns".into(),
+            limit: 4,
+        };
+        assert_eq!(err.status_code(), 429);
+    }
+
+    #[test]
+    fn test_rate_limited_status_code() {
+        let err = ZeppelinError::RateLimited {
+            namespace: "ns".into(),
+            retry_after_secs: 1,
+        };
+        assert_eq!(err.status_code(), 429);
+    }
+
+    #[test]
+    fn test_index_updating_status_code() {
+        let err = ZeppelinError::IndexUpdating {
+            namespace: "ns".into(),
+        };
+        assert_eq!(err.status_code(), 503);
+        assert!(err.to_string().contains("retry"));
+    }
+
     #[test]
     fn test_default_status_code() {
         let err = ZeppelinError::Bincode("bad data".into());
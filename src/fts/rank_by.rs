@@ -5,14 +5,19 @@
 //! ["content", "BM25", "search query"]                           // single field
 //! ["Sum", [["title", "BM25", "q"], ["content", "BM25", "q"]]]  // multi-field
 //! ["Product", 2.0, ["title", "BM25", "q"]]                     // weighted
+//! ["sparse", "DotProduct", {"indices": [3, 17], "values": [0.8, 0.2]}]
 //! ```
 //!
+//! The sparse form scores vectors' sparse representations by dot product
+//! and can't be combined with BM25 expressions.
+//!
 //! Custom Deserialize is used because these heterogeneous arrays
 //! cannot use `#[serde(untagged)]` with bincode (per learnings Bug 1).
 
 use serde::{Deserialize, Serialize};
 
 use crate::error::{Result, ZeppelinError};
+use crate::types::SparseVector;
 
 /// A rank_by expression for BM25 scoring.
 #[derive(Debug, Clone, PartialEq)]
@@ -25,6 +30,8 @@ pub enum RankBy {
     Max(Vec<RankBy>),
     /// Weighted expression: `["Product", weight, expr]`
     Product { weight: f32, expr: Box<RankBy> },
+    /// Sparse dot product: `["sparse", "DotProduct", {"indices": [...], "values": [...]}]`
+    Sparse(SparseVector),
 }
 
 impl RankBy {
@@ -84,6 +91,19 @@ impl RankBy {
                     expr: Box::new(expr),
                 })
             }
+            "sparse" if arr.get(1).and_then(|v| v.as_str()) == Some("DotProduct") => {
+                if arr.len() != 3 {
+                    return Err(ZeppelinError::Validation(
+                        "sparse expression requires 3 elements [\"sparse\", \"DotProduct\", vector]"
+                            .into(),
+                    ));
+                }
+                let query: SparseVector = serde_json::from_value(arr[2].clone()).map_err(|e| {
+                    ZeppelinError::Validation(format!("invalid sparse query vector: {e}"))
+                })?;
+                query.validate()?;
+                Ok(RankBy::Sparse(query))
+            }
             _ => {
                 // Assume it's a field-level BM25 expression: ["field", "BM25", "query"]
                 if arr.len() != 3 {
@@ -111,6 +131,24 @@ impl RankBy {
         }
     }
 
+    /// The query vector if this is a sparse expression.
+    pub fn sparse_query(&self) -> Option<&SparseVector> {
+        match self {
+            RankBy::Sparse(query) => Some(query),
+            _ => None,
+        }
+    }
+
+    /// Whether a sparse expression appears anywhere in this expression.
+    pub fn contains_sparse(&self) -> bool {
+        match self {
+            RankBy::Sparse(_) => true,
+            RankBy::Bm25 { .. } => false,
+            RankBy::Sum(exprs) | RankBy::Max(exprs) => exprs.iter().any(RankBy::contains_sparse),
+            RankBy::Product { expr, .. } => expr.contains_sparse(),
+        }
+    }
+
     /// Extract all unique (field, query) pairs from this expression.
     pub fn extract_field_queries(&self) -> Vec<(String, String)> {
         let mut result = Vec::new();
@@ -131,6 +169,7 @@ impl RankBy {
             RankBy::Product { expr, .. } => {
                 expr.collect_field_queries(out);
             }
+            RankBy::Sparse(_) => {}
         }
     }
 }
@@ -174,6 +213,9 @@ impl RankBy {
             RankBy::Product { weight, expr } => {
                 serde_json::json!(["Product", weight, expr.to_json_value()])
             }
+            RankBy::Sparse(query) => {
+                serde_json::json!(["sparse", "DotProduct", query])
+            }
         }
     }
 }
@@ -197,6 +239,8 @@ pub fn evaluate_rank_by(
             .map(|e| evaluate_rank_by(e, field_scores))
             .fold(0.0_f32, f32::max),
         RankBy::Product { weight, expr } => weight * evaluate_rank_by(expr, field_scores),
+        // Scored separately; see `query::execute_sparse_query`.
+        RankBy::Sparse(_) => 0.0,
     }
 }
 
@@ -217,6 +261,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_sparse() {
+        let json =
+            serde_json::json!(["sparse", "DotProduct", {"indices": [3, 17], "values": [0.5, 1.0]}]);
+        let rank_by = RankBy::from_value(&json).unwrap();
+        let query = rank_by.sparse_query().unwrap();
+        assert_eq!(query.indices, vec![3, 17]);
+        assert_eq!(serde_json::to_value(&rank_by).unwrap(), json);
+
+        let nested = serde_json::json!(["Sum", [json, ["content", "BM25", "q"]]]);
+        let rank_by = RankBy::from_value(&nested).unwrap();
+        assert!(rank_by.sparse_query().is_none());
+        assert!(rank_by.contains_sparse());
+
+        let bad = serde_json::json!(["sparse", "DotProduct", {"indices": [3], "values": []}]);
+        assert!(RankBy::from_value(&bad).is_err());
+        // A text field named "sparse" is still BM25.
+        let bm25 = serde_json::json!(["sparse", "BM25", "q"]);
+        assert!(!RankBy::from_value(&bm25).unwrap().contains_sparse());
+    }
+
     #[test]
     fn test_parse_sum_multi_field() {
         let json = serde_json::json!(["Sum", [["title", "BM25", "q"], ["content", "BM25", "q"]]]);
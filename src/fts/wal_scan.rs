@@ -254,6 +254,8 @@ pub fn wal_bm25_scan(
                 id: doc_id.clone(),
                 score: final_score,
                 attributes: attrs_opt.clone(),
+                rank: None,
+                values: None,
             });
         }
     }
@@ -289,6 +291,9 @@ mod tests {
             id: Ulid::new(),
             vectors,
             deletes,
+            patches: Vec::new(),
+            filter_patches: Vec::new(),
+            idempotency_key: None,
             checksum: 0,
         }
     }
@@ -303,6 +308,10 @@ mod tests {
             id: id.to_string(),
             values: vec![0.0],
             attributes: Some(attrs),
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         }
     }
 
@@ -409,6 +418,10 @@ mod tests {
                     id: "v1".to_string(),
                     values: vec![0.0],
                     attributes: Some(attrs),
+                    sparse: None,
+                    named_vectors: None,
+                    token_vectors: None,
+                    version: None,
                 }
             }],
             vec![],
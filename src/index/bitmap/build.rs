@@ -31,6 +31,10 @@ pub fn build_cluster_bitmaps(
         let pos = pos as u32;
         if let Some(attr_map) = attr_opt {
             for (field_name, value) in attr_map.iter() {
+                // Nested objects are not indexed; filters on them post-filter.
+                if matches!(value, AttributeValue::Object(_)) {
+                    continue;
+                }
                 let builder = field_data
                     .entry(field_name.clone())
                     .or_insert_with(FieldBuilder::new);
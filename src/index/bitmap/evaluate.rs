@@ -6,7 +6,7 @@
 //!
 //! `None` is returned when:
 //! - The field is not in the bitmap index
-//! - Contains is used on a String field (substring match)
+//! - Contains or ContainsAny is used on a String field (substring match)
 //! - A compound filter has a sub-filter that returns `None`
 
 use roaring::RoaringBitmap;
@@ -41,6 +41,28 @@ pub fn evaluate_filter_bitmap(
             Some(&universe - &eq_bitmap)
         }
 
+        Filter::ApproxEq {
+            field,
+            value,
+            tolerance,
+        } => {
+            let field_bitmaps = index.fields.get(field)?;
+            if field_bitmaps.is_list {
+                // Float list elements share the numeric keys — let the
+                // post-filter apply list membership instead.
+                return None;
+            }
+            let mut result = RoaringBitmap::new();
+            for (bits, key) in &field_bitmaps.sorted_numeric_keys {
+                if (f64::from_bits(*bits) - value).abs() <= *tolerance {
+                    if let Some(bm) = field_bitmaps.values.get(key) {
+                        result |= bm;
+                    }
+                }
+            }
+            Some(result)
+        }
+
         Filter::Range {
             field,
             gte,
@@ -147,8 +169,30 @@ pub fn evaluate_filter_bitmap(
             }
         }
 
+        Filter::ContainsAny { field, values } => {
+            let field_bitmaps = index.fields.get(field)?;
+            if !field_bitmaps.is_list {
+                return None;
+            }
+            let mut result = RoaringBitmap::new();
+            for v in values {
+                if let Some(bm) = field_bitmaps.values.get(&value_to_key(v)) {
+                    result |= bm;
+                }
+            }
+            Some(result)
+        }
+
+        // Pattern filters would need a scan over every distinct value — fall
+        // back to post-filter
+        Filter::StartsWith { .. } | Filter::Glob { .. } => None,
+
         // FTS token filters require tokenization — fall back to post-filter
         Filter::ContainsAllTokens { .. } | Filter::ContainsTokenSequence { .. } => None,
+
+        // Object values and empty lists have no bitmap entries, so presence
+        // can't be read off the value bitmaps — fall back to post-filter
+        Filter::Exists { .. } | Filter::NotExists { .. } => None,
     }
 }
 
@@ -431,6 +475,21 @@ mod tests {
         assert_eq!(bm_to_set(&result), vec![0, 1, 4]);
     }
 
+    #[test]
+    fn test_eval_contains_any_list() {
+        let index = build_test_index();
+        let filter = Filter::ContainsAny {
+            field: "tags".into(),
+            values: vec![
+                AttributeValue::String("a".into()),
+                AttributeValue::String("b".into()),
+            ],
+        };
+        let result = evaluate_filter_bitmap(&filter, &index).unwrap();
+        // tags with "a" or "b": every vector but 2
+        assert_eq!(bm_to_set(&result), vec![0, 1, 3, 4]);
+    }
+
     #[test]
     fn test_eval_contains_string_returns_none() {
         // Build index with a non-list string field
@@ -50,6 +50,8 @@ impl BitmapKey {
             AttributeValue::StringList(_) => BitmapKey("sl:list".to_string()),
             AttributeValue::IntegerList(_) => BitmapKey("il:list".to_string()),
             AttributeValue::FloatList(_) => BitmapKey("fl:list".to_string()),
+            // Objects are never indexed, so this key matches nothing.
+            AttributeValue::Object(_) => BitmapKey("o:object".to_string()),
         }
     }
 
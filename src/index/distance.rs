@@ -5,6 +5,13 @@
 
 use crate::types::DistanceMetric;
 
+/// Whether a result at `distance` with `id` ranks after `cursor`, a
+/// `(distance, id)` pair: it is farther, or as far with a greater ID.
+#[inline]
+pub fn ranks_after(distance: f32, id: &str, cursor: &(f32, String)) -> bool {
+    distance > cursor.0 || (distance == cursor.0 && id > cursor.1.as_str())
+}
+
 /// Dispatch to the appropriate distance function based on the metric.
 ///
 /// All distance functions return a *distance* (lower is closer) so that
@@ -18,6 +25,30 @@ pub fn compute_distance(a: &[f32], b: &[f32], metric: DistanceMetric) -> f32 {
     }
 }
 
+/// Compute distances from `query` to every vector in `vectors`, writing
+/// them into `out` (one slot per vector, same order).
+///
+/// The metric dispatch is hoisted out of the inner loop so a batch of
+/// stored vectors is scanned back-to-back against the same query. Results
+/// are bit-identical to calling [`compute_distance`] per vector.
+#[inline]
+pub fn compute_distances_batch<V: AsRef<[f32]>>(
+    query: &[f32],
+    vectors: &[V],
+    metric: DistanceMetric,
+    out: &mut [f32],
+) {
+    debug_assert_eq!(vectors.len(), out.len(), "output length must match batch");
+    let kernel: fn(&[f32], &[f32]) -> f32 = match metric {
+        DistanceMetric::Cosine => cosine_distance,
+        DistanceMetric::Euclidean => euclidean_distance,
+        DistanceMetric::DotProduct => dot_product_distance,
+    };
+    for (slot, v) in out.iter_mut().zip(vectors) {
+        *slot = kernel(query, v.as_ref());
+    }
+}
+
 /// Cosine distance: `1.0 - cosine_similarity(a, b)`.
 ///
 /// Returns 0.0 for identical directions and 2.0 for opposite directions.
@@ -51,7 +82,9 @@ pub fn euclidean_distance(a: &[f32], b: &[f32]) -> f32 {
 /// Dot-product distance: `-dot(a, b)`.
 ///
 /// Negated so that higher similarity (larger dot product) yields a
-/// smaller distance value, keeping the "lower is closer" invariant.
+/// smaller distance value, keeping the "lower is closer" invariant for
+/// index internals. Result scores report the dot product itself; see
+/// [`DistanceMetric::score_from_distance`].
 #[inline]
 pub fn dot_product_distance(a: &[f32], b: &[f32]) -> f32 {
     debug_assert_eq!(a.len(), b.len(), "vector dimensions must match");
@@ -479,6 +512,32 @@ mod tests {
         assert!((de - 2.0).abs() < 1e-6);
     }
 
+    #[test]
+    fn test_batch_matches_scalar() {
+        let dim = 37;
+        let query: Vec<f32> = (0..dim).map(|i| (i as f32 * 0.37).sin()).collect();
+        let vectors: Vec<Vec<f32>> = (0..50)
+            .map(|n| {
+                (0..dim)
+                    .map(|i| ((n * dim + i) as f32 * 0.11).cos())
+                    .collect()
+            })
+            .collect();
+
+        for metric in [
+            DistanceMetric::Cosine,
+            DistanceMetric::Euclidean,
+            DistanceMetric::DotProduct,
+        ] {
+            let mut batched = vec![0.0f32; vectors.len()];
+            compute_distances_batch(&query, &vectors, metric, &mut batched);
+            for (v, d) in vectors.iter().zip(&batched) {
+                let scalar = compute_distance(&query, v, metric);
+                assert_eq!(scalar.to_bits(), d.to_bits(), "metric {metric}");
+            }
+        }
+    }
+
     #[test]
     fn test_normalize() {
         let mut v = vec![3.0, 4.0];
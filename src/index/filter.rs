@@ -4,25 +4,66 @@
 //! metric-only.  To compensate for filtered-out results, callers should
 //! oversample by `config.oversample_factor` and then trim to `top_k`.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::types::{AttributeValue, Filter};
 
+/// Look up a filter field, following dot paths into nested objects.
+///
+/// An exact top-level key wins, so flat names that contain dots keep
+/// working. Otherwise `"a.b.c"` resolves `a`, then `b` inside it, and so on.
+pub fn resolve_field<'a>(
+    attributes: &'a HashMap<String, AttributeValue>,
+    field: &str,
+) -> Option<&'a AttributeValue> {
+    if let Some(value) = attributes.get(field) {
+        return Some(value);
+    }
+    let mut parts = field.split('.');
+    let mut current = attributes.get(parts.next()?)?;
+    for part in parts {
+        match current {
+            AttributeValue::Object(map) => current = map.get(part)?,
+            _ => return None,
+        }
+    }
+    Some(current)
+}
+
+/// Evaluate a filter against a row that may have no attributes. A row
+/// stored without attributes is evaluated as an empty map, so it fails
+/// `eq`/`exists` but passes `not_eq`/`not_exists`.
+pub fn matches_filter(
+    filter: &Filter,
+    attributes: Option<&HashMap<String, AttributeValue>>,
+) -> bool {
+    match attributes {
+        Some(attrs) => evaluate_filter(filter, attrs),
+        None => evaluate_filter(filter, &HashMap::new()),
+    }
+}
+
 /// Evaluate a filter predicate against a set of attributes.
 ///
-/// Returns `true` if the attributes satisfy the filter.
+/// Returns `true` if the attributes satisfy the filter. Field names may be
+/// dot paths into nested objects (see [`resolve_field`]).
 pub fn evaluate_filter(filter: &Filter, attributes: &HashMap<String, AttributeValue>) -> bool {
     match filter {
-        Filter::Eq { field, value } => attributes
-            .get(field)
+        Filter::Eq { field, value } => resolve_field(attributes, field)
             .map(|attr| attr_eq(attr, value))
             .unwrap_or(false),
 
-        Filter::NotEq { field, value } => attributes
-            .get(field)
+        Filter::NotEq { field, value } => resolve_field(attributes, field)
             .map(|attr| !attr_eq(attr, value))
             .unwrap_or(true),
 
+        Filter::ApproxEq {
+            field,
+            value,
+            tolerance,
+        } => resolve_field(attributes, field)
+            .is_some_and(|attr| attr_approx_eq(attr, *value, *tolerance)),
+
         Filter::Range {
             field,
             gte,
@@ -30,46 +71,24 @@ pub fn evaluate_filter(filter: &Filter, attributes: &HashMap<String, AttributeVa
             gt,
             lt,
         } => {
-            let Some(attr) = attributes.get(field) else {
+            let Some(attr) = resolve_field(attributes, field) else {
                 return false;
             };
-            let num = match attr_to_f64(attr) {
-                Some(n) => n,
-                None => return false,
-            };
-
-            if let Some(min) = gte {
-                if num < *min {
-                    return false;
-                }
-            }
-            if let Some(max) = lte {
-                if num > *max {
-                    return false;
-                }
+            match attr_to_f64(attr) {
+                Some(num) => in_range(num, *gte, *lte, *gt, *lt),
+                None => false,
             }
-            if let Some(min) = gt {
-                if num <= *min {
-                    return false;
-                }
-            }
-            if let Some(max) = lt {
-                if num >= *max {
-                    return false;
-                }
-            }
-            true
         }
 
         Filter::In { field, values } => {
-            let Some(attr) = attributes.get(field) else {
+            let Some(attr) = resolve_field(attributes, field) else {
                 return false;
             };
             values.iter().any(|v| attr_eq(attr, v))
         }
 
         Filter::NotIn { field, values } => {
-            let Some(attr) = attributes.get(field) else {
+            let Some(attr) = resolve_field(attributes, field) else {
                 return true;
             };
             !values.iter().any(|v| attr_eq(attr, v))
@@ -81,15 +100,32 @@ pub fn evaluate_filter(filter: &Filter, attributes: &HashMap<String, AttributeVa
 
         Filter::Not { filter } => !evaluate_filter(filter, attributes),
 
+        Filter::Exists { field } => resolve_field(attributes, field).is_some(),
+
+        Filter::NotExists { field } => resolve_field(attributes, field).is_none(),
+
         Filter::Contains { field, value } => {
-            let Some(attr) = attributes.get(field) else {
+            let Some(attr) = resolve_field(attributes, field) else {
                 return false;
             };
             attr_contains(attr, value)
         }
 
+        Filter::ContainsAny { field, values } => {
+            let Some(attr) = resolve_field(attributes, field) else {
+                return false;
+            };
+            values.iter().any(|v| attr_contains(attr, v))
+        }
+
+        Filter::StartsWith { field, prefix } => resolve_field(attributes, field)
+            .is_some_and(|attr| attr_any_str(attr, |s| s.starts_with(prefix.as_str()))),
+
+        Filter::Glob { field, pattern } => resolve_field(attributes, field)
+            .is_some_and(|attr| attr_any_str(attr, |s| glob_match(pattern, s))),
+
         Filter::ContainsAllTokens { field, tokens } => {
-            let Some(attr) = attributes.get(field) else {
+            let Some(attr) = resolve_field(attributes, field) else {
                 return false;
             };
             let text = match attr {
@@ -109,7 +145,7 @@ pub fn evaluate_filter(filter: &Filter, attributes: &HashMap<String, AttributeVa
         }
 
         Filter::ContainsTokenSequence { field, tokens } => {
-            let Some(attr) = attributes.get(field) else {
+            let Some(attr) = resolve_field(attributes, field) else {
                 return false;
             };
             let text = match attr {
@@ -135,6 +171,133 @@ pub fn evaluate_filter(filter: &Filter, attributes: &HashMap<String, AttributeVa
     }
 }
 
+/// Evaluate a filter against every row of a cluster's attribute sidecar,
+/// returning one selection flag per row.
+///
+/// Single-field `Eq`/`Range`/`In` filters gather the field into a column and
+/// go through [`evaluate_filter_column`]; compound filters fall back to
+/// [`matches_filter`] per row, so rows without attributes are evaluated as
+/// empty maps.
+pub fn evaluate_filter_rows(
+    filter: &Filter,
+    rows: &[Option<HashMap<String, AttributeValue>>],
+) -> Vec<bool> {
+    if let Filter::Eq { field, .. } | Filter::Range { field, .. } | Filter::In { field, .. } =
+        filter
+    {
+        let column: Vec<Option<&AttributeValue>> = rows
+            .iter()
+            .map(|row| row.as_ref().and_then(|attrs| resolve_field(attrs, field)))
+            .collect();
+        if let Some(selection) = evaluate_filter_column(filter, &column) {
+            return selection;
+        }
+    }
+    rows.iter()
+        .map(|row| matches_filter(filter, row.as_ref()))
+        .collect()
+}
+
+/// Evaluate a single-field `Eq`/`Range`/`In` filter over a whole attribute
+/// column at once. `column[i]` is row `i`'s value for the filter's field.
+///
+/// The predicate is specialised before the loop (string equality, a string
+/// set for `In`, numeric bounds for `Range`), so no per-row map lookups or
+/// filter dispatch happen. Returns `None` for any other filter.
+pub fn evaluate_filter_column(
+    filter: &Filter,
+    column: &[Option<&AttributeValue>],
+) -> Option<Vec<bool>> {
+    let selection = match filter {
+        Filter::Eq {
+            value: AttributeValue::String(target),
+            ..
+        } => column
+            .iter()
+            .map(|attr| match attr {
+                Some(AttributeValue::String(s)) => s == target,
+                Some(AttributeValue::StringList(list)) => list.contains(target),
+                _ => false,
+            })
+            .collect(),
+
+        Filter::Eq { value, .. } => column
+            .iter()
+            .map(|attr| attr.is_some_and(|a| attr_eq(a, value)))
+            .collect(),
+
+        Filter::Range {
+            gte, lte, gt, lt, ..
+        } => column
+            .iter()
+            .map(|attr| {
+                attr.and_then(attr_to_f64)
+                    .is_some_and(|num| in_range(num, *gte, *lte, *gt, *lt))
+            })
+            .collect(),
+
+        Filter::In { values, .. } => {
+            let strings: Option<HashSet<&str>> = values
+                .iter()
+                .map(|v| match v {
+                    AttributeValue::String(s) => Some(s.as_str()),
+                    _ => None,
+                })
+                .collect();
+            match strings {
+                Some(set) => column
+                    .iter()
+                    .map(|attr| match attr {
+                        Some(AttributeValue::String(s)) => set.contains(s.as_str()),
+                        Some(AttributeValue::StringList(list)) => {
+                            list.iter().any(|s| set.contains(s.as_str()))
+                        }
+                        _ => false,
+                    })
+                    .collect(),
+                None => column
+                    .iter()
+                    .map(|attr| attr.is_some_and(|a| values.iter().any(|v| attr_eq(a, v))))
+                    .collect(),
+            }
+        }
+
+        _ => return None,
+    };
+    Some(selection)
+}
+
+/// Check a number against optional range bounds (all present bounds must hold).
+fn in_range(
+    num: f64,
+    gte: Option<f64>,
+    lte: Option<f64>,
+    gt: Option<f64>,
+    lt: Option<f64>,
+) -> bool {
+    if let Some(min) = gte {
+        if num < min {
+            return false;
+        }
+    }
+    if let Some(max) = lte {
+        if num > max {
+            return false;
+        }
+    }
+    if let Some(min) = gt {
+        if num <= min {
+            return false;
+        }
+    }
+    if let Some(max) = lt {
+        if num >= max {
+            return false;
+        }
+    }
+    true
+}
+
 /// Compare two `AttributeValue`s for equality.
 fn attr_eq(a: &AttributeValue, b: &AttributeValue) -> bool {
     match (a, b) {
@@ -162,6 +325,16 @@ fn attr_eq(a: &AttributeValue, b: &AttributeValue) -> bool {
     }
 }
 
+/// Compare a numeric attribute to `value` within an absolute `tolerance`.
+/// Float lists match if any element is within tolerance; non-numeric
+/// attributes never match.
+fn attr_approx_eq(attr: &AttributeValue, value: f64, tolerance: f64) -> bool {
+    match attr {
+        AttributeValue::FloatList(list) => list.iter().any(|v| (v - value).abs() <= tolerance),
+        _ => attr_to_f64(attr).is_some_and(|num| (num - value).abs() <= tolerance),
+    }
+}
+
 /// Check if an attribute value contains another value.
 ///
 /// For list types, checks element membership. For strings, checks substring.
@@ -179,6 +352,48 @@ fn attr_contains(attr: &AttributeValue, value: &AttributeValue) -> bool {
     }
 }
 
+/// Whether a string attribute, or any element of a string list, satisfies
+/// `pred`. Non-string attributes never match.
+fn attr_any_str(attr: &AttributeValue, pred: impl Fn(&str) -> bool) -> bool {
+    match attr {
+        AttributeValue::String(s) => pred(s),
+        AttributeValue::StringList(list) => list.iter().any(|s| pred(s)),
+        _ => false,
+    }
+}
+
+/// Match `text` against a shell-style pattern where `*` matches any run of
+/// characters and `?` exactly one. Greedy with single-star backtracking, so
+/// linear in practice and never exponential.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` seen and the text position it currently covers up to.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((sp, st)) => {
+                    p = sp + 1;
+                    t = st + 1;
+                    star = Some((sp, st + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
 /// Extract a numeric value from an `AttributeValue`.
 fn attr_to_f64(attr: &AttributeValue) -> Option<f64> {
     match attr {
@@ -199,6 +414,48 @@ pub fn oversampled_k(top_k: usize, oversample_factor: usize) -> usize {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_nested_field_path() {
+        let attrs: HashMap<String, AttributeValue> = serde_json::from_value(serde_json::json!({
+            "metadata": {"author": "alice", "stats": {"pages": 120}},
+            "flat.key": "dotted",
+        }))
+        .unwrap();
+
+        let author = |name: &str| Filter::Eq {
+            field: "metadata.author".to_string(),
+            value: AttributeValue::String(name.to_string()),
+        };
+        assert!(evaluate_filter(&author("alice"), &attrs));
+        assert!(!evaluate_filter(&author("bob"), &attrs));
+
+        let pages = Filter::Range {
+            field: "metadata.stats.pages".to_string(),
+            gte: Some(100.0),
+            lte: None,
+            gt: None,
+            lt: None,
+        };
+        assert!(evaluate_filter(&pages, &attrs));
+
+        let missing = Filter::Eq {
+            field: "metadata.author.name".to_string(),
+            value: AttributeValue::String("alice".to_string()),
+        };
+        assert!(!evaluate_filter(&missing, &attrs));
+
+        // Flat keys containing dots still match exactly.
+        let flat = Filter::Eq {
+            field: "flat.key".to_string(),
+            value: AttributeValue::String("dotted".to_string()),
+        };
+        assert!(evaluate_filter(&flat, &attrs));
+        assert_eq!(
+            evaluate_filter_rows(&author("alice"), &[Some(attrs), None]),
+            vec![true, false]
+        );
+    }
+
     fn make_attrs() -> HashMap<String, AttributeValue> {
         let mut m = HashMap::new();
         m.insert(
@@ -387,6 +644,40 @@ mod tests {
         assert!(!evaluate_filter(&f2, &attrs)); // red != red → false
     }
 
+    #[test]
+    fn test_approx_eq_float_tolerance() {
+        let mut attrs = HashMap::new();
+        attrs.insert("price".to_string(), AttributeValue::Float(19.990000001));
+        let approx = |value: f64, tolerance: f64| Filter::ApproxEq {
+            field: "price".into(),
+            value,
+            tolerance,
+        };
+
+        // Exact `eq` misses the representation error; `approx_eq` absorbs it.
+        let exact = Filter::Eq {
+            field: "price".into(),
+            value: AttributeValue::Float(19.99),
+        };
+        assert!(!evaluate_filter(&exact, &attrs));
+        assert!(evaluate_filter(&approx(19.99, 1e-6), &attrs));
+        assert!(!evaluate_filter(&approx(19.98, 1e-6), &attrs));
+        assert!(evaluate_filter(&approx(19.98, 0.02), &attrs));
+
+        // Missing field never matches.
+        let missing = Filter::ApproxEq {
+            field: "weight".into(),
+            value: 19.99,
+            tolerance: 1.0,
+        };
+        assert!(!evaluate_filter(&missing, &attrs));
+
+        // Omitted tolerance falls back to the default.
+        let parsed: Filter =
+            serde_json::from_str(r#"{"op":"approx_eq","field":"price","value":19.99}"#).unwrap();
+        assert!(evaluate_filter(&parsed, &attrs));
+    }
+
     #[test]
     fn test_not_eq_missing_field() {
         let attrs = make_attrs();
@@ -472,6 +763,38 @@ mod tests {
         assert!(!evaluate_filter(&f2, &attrs)); // NOT (color == red) → false
     }
 
+    #[test]
+    fn test_exists_and_not_exists() {
+        let mut attrs = make_attrs();
+        attrs.insert(
+            "meta".to_string(),
+            AttributeValue::Object(HashMap::from([(
+                "source".to_string(),
+                AttributeValue::String("crawl".to_string()),
+            )])),
+        );
+        for (field, present) in [
+            ("color", true),
+            ("tags", true),
+            ("meta.source", true),
+            ("archived", false),
+            ("meta.author", false),
+        ] {
+            let exists = Filter::Exists {
+                field: field.into(),
+            };
+            let not_exists = Filter::NotExists {
+                field: field.into(),
+            };
+            assert_eq!(evaluate_filter(&exists, &attrs), present, "{field}");
+            assert_eq!(evaluate_filter(&not_exists, &attrs), !present, "{field}");
+        }
+
+        let parsed: Filter =
+            serde_json::from_str(r#"{"op":"not_exists","field":"archived"}"#).unwrap();
+        assert!(evaluate_filter(&parsed, &attrs));
+    }
+
     #[test]
     fn test_contains_string_list() {
         let attrs = make_attrs();
@@ -530,6 +853,99 @@ mod tests {
         assert!(!evaluate_filter(&f2, &attrs));
     }
 
+    #[test]
+    fn test_contains_any() {
+        let attrs = make_attrs();
+        let f = Filter::ContainsAny {
+            field: "tags".into(),
+            values: vec![
+                AttributeValue::String("c".into()),
+                AttributeValue::String("b".into()),
+            ],
+        };
+        assert!(evaluate_filter(&f, &attrs));
+
+        let f2 = Filter::ContainsAny {
+            field: "tags".into(),
+            values: vec![
+                AttributeValue::String("c".into()),
+                AttributeValue::String("d".into()),
+            ],
+        };
+        assert!(!evaluate_filter(&f2, &attrs));
+
+        let f3 = Filter::ContainsAny {
+            field: "scores".into(),
+            values: vec![AttributeValue::Integer(99), AttributeValue::Integer(30)],
+        };
+        assert!(evaluate_filter(&f3, &attrs));
+
+        let empty = Filter::ContainsAny {
+            field: "tags".into(),
+            values: vec![],
+        };
+        assert!(!evaluate_filter(&empty, &attrs));
+    }
+
+    #[test]
+    fn test_starts_with() {
+        let attrs = make_attrs();
+        let f = Filter::StartsWith {
+            field: "description".into(),
+            prefix: "a red".into(),
+        };
+        assert!(evaluate_filter(&f, &attrs));
+
+        let f2 = Filter::StartsWith {
+            field: "description".into(),
+            prefix: "red".into(),
+        };
+        assert!(!evaluate_filter(&f2, &attrs));
+
+        // Any element of a string list; never a non-string attribute.
+        let f3 = Filter::StartsWith {
+            field: "tags".into(),
+            prefix: "b".into(),
+        };
+        assert!(evaluate_filter(&f3, &attrs));
+        let f4 = Filter::StartsWith {
+            field: "size".into(),
+            prefix: "4".into(),
+        };
+        assert!(!evaluate_filter(&f4, &attrs));
+    }
+
+    #[test]
+    fn test_glob() {
+        let cases = [
+            (
+                "https://*.example.com/*",
+                "https://docs.example.com/a/b",
+                true,
+            ),
+            ("https://*.example.com/*", "https://example.com/a", false),
+            ("doc-??", "doc-42", true),
+            ("doc-??", "doc-420", false),
+            ("*", "", true),
+            ("", "", true),
+            ("", "x", false),
+            ("a*b*c", "aXXbYYbZZc", true),
+            ("a*b*c", "aXXbYYbZZ", false),
+            ("*.rs", "main.rs.bak", false),
+            ("caf?", "café", true),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(glob_match(pattern, text), expected, "{pattern} vs {text}");
+        }
+
+        let attrs = make_attrs();
+        let f = Filter::Glob {
+            field: "description".into(),
+            pattern: "*widget*".into(),
+        };
+        assert!(evaluate_filter(&f, &attrs));
+    }
+
     #[test]
     fn test_integer_list_eq() {
         let attrs = make_attrs();
@@ -569,4 +985,124 @@ mod tests {
         };
         assert!(evaluate_filter(&f, &attrs)); // First branch matches
     }
+
+    #[test]
+    fn test_column_selection_matches_evaluate_filter() {
+        // Mixed-type rows, including rows with no attributes or missing fields.
+        let rows: Vec<Option<HashMap<String, AttributeValue>>> = (0..60)
+            .map(|i| {
+                if i % 7 == 0 {
+                    return None;
+                }
+                let mut m = HashMap::new();
+                let color = match i % 4 {
+                    0 => AttributeValue::String("red".into()),
+                    1 => AttributeValue::String("blue".into()),
+                    2 => AttributeValue::StringList(vec!["red".into(), "green".into()]),
+                    _ => AttributeValue::Integer(i),
+                };
+                m.insert("color".to_string(), color);
+                if i % 5 != 0 {
+                    let size = if i % 2 == 0 {
+                        AttributeValue::Integer(i)
+                    } else {
+                        AttributeValue::Float(i as f64 + 0.5)
+                    };
+                    m.insert("size".to_string(), size);
+                }
+                Some(m)
+            })
+            .collect();
+
+        let filters = vec![
+            Filter::Eq {
+                field: "color".into(),
+                value: AttributeValue::String("red".into()),
+            },
+            Filter::Eq {
+                field: "size".into(),
+                value: AttributeValue::Integer(12),
+            },
+            Filter::Eq {
+                field: "missing".into(),
+                value: AttributeValue::String("red".into()),
+            },
+            Filter::Range {
+                field: "size".into(),
+                gte: Some(10.0),
+                lte: None,
+                gt: None,
+                lt: Some(40.0),
+            },
+            Filter::Range {
+                field: "color".into(),
+                gte: None,
+                lte: Some(30.0),
+                gt: Some(3.0),
+                lt: None,
+            },
+            Filter::In {
+                field: "color".into(),
+                values: vec![
+                    AttributeValue::String("blue".into()),
+                    AttributeValue::String("green".into()),
+                ],
+            },
+            Filter::In {
+                field: "size".into(),
+                values: vec![AttributeValue::Integer(4), AttributeValue::Float(9.5)],
+            },
+            // Compound filters take the generic fallback.
+            Filter::And {
+                filters: vec![
+                    Filter::Eq {
+                        field: "color".into(),
+                        value: AttributeValue::String("red".into()),
+                    },
+                    Filter::NotEq {
+                        field: "size".into(),
+                        value: AttributeValue::Integer(8),
+                    },
+                ],
+            },
+        ];
+
+        for f in &filters {
+            let expected: Vec<bool> = rows.iter().map(|r| matches_filter(f, r.as_ref())).collect();
+            assert_eq!(evaluate_filter_rows(f, &rows), expected, "filter: {f:?}");
+        }
+
+        let compound = filters.last().unwrap();
+        assert!(evaluate_filter_column(compound, &[]).is_none());
+    }
+
+    #[test]
+    fn test_rows_without_attributes_match_as_empty() {
+        let tag = || "tag".to_string();
+        assert!(matches_filter(&Filter::NotExists { field: tag() }, None));
+        assert!(!matches_filter(&Filter::Exists { field: tag() }, None));
+        assert!(matches_filter(
+            &Filter::NotEq {
+                field: tag(),
+                value: AttributeValue::Integer(1),
+            },
+            None
+        ));
+        assert!(!matches_filter(
+            &Filter::Eq {
+                field: tag(),
+                value: AttributeValue::Integer(1),
+            },
+            None
+        ));
+        assert_eq!(
+            evaluate_filter_rows(
+                &Filter::Not {
+                    filter: Box::new(Filter::Exists { field: tag() }),
+                },
+                &[None],
+            ),
+            [true]
+        );
+    }
 }
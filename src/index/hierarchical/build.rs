@@ -6,7 +6,7 @@
 //! it becomes a leaf cluster with data written in IVF-Flat format.
 
 use bytes::Bytes;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use tracing::{debug, info};
 use ulid::Ulid;
 
@@ -15,7 +15,7 @@ use crate::error::{Result, ZeppelinError};
 use crate::index::distance;
 use crate::index::ivf_flat::build::{attrs_key, cluster_key, serialize_attrs, serialize_cluster};
 use crate::index::ivf_flat::kmeans::train_kmeans;
-use crate::index::quantization::QuantizationType;
+use crate::index::quantization::{QuantizationType, DEFAULT_RERANK_FACTOR};
 use crate::storage::ZeppelinStore;
 use crate::types::{AttributeValue, VectorEntry};
 
@@ -184,6 +184,12 @@ pub async fn build_hierarchical(
         namespace: namespace.to_string(),
         segment_id: segment_id.to_string(),
         bitmap_fields,
+        skip_attributes: false,
+        max_distance: None,
+        after: None,
+        tombstones: BTreeSet::new(),
+        include_vectors: false,
+        rerank_factor: DEFAULT_RERANK_FACTOR,
     })
 }
 
@@ -527,5 +533,11 @@ pub async fn load_hierarchical(
         namespace: namespace.to_string(),
         segment_id: segment_id.to_string(),
         bitmap_fields: Vec::new(), // Populated from SegmentRef at search time
+        skip_attributes: false,
+        max_distance: None,
+        after: None,
+        tombstones: BTreeSet::new(),
+        include_vectors: false,
+        rerank_factor: DEFAULT_RERANK_FACTOR,
     })
 }
@@ -11,6 +11,8 @@
 pub mod build;
 pub mod search;
 
+use std::collections::BTreeSet;
+
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
@@ -69,6 +71,23 @@ pub struct HierarchicalIndex {
     pub(crate) segment_id: String,
     /// Fields that have bitmap indexes.
     pub(crate) bitmap_fields: Vec<String>,
+    /// Skip attribute fetches for unfiltered searches; results then carry
+    /// no attributes. Set by the query path.
+    pub(crate) skip_attributes: bool,
+    /// Quantized leaf scans rerank `fetch_k * rerank_factor` candidates
+    /// with full-precision vectors. Set by the query path.
+    pub(crate) rerank_factor: usize,
+    /// Drop results whose internal distance exceeds this bound. Set by the
+    /// query path.
+    pub(crate) max_distance: Option<f32>,
+    /// Return each result's stored vector. Set by the query path.
+    pub(crate) include_vectors: bool,
+    /// Keep only results ranking after this `(distance, id)` cursor. Set
+    /// by the query path.
+    pub(crate) after: Option<(f32, String)>,
+    /// IDs tombstoned in this segment, dropped before top-k selection. Set
+    /// by the query path.
+    pub(crate) tombstones: BTreeSet<String>,
 }
 
 // ---------------------------------------------------------------------------
@@ -4,15 +4,15 @@
 //! candidates at each level. At the leaf level, scans data clusters
 //! (identical to IVF-Flat scan) with optional quantized two-phase search.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
 
 use tracing::{debug, warn};
 
 use crate::cache::DiskCache;
 use crate::error::{Result, ZeppelinError};
-use crate::index::distance::compute_distance;
-use crate::index::filter::{evaluate_filter, oversampled_k};
+use crate::index::distance::{compute_distance, ranks_after};
+use crate::index::filter::{matches_filter, oversampled_k};
 use crate::index::ivf_flat::build::{
     attrs_key, cluster_key, deserialize_attrs, deserialize_cluster,
 };
@@ -30,6 +30,7 @@ struct Candidate {
     id: String,
     score: f32,
     attributes: Option<HashMap<String, AttributeValue>>,
+    values: Option<Vec<f32>>,
 }
 
 /// Fetch data from cache or S3.
@@ -201,11 +202,7 @@ pub async fn search_hierarchical(
 
         if internal_ids.is_empty() {
             // No more internal nodes to descend — return merged results.
-            accumulated.sort_by(|a, b| {
-                a.score
-                    .partial_cmp(&b.score)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            });
+            accumulated.sort_by(|a, b| distance_metric.compare_scores(a.score, b.score));
             accumulated.truncate(top_k);
             return Ok(accumulated);
         }
@@ -250,7 +247,11 @@ async fn scan_leaf_clusters(
                 distance_metric,
                 filter,
                 fetch_k,
+                index.rerank_factor,
                 has_bitmaps,
+                index.skip_attributes,
+                index.include_vectors,
+                &index.tombstones,
                 store,
                 cache,
             )
@@ -265,7 +266,11 @@ async fn scan_leaf_clusters(
                 distance_metric,
                 filter,
                 fetch_k,
+                index.rerank_factor,
                 has_bitmaps,
+                index.skip_attributes,
+                index.include_vectors,
+                &index.tombstones,
                 store,
                 cache,
             )
@@ -280,6 +285,8 @@ async fn scan_leaf_clusters(
                 distance_metric,
                 filter,
                 has_bitmaps,
+                index.skip_attributes,
+                index.include_vectors,
                 store,
                 cache,
             )
@@ -298,20 +305,29 @@ async fn scan_leaf_clusters(
         a.score
             .partial_cmp(&b.score)
             .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.id.cmp(&b.id))
     });
+    if !index.tombstones.is_empty() {
+        sorted.retain(|c| !index.tombstones.contains(&c.id));
+    }
+    if let Some(max) = index.max_distance {
+        sorted.retain(|c| c.score <= max);
+    }
+    if let Some(cursor) = &index.after {
+        sorted.retain(|c| ranks_after(c.score, &c.id, cursor));
+    }
 
     let results: Vec<SearchResult> = if let Some(f) = filter {
         sorted
             .into_iter()
-            .filter(|c| match &c.attributes {
-                Some(attrs) => evaluate_filter(f, attrs),
-                None => false,
-            })
+            .filter(|c| matches_filter(f, c.attributes.as_ref()))
             .take(top_k)
             .map(|c| SearchResult {
                 id: c.id,
-                score: c.score,
+                score: distance_metric.score_from_distance(c.score),
                 attributes: c.attributes,
+                rank: None,
+                values: c.values,
             })
             .collect()
     } else {
@@ -320,8 +336,10 @@ async fn scan_leaf_clusters(
             .take(top_k)
             .map(|c| SearchResult {
                 id: c.id,
-                score: c.score,
+                score: distance_metric.score_from_distance(c.score),
                 attributes: c.attributes,
+                rank: None,
+                values: c.values,
             })
             .collect()
     };
@@ -343,6 +361,8 @@ async fn scan_clusters_flat(
     distance_metric: DistanceMetric,
     filter: Option<&Filter>,
     has_bitmaps: bool,
+    skip_attributes: bool,
+    include_vectors: bool,
     store: &ZeppelinStore,
     cache: Option<&Arc<DiskCache>>,
 ) -> Result<Vec<Candidate>> {
@@ -361,7 +381,15 @@ async fn scan_clusters_flat(
                     store,
                     cache,
                 ),
-                load_attrs(namespace, segment_id, cluster_idx, filter, store, cache),
+                load_attrs(
+                    namespace,
+                    segment_id,
+                    cluster_idx,
+                    filter,
+                    skip_attributes,
+                    store,
+                    cache,
+                ),
             );
             (cluster_idx, cluster_res, prefilter, attrs)
         }
@@ -392,6 +420,7 @@ async fn scan_clusters_flat(
                 id: cluster.ids[j].clone(),
                 score,
                 attributes: vector_attrs,
+                values: include_vectors.then(|| vec.clone()),
             });
         }
     }
@@ -409,7 +438,11 @@ async fn scan_clusters_sq(
     distance_metric: DistanceMetric,
     filter: Option<&Filter>,
     fetch_k: usize,
+    rerank_factor: usize,
     has_bitmaps: bool,
+    skip_attributes: bool,
+    include_vectors: bool,
+    tombstones: &BTreeSet<String>,
     store: &ZeppelinStore,
     cache: Option<&Arc<DiskCache>>,
 ) -> Result<Vec<Candidate>> {
@@ -477,7 +510,8 @@ async fn scan_clusters_sq(
     }
 
     coarse.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
-    let rerank_count = fetch_k * 4;
+    coarse.retain(|c| !tombstones.contains(&c.0));
+    let rerank_count = fetch_k * rerank_factor;
     coarse.truncate(rerank_count);
 
     debug!(
@@ -498,7 +532,15 @@ async fn scan_clusters_sq(
             async move {
                 let (cluster_res, attrs) = tokio::join!(
                     fetch_with_cache(cache, store, &cvec_key),
-                    load_attrs(namespace, segment_id, cluster_idx, filter, store, cache),
+                    load_attrs(
+                        namespace,
+                        segment_id,
+                        cluster_idx,
+                        filter,
+                        skip_attributes,
+                        store,
+                        cache,
+                    ),
                 );
                 (needed_ids, cluster_res, attrs)
             }
@@ -523,6 +565,7 @@ async fn scan_clusters_sq(
                     id: id.clone(),
                     score,
                     attributes: vector_attrs,
+                    values: include_vectors.then(|| cluster.vectors[j].clone()),
                 });
             }
         }
@@ -541,7 +584,11 @@ async fn scan_clusters_pq(
     distance_metric: DistanceMetric,
     filter: Option<&Filter>,
     fetch_k: usize,
+    rerank_factor: usize,
     has_bitmaps: bool,
+    skip_attributes: bool,
+    include_vectors: bool,
+    tombstones: &BTreeSet<String>,
     store: &ZeppelinStore,
     cache: Option<&Arc<DiskCache>>,
 ) -> Result<Vec<Candidate>> {
@@ -598,7 +645,8 @@ async fn scan_clusters_pq(
     }
 
     coarse.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
-    let rerank_count = fetch_k * 4;
+    coarse.retain(|c| !tombstones.contains(&c.0));
+    let rerank_count = fetch_k * rerank_factor;
     coarse.truncate(rerank_count);
 
     debug!(
@@ -619,7 +667,15 @@ async fn scan_clusters_pq(
             async move {
                 let (cluster_res, attrs) = tokio::join!(
                     fetch_with_cache(cache, store, &cvec_key),
-                    load_attrs(namespace, segment_id, cluster_idx, filter, store, cache),
+                    load_attrs(
+                        namespace,
+                        segment_id,
+                        cluster_idx,
+                        filter,
+                        skip_attributes,
+                        store,
+                        cache,
+                    ),
                 );
                 (needed_ids, cluster_res, attrs)
             }
@@ -644,6 +700,7 @@ async fn scan_clusters_pq(
                     id: id.clone(),
                     score,
                     attributes: vector_attrs,
+                    values: include_vectors.then(|| cluster.vectors[j].clone()),
                 });
             }
         }
@@ -683,15 +740,20 @@ async fn try_bitmap_prefilter(
     evaluate_filter_bitmap(filter, &bitmap_index)
 }
 
-/// Load attribute data for a cluster.
+/// Load attribute data for a cluster. Unfiltered searches that skip
+/// attributes fetch nothing.
 async fn load_attrs(
     namespace: &str,
     segment_id: &str,
     cluster_idx: usize,
     filter: Option<&Filter>,
+    skip_attributes: bool,
     store: &ZeppelinStore,
     cache: Option<&Arc<DiskCache>>,
 ) -> Option<Vec<Option<HashMap<String, AttributeValue>>>> {
+    if filter.is_none() && skip_attributes {
+        return None;
+    }
     let akey = attrs_key(namespace, segment_id, cluster_idx);
     if filter.is_some() {
         match fetch_with_cache(cache, store, &akey).await {
@@ -0,0 +1,143 @@
+//! Per-segment map from vector ID to the partition that stores it.
+//!
+//! Written once per segment at build time as a JSON sidecar. Point lookups
+//! read it to fetch only the partitions holding the requested IDs instead of
+//! scanning every partition's ID list, and tiered compaction and attribute
+//! scans read it to find the IDs a segment holds without loading its
+//! vectors.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+
+/// S3 key for a segment's ID map sidecar.
+pub fn segment_id_map_key(namespace: &str, segment_id: &str) -> String {
+    format!("{namespace}/segments/{segment_id}/id_map.json")
+}
+
+/// IDs of a segment in sorted order, each with the index of the partition
+/// (IVF cluster, Vamana block or text-only partition) holding it and its
+/// position within that partition.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentIdMap {
+    ids: Vec<String>,
+    partitions: Vec<u32>,
+    /// Empty in maps written before positions were recorded.
+    #[serde(default)]
+    offsets: Vec<u32>,
+}
+
+impl SegmentIdMap {
+    /// Build from each partition's ID list, indexed by partition.
+    pub fn build(partitions: &[Vec<String>]) -> Self {
+        let mut entries: Vec<(&String, u32, u32)> = partitions
+            .iter()
+            .enumerate()
+            .flat_map(|(p, ids)| {
+                ids.iter()
+                    .enumerate()
+                    .map(move |(i, id)| (id, p as u32, i as u32))
+            })
+            .collect();
+        entries.sort_unstable();
+        let mut map = Self::default();
+        for (id, p, i) in entries {
+            map.ids.push(id.clone());
+            map.partitions.push(p);
+            map.offsets.push(i);
+        }
+        map
+    }
+
+    /// The partition holding `id`, if the segment has it.
+    pub fn partition_of(&self, id: &str) -> Option<usize> {
+        self.ids
+            .binary_search_by(|probe| probe.as_str().cmp(id))
+            .ok()
+            .map(|i| self.partitions[i] as usize)
+    }
+
+    /// Every ID in the segment, in sorted order.
+    pub fn ids(&self) -> &[String] {
+        &self.ids
+    }
+
+    /// Each of the first `partition_count` partitions' ID lists, in stored
+    /// order, or `None` for maps written before positions were recorded.
+    pub fn partition_ids(&self, partition_count: usize) -> Option<Vec<Vec<String>>> {
+        if self.offsets.len() != self.ids.len() {
+            return None;
+        }
+        let mut slots: Vec<Vec<Option<&String>>> = vec![Vec::new(); partition_count];
+        for ((id, &p), &i) in self.ids.iter().zip(&self.partitions).zip(&self.offsets) {
+            let slots = slots.get_mut(p as usize)?;
+            let i = i as usize;
+            if slots.len() <= i {
+                slots.resize(i + 1, None);
+            }
+            slots[i] = Some(id);
+        }
+        slots
+            .into_iter()
+            .map(|ids| ids.into_iter().map(|id| id.cloned()).collect())
+            .collect()
+    }
+
+    pub fn to_bytes(&self) -> Result<bytes::Bytes> {
+        Ok(bytes::Bytes::from(serde_json::to_vec(self)?))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_and_lookup() {
+        let map = SegmentIdMap::build(&[
+            vec!["c".to_string(), "a".to_string()],
+            vec![],
+            vec!["b".to_string()],
+        ]);
+        assert_eq!(map.ids(), ["a", "b", "c"]);
+        assert_eq!(map.partition_of("a"), Some(0));
+        assert_eq!(map.partition_of("b"), Some(2));
+        assert_eq!(map.partition_of("c"), Some(0));
+        assert_eq!(map.partition_of("d"), None);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let map = SegmentIdMap::build(&[vec!["x".to_string()], vec!["y".to_string()]]);
+        let back = SegmentIdMap::from_bytes(&map.to_bytes().unwrap()).unwrap();
+        assert_eq!(back, map);
+        assert_eq!(back.partition_of("y"), Some(1));
+    }
+
+    #[test]
+    fn test_partition_ids_in_stored_order() {
+        let map = SegmentIdMap::build(&[
+            vec!["c".to_string(), "a".to_string()],
+            vec![],
+            vec!["b".to_string()],
+        ]);
+        assert_eq!(
+            map.partition_ids(4).unwrap(),
+            vec![
+                vec!["c".to_string(), "a".to_string()],
+                vec![],
+                vec!["b".to_string()],
+                vec![],
+            ]
+        );
+
+        // Maps written before positions were recorded can't say.
+        let old = SegmentIdMap::from_bytes(br#"{"ids":["a"],"partitions":[0]}"#).unwrap();
+        assert_eq!(old.partition_of("a"), Some(0));
+        assert_eq!(old.partition_ids(1), None);
+    }
+}
@@ -1,22 +1,24 @@
 //! Build phase for IVF-Flat index.
 //!
 //! Pipeline: train centroids -> assign vectors to clusters -> serialize and
-//! write artifacts (centroids, cluster vectors, cluster attributes) to S3.
+//! write artifacts (centroids, cluster vectors, cluster attributes, numeric
+//! attribute stats) to S3.
 
 use bytes::Bytes;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use tracing::{debug, info};
 
+use crate::cache::DiskCache;
 use crate::config::IndexingConfig;
 use crate::error::{Result, ZeppelinError};
-use crate::index::quantization::QuantizationType;
+use crate::index::quantization::{QuantizationType, DEFAULT_RERANK_FACTOR};
 use crate::storage::ZeppelinStore;
-use crate::types::{AttributeValue, VectorEntry};
+use crate::types::{AttributeValue, DistanceMetric, VectorEntry};
 
 /// Pre-serialized cluster payload: (vec_key, vec_data, attr_key, attr_data, optional bitmap).
 type ClusterPayload = (String, Bytes, String, Bytes, Option<(String, Bytes)>);
 
-use super::kmeans::train_kmeans;
+use super::kmeans::{train_kmeans_with_metric, training_metric};
 use super::IvfFlatIndex;
 use crate::index::distance;
 
@@ -39,22 +41,41 @@ pub(crate) fn attrs_key(namespace: &str, segment_id: &str, cluster_idx: usize) -
     format!("{namespace}/segments/{segment_id}/attrs_{cluster_idx}.bin")
 }
 
+/// S3 key for the document IDs of partition `i` of a text-only segment, or
+/// of block `i` of a Vamana segment. The blob uses the cluster layout with
+/// dimension 0, so it holds IDs only.
+pub(crate) fn docs_key(namespace: &str, segment_id: &str, partition_idx: usize) -> String {
+    format!("{namespace}/segments/{segment_id}/docs_{partition_idx}.bin")
+}
+
 // ---------------------------------------------------------------------------
 // Serialization helpers
 // ---------------------------------------------------------------------------
 
-/// Header written before the centroid float array.
+/// Magic bytes at the start of a versioned centroids blob.
+const CENTROIDS_MAGIC: &[u8; 4] = b"ZCEN";
+
+/// Current centroids blob version.
+const CENTROIDS_VERSION: u8 = 1;
+
+/// Layout: `[magic "ZCEN"][version: u8][num_centroids: u32][dimension: u32]
+/// [f32 * num_centroids * dimension]`, all little-endian.
 ///
-/// Layout: `[num_centroids: u32][dimension: u32][f32 * num_centroids * dimension]`
+/// The floats are stored raw, so the blob is 13 bytes plus exactly
+/// `4 * num_centroids * dimension`: smaller than bincode or postcard, which
+/// add a length prefix per centroid, and a fraction of JSON's size. The
+/// version byte lets the layout change without breaking existing segments.
 pub(crate) fn serialize_centroids(centroids: &[Vec<f32>], dim: usize) -> Result<Bytes> {
     let num_centroids = centroids.len() as u32;
     let dimension = dim as u32;
 
-    // 8 bytes header + floats
+    // 5 bytes magic + version, 8 bytes header, then floats
     let float_bytes = centroids.len() * dim * std::mem::size_of::<f32>();
-    let total = 8 + float_bytes;
+    let total = 13 + float_bytes;
     let mut buf = Vec::with_capacity(total);
 
+    buf.extend_from_slice(CENTROIDS_MAGIC);
+    buf.push(CENTROIDS_VERSION);
     buf.extend_from_slice(&num_centroids.to_le_bytes());
     buf.extend_from_slice(&dimension.to_le_bytes());
 
@@ -69,7 +90,28 @@ pub(crate) fn serialize_centroids(centroids: &[Vec<f32>], dim: usize) -> Result<
 }
 
 /// Deserialize centroids from the binary format produced by `serialize_centroids`.
+///
+/// Blobs written before the version header (plain `[num_centroids][dimension]
+/// [floats]`) are still accepted. They can't be mistaken for the new format:
+/// "ZCEN" read as a legacy centroid count would be over a billion centroids.
 pub(crate) fn deserialize_centroids(data: &[u8]) -> Result<(Vec<Vec<f32>>, usize)> {
+    let data = match data.strip_prefix(CENTROIDS_MAGIC) {
+        Some(rest) => match rest.split_first() {
+            Some((&CENTROIDS_VERSION, body)) => body,
+            Some((version, _)) => {
+                return Err(ZeppelinError::Index(format!(
+                    "unsupported centroids version: expected {CENTROIDS_VERSION}, got {version}"
+                )));
+            }
+            None => {
+                return Err(ZeppelinError::Index(
+                    "centroids blob too small for header".into(),
+                ));
+            }
+        },
+        None => data,
+    };
+
     if data.len() < 8 {
         return Err(ZeppelinError::Index(
             "centroids blob too small for header".into(),
@@ -211,21 +253,57 @@ pub(crate) fn deserialize_cluster(data: &[u8]) -> Result<ClusterData> {
     Ok(ClusterData { ids, vectors })
 }
 
-/// Attributes blob: JSON-serialized `Vec<Option<HashMap<String, AttributeValue>>>`.
+/// Magic bytes at the start of a compressed attributes blob.
+const ATTRS_MAGIC: &[u8; 4] = b"ZATR";
+
+/// Current attributes blob version.
+const ATTRS_VERSION: u8 = 1;
+
+/// zstd level for attributes blobs. Low levels already get most of the gain
+/// on repetitive JSON keys and keep compaction fast.
+const ATTRS_ZSTD_LEVEL: i32 = 3;
+
+/// Layout: `[magic "ZATR"][version: u8][zstd-compressed JSON]`, where the
+/// JSON is the `Vec<Option<HashMap<String, AttributeValue>>>`.
 ///
 /// We use JSON rather than bincode because `AttributeValue` uses
 /// `#[serde(untagged)]`, which requires `deserialize_any` -- a method
-/// that bincode does not support.
+/// that bincode does not support. Attribute names repeat on every vector,
+/// so the JSON compresses well, which cuts both S3 bandwidth and cache
+/// footprint on filtered scans.
 pub(crate) fn serialize_attrs(attrs: &[Option<HashMap<String, AttributeValue>>]) -> Result<Bytes> {
-    let encoded = serde_json::to_vec(attrs)?;
-    Ok(Bytes::from(encoded))
+    let json = serde_json::to_vec(attrs)?;
+    let mut buf = Vec::with_capacity(5 + json.len() / 4);
+    buf.extend_from_slice(ATTRS_MAGIC);
+    buf.push(ATTRS_VERSION);
+    zstd::stream::copy_encode(json.as_slice(), &mut buf, ATTRS_ZSTD_LEVEL)?;
+    Ok(Bytes::from(buf))
 }
 
 /// Deserialize attributes blob.
+///
+/// Blobs written before compression are plain JSON arrays, which start with
+/// `[` and so never with the magic bytes; they are still accepted.
 pub(crate) fn deserialize_attrs(
     data: &[u8],
 ) -> Result<Vec<Option<HashMap<String, AttributeValue>>>> {
-    Ok(serde_json::from_slice(data)?)
+    let Some(rest) = data.strip_prefix(ATTRS_MAGIC) else {
+        return Ok(serde_json::from_slice(data)?);
+    };
+    match rest.split_first() {
+        Some((&ATTRS_VERSION, body)) => {
+            let json = zstd::stream::decode_all(body).map_err(|e| {
+                ZeppelinError::Index(format!("attrs blob decompression failed: {e}"))
+            })?;
+            Ok(serde_json::from_slice(&json)?)
+        }
+        Some((version, _)) => Err(ZeppelinError::Index(format!(
+            "unsupported attrs version: expected {ATTRS_VERSION}, got {version}"
+        ))),
+        None => Err(ZeppelinError::Index(
+            "attrs blob too small for header".into(),
+        )),
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -234,8 +312,9 @@ pub(crate) fn deserialize_attrs(
 
 /// Build an IVF-Flat index from the given vectors.
 ///
-/// 1. Train centroids via k-means++.
-/// 2. Assign every vector to its nearest centroid.
+/// 1. Train centroids via k-means++ under `config.kmeans_metric`
+///    (Euclidean when unset).
+/// 2. Assign every vector to its nearest centroid under the same metric.
 /// 3. Serialize and write all artifacts to S3.
 /// 4. Return an `IvfFlatIndex` handle with the metadata needed for search.
 pub async fn build_ivf_flat(
@@ -245,6 +324,180 @@ pub async fn build_ivf_flat(
     namespace: &str,
     segment_id: &str,
 ) -> Result<IvfFlatIndex> {
+    let dim = validate_build_input(vectors)?;
+    let k = config.default_num_centroids.min(vectors.len());
+
+    info!(
+        n = vectors.len(),
+        dim = dim,
+        k = k,
+        namespace = namespace,
+        segment_id = segment_id,
+        "building IVF-Flat index"
+    );
+
+    // --- Step 1: Train centroids ---
+    let metric = training_metric(config.kmeans_metric.unwrap_or(DistanceMetric::Euclidean));
+    let vec_refs: Vec<&[f32]> = vectors.iter().map(|v| v.values.as_slice()).collect();
+    let centroids = train_kmeans_with_metric(
+        &vec_refs,
+        dim,
+        k,
+        config.kmeans_max_iterations,
+        config.kmeans_convergence_epsilon,
+        metric,
+        config.kmeans_seed,
+    )?;
+
+    info!(num_clusters = centroids.len(), %metric, "k-means training complete");
+
+    // --- Step 2: Assign vectors to clusters ---
+    let assignments: Vec<usize> = vectors
+        .iter()
+        .map(|entry| {
+            let mut best_dist = f32::MAX;
+            let mut best_cluster = 0usize;
+            for (c, centroid) in centroids.iter().enumerate() {
+                let d = distance::compute_distance(&entry.values, centroid, metric);
+                if d < best_dist {
+                    best_dist = d;
+                    best_cluster = c;
+                }
+            }
+            best_cluster
+        })
+        .collect();
+
+    write_ivf_flat(
+        vectors,
+        &assignments,
+        centroids,
+        dim,
+        config,
+        store,
+        namespace,
+        segment_id,
+    )
+    .await
+}
+
+/// Build an IVF-Flat index whose clusters are contiguous ranges of `field`.
+///
+/// Vectors are sorted by the attribute (numbers ascending, then strings,
+/// then vectors missing the field) and the sorted run is cut into
+/// `default_num_centroids` equal-sized clusters. Each centroid is the mean
+/// of its cluster, so the segment stays searchable by vector, with lower
+/// recall than k-means clustering.
+pub async fn build_ivf_flat_by_attribute(
+    vectors: &[VectorEntry],
+    field: &str,
+    config: &IndexingConfig,
+    store: &ZeppelinStore,
+    namespace: &str,
+    segment_id: &str,
+) -> Result<IvfFlatIndex> {
+    let dim = validate_build_input(vectors)?;
+    let k = config.default_num_centroids.clamp(1, vectors.len());
+
+    info!(
+        n = vectors.len(),
+        dim = dim,
+        k = k,
+        field = field,
+        namespace = namespace,
+        segment_id = segment_id,
+        "building attribute-clustered IVF-Flat index"
+    );
+
+    let mut order: Vec<usize> = (0..vectors.len()).collect();
+    order.sort_by(|&a, &b| {
+        attr_sort_key(&vectors[a], field).cmp_key(&attr_sort_key(&vectors[b], field))
+    });
+
+    let chunk_size = vectors.len().div_ceil(k);
+    let mut assignments = vec![0usize; vectors.len()];
+    let mut centroids = Vec::with_capacity(k);
+    for (c, chunk) in order.chunks(chunk_size).enumerate() {
+        let mut centroid = vec![0.0f32; dim];
+        for &i in chunk {
+            assignments[i] = c;
+            for (acc, &x) in centroid.iter_mut().zip(&vectors[i].values) {
+                *acc += x;
+            }
+        }
+        let inv = 1.0 / chunk.len() as f32;
+        for x in centroid.iter_mut() {
+            *x *= inv;
+        }
+        centroids.push(centroid);
+    }
+
+    write_ivf_flat(
+        vectors,
+        &assignments,
+        centroids,
+        dim,
+        config,
+        store,
+        namespace,
+        segment_id,
+    )
+    .await
+}
+
+/// Documents per partition of a text-only segment.
+pub const TEXT_ONLY_PARTITION_SIZE: usize = 10_000;
+
+/// Write a text-only segment: documents are cut into partitions of
+/// [`TEXT_ONLY_PARTITION_SIZE`] in the given order, and each partition gets
+/// an ID blob and an attributes blob. No k-means, centroids or vector data.
+/// Partitions play the role of clusters for FTS indexing and BM25 search.
+/// Returns the partition count.
+pub async fn build_text_only(
+    docs: &[VectorEntry],
+    store: &ZeppelinStore,
+    namespace: &str,
+    segment_id: &str,
+) -> Result<usize> {
+    info!(
+        n = docs.len(),
+        namespace = namespace,
+        segment_id = segment_id,
+        "building text-only segment"
+    );
+
+    let mut payloads = Vec::new();
+    for (i, chunk) in docs.chunks(TEXT_ONLY_PARTITION_SIZE).enumerate() {
+        let ids: Vec<String> = chunk.iter().map(|d| d.id.clone()).collect();
+        let attrs: Vec<_> = chunk.iter().map(|d| d.attributes.clone()).collect();
+        let no_values = vec![Vec::new(); ids.len()];
+        payloads.push((
+            docs_key(namespace, segment_id, i),
+            serialize_cluster(&ids, &no_values, 0)?,
+        ));
+        payloads.push((
+            attrs_key(namespace, segment_id, i),
+            serialize_attrs(&attrs)?,
+        ));
+    }
+    let partitions = payloads.len() / 2;
+
+    let results = futures::future::join_all(
+        payloads
+            .iter()
+            .map(|(key, data)| store.put(key, data.clone())),
+    )
+    .await;
+    for result in results {
+        result?;
+    }
+
+    debug!(partitions, "text-only segment written");
+    Ok(partitions)
+}
+
+/// Check that `vectors` is non-empty with a consistent, non-zero dimension.
+fn validate_build_input(vectors: &[VectorEntry]) -> Result<usize> {
     if vectors.is_empty() {
         return Err(ZeppelinError::Index(
             "cannot build index from empty vector set".into(),
@@ -265,50 +518,68 @@ pub async fn build_ivf_flat(
             });
         }
     }
+    Ok(dim)
+}
 
-    let k = config.default_num_centroids.min(vectors.len());
+/// Ordering key for attribute clustering.
+enum AttrSortKey<'a> {
+    Number(f64),
+    Text(&'a str),
+    Missing,
+}
 
-    info!(
-        n = vectors.len(),
-        dim = dim,
-        k = k,
-        namespace = namespace,
-        segment_id = segment_id,
-        "building IVF-Flat index"
-    );
+impl AttrSortKey<'_> {
+    fn rank(&self) -> u8 {
+        match self {
+            AttrSortKey::Number(_) => 0,
+            AttrSortKey::Text(_) => 1,
+            AttrSortKey::Missing => 2,
+        }
+    }
 
-    // --- Step 1: Train centroids ---
-    let vec_refs: Vec<&[f32]> = vectors.iter().map(|v| v.values.as_slice()).collect();
-    let centroids = train_kmeans(
-        &vec_refs,
-        dim,
-        k,
-        config.kmeans_max_iterations,
-        config.kmeans_convergence_epsilon,
-    )?;
+    fn cmp_key(&self, other: &Self) -> std::cmp::Ordering {
+        match (self, other) {
+            (AttrSortKey::Number(a), AttrSortKey::Number(b)) => a.total_cmp(b),
+            (AttrSortKey::Text(a), AttrSortKey::Text(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+fn attr_sort_key<'a>(entry: &'a VectorEntry, field: &str) -> AttrSortKey<'a> {
+    match entry.attributes.as_ref().and_then(|a| a.get(field)) {
+        Some(AttributeValue::Integer(i)) => AttrSortKey::Number(*i as f64),
+        Some(AttributeValue::Float(f)) => AttrSortKey::Number(*f),
+        Some(AttributeValue::String(s)) => AttrSortKey::Text(s),
+        _ => AttrSortKey::Missing,
+    }
+}
 
+/// Group vectors by cluster assignment and write centroids, clusters,
+/// attributes, bitmaps, and quantized artifacts to S3.
+#[allow(clippy::too_many_arguments)]
+async fn write_ivf_flat(
+    vectors: &[VectorEntry],
+    assignments: &[usize],
+    centroids: Vec<Vec<f32>>,
+    dim: usize,
+    config: &IndexingConfig,
+    store: &ZeppelinStore,
+    namespace: &str,
+    segment_id: &str,
+) -> Result<IvfFlatIndex> {
     let num_clusters = centroids.len();
-    info!(num_clusters = num_clusters, "k-means training complete");
+    let vec_refs: Vec<&[f32]> = vectors.iter().map(|v| v.values.as_slice()).collect();
 
-    // --- Step 2: Assign vectors to clusters ---
     let mut cluster_ids: Vec<Vec<String>> = vec![Vec::new(); num_clusters];
     let mut cluster_vecs: Vec<Vec<Vec<f32>>> = vec![Vec::new(); num_clusters];
     let mut cluster_attrs: Vec<Vec<Option<HashMap<String, AttributeValue>>>> =
         vec![Vec::new(); num_clusters];
 
-    for entry in vectors {
-        let mut best_dist = f32::MAX;
-        let mut best_cluster = 0usize;
-        for (c, centroid) in centroids.iter().enumerate() {
-            let d = distance::euclidean_distance(&entry.values, centroid);
-            if d < best_dist {
-                best_dist = d;
-                best_cluster = c;
-            }
-        }
-        cluster_ids[best_cluster].push(entry.id.clone());
-        cluster_vecs[best_cluster].push(entry.values.clone());
-        cluster_attrs[best_cluster].push(entry.attributes.clone());
+    for (entry, &c) in vectors.iter().zip(assignments) {
+        cluster_ids[c].push(entry.id.clone());
+        cluster_vecs[c].push(entry.values.clone());
+        cluster_attrs[c].push(entry.attributes.clone());
     }
 
     for (i, ids) in cluster_ids.iter().enumerate() {
@@ -352,6 +623,9 @@ pub async fn build_ivf_flat(
     }
     let bitmap_fields: Vec<String> = bitmap_fields_set.into_iter().collect();
 
+    let stats_key = super::stats::attr_stats_key(namespace, segment_id);
+    let stats_data = super::stats::SegmentAttrStats::build(&cluster_attrs).to_bytes()?;
+
     // I/O phase: write all cluster data in parallel.
     let mut write_futs = Vec::new();
     for (cvec_key, cvec_data, cattr_key, cattr_data, bitmap) in &cluster_payloads {
@@ -361,6 +635,7 @@ pub async fn build_ivf_flat(
             write_futs.push(store.put(bkey, bitmap_data.clone()));
         }
     }
+    write_futs.push(store.put(&stats_key, stats_data));
     let results = futures::future::join_all(write_futs).await;
     for result in results {
         result?;
@@ -459,6 +734,16 @@ pub async fn build_ivf_flat(
         segment_id: segment_id.to_string(),
         quantization,
         bitmap_fields,
+        max_candidates_per_cluster: None,
+        deadline: None,
+        adaptive_nprobe_cap: None,
+        probe_gap: None,
+        skip_attributes: false,
+        max_distance: None,
+        after: None,
+        tombstones: BTreeSet::new(),
+        include_vectors: false,
+        rerank_factor: DEFAULT_RERANK_FACTOR,
     })
 }
 
@@ -466,16 +751,20 @@ pub async fn build_ivf_flat(
 ///
 /// Only fetches centroids from S3 — skips the cluster-count probe loop
 /// and quantization-type detection that `load_ivf_flat` performs, saving
-/// ~18 S3 GETs per query.
+/// ~18 S3 GETs per query. With a `cache`, centroids are read through it.
 pub async fn load_ivf_flat_from_manifest(
     store: &ZeppelinStore,
     namespace: &str,
     segment_id: &str,
     num_vectors: usize,
     quantization: QuantizationType,
+    cache: Option<&DiskCache>,
 ) -> Result<IvfFlatIndex> {
     let ckey = centroids_key(namespace, segment_id);
-    let data = store.get(&ckey).await?;
+    let data = match cache {
+        Some(c) => c.get_or_fetch(&ckey, || store.get(&ckey)).await?,
+        None => store.get(&ckey).await?,
+    };
     let (centroids, dim) = deserialize_centroids(&data)?;
 
     info!(
@@ -496,6 +785,16 @@ pub async fn load_ivf_flat_from_manifest(
         segment_id: segment_id.to_string(),
         quantization,
         bitmap_fields: Vec::new(), // Populated from SegmentRef at search time
+        max_candidates_per_cluster: None,
+        deadline: None,
+        adaptive_nprobe_cap: None,
+        probe_gap: None,
+        skip_attributes: false,
+        max_distance: None,
+        after: None,
+        tombstones: BTreeSet::new(),
+        include_vectors: false,
+        rerank_factor: DEFAULT_RERANK_FACTOR,
     })
 }
 
@@ -530,16 +829,17 @@ pub async fn load_ivf_flat(
     }
 
     // Detect quantization: check for PQ codebook first, then SQ calibration.
+    // Only presence matters, so a HEAD is enough.
     let quantization = {
         use crate::index::quantization::pq::pq_codebook_key;
         use crate::index::quantization::sq::sq_calibration_key;
 
         let pq_key = pq_codebook_key(namespace, segment_id);
-        if store.get(&pq_key).await.is_ok() {
+        if store.exists(&pq_key).await? {
             QuantizationType::Product
         } else {
             let sq_key = sq_calibration_key(namespace, segment_id);
-            if store.get(&sq_key).await.is_ok() {
+            if store.exists(&sq_key).await? {
                 QuantizationType::Scalar
             } else {
                 QuantizationType::None
@@ -565,6 +865,16 @@ pub async fn load_ivf_flat(
         segment_id: segment_id.to_string(),
         quantization,
         bitmap_fields: Vec::new(), // Populated from SegmentRef at search time
+        max_candidates_per_cluster: None,
+        deadline: None,
+        adaptive_nprobe_cap: None,
+        probe_gap: None,
+        skip_attributes: false,
+        max_distance: None,
+        after: None,
+        tombstones: BTreeSet::new(),
+        include_vectors: false,
+        rerank_factor: DEFAULT_RERANK_FACTOR,
     })
 }
 
@@ -581,6 +891,55 @@ mod tests {
         assert_eq!(decoded, centroids);
     }
 
+    #[test]
+    fn test_deserialize_legacy_centroids() {
+        let mut legacy = Vec::new();
+        legacy.extend_from_slice(&2u32.to_le_bytes());
+        legacy.extend_from_slice(&2u32.to_le_bytes());
+        for val in [1.0f32, 2.0, 3.0, 4.0] {
+            legacy.extend_from_slice(&val.to_le_bytes());
+        }
+        let (decoded, dim) = deserialize_centroids(&legacy).unwrap();
+        assert_eq!(dim, 2);
+        assert_eq!(decoded, vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+
+        let mut future = serialize_centroids(&decoded, 2).unwrap().to_vec();
+        future[4] = CENTROIDS_VERSION + 1;
+        assert!(deserialize_centroids(&future).is_err());
+    }
+
+    #[test]
+    fn test_built_centroids_reload() {
+        let vectors: Vec<VectorEntry> = (0..64)
+            .map(|i| VectorEntry {
+                id: format!("v{i}"),
+                values: (0..16).map(|d| ((i * 7 + d * 3) % 23) as f32).collect(),
+                attributes: None,
+                sparse: None,
+                named_vectors: None,
+                token_vectors: None,
+                version: None,
+            })
+            .collect();
+        let config = IndexingConfig {
+            default_num_centroids: 4,
+            ..Default::default()
+        };
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+        let built = rt
+            .block_on(build_ivf_flat(&vectors, &config, &store, "ns", "seg"))
+            .unwrap();
+        let loaded = rt.block_on(load_ivf_flat(&store, "ns", "seg")).unwrap();
+        assert_eq!(loaded.centroids, built.centroids);
+        assert_eq!(loaded.dim, 16);
+
+        // Header plus raw floats only: 13 + 4 * 4 * 16 bytes.
+        let blob = rt.block_on(store.get(&centroids_key("ns", "seg"))).unwrap();
+        assert_eq!(blob.len(), 13 + 4 * built.centroids.len() * 16);
+    }
+
     #[test]
     fn test_serialize_deserialize_cluster() {
         let ids = vec!["vec_1".to_string(), "vec_2".to_string()];
@@ -607,6 +966,59 @@ mod tests {
         assert!(decoded[1].is_none());
     }
 
+    #[test]
+    fn test_attrs_blob_compressed_and_round_trips() {
+        let attrs: Vec<Option<HashMap<String, AttributeValue>>> = (0..500)
+            .map(|i| {
+                (i % 10 != 0).then(|| {
+                    HashMap::from([
+                        (
+                            "category".to_string(),
+                            AttributeValue::String(["news", "sports", "tech"][i % 3].into()),
+                        ),
+                        ("published".to_string(), AttributeValue::Bool(i % 2 == 0)),
+                        ("score".to_string(), AttributeValue::Integer(i as i64)),
+                        (
+                            "tags".to_string(),
+                            AttributeValue::StringList(vec!["a".into(), format!("t{}", i % 7)]),
+                        ),
+                    ])
+                })
+            })
+            .collect();
+
+        let data = serialize_attrs(&attrs).unwrap();
+        assert!(data.starts_with(ATTRS_MAGIC));
+        let json_len = serde_json::to_vec(&attrs).unwrap().len();
+        assert!(
+            data.len() * 3 < json_len,
+            "compressed {} bytes vs {json_len} bytes of JSON",
+            data.len()
+        );
+        assert_eq!(deserialize_attrs(&data).unwrap(), attrs);
+    }
+
+    #[test]
+    fn test_deserialize_legacy_json_attrs() {
+        let attrs = vec![
+            Some(HashMap::from([(
+                "color".to_string(),
+                AttributeValue::String("red".to_string()),
+            )])),
+            None,
+        ];
+        let legacy = serde_json::to_vec(&attrs).unwrap();
+        assert_eq!(deserialize_attrs(&legacy).unwrap(), attrs);
+    }
+
+    #[test]
+    fn test_attrs_unsupported_version() {
+        let mut data = serialize_attrs(&[None]).unwrap().to_vec();
+        data[4] = ATTRS_VERSION + 1;
+        let err = deserialize_attrs(&data).unwrap_err();
+        assert!(err.to_string().contains("unsupported attrs version"));
+    }
+
     #[test]
     fn test_centroids_header_too_small() {
         let data = vec![0u8; 4]; // less than 8 bytes
@@ -657,4 +1069,56 @@ mod tests {
             other => panic!("expected Index error, got: {other}"),
         }
     }
+
+    #[test]
+    fn test_attribute_clustering_localizes_ranges() {
+        let n = 200;
+        // Vector values are unrelated to the clustering attribute.
+        let vectors: Vec<VectorEntry> = (0..n)
+            .map(|i| {
+                let mut attrs = HashMap::new();
+                attrs.insert(
+                    "ts".to_string(),
+                    AttributeValue::Integer((i * 7 % n) as i64),
+                );
+                VectorEntry {
+                    id: format!("v{i}"),
+                    values: vec![((i * 13) % 17) as f32, ((i * 5) % 11) as f32],
+                    attributes: Some(attrs),
+                    sparse: None,
+                    named_vectors: None,
+                    token_vectors: None,
+                    version: None,
+                }
+            })
+            .collect();
+        let config = IndexingConfig {
+            default_num_centroids: 8,
+            ..Default::default()
+        };
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+        let index = rt
+            .block_on(build_ivf_flat_by_attribute(
+                &vectors, "ts", &config, &store, "ns", "seg",
+            ))
+            .unwrap();
+        assert_eq!(index.num_clusters(), 8);
+
+        // A 10% range on the clustering attribute should touch at most 2 clusters.
+        let mut touched = 0;
+        for c in 0..index.num_clusters() {
+            let data = rt.block_on(store.get(&attrs_key("ns", "seg", c))).unwrap();
+            let attrs = deserialize_attrs(&data).unwrap();
+            let hit = attrs.iter().flatten().any(|a| match a.get("ts") {
+                Some(AttributeValue::Integer(ts)) => (100..120).contains(ts),
+                _ => false,
+            });
+            if hit {
+                touched += 1;
+            }
+        }
+        assert!(touched <= 2, "range touched {touched} clusters");
+    }
 }
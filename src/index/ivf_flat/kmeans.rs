@@ -4,10 +4,12 @@
 //! pre-allocated and reused across iterations to avoid per-iteration heap
 //! churn on large datasets.
 
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use tracing::{debug, info, warn};
 
 use crate::error::{Result, ZeppelinError};
+use crate::types::DistanceMetric;
 
 /// Train `k` centroids from the given data points using k-means++
 /// initialization followed by Lloyd's iterations.
@@ -27,6 +29,60 @@ pub fn train_kmeans(
     k: usize,
     max_iters: usize,
     epsilon: f64,
+) -> Result<Vec<Vec<f32>>> {
+    lloyd(vectors, dim, k, max_iters, epsilon, false, None)
+}
+
+/// The metric k-means actually trains under when asked for `metric`.
+///
+/// Cosine gets spherical k-means. Dot product keeps Euclidean: normalizing
+/// would discard the vector norms that dot-product ranking depends on.
+pub fn training_metric(metric: DistanceMetric) -> DistanceMetric {
+    match metric {
+        DistanceMetric::Cosine => DistanceMetric::Cosine,
+        DistanceMetric::Euclidean | DistanceMetric::DotProduct => DistanceMetric::Euclidean,
+    }
+}
+
+/// Like [`train_kmeans`], but clusters under `metric` (see [`training_metric`]).
+/// A `seed` makes the k-means++ initialization, and so the result,
+/// reproducible.
+///
+/// Spherical k-means normalizes the inputs and re-normalizes every centroid
+/// after each update. On unit vectors squared L2 is `2 - 2·cos`, so the
+/// nearest centroid by L2 is also the nearest by cosine distance.
+pub fn train_kmeans_with_metric(
+    vectors: &[&[f32]],
+    dim: usize,
+    k: usize,
+    max_iters: usize,
+    epsilon: f64,
+    metric: DistanceMetric,
+    seed: Option<u64>,
+) -> Result<Vec<Vec<f32>>> {
+    if training_metric(metric) != DistanceMetric::Cosine {
+        return lloyd(vectors, dim, k, max_iters, epsilon, false, seed);
+    }
+    let normalized: Vec<Vec<f32>> = vectors
+        .iter()
+        .map(|v| {
+            let mut v = v.to_vec();
+            normalize(&mut v);
+            v
+        })
+        .collect();
+    let refs: Vec<&[f32]> = normalized.iter().map(|v| v.as_slice()).collect();
+    lloyd(&refs, dim, k, max_iters, epsilon, true, seed)
+}
+
+fn lloyd(
+    vectors: &[&[f32]],
+    dim: usize,
+    k: usize,
+    max_iters: usize,
+    epsilon: f64,
+    spherical: bool,
+    seed: Option<u64>,
 ) -> Result<Vec<Vec<f32>>> {
     let n = vectors.len();
 
@@ -58,7 +114,7 @@ pub fn train_kmeans(
     );
 
     // --- k-means++ initialization ---
-    let mut centroids = kmeans_pp_init(vectors, dim, effective_k)?;
+    let mut centroids = kmeans_pp_init(vectors, dim, effective_k, seed)?;
 
     // --- Lloyd's iterations ---
     let mut assignments = vec![0usize; n];
@@ -110,6 +166,9 @@ pub fn train_kmeans(
             for val in new_centroid.iter_mut() {
                 *val *= inv;
             }
+            if spherical {
+                normalize(new_centroid);
+            }
             let shift = squared_l2(&centroids[c], new_centroid) as f64;
             if shift > max_shift {
                 max_shift = shift;
@@ -148,9 +207,17 @@ pub fn train_kmeans(
 
 /// k-means++ seeding: pick initial centroids with probability proportional
 /// to squared distance from the nearest already-chosen centroid.
-fn kmeans_pp_init(vectors: &[&[f32]], dim: usize, k: usize) -> Result<Vec<Vec<f32>>> {
+fn kmeans_pp_init(
+    vectors: &[&[f32]],
+    dim: usize,
+    k: usize,
+    seed: Option<u64>,
+) -> Result<Vec<Vec<f32>>> {
     let n = vectors.len();
-    let mut rng = rand::thread_rng();
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
 
     let mut centroids: Vec<Vec<f32>> = Vec::with_capacity(k);
 
@@ -213,6 +280,14 @@ fn kmeans_pp_init(vectors: &[&[f32]], dim: usize, k: usize) -> Result<Vec<Vec<f3
     Ok(centroids)
 }
 
+/// Scale `v` to unit length in place. Zero vectors are left as-is.
+fn normalize(v: &mut [f32]) {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > f32::EPSILON {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
 /// Squared L2 distance between two vectors.
 #[inline]
 fn squared_l2(a: &[f32], b: &[f32]) -> f32 {
@@ -274,6 +349,27 @@ mod tests {
         assert!(c1 > 9.0, "upper centroid should be near 10, got {c1}");
     }
 
+    #[test]
+    fn test_spherical_clusters_by_direction() {
+        // Two directions, each at wildly different magnitudes.
+        let mut data = Vec::new();
+        for scale in [0.1f32, 1.0, 10.0, 100.0] {
+            data.push(vec![scale, 0.01 * scale]);
+            data.push(vec![0.01 * scale, scale]);
+        }
+        let refs: Vec<&[f32]> = data.iter().map(|v| v.as_slice()).collect();
+        let centroids =
+            train_kmeans_with_metric(&refs, 2, 2, 50, 1e-6, DistanceMetric::Cosine, None).unwrap();
+
+        for c in &centroids {
+            let norm = (c[0] * c[0] + c[1] * c[1]).sqrt();
+            assert!((norm - 1.0).abs() < 1e-4, "centroid not unit length: {c:?}");
+        }
+        let mut xs: Vec<f32> = centroids.iter().map(|c| c[0]).collect();
+        xs.sort_by(f32::total_cmp);
+        assert!(xs[0] < 0.1 && xs[1] > 0.9, "centroids: {centroids:?}");
+    }
+
     #[test]
     fn test_squared_l2() {
         let a = [1.0, 2.0, 3.0];
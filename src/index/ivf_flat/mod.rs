@@ -7,6 +7,9 @@
 pub mod build;
 pub mod kmeans;
 pub mod search;
+pub mod stats;
+
+use std::collections::BTreeSet;
 
 use async_trait::async_trait;
 
@@ -16,6 +19,20 @@ use crate::index::traits::VectorIndex;
 use crate::storage::ZeppelinStore;
 use crate::types::{DistanceMetric, Filter, SearchResult, VectorEntry};
 
+/// Adaptive boundary probing: instead of a fixed `nprobe`, probe the nearest
+/// cluster plus every cluster whose centroid distance is within `ratio` of
+/// the nearest one, up to `max_nprobe`. A query deep inside one cluster
+/// probes just that cluster; one near a boundary also probes its
+/// neighbours. See `IndexingConfig::probe_gap_ratio`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProbeGap {
+    /// At least 1. Distances are the metric's own: squared for Euclidean,
+    /// and for dot product the gap is taken relative to the nearest
+    /// centroid's magnitude.
+    pub ratio: f32,
+    pub max_nprobe: usize,
+}
+
 /// In-memory handle for a built IVF-Flat index.
 ///
 /// Only the centroids live in memory; cluster vector data and attributes
@@ -36,6 +53,35 @@ pub struct IvfFlatIndex {
     pub(crate) quantization: crate::index::quantization::QuantizationType,
     /// Fields that have bitmap indexes.
     pub(crate) bitmap_fields: Vec<String>,
+    /// Search-time cap on how many vectors per cluster get a full-precision
+    /// distance. `None` scans every vector. Set by the query path.
+    pub(crate) max_candidates_per_cluster: Option<usize>,
+    /// Soft deadline after which search stops probing further clusters.
+    /// Set by the query path.
+    pub(crate) deadline: Option<std::time::Instant>,
+    /// Upper bound for the filter-selectivity nprobe adjustment. `None`
+    /// disables it. Set by the query path.
+    pub(crate) adaptive_nprobe_cap: Option<usize>,
+    /// Probe by centroid distance gap instead of a fixed `nprobe`. Set by
+    /// the query path.
+    pub(crate) probe_gap: Option<ProbeGap>,
+    /// Skip attribute fetches for unfiltered searches; results then carry
+    /// no attributes. Set by the query path.
+    pub(crate) skip_attributes: bool,
+    /// Quantized scans rerank `fetch_k * rerank_factor` candidates with
+    /// full-precision vectors. Set by the query path.
+    pub(crate) rerank_factor: usize,
+    /// Drop results whose internal distance exceeds this bound. Set by the
+    /// query path.
+    pub(crate) max_distance: Option<f32>,
+    /// Return each result's stored vector. Set by the query path.
+    pub(crate) include_vectors: bool,
+    /// Keep only results ranking after this `(distance, id)` cursor. Set
+    /// by the query path.
+    pub(crate) after: Option<(f32, String)>,
+    /// IDs tombstoned in this segment, dropped before top-k selection. Set
+    /// by the query path.
+    pub(crate) tombstones: BTreeSet<String>,
 }
 
 impl IvfFlatIndex {
@@ -62,16 +108,25 @@ impl IvfFlatIndex {
     /// Load an IVF-Flat index using pre-known metadata from the manifest.
     ///
     /// Only fetches centroids — skips cluster-count probing and quantization
-    /// detection, saving ~18 S3 GETs per query.
+    /// detection, saving ~18 S3 GETs per query. With a `cache`, centroids
+    /// are read through it.
     pub async fn load_from_manifest(
         store: &ZeppelinStore,
         namespace: &str,
         segment_id: &str,
         num_vectors: usize,
         quantization: crate::index::quantization::QuantizationType,
+        cache: Option<&crate::cache::DiskCache>,
     ) -> Result<Self> {
-        build::load_ivf_flat_from_manifest(store, namespace, segment_id, num_vectors, quantization)
-            .await
+        build::load_ivf_flat_from_manifest(
+            store,
+            namespace,
+            segment_id,
+            num_vectors,
+            quantization,
+            cache,
+        )
+        .await
     }
 }
 
@@ -1,8 +1,10 @@
 //! Search phase for IVF-Flat index.
 //!
 //! 1. Compute distance from query to all centroids.
-//! 2. Select top-`nprobe` closest centroids.
-//! 3. For each selected cluster, fetch and scan all vectors.
+//! 2. Select top-`nprobe` closest centroids, dropping any whose numeric
+//!    attribute ranges can't satisfy a range filter.
+//! 3. For each selected cluster, fetch and scan all vectors (optionally
+//!    capped per cluster, see `max_candidates_per_cluster`).
 //! 4. Apply post-filter with oversampling if a filter is present.
 //! 5. Return sorted top-k results.
 
@@ -12,23 +14,29 @@ use tracing::{debug, warn};
 
 use crate::cache::DiskCache;
 use crate::error::{Result, ZeppelinError};
-use crate::index::distance::compute_distance;
-use crate::index::filter::{evaluate_filter, oversampled_k};
+use crate::index::distance::{compute_distance, compute_distances_batch, ranks_after};
+use crate::index::filter::{evaluate_filter_rows, matches_filter, oversampled_k};
 use crate::index::quantization::QuantizationType;
 use crate::storage::ZeppelinStore;
 use crate::types::{AttributeValue, DistanceMetric, Filter, SearchResult};
 
 use super::build::{attrs_key, cluster_key, deserialize_attrs, deserialize_cluster};
-use super::IvfFlatIndex;
+use super::stats::{attr_stats_key, SegmentAttrStats};
+use super::{IvfFlatIndex, ProbeGap};
 
 use crate::index::bitmap::evaluate::evaluate_filter_bitmap;
 use crate::index::bitmap::{bitmap_key, ClusterBitmapIndex};
 
+/// Number of stored vectors scored together against the query in the flat
+/// scan. Sized so a batch of typical embeddings stays resident in L1/L2.
+const DISTANCE_BATCH_SIZE: usize = 64;
+
 /// A candidate result during search, before final ranking.
 struct Candidate {
     id: String,
     score: f32,
     attributes: Option<HashMap<String, AttributeValue>>,
+    values: Option<Vec<f32>>,
 }
 
 /// Fetch data from cache if available, otherwise from S3.
@@ -68,6 +76,36 @@ pub async fn search_ivf_flat(
     oversample_factor: usize,
     cache: Option<&Arc<DiskCache>>,
 ) -> Result<Vec<SearchResult>> {
+    let (results, _partial) = search_ivf_flat_partial(
+        index,
+        query,
+        top_k,
+        nprobe,
+        filter,
+        distance_metric,
+        store,
+        oversample_factor,
+        cache,
+    )
+    .await?;
+    Ok(results)
+}
+
+/// Like [`search_ivf_flat`], but honours `index.deadline`: once it passes,
+/// the remaining probed clusters are skipped (the closest one is always
+/// scanned). The returned flag is `true` if any cluster was skipped.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn search_ivf_flat_partial(
+    index: &IvfFlatIndex,
+    query: &[f32],
+    top_k: usize,
+    nprobe: usize,
+    filter: Option<&Filter>,
+    distance_metric: DistanceMetric,
+    store: &ZeppelinStore,
+    oversample_factor: usize,
+    cache: Option<&Arc<DiskCache>>,
+) -> Result<(Vec<SearchResult>, bool)> {
     // Validate query dimension.
     if query.len() != index.dim {
         return Err(ZeppelinError::DimensionMismatch {
@@ -77,28 +115,26 @@ pub async fn search_ivf_flat(
     }
 
     if top_k == 0 {
-        return Ok(Vec::new());
+        return Ok((Vec::new(), false));
     }
 
-    let num_clusters = index.centroids.len();
-    let effective_nprobe = nprobe.min(num_clusters);
-
-    // --- Step 1: Rank centroids by distance to query ---
-    let mut centroid_dists: Vec<(usize, f32)> = index
-        .centroids
-        .iter()
-        .enumerate()
-        .map(|(i, c)| (i, compute_distance(query, c, distance_metric)))
-        .collect();
-
-    // Sort ascending (lower distance = closer).
-    centroid_dists.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
-
-    let probe_clusters: Vec<usize> = centroid_dists
-        .iter()
-        .take(effective_nprobe)
-        .map(|(idx, _)| *idx)
-        .collect();
+    let plan = plan_probe(
+        index,
+        query,
+        top_k,
+        nprobe,
+        filter,
+        distance_metric,
+        store,
+        cache,
+    )
+    .await;
+    if !plan.pruned_clusters.is_empty() {
+        crate::metrics::CLUSTERS_PRUNED_TOTAL
+            .with_label_values(&[&index.namespace])
+            .inc_by(plan.pruned_clusters.len() as u64);
+    }
+    let (effective_nprobe, probe_clusters) = (plan.nprobe, plan.probe_clusters);
 
     debug!(
         nprobe = effective_nprobe,
@@ -115,7 +151,7 @@ pub async fn search_ivf_flat(
 
     // --- Step 3: Scan selected clusters ---
     // Use quantized search path if quantization is available.
-    let candidates = match index.quantization {
+    let (candidates, partial) = match index.quantization {
         QuantizationType::Scalar => {
             scan_clusters_sq(
                 index,
@@ -168,23 +204,30 @@ pub async fn search_ivf_flat(
         a.score
             .partial_cmp(&b.score)
             .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.id.cmp(&b.id))
     });
+    if !index.tombstones.is_empty() {
+        sorted.retain(|c| !index.tombstones.contains(&c.id));
+    }
+    if let Some(max) = index.max_distance {
+        sorted.retain(|c| c.score <= max);
+    }
+    if let Some(cursor) = &index.after {
+        sorted.retain(|c| ranks_after(c.score, &c.id, cursor));
+    }
 
     // --- Step 5: Apply post-filter if present ---
     let results: Vec<SearchResult> = if let Some(f) = filter {
         sorted
             .into_iter()
-            .filter(|c| {
-                match &c.attributes {
-                    Some(attrs) => evaluate_filter(f, attrs),
-                    None => false, // No attributes means filter cannot match.
-                }
-            })
+            .filter(|c| matches_filter(f, c.attributes.as_ref()))
             .take(top_k)
             .map(|c| SearchResult {
                 id: c.id,
-                score: c.score,
+                score: distance_metric.score_from_distance(c.score),
                 attributes: c.attributes,
+                rank: None,
+                values: c.values,
             })
             .collect()
     } else {
@@ -193,15 +236,123 @@ pub async fn search_ivf_flat(
             .take(top_k)
             .map(|c| SearchResult {
                 id: c.id,
-                score: c.score,
+                score: distance_metric.score_from_distance(c.score),
                 attributes: c.attributes,
+                rank: None,
+                values: c.values,
             })
             .collect()
     };
 
-    debug!(returned = results.len(), top_k = top_k, "search complete");
+    debug!(
+        returned = results.len(),
+        top_k = top_k,
+        partial,
+        "search complete"
+    );
 
-    Ok(results)
+    Ok((results, partial))
+}
+
+/// Clusters an IVF-Flat search would probe, decided before any cluster
+/// data is read.
+#[derive(Debug, Clone)]
+pub(crate) struct ProbePlan {
+    /// `nprobe` after capping at the cluster count and, with `probe_gap`
+    /// or `adaptive_nprobe_cap`, adapting to the query.
+    pub nprobe: usize,
+    /// Clusters to scan, closest centroid first.
+    pub probe_clusters: Vec<usize>,
+    /// Clusters among the closest `nprobe` dropped by attribute stats.
+    pub pruned_clusters: Vec<usize>,
+}
+
+/// Steps 1–2 of the search: rank centroids, adjust `nprobe` for filter
+/// selectivity and prune clusters by attribute stats. Reads only bitmap and
+/// stats sidecars, so it also backs query explain.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn plan_probe(
+    index: &IvfFlatIndex,
+    query: &[f32],
+    top_k: usize,
+    nprobe: usize,
+    filter: Option<&Filter>,
+    distance_metric: DistanceMetric,
+    store: &ZeppelinStore,
+    cache: Option<&Arc<DiskCache>>,
+) -> ProbePlan {
+    let mut effective_nprobe = nprobe.min(index.centroids.len());
+
+    let mut centroid_dists: Vec<(usize, f32)> = index
+        .centroids
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (i, compute_distance(query, c, distance_metric)))
+        .collect();
+
+    // Sort ascending (lower distance = closer).
+    centroid_dists.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    if let Some(gap) = index.probe_gap {
+        effective_nprobe = gap_nprobe(&centroid_dists, gap);
+    }
+
+    if let (Some(cap), Some(f)) = (index.adaptive_nprobe_cap, filter) {
+        let ranked: Vec<usize> = centroid_dists.iter().map(|(idx, _)| *idx).collect();
+        effective_nprobe = selectivity_nprobe(
+            index,
+            &ranked,
+            effective_nprobe,
+            top_k,
+            cap,
+            f,
+            store,
+            cache,
+        )
+        .await;
+    }
+
+    let mut probe_clusters: Vec<usize> = centroid_dists
+        .iter()
+        .take(effective_nprobe)
+        .map(|(idx, _)| *idx)
+        .collect();
+    let pruned_clusters = match filter {
+        Some(f) => prune_by_attr_stats(index, &mut probe_clusters, f, store, cache).await,
+        None => Vec::new(),
+    };
+
+    ProbePlan {
+        nprobe: effective_nprobe,
+        probe_clusters,
+        pruned_clusters,
+    }
+}
+
+/// How many of the ranked centroids fall within `gap.ratio` of the nearest:
+/// at least one, at most `gap.max_nprobe`.
+fn gap_nprobe(ranked: &[(usize, f32)], gap: ProbeGap) -> usize {
+    let Some(&(_, nearest)) = ranked.first() else {
+        return 0;
+    };
+    // `nearest * ratio` for non-negative distances; taking the slack from
+    // the magnitude keeps it meaningful for negative dot-product distances.
+    let slack = (gap.ratio - 1.0).max(0.0) * nearest.abs();
+    ranked
+        .iter()
+        .take(gap.max_nprobe.max(1))
+        .take_while(|(_, d)| *d - nearest <= slack)
+        .count()
+        .max(1)
+}
+
+/// Whether the soft deadline has passed. Never true before the first
+/// cluster has been scanned, so a search always returns something.
+fn deadline_passed(index: &IvfFlatIndex, clusters_scanned: usize) -> bool {
+    clusters_scanned > 0
+        && index
+            .deadline
+            .is_some_and(|deadline| std::time::Instant::now() >= deadline)
 }
 
 /// Scan clusters using full-precision vectors (no quantization).
@@ -213,7 +364,7 @@ async fn scan_clusters_flat(
     filter: Option<&Filter>,
     store: &ZeppelinStore,
     cache: Option<&Arc<DiskCache>>,
-) -> Result<Vec<Candidate>> {
+) -> Result<(Vec<Candidate>, bool)> {
     let has_bitmaps = !index.bitmap_fields.is_empty();
 
     // Phase 1: Parallel prefetch — all S3 I/O fires concurrently.
@@ -240,7 +391,14 @@ async fn scan_clusters_flat(
 
     // Phase 2: Sequential compute — CPU-bound, no I/O.
     let mut candidates = Vec::new();
-    for (cluster_idx, cluster_res, prefilter, attrs) in prefetched {
+    let mut partial = false;
+    for (scanned, (cluster_idx, cluster_res, prefilter, attrs)) in
+        prefetched.into_iter().enumerate()
+    {
+        if deadline_passed(index, scanned) {
+            partial = true;
+            break;
+        }
         let cluster_data = match cluster_res {
             Ok(data) => data,
             Err(e) => {
@@ -250,25 +408,51 @@ async fn scan_clusters_flat(
         };
         let cluster = deserialize_cluster(&cluster_data)?;
 
-        for (j, vec) in cluster.vectors.iter().enumerate() {
-            if let Some(ref bm) = prefilter {
-                if !bm.contains(j as u32) {
-                    continue;
-                }
+        // Positions that survive the filter, in cluster order. The bitmap
+        // resolves it when available; otherwise evaluate it over the attrs
+        // column so non-matching rows skip the distance computation.
+        let positions: Vec<usize> = match (&prefilter, filter, &attrs) {
+            (Some(bm), _, _) => bm
+                .iter()
+                .map(|p| p as usize)
+                .filter(|&p| p < cluster.vectors.len())
+                .collect(),
+            (None, Some(f), Some(a)) => evaluate_filter_rows(f, a)
+                .into_iter()
+                .enumerate()
+                .filter(|&(j, selected)| selected && j < cluster.vectors.len())
+                .map(|(j, _)| j)
+                .collect(),
+            _ => (0..cluster.vectors.len()).collect(),
+        };
+        let positions = match index.max_candidates_per_cluster {
+            Some(cap) if cap > 0 && positions.len() > cap => {
+                cap_positions(query, &cluster.vectors, positions, cap, distance_metric)
             }
+            _ => positions,
+        };
 
-            let score = compute_distance(query, vec, distance_metric);
-            let vector_attrs = attrs.as_ref().and_then(|a| a.get(j)).cloned().flatten();
+        let mut batch: Vec<&[f32]> = Vec::with_capacity(DISTANCE_BATCH_SIZE);
+        let mut scores = [0.0f32; DISTANCE_BATCH_SIZE];
+        for chunk in positions.chunks(DISTANCE_BATCH_SIZE) {
+            batch.clear();
+            batch.extend(chunk.iter().map(|&j| cluster.vectors[j].as_slice()));
+            let scores = &mut scores[..chunk.len()];
+            compute_distances_batch(query, &batch, distance_metric, scores);
 
-            candidates.push(Candidate {
-                id: cluster.ids[j].clone(),
-                score,
-                attributes: vector_attrs,
-            });
+            for (&j, &score) in chunk.iter().zip(scores.iter()) {
+                let vector_attrs = attrs.as_ref().and_then(|a| a.get(j)).cloned().flatten();
+                candidates.push(Candidate {
+                    id: cluster.ids[j].clone(),
+                    score,
+                    attributes: vector_attrs,
+                    values: index.include_vectors.then(|| cluster.vectors[j].clone()),
+                });
+            }
         }
     }
 
-    Ok(candidates)
+    Ok((candidates, partial))
 }
 
 /// Scan clusters using SQ8 quantized distances, then rerank top candidates
@@ -283,7 +467,7 @@ async fn scan_clusters_sq(
     fetch_k: usize,
     store: &ZeppelinStore,
     cache: Option<&Arc<DiskCache>>,
-) -> Result<Vec<Candidate>> {
+) -> Result<(Vec<Candidate>, bool)> {
     use crate::index::quantization::sq::{
         deserialize_sq_cluster, sq_calibration_key, sq_cluster_key, SqCalibration,
     };
@@ -317,7 +501,12 @@ async fn scan_clusters_sq(
     .await;
 
     let mut coarse_candidates: Vec<(String, f32, usize)> = Vec::new();
-    for (cluster_idx, prefilter, sq_res) in coarse_prefetched {
+    let mut partial = false;
+    for (scanned, (cluster_idx, prefilter, sq_res)) in coarse_prefetched.into_iter().enumerate() {
+        if deadline_passed(index, scanned) {
+            partial = true;
+            break;
+        }
         let sq_data = match sq_res {
             Ok(data) => data,
             Err(e) => {
@@ -340,6 +529,7 @@ async fn scan_clusters_sq(
         };
         let sq_cluster = deserialize_sq_cluster(&sq_data)?;
 
+        let mut cluster_candidates = Vec::new();
         for (j, codes) in sq_cluster.codes.iter().enumerate() {
             if let Some(ref bm) = prefilter {
                 if !bm.contains(j as u32) {
@@ -347,15 +537,20 @@ async fn scan_clusters_sq(
                 }
             }
             let approx_score = calibration.asymmetric_distance(query, codes, distance_metric);
-            coarse_candidates.push((sq_cluster.ids[j].clone(), approx_score, cluster_idx));
+            cluster_candidates.push((sq_cluster.ids[j].clone(), approx_score, cluster_idx));
+        }
+        if let Some(cap) = index.max_candidates_per_cluster {
+            keep_best(&mut cluster_candidates, cap, |c| c.1);
         }
+        coarse_candidates.extend(cluster_candidates);
     }
 
     // Sort by approximate distance and take top candidates for reranking.
     coarse_candidates.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
 
     // Rerank factor: take more candidates than needed for full-precision reranking.
-    let rerank_count = fetch_k * 4; // 4x reranking factor
+    coarse_candidates.retain(|c| !index.tombstones.contains(&c.0));
+    let rerank_count = fetch_k * index.rerank_factor;
     coarse_candidates.truncate(rerank_count);
 
     debug!(
@@ -406,12 +601,13 @@ async fn scan_clusters_sq(
                     id: id.clone(),
                     score,
                     attributes: vector_attrs,
+                    values: index.include_vectors.then(|| cluster.vectors[j].clone()),
                 });
             }
         }
     }
 
-    Ok(candidates)
+    Ok((candidates, partial))
 }
 
 /// Scan clusters using PQ-encoded distances with ADC lookup tables,
@@ -426,7 +622,7 @@ async fn scan_clusters_pq(
     fetch_k: usize,
     store: &ZeppelinStore,
     cache: Option<&Arc<DiskCache>>,
-) -> Result<Vec<Candidate>> {
+) -> Result<(Vec<Candidate>, bool)> {
     use crate::index::quantization::pq::{
         deserialize_pq_cluster, pq_cluster_key, pq_codebook_key, PqCodebook,
     };
@@ -463,7 +659,12 @@ async fn scan_clusters_pq(
     .await;
 
     let mut coarse_candidates: Vec<(String, f32, usize)> = Vec::new();
-    for (cluster_idx, prefilter, pq_res) in coarse_prefetched {
+    let mut partial = false;
+    for (scanned, (cluster_idx, prefilter, pq_res)) in coarse_prefetched.into_iter().enumerate() {
+        if deadline_passed(index, scanned) {
+            partial = true;
+            break;
+        }
         let pq_data = match pq_res {
             Ok(data) => data,
             Err(e) => {
@@ -473,6 +674,7 @@ async fn scan_clusters_pq(
         };
         let pq_cluster = deserialize_pq_cluster(&pq_data)?;
 
+        let mut cluster_candidates = Vec::new();
         for (j, codes) in pq_cluster.codes.iter().enumerate() {
             if let Some(ref bm) = prefilter {
                 if !bm.contains(j as u32) {
@@ -480,14 +682,19 @@ async fn scan_clusters_pq(
                 }
             }
             let approx_score = codebook.adc_distance(&adc_table, codes);
-            coarse_candidates.push((pq_cluster.ids[j].clone(), approx_score, cluster_idx));
+            cluster_candidates.push((pq_cluster.ids[j].clone(), approx_score, cluster_idx));
+        }
+        if let Some(cap) = index.max_candidates_per_cluster {
+            keep_best(&mut cluster_candidates, cap, |c| c.1);
         }
+        coarse_candidates.extend(cluster_candidates);
     }
 
     // Sort and take top candidates for reranking.
     coarse_candidates.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
 
-    let rerank_count = fetch_k * 4;
+    coarse_candidates.retain(|c| !index.tombstones.contains(&c.0));
+    let rerank_count = fetch_k * index.rerank_factor;
     coarse_candidates.truncate(rerank_count);
 
     debug!(
@@ -537,12 +744,187 @@ async fn scan_clusters_pq(
                     id: id.clone(),
                     score,
                     attributes: vector_attrs,
+                    values: index.include_vectors.then(|| cluster.vectors[j].clone()),
                 });
             }
         }
     }
 
-    Ok(candidates)
+    Ok((candidates, partial))
+}
+
+/// Keep only the `cap` lowest-scoring items (unordered). A cap of 0 disables it.
+fn keep_best<T>(items: &mut Vec<T>, cap: usize, score: impl Fn(&T) -> f32) {
+    if cap == 0 || items.len() <= cap {
+        return;
+    }
+    items.select_nth_unstable_by(cap - 1, |a, b| score(a).total_cmp(&score(b)));
+    items.truncate(cap);
+}
+
+/// Narrow `positions` to the `cap` most promising vectors in a cluster.
+///
+/// Candidates are ranked by a cheap distance over the first quarter of the
+/// dimensions, so only `cap` vectors pay for a full-precision distance.
+/// This bounds the work on heavily skewed clusters at some cost in recall.
+/// Returned positions are in cluster order.
+fn cap_positions(
+    query: &[f32],
+    vectors: &[Vec<f32>],
+    positions: Vec<usize>,
+    cap: usize,
+    distance_metric: DistanceMetric,
+) -> Vec<usize> {
+    let prefix = (query.len() / 4).max(1);
+    let mut scored: Vec<(usize, f32)> = positions
+        .into_iter()
+        .map(|j| {
+            let approx = compute_distance(&query[..prefix], &vectors[j][..prefix], distance_metric);
+            (j, approx)
+        })
+        .collect();
+    keep_best(&mut scored, cap, |c| c.1);
+    let mut kept: Vec<usize> = scored.into_iter().map(|(j, _)| j).collect();
+    kept.sort_unstable();
+    kept
+}
+
+/// Drop probed clusters whose numeric attribute ranges can't satisfy
+/// `filter`, returning the dropped ones. Segments built without the stats
+/// sidecar are left untouched.
+async fn prune_by_attr_stats(
+    index: &IvfFlatIndex,
+    probe_clusters: &mut Vec<usize>,
+    filter: &Filter,
+    store: &ZeppelinStore,
+    cache: Option<&Arc<DiskCache>>,
+) -> Vec<usize> {
+    let key = attr_stats_key(&index.namespace, &index.segment_id);
+    let Ok(data) = fetch_with_cache(cache, store, &key).await else {
+        return Vec::new();
+    };
+    let stats = match SegmentAttrStats::from_bytes(&data) {
+        Ok(stats) => stats,
+        Err(e) => {
+            warn!(error = %e, "failed to parse attribute stats");
+            return Vec::new();
+        }
+    };
+
+    let (kept, pruned): (Vec<usize>, Vec<usize>) = probe_clusters
+        .iter()
+        .partition(|&&c| stats.cluster_may_match(c, filter));
+    *probe_clusters = kept;
+    if !pruned.is_empty() {
+        debug!(
+            pruned = pruned.len(),
+            remaining = probe_clusters.len(),
+            "pruned clusters by attribute stats"
+        );
+    }
+    pruned
+}
+
+/// Estimate filter selectivity from the bitmaps of the first `nprobe`
+/// ranked clusters, then widen `nprobe` until roughly `top_k` vectors are
+/// expected to pass the filter, up to `cap`. The sampled bitmaps are the ones
+/// the scan loads next, so with a disk cache they are fetched only once.
+/// Returns `nprobe` unchanged when the segment has no bitmaps or the filter
+/// can't be resolved against them.
+#[allow(clippy::too_many_arguments)]
+async fn selectivity_nprobe(
+    index: &IvfFlatIndex,
+    ranked_clusters: &[usize],
+    nprobe: usize,
+    top_k: usize,
+    cap: usize,
+    filter: &Filter,
+    store: &ZeppelinStore,
+    cache: Option<&Arc<DiskCache>>,
+) -> usize {
+    if index.bitmap_fields.is_empty() || nprobe == 0 {
+        return nprobe;
+    }
+
+    let samples =
+        futures::future::join_all(ranked_clusters.iter().take(nprobe).map(|&cluster_idx| {
+            load_bitmap_index(
+                &index.namespace,
+                &index.segment_id,
+                cluster_idx,
+                store,
+                cache,
+            )
+        }))
+        .await;
+
+    let mut matched = 0u64;
+    let mut total = 0u64;
+    for bitmap_index in samples.into_iter().flatten() {
+        let Some(selected) = evaluate_filter_bitmap(filter, &bitmap_index) else {
+            return nprobe;
+        };
+        matched += selected.len();
+        total += u64::from(bitmap_index.vector_count);
+    }
+    if total == 0 {
+        return nprobe;
+    }
+
+    let selectivity = matched as f64 / total as f64;
+    let avg_cluster_size = index.num_vectors as f64 / ranked_clusters.len().max(1) as f64;
+    let adjusted = nprobe_for_selectivity(
+        nprobe,
+        top_k,
+        selectivity,
+        avg_cluster_size,
+        cap.min(ranked_clusters.len()),
+    );
+    debug!(
+        selectivity,
+        requested = nprobe,
+        adjusted,
+        "adjusted nprobe for filter selectivity"
+    );
+    adjusted
+}
+
+/// Clusters needed to expect `top_k` filter survivors when each holds
+/// `avg_cluster_size` vectors, never below `nprobe` nor above `cap`.
+fn nprobe_for_selectivity(
+    nprobe: usize,
+    top_k: usize,
+    selectivity: f64,
+    avg_cluster_size: f64,
+    cap: usize,
+) -> usize {
+    let cap = cap.max(nprobe);
+    let survivors_per_cluster = selectivity * avg_cluster_size;
+    if survivors_per_cluster <= 0.0 {
+        return cap;
+    }
+    let needed = (top_k as f64 / survivors_per_cluster).ceil() as usize;
+    needed.clamp(nprobe, cap)
+}
+
+/// Load and parse a cluster's bitmap index, or `None` if it is missing or
+/// unreadable.
+async fn load_bitmap_index(
+    namespace: &str,
+    segment_id: &str,
+    cluster_idx: usize,
+    store: &ZeppelinStore,
+    cache: Option<&Arc<DiskCache>>,
+) -> Option<ClusterBitmapIndex> {
+    let bkey = bitmap_key(namespace, segment_id, cluster_idx);
+    let data = fetch_with_cache(cache, store, &bkey).await.ok()?;
+    match ClusterBitmapIndex::from_bytes(&data) {
+        Ok(idx) => Some(idx),
+        Err(e) => {
+            tracing::debug!(cluster = cluster_idx, error = %e, "failed to load bitmap index");
+            None
+        }
+    }
 }
 
 /// Try to load a cluster's bitmap index and evaluate the filter against it.
@@ -562,23 +944,14 @@ async fn try_bitmap_prefilter(
         return None;
     }
 
-    let bkey = bitmap_key(namespace, segment_id, cluster_idx);
-    let data = match fetch_with_cache(cache, store, &bkey).await {
-        Ok(d) => d,
-        Err(_) => return None,
-    };
-    let bitmap_index = match ClusterBitmapIndex::from_bytes(&data) {
-        Ok(idx) => idx,
-        Err(e) => {
-            tracing::debug!(cluster = cluster_idx, error = %e, "failed to load bitmap index");
-            return None;
-        }
-    };
-
+    let bitmap_index = load_bitmap_index(namespace, segment_id, cluster_idx, store, cache).await?;
     evaluate_filter_bitmap(filter, &bitmap_index)
 }
 
 /// Load attribute data for a cluster. Shared helper for all scan methods.
+///
+/// Without a filter the attributes only enrich results, so nothing is
+/// fetched when the index is set to skip them.
 async fn load_attrs(
     index: &IvfFlatIndex,
     cluster_idx: usize,
@@ -586,6 +959,9 @@ async fn load_attrs(
     store: &ZeppelinStore,
     cache: Option<&Arc<DiskCache>>,
 ) -> Option<Vec<Option<HashMap<String, AttributeValue>>>> {
+    if filter.is_none() && index.skip_attributes {
+        return None;
+    }
     let akey = attrs_key(&index.namespace, &index.segment_id, cluster_idx);
     if filter.is_some() {
         match fetch_with_cache(cache, store, &akey).await {
@@ -612,6 +988,8 @@ async fn load_attrs(
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeSet;
+
     use super::*;
 
     fn make_index() -> IvfFlatIndex {
@@ -623,6 +1001,16 @@ mod tests {
             segment_id: "seg_001".to_string(),
             quantization: QuantizationType::None,
             bitmap_fields: Vec::new(),
+            max_candidates_per_cluster: None,
+            deadline: None,
+            adaptive_nprobe_cap: None,
+            probe_gap: None,
+            skip_attributes: false,
+            max_distance: None,
+            after: None,
+            tombstones: BTreeSet::new(),
+            include_vectors: false,
+            rerank_factor: crate::index::quantization::DEFAULT_RERANK_FACTOR,
         }
     }
 
@@ -684,4 +1072,437 @@ mod tests {
             .unwrap();
         assert!(results.is_empty());
     }
+
+    #[test]
+    fn test_candidate_cap_on_skewed_cluster() {
+        use super::super::build::serialize_cluster;
+        use rand::{Rng, SeedableRng};
+
+        let dim = 16;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let query: Vec<f32> = (0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect();
+
+        // Cluster 0 holds nearly everything; "nearest" sits right next to the query.
+        let mut ids: Vec<String> = Vec::new();
+        let mut vectors: Vec<Vec<f32>> = Vec::new();
+        for i in 0..1000 {
+            ids.push(format!("v{i}"));
+            vectors.push((0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect());
+        }
+        ids.push("nearest".to_string());
+        vectors.push(query.iter().map(|x| x + 0.01).collect());
+        let small_ids = vec!["far0".to_string(), "far1".to_string()];
+        let small_vectors = vec![vec![10.0; dim], vec![11.0; dim]];
+
+        let mut index = make_index();
+        index.dim = dim;
+        index.centroids = vec![vec![0.0; dim], vec![10.0; dim]];
+        index.num_vectors = ids.len() + small_ids.len();
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+        rt.block_on(async {
+            let big = serialize_cluster(&ids, &vectors, dim).unwrap();
+            let small = serialize_cluster(&small_ids, &small_vectors, dim).unwrap();
+            store
+                .put(&cluster_key("test_ns", "seg_001", 0), big)
+                .await
+                .unwrap();
+            store
+                .put(&cluster_key("test_ns", "seg_001", 1), small)
+                .await
+                .unwrap();
+        });
+
+        let scan = |index: &IvfFlatIndex| {
+            rt.block_on(scan_clusters_flat(
+                index,
+                &[0, 1],
+                &query,
+                DistanceMetric::Euclidean,
+                None,
+                &store,
+                None,
+            ))
+            .unwrap()
+            .0
+        };
+
+        let uncapped = scan(&index);
+        assert_eq!(uncapped.len(), 1003);
+
+        index.max_candidates_per_cluster = Some(50);
+        let capped = scan(&index);
+        // 50 from the skewed cluster plus both vectors of the small one.
+        assert_eq!(capped.len(), 52);
+        let best = capped
+            .iter()
+            .min_by(|a, b| a.score.total_cmp(&b.score))
+            .unwrap();
+        assert_eq!(best.id, "nearest");
+    }
+
+    #[test]
+    fn test_sq8_rerank_factor_improves_recall() {
+        use crate::config::IndexingConfig;
+        use crate::types::VectorEntry;
+        use rand::{Rng, SeedableRng};
+
+        let dim = 32;
+        let top_k = 10;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+        let vectors: Vec<VectorEntry> = (0..1000)
+            .map(|i| VectorEntry {
+                id: format!("v{i}"),
+                values: (0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect(),
+                attributes: None,
+                sparse: None,
+                named_vectors: None,
+                token_vectors: None,
+                version: None,
+            })
+            .collect();
+        let queries: Vec<Vec<f32>> = (0..20)
+            .map(|_| (0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect();
+        let config = IndexingConfig {
+            default_num_centroids: 4,
+            quantization: QuantizationType::Scalar,
+            bitmap_index: false,
+            ..Default::default()
+        };
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+        let mut index = rt
+            .block_on(super::super::build::build_ivf_flat(
+                &vectors, &config, &store, "test_ns", "seg_sq",
+            ))
+            .unwrap();
+
+        let mut recall_at = |factor: usize| {
+            index.rerank_factor = factor;
+            let mut hits = 0;
+            for query in &queries {
+                // Flat baseline: exact top-k over every vector.
+                let mut exact: Vec<(&str, f32)> = vectors
+                    .iter()
+                    .map(|v| {
+                        let d = compute_distance(query, &v.values, DistanceMetric::Euclidean);
+                        (v.id.as_str(), d)
+                    })
+                    .collect();
+                exact.sort_by(|a, b| a.1.total_cmp(&b.1));
+                let truth: std::collections::HashSet<&str> =
+                    exact.iter().take(top_k).map(|(id, _)| *id).collect();
+
+                let results = rt
+                    .block_on(search_ivf_flat(
+                        &index,
+                        query,
+                        top_k,
+                        4,
+                        None,
+                        DistanceMetric::Euclidean,
+                        &store,
+                        3,
+                        None,
+                    ))
+                    .unwrap();
+                hits += results
+                    .iter()
+                    .filter(|r| truth.contains(r.id.as_str()))
+                    .count();
+            }
+            hits as f64 / (queries.len() * top_k) as f64
+        };
+
+        let low = recall_at(1);
+        let high = recall_at(20);
+        assert!(
+            high > low,
+            "rerank factor 20 ({high}) should beat 1 ({low})"
+        );
+        assert!(high >= 0.99, "recall with rerank factor 20 too low: {high}");
+    }
+
+    #[test]
+    fn test_tombstones_dropped_before_top_k() {
+        use crate::config::IndexingConfig;
+        use crate::types::VectorEntry;
+        use rand::{Rng, SeedableRng};
+
+        let dim = 8;
+        let top_k = 5;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+        let vectors: Vec<VectorEntry> = (0..200)
+            .map(|i| VectorEntry {
+                id: format!("v{i}"),
+                values: (0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect(),
+                attributes: None,
+                sparse: None,
+                named_vectors: None,
+                token_vectors: None,
+                version: None,
+            })
+            .collect();
+        let query = vectors[0].values.clone();
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+
+        for quantization in [QuantizationType::None, QuantizationType::Scalar] {
+            let config = IndexingConfig {
+                default_num_centroids: 4,
+                quantization,
+                bitmap_index: false,
+                ..Default::default()
+            };
+            let segment_id = format!("seg_{quantization:?}");
+            let mut index = rt
+                .block_on(super::super::build::build_ivf_flat(
+                    &vectors,
+                    &config,
+                    &store,
+                    "test_ns",
+                    &segment_id,
+                ))
+                .unwrap();
+            let search = |index: &IvfFlatIndex| {
+                rt.block_on(search_ivf_flat(
+                    index,
+                    &query,
+                    top_k,
+                    4,
+                    None,
+                    DistanceMetric::Euclidean,
+                    &store,
+                    3,
+                    None,
+                ))
+                .unwrap()
+            };
+
+            // Tombstone the whole first page: the search must still fill
+            // top_k from the vectors behind it.
+            let first: BTreeSet<String> = search(&index).into_iter().map(|r| r.id).collect();
+            assert_eq!(first.len(), top_k);
+            index.tombstones = first.clone();
+            let results = search(&index);
+            assert_eq!(results.len(), top_k, "{quantization:?}");
+            assert!(
+                results.iter().all(|r| !first.contains(&r.id)),
+                "{quantization:?} returned a tombstoned ID"
+            );
+        }
+    }
+
+    #[test]
+    fn test_nprobe_for_selectivity() {
+        // 100 vectors per cluster, half pass: one cluster covers top_k=10.
+        assert_eq!(nprobe_for_selectivity(4, 10, 0.5, 100.0, 32), 4);
+        // 1% pass: one survivor per cluster, so 10 clusters.
+        assert_eq!(nprobe_for_selectivity(4, 10, 0.01, 100.0, 32), 10);
+        // Capped.
+        assert_eq!(nprobe_for_selectivity(4, 100, 0.01, 100.0, 32), 32);
+        // Nothing passes in the sample: go to the cap.
+        assert_eq!(nprobe_for_selectivity(4, 10, 0.0, 100.0, 32), 32);
+        // Never below the requested nprobe.
+        assert_eq!(nprobe_for_selectivity(8, 10, 0.01, 100.0, 4), 8);
+    }
+
+    #[test]
+    fn test_selective_filter_widens_nprobe() {
+        use crate::index::bitmap::build::build_cluster_bitmaps;
+
+        let num_clusters = 10;
+        let per_cluster = 20;
+        let mut index = make_index();
+        index.centroids = (0..num_clusters).map(|i| vec![i as f32, 0.0]).collect();
+        index.num_vectors = num_clusters * per_cluster;
+        index.bitmap_fields = vec!["tier".to_string()];
+
+        // One "gold" vector per cluster; the rest are "basic".
+        let attrs: Vec<HashMap<String, AttributeValue>> = (0..per_cluster)
+            .map(|i| {
+                let tier = if i == 0 { "gold" } else { "basic" };
+                HashMap::from([("tier".to_string(), AttributeValue::String(tier.into()))])
+            })
+            .collect();
+        let bitmaps = build_cluster_bitmaps(&attrs.iter().map(Some).collect::<Vec<_>>())
+            .to_bytes()
+            .unwrap();
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+        rt.block_on(async {
+            for c in 0..num_clusters {
+                store
+                    .put(&bitmap_key("test_ns", "seg_001", c), bitmaps.clone())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let ranked: Vec<usize> = (0..num_clusters).collect();
+        let nprobe_for = |tier: &str| {
+            let filter = Filter::Eq {
+                field: "tier".to_string(),
+                value: AttributeValue::String(tier.to_string()),
+            };
+            rt.block_on(selectivity_nprobe(
+                &index,
+                &ranked,
+                2,
+                5,
+                num_clusters,
+                &filter,
+                &store,
+                None,
+            ))
+        };
+
+        let selective = nprobe_for("gold");
+        let loose = nprobe_for("basic");
+        assert_eq!(loose, 2);
+        assert_eq!(selective, 5);
+        assert!(selective > loose);
+    }
+
+    #[test]
+    fn test_gap_nprobe() {
+        let gap = ProbeGap {
+            ratio: 1.5,
+            max_nprobe: 3,
+        };
+        let ranked = [(0, 1.0), (1, 1.4), (2, 1.6), (3, 1.7)];
+        assert_eq!(gap_nprobe(&ranked, gap), 2);
+        // Capped at max_nprobe.
+        let ranked = [(0, 1.0), (1, 1.1), (2, 1.2), (3, 1.3)];
+        assert_eq!(gap_nprobe(&ranked, gap), 3);
+        // Dot-product distances are negative: within half the nearest's
+        // magnitude.
+        let ranked = [(0, -10.0), (1, -6.0), (2, -4.0)];
+        assert_eq!(gap_nprobe(&ranked, gap), 2);
+        assert_eq!(gap_nprobe(&[], gap), 0);
+    }
+
+    #[test]
+    fn test_gap_probing_matches_full_recall_with_fewer_clusters() {
+        use crate::config::IndexingConfig;
+        use crate::types::VectorEntry;
+        use rand::{Rng, SeedableRng};
+
+        let (dim, num_clusters, per_cluster, top_k) = (16, 8, 100, 10);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+        let centers: Vec<Vec<f32>> = (0..num_clusters)
+            .map(|_| (0..dim).map(|_| rng.gen_range(-10.0..10.0)).collect())
+            .collect();
+        let vectors: Vec<VectorEntry> = (0..num_clusters * per_cluster)
+            .map(|i| VectorEntry {
+                id: format!("v{i}"),
+                values: centers[i % num_clusters]
+                    .iter()
+                    .map(|x| x + rng.gen_range(-1.0..1.0))
+                    .collect(),
+                attributes: None,
+                sparse: None,
+                named_vectors: None,
+                token_vectors: None,
+                version: None,
+            })
+            .collect();
+        // Half the queries sit inside a cluster, half on the boundary
+        // between two.
+        let mut queries: Vec<Vec<f32>> = Vec::new();
+        for c in 0..num_clusters {
+            let next = &centers[(c + 1) % num_clusters];
+            queries.push(centers[c].iter().map(|x| x + 0.1).collect());
+            queries.push(
+                centers[c]
+                    .iter()
+                    .zip(next)
+                    .map(|(a, b)| (a + b) / 2.0)
+                    .collect(),
+            );
+        }
+        let config = IndexingConfig {
+            default_num_centroids: num_clusters,
+            bitmap_index: false,
+            // A fixed seed keeps k-means from merging two of the clusters
+            // on some runs, which would cost gap probing recall.
+            kmeans_seed: Some(7),
+            ..Default::default()
+        };
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+        let mut index = rt
+            .block_on(super::super::build::build_ivf_flat(
+                &vectors, &config, &store, "test_ns", "seg_gap",
+            ))
+            .unwrap();
+
+        // (recall, mean clusters probed)
+        let mut run = |probe_gap: Option<ProbeGap>| {
+            index.probe_gap = probe_gap;
+            let (mut hits, mut probed) = (0, 0);
+            for query in &queries {
+                let mut exact: Vec<(&str, f32)> = vectors
+                    .iter()
+                    .map(|v| {
+                        let d = compute_distance(query, &v.values, DistanceMetric::Euclidean);
+                        (v.id.as_str(), d)
+                    })
+                    .collect();
+                exact.sort_by(|a, b| a.1.total_cmp(&b.1));
+                let truth: std::collections::HashSet<&str> =
+                    exact.iter().take(top_k).map(|(id, _)| *id).collect();
+
+                let plan = rt.block_on(plan_probe(
+                    &index,
+                    query,
+                    top_k,
+                    num_clusters,
+                    None,
+                    DistanceMetric::Euclidean,
+                    &store,
+                    None,
+                ));
+                probed += plan.probe_clusters.len();
+                let results = rt
+                    .block_on(search_ivf_flat(
+                        &index,
+                        query,
+                        top_k,
+                        num_clusters,
+                        None,
+                        DistanceMetric::Euclidean,
+                        &store,
+                        3,
+                        None,
+                    ))
+                    .unwrap();
+                hits += results
+                    .iter()
+                    .filter(|r| truth.contains(r.id.as_str()))
+                    .count();
+            }
+            (
+                hits as f64 / (queries.len() * top_k) as f64,
+                probed as f64 / queries.len() as f64,
+            )
+        };
+
+        let (full_recall, full_probed) = run(None);
+        let (gap_recall, gap_probed) = run(Some(ProbeGap {
+            ratio: 1.5,
+            max_nprobe: num_clusters,
+        }));
+        assert_eq!(full_probed, num_clusters as f64);
+        assert_eq!(gap_recall, full_recall, "gap probing lost recall");
+        assert!(
+            gap_probed < 2.0,
+            "gap probing scanned {gap_probed} clusters on average"
+        );
+    }
 }
@@ -0,0 +1,166 @@
+//! Per-cluster min/max statistics for numeric attributes.
+//!
+//! Written once per segment at build time as a JSON sidecar. At search time
+//! a probed cluster whose `[min, max]` for a range-filtered field can't
+//! overlap the filter range is skipped without fetching its data.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::types::{AttributeValue, Filter};
+
+/// S3 key for a segment's attribute statistics sidecar.
+pub fn attr_stats_key(namespace: &str, segment_id: &str) -> String {
+    format!("{namespace}/segments/{segment_id}/attr_stats.json")
+}
+
+/// Inclusive value range of one numeric field within a cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FieldRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Numeric field ranges for every cluster of a segment, indexed by cluster.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SegmentAttrStats {
+    pub clusters: Vec<HashMap<String, FieldRange>>,
+}
+
+impl SegmentAttrStats {
+    /// Compute stats from per-cluster attribute rows.
+    ///
+    /// Only top-level `Integer`/`Float` values count. A field with any
+    /// non-finite value in a cluster is left out for that cluster, since NaN
+    /// satisfies every range predicate.
+    pub fn build(clusters: &[Vec<Option<HashMap<String, AttributeValue>>>]) -> Self {
+        let clusters = clusters
+            .iter()
+            .map(|rows| {
+                let mut ranges: HashMap<String, FieldRange> = HashMap::new();
+                let mut unbounded: Vec<&str> = Vec::new();
+                for attrs in rows.iter().flatten() {
+                    for (field, value) in attrs {
+                        let num = match value {
+                            AttributeValue::Integer(i) => *i as f64,
+                            AttributeValue::Float(f) => *f,
+                            _ => continue,
+                        };
+                        if !num.is_finite() {
+                            unbounded.push(field);
+                            continue;
+                        }
+                        ranges
+                            .entry(field.clone())
+                            .and_modify(|r| {
+                                r.min = r.min.min(num);
+                                r.max = r.max.max(num);
+                            })
+                            .or_insert(FieldRange { min: num, max: num });
+                    }
+                }
+                for field in unbounded {
+                    ranges.remove(field);
+                }
+                ranges
+            })
+            .collect();
+        Self { clusters }
+    }
+
+    pub fn to_bytes(&self) -> Result<bytes::Bytes> {
+        Ok(bytes::Bytes::from(serde_json::to_vec(self)?))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(data)?)
+    }
+
+    /// Whether any vector in `cluster_idx` could satisfy `filter`. Errs on
+    /// the side of `true`: only `Range` leaves on fields with recorded stats
+    /// (and `And`/`Or` over them) can rule a cluster out.
+    pub fn cluster_may_match(&self, cluster_idx: usize, filter: &Filter) -> bool {
+        match self.clusters.get(cluster_idx) {
+            Some(ranges) => may_match(ranges, filter),
+            None => true,
+        }
+    }
+}
+
+fn may_match(ranges: &HashMap<String, FieldRange>, filter: &Filter) -> bool {
+    match filter {
+        Filter::Range {
+            field,
+            gte,
+            lte,
+            gt,
+            lt,
+        } => {
+            let Some(range) = ranges.get(field) else {
+                return true;
+            };
+            !(gte.is_some_and(|b| range.max < b)
+                || gt.is_some_and(|b| range.max <= b)
+                || lte.is_some_and(|b| range.min > b)
+                || lt.is_some_and(|b| range.min >= b))
+        }
+        Filter::And { filters } => filters.iter().all(|f| may_match(ranges, f)),
+        Filter::Or { filters } => filters.iter().any(|f| may_match(ranges, f)),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(field: &str, gte: Option<f64>, lt: Option<f64>) -> Filter {
+        Filter::Range {
+            field: field.to_string(),
+            gte,
+            lte: None,
+            gt: None,
+            lt,
+        }
+    }
+
+    #[test]
+    fn test_cluster_pruning() {
+        let row = |price: AttributeValue| Some(HashMap::from([("price".to_string(), price)]));
+        let stats = SegmentAttrStats::build(&[
+            vec![
+                row(AttributeValue::Integer(1)),
+                row(AttributeValue::Float(9.5)),
+            ],
+            vec![row(AttributeValue::Integer(100)), None],
+            vec![row(AttributeValue::Float(f64::NAN))],
+        ]);
+        assert_eq!(
+            stats.clusters[0]["price"],
+            FieldRange { min: 1.0, max: 9.5 }
+        );
+
+        let cheap = range("price", None, Some(10.0));
+        assert!(stats.cluster_may_match(0, &cheap));
+        assert!(!stats.cluster_may_match(1, &cheap));
+        // NaN matches any range, so that cluster keeps no stats for the field.
+        assert!(stats.cluster_may_match(2, &cheap));
+        // Unknown fields and clusters are never pruned.
+        assert!(stats.cluster_may_match(1, &range("other", Some(0.0), None)));
+        assert!(stats.cluster_may_match(9, &cheap));
+
+        let and = Filter::And {
+            filters: vec![cheap.clone(), range("other", Some(0.0), None)],
+        };
+        assert!(!stats.cluster_may_match(1, &and));
+        let or = Filter::Or {
+            filters: vec![cheap, range("price", Some(50.0), None)],
+        };
+        assert!(stats.cluster_may_match(1, &or));
+
+        let round_trip = SegmentAttrStats::from_bytes(&stats.to_bytes().unwrap()).unwrap();
+        assert_eq!(round_trip.clusters.len(), 3);
+    }
+}
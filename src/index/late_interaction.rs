@@ -0,0 +1,150 @@
+//! Token matrices for late-interaction (ColBERT-style) scoring.
+//!
+//! Documents may carry a bag of token vectors. At build time compaction
+//! packs them per segment partition (IVF cluster, Vamana block or text-only
+//! partition) into one contiguous matrix, so a MaxSim query reads each
+//! partition with a single GET and scores it without per-document objects.
+//! The matrices double as the segment's copy of the token vectors, which
+//! point lookups and merges rebuild from them.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::index::distance::dot_product_distance;
+use crate::types::VectorEntry;
+
+/// S3 key for the token matrix of one segment partition.
+pub fn token_matrix_key(namespace: &str, segment_id: &str, partition: usize) -> String {
+    format!("{namespace}/segments/{segment_id}/tokens_{partition}.bin")
+}
+
+/// MaxSim score of a document: for each query token, the best dot product
+/// with any document token, summed. Higher is better.
+pub fn maxsim<'a>(queries: &[Vec<f32>], tokens: impl Iterator<Item = &'a [f32]> + Clone) -> f32 {
+    queries
+        .iter()
+        .map(|q| {
+            tokens
+                .clone()
+                .map(|t| -dot_product_distance(q, t))
+                .fold(f32::NEG_INFINITY, f32::max)
+        })
+        .filter(|s| s.is_finite())
+        .sum()
+}
+
+/// The token vectors of one partition's documents, packed row-major.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenMatrix {
+    dimensions: usize,
+    ids: Vec<String>,
+    /// Token rows of document `i` are `offsets[i]..offsets[i + 1]`.
+    offsets: Vec<u32>,
+    data: Vec<f32>,
+}
+
+impl TokenMatrix {
+    /// Pack the token vectors of `vectors`; vectors without any are left
+    /// out.
+    pub fn build<'a>(vectors: impl IntoIterator<Item = &'a VectorEntry>) -> Self {
+        let mut matrix = Self {
+            offsets: vec![0],
+            ..Self::default()
+        };
+        for vector in vectors {
+            let Some(tokens) = vector.token_vectors.as_ref().filter(|t| !t.is_empty()) else {
+                continue;
+            };
+            matrix.dimensions = tokens[0].len();
+            matrix.ids.push(vector.id.clone());
+            for token in tokens {
+                matrix.data.extend_from_slice(token);
+            }
+            matrix
+                .offsets
+                .push(matrix.offsets[matrix.offsets.len() - 1] + tokens.len() as u32);
+        }
+        matrix
+    }
+
+    /// Whether no document has token vectors.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    fn rows(&self, doc: usize) -> impl Iterator<Item = &[f32]> + Clone {
+        let (start, end) = (self.offsets[doc] as usize, self.offsets[doc + 1] as usize);
+        self.data[start * self.dimensions..end * self.dimensions].chunks_exact(self.dimensions)
+    }
+
+    /// MaxSim score of every document against `queries`, in matrix order.
+    pub fn score(&self, queries: &[Vec<f32>]) -> Vec<(&str, f32)> {
+        self.ids
+            .iter()
+            .enumerate()
+            .map(|(doc, id)| (id.as_str(), maxsim(queries, self.rows(doc))))
+            .collect()
+    }
+
+    /// The token vectors of those `ids` the matrix holds.
+    pub fn vectors(&self, ids: &HashSet<&str>) -> HashMap<String, Vec<Vec<f32>>> {
+        self.ids
+            .iter()
+            .enumerate()
+            .filter(|(_, id)| ids.contains(id.as_str()))
+            .map(|(doc, id)| (id.clone(), self.rows(doc).map(<[f32]>::to_vec).collect()))
+            .collect()
+    }
+
+    pub fn to_bytes(&self) -> Result<bytes::Bytes> {
+        Ok(bytes::Bytes::from(bincode::serialize(self)?))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Ok(bincode::deserialize(data)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, tokens: Option<Vec<Vec<f32>>>) -> VectorEntry {
+        VectorEntry {
+            id: id.to_string(),
+            values: vec![0.0],
+            attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: tokens,
+            version: None,
+        }
+    }
+
+    #[test]
+    fn test_maxsim_sums_best_token_per_query() {
+        let doc = [vec![1.0, 0.0], vec![0.0, 2.0]];
+        let queries = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]];
+        // 1 + 2 + max(1, 2)
+        let score = maxsim(&queries, doc.iter().map(Vec::as_slice));
+        assert_eq!(score, 5.0);
+    }
+
+    #[test]
+    fn test_matrix_scores_and_roundtrips() {
+        let matrix = TokenMatrix::build(&[
+            entry("a", Some(vec![vec![1.0, 0.0], vec![0.0, 1.0]])),
+            entry("b", None),
+            entry("c", Some(vec![vec![3.0, 0.0]])),
+        ]);
+        let matrix = TokenMatrix::from_bytes(&matrix.to_bytes().unwrap()).unwrap();
+        let queries = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        assert_eq!(matrix.score(&queries), vec![("a", 2.0), ("c", 3.0)]);
+
+        let vectors = matrix.vectors(&HashSet::from(["a", "b"]));
+        assert_eq!(vectors.len(), 1);
+        assert_eq!(vectors["a"], vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+    }
+}
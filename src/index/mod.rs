@@ -8,12 +8,19 @@ pub mod distance;
 pub mod f16_storage;
 pub mod filter;
 pub mod hierarchical;
+pub mod id_map;
 pub mod ivf_flat;
+pub mod late_interaction;
+pub mod named;
 pub mod quantization;
+pub mod sparse;
 pub mod traits;
+pub mod vamana;
+pub mod versions;
 
 // Re-export the core trait and the IVF-Flat implementation at the module level
 // so callers can write `use crate::index::{VectorIndex, IvfFlatIndex}`.
 pub use hierarchical::HierarchicalIndex;
 pub use ivf_flat::IvfFlatIndex;
 pub use traits::VectorIndex;
+pub use vamana::VamanaIndex;
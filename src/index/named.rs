@@ -0,0 +1,141 @@
+//! Per-segment store of named vector fields.
+//!
+//! Written once per segment at build time as a bincode sidecar when any of
+//! its vectors carries a named vector. Queries against a named field scan
+//! that field's vectors exhaustively, and the sidecar doubles as the
+//! segment's copy of the named vectors, which point lookups and merges
+//! rebuild from it.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::index::distance::compute_distance;
+use crate::types::{DistanceMetric, VectorEntry};
+
+/// S3 key for a segment's named vector sidecar.
+pub fn named_vectors_key(namespace: &str, segment_id: &str) -> String {
+    format!("{namespace}/segments/{segment_id}/named_vectors.bin")
+}
+
+/// Vectors by field name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NamedVectorIndex {
+    /// field → (id, values), in segment order.
+    fields: BTreeMap<String, Vec<(String, Vec<f32>)>>,
+}
+
+impl NamedVectorIndex {
+    /// Collect the named vectors of `vectors`; vectors without any are left
+    /// out.
+    pub fn build(vectors: &[VectorEntry]) -> Self {
+        let mut index = Self::default();
+        for vector in vectors {
+            for (field, values) in vector.named_vectors.iter().flatten() {
+                index
+                    .fields
+                    .entry(field.clone())
+                    .or_default()
+                    .push((vector.id.clone(), values.clone()));
+            }
+        }
+        index
+    }
+
+    /// Whether no vector has a named vector.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Result score of `query` against every vector of `field` under
+    /// `metric`, best first.
+    pub fn search(&self, field: &str, query: &[f32], metric: DistanceMetric) -> Vec<(&str, f32)> {
+        let mut results: Vec<(&str, f32)> = self
+            .fields
+            .get(field)
+            .into_iter()
+            .flatten()
+            .map(|(id, values)| {
+                let distance = compute_distance(query, values, metric);
+                (id.as_str(), metric.score_from_distance(distance))
+            })
+            .collect();
+        results.sort_by(|a, b| metric.compare_scores(a.1, b.1).then_with(|| a.0.cmp(b.0)));
+        results
+    }
+
+    /// The named vectors of those `ids` the index holds.
+    pub fn vectors(&self, ids: &HashSet<&str>) -> HashMap<String, BTreeMap<String, Vec<f32>>> {
+        let mut vectors: HashMap<String, BTreeMap<String, Vec<f32>>> = HashMap::new();
+        for (field, entries) in &self.fields {
+            for (id, values) in entries {
+                if ids.contains(id.as_str()) {
+                    vectors
+                        .entry(id.clone())
+                        .or_default()
+                        .insert(field.clone(), values.clone());
+                }
+            }
+        }
+        vectors
+    }
+
+    pub fn to_bytes(&self) -> Result<bytes::Bytes> {
+        Ok(bytes::Bytes::from(bincode::serialize(self)?))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Ok(bincode::deserialize(data)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, named: &[(&str, &[f32])]) -> VectorEntry {
+        VectorEntry {
+            id: id.to_string(),
+            values: vec![0.0],
+            attributes: None,
+            sparse: None,
+            named_vectors: (!named.is_empty()).then(|| {
+                named
+                    .iter()
+                    .map(|(field, values)| (field.to_string(), values.to_vec()))
+                    .collect()
+            }),
+            token_vectors: None,
+            version: None,
+        }
+    }
+
+    #[test]
+    fn test_search_ranks_one_field() {
+        let index = NamedVectorIndex::build(&[
+            entry("a", &[("title", &[1.0, 0.0]), ("image", &[0.0, 1.0])]),
+            entry("b", &[("title", &[0.0, 1.0])]),
+            entry("c", &[]),
+        ]);
+        let results = index.search("title", &[0.0, 2.0], DistanceMetric::Euclidean);
+        let ids: Vec<&str> = results.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec!["b", "a"]);
+        assert!(index
+            .search("missing", &[0.0, 1.0], DistanceMetric::Cosine)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_vectors_roundtrip() {
+        let index = NamedVectorIndex::build(&[
+            entry("a", &[("title", &[1.0, 0.0]), ("image", &[0.5])]),
+            entry("b", &[("title", &[0.0, 1.0])]),
+        ]);
+        let index = NamedVectorIndex::from_bytes(&index.to_bytes().unwrap()).unwrap();
+        let vectors = index.vectors(&HashSet::from(["a", "missing"]));
+        assert_eq!(vectors.len(), 1);
+        assert_eq!(vectors["a"]["title"], vec![1.0, 0.0]);
+        assert_eq!(vectors["a"]["image"], vec![0.5]);
+    }
+}
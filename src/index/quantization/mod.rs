@@ -14,6 +14,10 @@ pub mod sq;
 
 use serde::{Deserialize, Serialize};
 
+/// Default multiple of the fetch size that quantized scans rerank with
+/// full-precision vectors. See `IndexingConfig::rerank_factor`.
+pub const DEFAULT_RERANK_FACTOR: usize = 4;
+
 /// Quantization method selection.
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -0,0 +1,157 @@
+//! Per-segment inverted index over sparse vectors.
+//!
+//! Written once per segment at build time as a JSON sidecar when any of its
+//! vectors has a sparse representation. Sparse queries score every posting
+//! of the query's dimensions, and the index doubles as the segment's copy
+//! of the sparse vectors, which point lookups and merges rebuild from it.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::types::{SparseVector, VectorEntry};
+
+/// S3 key for a segment's sparse index sidecar.
+pub fn sparse_index_key(namespace: &str, segment_id: &str) -> String {
+    format!("{namespace}/segments/{segment_id}/sparse_index.json")
+}
+
+/// Posting lists by sparse dimension. Postings refer to documents by their
+/// position in `ids`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SparseIndex {
+    ids: Vec<String>,
+    /// dimension → (document, weight), documents ascending.
+    postings: BTreeMap<u32, Vec<(u32, f32)>>,
+}
+
+impl SparseIndex {
+    /// Index the sparse representations of `vectors`; vectors without one
+    /// are left out.
+    pub fn build(vectors: &[VectorEntry]) -> Self {
+        let mut index = Self::default();
+        for vector in vectors {
+            let Some(sparse) = &vector.sparse else {
+                continue;
+            };
+            let doc = index.ids.len() as u32;
+            index.ids.push(vector.id.clone());
+            for (&dim, &weight) in sparse.indices.iter().zip(&sparse.values) {
+                index.postings.entry(dim).or_default().push((doc, weight));
+            }
+        }
+        index
+    }
+
+    /// Whether no vector has a sparse representation.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Dot product of `query` with every document sharing a dimension with
+    /// it, best first.
+    pub fn search(&self, query: &SparseVector) -> Vec<(&str, f32)> {
+        let mut scores: HashMap<u32, f32> = HashMap::new();
+        for (dim, weight) in query.indices.iter().zip(&query.values) {
+            for (doc, value) in self.postings.get(dim).into_iter().flatten() {
+                *scores.entry(*doc).or_insert(0.0) += weight * value;
+            }
+        }
+        let mut results: Vec<(&str, f32)> = scores
+            .into_iter()
+            .map(|(doc, score)| (self.ids[doc as usize].as_str(), score))
+            .collect();
+        results.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        results
+    }
+
+    /// The sparse vectors of those `ids` the index holds, rebuilt from the
+    /// posting lists.
+    pub fn vectors(&self, ids: &HashSet<&str>) -> HashMap<String, SparseVector> {
+        let docs: HashMap<u32, &str> = self
+            .ids
+            .iter()
+            .enumerate()
+            .filter(|(_, id)| ids.contains(id.as_str()))
+            .map(|(doc, id)| (doc as u32, id.as_str()))
+            .collect();
+        let mut vectors: HashMap<String, SparseVector> = HashMap::new();
+        if docs.is_empty() {
+            return vectors;
+        }
+        // Postings are walked in dimension order, so indices come out sorted.
+        for (&dim, postings) in &self.postings {
+            for (doc, weight) in postings {
+                if let Some(id) = docs.get(doc) {
+                    let sparse = vectors.entry(id.to_string()).or_default();
+                    sparse.indices.push(dim);
+                    sparse.values.push(*weight);
+                }
+            }
+        }
+        vectors
+    }
+
+    pub fn to_bytes(&self) -> Result<bytes::Bytes> {
+        Ok(bytes::Bytes::from(serde_json::to_vec(self)?))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, sparse: Option<(&[u32], &[f32])>) -> VectorEntry {
+        VectorEntry {
+            id: id.to_string(),
+            values: vec![0.0],
+            attributes: None,
+            sparse: sparse.map(|(indices, values)| SparseVector {
+                indices: indices.to_vec(),
+                values: values.to_vec(),
+            }),
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
+        }
+    }
+
+    #[test]
+    fn test_search_scores_dot_product() {
+        let index = SparseIndex::build(&[
+            entry("a", Some((&[1, 5], &[1.0, 2.0]))),
+            entry("b", Some((&[5, 9], &[0.5, 4.0]))),
+            entry("c", None),
+            entry("d", Some((&[7], &[3.0]))),
+        ]);
+        let query = SparseVector {
+            indices: vec![5, 9],
+            values: vec![1.0, 0.25],
+        };
+        assert_eq!(index.search(&query), vec![("a", 2.0), ("b", 1.5)]);
+    }
+
+    #[test]
+    fn test_vectors_roundtrip() {
+        let index = SparseIndex::build(&[
+            entry("a", Some((&[9, 1], &[1.0, 2.0]))),
+            entry("b", Some((&[3], &[0.5]))),
+        ]);
+        let index = SparseIndex::from_bytes(&index.to_bytes().unwrap()).unwrap();
+        let vectors = index.vectors(&HashSet::from(["a", "missing"]));
+        assert_eq!(vectors.len(), 1);
+        // Rebuilt in dimension order.
+        assert_eq!(
+            vectors["a"],
+            SparseVector {
+                indices: vec![1, 9],
+                values: vec![2.0, 1.0],
+            }
+        );
+    }
+}
@@ -0,0 +1,493 @@
+//! Build phase for the Vamana graph index.
+//!
+//! Follows DiskANN: start from a random regular graph, then for every node
+//! (in random order) greedy-search the graph for it from the medoid and
+//! robust-prune the visited nodes into its new out-edges, adding back
+//! edges as it goes. A first pass prunes with `alpha = 1`, a second with
+//! the configured `alpha`, which keeps longer edges. The graph is then
+//! renumbered in BFS order from the medoid and written in fixed-size blocks.
+
+use std::collections::{BTreeSet, HashSet, VecDeque};
+
+use bytes::Bytes;
+use rand::seq::SliceRandom;
+use tracing::{debug, info};
+
+use crate::config::IndexingConfig;
+use crate::error::{Result, ZeppelinError};
+use crate::index::distance::compute_distance;
+use crate::index::ivf_flat::build::{
+    attrs_key, deserialize_attrs, deserialize_cluster, docs_key, serialize_attrs, serialize_cluster,
+};
+use crate::index::ivf_flat::kmeans::training_metric;
+use crate::storage::ZeppelinStore;
+use crate::types::{DistanceMetric, VectorEntry};
+
+use super::{
+    deserialize_graph_block, graph_block_key, node_record_len, serialize_graph_block,
+    vamana_meta_key, GraphNode, VamanaIndex, VamanaMeta,
+};
+
+/// Build a Vamana graph index from the given vectors.
+///
+/// The graph is built under the namespace's k-means metric (see
+/// [`training_metric`]): dot-product namespaces get a Euclidean graph.
+pub async fn build_vamana(
+    vectors: &[VectorEntry],
+    config: &IndexingConfig,
+    store: &ZeppelinStore,
+    namespace: &str,
+    segment_id: &str,
+) -> Result<VamanaIndex> {
+    if vectors.is_empty() {
+        return Err(ZeppelinError::Index(
+            "cannot build index from empty vector set".into(),
+        ));
+    }
+
+    let dim = vectors[0].values.len();
+    if dim == 0 {
+        return Err(ZeppelinError::Index("vector dimension must be > 0".into()));
+    }
+
+    for v in vectors.iter() {
+        if v.values.len() != dim {
+            return Err(ZeppelinError::DimensionMismatch {
+                expected: dim,
+                actual: v.values.len(),
+            });
+        }
+    }
+
+    let metric = training_metric(config.kmeans_metric.unwrap_or(DistanceMetric::Euclidean));
+    let max_degree = config.vamana_max_degree.max(1);
+    let search_list_size = config.vamana_search_list_size.max(max_degree);
+    let alpha = config.vamana_alpha.max(1.0);
+
+    info!(
+        n = vectors.len(),
+        dim,
+        max_degree,
+        search_list_size,
+        alpha,
+        namespace,
+        segment_id,
+        "building Vamana graph index"
+    );
+
+    let build_start = std::time::Instant::now();
+    let refs: Vec<&[f32]> = vectors.iter().map(|v| v.values.as_slice()).collect();
+    let (graph, medoid) = build_graph(&refs, metric, max_degree, search_list_size, alpha);
+    debug!(
+        build_ms = build_start.elapsed().as_millis() as u64,
+        "graph construction complete"
+    );
+
+    // Renumber in BFS order so neighbors tend to share a block.
+    let order = bfs_order(&graph, medoid);
+    let mut position = vec![0u32; order.len()];
+    for (new, &old) in order.iter().enumerate() {
+        position[old as usize] = new as u32;
+    }
+
+    let nodes_per_block =
+        (config.vamana_block_size_bytes / node_record_len(dim, max_degree)).max(1);
+    let num_blocks = vectors.len().div_ceil(nodes_per_block);
+
+    // CPU phase: serialize all blocks.
+    let mut payloads: Vec<(String, Bytes)> = Vec::with_capacity(num_blocks * 3);
+    for (block_idx, chunk) in order.chunks(nodes_per_block).enumerate() {
+        let nodes: Vec<GraphNode> = chunk
+            .iter()
+            .map(|&old| GraphNode {
+                neighbors: graph[old as usize]
+                    .iter()
+                    .map(|&n| position[n as usize])
+                    .collect(),
+                vector: vectors[old as usize].values.clone(),
+            })
+            .collect();
+        let ids: Vec<String> = chunk
+            .iter()
+            .map(|&old| vectors[old as usize].id.clone())
+            .collect();
+        let attrs: Vec<_> = chunk
+            .iter()
+            .map(|&old| vectors[old as usize].attributes.clone())
+            .collect();
+        let no_values = vec![Vec::new(); ids.len()];
+
+        payloads.push((
+            graph_block_key(namespace, segment_id, block_idx),
+            serialize_graph_block(&nodes, dim, max_degree)?,
+        ));
+        payloads.push((
+            docs_key(namespace, segment_id, block_idx),
+            serialize_cluster(&ids, &no_values, 0)?,
+        ));
+        payloads.push((
+            attrs_key(namespace, segment_id, block_idx),
+            serialize_attrs(&attrs)?,
+        ));
+    }
+
+    // I/O phase: write all blocks in parallel.
+    let results = futures::future::join_all(
+        payloads
+            .iter()
+            .map(|(key, data)| store.put(key, data.clone())),
+    )
+    .await;
+    for result in results {
+        result?;
+    }
+
+    let meta = VamanaMeta {
+        num_nodes: vectors.len(),
+        dim,
+        max_degree,
+        search_list_size,
+        alpha,
+        medoid: position[medoid as usize],
+        nodes_per_block,
+        num_blocks,
+        metric,
+    };
+    let meta_json = serde_json::to_vec(&meta)?;
+    store
+        .put(
+            &vamana_meta_key(namespace, segment_id),
+            Bytes::from(meta_json),
+        )
+        .await?;
+
+    info!(
+        num_nodes = meta.num_nodes,
+        num_blocks, nodes_per_block, "Vamana graph index written"
+    );
+
+    Ok(VamanaIndex {
+        meta,
+        namespace: namespace.to_string(),
+        segment_id: segment_id.to_string(),
+        skip_attributes: false,
+        max_distance: None,
+        after: None,
+        tombstones: BTreeSet::new(),
+    })
+}
+
+/// Build the graph in memory. Returns out-edges per node and the medoid.
+pub(crate) fn build_graph(
+    vectors: &[&[f32]],
+    metric: DistanceMetric,
+    max_degree: usize,
+    search_list_size: usize,
+    alpha: f32,
+) -> (Vec<Vec<u32>>, u32) {
+    let n = vectors.len();
+    let mut rng = rand::thread_rng();
+
+    // Random initial graph: `max_degree` distinct out-edges per node.
+    let degree = max_degree.min(n.saturating_sub(1));
+    let mut graph: Vec<Vec<u32>> = (0..n)
+        .map(|p| {
+            rand::seq::index::sample(&mut rng, n - 1, degree)
+                .into_iter()
+                .map(|i| if i >= p { i + 1 } else { i } as u32)
+                .collect()
+        })
+        .collect();
+
+    let medoid = medoid(vectors, metric);
+    let mut order: Vec<usize> = (0..n).collect();
+    order.shuffle(&mut rng);
+
+    let passes: &[f32] = if alpha > 1.0 { &[1.0, alpha] } else { &[1.0] };
+    for &pass_alpha in passes {
+        for &p in &order {
+            let visited = greedy_search(
+                &graph,
+                vectors,
+                medoid,
+                vectors[p],
+                search_list_size,
+                metric,
+            );
+            let mut candidates = visited;
+            candidates.extend(
+                graph[p]
+                    .iter()
+                    .map(|&c| (compute_distance(vectors[p], vectors[c as usize], metric), c)),
+            );
+            graph[p] = robust_prune(
+                p as u32, candidates, vectors, pass_alpha, max_degree, metric,
+            );
+
+            for j in graph[p].clone() {
+                let j = j as usize;
+                if graph[j].contains(&(p as u32)) {
+                    continue;
+                }
+                if graph[j].len() < max_degree {
+                    graph[j].push(p as u32);
+                } else {
+                    let candidates = graph[j]
+                        .iter()
+                        .copied()
+                        .chain([p as u32])
+                        .map(|c| (compute_distance(vectors[j], vectors[c as usize], metric), c))
+                        .collect();
+                    graph[j] = robust_prune(
+                        j as u32, candidates, vectors, pass_alpha, max_degree, metric,
+                    );
+                }
+            }
+        }
+    }
+
+    (graph, medoid)
+}
+
+/// The node closest to the mean of all vectors.
+fn medoid(vectors: &[&[f32]], metric: DistanceMetric) -> u32 {
+    let dim = vectors[0].len();
+    let mut mean = vec![0.0f32; dim];
+    for v in vectors {
+        for (m, x) in mean.iter_mut().zip(v.iter()) {
+            *m += x;
+        }
+    }
+    for m in &mut mean {
+        *m /= vectors.len() as f32;
+    }
+    vectors
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (compute_distance(&mean, v, metric), i))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, i)| i as u32)
+        .unwrap_or(0)
+}
+
+/// Greedy search of the in-memory graph from `start`, keeping the
+/// `list_size` closest candidates. Returns every expanded node with its
+/// distance to `query`.
+fn greedy_search(
+    graph: &[Vec<u32>],
+    vectors: &[&[f32]],
+    start: u32,
+    query: &[f32],
+    list_size: usize,
+    metric: DistanceMetric,
+) -> Vec<(f32, u32)> {
+    let mut list = vec![(
+        compute_distance(query, vectors[start as usize], metric),
+        start,
+    )];
+    let mut seen: HashSet<u32> = HashSet::from([start]);
+    let mut expanded: HashSet<u32> = HashSet::new();
+    let mut visited = Vec::new();
+
+    while let Some(&(dist, p)) = list.iter().find(|(_, n)| !expanded.contains(n)) {
+        expanded.insert(p);
+        visited.push((dist, p));
+        for &neighbor in &graph[p as usize] {
+            if seen.insert(neighbor) {
+                let d = compute_distance(query, vectors[neighbor as usize], metric);
+                list.push((d, neighbor));
+            }
+        }
+        list.sort_by(|a, b| a.0.total_cmp(&b.0));
+        list.truncate(list_size);
+    }
+    visited
+}
+
+/// Pick at most `max_degree` out-edges for `p` from `candidates` (with
+/// their distances to `p`): repeatedly take the closest remaining one and
+/// drop every candidate it covers, i.e. that is more than `alpha` times
+/// closer to it than to `p`.
+fn robust_prune(
+    p: u32,
+    mut candidates: Vec<(f32, u32)>,
+    vectors: &[&[f32]],
+    alpha: f32,
+    max_degree: usize,
+    metric: DistanceMetric,
+) -> Vec<u32> {
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+    let mut seen = HashSet::from([p]);
+    candidates.retain(|(_, c)| seen.insert(*c));
+
+    let mut out = Vec::with_capacity(max_degree);
+    let mut pool: VecDeque<(f32, u32)> = candidates.into();
+    while let Some((_, best)) = pool.pop_front() {
+        out.push(best);
+        if out.len() >= max_degree {
+            break;
+        }
+        let best_vec = vectors[best as usize];
+        pool.retain(|&(d, c)| alpha * compute_distance(best_vec, vectors[c as usize], metric) > d);
+    }
+    out
+}
+
+/// Node order for block layout: BFS from the medoid, then BFS from each
+/// node it did not reach.
+fn bfs_order(graph: &[Vec<u32>], medoid: u32) -> Vec<u32> {
+    let mut placed = vec![false; graph.len()];
+    let mut order = Vec::with_capacity(graph.len());
+    let roots = std::iter::once(medoid).chain(0..graph.len() as u32);
+    for root in roots {
+        if placed[root as usize] {
+            continue;
+        }
+        placed[root as usize] = true;
+        let mut queue = VecDeque::from([root]);
+        while let Some(p) = queue.pop_front() {
+            order.push(p);
+            for &n in &graph[p as usize] {
+                if !placed[n as usize] {
+                    placed[n as usize] = true;
+                    queue.push_back(n);
+                }
+            }
+        }
+    }
+    order
+}
+
+/// Load an existing Vamana index from S3 (metadata only).
+pub async fn load_vamana(
+    store: &ZeppelinStore,
+    namespace: &str,
+    segment_id: &str,
+) -> Result<VamanaIndex> {
+    let data = store.get(&vamana_meta_key(namespace, segment_id)).await?;
+    let meta: VamanaMeta = serde_json::from_slice(&data)?;
+
+    debug!(
+        namespace,
+        segment_id,
+        num_nodes = meta.num_nodes,
+        num_blocks = meta.num_blocks,
+        "loaded Vamana index metadata"
+    );
+
+    Ok(VamanaIndex {
+        meta,
+        namespace: namespace.to_string(),
+        segment_id: segment_id.to_string(),
+        skip_attributes: false,
+        max_distance: None,
+        after: None,
+        tombstones: BTreeSet::new(),
+    })
+}
+
+/// Load every vector of a Vamana segment, e.g. to merge it into the next
+/// segment during compaction.
+pub async fn load_vamana_vectors(
+    store: &ZeppelinStore,
+    namespace: &str,
+    segment_id: &str,
+) -> Result<Vec<VectorEntry>> {
+    let index = load_vamana(store, namespace, segment_id).await?;
+    let mut vectors = Vec::with_capacity(index.meta.num_nodes);
+    for i in 0..index.meta.num_blocks {
+        let gkey = graph_block_key(namespace, segment_id, i);
+        let ikey = docs_key(namespace, segment_id, i);
+        let akey = attrs_key(namespace, segment_id, i);
+        let (graph_res, ids_res, attrs_res) =
+            tokio::join!(store.get(&gkey), store.get(&ikey), store.get(&akey));
+        let nodes = deserialize_graph_block(&graph_res?)?;
+        let ids = deserialize_cluster(&ids_res?)?.ids;
+        let attrs = match attrs_res {
+            Ok(data) => deserialize_attrs(&data)?,
+            Err(_) => vec![None; ids.len()],
+        };
+        for (j, (id, node)) in ids.into_iter().zip(nodes).enumerate() {
+            vectors.push(VectorEntry {
+                id,
+                values: node.vector,
+                attributes: attrs.get(j).cloned().flatten(),
+                sparse: None,
+                named_vectors: None,
+                token_vectors: None,
+                version: None,
+            });
+        }
+    }
+
+    debug!(
+        segment_id,
+        vectors_loaded = vectors.len(),
+        "loaded vectors from existing Vamana segment"
+    );
+
+    Ok(vectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(n: usize) -> Vec<Vec<f32>> {
+        (0..n)
+            .map(|i| vec![(i % 10) as f32, (i / 10) as f32])
+            .collect()
+    }
+
+    #[test]
+    fn test_build_graph_respects_max_degree() {
+        let vecs = grid(100);
+        let refs: Vec<&[f32]> = vecs.iter().map(|v| v.as_slice()).collect();
+        let (graph, medoid) = build_graph(&refs, DistanceMetric::Euclidean, 6, 20, 1.2);
+        assert_eq!(graph.len(), 100);
+        assert!((medoid as usize) < 100);
+        for (p, edges) in graph.iter().enumerate() {
+            assert!(!edges.is_empty() && edges.len() <= 6, "node {p}: {edges:?}");
+            assert!(!edges.contains(&(p as u32)), "self loop at {p}");
+        }
+    }
+
+    #[test]
+    fn test_build_graph_greedy_search_finds_exact_neighbor() {
+        let vecs = grid(100);
+        let refs: Vec<&[f32]> = vecs.iter().map(|v| v.as_slice()).collect();
+        let (graph, medoid) = build_graph(&refs, DistanceMetric::Euclidean, 8, 24, 1.2);
+        for target in [0usize, 9, 55, 99] {
+            let visited = greedy_search(
+                &graph,
+                &refs,
+                medoid,
+                refs[target],
+                24,
+                DistanceMetric::Euclidean,
+            );
+            assert!(
+                visited
+                    .iter()
+                    .any(|&(d, n)| n as usize == target && d == 0.0),
+                "node {target} not reached"
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_graph_single_node() {
+        let vecs = [vec![1.0f32, 2.0]];
+        let refs: Vec<&[f32]> = vecs.iter().map(|v| v.as_slice()).collect();
+        let (graph, medoid) = build_graph(&refs, DistanceMetric::Euclidean, 4, 8, 1.2);
+        assert_eq!(graph, vec![Vec::<u32>::new()]);
+        assert_eq!(medoid, 0);
+    }
+
+    #[test]
+    fn test_bfs_order_is_a_permutation_starting_at_medoid() {
+        let graph = vec![vec![1], vec![0], vec![3], vec![]];
+        let order = bfs_order(&graph, 2);
+        assert_eq!(order, vec![2, 3, 0, 1]);
+    }
+}
@@ -0,0 +1,359 @@
+//! DiskANN-style Vamana graph index.
+//!
+//! A single-layer proximity graph searched greedily from a medoid, for
+//! segments too large to scan cluster by cluster.
+//!
+//! - Each node keeps at most `max_degree` out-edges, chosen by robust
+//!   pruning so the graph has both short and long-range edges.
+//! - Node records (adjacency list + full-precision vector) have a fixed
+//!   size and are packed into fixed-size blocks on S3, so a node's block
+//!   and offset follow from its index.
+//! - Nodes are laid out in BFS order from the medoid, so neighbors tend to
+//!   share a block and a hop usually touches few blocks.
+//! - IDs and attributes are stored per block in the IVF-Flat formats and
+//!   only fetched for the final candidates.
+
+pub mod build;
+pub mod search;
+
+use std::collections::BTreeSet;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::config::IndexingConfig;
+use crate::error::{Result, ZeppelinError};
+use crate::index::traits::VectorIndex;
+use crate::storage::ZeppelinStore;
+use crate::types::{DistanceMetric, Filter, SearchResult, VectorEntry};
+
+/// Metadata describing the graph layout, stored as JSON on S3.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VamanaMeta {
+    /// Total nodes (vectors) in the graph.
+    pub num_nodes: usize,
+    /// Vector dimensionality.
+    pub dim: usize,
+    /// Maximum out-degree of a node.
+    pub max_degree: usize,
+    /// Candidate list size used to build the graph; also the minimum list
+    /// size at search time.
+    pub search_list_size: usize,
+    /// Pruning factor of the second build pass.
+    pub alpha: f32,
+    /// Index of the search entry point.
+    pub medoid: u32,
+    /// Node records per block. Every block but the last is full.
+    pub nodes_per_block: usize,
+    /// Number of graph blocks.
+    pub num_blocks: usize,
+    /// Metric the graph was built under.
+    pub metric: DistanceMetric,
+}
+
+impl VamanaMeta {
+    /// Block holding node `node`, and the node's position within it.
+    pub fn locate(&self, node: u32) -> (usize, usize) {
+        let node = node as usize;
+        (node / self.nodes_per_block, node % self.nodes_per_block)
+    }
+}
+
+/// A node record decoded from a graph block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphNode {
+    /// Out-neighbors, as global node indexes.
+    pub neighbors: Vec<u32>,
+    pub vector: Vec<f32>,
+}
+
+/// In-memory handle for a Vamana graph index.
+///
+/// Only the metadata is loaded eagerly. Graph blocks, IDs and attributes
+/// are fetched from S3 on demand during search.
+#[derive(Debug, Clone)]
+pub struct VamanaIndex {
+    pub(crate) meta: VamanaMeta,
+    /// Namespace (for S3 key construction).
+    pub(crate) namespace: String,
+    /// Segment ID (for S3 key construction).
+    pub(crate) segment_id: String,
+    /// Skip attribute fetches for unfiltered searches; results then carry
+    /// no attributes. Set by the query path.
+    pub(crate) skip_attributes: bool,
+    /// Drop results whose internal distance exceeds this bound. Set by the
+    /// query path.
+    pub(crate) max_distance: Option<f32>,
+    /// Keep only results ranking after this `(distance, id)` cursor. Set
+    /// by the query path.
+    pub(crate) after: Option<(f32, String)>,
+    /// IDs tombstoned in this segment, dropped before top-k selection. Set
+    /// by the query path.
+    pub(crate) tombstones: BTreeSet<String>,
+}
+
+// ---------------------------------------------------------------------------
+// S3 key helpers
+// ---------------------------------------------------------------------------
+
+/// S3 key for the graph metadata JSON.
+pub fn vamana_meta_key(namespace: &str, segment_id: &str) -> String {
+    format!("{namespace}/segments/{segment_id}/vamana_meta.json")
+}
+
+/// S3 key for graph block `i`.
+pub fn graph_block_key(namespace: &str, segment_id: &str, block_idx: usize) -> String {
+    format!("{namespace}/segments/{segment_id}/graph_{block_idx}.bin")
+}
+
+// IDs and attributes of block `i` reuse the IVF-Flat keys:
+//   docs_{i}.bin (IDs only), attrs_{i}.bin.
+
+// ---------------------------------------------------------------------------
+// Block serialization
+// ---------------------------------------------------------------------------
+
+/// Magic bytes at the start of a graph block.
+const BLOCK_MAGIC: &[u8; 4] = b"ZVAM";
+
+/// Current graph block version.
+const BLOCK_VERSION: u8 = 1;
+
+/// `[magic][version: u8][num_nodes: u32][dim: u32][max_degree: u32]`
+const BLOCK_HEADER_LEN: usize = 4 + 1 + 4 + 4 + 4;
+
+/// Size in bytes of one node record.
+pub fn node_record_len(dim: usize, max_degree: usize) -> usize {
+    4 + 4 * max_degree + 4 * dim
+}
+
+/// Serialize a block of node records.
+///
+/// Layout:
+/// ```text
+/// [magic "ZVAM"][version: u8][num_nodes: u32 LE][dim: u32 LE][max_degree: u32 LE]
+/// For each node:
+///   [degree: u32 LE][neighbor: u32 LE] * max_degree (zero-padded)
+///   [f32 LE] * dim
+/// ```
+pub fn serialize_graph_block(
+    nodes: &[GraphNode],
+    dim: usize,
+    max_degree: usize,
+) -> Result<bytes::Bytes> {
+    let mut buf =
+        Vec::with_capacity(BLOCK_HEADER_LEN + nodes.len() * node_record_len(dim, max_degree));
+    buf.extend_from_slice(BLOCK_MAGIC);
+    buf.push(BLOCK_VERSION);
+    buf.extend_from_slice(&(nodes.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&(dim as u32).to_le_bytes());
+    buf.extend_from_slice(&(max_degree as u32).to_le_bytes());
+
+    for node in nodes {
+        if node.neighbors.len() > max_degree || node.vector.len() != dim {
+            return Err(ZeppelinError::Index(format!(
+                "graph node has {} neighbors and dimension {}, expected at most {max_degree} and {dim}",
+                node.neighbors.len(),
+                node.vector.len()
+            )));
+        }
+        buf.extend_from_slice(&(node.neighbors.len() as u32).to_le_bytes());
+        for slot in 0..max_degree {
+            let neighbor = node.neighbors.get(slot).copied().unwrap_or(0);
+            buf.extend_from_slice(&neighbor.to_le_bytes());
+        }
+        for &val in &node.vector {
+            buf.extend_from_slice(&val.to_le_bytes());
+        }
+    }
+
+    Ok(bytes::Bytes::from(buf))
+}
+
+/// Deserialize a block of node records.
+pub fn deserialize_graph_block(data: &[u8]) -> Result<Vec<GraphNode>> {
+    if data.len() < BLOCK_HEADER_LEN || &data[0..4] != BLOCK_MAGIC {
+        return Err(ZeppelinError::Index("invalid graph block header".into()));
+    }
+    if data[4] != BLOCK_VERSION {
+        return Err(ZeppelinError::Index(format!(
+            "unsupported graph block version {}",
+            data[4]
+        )));
+    }
+    let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    let n = read_u32(5) as usize;
+    let dim = read_u32(9) as usize;
+    let max_degree = read_u32(13) as usize;
+
+    let record_len = node_record_len(dim, max_degree);
+    if data.len() != BLOCK_HEADER_LEN + n * record_len {
+        return Err(ZeppelinError::Index(format!(
+            "graph block is {} bytes, expected {}",
+            data.len(),
+            BLOCK_HEADER_LEN + n * record_len
+        )));
+    }
+
+    let mut nodes = Vec::with_capacity(n);
+    for i in 0..n {
+        let mut offset = BLOCK_HEADER_LEN + i * record_len;
+        let degree = read_u32(offset) as usize;
+        if degree > max_degree {
+            return Err(ZeppelinError::Index(format!(
+                "graph node degree {degree} exceeds max degree {max_degree}"
+            )));
+        }
+        offset += 4;
+        let neighbors = (0..degree).map(|j| read_u32(offset + 4 * j)).collect();
+        offset += 4 * max_degree;
+        let vector = (0..dim)
+            .map(|j| {
+                f32::from_le_bytes(data[offset + 4 * j..offset + 4 * j + 4].try_into().unwrap())
+            })
+            .collect();
+        nodes.push(GraphNode { neighbors, vector });
+    }
+    Ok(nodes)
+}
+
+impl VamanaIndex {
+    /// Number of graph blocks; IDs and attributes are partitioned the same way.
+    pub fn num_blocks(&self) -> usize {
+        self.meta.num_blocks
+    }
+
+    pub fn meta(&self) -> &VamanaMeta {
+        &self.meta
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    pub fn segment_id(&self) -> &str {
+        &self.segment_id
+    }
+
+    /// Load an existing Vamana index from S3 (metadata only).
+    pub async fn load(store: &ZeppelinStore, namespace: &str, segment_id: &str) -> Result<Self> {
+        build::load_vamana(store, namespace, segment_id).await
+    }
+}
+
+#[async_trait]
+impl VectorIndex for VamanaIndex {
+    async fn build(
+        vectors: &[VectorEntry],
+        config: &IndexingConfig,
+        store: &ZeppelinStore,
+        namespace: &str,
+        segment_id: &str,
+    ) -> Result<Self> {
+        build::build_vamana(vectors, config, store, namespace, segment_id).await
+    }
+
+    async fn search(
+        &self,
+        query: &[f32],
+        top_k: usize,
+        nprobe: usize,
+        filter: Option<&Filter>,
+        distance_metric: DistanceMetric,
+        store: &ZeppelinStore,
+    ) -> Result<Vec<SearchResult>> {
+        let oversample_factor = if filter.is_some() { 3 } else { 1 };
+        search::search_vamana(
+            self,
+            query,
+            top_k,
+            nprobe,
+            filter,
+            distance_metric,
+            store,
+            oversample_factor,
+            None,
+        )
+        .await
+    }
+
+    fn vector_count(&self) -> usize {
+        self.meta.num_nodes
+    }
+
+    fn dimension(&self) -> usize {
+        self.meta.dim
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graph_block_roundtrip() {
+        let nodes = vec![
+            GraphNode {
+                neighbors: vec![1, 7],
+                vector: vec![1.0, 2.0, 3.0],
+            },
+            GraphNode {
+                neighbors: vec![],
+                vector: vec![-1.0, 0.5, 0.0],
+            },
+        ];
+        let data = serialize_graph_block(&nodes, 3, 4).unwrap();
+        assert_eq!(data.len(), BLOCK_HEADER_LEN + 2 * node_record_len(3, 4));
+        assert_eq!(deserialize_graph_block(&data).unwrap(), nodes);
+    }
+
+    #[test]
+    fn test_graph_block_rejects_overfull_node() {
+        let nodes = vec![GraphNode {
+            neighbors: vec![1, 2, 3],
+            vector: vec![0.0],
+        }];
+        assert!(serialize_graph_block(&nodes, 1, 2).is_err());
+    }
+
+    #[test]
+    fn test_graph_block_rejects_truncated() {
+        let nodes = vec![GraphNode {
+            neighbors: vec![0],
+            vector: vec![1.0, 2.0],
+        }];
+        let data = serialize_graph_block(&nodes, 2, 2).unwrap();
+        assert!(deserialize_graph_block(&data[..data.len() - 1]).is_err());
+        assert!(deserialize_graph_block(b"nope").is_err());
+    }
+
+    #[test]
+    fn test_locate() {
+        let meta = VamanaMeta {
+            num_nodes: 10,
+            dim: 2,
+            max_degree: 4,
+            search_list_size: 8,
+            alpha: 1.2,
+            medoid: 0,
+            nodes_per_block: 4,
+            num_blocks: 3,
+            metric: DistanceMetric::Euclidean,
+        };
+        assert_eq!(meta.locate(0), (0, 0));
+        assert_eq!(meta.locate(5), (1, 1));
+        assert_eq!(meta.locate(9), (2, 1));
+    }
+
+    #[test]
+    fn test_s3_key_helpers() {
+        assert_eq!(
+            vamana_meta_key("ns1", "seg_001"),
+            "ns1/segments/seg_001/vamana_meta.json"
+        );
+        assert_eq!(
+            graph_block_key("ns1", "seg_001", 3),
+            "ns1/segments/seg_001/graph_3.bin"
+        );
+    }
+}
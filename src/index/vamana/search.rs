@@ -0,0 +1,259 @@
+//! Search phase for the Vamana graph index.
+//!
+//! Beam search from the medoid: each hop expands the `beam_width` closest
+//! unexpanded candidates, fetching the blocks holding their neighbors to
+//! score them, and keeps the closest `search_list_size` candidates. The
+//! search ends when every kept candidate has been expanded. IDs and
+//! attributes are fetched only for the blocks of the final candidates.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+
+use tracing::debug;
+
+use crate::cache::DiskCache;
+use crate::error::{Result, ZeppelinError};
+use crate::index::distance::{compute_distance, ranks_after};
+use crate::index::filter::{matches_filter, oversampled_k};
+use crate::index::ivf_flat::build::{attrs_key, deserialize_attrs, deserialize_cluster, docs_key};
+use crate::storage::ZeppelinStore;
+use crate::types::{DistanceMetric, Filter, SearchResult};
+
+use super::{deserialize_graph_block, graph_block_key, GraphNode, VamanaIndex};
+
+/// Fetch data from cache or S3.
+async fn fetch_with_cache(
+    cache: Option<&Arc<DiskCache>>,
+    store: &ZeppelinStore,
+    key: &str,
+) -> Result<bytes::Bytes> {
+    if let Some(c) = cache {
+        c.get_or_fetch(key, || store.get(key)).await
+    } else {
+        store.get(key).await
+    }
+}
+
+/// Graph blocks fetched so far during one search.
+struct BlockReader<'a> {
+    index: &'a VamanaIndex,
+    store: &'a ZeppelinStore,
+    cache: Option<&'a Arc<DiskCache>>,
+    blocks: HashMap<usize, Vec<GraphNode>>,
+}
+
+impl BlockReader<'_> {
+    /// Fetch every block in `needed` not read yet, in parallel.
+    async fn ensure(&mut self, needed: BTreeSet<usize>) -> Result<()> {
+        let missing: Vec<usize> = needed
+            .into_iter()
+            .filter(|b| !self.blocks.contains_key(b))
+            .collect();
+        let (index, store, cache) = (self.index, self.store, self.cache);
+        let fetched = futures::future::join_all(missing.iter().map(|&b| async move {
+            let key = graph_block_key(&index.namespace, &index.segment_id, b);
+            (b, fetch_with_cache(cache, store, &key).await)
+        }))
+        .await;
+        for (b, data) in fetched {
+            self.blocks.insert(b, deserialize_graph_block(&data?)?);
+        }
+        Ok(())
+    }
+
+    fn node(&self, node: u32) -> Result<&GraphNode> {
+        let (block, offset) = self.index.meta.locate(node);
+        self.blocks
+            .get(&block)
+            .and_then(|nodes| nodes.get(offset))
+            .ok_or_else(|| ZeppelinError::Index(format!("graph node {node} out of range")))
+    }
+}
+
+/// Execute a Vamana beam search.
+///
+/// `beam_width` bounds how many candidates are expanded per hop, and so the
+/// number of block fetches in flight. The candidate list holds
+/// `search_list_size` entries, or the oversampled `top_k` if larger.
+#[allow(clippy::too_many_arguments)]
+pub async fn search_vamana(
+    index: &VamanaIndex,
+    query: &[f32],
+    top_k: usize,
+    beam_width: usize,
+    filter: Option<&Filter>,
+    distance_metric: DistanceMetric,
+    store: &ZeppelinStore,
+    oversample_factor: usize,
+    cache: Option<&Arc<DiskCache>>,
+) -> Result<Vec<SearchResult>> {
+    if query.len() != index.meta.dim {
+        return Err(ZeppelinError::DimensionMismatch {
+            expected: index.meta.dim,
+            actual: query.len(),
+        });
+    }
+
+    if top_k == 0 || index.meta.num_nodes == 0 {
+        return Ok(Vec::new());
+    }
+
+    let fetch_k = if filter.is_some() {
+        oversampled_k(top_k, oversample_factor)
+    } else {
+        top_k
+    };
+    let list_size = index.meta.search_list_size.max(fetch_k);
+    let beam_width = beam_width.max(1);
+
+    let mut reader = BlockReader {
+        index,
+        store,
+        cache,
+        blocks: HashMap::new(),
+    };
+
+    // (distance, node, expanded), kept sorted by distance.
+    let medoid = index.meta.medoid;
+    reader
+        .ensure(BTreeSet::from([index.meta.locate(medoid).0]))
+        .await?;
+    let mut list = vec![(
+        compute_distance(query, &reader.node(medoid)?.vector, distance_metric),
+        medoid,
+        false,
+    )];
+    let mut seen: HashSet<u32> = HashSet::from([medoid]);
+    let mut hops = 0;
+
+    loop {
+        let frontier: Vec<u32> = list
+            .iter_mut()
+            .filter(|(_, _, expanded)| !*expanded)
+            .take(beam_width)
+            .map(|(_, node, expanded)| {
+                *expanded = true;
+                *node
+            })
+            .collect();
+        if frontier.is_empty() {
+            break;
+        }
+        hops += 1;
+
+        let mut discovered = Vec::new();
+        for &node in &frontier {
+            for &neighbor in &reader.node(node)?.neighbors {
+                if seen.insert(neighbor) {
+                    discovered.push(neighbor);
+                }
+            }
+        }
+        reader
+            .ensure(discovered.iter().map(|&n| index.meta.locate(n).0).collect())
+            .await?;
+        for node in discovered {
+            let dist = compute_distance(query, &reader.node(node)?.vector, distance_metric);
+            list.push((dist, node, false));
+        }
+        list.sort_by(|a, b| a.0.total_cmp(&b.0));
+        list.truncate(list_size);
+    }
+
+    debug!(
+        hops,
+        blocks_read = reader.blocks.len(),
+        candidates = list.len(),
+        "Vamana beam search complete"
+    );
+
+    resolve_results(index, list, top_k, filter, distance_metric, store, cache).await
+}
+
+/// Attach IDs (and attributes when needed) to the ranked candidates, apply
+/// the filter and keep `top_k`.
+async fn resolve_results(
+    index: &VamanaIndex,
+    list: Vec<(f32, u32, bool)>,
+    top_k: usize,
+    filter: Option<&Filter>,
+    distance_metric: DistanceMetric,
+    store: &ZeppelinStore,
+    cache: Option<&Arc<DiskCache>>,
+) -> Result<Vec<SearchResult>> {
+    let ns = &index.namespace;
+    let seg = &index.segment_id;
+    let load_attrs = filter.is_some() || !index.skip_attributes;
+    let list: Vec<(f32, u32, bool)> = list
+        .into_iter()
+        .filter(|&(d, _, _)| index.max_distance.is_none_or(|max| d <= max))
+        .filter(|&(d, _, _)| index.after.as_ref().is_none_or(|(after, _)| d >= *after))
+        .collect();
+    let blocks: BTreeSet<usize> = list
+        .iter()
+        .map(|&(_, n, _)| index.meta.locate(n).0)
+        .collect();
+
+    let fetched = futures::future::join_all(blocks.iter().map(|&b| async move {
+        let ids_key = docs_key(ns, seg, b);
+        let akey = attrs_key(ns, seg, b);
+        let attrs = async {
+            if load_attrs {
+                Some(fetch_with_cache(cache, store, &akey).await)
+            } else {
+                None
+            }
+        };
+        let (ids_res, attrs_res) = tokio::join!(fetch_with_cache(cache, store, &ids_key), attrs);
+        (b, ids_res, attrs_res)
+    }))
+    .await;
+
+    let mut ids = HashMap::new();
+    let mut attrs = HashMap::new();
+    for (b, ids_res, attrs_res) in fetched {
+        ids.insert(b, deserialize_cluster(&ids_res?)?.ids);
+        if let Some(attrs_res) = attrs_res {
+            attrs.insert(b, deserialize_attrs(&attrs_res?)?);
+        }
+    }
+
+    let mut results = Vec::with_capacity(top_k);
+    for (dist, node, _) in list {
+        let (block, offset) = index.meta.locate(node);
+        let Some(id) = ids.get(&block).and_then(|ids| ids.get(offset)) else {
+            return Err(ZeppelinError::Index(format!(
+                "graph node {node} has no ID in block {block}"
+            )));
+        };
+        if index.tombstones.contains(id) {
+            continue;
+        }
+        let attributes = attrs
+            .get(&block)
+            .and_then(|a| a.get(offset).cloned())
+            .flatten();
+        if filter.is_some_and(|f| !matches_filter(f, attributes.as_ref())) {
+            continue;
+        }
+        if index
+            .after
+            .as_ref()
+            .is_some_and(|c| !ranks_after(dist, id, c))
+        {
+            continue;
+        }
+        results.push(SearchResult {
+            id: id.clone(),
+            score: distance_metric.score_from_distance(dist),
+            attributes,
+            rank: None,
+            values: None,
+        });
+        if results.len() >= top_k {
+            break;
+        }
+    }
+
+    Ok(results)
+}
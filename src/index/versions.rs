@@ -0,0 +1,79 @@
+//! Per-segment store of vector versions.
+//!
+//! Written once per segment at build time as a bincode sidecar. A vector's
+//! [`version`](crate::types::VectorEntry::version) names the WAL write that
+//! stored it, which compaction removes, so the segment keeps a copy for
+//! conditional upserts and later merges to read back.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::types::VectorEntry;
+
+/// S3 key for a segment's version sidecar.
+pub fn versions_key(namespace: &str, segment_id: &str) -> String {
+    format!("{namespace}/segments/{segment_id}/versions.bin")
+}
+
+/// Versions by vector ID.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VersionIndex {
+    versions: HashMap<String, String>,
+}
+
+impl VersionIndex {
+    /// Collect the versions of `vectors`; vectors without one are left out.
+    pub fn build(vectors: &[VectorEntry]) -> Self {
+        let versions = vectors
+            .iter()
+            .filter_map(|v| Some((v.id.clone(), v.version.clone()?)))
+            .collect();
+        Self { versions }
+    }
+
+    /// Whether no vector has a version.
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty()
+    }
+
+    /// The version of vector `id`, if the index holds one.
+    pub fn version(&self, id: &str) -> Option<&str> {
+        self.versions.get(id).map(String::as_str)
+    }
+
+    pub fn to_bytes(&self) -> Result<bytes::Bytes> {
+        Ok(bytes::Bytes::from(bincode::serialize(self)?))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Ok(bincode::deserialize(data)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, version: Option<&str>) -> VectorEntry {
+        VectorEntry {
+            id: id.to_string(),
+            values: vec![0.0],
+            attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: version.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_versions_roundtrip() {
+        let index = VersionIndex::build(&[entry("a", Some("01J-0")), entry("b", None)]);
+        let index = VersionIndex::from_bytes(&index.to_bytes().unwrap()).unwrap();
+        assert_eq!(index.version("a"), Some("01J-0"));
+        assert_eq!(index.version("b"), None);
+        assert!(VersionIndex::build(&[entry("b", None)]).is_empty());
+    }
+}
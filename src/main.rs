@@ -9,6 +9,7 @@ use zeppelin::compaction::background::compaction_loop;
 use zeppelin::compaction::Compactor;
 use zeppelin::config::Config;
 use zeppelin::namespace::NamespaceManager;
+use zeppelin::server::limits::UpsertLimiter;
 use zeppelin::server::routes::build_router;
 use zeppelin::server::AppState;
 use zeppelin::storage::ZeppelinStore;
@@ -73,7 +74,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Initialize WAL writer and reader
-    let wal_writer = Arc::new(WalWriter::new(store.clone()));
+    let wal_writer = Arc::new(WalWriter::from_config(store.clone(), &config.wal));
     let wal_reader = Arc::new(WalReader::new(store.clone()));
 
     // Initialize disk cache
@@ -101,11 +102,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let state = AppState {
         store,
         namespace_manager,
-        wal_writer,
+        wal_writer: wal_writer.clone(),
         wal_reader,
         config: Arc::new(config.clone()),
         compactor,
         cache,
+        upsert_limiter: Arc::new(UpsertLimiter::new(
+            config.server.max_concurrent_upserts_per_ns,
+        )),
     };
 
     // Build router
@@ -138,9 +142,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with_graceful_shutdown(shutdown_signal)
         .await?;
 
-    tracing::info!("server stopped, shutting down background tasks");
+    tracing::info!("server stopped, flushing pending WAL writes");
+    let shutdown_timeout = Duration::from_secs(config.server.shutdown_timeout_secs);
+    if tokio::time::timeout(shutdown_timeout, wal_writer.shutdown())
+        .await
+        .is_err()
+    {
+        tracing::warn!("timed out waiting for pending WAL writes");
+    }
+
+    tracing::info!("shutting down background tasks");
     let _ = shutdown_tx.send(true);
-    tokio::time::sleep(Duration::from_secs(config.server.shutdown_timeout_secs)).await;
+    tokio::time::sleep(shutdown_timeout).await;
     tracing::info!("zeppelin shutdown complete");
 
     Ok(())
@@ -1,6 +1,6 @@
 use prometheus::{
     register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
-    HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    register_int_gauge_vec, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
 };
 
 lazy_static::lazy_static! {
@@ -80,6 +80,10 @@ lazy_static::lazy_static! {
         "zeppelin_bitmap_fallback_postfilter_total", "Times bitmap fell back to post-filter",
         &["namespace"]
     ).unwrap();
+    pub static ref CLUSTERS_PRUNED_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "zeppelin_clusters_pruned_total", "Probed clusters skipped by attribute min/max stats",
+        &["namespace"]
+    ).unwrap();
 
     // Full-text search metrics
     pub static ref FTS_QUERY_DURATION: HistogramVec = register_histogram_vec!(
@@ -96,6 +100,21 @@ lazy_static::lazy_static! {
         "zeppelin_fts_queries_total", "Total FTS queries",
         &["namespace"]
     ).unwrap();
+    pub static ref UNNORMALIZED_COSINE_UPSERTS_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "zeppelin_unnormalized_cosine_upserts_total",
+        "Upserts to cosine namespaces where many sampled vectors were not unit-norm",
+        &["namespace"]
+    ).unwrap();
+    pub static ref ASYNC_UPSERTS_PENDING: IntGaugeVec = register_int_gauge_vec!(
+        "zeppelin_async_upserts_pending",
+        "Upserts acknowledged with async_ack that have not reached the WAL yet",
+        &["namespace"]
+    ).unwrap();
+    pub static ref ASYNC_UPSERT_FAILURES_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "zeppelin_async_upsert_failures_total",
+        "Upserts acknowledged with async_ack whose WAL append failed",
+        &["namespace"]
+    ).unwrap();
 }
 
 /// RAII guard that decrements an IntGauge on drop.
@@ -127,7 +146,11 @@ pub fn init() {
     lazy_static::initialize(&BITMAP_FIELDS_BUILT);
     lazy_static::initialize(&BITMAP_PREFILTER_USED);
     lazy_static::initialize(&BITMAP_FALLBACK_POSTFILTER);
+    lazy_static::initialize(&CLUSTERS_PRUNED_TOTAL);
     lazy_static::initialize(&FTS_QUERY_DURATION);
     lazy_static::initialize(&FTS_INDEX_BUILD_DURATION);
     lazy_static::initialize(&FTS_QUERIES_TOTAL);
+    lazy_static::initialize(&UNNORMALIZED_COSINE_UPSERTS_TOTAL);
+    lazy_static::initialize(&ASYNC_UPSERTS_PENDING);
+    lazy_static::initialize(&ASYNC_UPSERT_FAILURES_TOTAL);
 }
@@ -1,13 +1,16 @@
+use std::collections::BTreeMap;
+
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
-use tracing::{info, instrument};
+use tracing::{info, instrument, warn};
 
+use crate::config::CompactionOverrides;
 use crate::error::{Result, ZeppelinError};
 use crate::fts::types::FtsFieldConfig;
 use crate::storage::ZeppelinStore;
-use crate::types::{DistanceMetric, IndexType};
+use crate::types::{DistanceMetric, IndexType, NamedVectorConfig, VectorEntry};
 
 /// Metadata for a namespace, stored as meta.json on S3.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,13 +26,123 @@ pub struct NamespaceMetadata {
     /// Empty map means FTS is not enabled for this namespace.
     #[serde(default)]
     pub full_text_search: std::collections::HashMap<String, FtsFieldConfig>,
+    /// Keep this namespace's segment centroids pinned in the disk cache so
+    /// they survive eviction.
+    #[serde(default)]
+    pub cache_pinned: bool,
+    /// Compaction triggers for this namespace, overriding the global
+    /// `[compaction]` config.
+    #[serde(default, skip_serializing_if = "CompactionOverrides::is_empty")]
+    pub compaction: CompactionOverrides,
+    /// Named vector fields documents may carry alongside their primary
+    /// vector, each with its own dimensions and metric. Fixed at creation.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub named_vectors: BTreeMap<String, NamedVectorConfig>,
+    /// Dimensions of documents' token vectors for late-interaction
+    /// (MaxSim) queries; 0 when they're not enabled. Fixed at creation.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub token_dimensions: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
 }
 
 impl NamespaceMetadata {
+    /// Metadata for a new, empty namespace, with every other setting at its
+    /// default. Set fields on it before passing it to
+    /// [`NamespaceManager::create_with_options`].
+    pub fn new(name: &str, dimensions: usize, distance_metric: DistanceMetric) -> Self {
+        let now = Utc::now();
+        Self {
+            name: name.to_string(),
+            dimensions,
+            distance_metric,
+            index_type: IndexType::default(),
+            vector_count: 0,
+            created_at: now,
+            updated_at: now,
+            full_text_search: std::collections::HashMap::new(),
+            cache_pinned: false,
+            compaction: CompactionOverrides::default(),
+            named_vectors: BTreeMap::new(),
+            token_dimensions: 0,
+        }
+    }
+
     pub fn s3_key(namespace: &str) -> String {
         format!("{namespace}/meta.json")
     }
 
+    /// A text-only namespace (`dimensions` 0) stores documents for BM25
+    /// search only: vector queries are rejected and compaction builds no
+    /// vector index.
+    pub fn is_text_only(&self) -> bool {
+        self.dimensions == 0
+    }
+
+    /// Check `entry`'s named vectors against the fields declared on this
+    /// namespace.
+    pub fn validate_named_vectors(&self, entry: &VectorEntry) -> Result<()> {
+        for (field, values) in entry.named_vectors.iter().flatten() {
+            let Some(config) = self.named_vectors.get(field) else {
+                return Err(ZeppelinError::Validation(format!(
+                    "vector '{}': named vector '{field}' is not declared on namespace '{}'",
+                    entry.id, self.name
+                )));
+            };
+            if values.len() != config.dimensions {
+                return Err(ZeppelinError::Validation(format!(
+                    "vector '{}': named vector '{field}' expects {} dimensions, got {}",
+                    entry.id,
+                    config.dimensions,
+                    values.len()
+                )));
+            }
+            if values.iter().any(|v| !v.is_finite()) {
+                return Err(ZeppelinError::Validation(format!(
+                    "vector '{}': named vector '{field}' values must be finite",
+                    entry.id
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check `entry`'s token vectors against this namespace's
+    /// `token_dimensions`.
+    pub fn validate_token_vectors(&self, entry: &VectorEntry) -> Result<()> {
+        let Some(tokens) = &entry.token_vectors else {
+            return Ok(());
+        };
+        let invalid = |msg: String| {
+            Err(ZeppelinError::Validation(format!(
+                "vector '{}': {msg}",
+                entry.id
+            )))
+        };
+        if self.token_dimensions == 0 {
+            return invalid(format!(
+                "namespace '{}' does not accept token_vectors",
+                self.name
+            ));
+        }
+        if tokens.is_empty() {
+            return invalid("token_vectors must not be empty".into());
+        }
+        if let Some(token) = tokens.iter().find(|t| t.len() != self.token_dimensions) {
+            return invalid(format!(
+                "token vectors must have {} dimensions, got {}",
+                self.token_dimensions,
+                token.len()
+            ));
+        }
+        if tokens.iter().flatten().any(|v| !v.is_finite()) {
+            return invalid("token vector values must be finite".into());
+        }
+        Ok(())
+    }
+
     pub fn to_bytes(&self) -> Result<Bytes> {
         let json = serde_json::to_vec_pretty(self)?;
         Ok(Bytes::from(json))
@@ -81,6 +194,21 @@ impl NamespaceManager {
         distance_metric: DistanceMetric,
         full_text_search: std::collections::HashMap<String, FtsFieldConfig>,
     ) -> Result<NamespaceMetadata> {
+        let mut meta = NamespaceMetadata::new(name, dimensions, distance_metric);
+        meta.full_text_search = full_text_search;
+        self.create_with_options(meta).await
+    }
+
+    /// Create a new namespace with all its settings, e.g. index type, FTS
+    /// fields and named vectors, from `meta`. Its metadata is written in
+    /// one piece, so the namespace never exists without any of them.
+    #[instrument(skip(self, meta), fields(namespace = %meta.name))]
+    pub async fn create_with_options(
+        &self,
+        mut meta: NamespaceMetadata,
+    ) -> Result<NamespaceMetadata> {
+        let name = meta.name.clone();
+        let name = name.as_str();
         // Validate namespace name
         if !is_valid_namespace_name(name) {
             return Err(ZeppelinError::Validation(format!(
@@ -89,46 +217,61 @@ impl NamespaceManager {
                 name,
             )));
         }
-        if dimensions == 0 {
+        if meta.dimensions == 0 && meta.full_text_search.is_empty() {
             return Err(ZeppelinError::Validation(
-                "dimensions must be > 0".to_string(),
+                "dimensions must be > 0 unless full_text_search is configured".to_string(),
             ));
         }
 
         // Check if exists
         let key = NamespaceMetadata::s3_key(name);
-        if self.store.exists(&key).await? {
+        if self.exists(name).await? {
             return Err(ZeppelinError::NamespaceAlreadyExists {
                 namespace: name.to_string(),
             });
         }
 
         let now = Utc::now();
-        let meta = NamespaceMetadata {
-            name: name.to_string(),
-            dimensions,
-            distance_metric,
-            index_type: IndexType::default(),
-            vector_count: 0,
-            created_at: now,
-            updated_at: now,
-            full_text_search,
-        };
+        meta.vector_count = 0;
+        meta.created_at = now;
+        meta.updated_at = now;
 
         // Write to S3
         self.store.put(&key, meta.to_bytes()?).await?;
 
-        // Also initialize an empty manifest
+        // Also initialize an empty manifest. If that fails, remove meta.json
+        // again so the namespace does not half-exist and a retry can succeed.
         let manifest = crate::wal::Manifest::new();
-        manifest.write(&self.store, name).await?;
+        if let Err(e) = manifest.write(&self.store, name).await {
+            self.rollback_create(name, &[&key, &crate::wal::Manifest::s3_key(name)])
+                .await;
+            return Err(e);
+        }
 
         // Add to registry
         self.registry.insert(name.to_string(), meta.clone());
 
-        info!(namespace = name, dimensions, %distance_metric, "created namespace");
+        info!(
+            namespace = name,
+            dimensions = meta.dimensions,
+            distance_metric = %meta.distance_metric,
+            "created namespace"
+        );
         Ok(meta)
     }
 
+    /// Best-effort removal of objects written by a failed `create`.
+    ///
+    /// Deletion errors are logged rather than returned so the caller still
+    /// sees the error that caused the rollback.
+    async fn rollback_create(&self, name: &str, keys: &[&str]) {
+        for key in keys {
+            if let Err(e) = self.store.delete(key).await {
+                warn!(namespace = name, key, error = %e, "failed to roll back namespace create");
+            }
+        }
+    }
+
     /// Get namespace metadata.
     #[instrument(skip(self), fields(namespace = name))]
     pub async fn get(&self, name: &str) -> Result<NamespaceMetadata> {
@@ -152,32 +295,84 @@ impl NamespaceManager {
         }
     }
 
-    /// List all namespaces, optionally filtered by prefix.
+    /// Check whether a namespace exists without treating absence as an error.
+    ///
+    /// Consults the in-memory registry first, then falls back to an S3 `HEAD`
+    /// on `meta.json`.
+    #[instrument(skip(self), fields(namespace = name))]
+    pub async fn exists(&self, name: &str) -> Result<bool> {
+        if self.registry.contains_key(name) {
+            return Ok(true);
+        }
+        self.store.exists(&NamespaceMetadata::s3_key(name)).await
+    }
+
+    /// List all namespaces ordered by name, optionally filtered by prefix.
     #[instrument(skip(self))]
     pub async fn list(&self, prefix: Option<&str>) -> Result<Vec<NamespaceMetadata>> {
-        // List all top-level prefixes that have meta.json
-        let list_prefix = prefix.unwrap_or("");
-        let keys = self.store.list_prefix(list_prefix).await?;
+        let names = self.namespace_names(prefix).await?;
+        self.get_existing(&names).await
+    }
 
-        let mut namespaces = Vec::new();
-        let mut seen = std::collections::HashSet::new();
+    /// One page of namespaces ordered by name: up to `limit` of those whose
+    /// name sorts after `after`. Also returns the cursor for the next page,
+    /// the last name on this one, or `None` when nothing follows it.
+    ///
+    /// A page costs one delimiter listing of the bucket's top level (one
+    /// entry per namespace, not per object) plus a `meta.json` read for each
+    /// candidate name after `after` until the page is full.
+    #[instrument(skip(self))]
+    pub async fn list_page(
+        &self,
+        prefix: Option<&str>,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<(Vec<NamespaceMetadata>, Option<String>)> {
+        let names = self.namespace_names(prefix).await?;
+        let start = after.map_or(0, |after| names.partition_point(|n| n.as_str() <= after));
+        let mut page = Vec::new();
+        let mut rest = names[start..].iter();
+        while page.len() < limit {
+            let Some(name) = rest.next() else { break };
+            page.extend(self.get_if_exists(name).await?);
+        }
+        let next = match page.last() {
+            Some(last) if rest.len() > 0 => Some(last.name.clone()),
+            _ => None,
+        };
+        Ok((page, next))
+    }
 
-        for key in &keys {
-            if key.ends_with("/meta.json") {
-                let ns_name = key.trim_end_matches("/meta.json");
-                if seen.insert(ns_name.to_string()) {
-                    match self.get(ns_name).await {
-                        Ok(meta) => namespaces.push(meta),
-                        Err(ZeppelinError::NamespaceNotFound { .. }) => continue,
-                        Err(e) => return Err(e),
-                    }
-                }
-            }
+    /// Sorted candidate namespace names starting with `prefix`: every
+    /// top-level directory in the store. Directories without a `meta.json`
+    /// (e.g. left by an interrupted delete) are dropped when read.
+    async fn namespace_names(&self, prefix: Option<&str>) -> Result<Vec<String>> {
+        let mut names = self.store.list_top_level_dirs().await?;
+        if let Some(prefix) = prefix {
+            names.retain(|name| name.starts_with(prefix));
         }
+        Ok(names)
+    }
 
+    /// Metadata for each of `names`, skipping any without a `meta.json`.
+    async fn get_existing(&self, names: &[String]) -> Result<Vec<NamespaceMetadata>> {
+        let mut namespaces = Vec::with_capacity(names.len());
+        for name in names {
+            namespaces.extend(self.get_if_exists(name).await?);
+        }
         Ok(namespaces)
     }
 
+    /// A namespace's metadata, or `None` if it has no `meta.json` (deleted
+    /// since listing, or never fully created).
+    async fn get_if_exists(&self, name: &str) -> Result<Option<NamespaceMetadata>> {
+        match self.get(name).await {
+            Ok(meta) => Ok(Some(meta)),
+            Err(ZeppelinError::NamespaceNotFound { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Delete a namespace and all its data.
     ///
     /// Deletes manifest first so concurrent queries see `None` manifest → empty
@@ -186,7 +381,7 @@ impl NamespaceManager {
     pub async fn delete(&self, name: &str) -> Result<()> {
         // Verify it exists
         let key = NamespaceMetadata::s3_key(name);
-        if !self.store.exists(&key).await? {
+        if !self.exists(name).await? {
             return Err(ZeppelinError::NamespaceNotFound {
                 namespace: name.to_string(),
             });
@@ -221,14 +416,27 @@ impl NamespaceManager {
 
     /// Update the vector count for a namespace.
     pub async fn update_vector_count(&self, name: &str, count: u64) -> Result<()> {
+        self.update_metadata(name, |meta| meta.vector_count = count)
+            .await
+            .map(|_| ())
+    }
+
+    /// Apply `update` to a namespace's metadata and write it back in one
+    /// put. Named vectors and token dimensions are fixed at creation:
+    /// changing them would strand existing vectors.
+    pub async fn update_metadata(
+        &self,
+        name: &str,
+        update: impl FnOnce(&mut NamespaceMetadata),
+    ) -> Result<NamespaceMetadata> {
         let mut meta = self.get(name).await?;
-        meta.vector_count = count;
+        update(&mut meta);
         meta.updated_at = Utc::now();
 
         let key = NamespaceMetadata::s3_key(name);
         self.store.put(&key, meta.to_bytes()?).await?;
-        self.registry.insert(name.to_string(), meta);
-        Ok(())
+        self.registry.insert(name.to_string(), meta.clone());
+        Ok(meta)
     }
 
     /// Scan S3 for existing namespaces and populate the registry.
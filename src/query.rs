@@ -1,30 +1,186 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
 use std::sync::Arc;
 
-use tracing::{debug, instrument};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument, warn};
+use ulid::Ulid;
 
 use crate::cache::DiskCache;
-use crate::error::Result;
+use crate::error::{Result, ZeppelinError};
 use crate::fts::bm25::Bm25Params;
 use crate::fts::inverted_index::{fts_index_key, InvertedIndex};
 use crate::fts::rank_by::{evaluate_rank_by, RankBy};
 use crate::fts::tokenizer::tokenize_text;
 use crate::fts::types::FtsFieldConfig;
 use crate::fts::wal_scan::wal_bm25_scan;
-use crate::index::distance::compute_distance;
-use crate::index::filter::evaluate_filter;
+use crate::index::distance::{compute_distance, ranks_after};
+use crate::index::filter::{matches_filter, resolve_field};
+use crate::index::id_map::{segment_id_map_key, SegmentIdMap};
+use crate::index::ivf_flat::ProbeGap;
+use crate::index::late_interaction::{maxsim, token_matrix_key, TokenMatrix};
+use crate::index::named::{named_vectors_key, NamedVectorIndex};
+use crate::index::sparse::{sparse_index_key, SparseIndex};
+use crate::index::versions::{versions_key, VersionIndex};
 use crate::index::HierarchicalIndex;
 use crate::index::IvfFlatIndex;
+use crate::index::VamanaIndex;
 use crate::server::handlers::query::QueryResponse;
 use crate::storage::ZeppelinStore;
-use crate::types::{ConsistencyLevel, DistanceMetric, Filter, SearchResult};
-use crate::wal::manifest::SegmentRef;
+use crate::types::{
+    AttributeValue, ConsistencyLevel, DistanceMetric, Filter, SearchResult, SparseVector,
+    UpsertCondition, VectorEntry, VectorId,
+};
+use crate::wal::manifest::{ManifestVersion, SegmentRef};
 use crate::wal::Manifest;
 use crate::wal::WalReader;
+use crate::wal::{AttributePatch, WalFragment};
+
+/// Search-time tuning knobs that are not part of the query itself.
+#[derive(Debug, Clone, Default)]
+pub struct QueryOptions {
+    /// Cap on full-precision distances per probed IVF cluster.
+    /// See `IndexingConfig::max_candidates_per_cluster`.
+    pub max_candidates_per_cluster: Option<usize>,
+    /// Soft deadline for segment search, measured from the start of the
+    /// query. See `QueryConfig::soft_deadline_ms`.
+    pub soft_deadline: Option<std::time::Duration>,
+    /// When set, filtered IVF-Flat searches may raise `nprobe` up to this
+    /// value based on bitmap selectivity. See `IndexingConfig::adaptive_nprobe`.
+    pub adaptive_nprobe_cap: Option<usize>,
+    /// Probe IVF-Flat clusters by centroid distance gap instead of a fixed
+    /// `nprobe`. See `IndexingConfig::probe_gap_ratio`.
+    pub probe_gap: Option<ProbeGap>,
+    /// Don't fetch attribute sidecars for unfiltered segment searches. Set
+    /// when the caller doesn't want attributes back.
+    pub skip_attributes: bool,
+    /// Multiple of the fetch size that quantized scans rerank with
+    /// full-precision vectors. `None` uses the default.
+    /// See `IndexingConfig::rerank_factor`.
+    pub rerank_factor: Option<usize>,
+    /// Keep the active segment's centroids pinned in the disk cache and load
+    /// them from there. Set from `NamespaceMetadata::cache_pinned`.
+    pub cache_pinned: bool,
+    /// With `cache_pinned`, pin cluster vector data too.
+    /// See `CacheConfig::pin_clusters`.
+    pub pin_clusters: bool,
+    /// Score only live vectors in uncompacted WAL fragments and skip the
+    /// segment. The WAL is scanned whatever the consistency level.
+    pub wal_only: bool,
+    /// Let a segment result beat a WAL result for the same ID when the WAL
+    /// write is older. See `QueryConfig::dedupe_by_write_time`.
+    pub dedupe_by_write_time: bool,
+    /// `next_sequence` of a manifest known to be committed, e.g. by this
+    /// node's last write. Strong queries re-read an older manifest up to
+    /// `manifest_read_retries` times. See `StorageConfig::read_after_write_retries`.
+    pub min_manifest_sequence: Option<u64>,
+    pub manifest_read_retries: u32,
+    /// Rerank an oversampled candidate set with Maximal Marginal Relevance
+    /// before truncating to `top_k`; see [`mmr_rerank`].
+    pub diversify: Option<Diversify>,
+    /// Keep at most `group_size` results per value of an attribute, picked
+    /// from an oversampled candidate set; see [`collapse_groups`]. Needs
+    /// attributes, so `skip_attributes` must be off.
+    pub group_by: Option<GroupBy>,
+    /// Drop results farther than this from the query, in the index's
+    /// lower-is-closer distance (negated dot product for `dot_product`).
+    /// Applied by the WAL scan and every segment index alike.
+    pub max_distance: Option<f32>,
+    /// Return each result's stored vector in `SearchResult::values`. The WAL
+    /// scan and IVF-Flat and hierarchical scans attach the vectors they
+    /// scored; the rest are fetched after the merge.
+    pub include_vectors: bool,
+    /// Resume after a previous page: keep only results ranking after this
+    /// `(distance, id)`, in the same lower-is-closer distance as
+    /// `max_distance`. Ties on distance are broken by ID; see [`QueryCursor`].
+    pub after: Option<(f32, String)>,
+    /// Loads shared with the other queries of a batch.
+    pub shared: Option<Arc<SharedLoads>>,
+}
+
+/// Reads shared by the queries of a batch: the manifest, and the centroids
+/// of each IVF-Flat segment. Whichever query needs one first loads it; the
+/// rest wait for that load instead of issuing their own.
+#[derive(Debug, Default)]
+pub struct SharedLoads {
+    manifest: tokio::sync::OnceCell<(Manifest, ManifestVersion)>,
+    #[allow(clippy::type_complexity)]
+    ivf_indexes: std::sync::Mutex<HashMap<String, Arc<tokio::sync::OnceCell<IvfFlatIndex>>>>,
+}
+
+impl SharedLoads {
+    /// The IVF-Flat index of `segment_id`, loaded by `load` on first use.
+    /// Each caller gets its own copy to set per-query knobs on.
+    async fn ivf_index<F>(&self, segment_id: &str, load: F) -> Result<IvfFlatIndex>
+    where
+        F: std::future::Future<Output = Result<IvfFlatIndex>>,
+    {
+        let cell = self
+            .ivf_indexes
+            .lock()
+            .expect("shared loads lock poisoned")
+            .entry(segment_id.to_string())
+            .or_default()
+            .clone();
+        Ok(cell.get_or_try_init(|| load).await?.clone())
+    }
+}
+
+/// Position of the last result of a page, handed out as an opaque
+/// `next_cursor` string: the score's bits as 8 hex digits, the number of
+/// results on this and earlier pages in hex, a `:`, then the ID. Results
+/// are ordered by score, then ID, so resuming after both skips exactly the
+/// results already returned; `offset` keeps ranks counting across pages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryCursor {
+    pub score: f32,
+    pub id: String,
+    pub offset: usize,
+}
+
+impl QueryCursor {
+    pub fn encode(&self) -> String {
+        format!("{:08x}{:x}:{}", self.score.to_bits(), self.offset, self.id)
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self> {
+        let invalid = || ZeppelinError::Validation("invalid cursor".into());
+        let (bits, offset, id) = match (cursor.get(..8), cursor.get(8..)) {
+            (Some(bits), Some(rest)) => match rest.split_once(':') {
+                Some((offset, id)) if !offset.is_empty() && !id.is_empty() => (bits, offset, id),
+                _ => return Err(invalid()),
+            },
+            _ => return Err(invalid()),
+        };
+        let score = u32::from_str_radix(bits, 16)
+            .map(f32::from_bits)
+            .map_err(|_| invalid())?;
+        if !score.is_finite() {
+            return Err(invalid());
+        }
+        let offset = usize::from_str_radix(offset, 16).map_err(|_| invalid())?;
+        Ok(Self {
+            score,
+            id: id.to_string(),
+            offset,
+        })
+    }
+
+    /// The cursor as `QueryOptions::after`.
+    pub fn after(&self, metric: DistanceMetric) -> (f32, String) {
+        (metric.score_from_distance(self.score), self.id.clone())
+    }
+}
+
+/// Warning attached to a response whose segment search stopped early at
+/// the soft deadline.
+pub const PARTIAL_RESULTS_WARNING: &str = "partial_results";
+
+/// How many times a query is re-run on a newer manifest when it fails while
+/// the manifest is being replaced, before returning `IndexUpdating`.
+const MANIFEST_CHANGE_RETRIES: usize = 1;
 
 /// Execute a query against a namespace, combining WAL scan and segment search.
 #[allow(clippy::too_many_arguments)]
-#[instrument(skip(store, wal_reader, query, filter, cache), fields(namespace = namespace))]
 pub async fn execute_query(
     store: &ZeppelinStore,
     wal_reader: &WalReader,
@@ -38,29 +194,181 @@ pub async fn execute_query(
     oversample_factor: usize,
     cache: Option<&Arc<DiskCache>>,
 ) -> Result<QueryResponse> {
-    let manifest = Manifest::read(store, namespace).await?.unwrap_or_default();
+    execute_query_with_options(
+        store,
+        wal_reader,
+        namespace,
+        query,
+        top_k,
+        nprobe,
+        filter,
+        consistency,
+        distance_metric,
+        oversample_factor,
+        cache,
+        &QueryOptions::default(),
+    )
+    .await
+}
+
+/// Execute a query with explicit search-time options.
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(store, wal_reader, query, filter, cache, options), fields(namespace = namespace))]
+pub async fn execute_query_with_options(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    query: &[f32],
+    top_k: usize,
+    nprobe: usize,
+    filter: Option<&Filter>,
+    consistency: ConsistencyLevel,
+    distance_metric: DistanceMetric,
+    oversample_factor: usize,
+    cache: Option<&Arc<DiskCache>>,
+    options: &QueryOptions,
+) -> Result<QueryResponse> {
+    let deadline = options.soft_deadline.map(|d| std::time::Instant::now() + d);
+    // Eventual reads tolerate a stale manifest by definition.
+    let min_sequence = if consistency == ConsistencyLevel::Strong || options.wal_only {
+        options.min_manifest_sequence.unwrap_or(0)
+    } else {
+        0
+    };
+    let read_manifest = || async {
+        Ok::<_, ZeppelinError>(
+            Manifest::read_versioned_at_least(
+                store,
+                namespace,
+                min_sequence,
+                options.manifest_read_retries,
+            )
+            .await?
+            .unwrap_or_else(|| (Manifest::default(), ManifestVersion(None))),
+        )
+    };
+    let mut retries = 0;
+    loop {
+        // Only the first attempt uses a batch's shared manifest, and only
+        // if it is recent enough for this query; a retry needs the one that
+        // replaced it.
+        let shared = match options.shared.as_ref().filter(|_| retries == 0) {
+            Some(shared) => Some(shared.manifest.get_or_try_init(read_manifest).await?),
+            None => None,
+        };
+        let fresh;
+        let (manifest, version) = match shared.filter(|(m, _)| m.next_sequence >= min_sequence) {
+            Some(snapshot) => snapshot,
+            None => {
+                fresh = read_manifest().await?;
+                &fresh
+            }
+        };
+        let result = execute_on_manifest(
+            store,
+            wal_reader,
+            namespace,
+            manifest,
+            query,
+            top_k,
+            nprobe,
+            filter,
+            consistency,
+            distance_metric,
+            oversample_factor,
+            cache,
+            options,
+            deadline,
+        )
+        .await;
+        let err = match result {
+            Ok(response) => return Ok(response),
+            Err(e) => e,
+        };
+
+        // A failure while compaction swaps the manifest is most likely a read
+        // of a segment or fragment it just replaced: retry on the new
+        // manifest, then give up with a transient error.
+        if !Manifest::changed_since(store, namespace, manifest, version).await? {
+            return Err(err);
+        }
+        if retries >= MANIFEST_CHANGE_RETRIES {
+            warn!(error = %err, "manifest kept changing during query");
+            return Err(ZeppelinError::IndexUpdating {
+                namespace: namespace.to_string(),
+            });
+        }
+        retries += 1;
+        debug!(error = %err, retries, "manifest changed during query, retrying");
+    }
+}
 
+/// Run a query against one manifest snapshot.
+#[allow(clippy::too_many_arguments)]
+async fn execute_on_manifest(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    manifest: &Manifest,
+    query: &[f32],
+    top_k: usize,
+    nprobe: usize,
+    filter: Option<&Filter>,
+    consistency: ConsistencyLevel,
+    distance_metric: DistanceMetric,
+    oversample_factor: usize,
+    cache: Option<&Arc<DiskCache>>,
+    options: &QueryOptions,
+    deadline: Option<std::time::Instant>,
+) -> Result<QueryResponse> {
     let mut scanned_fragments = 0;
     let mut scanned_segments = 0;
+    let mut partial = false;
+    // Diversification and grouping pick `top_k` from a larger candidate set.
+    let final_k = top_k;
+    let top_k = if options.diversify.is_some() || options.group_by.is_some() {
+        crate::index::filter::oversampled_k(top_k, oversample_factor)
+    } else {
+        top_k
+    };
 
     // WAL scan (always for Strong, never for Eventual)
     // Uses the manifest we already read for snapshot consistency — avoids re-reading
     // a newer manifest whose fragments may have been deleted by compaction.
     let wal_start = std::time::Instant::now();
+    let mut deleted_ids = HashSet::new();
+    let mut wal_ids = HashMap::new();
+    let consistency = if options.wal_only {
+        ConsistencyLevel::Strong
+    } else {
+        consistency
+    };
     let wal_results = match consistency {
         ConsistencyLevel::Strong => {
-            let (results, frag_count) = wal_scan(
+            let (results, ids, frag_count) = wal_scan(
+                store,
                 wal_reader,
                 namespace,
-                &manifest,
+                manifest,
                 query,
+                top_k,
                 filter,
                 distance_metric,
+                options.max_distance,
+                options.after.as_ref(),
+                options.include_vectors,
             )
             .await?;
             scanned_fragments = frag_count;
+            wal_ids = ids;
             results
         }
+        ConsistencyLevel::EventualWithDeletes => {
+            let (ids, frag_count) = scan_wal_deletes(wal_reader, namespace, manifest).await?;
+            scanned_fragments = frag_count;
+            deleted_ids = ids;
+            Vec::new()
+        }
         ConsistencyLevel::Eventual => Vec::new(),
     };
     let wal_duration = wal_start.elapsed();
@@ -70,37 +378,45 @@ pub async fn execute_query(
         "query phase: WAL scan"
     );
 
-    // Segment search
+    // Segment search, over every live segment (several under tiered
+    // compaction). Each ID is live in at most one of them.
     let segment_start = std::time::Instant::now();
-    let segment_results = if let Some(ref segment_id) = manifest.active_segment {
-        // Look up the full SegmentRef from the manifest.
-        let segment_ref = manifest
-            .segments
-            .iter()
-            .find(|s| s.id == *segment_id)
-            .cloned();
-        if let Some(seg_ref) = segment_ref {
-            let results = segment_search(
-                store,
-                namespace,
-                &seg_ref,
-                query,
-                top_k,
-                nprobe,
-                filter,
-                distance_metric,
-                oversample_factor,
-                cache,
-            )
-            .await?;
-            scanned_segments = 1;
-            results
-        } else {
-            Vec::new()
-        }
-    } else {
+    let live_segments = if options.wal_only {
         Vec::new()
+    } else {
+        manifest.live_segments()
     };
+    let mut segment_results = Vec::new();
+    for seg_ref in live_segments {
+        // Over-fetch so dropping deleted IDs can't starve top_k; tombstoned
+        // IDs are dropped inside the segment search.
+        let segment_top_k = top_k + deleted_ids.len();
+        let (results, segment_partial) = segment_search(
+            store,
+            namespace,
+            seg_ref,
+            query,
+            segment_top_k,
+            nprobe,
+            filter,
+            distance_metric,
+            oversample_factor,
+            cache,
+            options,
+            deadline,
+        )
+        .await?;
+        segment_results.extend(results);
+        scanned_segments += 1;
+        partial |= segment_partial;
+    }
+    if scanned_segments > 1 {
+        segment_results.sort_by(|a, b| {
+            distance_metric
+                .compare_scores(a.score, b.score)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+    }
     let segment_duration = segment_start.elapsed();
     debug!(
         segment_duration_ms = segment_duration.as_millis() as u64,
@@ -110,7 +426,69 @@ pub async fn execute_query(
 
     // Merge results
     let merge_start = std::time::Instant::now();
-    let results = merge_results(wal_results, segment_results, top_k, consistency);
+    // The segment holds writes up to the compaction watermark, so with
+    // write-time dedupe an older WAL write of the same ID loses to it.
+    let segment_written_at = manifest
+        .compaction_watermark
+        .filter(|_| options.dedupe_by_write_time && scanned_segments > 0);
+    let mut results = merge_results(
+        wal_results,
+        segment_results,
+        top_k,
+        consistency,
+        distance_metric,
+        &wal_ids,
+        &deleted_ids,
+        segment_written_at,
+    );
+    if let Some(group_by) = &options.group_by {
+        // Diversification still needs the whole (collapsed) candidate set.
+        let limit = if options.diversify.is_some() {
+            top_k
+        } else {
+            final_k
+        };
+        results = collapse_groups(results, group_by, limit);
+    }
+    let mut vectors: HashMap<String, Vec<f32>> = HashMap::new();
+    if let Some(diversify) = options.diversify {
+        let ids: Vec<String> = results.iter().map(|r| r.id.clone()).collect();
+        vectors = fetch_vectors_on_manifest(store, wal_reader, namespace, manifest, &ids)
+            .await?
+            .into_iter()
+            .map(|v| (v.id, v.values))
+            .collect();
+        results = mmr_rerank(
+            results,
+            &vectors,
+            query,
+            distance_metric,
+            diversify.lambda,
+            final_k,
+        );
+    }
+    if options.include_vectors {
+        // Results the scans attached no vector to, e.g. from a Vamana
+        // segment, take it from the MMR fetch or a fresh one.
+        let missing: Vec<String> = results
+            .iter()
+            .filter(|r| r.values.is_none() && !vectors.contains_key(&r.id))
+            .map(|r| r.id.clone())
+            .collect();
+        if !missing.is_empty() {
+            vectors.extend(
+                fetch_vectors_on_manifest(store, wal_reader, namespace, manifest, &missing)
+                    .await?
+                    .into_iter()
+                    .map(|v| (v.id, v.values)),
+            );
+        }
+        for result in &mut results {
+            if result.values.is_none() {
+                result.values = vectors.remove(&result.id);
+            }
+        }
+    }
     let merge_duration = merge_start.elapsed();
     debug!(
         merge_duration_ms = merge_duration.as_millis() as u64,
@@ -122,27 +500,150 @@ pub async fn execute_query(
         results,
         scanned_fragments,
         scanned_segments,
+        warnings: if partial {
+            vec![PARTIAL_RESULTS_WARNING.to_string()]
+        } else {
+            Vec::new()
+        },
+        aggregations: BTreeMap::new(),
+        next_cursor: None,
+    })
+}
+
+/// Execute a multi-vector (late-interaction, MaxSim-style) query.
+///
+/// Documents store a single vector, so each document's score is the
+/// weighted sum over query vectors of its score for that query vector:
+/// `score(d) = Σ wᵢ · score(qᵢ, d)`, ordered like single-vector scores for
+/// the metric. This is MaxSim with one stored vector per document.
+/// Candidates come from a normal ANN query per query vector, run
+/// concurrently against one manifest snapshot and fetching
+/// `top_k × oversample_factor` each. Every candidate is then scored
+/// exactly against every query vector from its stored vector, so a
+/// document missing from one query vector's list isn't penalised.
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(store, wal_reader, queries, weights, filter, cache, options), fields(namespace = namespace, query_vectors = queries.len()))]
+pub async fn execute_multi_vector_query(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    queries: &[Vec<f32>],
+    weights: Option<&[f32]>,
+    top_k: usize,
+    nprobe: usize,
+    filter: Option<&Filter>,
+    consistency: ConsistencyLevel,
+    distance_metric: DistanceMetric,
+    oversample_factor: usize,
+    cache: Option<&Arc<DiskCache>>,
+    options: &QueryOptions,
+) -> Result<QueryResponse> {
+    let fetch_k = crate::index::filter::oversampled_k(top_k, oversample_factor);
+    // Stored vectors come back with the candidates, for exact scoring.
+    let candidate_options = QueryOptions {
+        include_vectors: true,
+        shared: Some(options.shared.clone().unwrap_or_default()),
+        ..options.clone()
+    };
+    let responses = futures::future::try_join_all(queries.iter().map(|query| {
+        execute_query_with_options(
+            store,
+            wal_reader,
+            namespace,
+            query,
+            fetch_k,
+            nprobe,
+            filter,
+            consistency,
+            distance_metric,
+            oversample_factor,
+            cache,
+            &candidate_options,
+        )
+    }))
+    .await?;
+
+    let mut scanned_fragments = 0;
+    let mut scanned_segments = 0;
+    let mut warnings: Vec<String> = Vec::new();
+    let mut candidates: HashMap<String, SearchResult> = HashMap::new();
+    for response in responses {
+        scanned_fragments = scanned_fragments.max(response.scanned_fragments);
+        scanned_segments = scanned_segments.max(response.scanned_segments);
+        for warning in response.warnings {
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        }
+        for result in response.results {
+            candidates.entry(result.id.clone()).or_insert(result);
+        }
+    }
+
+    let mut results: Vec<SearchResult> = candidates
+        .into_values()
+        .filter_map(|mut result| {
+            // Only a vector deleted since its list was built has none.
+            let values = result.values.take()?;
+            result.score = queries
+                .iter()
+                .enumerate()
+                .map(|(i, query)| {
+                    let w = weights.map_or(1.0, |w| w[i]);
+                    let distance = compute_distance(query, &values, distance_metric);
+                    w * distance_metric.score_from_distance(distance)
+                })
+                .sum();
+            result.rank = None;
+            if options.include_vectors {
+                result.values = Some(values);
+            }
+            Some(result)
+        })
+        .collect();
+    results.sort_by(|a, b| {
+        distance_metric
+            .compare_scores(a.score, b.score)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    results.truncate(top_k);
+
+    Ok(QueryResponse {
+        results,
+        scanned_fragments,
+        scanned_segments,
+        warnings,
+        aggregations: BTreeMap::new(),
+        next_cursor: None,
     })
 }
 
 /// Scan all uncompacted WAL fragments, deduplicate, apply deletes, score, and filter.
 /// Reads fragments from the provided manifest snapshot (not re-reading manifest from S3).
+///
+/// Returns the `top_k` closest surviving vectors within `max_distance` and
+/// ranking after `after`,
+/// plus the IDs of every surviving vector, each with the ID of the fragment that last wrote it, so
+/// the merge can drop their stale segment versions.
+#[allow(clippy::too_many_arguments)]
 async fn wal_scan(
+    store: &ZeppelinStore,
     wal_reader: &WalReader,
     namespace: &str,
     manifest: &Manifest,
     query: &[f32],
+    top_k: usize,
     filter: Option<&Filter>,
     distance_metric: DistanceMetric,
-) -> Result<(Vec<SearchResult>, usize)> {
-    let refs = manifest.uncompacted_fragments().to_vec();
-    let fragments = wal_reader
-        .read_fragments_from_refs(namespace, &refs)
-        .await?;
+    max_distance: Option<f32>,
+    after: Option<&(f32, String)>,
+    include_vectors: bool,
+) -> Result<(Vec<SearchResult>, HashMap<String, Ulid>, usize)> {
+    let fragments = read_wal(store, wal_reader, namespace, manifest).await?;
     let frag_count = fragments.len();
 
     if fragments.is_empty() {
-        return Ok((Vec::new(), 0));
+        return Ok((Vec::new(), HashMap::new(), 0));
     }
 
     // Collect all delete tombstones
@@ -154,6 +655,7 @@ async fn wal_scan(
         (
             Vec<f32>,
             Option<HashMap<String, crate::types::AttributeValue>>,
+            Ulid,
         ),
     > = HashMap::new();
 
@@ -165,456 +667,2982 @@ async fn wal_scan(
         }
         for vec in &fragment.vectors {
             deleted_ids.remove(&vec.id);
-            latest_vectors.insert(vec.id.clone(), (vec.values.clone(), vec.attributes.clone()));
+            latest_vectors.insert(
+                vec.id.clone(),
+                (vec.values.clone(), vec.attributes.clone(), fragment.id),
+            );
         }
     }
 
     // Score surviving vectors
-    let mut results: Vec<SearchResult> = latest_vectors
+    let mut wal_ids = HashMap::new();
+    let scored = latest_vectors
         .into_iter()
-        .filter(|(_, (values, attrs))| {
-            if let Some(f) = filter {
-                match attrs {
-                    Some(a) => evaluate_filter(f, a),
-                    None => false,
-                }
-            } else {
-                let _ = values; // suppress unused warning
-                true
-            }
+        .filter(|(_, (_, attrs, _))| match filter {
+            Some(f) => matches_filter(f, attrs.as_ref()),
+            None => true,
         })
-        .map(|(id, (values, attributes))| {
-            let score = compute_distance(query, &values, distance_metric);
-            SearchResult {
+        .filter_map(|(id, (values, attributes, written_by))| {
+            wal_ids.insert(id.clone(), written_by);
+            let distance = compute_distance(query, &values, distance_metric);
+            if max_distance.is_some_and(|max| distance > max)
+                || after.is_some_and(|c| !ranks_after(distance, &id, c))
+            {
+                return None;
+            }
+            Some(SearchResult {
                 id,
-                score,
+                score: distance_metric.score_from_distance(distance),
                 attributes,
-            }
-        })
-        .collect();
-
-    results.sort_by(|a, b| {
-        a.score
-            .partial_cmp(&b.score)
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
+                rank: None,
+                values: include_vectors.then_some(values),
+            })
+        });
+    let results = select_top_k(scored, top_k, distance_metric);
 
     debug!(
-        surviving_vectors = results.len(),
+        surviving_vectors = wal_ids.len(),
+        returned = results.len(),
         total_fragments = frag_count,
         "WAL scan complete"
     );
 
-    Ok((results, frag_count))
+    Ok((results, wal_ids, frag_count))
 }
 
-/// Search a single segment via IVF-Flat or Hierarchical index.
-///
-/// Uses `SegmentRef` metadata to determine index type (hierarchical vs flat)
-/// without probing S3, and loads the IVF-Flat index with pre-known metadata
-/// to skip cluster-count probing and quantization detection.
-#[allow(clippy::too_many_arguments)]
-async fn segment_search(
-    store: &ZeppelinStore,
-    namespace: &str,
-    segment_ref: &SegmentRef,
-    query: &[f32],
-    top_k: usize,
-    nprobe: usize,
-    filter: Option<&Filter>,
-    distance_metric: DistanceMetric,
-    oversample_factor: usize,
-    cache: Option<&Arc<DiskCache>>,
-) -> Result<Vec<SearchResult>> {
-    let segment_id = &segment_ref.id;
+/// Orders results best first for the metric, breaking ties by ID so the
+/// output does not depend on hash map iteration order.
+struct ByScore(SearchResult, DistanceMetric);
 
-    // Use manifest metadata to determine index type — no S3 probe needed.
-    if segment_ref.hierarchical {
-        let mut index = HierarchicalIndex::load(store, namespace, segment_id).await?;
-        index.bitmap_fields = segment_ref.bitmap_fields.clone();
-        use crate::index::hierarchical::search::search_hierarchical;
-        let results = search_hierarchical(
-            &index,
-            query,
-            top_k,
-            nprobe, // beam_width uses nprobe
-            filter,
-            distance_metric,
-            store,
-            oversample_factor,
-            cache,
-        )
-        .await?;
-        return Ok(results);
+impl PartialEq for ByScore {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
     }
+}
 
-    // Use manifest metadata to skip cluster-count probing and quant detection.
-    let mut index = IvfFlatIndex::load_from_manifest(
-        store,
-        namespace,
-        segment_id,
-        segment_ref.vector_count,
-        segment_ref.quantization,
-    )
-    .await?;
-    index.bitmap_fields = segment_ref.bitmap_fields.clone();
-    use crate::index::ivf_flat::search::search_ivf_flat;
-    let results = search_ivf_flat(
-        &index,
-        query,
-        top_k,
-        nprobe,
-        filter,
-        distance_metric,
-        store,
-        oversample_factor,
-        cache,
-    )
-    .await?;
+impl Eq for ByScore {}
 
-    Ok(results)
+impl PartialOrd for ByScore {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
-/// Execute a BM25 full-text search query against a namespace.
+impl Ord for ByScore {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.1
+            .compare_scores(self.0.score, other.0.score)
+            .then_with(|| self.0.id.cmp(&other.0.id))
+    }
+}
+
+/// The `k` best results for the metric, best first, using a bounded
+/// max-heap: O(n log k) time and O(k) memory instead of a full sort.
+fn select_top_k(
+    results: impl IntoIterator<Item = SearchResult>,
+    k: usize,
+    metric: DistanceMetric,
+) -> Vec<SearchResult> {
+    if k == 0 {
+        return Vec::new();
+    }
+    let mut heap: BinaryHeap<ByScore> = BinaryHeap::with_capacity(k + 1);
+    for result in results {
+        let candidate = ByScore(result, metric);
+        if heap.len() < k {
+            heap.push(candidate);
+        } else if heap.peek().is_some_and(|worst| candidate < *worst) {
+            heap.pop();
+            heap.push(candidate);
+        }
+    }
+    heap.into_sorted_vec().into_iter().map(|r| r.0).collect()
+}
+
+/// How a hybrid query combines its vector and `rank_by` result lists; see
+/// [`fuse_results`].
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Fusion {
+    /// Reciprocal rank fusion: a result scores `1 / (k + rank)` in each
+    /// list it appears in, ranks starting at 1.
+    Rrf {
+        #[serde(default = "default_rrf_k")]
+        k: f32,
+    },
+    /// Each list's scores are min-max normalized to [0, 1], best = 1, then
+    /// combined as `vector_weight * vector + (1 - vector_weight) * rank_by`.
+    Weighted {
+        #[serde(alias = "vectorWeight")]
+        vector_weight: f32,
+    },
+}
+
+fn default_rrf_k() -> f32 {
+    60.0
+}
+
+impl Default for Fusion {
+    fn default() -> Self {
+        Fusion::Rrf { k: default_rrf_k() }
+    }
+}
+
+impl Fusion {
+    pub fn validate(&self) -> Result<()> {
+        match *self {
+            Fusion::Rrf { k } if !(k.is_finite() && k >= 0.0) => Err(ZeppelinError::Validation(
+                format!("fusion k must be a non-negative number, got {k}"),
+            )),
+            Fusion::Weighted { vector_weight } if !(0.0..=1.0).contains(&vector_weight) => {
+                Err(ZeppelinError::Validation(format!(
+                    "fusion vector_weight must be between 0 and 1, got {vector_weight}"
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Fuse the ranked results of a vector query (`dense`, scored under
+/// `metric`) and a `rank_by` query (`text`, higher is better) into the
+/// `top_k` best by fused score, highest first. A result in only one list
+/// gets nothing from the other.
+pub fn fuse_results(
+    dense: Vec<SearchResult>,
+    text: Vec<SearchResult>,
+    metric: DistanceMetric,
+    fusion: Fusion,
+    top_k: usize,
+) -> Vec<SearchResult> {
+    let dense_scores = fusion_scores(&dense, fusion, metric.higher_is_better(), true);
+    let text_scores = fusion_scores(&text, fusion, true, false);
+    let mut fused: HashMap<String, SearchResult> = HashMap::new();
+    let scored = dense
+        .into_iter()
+        .zip(dense_scores)
+        .chain(text.into_iter().zip(text_scores));
+    for (result, score) in scored {
+        match fused.entry(result.id.clone()) {
+            std::collections::hash_map::Entry::Occupied(mut entry) => {
+                let existing = entry.get_mut();
+                existing.score += score;
+                if existing.attributes.is_none() {
+                    existing.attributes = result.attributes;
+                }
+            }
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(SearchResult { score, ..result });
+            }
+        }
+    }
+    let mut results: Vec<SearchResult> = fused.into_values().collect();
+    results.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.id.cmp(&b.id)));
+    results.truncate(top_k);
+    results
+}
+
+/// Each result's contribution to its fused score. `results` are ranked
+/// best first.
+fn fusion_scores(
+    results: &[SearchResult],
+    fusion: Fusion,
+    higher_is_better: bool,
+    vector_side: bool,
+) -> Vec<f32> {
+    match fusion {
+        Fusion::Rrf { k } => (0..results.len())
+            .map(|rank| 1.0 / (k + rank as f32 + 1.0))
+            .collect(),
+        Fusion::Weighted { vector_weight } => {
+            let weight = if vector_side {
+                vector_weight
+            } else {
+                1.0 - vector_weight
+            };
+            let (min, max) = results.iter().fold((f32::MAX, f32::MIN), |(lo, hi), r| {
+                (lo.min(r.score), hi.max(r.score))
+            });
+            results
+                .iter()
+                .map(|r| {
+                    // A single result, or all tied, counts as the best.
+                    let normalized = if max > min {
+                        (r.score - min) / (max - min)
+                    } else {
+                        1.0
+                    };
+                    let normalized = if higher_is_better || max <= min {
+                        normalized
+                    } else {
+                        1.0 - normalized
+                    };
+                    weight * normalized
+                })
+                .collect()
+        }
+    }
+}
+
+/// Maximal Marginal Relevance settings for a vector query.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Diversify {
+    /// Trade-off between relevance (1.0) and diversity (0.0).
+    #[serde(default = "default_mmr_lambda")]
+    pub lambda: f32,
+}
+
+fn default_mmr_lambda() -> f32 {
+    0.5
+}
+
+impl Diversify {
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.lambda) {
+            return Err(ZeppelinError::Validation(format!(
+                "diversify lambda must be between 0 and 1, got {}",
+                self.lambda
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Pick `top_k` of the ranked `candidates` by Maximal Marginal Relevance:
+/// each pick maximizes `λ·sim(query, d) − (1−λ)·max sim(d, picked)`, with
+/// similarity the negated `metric` distance. Results keep their scores and
+/// come back in pick order. Candidates whose vector is missing from
+/// `vectors` are scored on their result score alone and never penalize
+/// others.
+pub fn mmr_rerank(
+    candidates: Vec<SearchResult>,
+    vectors: &HashMap<String, Vec<f32>>,
+    query: &[f32],
+    metric: DistanceMetric,
+    lambda: f32,
+    top_k: usize,
+) -> Vec<SearchResult> {
+    let similarity = |a: &[f32], b: &[f32]| -compute_distance(a, b, metric);
+    let mut remaining: Vec<(SearchResult, Option<&[f32]>, f32)> = candidates
+        .into_iter()
+        .map(|r| {
+            let vector = vectors.get(&r.id).map(Vec::as_slice);
+            let relevance = vector.map_or(-metric.score_from_distance(r.score), |v| {
+                similarity(query, v)
+            });
+            (r, vector, relevance)
+        })
+        .collect();
+    let mut picked: Vec<SearchResult> = Vec::with_capacity(top_k.min(remaining.len()));
+    let mut picked_vectors: Vec<&[f32]> = Vec::new();
+    while picked.len() < top_k && !remaining.is_empty() {
+        let marginal = |(_, vector, relevance): &(SearchResult, Option<&[f32]>, f32)| {
+            let redundancy = vector
+                .and_then(|v| {
+                    picked_vectors
+                        .iter()
+                        .map(|p| similarity(v, p))
+                        .reduce(f32::max)
+                })
+                .unwrap_or(0.0);
+            lambda * relevance - (1.0 - lambda) * redundancy
+        };
+        // Ties go to the better-ranked candidate.
+        let (best, _) = remaining
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (i, marginal(c)))
+            .fold((0, f32::NEG_INFINITY), |best, (i, m)| {
+                if m > best.1 {
+                    (i, m)
+                } else {
+                    best
+                }
+            });
+        let (result, vector, _) = remaining.remove(best);
+        picked_vectors.extend(vector);
+        picked.push(result);
+    }
+    picked
+}
+
+/// Collapse settings: at most `group_size` results per value of `field`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct GroupBy {
+    pub field: String,
+    #[serde(default = "default_group_size", alias = "groupSize")]
+    pub group_size: usize,
+}
+
+fn default_group_size() -> usize {
+    1
+}
+
+impl GroupBy {
+    pub fn validate(&self) -> Result<()> {
+        if self.field.is_empty() {
+            return Err(ZeppelinError::Validation(
+                "group_by field must not be empty".into(),
+            ));
+        }
+        if self.group_size == 0 {
+            return Err(ZeppelinError::Validation(
+                "group_by group_size must be > 0".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Keep the first `group_size` of the ranked `results` per value of the
+/// `group_by` field (dot paths allowed), up to `top_k` in all. Values are
+/// compared by their JSON text, so a list is one group. Results without
+/// the field are never collapsed.
+pub fn collapse_groups(
+    results: Vec<SearchResult>,
+    group_by: &GroupBy,
+    top_k: usize,
+) -> Vec<SearchResult> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    results
+        .into_iter()
+        .filter(|r| {
+            let value = r
+                .attributes
+                .as_ref()
+                .and_then(|attrs| resolve_field(attrs, &group_by.field));
+            let Some(value) = value else {
+                return true;
+            };
+            let key = serde_json::to_string(value).expect("serialization should not fail");
+            let count = counts.entry(key).or_insert(0);
+            *count += 1;
+            *count <= group_by.group_size
+        })
+        .take(top_k)
+        .collect()
+}
+
+/// Direction for [`order_by_attribute`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+/// Re-sort already ranked results by an attribute (dot paths allowed).
 ///
-/// Combines WAL brute-force scan with segment inverted index search.
+/// Numbers sort before strings, strings before booleans. Results missing
+/// the attribute, or holding a list or object there, go last in either
+/// direction. The sort is stable, so ties keep their ranking by score.
+pub fn order_by_attribute(results: &mut [SearchResult], field: &str, direction: SortDirection) {
+    results.sort_by(|a, b| match (sort_key(a, field), sort_key(b, field)) {
+        (Some(ka), Some(kb)) => {
+            let ord =
+                ka.0.cmp(&kb.0)
+                    .then(ka.1.total_cmp(&kb.1))
+                    .then(ka.2.cmp(kb.2));
+            match direction {
+                SortDirection::Asc => ord,
+                SortDirection::Desc => ord.reverse(),
+            }
+        }
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+}
+
+/// `(type rank, numeric value, string value)` for a result's scalar
+/// `field` attribute.
+fn sort_key<'a>(result: &'a SearchResult, field: &str) -> Option<(u8, f64, &'a str)> {
+    let attrs = result.attributes.as_ref()?;
+    match resolve_field(attrs, field)? {
+        AttributeValue::Integer(i) => Some((0, *i as f64, "")),
+        AttributeValue::Float(f) => Some((0, *f, "")),
+        AttributeValue::String(s) => Some((1, 0.0, s.as_str())),
+        AttributeValue::Bool(b) => Some((2, *b as u8 as f64, "")),
+        _ => None,
+    }
+}
+
+/// Collect IDs deleted by uncompacted WAL fragments, for `EventualWithDeletes`.
+///
+/// Only fragments whose manifest entry records deletes are fetched. An ID
+/// deleted in the WAL stays hidden until compaction, even if re-upserted.
+async fn scan_wal_deletes(
+    wal_reader: &WalReader,
+    namespace: &str,
+    manifest: &Manifest,
+) -> Result<(HashSet<String>, usize)> {
+    let refs: Vec<_> = manifest
+        .uncompacted_fragments()
+        .iter()
+        .filter(|fref| fref.delete_count > 0)
+        .cloned()
+        .collect();
+    if refs.is_empty() {
+        return Ok((HashSet::new(), 0));
+    }
+    let fragments = wal_reader
+        .read_fragments_from_refs(namespace, &refs)
+        .await?;
+    let deleted_ids: HashSet<String> = fragments
+        .iter()
+        .flat_map(|fragment| fragment.deletes.iter().cloned())
+        .collect();
+
+    debug!(
+        deleted_ids = deleted_ids.len(),
+        fragments = fragments.len(),
+        "WAL delete scan complete"
+    );
+
+    Ok((deleted_ids, fragments.len()))
+}
+
+/// Search a single segment via IVF-Flat, Hierarchical or Vamana index.
+///
+/// Uses `SegmentRef` metadata to determine index type (Vamana, hierarchical
+/// or flat) without probing S3, and loads the IVF-Flat index with pre-known
+/// metadata to skip cluster-count probing and quantization detection.
+///
+/// Returns whether the search stopped early at `deadline`. Hierarchical
+/// and Vamana segments ignore the deadline.
 #[allow(clippy::too_many_arguments)]
-#[instrument(skip(store, wal_reader, rank_by, fts_configs, filter), fields(namespace = namespace))]
-pub async fn execute_bm25_query(
+async fn segment_search(
     store: &ZeppelinStore,
-    wal_reader: &WalReader,
     namespace: &str,
-    rank_by: &RankBy,
-    fts_configs: &HashMap<String, FtsFieldConfig>,
+    segment_ref: &SegmentRef,
+    query: &[f32],
     top_k: usize,
+    nprobe: usize,
     filter: Option<&Filter>,
-    consistency: ConsistencyLevel,
-    last_as_prefix: bool,
-) -> Result<QueryResponse> {
-    let manifest = Manifest::read(store, namespace).await?.unwrap_or_default();
+    distance_metric: DistanceMetric,
+    oversample_factor: usize,
+    cache: Option<&Arc<DiskCache>>,
+    options: &QueryOptions,
+    deadline: Option<std::time::Instant>,
+) -> Result<(Vec<SearchResult>, bool)> {
+    let segment_id = &segment_ref.id;
 
-    let mut scanned_fragments = 0;
-    let mut scanned_segments = 0;
+    // Use manifest metadata to determine index type — no S3 probe needed.
+    if segment_ref.vamana {
+        let mut index = VamanaIndex::load(store, namespace, segment_id).await?;
+        index.skip_attributes = options.skip_attributes;
+        index.max_distance = options.max_distance;
+        index.after = options.after.clone();
+        index.tombstones = segment_ref.tombstones.clone();
+        use crate::index::vamana::search::search_vamana;
+        let results = search_vamana(
+            &index,
+            query,
+            top_k,
+            nprobe, // beam_width uses nprobe
+            filter,
+            distance_metric,
+            store,
+            oversample_factor,
+            cache,
+        )
+        .await?;
+        return Ok((results, false));
+    }
+    if segment_ref.hierarchical {
+        let mut index = HierarchicalIndex::load(store, namespace, segment_id).await?;
+        index.bitmap_fields = segment_ref.bitmap_fields.clone();
+        index.skip_attributes = options.skip_attributes;
+        index.max_distance = options.max_distance;
+        index.after = options.after.clone();
+        index.tombstones = segment_ref.tombstones.clone();
+        index.include_vectors = options.include_vectors;
+        if let Some(factor) = options.rerank_factor {
+            index.rerank_factor = factor;
+        }
+        use crate::index::hierarchical::search::search_hierarchical;
+        let results = search_hierarchical(
+            &index,
+            query,
+            top_k,
+            nprobe, // beam_width uses nprobe
+            filter,
+            distance_metric,
+            store,
+            oversample_factor,
+            cache,
+        )
+        .await?;
+        return Ok((results, false));
+    }
 
-    // WAL BM25 scan (always for Strong, never for Eventual)
-    let wal_start = std::time::Instant::now();
-    let mut wal_deleted_ids = std::collections::HashSet::new();
-    let wal_results = match consistency {
-        ConsistencyLevel::Strong => {
-            let refs = manifest.uncompacted_fragments().to_vec();
-            let fragments = wal_reader
-                .read_fragments_from_refs(namespace, &refs)
-                .await?;
-            let scan_result = wal_bm25_scan(&fragments, rank_by, fts_configs, last_as_prefix);
-            scanned_fragments = scan_result.fragment_count;
-            wal_deleted_ids = scan_result.deleted_ids;
-            // Apply post-filter to WAL results
-            let mut results = scan_result.results;
-            if let Some(f) = filter {
-                results.retain(|r| match &r.attributes {
-                    Some(attrs) => crate::index::filter::evaluate_filter(f, attrs),
-                    None => false,
-                });
-            }
-            results
+    // Pinned namespaces read centroids through the cache so the pin pays off.
+    let centroid_cache = match cache {
+        Some(c) if options.cache_pinned => {
+            pin_segment(c, namespace, segment_ref, options.pin_clusters).await;
+            Some(c.as_ref())
         }
-        ConsistencyLevel::Eventual => Vec::new(),
+        _ => None,
     };
-    let wal_duration = wal_start.elapsed();
-    debug!(
-        wal_duration_ms = wal_duration.as_millis() as u64,
-        fragments_scanned = scanned_fragments,
-        "BM25 query phase: WAL scan"
+
+    // Use manifest metadata to skip cluster-count probing and quant detection.
+    let load = IvfFlatIndex::load_from_manifest(
+        store,
+        namespace,
+        segment_id,
+        segment_ref.vector_count,
+        segment_ref.quantization,
+        centroid_cache,
     );
+    let mut index = match &options.shared {
+        Some(shared) => shared.ivf_index(segment_id, load).await?,
+        None => load.await?,
+    };
+    index.bitmap_fields = segment_ref.bitmap_fields.clone();
+    index.max_candidates_per_cluster = options.max_candidates_per_cluster;
+    index.deadline = deadline;
+    index.adaptive_nprobe_cap = options.adaptive_nprobe_cap;
+    index.probe_gap = options.probe_gap;
+    index.skip_attributes = options.skip_attributes;
+    index.max_distance = options.max_distance;
+    index.after = options.after.clone();
+    index.tombstones = segment_ref.tombstones.clone();
+    index.include_vectors = options.include_vectors;
+    if let Some(factor) = options.rerank_factor {
+        index.rerank_factor = factor;
+    }
+    use crate::index::ivf_flat::search::search_ivf_flat_partial;
+    search_ivf_flat_partial(
+        &index,
+        query,
+        top_k,
+        nprobe,
+        filter,
+        distance_metric,
+        store,
+        oversample_factor,
+        cache,
+    )
+    .await
+}
 
-    // Segment BM25 search
-    let segment_start = std::time::Instant::now();
-    let segment_results = if let Some(ref segment_id) = manifest.active_segment {
-        let segment_ref = manifest
-            .segments
-            .iter()
-            .find(|s| s.id == *segment_id)
-            .cloned();
+/// How uncompacted WAL fragments are read for a query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WalPlan {
+    /// Every fragment is read and its vectors scored.
+    Scan,
+    /// Only fragments with deletes are read, to hide deleted IDs.
+    DeletesOnly,
+    /// The WAL is not read.
+    Skip,
+}
+
+/// Execution plan for a vector query; see [`explain_query`].
+#[derive(Debug, Serialize)]
+pub struct QueryPlan {
+    pub consistency: ConsistencyLevel,
+    pub wal: WalPlan,
+    /// Uncompacted fragments that would be read.
+    pub wal_fragments: usize,
+    /// The active segment's search, or `None` when there is no segment or
+    /// the query skips it.
+    pub segment: Option<SegmentPlan>,
+    /// Searches of older segments still live under tiered compaction,
+    /// oldest first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tier_segments: Vec<SegmentPlan>,
+    /// Leaf clauses of the filter and how each can narrow the search.
+    pub filter: Vec<FilterClausePlan>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SegmentPlan {
+    pub segment_id: String,
+    pub hierarchical: bool,
+    pub vamana: bool,
+    pub quantization: crate::index::quantization::QuantizationType,
+    pub cluster_count: usize,
+    pub vector_count: usize,
+    /// Stored IDs hidden because they were deleted or rewritten later.
+    pub tombstones: usize,
+    pub requested_nprobe: usize,
+    /// `nprobe` after capping and any adaptive widening. For hierarchical
+    /// and Vamana segments this is the beam width.
+    pub nprobe: usize,
+    /// Clusters that would be scanned, closest centroid first. Empty for
+    /// hierarchical segments, whose beam search picks leaves as it descends,
+    /// and for Vamana segments.
+    pub probed_clusters: Vec<usize>,
+    /// Clusters among the closest `nprobe` skipped by attribute stats.
+    pub pruned_clusters: Vec<usize>,
+    /// Vectors in the probed clusters, assuming evenly sized clusters. Zero
+    /// for Vamana segments, whose graph walk has no up-front scan size.
+    pub estimated_candidates: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FilterClausePlan {
+    pub field: String,
+    pub op: &'static str,
+    /// The segment has a bitmap index on `field` that can resolve this
+    /// clause before any distance is computed.
+    pub bitmap_prefilter: bool,
+    /// Per-cluster attribute ranges can skip whole clusters for this clause.
+    pub range_pruning: bool,
+}
+
+/// Plan a vector query without scoring any vectors: which WAL fragments
+/// and segment clusters would be read, and how the filter can prune them.
+/// Reads the manifest, centroids and the segment's bitmap and stats
+/// sidecars, but no cluster vector data.
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(store, query, filter, cache, options), fields(namespace = namespace))]
+pub async fn explain_query(
+    store: &ZeppelinStore,
+    namespace: &str,
+    query: &[f32],
+    top_k: usize,
+    nprobe: usize,
+    filter: Option<&Filter>,
+    consistency: ConsistencyLevel,
+    distance_metric: DistanceMetric,
+    cache: Option<&Arc<DiskCache>>,
+    options: &QueryOptions,
+) -> Result<QueryPlan> {
+    let manifest = Manifest::read(store, namespace).await?.unwrap_or_default();
+    let consistency = if options.wal_only {
+        ConsistencyLevel::Strong
+    } else {
+        consistency
+    };
+    let fragments = manifest.uncompacted_fragments();
+    let (wal, wal_fragments) = match consistency {
+        ConsistencyLevel::Strong => (WalPlan::Scan, fragments.len()),
+        ConsistencyLevel::EventualWithDeletes => (
+            WalPlan::DeletesOnly,
+            fragments.iter().filter(|f| f.delete_count > 0).count(),
+        ),
+        ConsistencyLevel::Eventual => (WalPlan::Skip, 0),
+    };
+
+    let segment_ref = manifest
+        .active_segment
+        .as_ref()
+        .filter(|_| !options.wal_only)
+        .and_then(|id| manifest.segments.iter().find(|s| &s.id == id));
+    let Some(segment_ref) = segment_ref else {
+        return Ok(QueryPlan {
+            consistency,
+            wal,
+            wal_fragments,
+            segment: None,
+            tier_segments: Vec::new(),
+            filter: filter
+                .map(|f| plan_filter(f, &[], None))
+                .unwrap_or_default(),
+        });
+    };
 
-        if let Some(seg_ref) = segment_ref {
-            if !seg_ref.fts_fields.is_empty() {
-                let results = segment_bm25_search(
+    let mut tier_segments = Vec::new();
+    for seg in manifest.live_segments() {
+        if seg.id != segment_ref.id {
+            tier_segments.push(
+                plan_segment(
                     store,
                     namespace,
-                    &seg_ref,
-                    rank_by,
-                    fts_configs,
+                    seg,
+                    query,
+                    top_k,
+                    nprobe,
                     filter,
-                    last_as_prefix,
+                    distance_metric,
+                    cache,
+                    options,
                 )
-                .await?;
-                scanned_segments = 1;
-                results
-            } else {
-                Vec::new()
-            }
-        } else {
-            Vec::new()
+                .await?,
+            );
         }
+    }
+    let segment = plan_segment(
+        store,
+        namespace,
+        segment_ref,
+        query,
+        top_k,
+        nprobe,
+        filter,
+        distance_metric,
+        cache,
+        options,
+    )
+    .await?;
+
+    let stats = if segment_ref.hierarchical || segment_ref.vamana {
+        None
     } else {
-        Vec::new()
+        use crate::index::ivf_flat::stats::{attr_stats_key, SegmentAttrStats};
+        let key = attr_stats_key(namespace, &segment_ref.id);
+        store
+            .get(&key)
+            .await
+            .ok()
+            .and_then(|data| SegmentAttrStats::from_bytes(&data).ok())
+    };
+
+    Ok(QueryPlan {
+        consistency,
+        wal,
+        wal_fragments,
+        segment: Some(segment),
+        tier_segments,
+        filter: filter
+            .map(|f| plan_filter(f, &segment_ref.bitmap_fields, stats.as_ref()))
+            .unwrap_or_default(),
+    })
+}
+
+/// Plan the search of one segment for [`explain_query`].
+#[allow(clippy::too_many_arguments)]
+async fn plan_segment(
+    store: &ZeppelinStore,
+    namespace: &str,
+    segment_ref: &SegmentRef,
+    query: &[f32],
+    top_k: usize,
+    nprobe: usize,
+    filter: Option<&Filter>,
+    distance_metric: DistanceMetric,
+    cache: Option<&Arc<DiskCache>>,
+    options: &QueryOptions,
+) -> Result<SegmentPlan> {
+    let (effective_nprobe, probed_clusters, pruned_clusters) = if segment_ref.vamana {
+        (nprobe, Vec::new(), Vec::new())
+    } else if segment_ref.hierarchical {
+        (
+            nprobe.min(segment_ref.cluster_count),
+            Vec::new(),
+            Vec::new(),
+        )
+    } else {
+        let mut index = IvfFlatIndex::load_from_manifest(
+            store,
+            namespace,
+            &segment_ref.id,
+            segment_ref.vector_count,
+            segment_ref.quantization,
+            None,
+        )
+        .await?;
+        index.bitmap_fields = segment_ref.bitmap_fields.clone();
+        index.adaptive_nprobe_cap = options.adaptive_nprobe_cap;
+        index.probe_gap = options.probe_gap;
+        let plan = crate::index::ivf_flat::search::plan_probe(
+            &index,
+            query,
+            top_k,
+            nprobe,
+            filter,
+            distance_metric,
+            store,
+            cache,
+        )
+        .await;
+        (plan.nprobe, plan.probe_clusters, plan.pruned_clusters)
+    };
+    let scanned = if segment_ref.hierarchical {
+        effective_nprobe
+    } else {
+        probed_clusters.len()
+    };
+    let estimated_candidates = if segment_ref.vamana {
+        0
+    } else {
+        segment_ref.vector_count * scanned / segment_ref.cluster_count.max(1)
+    };
+
+    Ok(SegmentPlan {
+        segment_id: segment_ref.id.clone(),
+        hierarchical: segment_ref.hierarchical,
+        vamana: segment_ref.vamana,
+        quantization: segment_ref.quantization,
+        cluster_count: segment_ref.cluster_count,
+        vector_count: segment_ref.vector_count,
+        tombstones: segment_ref.tombstones.len(),
+        requested_nprobe: nprobe,
+        nprobe: effective_nprobe,
+        probed_clusters,
+        pruned_clusters,
+        estimated_candidates,
+    })
+}
+
+/// Plan each leaf clause of `filter` against a segment's bitmap fields and
+/// attribute stats.
+fn plan_filter(
+    filter: &Filter,
+    bitmap_fields: &[String],
+    stats: Option<&crate::index::ivf_flat::stats::SegmentAttrStats>,
+) -> Vec<FilterClausePlan> {
+    let has_range_stats =
+        |field: &str| stats.is_some_and(|s| s.clusters.iter().any(|c| c.contains_key(field)));
+    let mut clauses = Vec::new();
+    // `prunable` tracks whether attribute stats can act on a clause at this
+    // position: not under `not`, and under `or` only if every branch is a range.
+    let mut stack = vec![(filter, true)];
+    while let Some((f, prunable)) = stack.pop() {
+        let (field, op, bitmap_op) = match f {
+            Filter::And { filters } => {
+                stack.extend(filters.iter().rev().map(|f| (f, prunable)));
+                continue;
+            }
+            Filter::Or { filters } => {
+                let all_ranges = filters.iter().all(|f| matches!(f, Filter::Range { .. }));
+                stack.extend(filters.iter().rev().map(|f| (f, prunable && all_ranges)));
+                continue;
+            }
+            Filter::Not { filter } => {
+                stack.push((filter, false));
+                continue;
+            }
+            Filter::Eq { field, .. } => (field, "eq", true),
+            Filter::NotEq { field, .. } => (field, "not_eq", true),
+            Filter::ApproxEq { field, .. } => (field, "approx_eq", true),
+            Filter::Range { field, .. } => (field, "range", true),
+            Filter::In { field, .. } => (field, "in", true),
+            Filter::NotIn { field, .. } => (field, "not_in", true),
+            Filter::Contains { field, .. } => (field, "contains", true),
+            Filter::ContainsAny { field, .. } => (field, "contains_any", true),
+            Filter::StartsWith { field, .. } => (field, "starts_with", false),
+            Filter::Glob { field, .. } => (field, "glob", false),
+            Filter::ContainsAllTokens { field, .. } => (field, "contains_all_tokens", false),
+            Filter::ContainsTokenSequence { field, .. } => {
+                (field, "contains_token_sequence", false)
+            }
+            Filter::Exists { field } => (field, "exists", false),
+            Filter::NotExists { field } => (field, "not_exists", false),
+        };
+        clauses.push(FilterClausePlan {
+            field: field.clone(),
+            op,
+            bitmap_prefilter: bitmap_op && bitmap_fields.contains(field),
+            range_pruning: prunable && op == "range" && has_range_stats(field),
+        });
+    }
+    clauses
+}
+
+/// Pin an IVF segment's centroids, and with `pin_clusters` its cluster
+/// vector data, so eviction skips them. Pins left on the namespace's older
+/// segments are released first. A no-op when the centroids are already
+/// pinned, so repeat queries only pay for a set lookup.
+async fn pin_segment(
+    cache: &DiskCache,
+    namespace: &str,
+    segment_ref: &SegmentRef,
+    pin_clusters: bool,
+) {
+    use crate::index::ivf_flat::build::{centroids_key, cluster_key};
+    use crate::index::quantization::{pq::pq_cluster_key, sq::sq_cluster_key, QuantizationType};
+
+    let ckey = centroids_key(namespace, &segment_ref.id);
+    if cache.is_pinned(&ckey).await {
+        return;
+    }
+    cache.unpin_prefix(&format!("{namespace}/segments/")).await;
+    cache.pin(&ckey).await;
+    if pin_clusters {
+        for i in 0..segment_ref.cluster_count {
+            cache.pin(&cluster_key(namespace, &segment_ref.id, i)).await;
+            match segment_ref.quantization {
+                QuantizationType::Scalar => {
+                    cache
+                        .pin(&sq_cluster_key(namespace, &segment_ref.id, i))
+                        .await
+                }
+                QuantizationType::Product => {
+                    cache
+                        .pin(&pq_cluster_key(namespace, &segment_ref.id, i))
+                        .await
+                }
+                QuantizationType::None => {}
+            }
+        }
+    }
+    debug!(
+        namespace = namespace,
+        segment_id = %segment_ref.id,
+        pin_clusters,
+        "pinned segment in cache"
+    );
+}
+
+/// Execute a BM25 full-text search query against a namespace.
+///
+/// Combines WAL brute-force scan with segment inverted index search.
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(store, wal_reader, rank_by, fts_configs, filter), fields(namespace = namespace))]
+pub async fn execute_bm25_query(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    rank_by: &RankBy,
+    fts_configs: &HashMap<String, FtsFieldConfig>,
+    top_k: usize,
+    filter: Option<&Filter>,
+    consistency: ConsistencyLevel,
+    last_as_prefix: bool,
+) -> Result<QueryResponse> {
+    let manifest = Manifest::read(store, namespace).await?.unwrap_or_default();
+
+    let mut scanned_fragments = 0;
+    let mut scanned_segments = 0;
+
+    // WAL BM25 scan (always for Strong, never for Eventual)
+    let wal_start = std::time::Instant::now();
+    let mut wal_deleted_ids = std::collections::HashSet::new();
+    let wal_results = match consistency {
+        ConsistencyLevel::Strong => {
+            let fragments = read_wal(store, wal_reader, namespace, &manifest).await?;
+            let scan_result = wal_bm25_scan(&fragments, rank_by, fts_configs, last_as_prefix);
+            scanned_fragments = scan_result.fragment_count;
+            wal_deleted_ids = scan_result.deleted_ids;
+            // Apply post-filter to WAL results
+            let mut results = scan_result.results;
+            if let Some(f) = filter {
+                results.retain(|r| matches_filter(f, r.attributes.as_ref()));
+            }
+            results
+        }
+        ConsistencyLevel::EventualWithDeletes => {
+            let (ids, frag_count) = scan_wal_deletes(wal_reader, namespace, &manifest).await?;
+            scanned_fragments = frag_count;
+            wal_deleted_ids = ids;
+            Vec::new()
+        }
+        ConsistencyLevel::Eventual => Vec::new(),
+    };
+    let wal_duration = wal_start.elapsed();
+    debug!(
+        wal_duration_ms = wal_duration.as_millis() as u64,
+        fragments_scanned = scanned_fragments,
+        "BM25 query phase: WAL scan"
+    );
+
+    // Segment BM25 search
+    let segment_start = std::time::Instant::now();
+    let mut segment_results = Vec::new();
+    for seg_ref in manifest.live_segments() {
+        if seg_ref.fts_fields.is_empty() {
+            continue;
+        }
+        let mut results = segment_bm25_search(
+            store,
+            namespace,
+            seg_ref,
+            rank_by,
+            fts_configs,
+            filter,
+            last_as_prefix,
+        )
+        .await?;
+        results.retain(|r| !seg_ref.tombstones.contains(&r.id));
+        segment_results.extend(results);
+        scanned_segments += 1;
+    }
+    let segment_duration = segment_start.elapsed();
+    debug!(
+        segment_duration_ms = segment_duration.as_millis() as u64,
+        segments_scanned = scanned_segments,
+        "BM25 query phase: segment search"
+    );
+
+    // Merge results — BM25 is higher-is-better
+    // Pass deleted IDs so segment results for deleted docs are excluded
+    let results = merge_bm25_results(
+        wal_results,
+        segment_results,
+        top_k,
+        consistency,
+        &wal_deleted_ids,
+    );
+
+    Ok(QueryResponse {
+        results,
+        scanned_fragments,
+        scanned_segments,
+        warnings: Vec::new(),
+        aggregations: BTreeMap::new(),
+        next_cursor: None,
+    })
+}
+
+/// Search a segment's inverted indexes for a BM25 query.
+#[allow(clippy::too_many_arguments)]
+async fn segment_bm25_search(
+    store: &ZeppelinStore,
+    namespace: &str,
+    segment_ref: &SegmentRef,
+    rank_by: &RankBy,
+    fts_configs: &HashMap<String, FtsFieldConfig>,
+    filter: Option<&Filter>,
+    last_as_prefix: bool,
+) -> Result<Vec<SearchResult>> {
+    use crate::index::ivf_flat::build::{attrs_key, cluster_key, deserialize_attrs, docs_key};
+
+    let segment_id = &segment_ref.id;
+    let fts_fields = &segment_ref.fts_fields;
+
+    // Text-only partitions and Vamana blocks keep their IDs in docs blobs
+    // and have no centroids to load.
+    let num_clusters = if segment_ref.text_only || segment_ref.vamana {
+        segment_ref.cluster_count
+    } else {
+        // Load the IVF-Flat index using manifest metadata to skip cluster probing.
+        IvfFlatIndex::load_from_manifest(
+            store,
+            namespace,
+            segment_id,
+            segment_ref.vector_count,
+            segment_ref.quantization,
+            None,
+        )
+        .await?
+        .num_clusters()
+    };
+    let ids_key = if segment_ref.text_only || segment_ref.vamana {
+        docs_key
+    } else {
+        cluster_key
+    };
+
+    let field_queries = rank_by.extract_field_queries();
+    let mut all_results: HashMap<
+        String,
+        (f32, Option<HashMap<String, crate::types::AttributeValue>>),
+    > = HashMap::new();
+
+    // Parallel prefetch all cluster data (fts index, attrs, cluster vectors).
+    let prefetched = futures::future::join_all((0..num_clusters).map(|cluster_idx| {
+        let fts_key = fts_index_key(namespace, segment_id, cluster_idx);
+        let akey = attrs_key(namespace, segment_id, cluster_idx);
+        let ckey = ids_key(namespace, segment_id, cluster_idx);
+        async move {
+            let (fts_res, attrs_res, cluster_res) =
+                tokio::join!(store.get(&fts_key), store.get(&akey), store.get(&ckey),);
+            (cluster_idx, fts_res, attrs_res, cluster_res)
+        }
+    }))
+    .await;
+
+    // Process prefetched results — CPU-bound, no I/O.
+    for (_cluster_idx, fts_res, attrs_res, cluster_res) in prefetched {
+        let fts_data = match fts_res {
+            Ok(data) => data,
+            Err(crate::error::ZeppelinError::NotFound { .. }) => continue,
+            Err(e) => return Err(e),
+        };
+
+        let inv_index = InvertedIndex::from_bytes(&fts_data)?;
+
+        let attrs_data = match attrs_res {
+            Ok(data) => data,
+            Err(_) => continue,
+        };
+        let cluster_attrs = deserialize_attrs(&attrs_data)?;
+
+        let cluster_data = match cluster_res {
+            Ok(data) => data,
+            Err(e) => return Err(e),
+        };
+        let cluster = crate::index::ivf_flat::build::deserialize_cluster(&cluster_data)?;
+
+        // For each field+query, search the inverted index
+        let mut position_field_scores: HashMap<u32, HashMap<String, f32>> = HashMap::new();
+
+        for (field, query) in &field_queries {
+            let config = match fts_configs.get(field.as_str()) {
+                Some(c) => c,
+                None => continue,
+            };
+
+            if !fts_fields.contains(field) {
+                continue;
+            }
+
+            let query_tokens = tokenize_text(query, config, last_as_prefix);
+
+            let params = Bm25Params {
+                k1: config.k1,
+                b: config.b,
+            };
+            let results = if last_as_prefix {
+                inv_index.search_prefix(field, &query_tokens, &params)
+            } else {
+                inv_index.search(field, &query_tokens, &params)
+            };
+
+            for (pos, score) in results {
+                let entry = position_field_scores.entry(pos).or_default();
+                *entry.entry(field.to_string()).or_insert(0.0) += score;
+            }
+        }
+
+        // Evaluate rank_by expression and collect results
+        for (pos, field_scores) in position_field_scores {
+            let final_score = evaluate_rank_by(rank_by, &field_scores);
+            if final_score <= 0.0 {
+                continue;
+            }
+
+            let pos_usize = pos as usize;
+            if pos_usize >= cluster.ids.len() {
+                continue;
+            }
+
+            let id = cluster.ids[pos_usize].clone();
+            let attrs = cluster_attrs.get(pos_usize).cloned().flatten();
+
+            // Apply post-filter
+            if filter.is_some_and(|f| !matches_filter(f, attrs.as_ref())) {
+                continue;
+            }
+
+            // Accumulate: same ID might appear in multiple clusters (shouldn't, but be safe)
+            let entry = all_results.entry(id.clone()).or_insert((0.0, attrs));
+            if final_score > entry.0 {
+                entry.0 = final_score;
+            }
+        }
+    }
+
+    let mut results: Vec<SearchResult> = all_results
+        .into_iter()
+        .map(|(id, (score, attributes))| SearchResult {
+            id,
+            score,
+            attributes,
+            rank: None,
+            values: None,
+        })
+        .collect();
+
+    // Sort descending by score
+    results.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    Ok(results)
+}
+
+/// Execute a sparse (dot product) query against a namespace.
+///
+/// WAL vectors are scored directly; segments are searched through their
+/// [`SparseIndex`]. Only vectors sharing a dimension with `query` match.
+/// Attributes are read for the best segment candidates only, a `top_k`
+/// batch at a time until `top_k` pass the filter.
+#[instrument(skip(store, wal_reader, query, filter), fields(namespace = namespace))]
+pub async fn execute_sparse_query(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    query: &SparseVector,
+    top_k: usize,
+    filter: Option<&Filter>,
+    consistency: ConsistencyLevel,
+) -> Result<QueryResponse> {
+    let manifest = Manifest::read(store, namespace).await?.unwrap_or_default();
+    let matches = |attrs: Option<&HashMap<String, AttributeValue>>| {
+        filter.is_none_or(|f| matches_filter(f, attrs))
+    };
+
+    // IDs whose segment versions the WAL hides: everything it mentions for
+    // strong reads, its deletes otherwise.
+    let (wal_results, hidden, scanned_fragments) = match consistency {
+        ConsistencyLevel::Strong => {
+            let fragments = read_wal(store, wal_reader, namespace, &manifest).await?;
+            let mut latest: HashMap<&str, Option<&VectorEntry>> = HashMap::new();
+            for fragment in &fragments {
+                for id in &fragment.deletes {
+                    latest.insert(id, None);
+                }
+                for vector in &fragment.vectors {
+                    latest.insert(&vector.id, Some(vector));
+                }
+            }
+            let results = latest
+                .values()
+                .flatten()
+                .filter(|v| matches(v.attributes.as_ref()))
+                .filter_map(|v| {
+                    let sparse = v.sparse.as_ref()?;
+                    let shared = sparse
+                        .indices
+                        .iter()
+                        .any(|i| query.indices.binary_search(i).is_ok());
+                    shared.then(|| SearchResult {
+                        id: v.id.clone(),
+                        score: sparse.dot(query),
+                        attributes: v.attributes.clone(),
+                        rank: None,
+                        values: None,
+                    })
+                })
+                .collect();
+            let hidden = latest.keys().map(|id| id.to_string()).collect();
+            (results, hidden, fragments.len())
+        }
+        ConsistencyLevel::EventualWithDeletes => {
+            let (ids, fragment_count) = scan_wal_deletes(wal_reader, namespace, &manifest).await?;
+            (Vec::new(), ids, fragment_count)
+        }
+        ConsistencyLevel::Eventual => (Vec::new(), HashSet::new(), 0),
+    };
+
+    let mut segment_results = Vec::new();
+    let mut scanned_segments = 0;
+    for seg in manifest.live_segments() {
+        if !seg.sparse {
+            continue;
+        }
+        scanned_segments += 1;
+        let index =
+            SparseIndex::from_bytes(&store.get(&sparse_index_key(namespace, &seg.id)).await?)?;
+        let candidates: Vec<(&str, f32)> = index
+            .search(query)
+            .into_iter()
+            .filter(|(id, _)| !seg.tombstones.contains(*id) && !hidden.contains(*id))
+            .collect();
+        let id_map = load_id_map(store, namespace, seg).await?;
+        let mut found = 0;
+        for batch in candidates.chunks(top_k.max(1)) {
+            let ids: HashSet<&str> = batch.iter().map(|(id, _)| *id).collect();
+            let mut entries: HashMap<String, VectorEntry> =
+                fetch_from_segment(store, namespace, seg, id_map.as_ref(), &ids)
+                    .await?
+                    .into_iter()
+                    .map(|v| (v.id.clone(), v))
+                    .collect();
+            for (id, score) in batch {
+                let attributes = entries.remove(*id).and_then(|v| v.attributes);
+                if !matches(attributes.as_ref()) {
+                    continue;
+                }
+                segment_results.push(SearchResult {
+                    id: id.to_string(),
+                    score: *score,
+                    attributes,
+                    rank: None,
+                    values: None,
+                });
+                found += 1;
+            }
+            if found >= top_k {
+                break;
+            }
+        }
+    }
+    debug!(
+        fragments_scanned = scanned_fragments,
+        segments_scanned = scanned_segments,
+        "sparse query complete"
+    );
+
+    Ok(QueryResponse {
+        results: merge_bm25_results(wal_results, segment_results, top_k, consistency, &hidden),
+        scanned_fragments,
+        scanned_segments,
+        warnings: Vec::new(),
+        aggregations: BTreeMap::new(),
+        next_cursor: None,
+    })
+}
+
+/// Fill in the sparse representations `seg`'s [`SparseIndex`] holds for
+/// `vectors`, which were read from `seg`.
+pub(crate) async fn attach_sparse(
+    store: &ZeppelinStore,
+    namespace: &str,
+    seg: &SegmentRef,
+    vectors: &mut [VectorEntry],
+) -> Result<()> {
+    let index = SparseIndex::from_bytes(&store.get(&sparse_index_key(namespace, &seg.id)).await?)?;
+    let ids: HashSet<&str> = vectors.iter().map(|v| v.id.as_str()).collect();
+    let mut sparse = index.vectors(&ids);
+    for vector in vectors {
+        vector.sparse = sparse.remove(&vector.id);
+    }
+    Ok(())
+}
+
+/// Execute a query against one of a namespace's named vector fields.
+///
+/// Named vectors are not clustered: WAL vectors and each segment's
+/// [`NamedVectorIndex`] are scanned exhaustively under the field's
+/// `metric`. Attributes are read for the best segment candidates only, a
+/// `top_k` batch at a time until `top_k` pass the filter.
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(store, wal_reader, query, filter), fields(namespace = namespace))]
+pub async fn execute_named_vector_query(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    field: &str,
+    query: &[f32],
+    metric: DistanceMetric,
+    top_k: usize,
+    filter: Option<&Filter>,
+    consistency: ConsistencyLevel,
+) -> Result<QueryResponse> {
+    let manifest = Manifest::read(store, namespace).await?.unwrap_or_default();
+    let matches = |attrs: Option<&HashMap<String, AttributeValue>>| {
+        filter.is_none_or(|f| matches_filter(f, attrs))
+    };
+
+    // IDs whose segment versions the WAL hides: everything it mentions for
+    // strong reads, its deletes otherwise.
+    let (mut results, hidden, scanned_fragments) = match consistency {
+        ConsistencyLevel::Strong => {
+            let fragments = read_wal(store, wal_reader, namespace, &manifest).await?;
+            let mut latest: HashMap<&str, Option<&VectorEntry>> = HashMap::new();
+            for fragment in &fragments {
+                for id in &fragment.deletes {
+                    latest.insert(id, None);
+                }
+                for vector in &fragment.vectors {
+                    latest.insert(&vector.id, Some(vector));
+                }
+            }
+            let results: Vec<SearchResult> = latest
+                .values()
+                .flatten()
+                .filter(|v| matches(v.attributes.as_ref()))
+                .filter_map(|v| {
+                    let values = v.named_vectors.as_ref()?.get(field)?;
+                    (values.len() == query.len()).then(|| SearchResult {
+                        id: v.id.clone(),
+                        score: metric.score_from_distance(compute_distance(query, values, metric)),
+                        attributes: v.attributes.clone(),
+                        rank: None,
+                        values: None,
+                    })
+                })
+                .collect();
+            let hidden: HashSet<String> = latest.keys().map(|id| id.to_string()).collect();
+            (results, hidden, fragments.len())
+        }
+        ConsistencyLevel::EventualWithDeletes => {
+            let (ids, fragment_count) = scan_wal_deletes(wal_reader, namespace, &manifest).await?;
+            (Vec::new(), ids, fragment_count)
+        }
+        ConsistencyLevel::Eventual => (Vec::new(), HashSet::new(), 0),
+    };
+
+    let mut scanned_segments = 0;
+    for seg in manifest.live_segments() {
+        if !seg.named_vectors {
+            continue;
+        }
+        scanned_segments += 1;
+        let index = NamedVectorIndex::from_bytes(
+            &store.get(&named_vectors_key(namespace, &seg.id)).await?,
+        )?;
+        let candidates: Vec<(&str, f32)> = index
+            .search(field, query, metric)
+            .into_iter()
+            .filter(|(id, _)| !seg.tombstones.contains(*id) && !hidden.contains(*id))
+            .collect();
+        let id_map = load_id_map(store, namespace, seg).await?;
+        let mut found = 0;
+        for batch in candidates.chunks(top_k.max(1)) {
+            let ids: HashSet<&str> = batch.iter().map(|(id, _)| *id).collect();
+            let mut entries: HashMap<String, VectorEntry> =
+                fetch_from_segment(store, namespace, seg, id_map.as_ref(), &ids)
+                    .await?
+                    .into_iter()
+                    .map(|v| (v.id.clone(), v))
+                    .collect();
+            for (id, score) in batch {
+                let attributes = entries.remove(*id).and_then(|v| v.attributes);
+                if !matches(attributes.as_ref()) {
+                    continue;
+                }
+                results.push(SearchResult {
+                    id: id.to_string(),
+                    score: *score,
+                    attributes,
+                    rank: None,
+                    values: None,
+                });
+                found += 1;
+            }
+            if found >= top_k {
+                break;
+            }
+        }
+    }
+    debug!(
+        fragments_scanned = scanned_fragments,
+        segments_scanned = scanned_segments,
+        "named vector query complete"
+    );
+
+    // The WAL hides older segment versions, so every ID appears once.
+    results.sort_by(|a, b| {
+        metric
+            .compare_scores(a.score, b.score)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    results.truncate(top_k);
+    Ok(QueryResponse {
+        results,
+        scanned_fragments,
+        scanned_segments,
+        warnings: Vec::new(),
+        aggregations: BTreeMap::new(),
+        next_cursor: None,
+    })
+}
+
+/// Fill in the named vectors `seg`'s [`NamedVectorIndex`] holds for
+/// `vectors`, which were read from `seg`.
+pub(crate) async fn attach_named_vectors(
+    store: &ZeppelinStore,
+    namespace: &str,
+    seg: &SegmentRef,
+    vectors: &mut [VectorEntry],
+) -> Result<()> {
+    let index =
+        NamedVectorIndex::from_bytes(&store.get(&named_vectors_key(namespace, &seg.id)).await?)?;
+    let ids: HashSet<&str> = vectors.iter().map(|v| v.id.as_str()).collect();
+    let mut named = index.vectors(&ids);
+    for vector in vectors {
+        vector.named_vectors = named.remove(&vector.id);
+    }
+    Ok(())
+}
+
+/// Set the versions of `vectors`, all stored in `seg`, from its
+/// [`VersionIndex`].
+pub(crate) async fn attach_versions(
+    store: &ZeppelinStore,
+    namespace: &str,
+    seg: &SegmentRef,
+    vectors: &mut [VectorEntry],
+) -> Result<()> {
+    let index = VersionIndex::from_bytes(&store.get(&versions_key(namespace, &seg.id)).await?)?;
+    for vector in vectors {
+        vector.version = index.version(&vector.id).map(str::to_string);
+    }
+    Ok(())
+}
+
+/// Execute a late-interaction (ColBERT-style) query: each document with
+/// token vectors scores `Σᵢ maxⱼ qᵢ · dⱼ` over query tokens `qᵢ` and its
+/// tokens `dⱼ`, higher is better.
+///
+/// Token vectors are scored exhaustively: WAL vectors directly, segments
+/// through the [`TokenMatrix`] of every partition. Attributes are read for
+/// the best segment candidates only, a `top_k` batch at a time until
+/// `top_k` pass the filter.
+#[instrument(skip(store, wal_reader, queries, filter), fields(namespace = namespace, query_tokens = queries.len()))]
+pub async fn execute_maxsim_query(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    queries: &[Vec<f32>],
+    top_k: usize,
+    filter: Option<&Filter>,
+    consistency: ConsistencyLevel,
+) -> Result<QueryResponse> {
+    let manifest = Manifest::read(store, namespace).await?.unwrap_or_default();
+    let matches = |attrs: Option<&HashMap<String, AttributeValue>>| {
+        filter.is_none_or(|f| matches_filter(f, attrs))
+    };
+
+    // IDs whose segment versions the WAL hides: everything it mentions for
+    // strong reads, its deletes otherwise.
+    let (mut results, hidden, scanned_fragments) = match consistency {
+        ConsistencyLevel::Strong => {
+            let fragments = read_wal(store, wal_reader, namespace, &manifest).await?;
+            let mut latest: HashMap<&str, Option<&VectorEntry>> = HashMap::new();
+            for fragment in &fragments {
+                for id in &fragment.deletes {
+                    latest.insert(id, None);
+                }
+                for vector in &fragment.vectors {
+                    latest.insert(&vector.id, Some(vector));
+                }
+            }
+            let results: Vec<SearchResult> = latest
+                .values()
+                .flatten()
+                .filter(|v| matches(v.attributes.as_ref()))
+                .filter_map(|v| {
+                    let tokens = v.token_vectors.as_ref().filter(|t| !t.is_empty())?;
+                    Some(SearchResult {
+                        id: v.id.clone(),
+                        score: maxsim(queries, tokens.iter().map(Vec::as_slice)),
+                        attributes: v.attributes.clone(),
+                        rank: None,
+                        values: None,
+                    })
+                })
+                .collect();
+            let hidden: HashSet<String> = latest.keys().map(|id| id.to_string()).collect();
+            (results, hidden, fragments.len())
+        }
+        ConsistencyLevel::EventualWithDeletes => {
+            let (ids, fragment_count) = scan_wal_deletes(wal_reader, namespace, &manifest).await?;
+            (Vec::new(), ids, fragment_count)
+        }
+        ConsistencyLevel::Eventual => (Vec::new(), HashSet::new(), 0),
+    };
+
+    let mut scanned_segments = 0;
+    for seg in manifest.live_segments() {
+        if !seg.token_vectors {
+            continue;
+        }
+        scanned_segments += 1;
+        let matrices =
+            load_token_matrices(store, namespace, seg, 0..seg.cluster_count.max(1)).await?;
+        let mut candidates: Vec<(&str, f32)> = matrices
+            .iter()
+            .flat_map(|m| m.score(queries))
+            .filter(|(id, _)| !seg.tombstones.contains(*id) && !hidden.contains(*id))
+            .collect();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        let id_map = load_id_map(store, namespace, seg).await?;
+        let mut found = 0;
+        for batch in candidates.chunks(top_k.max(1)) {
+            let ids: HashSet<&str> = batch.iter().map(|(id, _)| *id).collect();
+            let mut entries: HashMap<String, VectorEntry> =
+                fetch_from_segment(store, namespace, seg, id_map.as_ref(), &ids)
+                    .await?
+                    .into_iter()
+                    .map(|v| (v.id.clone(), v))
+                    .collect();
+            for (id, score) in batch {
+                let attributes = entries.remove(*id).and_then(|v| v.attributes);
+                if !matches(attributes.as_ref()) {
+                    continue;
+                }
+                results.push(SearchResult {
+                    id: id.to_string(),
+                    score: *score,
+                    attributes,
+                    rank: None,
+                    values: None,
+                });
+                found += 1;
+            }
+            if found >= top_k {
+                break;
+            }
+        }
+    }
+    debug!(
+        fragments_scanned = scanned_fragments,
+        segments_scanned = scanned_segments,
+        "maxsim query complete"
+    );
+
+    // The WAL hides older segment versions, so every ID appears once.
+    results.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.id.cmp(&b.id)));
+    results.truncate(top_k);
+    Ok(QueryResponse {
+        results,
+        scanned_fragments,
+        scanned_segments,
+        warnings: Vec::new(),
+        aggregations: BTreeMap::new(),
+        next_cursor: None,
+    })
+}
+
+/// Read the [`TokenMatrix`] of each of `partitions` of `seg`, in parallel.
+async fn load_token_matrices(
+    store: &ZeppelinStore,
+    namespace: &str,
+    seg: &SegmentRef,
+    partitions: impl IntoIterator<Item = usize>,
+) -> Result<Vec<TokenMatrix>> {
+    let reads = partitions.into_iter().map(|i| async move {
+        TokenMatrix::from_bytes(&store.get(&token_matrix_key(namespace, &seg.id, i)).await?)
+    });
+    futures::future::try_join_all(reads).await
+}
+
+/// Fill in the token vectors `seg`'s token matrices hold for `vectors`,
+/// which were read from `seg`. With an ID map only the partitions holding
+/// `vectors` are read.
+pub(crate) async fn attach_token_vectors(
+    store: &ZeppelinStore,
+    namespace: &str,
+    seg: &SegmentRef,
+    id_map: Option<&SegmentIdMap>,
+    vectors: &mut [VectorEntry],
+) -> Result<()> {
+    let partitions: BTreeSet<usize> = match id_map {
+        Some(id_map) => vectors
+            .iter()
+            .filter_map(|v| id_map.partition_of(&v.id))
+            .collect(),
+        None => (0..seg.cluster_count.max(1)).collect(),
+    };
+    let matrices = load_token_matrices(store, namespace, seg, partitions).await?;
+    let ids: HashSet<&str> = vectors.iter().map(|v| v.id.as_str()).collect();
+    let mut tokens: HashMap<String, Vec<Vec<f32>>> =
+        matrices.iter().flat_map(|m| m.vectors(&ids)).collect();
+    for vector in vectors {
+        vector.token_vectors = tokens.remove(&vector.id);
+    }
+    Ok(())
+}
+
+/// Merge BM25 WAL and segment results (higher score = better).
+/// `wal_deleted_ids` contains IDs explicitly deleted in the WAL — these must
+/// not appear in the final results even if they exist in the segment.
+fn merge_bm25_results(
+    wal_results: Vec<SearchResult>,
+    segment_results: Vec<SearchResult>,
+    top_k: usize,
+    consistency: ConsistencyLevel,
+    wal_deleted_ids: &HashSet<String>,
+) -> Vec<SearchResult> {
+    match consistency {
+        ConsistencyLevel::Strong => {
+            let wal_ids: HashSet<String> = wal_results.iter().map(|r| r.id.clone()).collect();
+            let mut merged: Vec<SearchResult> = wal_results;
+
+            for sr in segment_results {
+                // Exclude if WAL has a newer version OR if explicitly deleted
+                if !wal_ids.contains(&sr.id) && !wal_deleted_ids.contains(&sr.id) {
+                    merged.push(sr);
+                }
+            }
+
+            // Sort DESCENDING (higher BM25 score = more relevant)
+            merged.sort_by(|a, b| {
+                b.score
+                    .partial_cmp(&a.score)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            merged.truncate(top_k);
+            merged
+        }
+        ConsistencyLevel::Eventual | ConsistencyLevel::EventualWithDeletes => {
+            // wal_deleted_ids is empty for Eventual.
+            let mut results = segment_results;
+            results.retain(|r| !wal_deleted_ids.contains(&r.id));
+            results.sort_by(|a, b| {
+                b.score
+                    .partial_cmp(&a.score)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            results.truncate(top_k);
+            results
+        }
+    }
+}
+
+/// Merge WAL results and segment results.
+///
+/// For Strong consistency: drop segment results whose IDs are in `wal_ids`
+/// (updated in the WAL), then merge both sorted lists and truncate to top_k.
+/// With `segment_written_at`, a segment result instead replaces the WAL
+/// result for its ID when the WAL write is not newer than that watermark.
+/// For EventualWithDeletes: drop segment results in `deleted_ids`.
+#[allow(clippy::too_many_arguments)]
+fn merge_results(
+    wal_results: Vec<SearchResult>,
+    segment_results: Vec<SearchResult>,
+    top_k: usize,
+    consistency: ConsistencyLevel,
+    distance_metric: DistanceMetric,
+    wal_ids: &HashMap<String, Ulid>,
+    deleted_ids: &HashSet<String>,
+    segment_written_at: Option<Ulid>,
+) -> Vec<SearchResult> {
+    match consistency {
+        ConsistencyLevel::Strong => {
+            // WAL results already have the latest state.
+            // Remove segment results whose IDs appear in the WAL (WAL is authoritative).
+            let mut merged: Vec<SearchResult> = wal_results;
+
+            let mut superseded = HashSet::new();
+            for sr in segment_results {
+                match (wal_ids.get(&sr.id), segment_written_at) {
+                    (None, _) => merged.push(sr),
+                    (Some(wal_written_at), Some(seg)) if *wal_written_at <= seg => {
+                        superseded.insert(sr.id.clone());
+                        merged.push(sr);
+                    }
+                    (Some(_), _) => {}
+                }
+            }
+            if !superseded.is_empty() {
+                let mut seen = HashSet::new();
+                // Segment versions were pushed after the WAL ones; keep the last.
+                merged.reverse();
+                merged.retain(|r| !superseded.contains(&r.id) || seen.insert(r.id.clone()));
+            }
+
+            merged.sort_by(|a, b| {
+                distance_metric
+                    .compare_scores(a.score, b.score)
+                    .then_with(|| a.id.cmp(&b.id))
+            });
+            merged.truncate(top_k);
+            merged
+        }
+        ConsistencyLevel::Eventual => {
+            let mut results = segment_results;
+            results.truncate(top_k);
+            results
+        }
+        ConsistencyLevel::EventualWithDeletes => {
+            let mut results = segment_results;
+            results.retain(|r| !deleted_ids.contains(&r.id));
+            results.truncate(top_k);
+            results
+        }
+    }
+}
+
+/// Live vector counts per attribute value, from [`count_by`].
+#[derive(Debug, Serialize)]
+pub struct CountByResponse {
+    /// Vectors per value of the field, for the `limit` largest groups.
+    pub counts: std::collections::BTreeMap<String, u64>,
+    /// Whether smaller groups were dropped to stay within `limit`.
+    pub truncated: bool,
+}
+
+/// Count live vectors (segment plus WAL, after deletes and overwrites)
+/// per value of `field`, optionally restricted to those matching `filter`.
+///
+/// Scalar values are grouped by their JSON text, so `3` and `"3"` share a
+/// group; a list counts the vector once per distinct element. Vectors
+/// without the field, or with an object there, are not counted. Only the
+/// `limit` largest groups are returned, ties broken by value.
+#[instrument(skip(store, wal_reader, filter), fields(namespace = namespace))]
+pub async fn count_by(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    field: &str,
+    filter: Option<&Filter>,
+    limit: usize,
+) -> Result<CountByResponse> {
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    let fragments = scan_live_attributes(store, wal_reader, namespace, |_, attributes| {
+        if filter.is_some_and(|f| !matches_filter(f, attributes)) {
+            return;
+        }
+        let Some(attrs) = attributes else { return };
+        for key in group_keys(resolve_field(attrs, field)) {
+            *counts.entry(key).or_insert(0) += 1;
+        }
+    })
+    .await?;
+
+    let resp = largest_groups(counts, limit);
+    debug!(
+        groups = resp.counts.len(),
+        truncated = resp.truncated,
+        fragments,
+        "count-by scan complete"
+    );
+    Ok(resp)
+}
+
+/// The `limit` largest of `counts`, ties broken by value.
+fn largest_groups(counts: HashMap<String, u64>, limit: usize) -> CountByResponse {
+    let mut groups: Vec<(String, u64)> = counts.into_iter().collect();
+    groups.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let truncated = groups.len() > limit;
+    groups.truncate(limit);
+    CountByResponse {
+        counts: groups.into_iter().collect(),
+        truncated,
+    }
+}
+
+/// Most groups a `terms` aggregation may return.
+pub const MAX_TERMS_SIZE: usize = 1000;
+
+/// A facet computed over every live vector matching a query's filter, not
+/// just the returned `top_k`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregation {
+    /// Vectors per value of `field`, for the `size` largest groups. Values
+    /// are grouped as in [`count_by`].
+    Terms {
+        field: String,
+        #[serde(default = "default_terms_size")]
+        size: usize,
+    },
+    /// Smallest numeric value of `field`.
+    Min { field: String },
+    /// Largest numeric value of `field`.
+    Max { field: String },
+    /// Mean of the numeric values of `field`.
+    Avg { field: String },
+}
+
+fn default_terms_size() -> usize {
+    10
+}
+
+impl Aggregation {
+    fn field(&self) -> &str {
+        match self {
+            Aggregation::Terms { field, .. }
+            | Aggregation::Min { field }
+            | Aggregation::Max { field }
+            | Aggregation::Avg { field } => field,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.field().is_empty() {
+            return Err(ZeppelinError::Validation(
+                "aggregation field cannot be empty".into(),
+            ));
+        }
+        match self {
+            Aggregation::Terms { size, .. } if *size == 0 || *size > MAX_TERMS_SIZE => {
+                Err(ZeppelinError::Validation(format!(
+                    "terms size must be between 1 and {MAX_TERMS_SIZE}"
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The result of one [`Aggregation`].
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum AggregationResult {
+    Terms(CountByResponse),
+    /// `value` is null when no matching vector has a numeric `field`;
+    /// `count` is the number of numeric values seen, list elements included.
+    Metric {
+        value: Option<f64>,
+        count: u64,
+    },
+}
+
+/// Running min, max and sum of the numeric values of one field.
+#[derive(Debug, Clone, Copy)]
+struct NumericStats {
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: u64,
+}
+
+impl Default for NumericStats {
+    fn default() -> Self {
+        Self {
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            sum: 0.0,
+            count: 0,
+        }
+    }
+}
+
+impl NumericStats {
+    fn add(&mut self, value: Option<&AttributeValue>) {
+        let values: Vec<f64> = match value {
+            Some(AttributeValue::Integer(i)) => vec![*i as f64],
+            Some(AttributeValue::Float(f)) => vec![*f],
+            Some(AttributeValue::IntegerList(xs)) => xs.iter().map(|&i| i as f64).collect(),
+            Some(AttributeValue::FloatList(xs)) => xs.clone(),
+            _ => return,
+        };
+        for v in values {
+            self.min = self.min.min(v);
+            self.max = self.max.max(v);
+            self.sum += v;
+            self.count += 1;
+        }
+    }
+
+    fn result(&self, aggregation: &Aggregation) -> AggregationResult {
+        let value = match aggregation {
+            _ if self.count == 0 => None,
+            Aggregation::Min { .. } => Some(self.min),
+            Aggregation::Max { .. } => Some(self.max),
+            _ => Some(self.sum / self.count as f64),
+        };
+        AggregationResult::Metric {
+            value,
+            count: self.count,
+        }
+    }
+}
+
+/// Compute `aggregations` over the live vectors (segment plus WAL, after
+/// deletes and overwrites) matching `filter`, in one attribute scan.
+/// Results are keyed like the request.
+#[instrument(skip(store, wal_reader, aggregations, filter), fields(namespace = namespace))]
+pub async fn aggregate(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    aggregations: &BTreeMap<String, Aggregation>,
+    filter: Option<&Filter>,
+) -> Result<BTreeMap<String, AggregationResult>> {
+    let mut terms: Vec<HashMap<String, u64>> = vec![HashMap::new(); aggregations.len()];
+    let mut stats = vec![NumericStats::default(); aggregations.len()];
+    let fragments = scan_live_attributes(store, wal_reader, namespace, |_, attributes| {
+        if filter.is_some_and(|f| !matches_filter(f, attributes)) {
+            return;
+        }
+        let Some(attrs) = attributes else { return };
+        for (i, aggregation) in aggregations.values().enumerate() {
+            let value = resolve_field(attrs, aggregation.field());
+            match aggregation {
+                Aggregation::Terms { .. } => {
+                    for key in group_keys(value) {
+                        *terms[i].entry(key).or_insert(0) += 1;
+                    }
+                }
+                _ => stats[i].add(value),
+            }
+        }
+    })
+    .await?;
+
+    debug!(
+        aggregations = aggregations.len(),
+        fragments, "aggregation scan complete"
+    );
+    Ok(aggregations
+        .iter()
+        .zip(terms.into_iter().zip(stats))
+        .map(|((name, aggregation), (counts, stats))| {
+            let result = match aggregation {
+                Aggregation::Terms { size, .. } => {
+                    AggregationResult::Terms(largest_groups(counts, *size))
+                }
+                _ => stats.result(aggregation),
+            };
+            (name.clone(), result)
+        })
+        .collect())
+}
+
+/// IDs of the live vectors (segments plus WAL, after deletes and
+/// overwrites) whose attributes match `filter`, in no particular order.
+/// Vectors without attributes are matched as if their attributes were
+/// empty; see [`matches_filter`].
+#[instrument(skip(store, wal_reader, filter), fields(namespace = namespace))]
+pub async fn matching_ids(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    filter: &Filter,
+) -> Result<Vec<String>> {
+    let mut ids = Vec::new();
+    let fragments = scan_live_attributes(store, wal_reader, namespace, |id, attributes| {
+        if matches_filter(filter, attributes) {
+            ids.push(id.to_string());
+        }
+    })
+    .await?;
+    debug!(matched = ids.len(), fragments, "filter scan complete");
+    Ok(ids)
+}
+
+/// Call `visit` with the ID and attributes of every live vector: the
+/// latest WAL version of each ID it mentions (unless deleted), then every
+/// segment vector not shadowed by the WAL or a segment tombstone. Only ID
+/// maps and attribute sidecars are read; see [`scan_segment_attributes`].
+/// Returns the number of WAL fragments scanned.
+async fn scan_live_attributes(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    mut visit: impl FnMut(&str, Option<&HashMap<String, AttributeValue>>),
+) -> Result<usize> {
+    let manifest = Manifest::read(store, namespace).await?.unwrap_or_default();
+
+    // Latest WAL state per ID; these IDs shadow their segment versions.
+    let fragments = read_wal(store, wal_reader, namespace, &manifest).await?;
+    let mut deleted_ids: HashSet<String> = HashSet::new();
+    let mut latest: HashMap<String, Option<HashMap<String, AttributeValue>>> = HashMap::new();
+    for fragment in &fragments {
+        for id in &fragment.deletes {
+            deleted_ids.insert(id.clone());
+            latest.remove(id);
+        }
+        for vec in &fragment.vectors {
+            deleted_ids.remove(&vec.id);
+            latest.insert(vec.id.clone(), vec.attributes.clone());
+        }
+    }
+
+    scan_segment_attributes(store, namespace, &manifest, |id, attributes| {
+        if !latest.contains_key(id) && !deleted_ids.contains(id) {
+            visit(id, attributes);
+        }
+    })
+    .await?;
+    for (id, attributes) in &latest {
+        visit(id, attributes.as_ref());
+    }
+    Ok(fragments.len())
+}
+
+/// Call `visit` with the ID and attributes of every vector in `manifest`'s
+/// live segments that no segment tombstone hides. The WAL is not consulted.
+/// Vectors in partitions without an attribute sidecar (none of them have
+/// attributes) are visited with `None`.
+///
+/// Each segment costs one read of its ID map plus one per attribute
+/// sidecar; vectors are never loaded. Segments written before ID maps
+/// recorded positions read each partition's ID list instead.
+async fn scan_segment_attributes(
+    store: &ZeppelinStore,
+    namespace: &str,
+    manifest: &Manifest,
+    mut visit: impl FnMut(&str, Option<&HashMap<String, AttributeValue>>),
+) -> Result<()> {
+    use crate::index::ivf_flat::build::{attrs_key, deserialize_attrs};
+
+    for seg in manifest.live_segments() {
+        let (partition_ids, attr_reads) = tokio::join!(
+            load_partition_ids(store, namespace, seg),
+            futures::future::join_all((0..seg.cluster_count).map(|i| {
+                let key = attrs_key(namespace, &seg.id, i);
+                async move { store.get(&key).await }
+            }))
+        );
+        for (ids, attrs_res) in partition_ids?.iter().zip(attr_reads) {
+            let attrs = match attrs_res {
+                Ok(data) => deserialize_attrs(&data)?,
+                Err(ZeppelinError::NotFound { .. }) => Vec::new(),
+                Err(e) => return Err(e),
+            };
+            for (i, id) in ids.iter().enumerate() {
+                if !seg.tombstones.contains(id) {
+                    visit(id, attrs.get(i).and_then(Option::as_ref));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Each partition's ID list for one segment, in stored order. Taken from
+/// the segment's ID map when it records positions, so IVF cluster blobs
+/// (which carry the vectors too) are only read for older segments.
+async fn load_partition_ids(
+    store: &ZeppelinStore,
+    namespace: &str,
+    seg: &SegmentRef,
+) -> Result<Vec<Vec<String>>> {
+    use crate::index::ivf_flat::build::{cluster_key, deserialize_cluster, docs_key};
+
+    if let Some(ids) = load_id_map(store, namespace, seg)
+        .await?
+        .and_then(|id_map| id_map.partition_ids(seg.cluster_count))
+    {
+        return Ok(ids);
+    }
+    let ids_key = if seg.text_only || seg.vamana {
+        docs_key
+    } else {
+        cluster_key
+    };
+    futures::future::join_all((0..seg.cluster_count).map(|i| {
+        let key = ids_key(namespace, &seg.id, i);
+        async move { Ok(deserialize_cluster(&store.get(&key).await?)?.ids) }
+    }))
+    .await
+    .into_iter()
+    .collect()
+}
+
+/// The latest version of vector `id`, or `None` if it was never written or
+/// its latest WAL entry is a delete. See [`fetch_vectors`].
+pub async fn get_vector(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    id: &str,
+) -> Result<Option<VectorEntry>> {
+    Ok(
+        fetch_vectors(store, wal_reader, namespace, &[id.to_string()])
+            .await?
+            .pop(),
+    )
+}
+
+/// The latest version of each of `ids` that exists, in request order and
+/// without duplicates. IDs never written, or whose latest WAL entry is a
+/// delete, are left out.
+///
+/// The uncompacted WAL decides first. The rest are looked up in the live
+/// segments, newest first, skipping segments that tombstone them; each
+/// segment's ID map narrows the read to the partitions holding them.
+#[instrument(skip(store, wal_reader, ids), fields(namespace = namespace, id_count = ids.len()))]
+pub async fn fetch_vectors(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    ids: &[String],
+) -> Result<Vec<VectorEntry>> {
+    let manifest = Manifest::read(store, namespace).await?.unwrap_or_default();
+    fetch_vectors_on_manifest(store, wal_reader, namespace, &manifest, ids).await
+}
+
+/// [`fetch_vectors`] against one manifest snapshot.
+async fn fetch_vectors_on_manifest(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    manifest: &Manifest,
+    ids: &[String],
+) -> Result<Vec<VectorEntry>> {
+    let wanted: HashSet<&str> = ids.iter().map(String::as_str).collect();
+
+    // Latest WAL entry per requested ID: the vector, or `None` for a delete.
+    let fragments = read_wal(store, wal_reader, namespace, manifest).await?;
+    let mut latest: HashMap<&str, Option<&VectorEntry>> = HashMap::new();
+    // Oldest first. Within a fragment upserts apply after deletes.
+    for fragment in &fragments {
+        for id in &fragment.deletes {
+            if wanted.contains(id.as_str()) {
+                latest.insert(id, None);
+            }
+        }
+        for vector in &fragment.vectors {
+            if wanted.contains(vector.id.as_str()) {
+                latest.insert(&vector.id, Some(vector));
+            }
+        }
+    }
+    let mut found: HashMap<String, VectorEntry> = latest
+        .values()
+        .flatten()
+        .map(|v| (v.id.clone(), (*v).clone()))
+        .collect();
+
+    let pending: HashSet<&str> = wanted
+        .into_iter()
+        .filter(|id| !latest.contains_key(id))
+        .collect();
+    let (from_segments, segments_read) =
+        fetch_from_segments(store, namespace, manifest, pending).await?;
+    found.extend(from_segments);
+
+    debug!(
+        found = found.len(),
+        fragments = fragments.len(),
+        segments_read,
+        "vector fetch complete"
+    );
+    // Removing as we go drops repeated IDs.
+    Ok(ids.iter().filter_map(|id| found.remove(id)).collect())
+}
+
+/// IDs in `vectors` whose latest version violates `condition`, in request
+/// order. An ID missing from an `IfVersionMatches` map only passes if the
+/// vector doesn't exist.
+#[instrument(skip(store, wal_reader, vectors, condition), fields(namespace = namespace))]
+pub async fn upsert_conflicts(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    vectors: &[VectorEntry],
+    condition: &UpsertCondition,
+) -> Result<Vec<VectorId>> {
+    let ids: Vec<String> = vectors.iter().map(|v| v.id.clone()).collect();
+    let current: HashMap<String, Option<String>> =
+        fetch_vectors(store, wal_reader, namespace, &ids)
+            .await?
+            .into_iter()
+            .map(|v| (v.id, v.version))
+            .collect();
+    Ok(ids
+        .into_iter()
+        .filter(|id| match condition {
+            UpsertCondition::IfNotExists => current.contains_key(id),
+            UpsertCondition::IfVersionMatches(expected) => {
+                match (current.get(id), expected.get(id)) {
+                    (Some(Some(version)), Some(expected)) => version != expected,
+                    (None, None) => false,
+                    _ => true,
+                }
+            }
+        })
+        .collect())
+}
+
+/// The newest segment version of each of `ids` that a live segment holds,
+/// skipping segments that tombstone it, plus the number of segments read.
+/// The WAL is not consulted.
+async fn fetch_from_segments(
+    store: &ZeppelinStore,
+    namespace: &str,
+    manifest: &Manifest,
+    mut pending: HashSet<&str>,
+) -> Result<(HashMap<String, VectorEntry>, usize)> {
+    let mut found = HashMap::new();
+    let mut segments_read = 0;
+    for seg in manifest.live_segments().into_iter().rev() {
+        if pending.is_empty() {
+            break;
+        }
+        let lookup: HashSet<&str> = pending
+            .iter()
+            .copied()
+            .filter(|id| !seg.tombstones.contains(*id))
+            .collect();
+        if lookup.is_empty() {
+            continue;
+        }
+        segments_read += 1;
+        let id_map = load_id_map(store, namespace, seg).await?;
+        let mut vectors =
+            fetch_from_segment(store, namespace, seg, id_map.as_ref(), &lookup).await?;
+        if seg.sparse && !vectors.is_empty() {
+            attach_sparse(store, namespace, seg, &mut vectors).await?;
+        }
+        if seg.named_vectors && !vectors.is_empty() {
+            attach_named_vectors(store, namespace, seg, &mut vectors).await?;
+        }
+        if seg.token_vectors && !vectors.is_empty() {
+            attach_token_vectors(store, namespace, seg, id_map.as_ref(), &mut vectors).await?;
+        }
+        if seg.versions && !vectors.is_empty() {
+            attach_versions(store, namespace, seg, &mut vectors).await?;
+        }
+        for vector in vectors {
+            pending.remove(vector.id.as_str());
+            found.insert(vector.id.clone(), vector);
+        }
+    }
+    Ok((found, segments_read))
+}
+
+/// The manifest's uncompacted WAL fragments, oldest first, with their
+/// attribute patches resolved into upserts; see [`resolve_patches`].
+///
+/// While a filter patch sits in the uncompacted WAL, every call rescans
+/// the attribute sidecars of all live segments to expand it (see
+/// [`expand_filter_patches`]), so strong queries pay that scan until the
+/// next compaction folds the patch into a segment.
+async fn read_wal(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    manifest: &Manifest,
+) -> Result<Vec<WalFragment>> {
+    let refs = manifest.uncompacted_fragments().to_vec();
+    let mut fragments = wal_reader
+        .read_fragments_from_refs(namespace, &refs)
+        .await?;
+    resolve_patches(store, namespace, manifest, &mut fragments).await?;
+    for fragment in &mut fragments {
+        fragment.stamp_versions();
+    }
+    Ok(fragments)
+}
+
+/// Rewrite the attribute patches in `fragments` (uncompacted, oldest first)
+/// as full upserts in the fragment carrying them, so readers and compaction
+/// only deal with upserts and deletes. Filter patches are first expanded
+/// into ID patches; see [`expand_filter_patches`].
+///
+/// Each patch merges over the state its ID has at that point: the latest
+/// WAL upsert before it, or, for IDs the WAL hasn't touched yet, the newest
+/// version in `manifest`'s live segments. A patch on an ID that is deleted
+/// or was never written is dropped.
+pub(crate) async fn resolve_patches(
+    store: &ZeppelinStore,
+    namespace: &str,
+    manifest: &Manifest,
+    fragments: &mut [WalFragment],
+) -> Result<()> {
+    if fragments.iter().any(|f| !f.filter_patches.is_empty()) {
+        expand_filter_patches(store, namespace, manifest, fragments).await?;
+    }
+    if fragments.iter().all(|f| f.patches.is_empty()) {
+        return Ok(());
+    }
+
+    // Patched IDs, and those whose first WAL mention is a patch: their base
+    // version lives in a segment.
+    let mut patched: HashSet<String> = HashSet::new();
+    let mut from_segments: HashSet<String> = HashSet::new();
+    let mut touched: HashSet<&str> = HashSet::new();
+    for fragment in fragments.iter() {
+        touched.extend(fragment.deletes.iter().map(String::as_str));
+        touched.extend(fragment.vectors.iter().map(|v| v.id.as_str()));
+        for patch in &fragment.patches {
+            patched.insert(patch.id.clone());
+            if !touched.contains(patch.id.as_str()) {
+                from_segments.insert(patch.id.clone());
+            }
+        }
+    }
+    let (base, _) = fetch_from_segments(
+        store,
+        namespace,
+        manifest,
+        from_segments.iter().map(String::as_str).collect(),
+    )
+    .await?;
+
+    // Current state of each patched ID; `None` once deleted.
+    let mut state: HashMap<String, Option<VectorEntry>> = from_segments
+        .into_iter()
+        .map(|id| {
+            let vector = base.get(&id).cloned();
+            (id, vector)
+        })
+        .collect();
+    let mut resolved = 0;
+    for fragment in fragments.iter_mut() {
+        for id in &fragment.deletes {
+            if patched.contains(id) {
+                state.insert(id.clone(), None);
+            }
+        }
+        for vector in &fragment.vectors {
+            if patched.contains(&vector.id) {
+                state.insert(vector.id.clone(), Some(vector.clone()));
+            }
+        }
+        for patch in std::mem::take(&mut fragment.patches) {
+            let Some(Some(current)) = state.get_mut(&patch.id) else {
+                continue;
+            };
+            current
+                .attributes
+                .get_or_insert_with(HashMap::new)
+                .extend(patch.attributes);
+            resolved += 1;
+            match fragment.vectors.iter_mut().find(|v| v.id == patch.id) {
+                Some(existing) => *existing = current.clone(),
+                None => fragment.vectors.push(current.clone()),
+            }
+        }
+    }
+    debug!(
+        patched = patched.len(),
+        resolved, "resolved WAL attribute patches"
+    );
+    Ok(())
+}
+
+/// Replace each filter patch in `fragments` with an ID patch for every
+/// vector it matches at that point in the WAL, appended to its fragment's
+/// ID patches.
+///
+/// Matching needs the attributes of every live vector, so this reads every
+/// live segment's ID map and attribute sidecars (not its vectors); see
+/// [`scan_segment_attributes`]. Vectors without attributes are matched as
+/// if their attributes were empty.
+async fn expand_filter_patches(
+    store: &ZeppelinStore,
+    namespace: &str,
+    manifest: &Manifest,
+    fragments: &mut [WalFragment],
+) -> Result<()> {
+    // Attributes of every live vector, as of the fragment being replayed.
+    let mut live: HashMap<String, Option<HashMap<String, AttributeValue>>> = HashMap::new();
+    scan_segment_attributes(store, namespace, manifest, |id, attributes| {
+        live.insert(id.to_string(), attributes.cloned());
+    })
+    .await?;
+
+    let mut expanded = 0;
+    for fragment in fragments.iter_mut() {
+        for id in &fragment.deletes {
+            live.remove(id);
+        }
+        for vector in &fragment.vectors {
+            live.insert(vector.id.clone(), vector.attributes.clone());
+        }
+        for patch in &fragment.patches {
+            if let Some(attrs) = live.get_mut(&patch.id) {
+                attrs
+                    .get_or_insert_with(HashMap::new)
+                    .extend(patch.attributes.clone());
+            }
+        }
+        for filter_patch in std::mem::take(&mut fragment.filter_patches) {
+            for (id, attrs) in live.iter_mut() {
+                if !matches_filter(&filter_patch.filter, attrs.as_ref()) {
+                    continue;
+                }
+                attrs
+                    .get_or_insert_with(HashMap::new)
+                    .extend(filter_patch.attributes.clone());
+                fragment.patches.push(AttributePatch {
+                    id: id.clone(),
+                    attributes: filter_patch.attributes.clone(),
+                });
+                expanded += 1;
+            }
+        }
+    }
+    debug!(expanded, "expanded WAL filter patches");
+    Ok(())
+}
+
+/// One page of [`list_vectors`].
+#[derive(Debug, Serialize)]
+pub struct VectorPage {
+    pub vectors: Vec<VectorEntry>,
+    /// Pass back to continue after this page; absent on the last one.
+    /// Opaque to clients.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Where a listed vector's latest version lives.
+enum ListedFrom<'a> {
+    Wal(&'a VectorEntry),
+    /// Index into the manifest's live segments.
+    Segment(usize),
+}
+
+/// Up to `limit` live vectors in ID order, starting after the ID `after`.
+///
+/// Ordering by ID rather than storage layout keeps cursors valid across
+/// compactions. Each live segment contributes its first `limit + 1` IDs
+/// past the cursor, read from its ID map, and only the partitions holding
+/// the page's vectors are fetched. A write or delete landing mid-scan shows
+/// up in later pages only if its ID sorts after the cursor.
+#[instrument(skip(store, wal_reader), fields(namespace = namespace))]
+pub async fn list_vectors(
+    store: &ZeppelinStore,
+    wal_reader: &WalReader,
+    namespace: &str,
+    after: Option<&str>,
+    limit: usize,
+) -> Result<VectorPage> {
+    let manifest = Manifest::read(store, namespace).await?.unwrap_or_default();
+    let fragments = read_wal(store, wal_reader, namespace, &manifest).await?;
+    // Latest WAL entry per ID: the vector, or `None` for a delete. Oldest
+    // first; within a fragment upserts apply after deletes.
+    let mut wal: BTreeMap<&str, Option<&VectorEntry>> = BTreeMap::new();
+    for fragment in &fragments {
+        for id in &fragment.deletes {
+            wal.insert(id, None);
+        }
+        for vector in &fragment.vectors {
+            wal.insert(&vector.id, Some(vector));
+        }
+    }
+    let past_cursor = |id: &str| after.is_none_or(|a| id > a);
+
+    // The first `limit + 1` live IDs past the cursor; the extra one tells
+    // whether another page follows.
+    let mut page: BTreeMap<String, ListedFrom> = wal
+        .iter()
+        .filter(|(id, _)| past_cursor(id))
+        .filter_map(|(id, v)| v.map(|v| (id.to_string(), ListedFrom::Wal(v))))
+        .take(limit + 1)
+        .collect();
+    let live = manifest.live_segments();
+    let mut id_maps = Vec::with_capacity(live.len());
+    for (s, seg) in live.iter().enumerate() {
+        let id_map = load_id_map(store, namespace, seg).await?;
+        let scanned;
+        let ids = match &id_map {
+            Some(id_map) => id_map.ids(),
+            None => {
+                scanned = scan_segment_ids(store, namespace, seg).await?;
+                &scanned[..]
+            }
+        };
+        let start = after.map_or(0, |a| ids.partition_point(|id| id.as_str() <= a));
+        let candidates = ids[start..]
+            .iter()
+            .filter(|id| !seg.tombstones.contains(*id) && !wal.contains_key(id.as_str()))
+            .take(limit + 1);
+        for id in candidates {
+            page.insert(id.clone(), ListedFrom::Segment(s));
+        }
+        while page.len() > limit + 1 {
+            page.pop_last();
+        }
+        id_maps.push(id_map);
+    }
+    let next_cursor = if page.len() > limit {
+        page.pop_last();
+        page.keys().next_back().cloned()
+    } else {
+        None
     };
-    let segment_duration = segment_start.elapsed();
-    debug!(
-        segment_duration_ms = segment_duration.as_millis() as u64,
-        segments_scanned = scanned_segments,
-        "BM25 query phase: segment search"
-    );
 
-    // Merge results — BM25 is higher-is-better
-    // Pass deleted IDs so segment results for deleted docs are excluded
-    let results = merge_bm25_results(
-        wal_results,
-        segment_results,
-        top_k,
-        consistency,
-        &wal_deleted_ids,
-    );
+    let mut wanted: HashMap<usize, HashSet<&str>> = HashMap::new();
+    for (id, from) in &page {
+        if let ListedFrom::Segment(s) = from {
+            wanted.entry(*s).or_default().insert(id);
+        }
+    }
+    let reads =
+        futures::future::join_all(wanted.iter().map(|(&s, ids)| {
+            fetch_from_segment(store, namespace, live[s], id_maps[s].as_ref(), ids)
+        }))
+        .await;
+    let mut fetched: HashMap<String, VectorEntry> = HashMap::new();
+    for read in reads {
+        fetched.extend(read?.into_iter().map(|v| (v.id.clone(), v)));
+    }
+    let vectors: Vec<VectorEntry> = page
+        .iter()
+        .filter_map(|(id, from)| match from {
+            ListedFrom::Wal(v) => Some((*v).clone()),
+            ListedFrom::Segment(_) => fetched.remove(id),
+        })
+        .collect();
 
-    Ok(QueryResponse {
-        results,
-        scanned_fragments,
-        scanned_segments,
+    debug!(
+        listed = vectors.len(),
+        more = next_cursor.is_some(),
+        "vector page listed"
+    );
+    Ok(VectorPage {
+        vectors,
+        next_cursor,
     })
 }
 
-/// Search a segment's inverted indexes for a BM25 query.
-#[allow(clippy::too_many_arguments)]
-async fn segment_bm25_search(
+/// Every ID a segment stores, sorted, read from its partitions' ID lists.
+/// Only needed for segments without an ID map.
+async fn scan_segment_ids(
     store: &ZeppelinStore,
     namespace: &str,
-    segment_ref: &SegmentRef,
-    rank_by: &RankBy,
-    fts_configs: &HashMap<String, FtsFieldConfig>,
-    filter: Option<&Filter>,
-    last_as_prefix: bool,
-) -> Result<Vec<SearchResult>> {
-    use crate::index::ivf_flat::build::{attrs_key, deserialize_attrs};
-
-    let segment_id = &segment_ref.id;
-    let fts_fields = &segment_ref.fts_fields;
+    seg: &SegmentRef,
+) -> Result<Vec<String>> {
+    use crate::index::ivf_flat::build::{cluster_key, deserialize_cluster, docs_key};
 
-    // Load the IVF-Flat index using manifest metadata to skip cluster probing.
-    let index = IvfFlatIndex::load_from_manifest(
-        store,
-        namespace,
-        segment_id,
-        segment_ref.vector_count,
-        segment_ref.quantization,
-    )
-    .await?;
-    let num_clusters = index.num_clusters();
+    let ids_key = if seg.text_only || seg.vamana {
+        docs_key
+    } else {
+        cluster_key
+    };
+    let reads = futures::future::join_all((0..seg.cluster_count).map(|i| {
+        let key = ids_key(namespace, &seg.id, i);
+        async move { store.get(&key).await }
+    }))
+    .await;
+    let mut ids = Vec::new();
+    for data in reads {
+        ids.extend(deserialize_cluster(&data?)?.ids);
+    }
+    ids.sort_unstable();
+    Ok(ids)
+}
 
-    let field_queries = rank_by.extract_field_queries();
-    let mut all_results: HashMap<
-        String,
-        (f32, Option<HashMap<String, crate::types::AttributeValue>>),
-    > = HashMap::new();
+/// A segment's ID map, or `None` for segments written before ID maps
+/// existed.
+async fn load_id_map(
+    store: &ZeppelinStore,
+    namespace: &str,
+    seg: &SegmentRef,
+) -> Result<Option<SegmentIdMap>> {
+    match store.get(&segment_id_map_key(namespace, &seg.id)).await {
+        Ok(data) => Ok(Some(SegmentIdMap::from_bytes(&data)?)),
+        Err(ZeppelinError::NotFound { .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
 
-    // Parallel prefetch all cluster data (fts index, attrs, cluster vectors).
-    let prefetched = futures::future::join_all((0..num_clusters).map(|cluster_idx| {
-        let fts_key = fts_index_key(namespace, segment_id, cluster_idx);
-        let akey = attrs_key(namespace, segment_id, cluster_idx);
-        let ckey = crate::index::ivf_flat::build::cluster_key(namespace, segment_id, cluster_idx);
-        async move {
-            let (fts_res, attrs_res, cluster_res) =
-                tokio::join!(store.get(&fts_key), store.get(&akey), store.get(&ckey),);
-            (cluster_idx, fts_res, attrs_res, cluster_res)
+/// Read the vectors in `ids` that one segment stores. Only the partitions
+/// its ID map points at are read; without one every partition is scanned.
+async fn fetch_from_segment(
+    store: &ZeppelinStore,
+    namespace: &str,
+    seg: &SegmentRef,
+    id_map: Option<&SegmentIdMap>,
+    ids: &HashSet<&str>,
+) -> Result<Vec<VectorEntry>> {
+    let partitions: Vec<usize> = match id_map {
+        Some(id_map) => {
+            let hit: std::collections::BTreeSet<usize> = ids
+                .iter()
+                .filter_map(|id| id_map.partition_of(id))
+                .collect();
+            hit.into_iter().collect()
         }
-    }))
+        None => (0..seg.cluster_count).collect(),
+    };
+    let reads = futures::future::join_all(
+        partitions
+            .into_iter()
+            .map(|i| fetch_from_partition(store, namespace, seg, i, ids)),
+    )
     .await;
+    let mut vectors = Vec::new();
+    for read in reads {
+        vectors.extend(read?);
+    }
+    Ok(vectors)
+}
 
-    // Process prefetched results — CPU-bound, no I/O.
-    for (_cluster_idx, fts_res, attrs_res, cluster_res) in prefetched {
-        let fts_data = match fts_res {
-            Ok(data) => data,
-            Err(crate::error::ZeppelinError::NotFound { .. }) => continue,
-            Err(e) => return Err(e),
-        };
+/// Read the vectors in `ids` that one partition of a segment stores, with
+/// their values and attributes.
+async fn fetch_from_partition(
+    store: &ZeppelinStore,
+    namespace: &str,
+    seg: &SegmentRef,
+    partition: usize,
+    ids: &HashSet<&str>,
+) -> Result<Vec<VectorEntry>> {
+    use crate::index::ivf_flat::build::{
+        attrs_key, cluster_key, deserialize_attrs, deserialize_cluster, docs_key,
+    };
+    use crate::index::vamana::{deserialize_graph_block, graph_block_key};
 
-        let inv_index = InvertedIndex::from_bytes(&fts_data)?;
+    let ids_key = if seg.text_only || seg.vamana {
+        docs_key
+    } else {
+        cluster_key
+    };
+    let cluster = deserialize_cluster(&store.get(&ids_key(namespace, &seg.id, partition)).await?)?;
+    let positions: Vec<usize> = (0..cluster.ids.len())
+        .filter(|&j| ids.contains(cluster.ids[j].as_str()))
+        .collect();
+    if positions.is_empty() {
+        return Ok(Vec::new());
+    }
 
-        let attrs_data = match attrs_res {
-            Ok(data) => data,
-            Err(_) => continue,
-        };
-        let cluster_attrs = deserialize_attrs(&attrs_data)?;
+    let mut attrs = match store.get(&attrs_key(namespace, &seg.id, partition)).await {
+        Ok(data) => deserialize_attrs(&data)?,
+        Err(ZeppelinError::NotFound { .. }) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    let mut values = if seg.text_only {
+        Vec::new()
+    } else if seg.vamana {
+        let block = store
+            .get(&graph_block_key(namespace, &seg.id, partition))
+            .await?;
+        deserialize_graph_block(&block)?
+            .into_iter()
+            .map(|node| node.vector)
+            .collect()
+    } else {
+        cluster.vectors
+    };
+    Ok(positions
+        .into_iter()
+        .map(|j| VectorEntry {
+            id: cluster.ids[j].clone(),
+            values: values.get_mut(j).map(std::mem::take).unwrap_or_default(),
+            attributes: attrs.get_mut(j).and_then(Option::take),
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
+        })
+        .collect())
+}
 
-        let cluster_data = match cluster_res {
-            Ok(data) => data,
-            Err(e) => return Err(e),
-        };
-        let cluster = crate::index::ivf_flat::build::deserialize_cluster(&cluster_data)?;
+/// The groups a value counts toward in [`count_by`] and `terms`
+/// aggregations.
+fn group_keys(value: Option<&AttributeValue>) -> Vec<String> {
+    let mut keys = match value {
+        Some(AttributeValue::String(s)) => vec![s.clone()],
+        Some(AttributeValue::Integer(i)) => vec![i.to_string()],
+        Some(AttributeValue::Float(f)) => vec![f.to_string()],
+        Some(AttributeValue::Bool(b)) => vec![b.to_string()],
+        Some(AttributeValue::StringList(xs)) => xs.clone(),
+        Some(AttributeValue::IntegerList(xs)) => xs.iter().map(i64::to_string).collect(),
+        Some(AttributeValue::FloatList(xs)) => xs.iter().map(f64::to_string).collect(),
+        Some(AttributeValue::Object(_)) | None => Vec::new(),
+    };
+    keys.sort();
+    keys.dedup();
+    keys
+}
 
-        // For each field+query, search the inverted index
-        let mut position_field_scores: HashMap<u32, HashMap<String, f32>> = HashMap::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
 
-        for (field, query) in &field_queries {
-            let config = match fts_configs.get(field.as_str()) {
-                Some(c) => c,
-                None => continue,
-            };
+    fn result(id: &str, score: f32) -> SearchResult {
+        SearchResult {
+            id: id.to_string(),
+            score,
+            attributes: None,
+            rank: None,
+            values: None,
+        }
+    }
 
-            if !fts_fields.contains(field) {
-                continue;
-            }
+    #[test]
+    fn test_fuse_results_rrf() {
+        // Euclidean: lower is closer, but only ranks matter for RRF.
+        let dense = vec![result("a", 0.1), result("b", 0.2), result("c", 0.3)];
+        let text = vec![result("c", 9.0), result("d", 5.0)];
+        let fused = fuse_results(
+            dense,
+            text,
+            DistanceMetric::Euclidean,
+            Fusion::Rrf { k: 1.0 },
+            3,
+        );
+        let ids: Vec<&str> = fused.iter().map(|r| r.id.as_str()).collect();
+        // c: 1/4 + 1/2, a: 1/2, d: 1/3, b: 1/3.
+        assert_eq!(ids, ["c", "a", "b"]);
+        assert!((fused[0].score - 0.75).abs() < 1e-6);
+    }
 
-            let query_tokens = tokenize_text(query, config, last_as_prefix);
+    #[test]
+    fn test_fuse_results_weighted() {
+        let dense = vec![result("a", 0.0), result("b", 1.0)];
+        let text = vec![result("b", 10.0), result("a", 2.0)];
+        let fuse = |vector_weight| {
+            fuse_results(
+                dense.clone(),
+                text.clone(),
+                DistanceMetric::Cosine,
+                Fusion::Weighted { vector_weight },
+                2,
+            )
+        };
+        assert_eq!(fuse(0.9)[0].id, "a");
+        assert!((fuse(0.9)[0].score - 0.9).abs() < 1e-6);
+        assert_eq!(fuse(0.1)[0].id, "b");
+        assert!(Fusion::Weighted { vector_weight: 1.5 }.validate().is_err());
+        assert!(Fusion::default().validate().is_ok());
+    }
 
-            let params = Bm25Params {
-                k1: config.k1,
-                b: config.b,
-            };
-            let results = if last_as_prefix {
-                inv_index.search_prefix(field, &query_tokens, &params)
-            } else {
-                inv_index.search(field, &query_tokens, &params)
-            };
+    #[test]
+    fn test_mmr_rerank_skips_near_duplicates() {
+        let candidates = vec![result("a", 0.0), result("a2", 0.0), result("b", 0.632)];
+        let vectors: HashMap<String, Vec<f32>> = [
+            ("a".to_string(), vec![1.0, 0.0]),
+            ("a2".to_string(), vec![1.0, 0.0]),
+            ("b".to_string(), vec![0.8, 0.6]),
+        ]
+        .into();
+        let ids = |lambda| -> Vec<String> {
+            mmr_rerank(
+                candidates.clone(),
+                &vectors,
+                &[1.0, 0.0],
+                DistanceMetric::Euclidean,
+                lambda,
+                2,
+            )
+            .into_iter()
+            .map(|r| r.id)
+            .collect()
+        };
+        assert_eq!(ids(0.3), vec!["a", "b"]);
+        assert_eq!(ids(1.0), vec!["a", "a2"]);
+        assert!(Diversify { lambda: -0.1 }.validate().is_err());
+    }
 
-            for (pos, score) in results {
-                let entry = position_field_scores.entry(pos).or_default();
-                *entry.entry(field.to_string()).or_insert(0.0) += score;
-            }
+    #[test]
+    fn test_collapse_groups_caps_each_value() {
+        let with_doc = |id: &str, score: f32, doc: Option<&str>| SearchResult {
+            attributes: doc.map(|d| {
+                HashMap::from([("doc".to_string(), AttributeValue::String(d.to_string()))])
+            }),
+            ..result(id, score)
+        };
+        let results = vec![
+            with_doc("a1", 0.1, Some("a")),
+            with_doc("a2", 0.2, Some("a")),
+            with_doc("a3", 0.3, Some("a")),
+            with_doc("x", 0.4, None),
+            with_doc("b1", 0.5, Some("b")),
+        ];
+        let group_by = GroupBy {
+            field: "doc".to_string(),
+            group_size: 2,
+        };
+        let ids: Vec<String> = collapse_groups(results.clone(), &group_by, 10)
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(ids, vec!["a1", "a2", "x", "b1"]);
+        assert_eq!(collapse_groups(results, &group_by, 3).len(), 3);
+        assert!(GroupBy {
+            group_size: 0,
+            ..group_by
         }
+        .validate()
+        .is_err());
+    }
 
-        // Evaluate rank_by expression and collect results
-        for (pos, field_scores) in position_field_scores {
-            let final_score = evaluate_rank_by(rank_by, &field_scores);
-            if final_score <= 0.0 {
-                continue;
-            }
-
-            let pos_usize = pos as usize;
-            if pos_usize >= cluster.ids.len() {
-                continue;
-            }
-
-            let id = cluster.ids[pos_usize].clone();
-            let attrs = cluster_attrs.get(pos_usize).cloned().flatten();
+    #[test]
+    fn test_select_top_k_matches_full_sort() {
+        let mut rng = rand::thread_rng();
+        // Coarse scores so many results tie and the ID tie-break matters.
+        let results: Vec<SearchResult> = (0..500)
+            .map(|i| result(&format!("v{i}"), rng.gen_range(0..20) as f32 / 4.0))
+            .collect();
 
-            // Apply post-filter
-            if let Some(f) = filter {
-                match &attrs {
-                    Some(a) => {
-                        if !evaluate_filter(f, a) {
-                            continue;
-                        }
-                    }
-                    None => continue,
-                }
-            }
+        let mut sorted = results.clone();
+        sorted.sort_by(|a, b| a.score.total_cmp(&b.score).then_with(|| a.id.cmp(&b.id)));
 
-            // Accumulate: same ID might appear in multiple clusters (shouldn't, but be safe)
-            let entry = all_results.entry(id.clone()).or_insert((0.0, attrs));
-            if final_score > entry.0 {
-                entry.0 = final_score;
-            }
+        for k in [0, 1, 10, 499, 500, 1000] {
+            let top = select_top_k(results.clone(), k, DistanceMetric::Euclidean);
+            let expected: Vec<_> = sorted.iter().take(k).map(|r| (&r.id, r.score)).collect();
+            let got: Vec<_> = top.iter().map(|r| (&r.id, r.score)).collect();
+            assert_eq!(got, expected, "k={k}");
         }
     }
 
-    let mut results: Vec<SearchResult> = all_results
-        .into_iter()
-        .map(|(id, (score, attributes))| SearchResult {
-            id,
-            score,
-            attributes,
-        })
-        .collect();
-
-    // Sort descending by score
-    results.sort_by(|a, b| {
-        b.score
-            .partial_cmp(&a.score)
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
+    #[test]
+    fn test_merge_drops_segment_versions_of_truncated_wal_ids() {
+        // "b" was updated in the WAL and fell outside the WAL's top_k; its
+        // stale segment version must not resurface.
+        let wal = vec![result("a", 0.1)];
+        let wal_ids: HashMap<String, Ulid> = ["a", "b"]
+            .iter()
+            .map(|s| (s.to_string(), Ulid::new()))
+            .collect();
+        let segment = vec![result("b", 0.05), result("c", 0.2)];
 
-    Ok(results)
-}
+        let merged = merge_results(
+            wal,
+            segment,
+            2,
+            ConsistencyLevel::Strong,
+            DistanceMetric::Euclidean,
+            &wal_ids,
+            &HashSet::new(),
+            None,
+        );
+        let ids: Vec<_> = merged.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["a", "c"]);
+    }
 
-/// Merge BM25 WAL and segment results (higher score = better).
-/// `wal_deleted_ids` contains IDs explicitly deleted in the WAL — these must
-/// not appear in the final results even if they exist in the segment.
-fn merge_bm25_results(
-    wal_results: Vec<SearchResult>,
-    segment_results: Vec<SearchResult>,
-    top_k: usize,
-    consistency: ConsistencyLevel,
-    wal_deleted_ids: &HashSet<String>,
-) -> Vec<SearchResult> {
-    match consistency {
-        ConsistencyLevel::Strong => {
-            let wal_ids: HashSet<String> = wal_results.iter().map(|r| r.id.clone()).collect();
-            let mut merged: Vec<SearchResult> = wal_results;
+    #[test]
+    fn test_merge_by_write_time_keeps_newer_source() {
+        let older = Ulid::from_parts(1, 0);
+        let watermark = Ulid::from_parts(2, 0);
+        let newer = Ulid::from_parts(3, 0);
+        let wal = vec![result("old", 0.5), result("new", 0.6)];
+        let wal_ids = HashMap::from([("old".to_string(), older), ("new".to_string(), newer)]);
+        let segment = vec![result("old", 0.1), result("new", 0.2)];
 
-            for sr in segment_results {
-                // Exclude if WAL has a newer version OR if explicitly deleted
-                if !wal_ids.contains(&sr.id) && !wal_deleted_ids.contains(&sr.id) {
-                    merged.push(sr);
-                }
-            }
+        let merged = merge_results(
+            wal,
+            segment,
+            10,
+            ConsistencyLevel::Strong,
+            DistanceMetric::Euclidean,
+            &wal_ids,
+            &HashSet::new(),
+            Some(watermark),
+        );
+        let got: Vec<_> = merged.iter().map(|r| (r.id.as_str(), r.score)).collect();
+        assert_eq!(got, [("old", 0.1), ("new", 0.6)]);
+    }
 
-            // Sort DESCENDING (higher BM25 score = more relevant)
-            merged.sort_by(|a, b| {
-                b.score
-                    .partial_cmp(&a.score)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            });
-            merged.truncate(top_k);
-            merged
+    #[test]
+    fn test_merge_puts_best_result_first_per_metric() {
+        let wal_ids = HashMap::from([("w".to_string(), Ulid::new())]);
+        for (metric, best) in [
+            (DistanceMetric::Cosine, 0.1),
+            (DistanceMetric::Euclidean, 0.1),
+            (DistanceMetric::DotProduct, 0.9),
+        ] {
+            let merged = merge_results(
+                vec![result("w", 0.5)],
+                vec![result("a", 0.1), result("b", 0.9)],
+                3,
+                ConsistencyLevel::Strong,
+                metric,
+                &wal_ids,
+                &HashSet::new(),
+                None,
+            );
+            assert_eq!(merged[0].score, best, "{metric}");
+            assert!(
+                merged
+                    .windows(2)
+                    .all(|w| !metric.better_than(w[1].score, w[0].score)),
+                "{metric}: {merged:?}"
+            );
+            let top = select_top_k(merged.clone(), 1, metric);
+            assert_eq!(top[0].score, best, "{metric}");
         }
-        ConsistencyLevel::Eventual => {
-            let mut results = segment_results;
-            results.sort_by(|a, b| {
-                b.score
-                    .partial_cmp(&a.score)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            });
-            results.truncate(top_k);
-            results
+    }
+
+    #[test]
+    fn test_query_cursor_round_trips() {
+        let cursor = QueryCursor {
+            score: -0.25,
+            id: "doc:7".into(),
+            offset: 42,
+        };
+        assert_eq!(QueryCursor::decode(&cursor.encode()).unwrap(), cursor);
+        for bad in [
+            "",
+            "3f800000",
+            "3f800000doc",
+            "3f800000:doc",
+            "3f8000002a:",
+            "3f800000zz:doc",
+            "zzzzzzzz2a:doc",
+            "7fc000002a:doc",
+        ] {
+            assert!(QueryCursor::decode(bad).is_err(), "{bad}");
         }
     }
-}
 
-/// Merge WAL results and segment results.
-///
-/// For Strong consistency: filter segment results to remove any IDs that were
-/// deleted or updated in the WAL, then merge both sorted lists and truncate to top_k.
-fn merge_results(
-    wal_results: Vec<SearchResult>,
-    segment_results: Vec<SearchResult>,
-    top_k: usize,
-    consistency: ConsistencyLevel,
-) -> Vec<SearchResult> {
-    match consistency {
-        ConsistencyLevel::Strong => {
-            // WAL results already have the latest state.
-            // Remove segment results whose IDs appear in WAL results (WAL is authoritative).
-            let wal_ids: HashSet<String> = wal_results.iter().map(|r| r.id.clone()).collect();
-            let mut merged: Vec<SearchResult> = wal_results;
+    #[test]
+    fn test_numeric_stats_skip_non_numeric_values() {
+        let mut stats = NumericStats::default();
+        stats.add(Some(&AttributeValue::Integer(4)));
+        stats.add(Some(&AttributeValue::FloatList(vec![1.0, 7.0])));
+        stats.add(Some(&AttributeValue::String("9".into())));
+        stats.add(None);
 
-            for sr in segment_results {
-                if !wal_ids.contains(&sr.id) {
-                    merged.push(sr);
-                }
+        let field = "price".to_string();
+        let value = |aggregation: Aggregation| match stats.result(&aggregation) {
+            AggregationResult::Metric { value, count } => (value, count),
+            AggregationResult::Terms(_) => unreachable!(),
+        };
+        assert_eq!(
+            value(Aggregation::Min {
+                field: field.clone()
+            }),
+            (Some(1.0), 3)
+        );
+        assert_eq!(
+            value(Aggregation::Max {
+                field: field.clone()
+            }),
+            (Some(7.0), 3)
+        );
+        assert_eq!(
+            value(Aggregation::Avg {
+                field: field.clone()
+            }),
+            (Some(4.0), 3)
+        );
+        assert!(matches!(
+            NumericStats::default().result(&Aggregation::Avg { field }),
+            AggregationResult::Metric {
+                value: None,
+                count: 0
             }
-
-            merged.sort_by(|a, b| {
-                a.score
-                    .partial_cmp(&b.score)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            });
-            merged.truncate(top_k);
-            merged
-        }
-        ConsistencyLevel::Eventual => {
-            let mut results = segment_results;
-            results.truncate(top_k);
-            results
-        }
+        ));
     }
 }
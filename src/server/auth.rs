@@ -0,0 +1,272 @@
+//! API key authentication with per-namespace roles; see [`AuthConfig`].
+//!
+//! [`authenticate`] checks every request against the role its route needs
+//! on the namespace in its path. Routes that name namespaces in the body
+//! or list them (`POST /v1/namespaces`, `GET /v1/namespaces`, `POST
+//! /v1/query`) only require a valid key here; their handlers check the
+//! [`Principal`] left in the request extensions.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::extract::{MatchedPath, RawPathParams, Request, State};
+use axum::http::{header, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+
+use crate::config::{AuthConfig, Role, RoleGrant};
+use crate::error::ZeppelinError;
+
+use super::handlers::ApiError;
+
+/// The API key a request authenticated with.
+#[derive(Debug)]
+pub struct Principal {
+    /// Position of the key in the config; identifies it without the secret.
+    index: usize,
+    name: Option<String>,
+    grants: Vec<RoleGrant>,
+}
+
+impl Principal {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The key's role on `namespace`: that of its longest matching prefix.
+    pub fn role_for(&self, namespace: &str) -> Option<Role> {
+        self.grants
+            .iter()
+            .filter(|g| namespace.starts_with(g.prefix.as_str()))
+            .max_by_key(|g| g.prefix.len())
+            .map(|g| g.role)
+    }
+
+    /// Fail with 403 unless the key holds at least `role` on `namespace`.
+    pub fn require(&self, namespace: &str, role: Role) -> Result<(), ApiError> {
+        if self.role_for(namespace).is_some_and(|held| held >= role) {
+            return Ok(());
+        }
+        tracing::warn!(
+            key = self.name.as_deref(),
+            namespace,
+            role = role.as_str(),
+            "forbidden"
+        );
+        Err(ApiError(ZeppelinError::Forbidden {
+            namespace: namespace.to_string(),
+            role: role.as_str().to_string(),
+        }))
+    }
+}
+
+/// Handler argument for the principal [`authenticate`] attached; `None`
+/// when auth is disabled.
+pub type MaybePrincipal = Option<Extension<Arc<Principal>>>;
+
+/// Check `principal` (if auth is enabled) for `role` on `namespace`.
+pub fn require(principal: &MaybePrincipal, namespace: &str, role: Role) -> Result<(), ApiError> {
+    match principal {
+        Some(Extension(principal)) => principal.require(namespace, role),
+        None => Ok(()),
+    }
+}
+
+/// Configured API keys, by key.
+#[derive(Debug, Default)]
+pub struct ApiKeys(HashMap<String, Arc<Principal>>);
+
+impl ApiKeys {
+    pub fn from_config(config: &AuthConfig) -> Self {
+        Self(
+            config
+                .api_keys
+                .iter()
+                .enumerate()
+                .map(|(index, k)| {
+                    let principal = Principal {
+                        index,
+                        name: k.name.clone(),
+                        grants: k.grants.clone(),
+                    };
+                    (k.key.clone(), Arc::new(principal))
+                })
+                .collect(),
+        )
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.0.is_empty()
+    }
+}
+
+/// Where a route's permission check happens.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Access {
+    /// No key needed.
+    Public,
+    /// `role` on the `:ns` path parameter, or on every namespace (the empty
+    /// prefix) for routes without one.
+    Role(Role),
+    /// A valid key; the handler checks the namespaces involved.
+    Handler,
+}
+
+/// The access `method` on the route pattern `path` needs.
+pub(crate) fn route_access(method: &Method, path: &str) -> Access {
+    match path {
+        "/healthz" | "/readyz" | "/metrics" => Access::Public,
+        "/v1/namespaces" | "/v1/query" => Access::Handler,
+        "/v1/namespaces/:ns" if method == Method::GET || method == Method::HEAD => {
+            Access::Role(Role::Read)
+        }
+        "/v1/namespaces/:ns" | "/v1/namespaces/:ns/compact" => Access::Role(Role::Admin),
+        _ if path.starts_with("/v1/admin/") => Access::Role(Role::Admin),
+        "/v1/namespaces/:ns/query"
+        | "/v1/namespaces/:ns/query_batch"
+        | "/v1/namespaces/:ns/query/explain"
+        | "/v1/namespaces/:ns/fetch"
+        | "/v1/namespaces/:ns/count-by" => Access::Role(Role::Read),
+        _ if method == Method::GET || method == Method::HEAD => Access::Role(Role::Read),
+        _ => Access::Role(Role::Write),
+    }
+}
+
+/// Middleware enforcing [`AuthConfig`]: 401 without a known
+/// `Authorization: Bearer` key, 403 if the key's role on the request's
+/// namespace is too low. A no-op while no keys are configured.
+pub async fn authenticate(
+    State(keys): State<Arc<ApiKeys>>,
+    matched_path: Option<MatchedPath>,
+    params: RawPathParams,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !keys.is_enabled() {
+        return next.run(request).await;
+    }
+    let path = matched_path.as_ref().map_or("", |p| p.as_str());
+    let access = route_access(request.method(), path);
+    if access == Access::Public {
+        return next.run(request).await;
+    }
+
+    let principal = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .and_then(|key| keys.0.get(key.trim()));
+    let Some(principal) = principal.cloned() else {
+        let mut response = ApiError(ZeppelinError::Unauthorized).into_response();
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        return response;
+    };
+    if let Access::Role(role) = access {
+        let namespace = params
+            .iter()
+            .find(|(name, _)| *name == "ns")
+            .map_or("", |(_, value)| value);
+        if let Err(e) = principal.require(namespace, role) {
+            return e.into_response();
+        }
+    }
+    request.extensions_mut().insert(principal);
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal(grants: &[(&str, Role)]) -> Principal {
+        Principal {
+            index: 0,
+            name: None,
+            grants: grants
+                .iter()
+                .map(|(prefix, role)| RoleGrant {
+                    prefix: prefix.to_string(),
+                    role: *role,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_longest_prefix_wins() {
+        let p = principal(&[
+            ("", Role::Write),
+            ("prod-", Role::Read),
+            ("dev-", Role::Admin),
+        ]);
+        assert_eq!(p.role_for("staging"), Some(Role::Write));
+        assert_eq!(p.role_for("prod-events"), Some(Role::Read));
+        assert_eq!(p.role_for("dev-x"), Some(Role::Admin));
+        assert!(p.require("prod-events", Role::Read).is_ok());
+        assert!(p.require("prod-events", Role::Write).is_err());
+        assert!(p.require("dev-x", Role::Write).is_ok());
+
+        let scoped = principal(&[("team-a-", Role::Admin)]);
+        assert_eq!(scoped.role_for("team-b-x"), None);
+        assert!(scoped.require("", Role::Admin).is_err());
+    }
+
+    #[test]
+    fn test_route_access() {
+        use Access::*;
+        let cases = [
+            (Method::GET, "/healthz", Public),
+            (Method::GET, "/v1/namespaces", Handler),
+            (Method::POST, "/v1/query", Handler),
+            (Method::GET, "/v1/namespaces/:ns", Role(super::Role::Read)),
+            (
+                Method::DELETE,
+                "/v1/namespaces/:ns",
+                Role(super::Role::Admin),
+            ),
+            (
+                Method::POST,
+                "/v1/namespaces/:ns/compact",
+                Role(super::Role::Admin),
+            ),
+            (Method::GET, "/v1/admin/config", Role(super::Role::Admin)),
+            (
+                Method::POST,
+                "/v1/namespaces/:ns/query",
+                Role(super::Role::Read),
+            ),
+            (
+                Method::POST,
+                "/v1/namespaces/:ns/query_batch",
+                Role(super::Role::Read),
+            ),
+            (
+                Method::GET,
+                "/v1/namespaces/:ns/vectors",
+                Role(super::Role::Read),
+            ),
+            (
+                Method::POST,
+                "/v1/namespaces/:ns/vectors",
+                Role(super::Role::Write),
+            ),
+            (
+                Method::DELETE,
+                "/v1/namespaces/:ns/vectors",
+                Role(super::Role::Write),
+            ),
+            (
+                Method::PATCH,
+                "/v1/namespaces/:ns/vectors/:id",
+                Role(super::Role::Write),
+            ),
+        ];
+        for (method, path, want) in cases {
+            assert_eq!(route_access(&method, path), want, "{method} {path}");
+        }
+    }
+}
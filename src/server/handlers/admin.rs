@@ -0,0 +1,107 @@
+use axum::extract::{Path, State};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument};
+
+use crate::compaction::verify::{verify_namespace, VerifyReport};
+use crate::compaction::NamespaceCompaction;
+use crate::config::Config;
+use crate::server::AppState;
+
+use super::ApiError;
+
+/// `GET /v1/admin/config` — the effective config (file + env overrides)
+/// the server is running with, with credentials redacted.
+///
+/// Requires the admin role on every namespace (the empty prefix) when auth
+/// is enabled; secrets are never returned.
+pub async fn get_config(State(state): State<AppState>) -> Json<Config> {
+    Json(state.config.redacted())
+}
+
+#[derive(Debug, Serialize)]
+pub struct InvalidateCacheResponse {
+    pub invalidated: usize,
+}
+
+/// `POST /v1/admin/namespaces/:ns/cache/invalidate` — drop every disk cache
+/// entry under the namespace, e.g. after repairing segments by hand.
+/// Requires the admin role on the namespace.
+#[instrument(skip(state), fields(namespace = %ns))]
+pub async fn invalidate_namespace_cache(
+    State(state): State<AppState>,
+    Path(ns): Path<String>,
+) -> Result<Json<InvalidateCacheResponse>, ApiError> {
+    let invalidated = state.cache.invalidate_prefix(&format!("{ns}/")).await?;
+    info!(namespace = %ns, invalidated, "invalidated namespace cache");
+    Ok(Json(InvalidateCacheResponse { invalidated }))
+}
+
+/// `POST /v1/admin/namespaces/:ns/verify` — cross-check the manifest
+/// against the objects on S3 and report missing segment artifacts,
+/// unreadable clusters and orphaned fragments or segments.
+///
+/// Read-only; nothing is repaired. Requires the admin role on the namespace.
+#[instrument(skip(state), fields(namespace = %ns))]
+pub async fn verify_namespace_storage(
+    State(state): State<AppState>,
+    Path(ns): Path<String>,
+) -> Result<Json<VerifyReport>, ApiError> {
+    state.namespace_manager.get(&ns).await?;
+    let report = verify_namespace(&state.store, &ns).await?;
+    info!(
+        namespace = %ns,
+        consistent = report.is_consistent(),
+        inconsistencies = report.inconsistencies.len(),
+        "verified namespace"
+    );
+    Ok(Json(report))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CompactAllRequest {
+    /// Only consider namespaces whose name starts with this prefix.
+    #[serde(default)]
+    pub prefix: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompactAllResponse {
+    /// Namespaces considered, including those under the threshold.
+    pub namespaces_checked: usize,
+    /// One entry per namespace that was over the threshold or failed.
+    pub results: Vec<NamespaceCompaction>,
+}
+
+/// `POST /v1/admin/compact-all` — compact every namespace over the WAL
+/// fragment threshold, e.g. for an operational rebuild.
+///
+/// Namespaces are compacted sequentially; a failure is reported in that
+/// namespace's entry and the rest still run. Requires the same role as
+/// `get_config`.
+#[instrument(skip(state, body))]
+pub async fn compact_all(
+    State(state): State<AppState>,
+    body: Option<Json<CompactAllRequest>>,
+) -> Result<Json<CompactAllResponse>, ApiError> {
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    // `NamespaceManager::list` matches whole path segments, so filter by
+    // name prefix here.
+    let mut namespaces = state.namespace_manager.list(None).await?;
+    if let Some(ref prefix) = req.prefix {
+        namespaces.retain(|ns| ns.name.starts_with(prefix.as_str()));
+    }
+
+    info!(namespaces = namespaces.len(), "compact-all requested");
+    let results = state.compactor.compact_all(&namespaces).await;
+    info!(
+        compacted = results.iter().filter(|r| r.error.is_none()).count(),
+        failed = results.iter().filter(|r| r.error.is_some()).count(),
+        "compact-all finished"
+    );
+
+    Ok(Json(CompactAllResponse {
+        namespaces_checked: namespaces.len(),
+        results,
+    }))
+}
@@ -1,13 +1,19 @@
+pub mod admin;
 pub mod health;
 pub mod metrics;
 pub mod namespace;
 pub mod query;
 pub mod vectors;
 
-use axum::http::StatusCode;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{FromRequest, Request};
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
-use serde_json::json;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
 
+use crate::config::JsonCase;
 use crate::error::ZeppelinError;
 
 /// Wrapper that converts `ZeppelinError` into an HTTP response.
@@ -35,3 +41,144 @@ impl IntoResponse for ApiError {
         (status_code, axum::Json(body)).into_response()
     }
 }
+
+/// JSON request body extractor that reports rejections as `ApiError`s.
+///
+/// A missing or non-JSON `Content-Type` is a 415, and a body that is not
+/// valid JSON or does not match the request type is a 400 whose message
+/// includes the line and column of the error. Other rejections, such as an
+/// oversized body, keep axum's response.
+pub struct ApiJson<T>(pub T);
+
+#[axum::async_trait]
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_owned);
+        match axum::Json::<T>::from_request(req, state).await {
+            Ok(axum::Json(value)) => Ok(ApiJson(value)),
+            Err(rejection) => Err(match rejection {
+                JsonRejection::MissingJsonContentType(_) => {
+                    ApiError(ZeppelinError::UnsupportedMediaType(format!(
+                        "expected Content-Type: application/json, got {}",
+                        content_type.as_deref().unwrap_or("none")
+                    )))
+                    .into_response()
+                }
+                JsonRejection::JsonSyntaxError(e) => {
+                    ApiError(ZeppelinError::Validation(e.body_text())).into_response()
+                }
+                JsonRejection::JsonDataError(e) => {
+                    ApiError(ZeppelinError::Validation(e.body_text())).into_response()
+                }
+                other => other.into_response(),
+            }),
+        }
+    }
+}
+
+/// JSON response whose field names follow `server.json_case`.
+///
+/// Camel mode renames the keys of the DTO and of every DTO nested in it,
+/// but never user data: vector attributes and count-by groups are left as
+/// stored, and maps keyed by user-chosen names (aggregations, named vector
+/// fields, FTS fields) keep their names while the DTOs under them are
+/// renamed.
+pub struct CasedJson<T>(pub T, pub JsonCase);
+
+impl<T: Serialize> IntoResponse for CasedJson<T> {
+    fn into_response(self) -> Response {
+        let CasedJson(body, case) = self;
+        if case == JsonCase::Snake {
+            return axum::Json(body).into_response();
+        }
+        match serde_json::to_value(&body) {
+            Ok(mut value) => {
+                camel_case_keys(&mut value);
+                axum::Json(value).into_response()
+            }
+            Err(e) => ApiError(e.into()).into_response(),
+        }
+    }
+}
+
+/// Fields holding user data, left untouched in camel mode.
+const USER_DATA_FIELDS: &[&str] = &["attributes", "counts"];
+
+/// Fields holding maps keyed by user-chosen names: the names are kept, the
+/// values are renamed like any other DTO.
+const USER_KEYED_FIELDS: &[&str] = &["aggregations", "named_vectors", "full_text_search"];
+
+fn camel_case_keys(value: &mut Value) {
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(camel_case_keys),
+        Value::Object(map) => {
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(k, mut v)| {
+                    if USER_KEYED_FIELDS.contains(&k.as_str()) {
+                        if let Value::Object(entries) = &mut v {
+                            entries.values_mut().for_each(camel_case_keys);
+                        }
+                    } else if !USER_DATA_FIELDS.contains(&k.as_str()) {
+                        camel_case_keys(&mut v);
+                    }
+                    (snake_to_camel(&k), v)
+                })
+                .collect();
+        }
+        _ => {}
+    }
+}
+
+fn snake_to_camel(key: &str) -> String {
+    let mut out = String::with_capacity(key.len());
+    let mut upper = false;
+    for c in key.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_camel_case_keys_recurses_but_keeps_user_data() {
+        let mut value = json!({
+            "scanned_fragments": 1,
+            "results": [{"id": "a", "attributes": {"user_field": {"inner_key": 1}}}],
+            "aggregations": {"by_kind": {"counts": {"some_value": 2}, "truncated": false}},
+            "named_vectors": {"title_vec": {"distance_metric": "cosine"}},
+            "compaction": {"max_wal_fragments_before_compact": 10},
+        });
+        camel_case_keys(&mut value);
+        assert_eq!(
+            value,
+            json!({
+                "scannedFragments": 1,
+                "results": [{"id": "a", "attributes": {"user_field": {"inner_key": 1}}}],
+                "aggregations": {"by_kind": {"counts": {"some_value": 2}, "truncated": false}},
+                "namedVectors": {"title_vec": {"distanceMetric": "cosine"}},
+                "compaction": {"maxWalFragmentsBeforeCompact": 10},
+            })
+        );
+    }
+}
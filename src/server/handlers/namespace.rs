@@ -1,41 +1,84 @@
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
-use axum::Json;
+use std::collections::BTreeMap;
+
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::{Extension, Json};
 use serde::{Deserialize, Serialize};
 use tracing::{info, instrument};
 
+use crate::config::{CompactionOverrides, Role};
 use crate::error::ZeppelinError;
 use crate::fts::types::FtsFieldConfig;
+use crate::index::quantization::QuantizationType;
 use crate::namespace::manager::NamespaceMetadata;
+use crate::server::auth::{self, MaybePrincipal};
 use crate::server::AppState;
-use crate::types::DistanceMetric;
+use crate::types::{DistanceMetric, IndexType, NamedVectorConfig};
+use crate::wal::Manifest;
 
-use super::ApiError;
+use super::{ApiError, ApiJson, CasedJson};
 
 #[derive(Debug, Deserialize)]
 pub struct CreateNamespaceRequest {
     pub name: String,
+    /// 0 declares a text-only namespace, which requires `full_text_search`.
     pub dimensions: usize,
-    #[serde(default = "default_distance_metric")]
+    #[serde(default = "default_distance_metric", alias = "distanceMetric")]
     pub distance_metric: DistanceMetric,
-    #[serde(default)]
+    /// Segment index type built at compaction. Defaults to `ivf_flat`,
+    /// which follows the server-wide indexing config.
+    #[serde(default, alias = "indexType")]
+    pub index_type: IndexType,
+    #[serde(default, alias = "fullTextSearch")]
     pub full_text_search: std::collections::HashMap<String, FtsFieldConfig>,
+    /// Pin the namespace's segment centroids in the disk cache.
+    #[serde(default, alias = "cachePinned")]
+    pub cache_pinned: bool,
+    /// Compaction triggers overriding the global `[compaction]` config.
+    #[serde(default)]
+    pub compaction: CompactionOverrides,
+    /// Named vector fields, e.g. `{"image_embedding": {"dimensions": 512}}`.
+    #[serde(default, alias = "namedVectors")]
+    pub named_vectors: BTreeMap<String, NamedVectorConfig>,
+    /// Dimensions of per-document token vectors for late-interaction
+    /// queries, e.g. 128 for ColBERT. 0 (the default) disables them.
+    #[serde(default, alias = "tokenDimensions")]
+    pub token_dimensions: usize,
 }
 
 fn default_distance_metric() -> DistanceMetric {
     DistanceMetric::Cosine
 }
 
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
 #[derive(Debug, Serialize)]
 pub struct NamespaceResponse {
     pub name: String,
     pub dimensions: usize,
     pub distance_metric: DistanceMetric,
+    pub index_type: IndexType,
     pub vector_count: u64,
     pub created_at: String,
     pub updated_at: String,
     #[serde(skip_serializing_if = "std::collections::HashMap::is_empty")]
     pub full_text_search: std::collections::HashMap<String, FtsFieldConfig>,
+    pub cache_pinned: bool,
+    #[serde(skip_serializing_if = "CompactionOverrides::is_empty")]
+    pub compaction: CompactionOverrides,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub named_vectors: BTreeMap<String, NamedVectorConfig>,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub token_dimensions: usize,
+    /// Objects stored under the namespace prefix. Only set when requested
+    /// with `include_storage=true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub s3_object_count: Option<usize>,
+    /// Total size of those objects in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub s3_total_bytes: Option<u64>,
 }
 
 impl From<NamespaceMetadata> for NamespaceResponse {
@@ -44,69 +87,314 @@ impl From<NamespaceMetadata> for NamespaceResponse {
             name: meta.name,
             dimensions: meta.dimensions,
             distance_metric: meta.distance_metric,
+            index_type: meta.index_type,
             vector_count: meta.vector_count,
             created_at: meta.created_at.to_rfc3339(),
             updated_at: meta.updated_at.to_rfc3339(),
             full_text_search: meta.full_text_search,
+            cache_pinned: meta.cache_pinned,
+            compaction: meta.compaction,
+            named_vectors: meta.named_vectors,
+            token_dimensions: meta.token_dimensions,
+            s3_object_count: None,
+            s3_total_bytes: None,
         }
     }
 }
 
-#[instrument(skip(state), fields(namespace = %req.name, dimensions = req.dimensions))]
+#[instrument(skip(state, principal), fields(namespace = %req.name, dimensions = req.dimensions))]
 pub async fn create_namespace(
     State(state): State<AppState>,
-    Json(req): Json<CreateNamespaceRequest>,
-) -> Result<(StatusCode, Json<NamespaceResponse>), ApiError> {
-    if req.dimensions == 0 || req.dimensions > state.config.server.max_dimensions {
+    principal: MaybePrincipal,
+    ApiJson(req): ApiJson<CreateNamespaceRequest>,
+) -> Result<(StatusCode, CasedJson<NamespaceResponse>), ApiError> {
+    auth::require(&principal, &req.name, Role::Admin)?;
+    // `dimensions: 0` declares a text-only namespace, searchable by BM25 only.
+    if req.dimensions == 0 {
+        if req.full_text_search.is_empty() {
+            return Err(ApiError(ZeppelinError::Validation(
+                "dimensions 0 (text-only) requires full_text_search".into(),
+            )));
+        }
+    } else if req.dimensions > state.config.server.max_dimensions {
         return Err(ApiError(ZeppelinError::Validation(format!(
             "dimensions {} must be between 1 and {}",
             req.dimensions, state.config.server.max_dimensions
         ))));
     }
 
+    if req.index_type == IndexType::IvfPq && req.dimensions % state.config.indexing.pq_m != 0 {
+        return Err(ApiError(ZeppelinError::Validation(format!(
+            "index_type ivf_pq requires dimensions divisible by pq_m ({}), got {}",
+            state.config.indexing.pq_m, req.dimensions
+        ))));
+    }
+
+    for (field, config) in &req.named_vectors {
+        if field.is_empty() {
+            return Err(ApiError(ZeppelinError::Validation(
+                "named vector names must not be empty".into(),
+            )));
+        }
+        if config.dimensions == 0 || config.dimensions > state.config.server.max_dimensions {
+            return Err(ApiError(ZeppelinError::Validation(format!(
+                "named vector '{field}' dimensions {} must be between 1 and {}",
+                config.dimensions, state.config.server.max_dimensions
+            ))));
+        }
+    }
+
+    if req.token_dimensions > state.config.server.max_dimensions {
+        return Err(ApiError(ZeppelinError::Validation(format!(
+            "token_dimensions {} must be at most {}",
+            req.token_dimensions, state.config.server.max_dimensions
+        ))));
+    }
+
     info!(namespace = %req.name, dimensions = req.dimensions, "creating namespace");
+    let mut meta = NamespaceMetadata::new(&req.name, req.dimensions, req.distance_metric);
+    meta.index_type = req.index_type;
+    meta.full_text_search = req.full_text_search;
+    meta.cache_pinned = req.cache_pinned;
+    meta.compaction = req.compaction;
+    meta.named_vectors = req.named_vectors;
+    meta.token_dimensions = req.token_dimensions;
     let meta = state
         .namespace_manager
-        .create_with_fts(
-            &req.name,
-            req.dimensions,
-            req.distance_metric,
-            req.full_text_search,
-        )
+        .create_with_options(meta)
         .await
         .map_err(ApiError::from)?;
 
     info!(namespace = %req.name, "namespace created");
-    Ok((StatusCode::CREATED, Json(NamespaceResponse::from(meta))))
+    Ok((
+        StatusCode::CREATED,
+        CasedJson(NamespaceResponse::from(meta), state.config.server.json_case),
+    ))
 }
 
-#[instrument(skip(state))]
+#[derive(Debug, Default, Deserialize)]
+pub struct ListNamespacesParams {
+    /// Return at most this many namespaces. Without it, all are returned.
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Resume after this namespace name: the `x-next-cursor` header of the
+    /// previous page.
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+/// Response header carrying the cursor for the next page of namespaces.
+/// Absent on the last page.
+pub const NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
+/// `GET /v1/namespaces` — namespaces ordered by name, paginated with
+/// `?limit=&cursor=`. With auth enabled, namespaces the key has no role on
+/// are left out, so a page may hold fewer than `limit`.
+#[instrument(skip(state, principal))]
 pub async fn list_namespaces(
     State(state): State<AppState>,
-) -> Result<Json<Vec<NamespaceResponse>>, ApiError> {
-    let namespaces = state
+    principal: MaybePrincipal,
+    Query(params): Query<ListNamespacesParams>,
+) -> Result<(HeaderMap, CasedJson<Vec<NamespaceResponse>>), ApiError> {
+    if params.limit == Some(0) {
+        return Err(ApiError(ZeppelinError::Validation(
+            "limit must be > 0".into(),
+        )));
+    }
+    let (namespaces, next) = state
         .namespace_manager
-        .list(None)
+        .list_page(
+            None,
+            params.cursor.as_deref(),
+            params.limit.unwrap_or(usize::MAX),
+        )
         .await
         .map_err(ApiError::from)?;
 
+    let mut headers = HeaderMap::new();
+    // Namespace names are restricted to `[a-zA-Z0-9._-]`, so always valid
+    // header values.
+    if let Some(value) = next.and_then(|next| HeaderValue::from_str(&next).ok()) {
+        headers.insert(NEXT_CURSOR_HEADER, value);
+    }
+
+    let namespaces: Vec<_> = match &principal {
+        Some(Extension(principal)) => namespaces
+            .into_iter()
+            .filter(|meta| principal.role_for(&meta.name).is_some())
+            .collect(),
+        None => namespaces,
+    };
     info!(count = namespaces.len(), "listed namespaces");
     let responses: Vec<NamespaceResponse> = namespaces.into_iter().map(Into::into).collect();
-    Ok(Json(responses))
+    Ok((headers, CasedJson(responses, state.config.server.json_case)))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct GetNamespaceParams {
+    /// Report object count and bytes under the namespace prefix. Opt-in
+    /// because it lists every object the namespace owns.
+    #[serde(default)]
+    pub include_storage: bool,
 }
 
 #[instrument(skip(state), fields(namespace = %ns))]
 pub async fn get_namespace(
     State(state): State<AppState>,
     Path(ns): Path<String>,
-) -> Result<Json<NamespaceResponse>, ApiError> {
+    Query(params): Query<GetNamespaceParams>,
+) -> Result<CasedJson<NamespaceResponse>, ApiError> {
     let meta = state
         .namespace_manager
         .get(&ns)
         .await
         .map_err(ApiError::from)?;
 
-    Ok(Json(NamespaceResponse::from(meta)))
+    let mut response = NamespaceResponse::from(meta);
+    if params.include_storage {
+        let (count, bytes) = state.store.prefix_usage(&format!("{ns}/")).await?;
+        response.s3_object_count = Some(count);
+        response.s3_total_bytes = Some(bytes);
+    }
+
+    Ok(CasedJson(response, state.config.server.json_case))
+}
+
+#[derive(Debug, Serialize)]
+pub struct NamespaceIndexResponse {
+    pub distance_metric: DistanceMetric,
+    pub index_type: IndexType,
+    /// Active segment, if the namespace has been compacted.
+    pub segment_id: Option<String>,
+    /// Clusters in the active segment; 0 before the first compaction.
+    pub cluster_count: usize,
+    /// Vectors in the active segment.
+    pub vector_count: usize,
+    /// The active segment's quantization, or what the next compaction will
+    /// build from the namespace's index type.
+    pub quantization: QuantizationType,
+    pub hierarchical: bool,
+    pub vamana: bool,
+    /// `nprobe` used when a query doesn't set one: the server default,
+    /// capped at `max_nprobe` and the segment's cluster count.
+    pub recommended_nprobe: usize,
+    pub max_nprobe: usize,
+}
+
+/// `GET /v1/namespaces/:ns/index` — effective index parameters, for
+/// clients tuning `nprobe` and the like.
+#[instrument(skip(state), fields(namespace = %ns))]
+pub async fn get_namespace_index(
+    State(state): State<AppState>,
+    Path(ns): Path<String>,
+) -> Result<CasedJson<NamespaceIndexResponse>, ApiError> {
+    let meta = state
+        .namespace_manager
+        .get(&ns)
+        .await
+        .map_err(ApiError::from)?;
+    let manifest = Manifest::read(&state.store, &ns).await?.unwrap_or_default();
+    let segment = manifest
+        .active_segment
+        .as_ref()
+        .and_then(|id| manifest.segments.iter().find(|s| &s.id == id));
+
+    let indexing = state.config.indexing.for_index_type(meta.index_type);
+    let max_nprobe = indexing.max_nprobe;
+    let mut recommended_nprobe = indexing.default_nprobe.min(max_nprobe);
+    let response = match segment {
+        Some(seg) => {
+            recommended_nprobe = recommended_nprobe.min(seg.cluster_count).max(1);
+            NamespaceIndexResponse {
+                distance_metric: meta.distance_metric,
+                index_type: meta.index_type,
+                segment_id: Some(seg.id.clone()),
+                cluster_count: seg.cluster_count,
+                vector_count: seg.vector_count,
+                quantization: seg.quantization,
+                hierarchical: seg.hierarchical,
+                vamana: seg.vamana,
+                recommended_nprobe,
+                max_nprobe,
+            }
+        }
+        None => NamespaceIndexResponse {
+            distance_metric: meta.distance_metric,
+            index_type: meta.index_type,
+            segment_id: None,
+            cluster_count: 0,
+            vector_count: 0,
+            quantization: indexing.quantization,
+            hierarchical: indexing.hierarchical,
+            vamana: indexing.vamana,
+            recommended_nprobe,
+            max_nprobe,
+        },
+    };
+
+    Ok(CasedJson(response, state.config.server.json_case))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateNamespaceRequest {
+    #[serde(default, alias = "cachePinned")]
+    pub cache_pinned: Option<bool>,
+    /// Replaces the namespace's compaction overrides; `{}` clears them.
+    #[serde(default)]
+    pub compaction: Option<CompactionOverrides>,
+}
+
+/// `PATCH /v1/namespaces/:ns` — update mutable namespace settings.
+///
+/// Clearing `cache_pinned` releases the namespace's cache pins straight
+/// away; setting it takes effect on the next query. `compaction` overrides
+/// apply from the next background compaction check.
+#[instrument(skip(state, req), fields(namespace = %ns))]
+pub async fn update_namespace(
+    State(state): State<AppState>,
+    Path(ns): Path<String>,
+    ApiJson(req): ApiJson<UpdateNamespaceRequest>,
+) -> Result<CasedJson<NamespaceResponse>, ApiError> {
+    let meta = state
+        .namespace_manager
+        .update_metadata(&ns, |meta| {
+            if let Some(pinned) = req.cache_pinned {
+                meta.cache_pinned = pinned;
+            }
+            if let Some(overrides) = req.compaction {
+                meta.compaction = overrides;
+            }
+        })
+        .await
+        .map_err(ApiError::from)?;
+    if req.cache_pinned == Some(false) {
+        let unpinned = state.cache.unpin_prefix(&format!("{ns}/")).await;
+        info!(namespace = %ns, unpinned, "unpinned namespace cache");
+    }
+
+    Ok(CasedJson(
+        NamespaceResponse::from(meta),
+        state.config.server.json_case,
+    ))
+}
+
+/// `HEAD /v1/namespaces/:ns` — 200 if the namespace exists, 404 otherwise.
+#[instrument(skip(state), fields(namespace = %ns))]
+pub async fn head_namespace(
+    State(state): State<AppState>,
+    Path(ns): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let exists = state
+        .namespace_manager
+        .exists(&ns)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(if exists {
+        StatusCode::OK
+    } else {
+        StatusCode::NOT_FOUND
+    })
 }
 
 #[instrument(skip(state), fields(namespace = %ns))]
@@ -120,7 +408,90 @@ pub async fn delete_namespace(
         .delete(&ns)
         .await
         .map_err(ApiError::from)?;
+    state.cache.unpin_prefix(&format!("{ns}/")).await;
+    state.wal_writer.forget_namespace(&ns);
 
     info!(namespace = %ns, "namespace deleted");
     Ok(StatusCode::NO_CONTENT)
 }
+
+/// Vectors sampled when verifying a compaction.
+const VERIFY_SAMPLE_SIZE: usize = 32;
+/// Neighbours compared per sampled vector when verifying a compaction.
+const VERIFY_TOP_K: usize = 10;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CompactRequest {
+    /// Estimate recall@10 of the new segment against brute force.
+    #[serde(default)]
+    pub verify: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompactResponse {
+    pub segment_id: Option<String>,
+    pub vectors_compacted: usize,
+    pub fragments_removed: usize,
+    /// Mean recall@10 of the new segment at the default `nprobe`.
+    /// Only present when `verify` was requested and a segment was built.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recall_estimate: Option<f64>,
+}
+
+/// `POST /v1/namespaces/:ns/compact` — compact uncompacted WAL fragments now.
+#[instrument(skip(state, body), fields(namespace = %ns))]
+pub async fn compact_namespace(
+    State(state): State<AppState>,
+    Path(ns): Path<String>,
+    body: Option<Json<CompactRequest>>,
+) -> Result<Json<CompactResponse>, ApiError> {
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    let meta = state
+        .namespace_manager
+        .get(&ns)
+        .await
+        .map_err(ApiError::from)?;
+
+    info!(namespace = %ns, verify = req.verify, "manual compaction requested");
+    let result = match state
+        .compactor
+        .compact_with_fts(&ns, None, &meta.full_text_search)
+        .await
+    {
+        Ok(result) => {
+            crate::metrics::COMPACTIONS_TOTAL
+                .with_label_values(&[&ns, "success"])
+                .inc();
+            result
+        }
+        Err(e) => {
+            crate::metrics::COMPACTIONS_TOTAL
+                .with_label_values(&[&ns, "failure"])
+                .inc();
+            return Err(ApiError(e));
+        }
+    };
+
+    let recall_estimate = if req.verify && result.segment_id.is_some() {
+        state
+            .compactor
+            .estimate_recall(
+                &ns,
+                meta.distance_metric,
+                state.config.indexing.default_nprobe,
+                VERIFY_SAMPLE_SIZE,
+                VERIFY_TOP_K,
+            )
+            .await
+            .map_err(ApiError::from)?
+    } else {
+        None
+    };
+
+    Ok(Json(CompactResponse {
+        segment_id: result.segment_id,
+        vectors_compacted: result.vectors_compacted,
+        fragments_removed: result.fragments_removed,
+        recall_estimate,
+    }))
+}
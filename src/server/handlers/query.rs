@@ -1,28 +1,84 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
 use axum::extract::{Path, State};
-use axum::Json;
+use axum::http::{header, HeaderMap};
+use axum::response::{IntoResponse, Response};
 use serde::{Deserialize, Serialize};
 use tracing::{info, instrument};
 
+use crate::config::Role;
 use crate::error::ZeppelinError;
 use crate::fts::rank_by::RankBy;
+use crate::index::ivf_flat::ProbeGap;
+use crate::namespace::manager::NamespaceMetadata;
 use crate::query;
+use crate::server::auth::{self, MaybePrincipal};
 use crate::server::AppState;
-use crate::types::{ConsistencyLevel, Filter, SearchResult};
+use crate::types::{
+    AttributeValue, ConsistencyLevel, DistanceMetric, Filter, NamedVectorConfig, SearchResult,
+    VectorId,
+};
 
-use super::ApiError;
+use super::{ApiError, ApiJson, CasedJson};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct QueryRequest {
-    /// Vector for ANN search. Required unless `rank_by` is provided.
+    /// Vector for ANN search. Required unless `vectors` or `rank_by` is provided.
     #[serde(default)]
     pub vector: Option<Vec<f32>>,
-    /// BM25 ranking expression. Required unless `vector` is provided.
+    /// Search with the stored vector of an existing document instead of
+    /// `vector`, e.g. `{"id": "doc_42"}`. That document is left out of the
+    /// results.
+    #[serde(default)]
+    pub query: Option<QueryById>,
+    /// Search `vector` against this named vector field instead of the
+    /// namespace's primary vector. Named fields are scanned exhaustively.
+    #[serde(default, alias = "vectorField")]
+    pub vector_field: Option<String>,
+    /// Multiple query vectors for MaxSim-style late-interaction scoring.
+    /// Each document scores `Σ weights[i] · distance(vectors[i], doc)`.
     #[serde(default)]
+    pub vectors: Option<Vec<Vec<f32>>>,
+    /// Optional per-vector weights for `vectors` (default 1.0 each).
+    #[serde(default)]
+    pub weights: Option<Vec<f32>>,
+    /// Query token vectors for late-interaction scoring against documents'
+    /// stored `token_vectors`: `Σᵢ maxⱼ qᵢ · dⱼ`, higher is better.
+    #[serde(default, alias = "tokenVectors")]
+    pub token_vectors: Option<Vec<Vec<f32>>>,
+    /// BM25 or sparse ranking expression. Required unless `vector` or
+    /// `vectors` is provided. With `vector`, both run and their results are
+    /// combined per `fusion`.
+    #[serde(default, alias = "rankBy")]
     pub rank_by: Option<RankBy>,
-    /// Whether the last token of each BM25 query should be treated as a prefix.
+    /// How a `vector` + `rank_by` query combines the two result lists.
+    /// Defaults to reciprocal rank fusion; result scores are then fused
+    /// scores, higher is better.
     #[serde(default)]
+    pub fusion: Option<query::Fusion>,
+    /// Rerank `vector` results with Maximal Marginal Relevance, e.g.
+    /// `{"lambda": 0.5}`, picking `top_k` from `top_k × oversample_factor`
+    /// candidates. Results come back in pick order.
+    #[serde(default)]
+    pub diversify: Option<query::Diversify>,
+    /// Keep at most `group_size` `vector` results per value of an
+    /// attribute, e.g. `{"field": "doc_id", "group_size": 2}`, picking from
+    /// `top_k × oversample_factor` candidates.
+    #[serde(default, alias = "groupBy")]
+    pub group_by: Option<query::GroupBy>,
+    /// Drop `vector` results scoring below this. Only for `dot_product`
+    /// namespaces, whose scores are higher-is-better.
+    #[serde(default, alias = "minScore")]
+    pub min_score: Option<f32>,
+    /// Drop `vector` results farther than this. Only for `cosine` and
+    /// `euclidean` namespaces, whose scores are distances.
+    #[serde(default, alias = "maxDistance")]
+    pub max_distance: Option<f32>,
+    /// Whether the last token of each BM25 query should be treated as a prefix.
+    #[serde(default, alias = "lastAsPrefix")]
     pub last_as_prefix: bool,
-    #[serde(default = "default_top_k")]
+    #[serde(default = "default_top_k", alias = "topK")]
     pub top_k: usize,
     #[serde(default)]
     pub filter: Option<Filter>,
@@ -30,25 +86,411 @@ pub struct QueryRequest {
     pub consistency: ConsistencyLevel,
     #[serde(default)]
     pub nprobe: Option<usize>,
+    /// Probe IVF-Flat clusters within this ratio of the nearest centroid's
+    /// distance instead of a fixed `nprobe`. Overrides the server's
+    /// `probe_gap_ratio`.
+    #[serde(default, alias = "probeGapRatio")]
+    pub probe_gap_ratio: Option<f32>,
+    /// Return result attributes (default true), or only the listed ones,
+    /// e.g. `["title", "url"]`. When false and there is no filter, segment
+    /// search skips fetching attribute objects entirely.
+    #[serde(default, alias = "includeAttributes")]
+    pub include_attributes: IncludeAttributes,
+    /// Attributes to leave out of the response, applied after
+    /// `include_attributes`.
+    #[serde(default, alias = "excludeAttributes")]
+    pub exclude_attributes: Option<Vec<String>>,
+    /// Return only `{id, score}` per result. Implies `include_attributes:
+    /// false`; attributes are loaded only to evaluate a filter.
+    #[serde(default, alias = "idsOnly")]
+    pub ids_only: bool,
+    /// Return each result's stored vector as `values`, e.g. for client-side
+    /// reranking.
+    #[serde(default, alias = "includeVectors")]
+    pub include_vectors: bool,
+    /// Search only uncompacted WAL fragments, skipping the segment.
+    /// Not supported with `rank_by`.
+    #[serde(default, alias = "walOnly")]
+    pub wal_only: bool,
+    /// Round each result `score` to this many decimal places in the
+    /// response. Results are ranked at full precision first.
+    #[serde(default, alias = "scoreDecimals")]
+    pub score_decimals: Option<u8>,
+    /// Override `indexing.rerank_factor` for quantized segments.
+    #[serde(default, alias = "rerankFactor")]
+    pub rerank_factor: Option<usize>,
+    /// Re-sort the retrieved `top_k` by an attribute, e.g.
+    /// `["published_at", "desc"]`. Ties keep their ranking by score.
+    #[serde(default, alias = "thenOrderBy")]
+    pub then_order_by: Option<(String, query::SortDirection)>,
+    /// A previous response's `next_cursor`: return the `top_k` results
+    /// ranked after that page's last one.
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Facets over every vector matching `filter`, by name, e.g.
+    /// `{"brands": {"terms": {"field": "brand"}}, "cheapest": {"min":
+    /// {"field": "price"}}}`. Always computed from the latest WAL state.
+    #[serde(default)]
+    pub aggregations: Option<BTreeMap<String, query::Aggregation>>,
+    /// Annotate each result with its 0-based `rank` in the returned list,
+    /// counting the results of earlier `cursor` pages.
+    #[serde(default, alias = "includeRank")]
+    pub include_rank: bool,
+    /// Read segment artifacts from storage, bypassing the disk cache.
+    /// Also set by a `Cache-Control: no-cache` request header.
+    #[serde(default, alias = "noCache")]
+    pub no_cache: bool,
+}
+
+/// A stored document whose vector a query searches with.
+#[derive(Debug, Clone, Deserialize)]
+pub struct QueryById {
+    pub id: VectorId,
+}
+
+/// The response to one query: full results, or `{id, score}` pairs for
+/// `ids_only` queries.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum QueryOutput {
+    Full(QueryResponse),
+    IdsOnly(IdsOnlyResponse),
+}
+
+/// A result of an `ids_only` query.
+#[derive(Debug, Serialize)]
+pub struct IdScore {
+    pub id: VectorId,
+    pub score: f32,
+}
+
+/// [`QueryResponse`] for `ids_only` queries.
+#[derive(Debug, Serialize)]
+pub struct IdsOnlyResponse {
+    pub results: Vec<IdScore>,
+    pub scanned_fragments: usize,
+    pub scanned_segments: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub aggregations: BTreeMap<String, query::AggregationResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+impl From<QueryResponse> for IdsOnlyResponse {
+    fn from(resp: QueryResponse) -> Self {
+        Self {
+            results: resp
+                .results
+                .into_iter()
+                .map(|r| IdScore {
+                    id: r.id,
+                    score: r.score,
+                })
+                .collect(),
+            scanned_fragments: resp.scanned_fragments,
+            scanned_segments: resp.scanned_segments,
+            warnings: resp.warnings,
+            aggregations: resp.aggregations,
+            next_cursor: resp.next_cursor,
+        }
+    }
 }
 
 fn default_top_k() -> usize {
     10
 }
 
+/// Which attributes a query returns: all or none, or a list of names.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum IncludeAttributes {
+    All(bool),
+    Only(Vec<String>),
+}
+
+impl Default for IncludeAttributes {
+    fn default() -> Self {
+        IncludeAttributes::All(true)
+    }
+}
+
+impl IncludeAttributes {
+    /// Whether any attributes are returned.
+    fn any(&self) -> bool {
+        match self {
+            IncludeAttributes::All(all) => *all,
+            IncludeAttributes::Only(names) => !names.is_empty(),
+        }
+    }
+}
+
+/// Trim `attributes` to the response's projection. Results left without
+/// attributes omit the field.
+fn project_attributes(
+    attributes: &mut Option<HashMap<String, AttributeValue>>,
+    include: &IncludeAttributes,
+    exclude: Option<&[String]>,
+) {
+    if !include.any() {
+        *attributes = None;
+        return;
+    }
+    let Some(attrs) = attributes else {
+        return;
+    };
+    if let IncludeAttributes::Only(names) = include {
+        attrs.retain(|name, _| names.contains(name));
+    }
+    for name in exclude.into_iter().flatten() {
+        attrs.remove(name);
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct QueryResponse {
     pub results: Vec<SearchResult>,
     pub scanned_fragments: usize,
     pub scanned_segments: usize,
+    /// Non-fatal conditions, e.g. `"partial_results"` when the soft
+    /// deadline cut segment search short. Omitted when empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Results of the request's `aggregations`, by name. Omitted when none
+    /// were asked for.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub aggregations: BTreeMap<String, query::AggregationResult>,
+    /// Pass as `cursor` to fetch the next page. Set on full pages of
+    /// cursor-capable queries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Effective `nprobe`: the request's value or the default, capped at the max.
+fn resolve_nprobe(state: &AppState, nprobe: Option<usize>) -> usize {
+    nprobe
+        .unwrap_or(state.config.indexing.default_nprobe)
+        .min(state.config.indexing.max_nprobe)
+}
+
+/// Gap probing at `ratio`, capped at the server's `max_nprobe`.
+fn probe_gap(state: &AppState, ratio: Option<f32>) -> Option<ProbeGap> {
+    ratio.map(|ratio| ProbeGap {
+        ratio,
+        max_nprobe: state.config.indexing.max_nprobe,
+    })
+}
+
+fn validate_probe_gap_ratio(ratio: Option<f32>) -> Result<(), ApiError> {
+    match ratio {
+        Some(r) if !(r.is_finite() && r >= 1.0) => Err(ApiError(ZeppelinError::Validation(
+            format!("probe_gap_ratio must be a finite number >= 1, got {r}"),
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Round `score` to `decimals` places, leaving it unchanged if scaling
+/// would overflow.
+fn round_score(score: f32, decimals: u8) -> f32 {
+    let scale = 10f64.powi(decimals as i32);
+    let scaled = score as f64 * scale;
+    if !scaled.is_finite() {
+        return score;
+    }
+    (scaled.round() / scale) as f32
+}
+
+fn query_options(
+    state: &AppState,
+    meta: &NamespaceMetadata,
+    include_attributes: bool,
+) -> query::QueryOptions {
+    query::QueryOptions {
+        max_candidates_per_cluster: state.config.indexing.max_candidates_per_cluster,
+        soft_deadline: state
+            .config
+            .query
+            .soft_deadline_ms
+            .map(std::time::Duration::from_millis),
+        adaptive_nprobe_cap: state
+            .config
+            .indexing
+            .adaptive_nprobe
+            .then_some(state.config.indexing.max_nprobe),
+        probe_gap: probe_gap(state, state.config.indexing.probe_gap_ratio),
+        skip_attributes: !include_attributes,
+        rerank_factor: Some(state.config.indexing.rerank_factor),
+        cache_pinned: meta.cache_pinned,
+        pin_clusters: state.config.cache.pin_clusters,
+        wal_only: false,
+        dedupe_by_write_time: state.config.query.dedupe_by_write_time,
+        min_manifest_sequence: state.wal_writer.committed_sequence(&meta.name),
+        manifest_read_retries: state.config.storage.read_after_write_retries,
+        diversify: None,
+        group_by: None,
+        max_distance: None,
+        include_vectors: false,
+        after: None,
+        shared: None,
+    }
+}
+
+/// Whether a query's results are ordered by score then ID, so that a
+/// cursor can resume after any of them: a plain `vector` query, without
+/// reranking, grouping or re-sorting.
+fn pages_by_cursor(req: &QueryRequest) -> bool {
+    req.vector.is_some()
+        && req.rank_by.is_none()
+        && req.vector_field.is_none()
+        && req.diversify.is_none()
+        && req.group_by.is_none()
+        && req.then_order_by.is_none()
+}
+
+/// The `min_score` / `max_distance` cutoff of a `vector` query as an index
+/// distance (lower is closer), checked against the namespace metric.
+fn score_threshold(req: &QueryRequest, metric: DistanceMetric) -> Result<Option<f32>, ApiError> {
+    let (name, value) = match (req.min_score, req.max_distance) {
+        (None, None) => return Ok(None),
+        (Some(_), Some(_)) => {
+            return Err(ApiError(ZeppelinError::Validation(
+                "only one of 'min_score' or 'max_distance' may be provided".into(),
+            )));
+        }
+        (Some(v), None) => ("min_score", v),
+        (None, Some(v)) => ("max_distance", v),
+    };
+    if req.vector.is_none() || req.vector_field.is_some() {
+        return Err(ApiError(ZeppelinError::Validation(format!(
+            "'{name}' requires a 'vector' query without 'vector_field'"
+        ))));
+    }
+    if !value.is_finite() {
+        return Err(ApiError(ZeppelinError::Validation(format!(
+            "{name} must be a finite number, got {value}"
+        ))));
+    }
+    let expected = if metric.higher_is_better() {
+        "min_score"
+    } else {
+        "max_distance"
+    };
+    if name != expected {
+        return Err(ApiError(ZeppelinError::Validation(format!(
+            "'{name}' is not supported for {metric} namespaces; use '{expected}'"
+        ))));
+    }
+    Ok(Some(metric.score_from_distance(value)))
+}
+
+/// Parse a query body. It is parsed here rather than by the extractor so
+/// that an unknown filter op gets a 400 naming the supported ops.
+fn parse_query_request(body: serde_json::Value) -> Result<QueryRequest, ApiError> {
+    if let Some(filter) = body.get("filter") {
+        Filter::validate_ops(filter)?;
+    }
+    validate_vector_fields(&body)?;
+    serde_json::from_value(body).map_err(|e| {
+        ApiError(ZeppelinError::Validation(format!(
+            "invalid query request: {e}"
+        )))
+    })
+}
+
+/// Vector queries need vectors: a text-only namespace only serves `rank_by`.
+fn require_vectors(meta: &NamespaceMetadata) -> Result<(), ApiError> {
+    if meta.is_text_only() {
+        return Err(ApiError(ZeppelinError::Validation(format!(
+            "namespace '{}' is text-only; query it with 'rank_by'",
+            meta.name
+        ))));
+    }
+    Ok(())
 }
 
-#[instrument(skip(state, req), fields(namespace = %ns, top_k = req.top_k))]
+/// The named vector field a query's `vector_field` refers to.
+fn named_vector_config(
+    meta: &NamespaceMetadata,
+    field: &str,
+) -> Result<NamedVectorConfig, ApiError> {
+    meta.named_vectors.get(field).copied().ok_or_else(|| {
+        ApiError(ZeppelinError::Validation(format!(
+            "named vector '{field}' is not declared on namespace '{}'",
+            meta.name
+        )))
+    })
+}
+
+/// Reject a non-numeric `vector` (or `vectors` or `token_vectors` entry)
+/// with a clear message instead of serde's "invalid type: string, expected
+/// f32".
+fn validate_vector_fields(body: &serde_json::Value) -> Result<(), ApiError> {
+    let is_numbers = |v: &serde_json::Value| {
+        v.as_array()
+            .is_some_and(|xs| xs.iter().all(serde_json::Value::is_number))
+    };
+    if let Some(vector) = body.get("vector").filter(|v| !v.is_null()) {
+        if !is_numbers(vector) {
+            return Err(ApiError(ZeppelinError::Validation(
+                "vector must be an array of numbers".into(),
+            )));
+        }
+    }
+    for field in ["vectors", "token_vectors"] {
+        if let Some(vectors) = body.get(field).filter(|v| !v.is_null()) {
+            if !vectors
+                .as_array()
+                .is_some_and(|vs| vs.iter().all(is_numbers))
+            {
+                return Err(ApiError(ZeppelinError::Validation(format!(
+                    "{field} must be an array of arrays of numbers"
+                ))));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The disk cache to read segments through, or `None` when the request
+/// opted out with `no_cache` or `Cache-Control: no-cache`.
+fn request_cache<'a>(
+    state: &'a AppState,
+    headers: &HeaderMap,
+    no_cache: bool,
+) -> Option<&'a std::sync::Arc<crate::cache::DiskCache>> {
+    let no_cache_header = headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|directive| directive.trim().eq_ignore_ascii_case("no-cache"));
+    (!no_cache && !no_cache_header).then_some(&state.cache)
+}
+
+fn validate_top_k(state: &AppState, top_k: usize) -> Result<(), ApiError> {
+    if top_k == 0 {
+        return Err(ApiError(ZeppelinError::Validation(
+            "top_k must be > 0".into(),
+        )));
+    }
+    if top_k > state.config.server.max_top_k {
+        return Err(ApiError(ZeppelinError::Validation(format!(
+            "top_k {} exceeds maximum of {}",
+            top_k, state.config.server.max_top_k
+        ))));
+    }
+    Ok(())
+}
+
+#[instrument(skip(state, body), fields(namespace = %ns, top_k = tracing::field::Empty))]
 pub async fn query_namespace(
     State(state): State<AppState>,
     Path(ns): Path<String>,
-    Json(req): Json<QueryRequest>,
-) -> Result<Json<QueryResponse>, ApiError> {
+    headers: HeaderMap,
+    ApiJson(body): ApiJson<serde_json::Value>,
+) -> Result<Response, ApiError> {
     let start = std::time::Instant::now();
     crate::metrics::ACTIVE_QUERIES.inc();
     let _guard = crate::metrics::GaugeGuard(&crate::metrics::ACTIVE_QUERIES);
@@ -56,17 +498,384 @@ pub async fn query_namespace(
         .with_label_values(&[&ns])
         .inc();
 
-    // Exactly one of vector or rank_by must be provided
-    if req.vector.is_none() && req.rank_by.is_none() {
+    // Resolve the namespace first so a missing namespace is reported as 404
+    // ahead of any request validation errors.
+    let meta = state
+        .namespace_manager
+        .get(&ns)
+        .await
+        .map_err(ApiError::from)?;
+
+    let req = parse_query_request(body)?;
+    tracing::Span::current().record("top_k", req.top_k);
+
+    let output = run_query(&state, &ns, &meta, &headers, &req, None, start).await?;
+    Ok(CasedJson(output, state.config.server.json_case).into_response())
+}
+
+/// Validate and run one parsed query against `meta`'s namespace. Batch
+/// queries pass the loads they share in `shared`.
+async fn run_query(
+    state: &AppState,
+    ns: &str,
+    meta: &NamespaceMetadata,
+    headers: &HeaderMap,
+    req: &QueryRequest,
+    shared: Option<Arc<query::SharedLoads>>,
+    start: std::time::Instant,
+) -> Result<QueryOutput, ApiError> {
+    let top_k = req.top_k;
+    let by_id;
+    let req = match &req.query {
+        Some(source) => {
+            by_id = with_document_vector(state, ns, req, &source.id).await?;
+            &by_id
+        }
+        None => req,
+    };
+
+    // One of vector, vectors, token_vectors or rank_by must be provided;
+    // vector with rank_by runs both and fuses the results.
+    let hybrid = req.vector.is_some() && req.rank_by.is_some();
+    let modes = [
+        req.vector.is_some(),
+        req.vectors.is_some(),
+        req.token_vectors.is_some(),
+        req.rank_by.is_some(),
+    ];
+    match modes.iter().filter(|&&m| m).count() {
+        0 => {
+            return Err(ApiError(ZeppelinError::Validation(
+                "exactly one of 'vector', 'vectors', 'token_vectors' or 'rank_by' must be provided"
+                    .into(),
+            )));
+        }
+        1 => {}
+        2 if hybrid => {}
+        _ => {
+            return Err(ApiError(ZeppelinError::Validation(
+                "only one of 'vector', 'vectors', 'token_vectors' or 'rank_by' may be provided, \
+                 or 'vector' with 'rank_by'"
+                    .into(),
+            )));
+        }
+    }
+    if let Some(fusion) = req.fusion {
+        if !hybrid {
+            return Err(ApiError(ZeppelinError::Validation(
+                "'fusion' requires both 'vector' and 'rank_by'".into(),
+            )));
+        }
+        fusion.validate()?;
+    }
+    if let Some(diversify) = req.diversify {
+        if req.vector.is_none() || req.rank_by.is_some() || req.vector_field.is_some() {
+            return Err(ApiError(ZeppelinError::Validation(
+                "'diversify' requires a 'vector' query without 'rank_by' or 'vector_field'".into(),
+            )));
+        }
+        diversify.validate()?;
+    }
+    if let Some(group_by) = &req.group_by {
+        if req.vector.is_none() || req.rank_by.is_some() || req.vector_field.is_some() {
+            return Err(ApiError(ZeppelinError::Validation(
+                "'group_by' requires a 'vector' query without 'rank_by' or 'vector_field'".into(),
+            )));
+        }
+        group_by.validate()?;
+    }
+    if req.wal_only && (req.rank_by.is_some() || req.token_vectors.is_some()) {
+        return Err(ApiError(ZeppelinError::Validation(
+            "'wal_only' is not supported with 'rank_by' or 'token_vectors'".into(),
+        )));
+    }
+    let metric = match &req.vector_field {
+        Some(field) => {
+            if req.vector.is_none() {
+                return Err(ApiError(ZeppelinError::Validation(
+                    "'vector_field' requires 'vector'".into(),
+                )));
+            }
+            if req.wal_only {
+                return Err(ApiError(ZeppelinError::Validation(
+                    "'wal_only' is not supported with 'vector_field'".into(),
+                )));
+            }
+            named_vector_config(meta, field)?.distance_metric
+        }
+        None => meta.distance_metric,
+    };
+    if (req.vector.is_some() && req.vector_field.is_none()) || req.vectors.is_some() {
+        require_vectors(meta)?;
+    }
+    if req.weights.is_some() && req.vectors.is_none() {
         return Err(ApiError(ZeppelinError::Validation(
-            "exactly one of 'vector' or 'rank_by' must be provided".into(),
+            "'weights' requires 'vectors'".into(),
         )));
     }
-    if req.vector.is_some() && req.rank_by.is_some() {
+
+    // The caller's top_k: a `query` by document searches one past it.
+    validate_top_k(state, top_k)?;
+    if req.rerank_factor == Some(0) {
+        return Err(ApiError(ZeppelinError::Validation(
+            "rerank_factor must be > 0".into(),
+        )));
+    }
+
+    validate_probe_gap_ratio(req.probe_gap_ratio)?;
+    for aggregation in req.aggregations.iter().flat_map(BTreeMap::values) {
+        aggregation.validate()?;
+    }
+    let max_distance = score_threshold(req, metric)?;
+    if req.ids_only && (req.then_order_by.is_some() || req.include_rank || req.include_vectors) {
         return Err(ApiError(ZeppelinError::Validation(
-            "cannot provide both 'vector' and 'rank_by'".into(),
+            "ids_only cannot be combined with then_order_by, include_rank or include_vectors"
+                .into(),
         )));
     }
+    if req.include_vectors
+        && (req.vector.is_none() || req.rank_by.is_some() || req.vector_field.is_some())
+    {
+        return Err(ApiError(ZeppelinError::Validation(
+            "'include_vectors' requires a 'vector' query without 'rank_by' or 'vector_field'"
+                .into(),
+        )));
+    }
+    let cursor = match &req.cursor {
+        Some(cursor) => {
+            if !pages_by_cursor(req) {
+                return Err(ApiError(ZeppelinError::Validation(
+                    "'cursor' requires a 'vector' query without 'rank_by', 'vector_field', \
+                     'diversify', 'group_by' or 'then_order_by'"
+                        .into(),
+                )));
+            }
+            Some(query::QueryCursor::decode(cursor)?)
+        }
+        None => None,
+    };
+    let after = cursor.as_ref().map(|c| c.after(metric));
+    let offset = cursor.map_or(0, |c| c.offset);
+    let include_attributes = if req.ids_only {
+        IncludeAttributes::All(false)
+    } else {
+        req.include_attributes.clone()
+    };
+
+    let nprobe = resolve_nprobe(state, req.nprobe);
+    let cache = request_cache(state, headers, req.no_cache);
+    // Re-sorting and grouping need attributes even when they aren't
+    // returned.
+    let defaults = query_options(
+        state,
+        meta,
+        include_attributes.any() || req.then_order_by.is_some() || req.group_by.is_some(),
+    );
+    let options = query::QueryOptions {
+        wal_only: req.wal_only,
+        rerank_factor: req.rerank_factor.or(defaults.rerank_factor),
+        probe_gap: probe_gap(state, req.probe_gap_ratio).or(defaults.probe_gap),
+        diversify: req.diversify,
+        group_by: req.group_by.clone(),
+        max_distance,
+        include_vectors: req.include_vectors,
+        after,
+        shared,
+        ..defaults
+    };
+
+    let mut result = match (&req.vector, &req.rank_by) {
+        (Some(vector), Some(rank_by)) => {
+            let fusion = req.fusion.unwrap_or_default();
+            let (dense, text) = tokio::try_join!(
+                vector_query(state, ns, meta, req, vector, nprobe, cache, &options),
+                rank_by_query(state, ns, meta, req, rank_by),
+            )?;
+            QueryResponse {
+                scanned_fragments: dense.scanned_fragments.max(text.scanned_fragments),
+                scanned_segments: dense.scanned_segments.max(text.scanned_segments),
+                warnings: [dense.warnings, text.warnings].concat(),
+                results: query::fuse_results(
+                    dense.results,
+                    text.results,
+                    metric,
+                    fusion,
+                    req.top_k,
+                ),
+                aggregations: BTreeMap::new(),
+                next_cursor: None,
+            }
+        }
+        (Some(vector), None) => {
+            vector_query(state, ns, meta, req, vector, nprobe, cache, &options).await?
+        }
+        (None, Some(rank_by)) => rank_by_query(state, ns, meta, req, rank_by).await?,
+        (None, None) if req.token_vectors.is_some() => {
+            let queries = req.token_vectors.as_ref().expect("checked above");
+            if meta.token_dimensions == 0 {
+                return Err(ApiError(ZeppelinError::Validation(format!(
+                    "namespace '{ns}' has no token vectors; set token_dimensions at creation"
+                ))));
+            }
+            if queries.is_empty() {
+                return Err(ApiError(ZeppelinError::Validation(
+                    "'token_vectors' must not be empty".into(),
+                )));
+            }
+            if let Some(q) = queries.iter().find(|q| q.len() != meta.token_dimensions) {
+                return Err(ApiError(ZeppelinError::DimensionMismatch {
+                    expected: meta.token_dimensions,
+                    actual: q.len(),
+                }));
+            }
+            query::execute_maxsim_query(
+                &state.store,
+                &state.wal_reader,
+                ns,
+                queries,
+                req.top_k,
+                req.filter.as_ref(),
+                req.consistency,
+            )
+            .await
+            .map_err(ApiError::from)?
+        }
+        (None, None) => {
+            // Multi-vector (MaxSim) query path
+            let vectors = req.vectors.as_ref().expect("one query mode is set");
+            if vectors.is_empty() {
+                return Err(ApiError(ZeppelinError::Validation(
+                    "'vectors' must not be empty".into(),
+                )));
+            }
+            if let Some(v) = vectors.iter().find(|v| v.len() != meta.dimensions) {
+                return Err(ApiError(ZeppelinError::DimensionMismatch {
+                    expected: meta.dimensions,
+                    actual: v.len(),
+                }));
+            }
+            if let Some(ref weights) = req.weights {
+                if weights.len() != vectors.len() {
+                    return Err(ApiError(ZeppelinError::Validation(format!(
+                        "'weights' has {} entries but 'vectors' has {}",
+                        weights.len(),
+                        vectors.len()
+                    ))));
+                }
+            }
+
+            query::execute_multi_vector_query(
+                &state.store,
+                &state.wal_reader,
+                ns,
+                vectors,
+                req.weights.as_deref(),
+                req.top_k,
+                nprobe,
+                req.filter.as_ref(),
+                req.consistency,
+                meta.distance_metric,
+                state.config.indexing.oversample_factor,
+                cache,
+                &options,
+            )
+            .await
+            .map_err(ApiError::from)?
+        }
+    };
+
+    if let Some(aggregations) = &req.aggregations {
+        result.aggregations = query::aggregate(
+            &state.store,
+            &state.wal_reader,
+            ns,
+            aggregations,
+            req.filter.as_ref(),
+        )
+        .await?;
+    }
+    if let Some(source) = &req.query {
+        result.results.retain(|r| r.id != source.id);
+        result.results.truncate(top_k);
+    }
+    // A full page may have more after it, starting past its last result.
+    if pages_by_cursor(req) && result.results.len() == top_k {
+        result.next_cursor = result.results.last().map(|last| {
+            query::QueryCursor {
+                score: last.score,
+                id: last.id.clone(),
+                offset: offset + top_k,
+            }
+            .encode()
+        });
+    }
+    if let Some((ref field, direction)) = req.then_order_by {
+        query::order_by_attribute(&mut result.results, field, direction);
+    }
+    if req.include_rank {
+        for (rank, r) in result.results.iter_mut().enumerate() {
+            r.rank = Some(offset + rank);
+        }
+    }
+    // Filtered searches and WAL results carry every attribute.
+    for r in &mut result.results {
+        project_attributes(
+            &mut r.attributes,
+            &include_attributes,
+            req.exclude_attributes.as_deref(),
+        );
+    }
+    if let Some(decimals) = req.score_decimals {
+        for r in &mut result.results {
+            r.score = round_score(r.score, decimals);
+        }
+    }
+
+    let elapsed = start.elapsed();
+    crate::metrics::QUERY_DURATION
+        .with_label_values(&[ns])
+        .observe(elapsed.as_secs_f64());
+
+    info!(
+        results = result.results.len(),
+        scanned_fragments = result.scanned_fragments,
+        scanned_segments = result.scanned_segments,
+        elapsed_ms = elapsed.as_millis(),
+        "query complete"
+    );
+
+    if req.ids_only {
+        return Ok(QueryOutput::IdsOnly(IdsOnlyResponse::from(result)));
+    }
+    Ok(QueryOutput::Full(result))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchQueryRequest {
+    /// Query bodies, each as accepted by `/query`.
+    pub queries: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchQueryResponse {
+    /// One response per query, in request order.
+    pub responses: Vec<QueryOutput>,
+}
+
+/// `POST /v1/namespaces/:ns/query_batch` — run several queries against one
+/// namespace concurrently. They share the manifest read and IVF-Flat
+/// centroid loads. Any failing query fails the batch; validation errors
+/// name the query's index.
+#[instrument(skip(state, body), fields(namespace = %ns, queries = tracing::field::Empty))]
+pub async fn query_batch(
+    State(state): State<AppState>,
+    Path(ns): Path<String>,
+    headers: HeaderMap,
+    ApiJson(body): ApiJson<serde_json::Value>,
+) -> Result<CasedJson<BatchQueryResponse>, ApiError> {
+    let start = std::time::Instant::now();
+    crate::metrics::ACTIVE_QUERIES.inc();
+    let _guard = crate::metrics::GaugeGuard(&crate::metrics::ACTIVE_QUERIES);
 
     let meta = state
         .namespace_manager
@@ -74,91 +883,459 @@ pub async fn query_namespace(
         .await
         .map_err(ApiError::from)?;
 
-    if req.top_k == 0 {
-        return Err(ApiError(ZeppelinError::Validation(
-            "top_k must be > 0".into(),
-        )));
-    }
-    if req.top_k > state.config.server.max_top_k {
+    let batch: BatchQueryRequest = serde_json::from_value(body).map_err(|e| {
+        ApiError(ZeppelinError::Validation(format!(
+            "invalid query batch: {e}"
+        )))
+    })?;
+    tracing::Span::current().record("queries", batch.queries.len());
+    let max = state.config.server.max_query_batch_size;
+    if batch.queries.is_empty() || batch.queries.len() > max {
         return Err(ApiError(ZeppelinError::Validation(format!(
-            "top_k {} exceeds maximum of {}",
-            req.top_k, state.config.server.max_top_k
+            "'queries' must hold 1 to {max} queries, got {}",
+            batch.queries.len()
         ))));
     }
+    let reqs = batch
+        .queries
+        .into_iter()
+        .enumerate()
+        .map(|(i, body)| parse_query_request(body).map_err(|e| batch_error(i, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    crate::metrics::QUERIES_TOTAL
+        .with_label_values(&[&ns])
+        .inc_by(reqs.len() as u64);
 
-    let result = if let Some(ref rank_by) = req.rank_by {
-        // BM25 query path
-        // Validate all referenced fields are configured
-        for (field, _) in rank_by.extract_field_queries() {
-            if !meta.full_text_search.contains_key(&field) {
-                return Err(ApiError(ZeppelinError::FtsFieldNotConfigured {
-                    namespace: ns.clone(),
-                    field,
-                }));
-            }
+    let shared = Arc::new(query::SharedLoads::default());
+    let (state, ns, meta, headers) = (&state, ns.as_str(), &meta, &headers);
+    let responses = futures::future::try_join_all(reqs.iter().enumerate().map(|(i, req)| {
+        let shared = Some(shared.clone());
+        async move {
+            run_query(state, ns, meta, headers, req, shared, start)
+                .await
+                .map_err(|e| batch_error(i, e))
         }
+    }))
+    .await?;
+
+    info!(
+        queries = responses.len(),
+        elapsed_ms = start.elapsed().as_millis(),
+        "query batch complete"
+    );
+    Ok(CasedJson(
+        BatchQueryResponse { responses },
+        state.config.server.json_case,
+    ))
+}
+
+/// Prefix a validation error with the index of the batch query it is about.
+fn batch_error(index: usize, err: ApiError) -> ApiError {
+    match err.0 {
+        ZeppelinError::Validation(msg) => ApiError(ZeppelinError::Validation(format!(
+            "queries[{index}]: {msg}"
+        ))),
+        other => ApiError(other),
+    }
+}
 
-        crate::metrics::FTS_QUERIES_TOTAL
-            .with_label_values(&[&ns])
-            .inc();
+/// `req` as a `vector` query with the stored vector of document `id`, or
+/// its `vector_field` vector. Asks for one extra result to make up for the
+/// document itself, which the caller drops.
+async fn with_document_vector(
+    state: &AppState,
+    ns: &str,
+    req: &QueryRequest,
+    id: &str,
+) -> Result<QueryRequest, ApiError> {
+    if req.vector.is_some() || req.vectors.is_some() || req.token_vectors.is_some() {
+        return Err(ApiError(ZeppelinError::Validation(
+            "'query' cannot be combined with 'vector', 'vectors' or 'token_vectors'".into(),
+        )));
+    }
+    let document = query::get_vector(&state.store, &state.wal_reader, ns, id)
+        .await?
+        .ok_or_else(|| {
+            ApiError(ZeppelinError::VectorNotFound {
+                namespace: ns.to_string(),
+                id: id.to_string(),
+            })
+        })?;
+    let vector = match &req.vector_field {
+        Some(field) => document
+            .named_vectors
+            .and_then(|mut named| named.remove(field))
+            .ok_or_else(|| {
+                ApiError(ZeppelinError::Validation(format!(
+                    "vector '{id}' has no '{field}' vector"
+                )))
+            })?,
+        None => document.values,
+    };
+    Ok(QueryRequest {
+        vector: Some(vector),
+        top_k: req.top_k + 1,
+        ..req.clone()
+    })
+}
 
-        query::execute_bm25_query(
+/// Run the `rank_by` half of a query: sparse or BM25.
+async fn rank_by_query(
+    state: &AppState,
+    ns: &str,
+    meta: &NamespaceMetadata,
+    req: &QueryRequest,
+    rank_by: &RankBy,
+) -> Result<QueryResponse, ApiError> {
+    if let Some(query) = rank_by.sparse_query() {
+        return query::execute_sparse_query(
             &state.store,
             &state.wal_reader,
-            &ns,
-            rank_by,
-            &meta.full_text_search,
+            ns,
+            query,
             req.top_k,
             req.filter.as_ref(),
             req.consistency,
-            req.last_as_prefix,
         )
         .await
-        .map_err(ApiError::from)?
-    } else {
-        // Vector query path
-        let vector = req.vector.as_ref().unwrap();
-        if vector.len() != meta.dimensions {
-            return Err(ApiError(ZeppelinError::DimensionMismatch {
-                expected: meta.dimensions,
-                actual: vector.len(),
+        .map_err(ApiError::from);
+    }
+    if rank_by.contains_sparse() {
+        return Err(ApiError(ZeppelinError::Validation(
+            "a sparse rank_by can't be combined with other expressions".into(),
+        )));
+    }
+    // Validate all referenced fields are configured
+    for (field, _) in rank_by.extract_field_queries() {
+        if !meta.full_text_search.contains_key(&field) {
+            return Err(ApiError(ZeppelinError::FtsFieldNotConfigured {
+                namespace: ns.to_string(),
+                field,
             }));
         }
+    }
 
-        let nprobe = req
-            .nprobe
-            .unwrap_or(state.config.indexing.default_nprobe)
-            .min(state.config.indexing.max_nprobe);
+    crate::metrics::FTS_QUERIES_TOTAL
+        .with_label_values(&[ns])
+        .inc();
+
+    query::execute_bm25_query(
+        &state.store,
+        &state.wal_reader,
+        ns,
+        rank_by,
+        &meta.full_text_search,
+        req.top_k,
+        req.filter.as_ref(),
+        req.consistency,
+        req.last_as_prefix,
+    )
+    .await
+    .map_err(ApiError::from)
+}
 
-        query::execute_query(
+/// Run the `vector` half of a query, against `vector_field` if set.
+#[allow(clippy::too_many_arguments)]
+async fn vector_query(
+    state: &AppState,
+    ns: &str,
+    meta: &NamespaceMetadata,
+    req: &QueryRequest,
+    vector: &[f32],
+    nprobe: usize,
+    cache: Option<&std::sync::Arc<crate::cache::DiskCache>>,
+    options: &query::QueryOptions,
+) -> Result<QueryResponse, ApiError> {
+    if let Some(field) = &req.vector_field {
+        let config = named_vector_config(meta, field)?;
+        if vector.len() != config.dimensions {
+            return Err(ApiError(ZeppelinError::DimensionMismatch {
+                expected: config.dimensions,
+                actual: vector.len(),
+            }));
+        }
+        return query::execute_named_vector_query(
             &state.store,
             &state.wal_reader,
-            &ns,
+            ns,
+            field,
             vector,
+            config.distance_metric,
             req.top_k,
-            nprobe,
             req.filter.as_ref(),
             req.consistency,
-            meta.distance_metric,
-            state.config.indexing.oversample_factor,
-            Some(&state.cache),
         )
         .await
-        .map_err(ApiError::from)?
+        .map_err(ApiError::from);
+    }
+    if vector.len() != meta.dimensions {
+        return Err(ApiError(ZeppelinError::DimensionMismatch {
+            expected: meta.dimensions,
+            actual: vector.len(),
+        }));
+    }
+
+    query::execute_query_with_options(
+        &state.store,
+        &state.wal_reader,
+        ns,
+        vector,
+        req.top_k,
+        nprobe,
+        req.filter.as_ref(),
+        req.consistency,
+        meta.distance_metric,
+        state.config.indexing.oversample_factor,
+        cache,
+        options,
+    )
+    .await
+    .map_err(ApiError::from)
+}
+
+/// `POST /v1/namespaces/:ns/query/explain` — how a vector query would run
+/// (WAL fragments, `nprobe`, probed and pruned clusters, filter pruning per
+/// clause) without scoring any vectors. Takes the same body as `/query`.
+#[instrument(skip(state, body), fields(namespace = %ns))]
+pub async fn explain_query(
+    State(state): State<AppState>,
+    Path(ns): Path<String>,
+    headers: HeaderMap,
+    ApiJson(body): ApiJson<serde_json::Value>,
+) -> Result<CasedJson<query::QueryPlan>, ApiError> {
+    let meta = state
+        .namespace_manager
+        .get(&ns)
+        .await
+        .map_err(ApiError::from)?;
+    let req = parse_query_request(body)?;
+
+    let Some(vector) = req.vector.as_ref() else {
+        return Err(ApiError(ZeppelinError::Validation(
+            "explain requires 'vector'; 'vectors' and 'rank_by' queries are not supported".into(),
+        )));
+    };
+    if req.vector_field.is_some() {
+        return Err(ApiError(ZeppelinError::Validation(
+            "explain does not support 'vector_field' queries".into(),
+        )));
+    }
+    require_vectors(&meta)?;
+    if vector.len() != meta.dimensions {
+        return Err(ApiError(ZeppelinError::DimensionMismatch {
+            expected: meta.dimensions,
+            actual: vector.len(),
+        }));
+    }
+    validate_top_k(&state, req.top_k)?;
+    validate_probe_gap_ratio(req.probe_gap_ratio)?;
+
+    let defaults = query_options(&state, &meta, req.include_attributes.any() && !req.ids_only);
+    let options = query::QueryOptions {
+        wal_only: req.wal_only,
+        probe_gap: probe_gap(&state, req.probe_gap_ratio).or(defaults.probe_gap),
+        ..defaults
     };
+    let plan = query::explain_query(
+        &state.store,
+        &ns,
+        vector,
+        req.top_k,
+        resolve_nprobe(&state, req.nprobe),
+        req.filter.as_ref(),
+        req.consistency,
+        meta.distance_metric,
+        request_cache(&state, &headers, req.no_cache),
+        &options,
+    )
+    .await
+    .map_err(ApiError::from)?;
 
-    let elapsed = start.elapsed();
-    crate::metrics::QUERY_DURATION
-        .with_label_values(&[&ns])
-        .observe(elapsed.as_secs_f64());
+    Ok(CasedJson(plan, state.config.server.json_case))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MultiNamespaceQueryRequest {
+    /// Namespaces to search. All must share dimensions and distance metric.
+    pub namespaces: Vec<String>,
+    pub vector: Vec<f32>,
+    #[serde(default = "default_top_k", alias = "topK")]
+    pub top_k: usize,
+    #[serde(default)]
+    pub filter: Option<Filter>,
+    #[serde(default)]
+    pub consistency: ConsistencyLevel,
+    #[serde(default)]
+    pub nprobe: Option<usize>,
+    #[serde(default, alias = "includeAttributes")]
+    pub include_attributes: IncludeAttributes,
+    #[serde(default, alias = "excludeAttributes")]
+    pub exclude_attributes: Option<Vec<String>>,
+    #[serde(default, alias = "noCache")]
+    pub no_cache: bool,
+}
+
+/// A search result tagged with the namespace it came from.
+#[derive(Debug, Serialize)]
+pub struct NamespacedResult {
+    pub namespace: String,
+    #[serde(flatten)]
+    pub result: SearchResult,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MultiNamespaceQueryResponse {
+    pub results: Vec<NamespacedResult>,
+    pub scanned_fragments: usize,
+    pub scanned_segments: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// `POST /v1/query` — run one vector query against several namespaces
+/// concurrently and merge the results by score.
+///
+/// Each namespace is searched for `top_k` results on its own, so the merged
+/// list is the exact top `top_k` of the union. Scores are only comparable
+/// when every namespace has the same dimensions and metric, which is
+/// validated up front.
+#[instrument(skip(state, principal, body), fields(namespaces = tracing::field::Empty))]
+pub async fn query_namespaces(
+    State(state): State<AppState>,
+    principal: MaybePrincipal,
+    headers: HeaderMap,
+    ApiJson(body): ApiJson<serde_json::Value>,
+) -> Result<CasedJson<MultiNamespaceQueryResponse>, ApiError> {
+    let start = std::time::Instant::now();
+    crate::metrics::ACTIVE_QUERIES.inc();
+    let _guard = crate::metrics::GaugeGuard(&crate::metrics::ACTIVE_QUERIES);
+
+    if let Some(filter) = body.get("filter") {
+        Filter::validate_ops(filter)?;
+    }
+    validate_vector_fields(&body)?;
+    let req: MultiNamespaceQueryRequest = serde_json::from_value(body).map_err(|e| {
+        ApiError(ZeppelinError::Validation(format!(
+            "invalid query request: {e}"
+        )))
+    })?;
+    tracing::Span::current().record("namespaces", req.namespaces.len());
+
+    if req.namespaces.is_empty() {
+        return Err(ApiError(ZeppelinError::Validation(
+            "'namespaces' must not be empty".into(),
+        )));
+    }
+    let mut seen = std::collections::HashSet::new();
+    if let Some(dup) = req.namespaces.iter().find(|ns| !seen.insert(*ns)) {
+        return Err(ApiError(ZeppelinError::Validation(format!(
+            "namespace '{dup}' listed more than once"
+        ))));
+    }
+    validate_top_k(&state, req.top_k)?;
+    for ns in &req.namespaces {
+        auth::require(&principal, ns, Role::Read)?;
+    }
+
+    let metas = futures::future::try_join_all(
+        req.namespaces
+            .iter()
+            .map(|ns| state.namespace_manager.get(ns)),
+    )
+    .await?;
+    for meta in &metas {
+        require_vectors(meta)?;
+    }
+    let first = &metas[0];
+    for meta in &metas[1..] {
+        if meta.dimensions != first.dimensions || meta.distance_metric != first.distance_metric {
+            return Err(ApiError(ZeppelinError::Validation(format!(
+                "namespace '{}' ({} dimensions, {}) does not match '{}' ({} dimensions, {})",
+                meta.name,
+                meta.dimensions,
+                meta.distance_metric,
+                first.name,
+                first.dimensions,
+                first.distance_metric
+            ))));
+        }
+    }
+    if req.vector.len() != first.dimensions {
+        return Err(ApiError(ZeppelinError::DimensionMismatch {
+            expected: first.dimensions,
+            actual: req.vector.len(),
+        }));
+    }
+
+    for ns in &req.namespaces {
+        crate::metrics::QUERIES_TOTAL.with_label_values(&[ns]).inc();
+    }
+    let nprobe = resolve_nprobe(&state, req.nprobe);
+    let cache = request_cache(&state, &headers, req.no_cache);
+    let options: Vec<_> = metas
+        .iter()
+        .map(|meta| query_options(&state, meta, req.include_attributes.any()))
+        .collect();
+    let responses =
+        futures::future::try_join_all(metas.iter().zip(&options).map(|(meta, options)| {
+            query::execute_query_with_options(
+                &state.store,
+                &state.wal_reader,
+                &meta.name,
+                &req.vector,
+                req.top_k,
+                nprobe,
+                req.filter.as_ref(),
+                req.consistency,
+                meta.distance_metric,
+                state.config.indexing.oversample_factor,
+                cache,
+                options,
+            )
+        }))
+        .await?;
+
+    let mut merged = MultiNamespaceQueryResponse {
+        results: Vec::new(),
+        scanned_fragments: 0,
+        scanned_segments: 0,
+        warnings: Vec::new(),
+    };
+    for (meta, response) in metas.iter().zip(responses) {
+        merged.scanned_fragments += response.scanned_fragments;
+        merged.scanned_segments += response.scanned_segments;
+        for warning in response.warnings {
+            if !merged.warnings.contains(&warning) {
+                merged.warnings.push(warning);
+            }
+        }
+        merged
+            .results
+            .extend(response.results.into_iter().map(|mut result| {
+                project_attributes(
+                    &mut result.attributes,
+                    &req.include_attributes,
+                    req.exclude_attributes.as_deref(),
+                );
+                NamespacedResult {
+                    namespace: meta.name.clone(),
+                    result,
+                }
+            }));
+    }
+    merged.results.sort_by(|a, b| {
+        first
+            .distance_metric
+            .compare_scores(a.result.score, b.result.score)
+    });
+    merged.results.truncate(req.top_k);
 
     info!(
-        results = result.results.len(),
-        scanned_fragments = result.scanned_fragments,
-        scanned_segments = result.scanned_segments,
-        elapsed_ms = elapsed.as_millis(),
-        "query complete"
+        namespaces = req.namespaces.len(),
+        results = merged.results.len(),
+        elapsed_ms = start.elapsed().as_millis(),
+        "multi-namespace query complete"
     );
 
-    Ok(Json(result))
+    Ok(CasedJson(merged, state.config.server.json_case))
 }
@@ -1,25 +1,106 @@
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode};
 use axum::Json;
 use serde::{Deserialize, Serialize};
-use tracing::{info, instrument};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tracing::{info, instrument, warn};
 
+use crate::config::ServerConfig;
 use crate::error::ZeppelinError;
+use crate::namespace::manager::NamespaceMetadata;
+use crate::query::{self, CountByResponse};
 use crate::server::AppState;
-use crate::types::{VectorEntry, VectorId};
+use crate::types::{
+    AttributeValue, DistanceMetric, Filter, SparseVector, UpsertCondition, VectorEntry, VectorId,
+};
+use crate::wal::{AttributePatch, FilterPatch, WalFragment};
 
-use super::ApiError;
+use super::{ApiError, ApiJson, CasedJson};
+
+/// Most vectors of an upsert checked for unit norm on cosine namespaces.
+const NORM_CHECK_SAMPLE: usize = 64;
+/// A sampled vector counts as unnormalized when its norm is further than
+/// this from 1.
+const NORM_TOLERANCE: f32 = 0.01;
+/// Warn when more than this fraction of the sample is unnormalized.
+const UNNORMALIZED_WARN_FRACTION: f64 = 0.1;
+/// Groups returned by count-by when the request doesn't set `limit`.
+const DEFAULT_COUNT_BY_LIMIT: usize = 100;
+/// Most groups a count-by request may ask for.
+const MAX_COUNT_BY_LIMIT: usize = 1000;
+/// Vectors per page of a listing when the request doesn't set `limit`.
+const DEFAULT_LIST_LIMIT: usize = 100;
+/// Most vectors a single listing page may ask for.
+const MAX_LIST_LIMIT: usize = 1000;
+/// Tombstoned IDs per page when the request doesn't set `limit`.
+const DEFAULT_TOMBSTONES_LIMIT: usize = 1000;
+/// Most tombstoned IDs a single page may ask for.
+const MAX_TOMBSTONES_LIMIT: usize = 10_000;
 
 #[derive(Debug, Deserialize)]
 pub struct UpsertVectorsRequest {
     pub vectors: Vec<VectorEntry>,
+    /// Acknowledge with 202 as soon as the write is queued instead of once
+    /// it is in the WAL. The data becomes durable and queryable shortly
+    /// after, but a strong read issued right away may not see it, and a
+    /// failed write can't be reported back. Meant for bulk backfills.
+    #[serde(default, alias = "asyncAck")]
+    pub async_ack: bool,
+    /// Reject the whole batch with 409 unless every vector meets this.
+    /// Can't be combined with `async_ack`.
+    #[serde(default)]
+    pub condition: Option<UpsertCondition>,
 }
 
+/// Request header naming a write for deduplication of client retries.
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+/// Response header set when a keyed write was a retry and wasn't repeated.
+const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
 #[derive(Debug, Serialize)]
 pub struct UpsertVectorsResponse {
     pub upserted: usize,
 }
 
+/// Body of `PUT /v1/namespaces/:ns/vectors/:id`. The ID comes from the path.
+#[derive(Debug, Deserialize)]
+pub struct PutVectorRequest {
+    #[serde(default)]
+    pub values: Vec<f32>,
+    #[serde(default)]
+    pub attributes: Option<HashMap<String, AttributeValue>>,
+    #[serde(default)]
+    pub sparse: Option<SparseVector>,
+    #[serde(default)]
+    pub named_vectors: Option<BTreeMap<String, Vec<f32>>>,
+    #[serde(default)]
+    pub token_vectors: Option<Vec<Vec<f32>>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PutVectorResponse {
+    pub id: VectorId,
+    pub upserted: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateByFilterRequest {
+    pub filter: Filter,
+    pub attributes: HashMap<String, AttributeValue>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PatchVectorRequest {
+    pub attributes: HashMap<String, AttributeValue>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatchVectorResponse {
+    pub id: VectorId,
+    pub patched: usize,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct DeleteVectorsRequest {
     pub ids: Vec<VectorId>,
@@ -30,12 +111,88 @@ pub struct DeleteVectorsResponse {
     pub deleted: usize,
 }
 
-#[instrument(skip(state, req), fields(namespace = %ns, vector_count = req.vectors.len()))]
+/// Body of `POST /v1/namespaces/:ns/delete_by_filter`.
+#[derive(Debug, Deserialize)]
+pub struct DeleteByFilterRequest {
+    pub filter: Filter,
+}
+
+/// Body of `POST /v1/namespaces/:ns/fetch`.
+#[derive(Debug, Deserialize)]
+pub struct FetchVectorsRequest {
+    pub ids: Vec<VectorId>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FetchVectorsResponse {
+    /// The requested vectors that exist, in request order. Missing or
+    /// deleted IDs are left out.
+    pub vectors: Vec<VectorEntry>,
+}
+
+/// Body of `POST /v1/namespaces/:ns/count-by`.
+#[derive(Debug, Deserialize)]
+pub struct CountByRequest {
+    /// Attribute to group by; dot paths reach into objects.
+    pub field: String,
+    #[serde(default)]
+    pub filter: Option<Filter>,
+    /// Most groups to return, largest first.
+    #[serde(default = "default_count_by_limit")]
+    pub limit: usize,
+}
+
+fn default_count_by_limit() -> usize {
+    DEFAULT_COUNT_BY_LIMIT
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListVectorsParams {
+    /// Vectors per page; defaults to 100.
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Resume where the previous page stopped: its `next_cursor`.
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListTombstonesParams {
+    /// IDs per page; defaults to 1000.
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Resume after this ID: the `next_cursor` of the previous page.
+    #[serde(default)]
+    pub cursor: Option<VectorId>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListTombstonesResponse {
+    pub ids: Vec<VectorId>,
+    /// Pass as `cursor` to fetch the next page; absent on the last one. In
+    /// the body rather than a header because IDs need not be valid header
+    /// values.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<VectorId>,
+}
+
+/// Upsert a batch of vectors.
+///
+/// With an `Idempotency-Key` header, a retry of a write that already landed
+/// is not written again; its response carries `Idempotent-Replayed: true`.
+#[instrument(skip(state, headers, req), fields(namespace = %ns, vector_count = req.vectors.len()))]
 pub async fn upsert_vectors(
     State(state): State<AppState>,
     Path(ns): Path<String>,
-    Json(req): Json<UpsertVectorsRequest>,
-) -> Result<(StatusCode, Json<UpsertVectorsResponse>), ApiError> {
+    headers: HeaderMap,
+    ApiJson(req): ApiJson<UpsertVectorsRequest>,
+) -> Result<(StatusCode, HeaderMap, Json<UpsertVectorsResponse>), ApiError> {
+    let key = idempotency_key(&headers)?;
+    if key.is_some() && (req.async_ack || req.condition.is_some()) {
+        return Err(ApiError(ZeppelinError::Validation(
+            "Idempotency-Key cannot be combined with async_ack or condition".into(),
+        )));
+    }
     if req.vectors.is_empty() {
         return Err(ApiError(ZeppelinError::Validation(
             "vectors array cannot be empty".into(),
@@ -50,18 +207,7 @@ pub async fn upsert_vectors(
     }
 
     for vec in &req.vectors {
-        if vec.id.is_empty() {
-            return Err(ApiError(ZeppelinError::Validation(
-                "vector id cannot be empty".into(),
-            )));
-        }
-        if vec.id.len() > state.config.server.max_vector_id_length {
-            return Err(ApiError(ZeppelinError::Validation(format!(
-                "vector id length {} exceeds maximum of {}",
-                vec.id.len(),
-                state.config.server.max_vector_id_length
-            ))));
-        }
+        validate_vector_id(&vec.id, &state.config.server)?;
     }
 
     info!(count = req.vectors.len(), "upserting vectors");
@@ -74,6 +220,14 @@ pub async fn upsert_vectors(
         .map_err(ApiError::from)?;
 
     for vec in &req.vectors {
+        // Documents in a text-only namespace carry no values.
+        if !meta.is_text_only() {
+            vec.validate()?;
+        } else {
+            vec.validate_sparse()?;
+        }
+        meta.validate_named_vectors(vec)?;
+        meta.validate_token_vectors(vec)?;
         if vec.values.len() != meta.dimensions {
             return Err(ApiError(ZeppelinError::DimensionMismatch {
                 expected: meta.dimensions,
@@ -82,26 +236,400 @@ pub async fn upsert_vectors(
         }
     }
 
+    warn_if_unnormalized(&ns, &meta, &req.vectors);
+
+    // Held until the WAL append finishes.
+    let permit = state.upsert_limiter.try_acquire(&ns)?;
+
     let count = req.vectors.len();
+    if let Some(condition) = &req.condition {
+        if req.async_ack {
+            return Err(ApiError(ZeppelinError::Validation(
+                "condition cannot be combined with async_ack".into(),
+            )));
+        }
+        let _permit = permit;
+        conditional_upsert(&state, &ns, req.vectors, condition).await?;
+        info!(upserted = count, "vectors conditionally upserted");
+        return Ok((
+            StatusCode::OK,
+            HeaderMap::new(),
+            Json(UpsertVectorsResponse { upserted: count }),
+        ));
+    }
+    if req.async_ack {
+        state
+            .wal_writer
+            .submit_detached(&ns, req.vectors, vec![], permit);
+        info!(queued = count, "vectors queued for upsert");
+        return Ok((
+            StatusCode::ACCEPTED,
+            HeaderMap::new(),
+            Json(UpsertVectorsResponse { upserted: count }),
+        ));
+    }
+    let _permit = permit;
+    let replayed = match &key {
+        Some(key) => {
+            let hash = WalFragment::payload_hash(&req.vectors, &[]);
+            state
+                .wal_writer
+                .append_idempotent(&ns, req.vectors, vec![], key, hash)
+                .await
+                .map_err(ApiError::from)?
+        }
+        None => {
+            state
+                .wal_writer
+                .submit(&ns, req.vectors, vec![])
+                .await
+                .map_err(ApiError::from)?;
+            false
+        }
+    };
+
+    info!(upserted = count, replayed, "vectors upserted");
+    Ok((
+        StatusCode::OK,
+        replay_headers(replayed),
+        Json(UpsertVectorsResponse { upserted: count }),
+    ))
+}
+
+/// The `Idempotency-Key` header, if present.
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<String>, ApiError> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY) else {
+        return Ok(None);
+    };
+    let key = value
+        .to_str()
+        .ok()
+        .filter(|k| !k.is_empty() && k.len() <= MAX_IDEMPOTENCY_KEY_LENGTH)
+        .ok_or_else(|| {
+            ApiError(ZeppelinError::Validation(format!(
+                "Idempotency-Key must be 1 to {MAX_IDEMPOTENCY_KEY_LENGTH} visible ASCII characters"
+            )))
+        })?;
+    Ok(Some(key.to_string()))
+}
+
+/// `Idempotent-Replayed: true` when a write was a replayed retry.
+fn replay_headers(replayed: bool) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if replayed {
+        headers.insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    }
+    headers
+}
+
+/// Check `condition` and append `vectors` under the writer's namespace
+/// lock, failing with 409 and the offending IDs if any violate it.
+async fn conditional_upsert(
+    state: &AppState,
+    ns: &str,
+    vectors: Vec<VectorEntry>,
+    condition: &UpsertCondition,
+) -> Result<(), ApiError> {
+    let check_vectors = vectors.clone();
     state
         .wal_writer
-        .append(&ns, req.vectors, vec![])
+        .append_if(ns, vectors, || async {
+            let ids = query::upsert_conflicts(
+                &state.store,
+                &state.wal_reader,
+                ns,
+                &check_vectors,
+                condition,
+            )
+            .await?;
+            if ids.is_empty() {
+                Ok(())
+            } else {
+                Err(ZeppelinError::UpsertConditionFailed {
+                    namespace: ns.to_string(),
+                    ids,
+                })
+            }
+        })
         .await
         .map_err(ApiError::from)?;
+    Ok(())
+}
 
-    info!(upserted = count, "vectors upserted");
-    Ok((
-        StatusCode::OK,
-        Json(UpsertVectorsResponse { upserted: count }),
+/// The upsert condition requested by `If-None-Match: *` (create only) or
+/// `If-Match: "<version>"` on a single-vector write.
+fn condition_from_headers(
+    headers: &HeaderMap,
+    id: &str,
+) -> Result<Option<UpsertCondition>, ApiError> {
+    let header = |name: HeaderName| {
+        headers
+            .get(&name)
+            .map(|v| {
+                v.to_str()
+                    .map(|s| s.trim().trim_matches('"').to_string())
+                    .map_err(|_| {
+                        ApiError(ZeppelinError::Validation(format!("invalid {name} header")))
+                    })
+            })
+            .transpose()
+    };
+    match (header(header::IF_NONE_MATCH)?, header(header::IF_MATCH)?) {
+        (Some(_), Some(_)) => Err(ApiError(ZeppelinError::Validation(
+            "If-Match and If-None-Match cannot be combined".into(),
+        ))),
+        (Some(tag), None) if tag == "*" => Ok(Some(UpsertCondition::IfNotExists)),
+        (Some(_), None) => Err(ApiError(ZeppelinError::Validation(
+            "If-None-Match only supports *".into(),
+        ))),
+        (None, Some(version)) => Ok(Some(UpsertCondition::IfVersionMatches(HashMap::from([(
+            id.to_string(),
+            version,
+        )])))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Upsert a single vector whose ID is taken from the path.
+///
+/// Sugar over the batch upsert: the vector is appended to the WAL as a
+/// one-element batch. `If-None-Match: *` makes it create-only and
+/// `If-Match` with the vector's `ETag` makes it a compare-and-set; either
+/// fails with 409 when the condition doesn't hold.
+#[instrument(skip(state, headers, req), fields(namespace = %ns, id = %id))]
+pub async fn put_vector(
+    State(state): State<AppState>,
+    Path((ns, id)): Path<(String, String)>,
+    headers: HeaderMap,
+    ApiJson(req): ApiJson<PutVectorRequest>,
+) -> Result<Json<PutVectorResponse>, ApiError> {
+    validate_vector_id(&id, &state.config.server)?;
+    let condition = condition_from_headers(&headers, &id)?;
+    let entry = VectorEntry {
+        id: id.clone(),
+        values: req.values,
+        attributes: req.attributes,
+        sparse: req.sparse,
+        named_vectors: req.named_vectors,
+        token_vectors: req.token_vectors,
+        version: None,
+    };
+
+    let meta = state
+        .namespace_manager
+        .get(&ns)
+        .await
+        .map_err(ApiError::from)?;
+    if !meta.is_text_only() {
+        entry.validate()?;
+    } else {
+        entry.validate_sparse()?;
+    }
+    meta.validate_named_vectors(&entry)?;
+    meta.validate_token_vectors(&entry)?;
+
+    if entry.values.len() != meta.dimensions {
+        return Err(ApiError(ZeppelinError::DimensionMismatch {
+            expected: meta.dimensions,
+            actual: entry.values.len(),
+        }));
+    }
+
+    warn_if_unnormalized(&ns, &meta, std::slice::from_ref(&entry));
+
+    let _permit = state.upsert_limiter.try_acquire(&ns)?;
+
+    match &condition {
+        Some(condition) => conditional_upsert(&state, &ns, vec![entry], condition).await?,
+        None => state
+            .wal_writer
+            .submit(&ns, vec![entry], vec![])
+            .await
+            .map_err(ApiError::from)?,
+    }
+
+    info!("vector upserted");
+    Ok(Json(PutVectorResponse { id, upserted: 1 }))
+}
+
+/// `PATCH /v1/namespaces/:ns/vectors/:id` — merge `attributes` into a
+/// vector's existing ones without resubmitting its values.
+///
+/// Given attributes replace those of the same name; others are kept. The
+/// patch is merged at read and compaction time against the vector's state
+/// when it lands, so patching a vector that doesn't exist (or is deleted
+/// by then) has no effect.
+#[instrument(skip(state, req), fields(namespace = %ns, id = %id))]
+pub async fn patch_vector(
+    State(state): State<AppState>,
+    Path((ns, id)): Path<(String, String)>,
+    ApiJson(req): ApiJson<PatchVectorRequest>,
+) -> Result<Json<PatchVectorResponse>, ApiError> {
+    validate_vector_id(&id, &state.config.server)?;
+    if req.attributes.is_empty() {
+        return Err(ApiError(ZeppelinError::Validation(
+            "attributes must not be empty".into(),
+        )));
+    }
+    state
+        .namespace_manager
+        .get(&ns)
+        .await
+        .map_err(ApiError::from)?;
+
+    let _permit = state.upsert_limiter.try_acquire(&ns)?;
+
+    state
+        .wal_writer
+        .append_patches(
+            &ns,
+            vec![AttributePatch {
+                id: id.clone(),
+                attributes: req.attributes,
+            }],
+            Vec::new(),
+        )
+        .await
+        .map_err(ApiError::from)?;
+
+    info!("vector attributes patched");
+    Ok(Json(PatchVectorResponse { id, patched: 1 }))
+}
+
+/// `GET /v1/namespaces/:ns/vectors/:id` — the latest version of one vector,
+/// with its values and attributes. 404 if it doesn't exist or was deleted.
+/// The `ETag` header carries its version, for `If-Match` on a later PUT.
+///
+/// Always reads the uncompacted WAL, so a vector is visible as soon as its
+/// upsert is acknowledged.
+#[instrument(skip(state), fields(namespace = %ns, id = %id))]
+pub async fn get_vector(
+    State(state): State<AppState>,
+    Path((ns, id)): Path<(String, String)>,
+) -> Result<(HeaderMap, CasedJson<VectorEntry>), ApiError> {
+    state
+        .namespace_manager
+        .get(&ns)
+        .await
+        .map_err(ApiError::from)?;
+
+    let vector = query::get_vector(&state.store, &state.wal_reader, &ns, &id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or_else(|| {
+            ApiError(ZeppelinError::VectorNotFound {
+                namespace: ns.clone(),
+                id: id.clone(),
+            })
+        })?;
+
+    info!("vector fetched");
+    let mut headers = HeaderMap::new();
+    if let Some(version) = &vector.version {
+        headers.insert(
+            header::ETAG,
+            HeaderValue::from_str(&format!("\"{version}\""))
+                .expect("ULID-based version is a valid header value"),
+        );
+    }
+    Ok((headers, CasedJson(vector, state.config.server.json_case)))
+}
+
+/// `GET /v1/namespaces/:ns/vectors` — every live vector, ordered by ID and
+/// paginated with `?limit=&cursor=`.
+///
+/// For exports and audits. Cursors survive compaction; writes landing
+/// during a scan appear in later pages only if their IDs sort after the
+/// cursor.
+#[instrument(skip(state), fields(namespace = %ns))]
+pub async fn list_vectors(
+    State(state): State<AppState>,
+    Path(ns): Path<String>,
+    Query(params): Query<ListVectorsParams>,
+) -> Result<CasedJson<query::VectorPage>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_LIST_LIMIT);
+    if limit == 0 || limit > MAX_LIST_LIMIT {
+        return Err(ApiError(ZeppelinError::Validation(format!(
+            "limit must be between 1 and {MAX_LIST_LIMIT}"
+        ))));
+    }
+
+    state
+        .namespace_manager
+        .get(&ns)
+        .await
+        .map_err(ApiError::from)?;
+
+    let page = query::list_vectors(
+        &state.store,
+        &state.wal_reader,
+        &ns,
+        params.cursor.as_deref(),
+        limit,
+    )
+    .await
+    .map_err(ApiError::from)?;
+
+    info!(
+        count = page.vectors.len(),
+        more = page.next_cursor.is_some(),
+        "listed vectors"
+    );
+    Ok(CasedJson(page, state.config.server.json_case))
+}
+
+/// `POST /v1/namespaces/:ns/fetch` — the latest version of several
+/// vectors in one call. At most `max_batch_size` IDs per request.
+#[instrument(skip(state, req), fields(namespace = %ns, id_count = req.ids.len()))]
+pub async fn fetch_vectors(
+    State(state): State<AppState>,
+    Path(ns): Path<String>,
+    ApiJson(req): ApiJson<FetchVectorsRequest>,
+) -> Result<CasedJson<FetchVectorsResponse>, ApiError> {
+    if req.ids.is_empty() {
+        return Err(ApiError(ZeppelinError::Validation(
+            "ids array cannot be empty".into(),
+        )));
+    }
+    if req.ids.len() > state.config.server.max_batch_size {
+        return Err(ApiError(ZeppelinError::Validation(format!(
+            "batch size {} exceeds maximum of {}",
+            req.ids.len(),
+            state.config.server.max_batch_size
+        ))));
+    }
+
+    state
+        .namespace_manager
+        .get(&ns)
+        .await
+        .map_err(ApiError::from)?;
+
+    let vectors = query::fetch_vectors(&state.store, &state.wal_reader, &ns, &req.ids)
+        .await
+        .map_err(ApiError::from)?;
+
+    info!(
+        requested = req.ids.len(),
+        found = vectors.len(),
+        "vectors fetched"
+    );
+    Ok(CasedJson(
+        FetchVectorsResponse { vectors },
+        state.config.server.json_case,
     ))
 }
 
-#[instrument(skip(state, req), fields(namespace = %ns, delete_count = req.ids.len()))]
+/// Delete vectors by ID. Takes an `Idempotency-Key` header like
+/// [`upsert_vectors`].
+#[instrument(skip(state, headers, req), fields(namespace = %ns, delete_count = req.ids.len()))]
 pub async fn delete_vectors(
     State(state): State<AppState>,
     Path(ns): Path<String>,
-    Json(req): Json<DeleteVectorsRequest>,
-) -> Result<Json<DeleteVectorsResponse>, ApiError> {
+    headers: HeaderMap,
+    ApiJson(req): ApiJson<DeleteVectorsRequest>,
+) -> Result<(HeaderMap, Json<DeleteVectorsResponse>), ApiError> {
+    let key = idempotency_key(&headers)?;
     info!(count = req.ids.len(), "deleting vectors");
 
     // Validate namespace exists
@@ -111,13 +639,305 @@ pub async fn delete_vectors(
         .await
         .map_err(ApiError::from)?;
 
+    // Deletes are idempotent: IDs already tombstoned in the WAL (and not
+    // re-upserted since) are skipped, so repeated deletes don't pile up
+    // redundant tombstone fragments. The check runs under the writer's
+    // namespace lock against a manifest no older than its last commit, so
+    // a delete this node just wrote is always seen. A retry finds its IDs
+    // already tombstoned, so the replay check comes first.
     let count = req.ids.len();
-    state
+    let hash = WalFragment::payload_hash(&[], &req.ids);
+    let written = state
         .wal_writer
-        .append(&ns, vec![], req.ids)
+        .append_deletes_if(&ns, key.as_deref().map(|key| (key, hash)), || async {
+            let tombstoned = state
+                .wal_reader
+                .tombstoned_ids(
+                    &ns,
+                    &req.ids,
+                    state.wal_writer.committed_sequence(&ns).unwrap_or(0),
+                    state.config.storage.read_after_write_retries,
+                )
+                .await?;
+            let mut seen = HashSet::new();
+            Ok(req
+                .ids
+                .iter()
+                .filter(|id| !tombstoned.contains(*id) && seen.insert(id.as_str()))
+                .cloned()
+                .collect())
+        })
+        .await
+        .map_err(ApiError::from)?;
+
+    let replayed = written.is_none();
+    info!(
+        deleted = count,
+        redundant = written.map(|n| count - n),
+        replayed,
+        "vectors deleted"
+    );
+    Ok((
+        replay_headers(replayed),
+        Json(DeleteVectorsResponse { deleted: count }),
+    ))
+}
+
+/// Delete every live vector whose attributes match a filter.
+///
+/// Scans the segments and the uncompacted WAL like count-by, then writes
+/// the matching IDs to the WAL in delete batches of at most
+/// `max_batch_size`. Not atomic with respect to concurrent writes: a
+/// matching vector upserted during the scan survives, and a failed write
+/// leaves earlier batches deleted.
+#[instrument(skip(state, body), fields(namespace = %ns))]
+pub async fn delete_by_filter(
+    State(state): State<AppState>,
+    Path(ns): Path<String>,
+    ApiJson(body): ApiJson<serde_json::Value>,
+) -> Result<Json<DeleteVectorsResponse>, ApiError> {
+    if let Some(filter) = body.get("filter") {
+        Filter::validate_ops(filter)?;
+    }
+    let req: DeleteByFilterRequest = serde_json::from_value(body).map_err(|e| {
+        ApiError(ZeppelinError::Validation(format!(
+            "invalid delete-by-filter request: {e}"
+        )))
+    })?;
+
+    state
+        .namespace_manager
+        .get(&ns)
+        .await
+        .map_err(ApiError::from)?;
+
+    let ids = query::matching_ids(&state.store, &state.wal_reader, &ns, &req.filter)
         .await
         .map_err(ApiError::from)?;
+    let count = ids.len();
+    for batch in ids.chunks(state.config.server.max_batch_size.max(1)) {
+        state
+            .wal_writer
+            .submit(&ns, vec![], batch.to_vec())
+            .await
+            .map_err(ApiError::from)?;
+    }
 
-    info!(deleted = count, "vectors deleted");
+    info!(deleted = count, "vectors deleted by filter");
     Ok(Json(DeleteVectorsResponse { deleted: count }))
 }
+
+/// Set attributes on every live vector matching a filter.
+///
+/// Unlike delete-by-filter nothing is scanned up front: the filter and
+/// attributes go to the WAL as one record, matched against each vector's
+/// state when the record applies. Reads merge it in until compaction
+/// rewrites the matching vectors. Returns 202 once the record is durable.
+#[instrument(skip(state, body), fields(namespace = %ns))]
+pub async fn update_by_filter(
+    State(state): State<AppState>,
+    Path(ns): Path<String>,
+    ApiJson(body): ApiJson<serde_json::Value>,
+) -> Result<StatusCode, ApiError> {
+    if let Some(filter) = body.get("filter") {
+        Filter::validate_ops(filter)?;
+    }
+    let req: UpdateByFilterRequest = serde_json::from_value(body).map_err(|e| {
+        ApiError(ZeppelinError::Validation(format!(
+            "invalid update-by-filter request: {e}"
+        )))
+    })?;
+    if req.attributes.is_empty() {
+        return Err(ApiError(ZeppelinError::Validation(
+            "attributes must not be empty".into(),
+        )));
+    }
+
+    state
+        .namespace_manager
+        .get(&ns)
+        .await
+        .map_err(ApiError::from)?;
+
+    let _permit = state.upsert_limiter.try_acquire(&ns)?;
+
+    state
+        .wal_writer
+        .append_patches(
+            &ns,
+            Vec::new(),
+            vec![FilterPatch {
+                filter: req.filter,
+                attributes: req.attributes,
+            }],
+        )
+        .await
+        .map_err(ApiError::from)?;
+
+    info!("attribute update by filter recorded");
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Count live vectors per value of an attribute.
+///
+/// Scans the active segment and the uncompacted WAL, so the cost grows with
+/// the namespace; meant for dashboards and sanity checks, not hot paths.
+#[instrument(skip(state, body), fields(namespace = %ns))]
+pub async fn count_by(
+    State(state): State<AppState>,
+    Path(ns): Path<String>,
+    ApiJson(body): ApiJson<serde_json::Value>,
+) -> Result<Json<CountByResponse>, ApiError> {
+    if let Some(filter) = body.get("filter") {
+        Filter::validate_ops(filter)?;
+    }
+    let req: CountByRequest = serde_json::from_value(body).map_err(|e| {
+        ApiError(ZeppelinError::Validation(format!(
+            "invalid count-by request: {e}"
+        )))
+    })?;
+    if req.field.is_empty() {
+        return Err(ApiError(ZeppelinError::Validation(
+            "field cannot be empty".into(),
+        )));
+    }
+    if req.limit == 0 || req.limit > MAX_COUNT_BY_LIMIT {
+        return Err(ApiError(ZeppelinError::Validation(format!(
+            "limit must be between 1 and {MAX_COUNT_BY_LIMIT}"
+        ))));
+    }
+
+    state
+        .namespace_manager
+        .get(&ns)
+        .await
+        .map_err(ApiError::from)?;
+
+    let resp = query::count_by(
+        &state.store,
+        &state.wal_reader,
+        &ns,
+        &req.field,
+        req.filter.as_ref(),
+        req.limit,
+    )
+    .await
+    .map_err(ApiError::from)?;
+
+    info!(
+        field = %req.field,
+        groups = resp.counts.len(),
+        truncated = resp.truncated,
+        "count-by complete"
+    );
+    // Plain `Json`: the keys are attribute values and must not be re-cased.
+    Ok(Json(resp))
+}
+
+/// `GET /v1/namespaces/:ns/tombstones` — IDs deleted in the uncompacted WAL,
+/// ordered by ID and paginated with `?limit=&cursor=`.
+///
+/// For change-data-capture consumers reconciling a downstream store: an ID
+/// is listed from its delete until compaction reclaims it, so poll at least
+/// as often as the namespace compacts. IDs upserted again since are omitted.
+#[instrument(skip(state), fields(namespace = %ns))]
+pub async fn list_tombstones(
+    State(state): State<AppState>,
+    Path(ns): Path<String>,
+    Query(params): Query<ListTombstonesParams>,
+) -> Result<CasedJson<ListTombstonesResponse>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_TOMBSTONES_LIMIT);
+    if limit == 0 || limit > MAX_TOMBSTONES_LIMIT {
+        return Err(ApiError(ZeppelinError::Validation(format!(
+            "limit must be between 1 and {MAX_TOMBSTONES_LIMIT}"
+        ))));
+    }
+
+    state
+        .namespace_manager
+        .get(&ns)
+        .await
+        .map_err(ApiError::from)?;
+
+    let tombstones = state
+        .wal_reader
+        .tombstones(&ns)
+        .await
+        .map_err(ApiError::from)?;
+    let mut rest = match &params.cursor {
+        Some(after) => tombstones
+            .range::<VectorId, _>((std::ops::Bound::Excluded(after), std::ops::Bound::Unbounded))
+            .peekable(),
+        None => tombstones.range::<VectorId, _>(..).peekable(),
+    };
+    let ids: Vec<VectorId> = rest.by_ref().take(limit).cloned().collect();
+    let next_cursor = match (rest.peek(), ids.last()) {
+        (Some(_), Some(last)) => Some(last.clone()),
+        _ => None,
+    };
+
+    info!(
+        count = ids.len(),
+        total = tombstones.len(),
+        "listed tombstones"
+    );
+    Ok(CasedJson(
+        ListTombstonesResponse { ids, next_cursor },
+        state.config.server.json_case,
+    ))
+}
+
+/// Warn (log and metric) when a cosine namespace receives vectors that are
+/// mostly not unit-norm. Cosine distance itself is scale-invariant, but
+/// quantization and k-means assume normalized inputs, so unnormalized
+/// vectors tend to give surprising results. Only an evenly spaced sample of
+/// the batch is checked. Text-only namespaces store no vector values, so
+/// they are never checked.
+fn warn_if_unnormalized(namespace: &str, meta: &NamespaceMetadata, vectors: &[VectorEntry]) {
+    if meta.distance_metric != DistanceMetric::Cosine || meta.is_text_only() || vectors.is_empty() {
+        return;
+    }
+    let step = vectors.len().div_ceil(NORM_CHECK_SAMPLE);
+    let sample: Vec<&VectorEntry> = vectors.iter().step_by(step).collect();
+    let unnormalized = sample
+        .iter()
+        .filter(|v| {
+            let norm = v.values.iter().map(|x| x * x).sum::<f32>().sqrt();
+            (norm - 1.0).abs() > NORM_TOLERANCE
+        })
+        .count();
+    let fraction = unnormalized as f64 / sample.len() as f64;
+    if fraction > UNNORMALIZED_WARN_FRACTION {
+        crate::metrics::UNNORMALIZED_COSINE_UPSERTS_TOTAL
+            .with_label_values(&[namespace])
+            .inc();
+        warn!(
+            sampled = sample.len(),
+            unnormalized,
+            "cosine namespace received vectors that are not unit-norm; \
+             normalize vectors before upserting"
+        );
+    }
+}
+
+fn validate_vector_id(id: &str, config: &ServerConfig) -> Result<(), ApiError> {
+    if id.is_empty() {
+        return Err(ApiError(ZeppelinError::Validation(
+            "vector id cannot be empty".into(),
+        )));
+    }
+    if id.len() > config.max_vector_id_length {
+        return Err(ApiError(ZeppelinError::Validation(format!(
+            "vector id length {} exceeds maximum of {}",
+            id.len(),
+            config.max_vector_id_length
+        ))));
+    }
+    if let Some(c) = config.vector_id_charset.first_invalid(id) {
+        return Err(ApiError(ZeppelinError::Validation(format!(
+            "vector id {id:?} contains character {c:?}, not allowed by vector_id_charset \"{}\"",
+            config.vector_id_charset
+        ))));
+    }
+    Ok(())
+}
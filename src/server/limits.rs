@@ -0,0 +1,305 @@
+//! Per-namespace admission limits: concurrent upserts and request rates.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::{MatchedPath, RawPathParams, Request, State};
+use axum::http::{header, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use dashmap::DashMap;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::{RateLimit, Role, ServerConfig};
+use crate::error::{Result, ZeppelinError};
+
+use super::auth::{route_access, Access, Principal};
+use super::handlers::ApiError;
+
+/// Bounds in-flight upserts per namespace. Upserts past the limit are
+/// rejected immediately rather than queueing behind the namespace's WAL
+/// writer.
+pub struct UpsertLimiter {
+    limit: usize,
+    semaphores: DashMap<String, Arc<Semaphore>>,
+}
+
+impl UpsertLimiter {
+    /// Create a limiter allowing `limit` concurrent upserts per namespace.
+    /// A limit of 0 disables it.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            semaphores: DashMap::new(),
+        }
+    }
+
+    /// Take an upsert slot for `namespace`, held until the returned permit
+    /// is dropped. `Ok(None)` when limiting is disabled.
+    pub fn try_acquire(&self, namespace: &str) -> Result<Option<OwnedSemaphorePermit>> {
+        if self.limit == 0 {
+            return Ok(None);
+        }
+        let semaphore = self
+            .semaphores
+            .entry(namespace.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.limit)))
+            .clone();
+        semaphore.try_acquire_owned().map(Some).map_err(|_| {
+            ZeppelinError::TooManyConcurrentUpserts {
+                namespace: namespace.to_string(),
+                limit: self.limit,
+            }
+        })
+    }
+}
+
+/// Which rate limit a request counts against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateClass {
+    Write,
+    Query,
+}
+
+/// Tokens left in one bucket as of `updated`.
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Bucket count below which [`RateLimiter`] never sweeps.
+const MIN_SWEEP_BUCKETS: usize = 1024;
+
+/// Token-bucket rate limits per API key, namespace and [`RateClass`], so
+/// one busy tenant can't starve the rest of the node.
+pub struct RateLimiter {
+    write: RateLimit,
+    query: RateLimit,
+    /// Keyed by (key index, namespace, class). The key index is `None`
+    /// while auth is disabled.
+    buckets: DashMap<(Option<usize>, String, RateClass), Bucket>,
+    /// Bucket count at which the next sweep runs. Buckets are created
+    /// before the namespace is known to exist, so without sweeps requests
+    /// for made-up namespaces would grow the map forever.
+    sweep_at: AtomicUsize,
+}
+
+impl RateLimiter {
+    pub fn new(write: RateLimit, query: RateLimit) -> Self {
+        Self {
+            write,
+            query,
+            buckets: DashMap::new(),
+            sweep_at: AtomicUsize::new(MIN_SWEEP_BUCKETS),
+        }
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self::new(config.write_rate_limit, config.query_rate_limit)
+    }
+
+    fn limit(&self, class: RateClass) -> RateLimit {
+        match class {
+            RateClass::Write => self.write,
+            RateClass::Query => self.query,
+        }
+    }
+
+    /// Take a token for a request, or fail with how long until one is
+    /// available.
+    pub fn try_acquire(&self, key: Option<usize>, namespace: &str, class: RateClass) -> Result<()> {
+        self.try_acquire_at(key, namespace, class, Instant::now())
+    }
+
+    fn try_acquire_at(
+        &self,
+        key: Option<usize>,
+        namespace: &str,
+        class: RateClass,
+        now: Instant,
+    ) -> Result<()> {
+        let limit = self.limit(class);
+        if !limit.is_enabled() {
+            return Ok(());
+        }
+        let capacity = limit.capacity();
+        if self.buckets.len() >= self.sweep_at.load(Ordering::Relaxed) {
+            self.sweep(now);
+        }
+        let mut bucket = self
+            .buckets
+            .entry((key, namespace.to_string(), class))
+            .or_insert_with(|| Bucket {
+                tokens: capacity,
+                updated: now,
+            });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.qps).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        let wait = Duration::from_secs_f64((1.0 - bucket.tokens) / limit.qps);
+        Err(ZeppelinError::RateLimited {
+            namespace: namespace.to_string(),
+            retry_after_secs: wait.as_secs() + u64::from(wait.subsec_nanos() > 0),
+        })
+    }
+
+    /// Drop buckets that have refilled to capacity by `now`. A full bucket
+    /// behaves exactly like a new one, so no limit is lost. The next sweep
+    /// waits until the map doubles, keeping sweeps amortized O(1).
+    fn sweep(&self, now: Instant) {
+        self.buckets.retain(|(_, _, class), bucket| {
+            let limit = self.limit(*class);
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            bucket.tokens + elapsed * limit.qps < limit.capacity()
+        });
+        self.sweep_at.store(
+            (self.buckets.len() * 2).max(MIN_SWEEP_BUCKETS),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+/// Middleware applying [`RateLimiter`]: write routes count against the
+/// write limit and read routes against the query limit, per namespace in
+/// the path. `POST /v1/query` shares one bucket per key across its
+/// namespaces; admin and health routes are not limited. Runs after
+/// authentication so buckets are per API key.
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    matched_path: Option<MatchedPath>,
+    params: RawPathParams,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = matched_path.as_ref().map_or("", |p| p.as_str());
+    let class = match route_access(request.method(), path) {
+        Access::Role(Role::Write) => RateClass::Write,
+        Access::Role(Role::Read) => RateClass::Query,
+        Access::Handler if path == "/v1/query" => RateClass::Query,
+        Access::Handler if path == "/v1/namespaces" && request.method() == Method::GET => {
+            RateClass::Query
+        }
+        _ => return next.run(request).await,
+    };
+    let namespace = params
+        .iter()
+        .find(|(name, _)| *name == "ns")
+        .map_or("", |(_, value)| value);
+    let key = request
+        .extensions()
+        .get::<Arc<Principal>>()
+        .map(|p| p.index());
+    if let Err(e) = limiter.try_acquire(key, namespace, class) {
+        let retry_after = match &e {
+            ZeppelinError::RateLimited {
+                retry_after_secs, ..
+            } => *retry_after_secs,
+            _ => 1,
+        };
+        let mut response = ApiError(e).into_response();
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        return response;
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_is_per_namespace() {
+        let limiter = UpsertLimiter::new(1);
+        let held = limiter.try_acquire("a").unwrap();
+        assert!(held.is_some());
+        assert!(limiter.try_acquire("a").is_err());
+        assert!(limiter.try_acquire("b").unwrap().is_some());
+
+        drop(held);
+        assert!(limiter.try_acquire("a").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_zero_disables_limit() {
+        let limiter = UpsertLimiter::new(0);
+        let _a = limiter.try_acquire("a").unwrap();
+        assert!(limiter.try_acquire("a").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_rate_limit_refills() {
+        let limit = RateLimit { qps: 2.0, burst: 3 };
+        let limiter = RateLimiter::new(limit, RateLimit::default());
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter
+                .try_acquire_at(None, "a", RateClass::Write, start)
+                .unwrap();
+        }
+        match limiter.try_acquire_at(None, "a", RateClass::Write, start) {
+            Err(ZeppelinError::RateLimited {
+                retry_after_secs, ..
+            }) => assert_eq!(retry_after_secs, 1),
+            other => panic!("expected RateLimited, got {other:?}"),
+        }
+        // Other namespaces, keys and the (disabled) query limit are separate.
+        limiter
+            .try_acquire_at(None, "b", RateClass::Write, start)
+            .unwrap();
+        limiter
+            .try_acquire_at(Some(0), "a", RateClass::Write, start)
+            .unwrap();
+        limiter
+            .try_acquire_at(None, "a", RateClass::Query, start)
+            .unwrap();
+
+        // Half a second refills one token at 2 qps.
+        let later = start + Duration::from_millis(500);
+        limiter
+            .try_acquire_at(None, "a", RateClass::Write, later)
+            .unwrap();
+        assert!(limiter
+            .try_acquire_at(None, "a", RateClass::Write, later)
+            .is_err());
+    }
+
+    #[test]
+    fn test_rate_limit_sweeps_refilled_buckets() {
+        let limit = RateLimit {
+            qps: 10.0,
+            burst: 0,
+        };
+        let limiter = RateLimiter::new(limit, RateLimit::default());
+        let start = Instant::now();
+        while limiter
+            .try_acquire_at(None, "busy", RateClass::Write, start)
+            .is_ok()
+        {}
+        for i in 1..MIN_SWEEP_BUCKETS {
+            limiter
+                .try_acquire_at(None, &format!("ns-{i}"), RateClass::Write, start)
+                .unwrap();
+        }
+
+        // After 100ms the buckets that gave up one token are full again and
+        // get dropped; the drained one keeps its state.
+        let later = start + Duration::from_millis(100);
+        limiter
+            .try_acquire_at(None, "new", RateClass::Write, later)
+            .unwrap();
+        assert_eq!(limiter.buckets.len(), 2);
+        limiter
+            .try_acquire_at(None, "busy", RateClass::Write, later)
+            .unwrap();
+        assert!(limiter
+            .try_acquire_at(None, "busy", RateClass::Write, later)
+            .is_err());
+    }
+}
@@ -1,7 +1,11 @@
+use std::any::Any;
+
 use axum::extract::MatchedPath;
-use axum::http::Request;
+use axum::http::{Request, StatusCode};
 use axum::middleware::Next;
-use axum::response::Response;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::json;
 use tracing::Instrument;
 
 use crate::metrics::HTTP_REQUESTS_TOTAL;
@@ -27,6 +31,25 @@ pub async fn http_metrics(
     response
 }
 
+/// Response for a handler that panicked, used with `CatchPanicLayer`.
+///
+/// Without it the connection is dropped with no response at all. The panic
+/// is logged inside the request span, so it carries the request ID.
+pub fn panic_response(err: Box<dyn Any + Send + 'static>) -> Response {
+    let message = err
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| err.downcast_ref::<&str>().copied())
+        .unwrap_or("unknown panic payload");
+    tracing::error!(panic = message, "handler panicked");
+    let body = json!({
+        "error": "internal error",
+        "code": "panic",
+        "status": 500,
+    });
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response()
+}
+
 /// Middleware that attaches a request ID to every request.
 ///
 /// - Respects an incoming `x-request-id` header if present.
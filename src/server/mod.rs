@@ -1,4 +1,6 @@
+pub mod auth;
 pub mod handlers;
+pub mod limits;
 pub mod middleware;
 pub mod routes;
 
@@ -21,4 +23,5 @@ pub struct AppState {
     pub config: Arc<Config>,
     pub compactor: Arc<Compactor>,
     pub cache: Arc<DiskCache>,
+    pub upsert_limiter: Arc<limits::UpsertLimiter>,
 }
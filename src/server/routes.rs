@@ -1,37 +1,102 @@
+use std::sync::Arc;
 use std::time::Duration;
 
 use axum::extract::DefaultBodyLimit;
 use axum::routing::{get, post};
 use axum::Router;
+use tower_http::catch_panic::CatchPanicLayer;
 use tower_http::limit::RequestBodyLimitLayer;
 use tower_http::timeout::TimeoutLayer;
 use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
 use tracing::Level;
 
-use super::handlers::{health, metrics, namespace, query, vectors};
+use super::auth::{self, ApiKeys};
+use super::handlers::{admin, health, metrics, namespace, query, vectors};
+use super::limits::{self, RateLimiter};
 use super::middleware;
 use super::AppState;
 
 pub fn build_router(state: AppState) -> Router {
     let timeout = Duration::from_secs(state.config.server.request_timeout_secs);
+    let api_keys = Arc::new(ApiKeys::from_config(&state.config.auth));
+    let rate_limiter = Arc::new(RateLimiter::from_config(&state.config.server));
 
     Router::new()
         .route("/healthz", get(health::health_check))
         .route("/readyz", get(health::readiness_check))
         .route("/metrics", get(metrics::metrics_handler))
+        .route("/v1/admin/config", get(admin::get_config))
+        .route("/v1/admin/compact-all", post(admin::compact_all))
+        .route(
+            "/v1/admin/namespaces/:ns/cache/invalidate",
+            post(admin::invalidate_namespace_cache),
+        )
+        .route(
+            "/v1/admin/namespaces/:ns/verify",
+            post(admin::verify_namespace_storage),
+        )
+        .route("/v1/query", post(query::query_namespaces))
         .route(
             "/v1/namespaces",
             post(namespace::create_namespace).get(namespace::list_namespaces),
         )
         .route(
             "/v1/namespaces/:ns",
-            get(namespace::get_namespace).delete(namespace::delete_namespace),
+            get(namespace::get_namespace)
+                .head(namespace::head_namespace)
+                .patch(namespace::update_namespace)
+                .delete(namespace::delete_namespace),
         )
         .route(
             "/v1/namespaces/:ns/vectors",
-            post(vectors::upsert_vectors).delete(vectors::delete_vectors),
+            get(vectors::list_vectors)
+                .post(vectors::upsert_vectors)
+                .delete(vectors::delete_vectors),
+        )
+        .route(
+            "/v1/namespaces/:ns/vectors/:id",
+            get(vectors::get_vector)
+                .put(vectors::put_vector)
+                .patch(vectors::patch_vector),
+        )
+        // Verb routes sit beside `vectors`, not under it, so they can't
+        // shadow a vector whose ID is the verb.
+        .route(
+            "/v1/namespaces/:ns/delete_by_filter",
+            post(vectors::delete_by_filter),
+        )
+        .route(
+            "/v1/namespaces/:ns/update_by_filter",
+            post(vectors::update_by_filter),
+        )
+        .route("/v1/namespaces/:ns/fetch", post(vectors::fetch_vectors))
+        .route("/v1/namespaces/:ns/count-by", post(vectors::count_by))
+        .route(
+            "/v1/namespaces/:ns/tombstones",
+            get(vectors::list_tombstones),
         )
         .route("/v1/namespaces/:ns/query", post(query::query_namespace))
+        .route("/v1/namespaces/:ns/query_batch", post(query::query_batch))
+        .route(
+            "/v1/namespaces/:ns/query/explain",
+            post(query::explain_query),
+        )
+        .route(
+            "/v1/namespaces/:ns/index",
+            get(namespace::get_namespace_index),
+        )
+        .route(
+            "/v1/namespaces/:ns/compact",
+            post(namespace::compact_namespace),
+        )
+        .layer(axum::middleware::from_fn_with_state(
+            rate_limiter,
+            limits::rate_limit,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            api_keys,
+            auth::authenticate,
+        ))
         .layer(axum::middleware::from_fn(middleware::http_metrics))
         .layer(TimeoutLayer::new(timeout))
         .layer(DefaultBodyLimit::max(
@@ -40,6 +105,8 @@ pub fn build_router(state: AppState) -> Router {
         .layer(RequestBodyLimitLayer::new(
             state.config.server.max_request_body_mb * 1024 * 1024,
         ))
+        // Inside the request ID span so the panic log carries the ID.
+        .layer(CatchPanicLayer::custom(middleware::panic_response))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
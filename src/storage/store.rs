@@ -1,4 +1,6 @@
 use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::StreamExt;
 use object_store::aws::{AmazonS3Builder, S3ConditionalPut};
 use object_store::path::Path;
 use object_store::{ObjectStore, PutMode, PutOptions, PutPayload, UpdateVersion};
@@ -8,6 +10,10 @@ use tracing::{debug, instrument};
 use crate::config::StorageConfig;
 use crate::error::{Result, ZeppelinError};
 
+/// Number of keys deleted concurrently by `delete_prefix` before pulling the
+/// next page of keys from the listing stream.
+const DELETE_PREFIX_BATCH: usize = 1000;
+
 /// Wrapper around the `object_store` crate providing a unified interface
 /// for S3, GCS, Azure, and local storage backends.
 #[derive(Clone)]
@@ -225,6 +231,85 @@ impl ZeppelinStore {
         Ok(keys)
     }
 
+    /// Names of the top-level "directories" in the store: the first path
+    /// segment of every key, sorted and deduplicated. Uses a delimiter
+    /// listing, so the objects below them are never enumerated.
+    #[instrument(skip(self))]
+    pub async fn list_top_level_dirs(&self) -> Result<Vec<String>> {
+        let start = std::time::Instant::now();
+        let listing = self.inner.list_with_delimiter(None).await?;
+        let mut dirs: Vec<String> = listing
+            .common_prefixes
+            .iter()
+            .filter_map(|p| p.parts().next().map(|part| part.as_ref().to_string()))
+            .collect();
+        dirs.sort_unstable();
+        dirs.dedup();
+        let elapsed = start.elapsed();
+        debug!(
+            elapsed_ms = elapsed.as_millis(),
+            count = dirs.len(),
+            "s3 list_top_level_dirs"
+        );
+        crate::metrics::S3_OPERATION_DURATION
+            .with_label_values(&["list_with_delimiter"])
+            .observe(elapsed.as_secs_f64());
+        Ok(dirs)
+    }
+
+    /// Stream object keys under a prefix as the backend pages through them.
+    ///
+    /// Unlike [`list_prefix`](Self::list_prefix), keys are yielded
+    /// incrementally, so callers walking namespaces with millions of
+    /// objects never hold the full key set in memory.
+    pub fn list_prefix_stream(&self, prefix: &str) -> BoxStream<'_, Result<String>> {
+        let path = match Path::parse(prefix) {
+            Ok(p) => p,
+            Err(e) => return futures::stream::once(async move { Err(e.into()) }).boxed(),
+        };
+        self.inner
+            .list(Some(&path))
+            .map(|res| {
+                res.map(|meta| meta.location.to_string()).map_err(|e| {
+                    crate::metrics::S3_ERRORS_TOTAL
+                        .with_label_values(&["list_prefix"])
+                        .inc();
+                    ZeppelinError::Storage(e)
+                })
+            })
+            .boxed()
+    }
+
+    /// Count the objects under a prefix and sum their sizes in bytes.
+    ///
+    /// Walks the full listing, so the cost grows with the number of objects.
+    #[instrument(skip(self), fields(prefix = prefix))]
+    pub async fn prefix_usage(&self, prefix: &str) -> Result<(usize, u64)> {
+        let start = std::time::Instant::now();
+        let path = Path::parse(prefix)?;
+        let mut stream = self.inner.list(Some(&path));
+        let (mut count, mut bytes) = (0usize, 0u64);
+        while let Some(meta) = stream.next().await {
+            let meta = meta.map_err(|e| {
+                crate::metrics::S3_ERRORS_TOTAL
+                    .with_label_values(&["list_prefix"])
+                    .inc();
+                ZeppelinError::Storage(e)
+            })?;
+            count += 1;
+            bytes += meta.size as u64;
+        }
+        let elapsed = start.elapsed();
+        debug!(
+            elapsed_ms = elapsed.as_millis(),
+            count, bytes, "s3 prefix_usage"
+        );
+        crate::metrics::S3_OPERATION_DURATION
+            .with_label_values(&["list_prefix"])
+            .observe(elapsed.as_secs_f64());
+        Ok((count, bytes))
+    }
+
     /// Check if an object exists.
     #[instrument(skip(self), fields(key = key))]
     pub async fn exists(&self, key: &str) -> Result<bool> {
@@ -273,23 +358,30 @@ impl ZeppelinStore {
     }
 
     /// Delete all objects under a prefix (for cleanup).
+    ///
+    /// Keys are consumed from [`list_prefix_stream`](Self::list_prefix_stream)
+    /// in batches, so memory stays bounded regardless of prefix size.
     #[instrument(skip(self), fields(prefix = prefix))]
     pub async fn delete_prefix(&self, prefix: &str) -> Result<usize> {
         let start = std::time::Instant::now();
-        let keys = self.list_prefix(prefix).await?;
-        let count = keys.len();
         let inner = &self.inner;
-        let delete_futs: Vec<_> = keys
-            .iter()
-            .map(|key| async move {
-                let path = Path::parse(key)?;
-                inner.delete(&path).await?;
-                Ok::<_, ZeppelinError>(())
-            })
-            .collect();
-        let results = futures::future::join_all(delete_futs).await;
-        for result in results {
-            result?;
+        let mut count = 0;
+        let mut batches = self.list_prefix_stream(prefix).chunks(DELETE_PREFIX_BATCH);
+        while let Some(batch) = batches.next().await {
+            let keys = batch.into_iter().collect::<Result<Vec<String>>>()?;
+            let delete_futs: Vec<_> = keys
+                .iter()
+                .map(|key| async move {
+                    let path = Path::parse(key)?;
+                    inner.delete(&path).await?;
+                    Ok::<_, ZeppelinError>(())
+                })
+                .collect();
+            let results = futures::future::join_all(delete_futs).await;
+            for result in results {
+                result?;
+            }
+            count += keys.len();
         }
         let elapsed = start.elapsed();
         debug!(elapsed_ms = elapsed.as_millis(), count, "s3 delete_prefix");
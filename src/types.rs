@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// A unique identifier for a vector within a namespace.
 pub type VectorId = String;
@@ -23,6 +23,52 @@ impl std::fmt::Display for DistanceMetric {
     }
 }
 
+impl DistanceMetric {
+    /// Whether a higher result `score` is closer. Cosine and Euclidean
+    /// scores are distances; a dot-product score is the dot product itself.
+    pub fn higher_is_better(self) -> bool {
+        self == DistanceMetric::DotProduct
+    }
+
+    /// Whether result score `a` is strictly closer than `b`.
+    pub fn better_than(self, a: f32, b: f32) -> bool {
+        self.compare_scores(a, b) == std::cmp::Ordering::Less
+    }
+
+    /// Orders result scores best first.
+    pub fn compare_scores(self, a: f32, b: f32) -> std::cmp::Ordering {
+        if self.higher_is_better() {
+            b.total_cmp(&a)
+        } else {
+            a.total_cmp(&b)
+        }
+    }
+
+    /// The result score for an index distance. Index internals (k-means,
+    /// candidate ranking) always work with a lower-is-closer distance, which
+    /// for dot product is `-dot`; results report `dot`.
+    pub fn score_from_distance(self, distance: f32) -> f32 {
+        if self.higher_is_better() {
+            -distance
+        } else {
+            distance
+        }
+    }
+}
+
+/// A named vector field declared on a namespace. Each field has its own
+/// dimensions and metric, independent of the namespace's primary vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamedVectorConfig {
+    pub dimensions: usize,
+    #[serde(default = "default_named_vector_metric", alias = "distanceMetric")]
+    pub distance_metric: DistanceMetric,
+}
+
+fn default_named_vector_metric() -> DistanceMetric {
+    DistanceMetric::Cosine
+}
+
 /// Attribute values that can be attached to vectors for filtering.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -34,24 +80,146 @@ pub enum AttributeValue {
     StringList(Vec<String>),
     IntegerList(Vec<i64>),
     FloatList(Vec<f64>),
+    /// Nested attributes. Filters reach into objects with dot paths, e.g.
+    /// `"metadata.author"`. Not bitmap-indexed.
+    Object(HashMap<String, AttributeValue>),
+}
+
+/// A sparse vector as parallel `indices` and `values` arrays, e.g. SPLADE
+/// term weights, with indices in increasing order. Dimensions not listed
+/// are zero.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SparseVector {
+    pub indices: Vec<u32>,
+    pub values: Vec<f32>,
+}
+
+impl SparseVector {
+    /// Check that `indices` and `values` line up, indices are strictly
+    /// increasing and values are finite.
+    pub fn validate(&self) -> crate::error::Result<()> {
+        let invalid = |msg: String| Err(crate::error::ZeppelinError::Validation(msg));
+        if self.indices.len() != self.values.len() {
+            return invalid(format!(
+                "sparse vector has {} indices but {} values",
+                self.indices.len(),
+                self.values.len()
+            ));
+        }
+        if self.indices.windows(2).any(|w| w[0] >= w[1]) {
+            return invalid("sparse vector indices must be strictly increasing".into());
+        }
+        if self.values.iter().any(|v| !v.is_finite()) {
+            return invalid("sparse vector values must be finite".into());
+        }
+        Ok(())
+    }
+
+    /// Dot product with `other`.
+    pub fn dot(&self, other: &SparseVector) -> f32 {
+        let weights: HashMap<u32, f32> = other
+            .indices
+            .iter()
+            .copied()
+            .zip(other.values.iter().copied())
+            .collect();
+        self.indices
+            .iter()
+            .zip(&self.values)
+            .filter_map(|(i, v)| weights.get(i).map(|w| v * w))
+            .sum()
+    }
 }
 
 /// A vector entry with its ID, values, and optional attributes.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VectorEntry {
     pub id: VectorId,
+    /// Omitted for documents in a text-only namespace.
+    #[serde(default)]
     pub values: Vec<f32>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub attributes: Option<HashMap<String, AttributeValue>>,
+    /// Sparse representation alongside `values`, searched with a sparse
+    /// `rank_by`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sparse: Option<SparseVector>,
+    /// Embeddings for the namespace's named vector fields, searched with a
+    /// query's `vector_field`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub named_vectors: Option<BTreeMap<String, Vec<f32>>>,
+    /// A bag of token vectors for late-interaction (MaxSim) scoring, e.g.
+    /// ColBERT document embeddings, of the namespace's `token_dimensions`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_vectors: Option<Vec<Vec<f32>>>,
+    /// Version token for conditional upserts, naming the write that stored
+    /// this vector: its WAL fragment's ULID and its position there, kept
+    /// through compaction. Rewriting identical content still gets a new
+    /// version. Set on vectors read back, never taken from a request.
+    #[serde(skip)]
+    pub version: Option<String>,
+}
+
+impl VectorEntry {
+    /// Check invariants that deserialization alone can't enforce. An empty
+    /// `values` array would otherwise surface as a confusing dimension
+    /// mismatch, or as degenerate distances if it ever reached an index.
+    pub fn validate(&self) -> crate::error::Result<()> {
+        if self.values.is_empty() {
+            return Err(crate::error::ZeppelinError::Validation(format!(
+                "vector '{}' has empty values",
+                self.id
+            )));
+        }
+        self.validate_sparse()
+    }
+
+    /// The sparse half of [`VectorEntry::validate`], which also applies to
+    /// documents in text-only namespaces.
+    pub fn validate_sparse(&self) -> crate::error::Result<()> {
+        match &self.sparse {
+            Some(sparse) => sparse.validate().map_err(|e| match e {
+                crate::error::ZeppelinError::Validation(msg) => {
+                    crate::error::ZeppelinError::Validation(format!("vector '{}': {msg}", self.id))
+                }
+                other => other,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Precondition on an upsert, checked against the latest version of each
+/// vector (WAL and segments) atomically with the write.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpsertCondition {
+    /// Fail if any of the upserted IDs already exists.
+    IfNotExists,
+    /// Fail unless every upserted ID exists with the given version; see
+    /// [`VectorEntry::version`].
+    IfVersionMatches(HashMap<VectorId, String>),
 }
 
 /// A search result containing the vector ID, distance/score, and optional attributes.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
     pub id: VectorId,
+    /// Cosine or Euclidean distance (lower is closer), or the dot product
+    /// (higher is closer); [`DistanceMetric::compare_scores`] orders them.
+    /// Quantized and spherically trained indexes rerank with full-precision
+    /// vectors, so this is always exact, never an internal approximation.
     pub score: f32,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub attributes: Option<HashMap<String, AttributeValue>>,
+    /// 0-based position in the final result list, set only when the query
+    /// asks for `include_rank`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<usize>,
+    /// The stored vector, set only when the query asks for
+    /// `include_vectors`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub values: Option<Vec<f32>>,
 }
 
 /// Filter conditions for post-filtering search results.
@@ -67,6 +235,16 @@ pub enum Filter {
         field: String,
         value: AttributeValue,
     },
+    /// Numeric equality within `tolerance`, for float attributes whose
+    /// stored value rarely matches a literal exactly (`19.99` vs
+    /// `19.990000001`). `eq` stays exact.
+    #[serde(rename = "approx_eq")]
+    ApproxEq {
+        field: String,
+        value: f64,
+        #[serde(default = "default_approx_tolerance")]
+        tolerance: f64,
+    },
     Range {
         field: String,
         #[serde(skip_serializing_if = "Option::is_none")]
@@ -100,6 +278,25 @@ pub enum Filter {
         field: String,
         value: AttributeValue,
     },
+    /// A list attribute holds at least one of `values` (for a string
+    /// attribute: contains at least one of them as a substring).
+    ContainsAny {
+        field: String,
+        values: Vec<AttributeValue>,
+    },
+    /// A string attribute (or any element of a string list) begins with
+    /// `prefix`.
+    StartsWith {
+        field: String,
+        prefix: String,
+    },
+    /// A string attribute (or any element of a string list) matches a
+    /// shell-style pattern: `*` matches any run of characters, `?` exactly
+    /// one. The pattern must match the whole value.
+    Glob {
+        field: String,
+        pattern: String,
+    },
     /// All specified tokens must be present in the field (order-independent).
     ContainsAllTokens {
         field: String,
@@ -110,6 +307,70 @@ pub enum Filter {
         field: String,
         tokens: Vec<String>,
     },
+    /// The document has the attribute, whatever its value.
+    Exists {
+        field: String,
+    },
+    /// The document doesn't have the attribute. Documents stored without
+    /// attributes match.
+    #[serde(rename = "not_exists")]
+    NotExists {
+        field: String,
+    },
+}
+
+impl Filter {
+    /// Absolute tolerance used by `approx_eq` when the request omits one.
+    pub const DEFAULT_APPROX_TOLERANCE: f64 = 1e-6;
+
+    /// Every accepted `op` tag.
+    pub const OPS: &'static [&'static str] = &[
+        "eq",
+        "not_eq",
+        "approx_eq",
+        "range",
+        "in",
+        "not_in",
+        "and",
+        "or",
+        "not",
+        "contains",
+        "contains_any",
+        "starts_with",
+        "glob",
+        "contains_all_tokens",
+        "contains_token_sequence",
+        "exists",
+        "not_exists",
+    ];
+
+    /// Check every `op` in a raw JSON filter (including nested `and`/`or`/
+    /// `not` filters) before deserializing it, so an unknown operator is
+    /// reported by name instead of as a generic serde error.
+    pub fn validate_ops(value: &serde_json::Value) -> crate::error::Result<()> {
+        let Some(obj) = value.as_object() else {
+            return Ok(());
+        };
+        if let Some(op) = obj.get("op").and_then(|op| op.as_str()) {
+            if !Self::OPS.contains(&op) {
+                return Err(crate::error::ZeppelinError::Validation(format!(
+                    "unknown filter op '{op}'; supported ops: {}",
+                    Self::OPS.join(", ")
+                )));
+            }
+        }
+        if let Some(filters) = obj.get("filters").and_then(|f| f.as_array()) {
+            filters.iter().try_for_each(Self::validate_ops)?;
+        }
+        if let Some(inner) = obj.get("filter") {
+            Self::validate_ops(inner)?;
+        }
+        Ok(())
+    }
+}
+
+fn default_approx_tolerance() -> f64 {
+    Filter::DEFAULT_APPROX_TOLERANCE
 }
 
 /// Consistency level for queries.
@@ -121,6 +382,10 @@ pub enum ConsistencyLevel {
     Strong,
     /// Read from index only (faster, may miss recent writes)
     Eventual,
+    /// Read from index only, but drop results deleted by uncompacted WAL
+    /// fragments. Only fragments that carry deletes are fetched and no WAL
+    /// vectors are scored, so recent upserts are not returned.
+    EventualWithDeletes,
 }
 
 /// Index type for a namespace.
@@ -135,6 +400,8 @@ pub enum IndexType {
     IvfPq,
     /// Hierarchical ANN index (multi-level centroid tree).
     Hierarchical,
+    /// Vamana proximity graph stored in fixed-size S3 blocks.
+    Vamana,
 }
 
 #[cfg(test)]
@@ -155,6 +422,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_filter_ops_match_serde_tags() {
+        for op in Filter::OPS {
+            let err = serde_json::from_value::<Filter>(serde_json::json!({ "op": op }))
+                .unwrap_err()
+                .to_string();
+            assert!(!err.contains("unknown variant"), "{op}: {err}");
+        }
+        let nested = serde_json::json!({
+            "op": "and",
+            "filters": [{ "op": "not", "filter": { "op": "gte", "field": "x" } }]
+        });
+        let err = Filter::validate_ops(&nested).unwrap_err().to_string();
+        assert!(err.contains("'gte'"), "{err}");
+    }
+
     #[test]
     fn test_distance_metric_display() {
         assert_eq!(DistanceMetric::Cosine.to_string(), "cosine");
@@ -191,6 +474,10 @@ mod tests {
             id: "vec-1".into(),
             values: vec![1.0, 2.0, 3.0],
             attributes: Some(attrs),
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         };
         let json = serde_json::to_string(&entry).unwrap();
         let back: VectorEntry = serde_json::from_str(&json).unwrap();
@@ -211,6 +498,10 @@ mod tests {
             id: "vec-2".into(),
             values: vec![0.5],
             attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         };
         let json = serde_json::to_string(&entry).unwrap();
         assert!(!json.contains("attributes"));
@@ -311,6 +602,8 @@ mod tests {
             id: "vec-1".into(),
             score: 0.95,
             attributes: Some(attrs),
+            rank: None,
+            values: None,
         };
         let json = serde_json::to_string(&result).unwrap();
         let back: SearchResult = serde_json::from_str(&json).unwrap();
@@ -323,6 +616,8 @@ mod tests {
             id: "vec-2".into(),
             score: 0.5,
             attributes: None,
+            rank: None,
+            values: None,
         };
         let json = serde_json::to_string(&result).unwrap();
         assert!(!json.contains("attributes"));
@@ -435,6 +730,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_filter_contains_any_serde() {
+        let json = r#"{"op":"contains_any","field":"tags","values":["rust","go"]}"#;
+        let f: Filter = serde_json::from_str(json).unwrap();
+        match f {
+            Filter::ContainsAny { field, values } => {
+                assert_eq!(field, "tags");
+                assert_eq!(
+                    values,
+                    vec![
+                        AttributeValue::String("rust".into()),
+                        AttributeValue::String("go".into())
+                    ]
+                );
+            }
+            _ => panic!("expected ContainsAny"),
+        }
+    }
+
     // --- New attribute type serde tests ---
 
     #[test]
@@ -462,6 +776,7 @@ mod tests {
             (IndexType::IvfSq, "\"ivf_sq\""),
             (IndexType::IvfPq, "\"ivf_pq\""),
             (IndexType::Hierarchical, "\"hierarchical\""),
+            (IndexType::Vamana, "\"vamana\""),
         ] {
             let json = serde_json::to_string(&variant).unwrap();
             assert_eq!(json, expected);
@@ -469,4 +784,73 @@ mod tests {
             assert_eq!(back, variant);
         }
     }
+
+    #[test]
+    fn test_vector_entry_validate_rejects_empty_values() {
+        let mut entry = VectorEntry {
+            id: "v1".to_string(),
+            values: vec![],
+            attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
+        };
+        let err = entry.validate().unwrap_err();
+        assert_eq!(err.status_code(), 400);
+        assert!(err.to_string().contains("'v1' has empty values"), "{err}");
+
+        entry.values = vec![0.0];
+        assert!(entry.validate().is_ok());
+    }
+
+    #[test]
+    fn test_sparse_vector_validate() {
+        let sparse = |indices: Vec<u32>, values: Vec<f32>| SparseVector { indices, values };
+        assert!(sparse(vec![1, 4, 9], vec![0.5, 1.0, 2.0])
+            .validate()
+            .is_ok());
+        assert!(sparse(vec![], vec![]).validate().is_ok());
+        for (bad, msg) in [
+            (sparse(vec![1, 2], vec![1.0]), "2 indices but 1 values"),
+            (sparse(vec![4, 1], vec![1.0, 1.0]), "strictly increasing"),
+            (sparse(vec![1, 1], vec![1.0, 1.0]), "strictly increasing"),
+            (sparse(vec![1], vec![f32::NAN]), "finite"),
+        ] {
+            let err = bad.validate().unwrap_err();
+            assert!(err.to_string().contains(msg), "{err}");
+        }
+
+        let entry = VectorEntry {
+            id: "v1".to_string(),
+            values: vec![1.0],
+            attributes: None,
+            sparse: Some(sparse(vec![2, 1], vec![1.0, 1.0])),
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
+        };
+        let err = entry.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "validation error: vector 'v1': sparse vector indices must be strictly increasing"
+        );
+        assert_eq!(
+            sparse(vec![1, 3, 7], vec![1.0, 2.0, 3.0])
+                .dot(&sparse(vec![3, 7, 8], vec![0.5, 1.0, 9.0])),
+            4.0
+        );
+    }
+
+    #[test]
+    fn test_upsert_condition_serde() {
+        let c: UpsertCondition = serde_json::from_str(r#""if_not_exists""#).unwrap();
+        assert_eq!(c, UpsertCondition::IfNotExists);
+        let c: UpsertCondition =
+            serde_json::from_str(r#"{"if_version_matches": {"v1": "00ff"}}"#).unwrap();
+        assert_eq!(
+            c,
+            UpsertCondition::IfVersionMatches(HashMap::from([("v1".into(), "00ff".into())]))
+        );
+    }
 }
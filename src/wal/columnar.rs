@@ -0,0 +1,203 @@
+//! Column-oriented binary encoding for WAL fragments.
+//!
+//! Layout (all integers little-endian):
+//!
+//! ```text
+//! magic "ZWC1" | id: u128 | checksum: u64 | n: u32 | dim: u32
+//! ids:        n × (len: u32, utf8 bytes)
+//! values:     n × dim × f32  (row-major matrix)
+//! has_attrs:  n × u8         (0 = no attribute map, 1 = map present)
+//! attrs:      len: u32, JSON { field: [value | null; n] }
+//! deletes:    count: u32, count × (len: u32, utf8 bytes)
+//! ```
+//!
+//! Vector values are stored as raw floats rather than JSON decimals, which
+//! makes large fragments both smaller and much cheaper to decode. Attribute
+//! columns stay JSON because `AttributeValue` is `#[serde(untagged)]`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use bytes::Bytes;
+use ulid::Ulid;
+
+use crate::error::{Result, ZeppelinError};
+use crate::types::{AttributeValue, VectorEntry};
+
+use super::fragment::WalFragment;
+
+/// Leading bytes identifying a columnar fragment. Row fragments are JSON
+/// objects and always start with `{`.
+pub(crate) const MAGIC: &[u8; 4] = b"ZWC1";
+
+/// Encode a fragment in columnar layout.
+///
+/// Returns `None` if the fragment's vectors do not all share one dimension,
+/// or it carries patches, sparse, named or token vectors or an idempotency
+/// key, which only the row layout stores. The caller should then fall back to the
+/// row layout.
+pub(crate) fn encode(fragment: &WalFragment) -> Result<Option<Bytes>> {
+    let n = fragment.vectors.len();
+    let dim = fragment.vectors.first().map_or(0, |v| v.values.len());
+    if !fragment.patches.is_empty()
+        || !fragment.filter_patches.is_empty()
+        || fragment.idempotency_key.is_some()
+        || fragment.vectors.iter().any(|v| {
+            v.values.len() != dim
+                || v.sparse.is_some()
+                || v.named_vectors.is_some()
+                || v.token_vectors.is_some()
+        })
+    {
+        return Ok(None);
+    }
+
+    let mut columns: BTreeMap<&str, Vec<Option<&AttributeValue>>> = BTreeMap::new();
+    for (row, vector) in fragment.vectors.iter().enumerate() {
+        for (field, value) in vector.attributes.iter().flatten() {
+            columns
+                .entry(field.as_str())
+                .or_insert_with(|| vec![None; n])[row] = Some(value);
+        }
+    }
+    let attrs_json = serde_json::to_vec(&columns)?;
+
+    let mut buf = Vec::with_capacity(40 + n * (dim * 4 + 16) + attrs_json.len());
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&u128::from(fragment.id).to_le_bytes());
+    buf.extend_from_slice(&fragment.checksum.to_le_bytes());
+    buf.extend_from_slice(&(n as u32).to_le_bytes());
+    buf.extend_from_slice(&(dim as u32).to_le_bytes());
+    for vector in &fragment.vectors {
+        put_str(&mut buf, &vector.id);
+    }
+    for vector in &fragment.vectors {
+        for x in &vector.values {
+            buf.extend_from_slice(&x.to_le_bytes());
+        }
+    }
+    buf.extend(
+        fragment
+            .vectors
+            .iter()
+            .map(|v| v.attributes.is_some() as u8),
+    );
+    buf.extend_from_slice(&(attrs_json.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&attrs_json);
+    buf.extend_from_slice(&(fragment.deletes.len() as u32).to_le_bytes());
+    for id in &fragment.deletes {
+        put_str(&mut buf, id);
+    }
+    Ok(Some(Bytes::from(buf)))
+}
+
+/// Decode a columnar fragment. Does not validate the checksum.
+pub(crate) fn decode(data: &[u8]) -> Result<WalFragment> {
+    let mut r = Reader { data, pos: 0 };
+    if r.take(4)? != MAGIC {
+        return Err(malformed("bad magic"));
+    }
+    let id = Ulid::from(u128::from_le_bytes(r.array()?));
+    let checksum = u64::from_le_bytes(r.array()?);
+    let n = r.u32()? as usize;
+    let dim = r.u32()? as usize;
+
+    let mut ids = Vec::with_capacity(n.min(data.len()));
+    for _ in 0..n {
+        ids.push(r.string()?);
+    }
+    let matrix_len = n
+        .checked_mul(dim)
+        .and_then(|x| x.checked_mul(4))
+        .ok_or_else(|| malformed("matrix size overflow"))?;
+    let matrix = r.take(matrix_len)?;
+    let has_attrs = r.take(n)?;
+    let attrs_len = r.u32()? as usize;
+    let mut columns: BTreeMap<String, Vec<Option<AttributeValue>>> =
+        serde_json::from_slice(r.take(attrs_len)?)?;
+    if columns.values().any(|c| c.len() != n) {
+        return Err(malformed("attribute column length mismatch"));
+    }
+
+    let mut vectors = Vec::with_capacity(n);
+    for (row, id) in ids.into_iter().enumerate() {
+        let values = matrix[row * dim * 4..(row + 1) * dim * 4]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        let attributes = (has_attrs[row] != 0).then(|| {
+            columns
+                .iter_mut()
+                .filter_map(|(field, col)| col[row].take().map(|v| (field.clone(), v)))
+                .collect::<HashMap<_, _>>()
+        });
+        vectors.push(VectorEntry {
+            id,
+            values,
+            attributes,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
+        });
+    }
+
+    let delete_count = r.u32()? as usize;
+    let mut deletes = Vec::with_capacity(delete_count.min(data.len()));
+    for _ in 0..delete_count {
+        deletes.push(r.string()?);
+    }
+    if r.pos != data.len() {
+        return Err(malformed("trailing bytes"));
+    }
+
+    Ok(WalFragment {
+        id,
+        vectors,
+        deletes,
+        patches: Vec::new(),
+        filter_patches: Vec::new(),
+        idempotency_key: None,
+        checksum,
+    })
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn malformed(reason: &str) -> ZeppelinError {
+    ZeppelinError::Bincode(format!("malformed columnar WAL fragment: {reason}"))
+}
+
+/// Bounds-checked cursor over the encoded bytes.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| malformed("truncated"))?;
+        let out = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("take returns N bytes"))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| malformed("invalid utf-8"))
+    }
+}
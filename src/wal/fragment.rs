@@ -4,10 +4,41 @@ use ulid::Ulid;
 use xxhash_rust::xxh3::xxh3_64;
 
 use crate::error::{Result, ZeppelinError};
-use crate::types::{VectorEntry, VectorId};
+use crate::types::{AttributeValue, Filter, SparseVector, VectorEntry, VectorId};
+use std::collections::HashMap;
 
-/// A single WAL fragment containing upserted vectors and/or deletes.
-/// Fragments are immutable once written to S3.
+/// On-disk encoding of WAL fragments. Readers detect the layout from the
+/// fragment bytes, so namespaces may mix both.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WalLayout {
+    /// One JSON object per fragment with a row per vector.
+    #[default]
+    Row,
+    /// Binary ids column, values matrix and per-attribute columns.
+    Columnar,
+}
+
+/// Attribute-only update for an existing vector. The given attributes are
+/// merged over the vector's current ones; its values are left untouched.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttributePatch {
+    pub id: VectorId,
+    pub attributes: HashMap<String, AttributeValue>,
+}
+
+/// Attribute update for every vector that matches `filter` when the patch
+/// applies, merged like an [`AttributePatch`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterPatch {
+    pub filter: Filter,
+    pub attributes: HashMap<String, AttributeValue>,
+}
+
+/// A single WAL fragment containing upserted vectors, deletes and/or
+/// attribute patches. Within a fragment deletes apply first, then upserts,
+/// then ID patches, then filter patches. Fragments are immutable once
+/// written to S3.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalFragment {
     /// Unique, time-ordered identifier for this fragment.
@@ -16,7 +47,18 @@ pub struct WalFragment {
     pub vectors: Vec<VectorEntry>,
     /// Vector IDs to delete.
     pub deletes: Vec<VectorId>,
-    /// xxHash checksum of the serialized payload (vectors + deletes).
+    /// Attribute patches. A patch on an ID with no live vector is a no-op.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patches: Vec<AttributePatch>,
+    /// Attribute patches by filter.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filter_patches: Vec<FilterPatch>,
+    /// Client-supplied key identifying the request that wrote this fragment;
+    /// see [`WalWriter::append_idempotent`](super::WalWriter::append_idempotent).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    /// xxHash checksum of the serialized payload (vectors + deletes + patches
+    /// + sparse, named and token vectors + idempotency key).
     pub checksum: u64,
 }
 
@@ -33,6 +75,17 @@ impl WalFragment {
     pub fn try_new(
         vectors: Vec<VectorEntry>,
         deletes: Vec<VectorId>,
+    ) -> std::result::Result<Self, ZeppelinError> {
+        Self::try_new_with_patches(vectors, deletes, Vec::new(), Vec::new())
+    }
+
+    /// Like [`WalFragment::try_new`], additionally carrying attribute patches
+    /// by ID and by filter.
+    pub fn try_new_with_patches(
+        vectors: Vec<VectorEntry>,
+        deletes: Vec<VectorId>,
+        patches: Vec<AttributePatch>,
+        filter_patches: Vec<FilterPatch>,
     ) -> std::result::Result<Self, ZeppelinError> {
         use std::collections::HashSet;
 
@@ -46,17 +99,27 @@ impl WalFragment {
             }
         }
 
-        let id = Ulid::new();
-        let checksum = Self::compute_checksum(&vectors, &deletes);
-        Ok(Self {
-            id,
+        let mut fragment = Self {
+            id: Ulid::new(),
             vectors,
             deletes,
-            checksum,
-        })
+            patches,
+            filter_patches,
+            idempotency_key: None,
+            checksum: 0,
+        };
+        fragment.checksum = fragment.compute_checksum();
+        Ok(fragment)
     }
 
-    /// Compute the checksum for a set of vectors and deletes.
+    /// Tag this fragment with the idempotency key of the request writing it.
+    pub fn with_idempotency_key(mut self, key: String) -> Self {
+        self.idempotency_key = Some(key);
+        self.checksum = self.compute_checksum();
+        self
+    }
+
+    /// Compute the checksum of this fragment's payload.
     ///
     /// Uses JSON serialization because `AttributeValue` uses `#[serde(untagged)]`
     /// which is incompatible with bincode's non-self-describing format.
@@ -64,29 +127,31 @@ impl WalFragment {
     /// Attributes are canonicalized via BTreeMap to ensure deterministic key
     /// ordering across serialization round-trips (HashMap iteration order is
     /// not guaranteed to be stable after deserialize → re-serialize).
-    fn compute_checksum(vectors: &[VectorEntry], deletes: &[VectorId]) -> u64 {
-        use crate::types::AttributeValue;
-        use std::collections::BTreeMap;
+    ///
+    /// Patches, sparse, named and token vectors and the idempotency key only
+    /// enter the payload when present, so checksums of fragments without
+    /// them are unchanged. Filters go
+    /// through `serde_json::Value`, whose maps are key-sorted.
+    fn compute_checksum(&self) -> u64 {
+        payload_checksum(
+            &self.vectors,
+            &self.deletes,
+            &self.patches,
+            &self.filter_patches,
+            self.idempotency_key.as_deref(),
+        )
+    }
 
-        #[allow(clippy::type_complexity)]
-        let canonical: Vec<(&str, &[f32], Option<BTreeMap<&String, &AttributeValue>>)> = vectors
-            .iter()
-            .map(|v| {
-                let attrs = v
-                    .attributes
-                    .as_ref()
-                    .map(|a| a.iter().collect::<BTreeMap<_, _>>());
-                (v.id.as_str(), v.values.as_slice(), attrs)
-            })
-            .collect();
-        let payload =
-            serde_json::to_vec(&(&canonical, deletes)).expect("serialization should not fail");
-        xxh3_64(&payload)
+    /// Hash of a write request's upserts and deletes, as they were sent.
+    /// Stored with an idempotency key so a reuse of the key for a different
+    /// payload can be told apart from a retry.
+    pub fn payload_hash(vectors: &[VectorEntry], deletes: &[VectorId]) -> u64 {
+        payload_checksum(vectors, deletes, &[], &[], None)
     }
 
     /// Validate the checksum of this fragment.
     pub fn validate_checksum(&self) -> Result<()> {
-        let expected = Self::compute_checksum(&self.vectors, &self.deletes);
+        let expected = self.compute_checksum();
         if self.checksum != expected {
             return Err(ZeppelinError::ChecksumMismatch {
                 expected,
@@ -102,13 +167,39 @@ impl WalFragment {
         Ok(Bytes::from(data))
     }
 
-    /// Deserialize a fragment from JSON bytes.
+    /// Serialize this fragment in the given layout. Columnar falls back to
+    /// row (JSON) when vectors have differing dimensions or the fragment
+    /// carries patches, sparse, named or token vectors or an idempotency key.
+    pub fn to_bytes_with_layout(&self, layout: WalLayout) -> Result<Bytes> {
+        match layout {
+            WalLayout::Row => self.to_bytes(),
+            WalLayout::Columnar => match super::columnar::encode(self)? {
+                Some(data) => Ok(data),
+                None => self.to_bytes(),
+            },
+        }
+    }
+
+    /// Deserialize a fragment from row (JSON) or columnar bytes.
     pub fn from_bytes(data: &[u8]) -> Result<Self> {
-        let fragment: Self = serde_json::from_slice(data)?;
+        let fragment: Self = if data.starts_with(super::columnar::MAGIC) {
+            super::columnar::decode(data)?
+        } else {
+            serde_json::from_slice(data)?
+        };
         fragment.validate_checksum()?;
         Ok(fragment)
     }
 
+    /// Set the [`version`](VectorEntry::version) of each vector to this
+    /// write's: the fragment's ULID and the vector's position in it. Run
+    /// after patches are resolved, so patched vectors get the patch's.
+    pub fn stamp_versions(&mut self) {
+        for (position, vector) in self.vectors.iter_mut().enumerate() {
+            vector.version = Some(format!("{}-{position}", self.id));
+        }
+    }
+
     /// Get the S3 key for this fragment within a namespace.
     pub fn s3_key(namespace: &str, id: &Ulid) -> String {
         format!("{namespace}/wal/{id}.wal")
@@ -116,6 +207,86 @@ impl WalFragment {
 
     /// Total number of vector operations in this fragment.
     pub fn operation_count(&self) -> usize {
-        self.vectors.len() + self.deletes.len()
+        self.vectors.len() + self.deletes.len() + self.patches.len() + self.filter_patches.len()
+    }
+}
+
+/// See [`WalFragment::compute_checksum`].
+fn payload_checksum(
+    vectors: &[VectorEntry],
+    deletes: &[VectorId],
+    patches: &[AttributePatch],
+    filter_patches: &[FilterPatch],
+    idempotency_key: Option<&str>,
+) -> u64 {
+    use std::collections::BTreeMap;
+
+    #[allow(clippy::type_complexity)]
+    let canonical: Vec<(&str, &[f32], Option<BTreeMap<&String, &AttributeValue>>)> = vectors
+        .iter()
+        .map(|v| {
+            let attrs = v
+                .attributes
+                .as_ref()
+                .map(|a| a.iter().collect::<BTreeMap<_, _>>());
+            (v.id.as_str(), v.values.as_slice(), attrs)
+        })
+        .collect();
+    let mut payload = if patches.is_empty() && filter_patches.is_empty() {
+        serde_json::to_vec(&(&canonical, deletes))
+    } else {
+        let patches: Vec<(&str, BTreeMap<&String, &AttributeValue>)> = patches
+            .iter()
+            .map(|p| (p.id.as_str(), p.attributes.iter().collect()))
+            .collect();
+        if filter_patches.is_empty() {
+            serde_json::to_vec(&(&canonical, deletes, &patches))
+        } else {
+            #[allow(clippy::type_complexity)]
+            let filter_patches: Vec<(
+                serde_json::Value,
+                BTreeMap<&String, &AttributeValue>,
+            )> = filter_patches
+                .iter()
+                .map(|p| {
+                    let filter =
+                        serde_json::to_value(&p.filter).expect("serialization should not fail");
+                    (filter, p.attributes.iter().collect())
+                })
+                .collect();
+            serde_json::to_vec(&(&canonical, deletes, &patches, &filter_patches))
+        }
+    }
+    .expect("serialization should not fail");
+    let sparse: Vec<(&str, &SparseVector)> = vectors
+        .iter()
+        .filter_map(|v| v.sparse.as_ref().map(|s| (v.id.as_str(), s)))
+        .collect();
+    if !sparse.is_empty() {
+        payload.push(0);
+        payload.extend(serde_json::to_vec(&sparse).expect("serialization should not fail"));
+    }
+    #[allow(clippy::type_complexity)]
+    let named: Vec<(&str, &BTreeMap<String, Vec<f32>>)> = vectors
+        .iter()
+        .filter_map(|v| v.named_vectors.as_ref().map(|n| (v.id.as_str(), n)))
+        .collect();
+    if !named.is_empty() {
+        payload.push(0);
+        payload.extend(serde_json::to_vec(&named).expect("serialization should not fail"));
+    }
+    #[allow(clippy::type_complexity)]
+    let tokens: Vec<(&str, &Vec<Vec<f32>>)> = vectors
+        .iter()
+        .filter_map(|v| v.token_vectors.as_ref().map(|t| (v.id.as_str(), t)))
+        .collect();
+    if !tokens.is_empty() {
+        payload.push(0);
+        payload.extend(serde_json::to_vec(&tokens).expect("serialization should not fail"));
+    }
+    if let Some(key) = idempotency_key {
+        payload.push(0);
+        payload.extend_from_slice(key.as_bytes());
     }
+    xxh3_64(&payload)
 }
@@ -1,11 +1,19 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
 use ulid::Ulid;
 
 use crate::error::Result;
 use crate::storage::ZeppelinStore;
 
+/// Delay before the first re-read in [`Manifest::read_versioned_at_least`];
+/// doubles on each further retry.
+pub const READ_RETRY_BACKOFF: Duration = Duration::from_millis(50);
+
 /// A reference to a WAL fragment stored on S3.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct FragmentRef {
@@ -16,6 +24,13 @@ pub struct FragmentRef {
     /// Immune to clock skew — determines merge order instead of ULID.
     #[serde(default)]
     pub sequence_number: u64,
+    /// Idempotency key of the request that wrote the fragment, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    /// [`WalFragment::payload_hash`](super::WalFragment::payload_hash) of
+    /// that request, to reject reuse of its key for a different payload.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload_hash: Option<u64>,
 }
 
 /// A reference to an IVF segment stored on S3.
@@ -30,6 +45,10 @@ pub struct SegmentRef {
     /// Whether this segment uses a hierarchical index.
     #[serde(default)]
     pub hierarchical: bool,
+    /// Whether this segment is a Vamana graph; `cluster_count` is then its
+    /// number of graph blocks.
+    #[serde(default)]
+    pub vamana: bool,
     /// Fields that have bitmap indexes in this segment.
     /// Empty if bitmap indexing was not enabled when the segment was built.
     #[serde(default)]
@@ -37,6 +56,38 @@ pub struct SegmentRef {
     /// Fields that have FTS inverted indexes in this segment.
     #[serde(default)]
     pub fts_fields: Vec<String>,
+    /// Metric the segment's k-means centroids were trained under. `None`
+    /// for segments not clustered by k-means over vectors, and for
+    /// segments written before this was recorded (those used Euclidean).
+    #[serde(default)]
+    pub kmeans_metric: Option<crate::types::DistanceMetric>,
+    /// Segment of a text-only namespace: `cluster_count` partitions of
+    /// document IDs, attributes and FTS indexes, with no centroids or
+    /// vector data.
+    #[serde(default)]
+    pub text_only: bool,
+    /// Whether the segment has a sparse index; see
+    /// [`SparseIndex`](crate::index::sparse::SparseIndex).
+    #[serde(default)]
+    pub sparse: bool,
+    /// Whether the segment has a named vector sidecar; see
+    /// [`NamedVectorIndex`](crate::index::named::NamedVectorIndex).
+    #[serde(default)]
+    pub named_vectors: bool,
+    /// Whether the segment packs token vectors, one
+    /// [`TokenMatrix`](crate::index::late_interaction::TokenMatrix) per
+    /// partition.
+    #[serde(default)]
+    pub token_vectors: bool,
+    /// Whether the segment has a version sidecar; see
+    /// [`VersionIndex`](crate::index::versions::VersionIndex).
+    #[serde(default)]
+    pub versions: bool,
+    /// IDs stored in this segment that were deleted or rewritten after it
+    /// was built. Hidden from its search results and dropped when it is
+    /// merged. Only tiered compaction leaves segments with tombstones.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tombstones: BTreeSet<String>,
 }
 
 /// The manifest is the single source of truth for what data exists
@@ -54,6 +105,11 @@ pub struct Manifest {
     /// The currently active segment (latest).
     #[serde(default)]
     pub active_segment: Option<String>,
+    /// Older segments still searched alongside the active one, oldest
+    /// first. Only tiered compaction leaves segments here; a vector ID is
+    /// live in at most one of these and the active segment.
+    #[serde(default)]
+    pub tier_segments: Vec<String>,
     /// Monotonic counter for assigning sequence numbers to fragments.
     #[serde(default)]
     pub next_sequence: u64,
@@ -77,6 +133,7 @@ impl Manifest {
             segments: Vec::new(),
             compaction_watermark: None,
             active_segment: None,
+            tier_segments: Vec::new(),
             next_sequence: 0,
             pending_deletes: Vec::new(),
             fencing_token: 0,
@@ -111,6 +168,63 @@ impl Manifest {
         self.updated_at = Utc::now();
     }
 
+    /// Add a segment holding newer writes than every live segment, keeping
+    /// the previous active segment live as a tier segment.
+    pub fn push_segment(&mut self, sref: SegmentRef) {
+        if let Some(previous) = self.active_segment.take() {
+            self.tier_segments.push(previous);
+        }
+        self.add_segment(sref);
+    }
+
+    /// Replace the live segments in `replaced` with `sref`, dropping their
+    /// references. `sref` becomes the active segment if the active one was
+    /// replaced or there is none.
+    pub fn replace_segments(&mut self, replaced: &[String], sref: SegmentRef) {
+        let replaces_active = self
+            .active_segment
+            .as_ref()
+            .is_some_and(|id| replaced.contains(id));
+        let position = self
+            .tier_segments
+            .iter()
+            .position(|id| replaced.contains(id));
+        self.segments.retain(|s| !replaced.contains(&s.id));
+        self.tier_segments.retain(|id| !replaced.contains(id));
+        if replaces_active || self.active_segment.is_none() {
+            self.add_segment(sref);
+        } else {
+            let position = position.unwrap_or(self.tier_segments.len());
+            self.tier_segments.insert(position, sref.id.clone());
+            self.segments.push(sref);
+            self.updated_at = Utc::now();
+        }
+    }
+
+    /// Drop the live segments in `removed`. If the active one goes, the
+    /// newest remaining tier segment takes its place.
+    pub fn remove_segments(&mut self, removed: &[String]) {
+        self.segments.retain(|s| !removed.contains(&s.id));
+        self.tier_segments.retain(|id| !removed.contains(id));
+        if self
+            .active_segment
+            .as_ref()
+            .is_some_and(|id| removed.contains(id))
+        {
+            self.active_segment = self.tier_segments.pop();
+        }
+        self.updated_at = Utc::now();
+    }
+
+    /// Live segments, oldest first: the tier segments, then the active one.
+    pub fn live_segments(&self) -> Vec<&SegmentRef> {
+        self.tier_segments
+            .iter()
+            .chain(&self.active_segment)
+            .filter_map(|id| self.segments.iter().find(|s| &s.id == id))
+            .collect()
+    }
+
     /// Get uncompacted fragments (those after the compaction watermark).
     pub fn uncompacted_fragments(&self) -> &[FragmentRef] {
         &self.fragments
@@ -166,6 +280,43 @@ impl Manifest {
         }
     }
 
+    /// Like [`read_versioned`](Self::read_versioned), but re-reads up to
+    /// `retries` times, backing off from [`READ_RETRY_BACKOFF`], while the
+    /// manifest's `next_sequence` is below `min_sequence`. Some S3-compatible
+    /// backends serve stale data for a while after an overwrite, so a reader
+    /// that knows a newer manifest was committed waits for it. If it never
+    /// shows up, the last (stale) read is returned.
+    pub async fn read_versioned_at_least(
+        store: &ZeppelinStore,
+        namespace: &str,
+        min_sequence: u64,
+        retries: u32,
+    ) -> Result<Option<(Self, ManifestVersion)>> {
+        let mut backoff = READ_RETRY_BACKOFF;
+        let mut attempt = 0;
+        loop {
+            let read = Self::read_versioned(store, namespace).await?;
+            let seen = read.as_ref().map_or(0, |(m, _)| m.next_sequence);
+            if seen >= min_sequence {
+                return Ok(read);
+            }
+            if attempt == retries {
+                warn!(
+                    namespace,
+                    seen, min_sequence, "manifest still stale after read retries"
+                );
+                return Ok(read);
+            }
+            debug!(
+                namespace,
+                seen, min_sequence, attempt, "stale manifest read, retrying"
+            );
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+
     /// Write manifest to S3 using conditional PUT (CAS).
     /// If version has an ETag, uses put_if_match for optimistic concurrency.
     /// For first-writes (no ETag), falls back to unconditional put.
@@ -182,6 +333,24 @@ impl Manifest {
             None => store.put(&key, data).await,
         }
     }
+
+    /// Whether the stored manifest has been replaced since `snapshot` was
+    /// read at `version`. Compares ETags when the backend provides them and
+    /// falls back to `updated_at` otherwise.
+    pub async fn changed_since(
+        store: &ZeppelinStore,
+        namespace: &str,
+        snapshot: &Manifest,
+        version: &ManifestVersion,
+    ) -> Result<bool> {
+        let Some((current, current_version)) = Self::read_versioned(store, namespace).await? else {
+            return Ok(version.0.is_some());
+        };
+        Ok(match (&version.0, &current_version.0) {
+            (Some(ours), Some(theirs)) => ours != theirs,
+            _ => current.updated_at != snapshot.updated_at,
+        })
+    }
 }
 
 /// Wraps the ETag for optimistic concurrency control on manifest writes.
@@ -1,10 +1,11 @@
+mod columnar;
 pub mod fragment;
 pub mod lease;
 pub mod manifest;
 pub mod reader;
 pub mod writer;
 
-pub use fragment::WalFragment;
+pub use fragment::{AttributePatch, FilterPatch, WalFragment, WalLayout};
 pub use lease::{Lease, LeaseManager};
 pub use manifest::{Manifest, ManifestVersion};
 pub use reader::WalReader;
@@ -1,8 +1,11 @@
+use std::collections::{BTreeSet, HashSet};
+
 use tracing::{debug, instrument, warn};
 use ulid::Ulid;
 
 use crate::error::{Result, ZeppelinError};
 use crate::storage::ZeppelinStore;
+use crate::types::VectorId;
 
 use super::fragment::WalFragment;
 use super::manifest::{FragmentRef, Manifest};
@@ -48,6 +51,86 @@ impl WalReader {
         self.read_fragments_from_refs(namespace, &refs).await
     }
 
+    /// Which of `ids` are currently tombstoned in the uncompacted WAL, i.e.
+    /// whose most recent WAL entry is a delete rather than an upsert.
+    ///
+    /// IDs the WAL does not mention are not returned, even if they are absent
+    /// from every segment. Only fragments from the oldest one carrying
+    /// deletes onward are read.
+    ///
+    /// The manifest is re-read up to `retries` times while its
+    /// `next_sequence` is below `min_sequence`; see
+    /// [`Manifest::read_versioned_at_least`].
+    #[instrument(skip(self, ids), fields(namespace = namespace, id_count = ids.len()))]
+    pub async fn tombstoned_ids(
+        &self,
+        namespace: &str,
+        ids: &[VectorId],
+        min_sequence: u64,
+        retries: u32,
+    ) -> Result<HashSet<VectorId>> {
+        let Some((manifest, _)) =
+            Manifest::read_versioned_at_least(&self.store, namespace, min_sequence, retries)
+                .await?
+        else {
+            return Ok(HashSet::new());
+        };
+        let refs = manifest.uncompacted_fragments();
+        let Some(first) = refs.iter().position(|r| r.delete_count > 0) else {
+            return Ok(HashSet::new());
+        };
+        let fragments = self
+            .read_fragments_from_refs(namespace, &refs[first..])
+            .await?;
+
+        let mut pending: HashSet<&str> = ids.iter().map(String::as_str).collect();
+        let mut tombstoned = HashSet::new();
+        // Newest first. Within a fragment upserts apply after deletes.
+        for fragment in fragments.iter().rev() {
+            if pending.is_empty() {
+                break;
+            }
+            for vector in &fragment.vectors {
+                pending.remove(vector.id.as_str());
+            }
+            for id in &fragment.deletes {
+                if pending.remove(id.as_str()) {
+                    tombstoned.insert(id.clone());
+                }
+            }
+        }
+        Ok(tombstoned)
+    }
+
+    /// Every ID currently tombstoned in the uncompacted WAL, in ID order.
+    ///
+    /// An ID stays here until compaction reclaims its delete or a later
+    /// upsert revives it. Only fragments from the oldest one carrying deletes
+    /// onward are read.
+    #[instrument(skip(self), fields(namespace = namespace))]
+    pub async fn tombstones(&self, namespace: &str) -> Result<BTreeSet<VectorId>> {
+        let Some(manifest) = Manifest::read(&self.store, namespace).await? else {
+            return Ok(BTreeSet::new());
+        };
+        let refs = manifest.uncompacted_fragments();
+        let Some(first) = refs.iter().position(|r| r.delete_count > 0) else {
+            return Ok(BTreeSet::new());
+        };
+        let fragments = self
+            .read_fragments_from_refs(namespace, &refs[first..])
+            .await?;
+
+        let mut tombstoned = BTreeSet::new();
+        // Oldest first. Within a fragment upserts apply after deletes.
+        for fragment in &fragments {
+            tombstoned.extend(fragment.deletes.iter().cloned());
+            for vector in &fragment.vectors {
+                tombstoned.remove(&vector.id);
+            }
+        }
+        Ok(tombstoned)
+    }
+
     /// Read specific fragments by their refs, preserving the caller's ordering.
     /// Gracefully skips fragments that return NotFound (deferred deletion safety).
     #[instrument(skip(self, refs), fields(namespace = namespace, ref_count = refs.len()))]
@@ -1,13 +1,16 @@
 use dashmap::DashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use tracing::{debug, instrument, warn};
+use std::time::Duration;
+use tokio::sync::{oneshot, Mutex, Notify, OwnedSemaphorePermit};
+use tracing::{debug, error, instrument, warn};
 
+use crate::config::WalConfig;
 use crate::error::{Result, ZeppelinError};
 use crate::storage::ZeppelinStore;
 use crate::types::{VectorEntry, VectorId};
 
-use super::fragment::WalFragment;
+use super::fragment::{AttributePatch, FilterPatch, WalFragment, WalLayout};
 use super::manifest::{FragmentRef, Manifest, ManifestVersion};
 
 /// Maximum CAS retry attempts for manifest updates.
@@ -18,16 +21,111 @@ pub struct WalWriter {
     store: ZeppelinStore,
     /// Per-namespace locks to serialize writes within a namespace.
     locks: DashMap<String, Arc<Mutex<()>>>,
+    /// Encoding used for newly written fragments.
+    layout: WalLayout,
+    /// Set when small writes are coalesced; see [`WalWriter::submit`].
+    coalescer: Option<Coalescer>,
+    /// Writes not yet in the manifest, including buffered ones.
+    in_flight: Arc<InFlight>,
+    /// `next_sequence` of the last manifest this writer committed, per
+    /// namespace; see [`WalWriter::committed_sequence`].
+    committed: Arc<DashMap<String, u64>>,
+}
+
+/// Counts in-flight writes so [`WalWriter::shutdown`] can wait them out.
+#[derive(Default)]
+struct InFlight {
+    count: AtomicUsize,
+    idle: Notify,
+}
+
+/// Holds one count on [`InFlight`] until dropped.
+struct InFlightGuard(Arc<InFlight>);
+
+impl InFlight {
+    fn enter(self: &Arc<Self>) -> InFlightGuard {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(self.clone())
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+/// Buffers small writes per namespace so they share one fragment.
+struct Coalescer {
+    window: Duration,
+    max_vectors: usize,
+    pending: DashMap<String, Arc<PendingWrites>>,
+    /// Set by [`WalWriter::shutdown`]; batches then flush without waiting.
+    closed: AtomicBool,
+}
+
+#[derive(Default)]
+struct PendingWrites {
+    batch: std::sync::Mutex<Batch>,
+    /// Wakes the flush task early once the batch reaches `max_vectors`
+    /// or the writer shuts down.
+    full: Notify,
+}
+
+/// Writes waiting for the next flush of a namespace.
+#[derive(Default)]
+struct Batch {
+    vectors: Vec<VectorEntry>,
+    deletes: Vec<VectorId>,
+    waiters: Vec<oneshot::Sender<Result<()>>>,
+    flush_scheduled: bool,
 }
 
 impl WalWriter {
     pub fn new(store: ZeppelinStore) -> Self {
+        Self::new_with_layout(store, WalLayout::default())
+    }
+
+    /// Create a writer that encodes fragments with the given layout.
+    pub fn new_with_layout(store: ZeppelinStore, layout: WalLayout) -> Self {
         Self {
             store,
             locks: DashMap::new(),
+            layout,
+            coalescer: None,
+            in_flight: Arc::default(),
+            committed: Arc::default(),
         }
     }
 
+    /// Create a writer from the `[wal]` config: fragment layout and, when
+    /// `coalesce_window_ms` is set, write coalescing for [`submit`](Self::submit).
+    pub fn from_config(store: ZeppelinStore, config: &WalConfig) -> Self {
+        let mut writer = Self::new_with_layout(store, config.layout);
+        writer.coalescer = config.coalesce_window_ms.map(|ms| Coalescer {
+            window: Duration::from_millis(ms),
+            max_vectors: config.coalesce_max_vectors,
+            pending: DashMap::new(),
+            closed: AtomicBool::new(false),
+        });
+        writer
+    }
+
+    /// `next_sequence` of the last manifest this writer committed for
+    /// `namespace`. A read of an older manifest is stale, which happens on
+    /// backends with eventually consistent overwrites.
+    pub fn committed_sequence(&self, namespace: &str) -> Option<u64> {
+        self.committed.get(namespace).map(|seq| *seq)
+    }
+
+    /// Drop the recorded sequence for a deleted namespace, so reads of a
+    /// namespace recreated under the same name don't wait for it.
+    pub fn forget_namespace(&self, namespace: &str) {
+        self.committed.remove(namespace);
+    }
+
     /// Get or create the per-namespace lock.
     fn namespace_lock(&self, namespace: &str) -> Arc<Mutex<()>> {
         self.locks
@@ -68,79 +166,484 @@ impl WalWriter {
         deletes: Vec<VectorId>,
         fencing_token: Option<u64>,
     ) -> Result<WalFragment> {
+        let _in_flight = self.in_flight.enter();
+        let (fragment, sequence) = write_fragment(
+            &self.store,
+            self.layout,
+            &self.namespace_lock(namespace),
+            namespace,
+            || Ok(WalFragment::new(vectors, deletes)),
+            fencing_token,
+        )
+        .await?;
+        self.committed.insert(namespace.to_string(), sequence);
+        Ok(fragment)
+    }
+
+    /// Append attribute patches, by ID and by filter, to the WAL for a
+    /// namespace as their own fragment. Patches are never coalesced, so they
+    /// land after every write that was acknowledged before this call.
+    #[instrument(skip(self, patches, filter_patches), fields(namespace = namespace))]
+    pub async fn append_patches(
+        &self,
+        namespace: &str,
+        patches: Vec<AttributePatch>,
+        filter_patches: Vec<FilterPatch>,
+    ) -> Result<WalFragment> {
+        let _in_flight = self.in_flight.enter();
+        let (fragment, sequence) = write_fragment(
+            &self.store,
+            self.layout,
+            &self.namespace_lock(namespace),
+            namespace,
+            || WalFragment::try_new_with_patches(Vec::new(), Vec::new(), patches, filter_patches),
+            None,
+        )
+        .await?;
+        self.committed.insert(namespace.to_string(), sequence);
+        Ok(fragment)
+    }
+
+    /// Append vectors only if `check` succeeds. The namespace lock is held
+    /// from the check until the manifest is updated, so no other write from
+    /// this writer lands in between. Never coalesced.
+    #[instrument(skip(self, vectors, check), fields(namespace = namespace))]
+    pub async fn append_if<F, Fut>(
+        &self,
+        namespace: &str,
+        vectors: Vec<VectorEntry>,
+        check: F,
+    ) -> Result<WalFragment>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<()>>,
+    {
+        let _in_flight = self.in_flight.enter();
         let lock = self.namespace_lock(namespace);
         let _guard = lock.lock().await;
+        check().await?;
+        let (fragment, sequence) = write_fragment_locked(
+            &self.store,
+            self.layout,
+            namespace,
+            WalFragment::new(vectors, Vec::new()),
+            None,
+            None,
+        )
+        .await?;
+        self.committed.insert(namespace.to_string(), sequence);
+        Ok(fragment)
+    }
 
-        crate::metrics::WAL_APPENDS_TOTAL
-            .with_label_values(&[namespace])
-            .inc();
-
-        let fragment = WalFragment::new(vectors, deletes);
-
-        // Write the fragment to S3
-        let key = WalFragment::s3_key(namespace, &fragment.id);
-        let data = fragment.to_bytes()?;
-        self.store.put(&key, data).await?;
-
-        debug!(
-            fragment_id = %fragment.id,
-            vectors = fragment.vectors.len(),
-            deletes = fragment.deletes.len(),
-            "wrote WAL fragment"
-        );
-
-        // CAS retry loop for manifest update
-        for attempt in 0..MAX_CAS_RETRIES {
-            let (mut manifest, version) =
-                match Manifest::read_versioned(&self.store, namespace).await? {
-                    Some(pair) => pair,
-                    None => (Manifest::default(), ManifestVersion(None)),
-                };
-
-            // Layer 1: Fencing check — reject zombie writers.
-            if let Some(token) = fencing_token {
-                if manifest.fencing_token > token {
-                    return Err(ZeppelinError::FencingTokenStale {
-                        namespace: namespace.to_string(),
-                        our_token: token,
-                        manifest_token: manifest.fencing_token,
-                    });
-                }
-                manifest.fencing_token = token;
+    /// Append a delete of the IDs `select` returns; nothing is written if it
+    /// returns none. The namespace lock is held from `select` until the
+    /// manifest is updated, as in [`append_if`](Self::append_if), so no
+    /// other write from this writer lands in between. Writes already
+    /// waiting to be coalesced are flushed first, so the delete lands after
+    /// them.
+    ///
+    /// With a `key` (and the request's payload hash) the write is
+    /// idempotent like [`append_idempotent`](Self::append_idempotent); the
+    /// replay check runs first, and a replay returns `None` without calling
+    /// `select`. Otherwise returns the number of IDs deleted. Never
+    /// coalesced.
+    #[instrument(skip(self, select), fields(namespace = namespace))]
+    pub async fn append_deletes_if<F, Fut>(
+        &self,
+        namespace: &str,
+        key: Option<(&str, u64)>,
+        select: F,
+    ) -> Result<Option<usize>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<Vec<VectorId>>>,
+    {
+        let _in_flight = self.in_flight.enter();
+        self.flush_pending(namespace).await;
+        let lock = self.namespace_lock(namespace);
+        let _guard = lock.lock().await;
+        if let Some((key, payload_hash)) = key {
+            if self.is_replay(namespace, key, payload_hash).await? {
+                return Ok(None);
             }
+        }
+        let ids = select().await?;
+        if ids.is_empty() {
+            return Ok(Some(0));
+        }
+        let count = ids.len();
+        let mut fragment = WalFragment::new(Vec::new(), ids);
+        if let Some((key, _)) = key {
+            fragment = fragment.with_idempotency_key(key.to_string());
+        }
+        let (_, sequence) = write_fragment_locked(
+            &self.store,
+            self.layout,
+            namespace,
+            fragment,
+            key.map(|(_, payload_hash)| payload_hash),
+            None,
+        )
+        .await?;
+        self.committed.insert(namespace.to_string(), sequence);
+        Ok(Some(count))
+    }
 
-            manifest.add_fragment(FragmentRef {
-                id: fragment.id,
-                vector_count: fragment.vectors.len(),
-                delete_count: fragment.deletes.len(),
-                sequence_number: 0, // assigned by add_fragment
+    /// Append a client write tagged with an idempotency key. Returns
+    /// `Ok(true)` without writing if a fragment with the same key is still
+    /// in the manifest, i.e. this is a retry of a write that already landed.
+    /// `payload_hash` is the request's [`WalFragment::payload_hash`]; a key
+    /// found with a different one fails with `IdempotencyKeyReused`.
+    ///
+    /// The check runs under the namespace lock, so concurrent retries
+    /// through this writer write once. A key is forgotten once compaction
+    /// removes its fragment. Never coalesced.
+    #[instrument(skip(self, vectors, deletes), fields(namespace = namespace))]
+    pub async fn append_idempotent(
+        &self,
+        namespace: &str,
+        vectors: Vec<VectorEntry>,
+        deletes: Vec<VectorId>,
+        key: &str,
+        payload_hash: u64,
+    ) -> Result<bool> {
+        let _in_flight = self.in_flight.enter();
+        let lock = self.namespace_lock(namespace);
+        let _guard = lock.lock().await;
+        if self.is_replay(namespace, key, payload_hash).await? {
+            return Ok(true);
+        }
+        let (_, sequence) = write_fragment_locked(
+            &self.store,
+            self.layout,
+            namespace,
+            WalFragment::new(vectors, deletes).with_idempotency_key(key.to_string()),
+            Some(payload_hash),
+            None,
+        )
+        .await?;
+        self.committed.insert(namespace.to_string(), sequence);
+        Ok(false)
+    }
+
+    /// Whether a write tagged `key` with this `payload_hash` already landed,
+    /// per [`append_idempotent`](Self::append_idempotent). Lets a caller
+    /// answer a retry before doing work that depends on current state.
+    pub async fn is_replay(&self, namespace: &str, key: &str, payload_hash: u64) -> Result<bool> {
+        let manifest = Manifest::read(&self.store, namespace)
+            .await?
+            .unwrap_or_default();
+        let Some(original) = manifest
+            .uncompacted_fragments()
+            .iter()
+            .find(|f| f.idempotency_key.as_deref() == Some(key))
+        else {
+            return Ok(false);
+        };
+        // Entries written before payload hashes were recorded have none.
+        if original.payload_hash.is_some_and(|h| h != payload_hash) {
+            return Err(ZeppelinError::IdempotencyKeyReused {
+                namespace: namespace.to_string(),
+                key: key.to_string(),
             });
+        }
+        debug!(fragment_id = %original.id, key, "idempotent write replayed");
+        Ok(true)
+    }
+
+    /// Append a client write, coalescing it with other small writes to the
+    /// same namespace when `wal.coalesce_window_ms` is set.
+    ///
+    /// Returns only once the fragment holding the write is in the manifest,
+    /// so a strong read issued after this returns sees the data. Without
+    /// coalescing, or for writes of at least `coalesce_max_vectors`
+    /// operations, this is a plain [`append`](Self::append).
+    pub async fn submit(
+        &self,
+        namespace: &str,
+        vectors: Vec<VectorEntry>,
+        deletes: Vec<VectorId>,
+    ) -> Result<()> {
+        let Some(coalescer) = &self.coalescer else {
+            return self.append(namespace, vectors, deletes).await.map(|_| ());
+        };
+        if vectors.len() + deletes.len() >= coalescer.max_vectors {
+            return self.append(namespace, vectors, deletes).await.map(|_| ());
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let pending = coalescer
+            .pending
+            .entry(namespace.to_string())
+            .or_default()
+            .clone();
+        let (start_flusher, flush_now) = {
+            let mut batch = pending.batch.lock().unwrap_or_else(|e| e.into_inner());
+            // The later of a delete and an upsert of the same ID must win,
+            // and a fragment can't hold both: drop the earlier one.
+            for id in &deletes {
+                batch.vectors.retain(|v| &v.id != id);
+            }
+            batch
+                .deletes
+                .retain(|d| !vectors.iter().any(|v| &v.id == d));
+            batch.deletes.extend(deletes);
+            batch.vectors.extend(vectors);
+            batch.waiters.push(tx);
+            let start_flusher = !batch.flush_scheduled;
+            batch.flush_scheduled = true;
+            (
+                start_flusher,
+                batch.vectors.len() + batch.deletes.len() >= coalescer.max_vectors
+                    || coalescer.closed.load(Ordering::SeqCst),
+            )
+        };
 
-            // Layer 2: CAS — catches TOCTOU gap between fencing check and write.
-            match manifest
-                .write_conditional(&self.store, namespace, &version)
-                .await
-            {
-                Ok(()) => {
-                    debug!(
-                        fragment_count = manifest.fragments.len(),
-                        attempt, "updated manifest"
-                    );
-                    return Ok(fragment);
+        if start_flusher {
+            // Spawned so the batch is flushed even if this request is dropped.
+            let store = self.store.clone();
+            let layout = self.layout;
+            let lock = self.namespace_lock(namespace);
+            let namespace = namespace.to_string();
+            let window = coalescer.window;
+            let pending = pending.clone();
+            let committed = self.committed.clone();
+            let in_flight = self.in_flight.enter();
+            tokio::spawn(async move {
+                let _in_flight = in_flight;
+                tokio::select! {
+                    _ = tokio::time::sleep(window) => {}
+                    _ = pending.full.notified() => {}
                 }
-                Err(ZeppelinError::ManifestConflict { .. }) => {
-                    warn!(
-                        attempt,
-                        namespace, "manifest CAS conflict in writer, retrying"
-                    );
-                    continue;
+                let batch = {
+                    let mut batch = pending.batch.lock().unwrap_or_else(|e| e.into_inner());
+                    std::mem::take(&mut *batch)
+                };
+                debug!(
+                    namespace = %namespace,
+                    writes = batch.waiters.len(),
+                    vectors = batch.vectors.len(),
+                    deletes = batch.deletes.len(),
+                    "flushing coalesced WAL writes"
+                );
+                let result = write_fragment(
+                    &store,
+                    layout,
+                    &lock,
+                    &namespace,
+                    || WalFragment::try_new(batch.vectors, batch.deletes),
+                    None,
+                )
+                .await;
+                if let Ok((_, sequence)) = &result {
+                    committed.insert(namespace.clone(), *sequence);
                 }
-                Err(e) => return Err(e),
-            }
+                for waiter in batch.waiters {
+                    let _ = waiter.send(match &result {
+                        Ok(_) => Ok(()),
+                        Err(e) => Err(shared_error(e)),
+                    });
+                }
+            });
+        }
+        if flush_now {
+            pending.full.notify_one();
         }
 
-        Err(ZeppelinError::ManifestConflict {
-            namespace: namespace.to_string(),
+        rx.await.unwrap_or_else(|_| {
+            Err(ZeppelinError::CoalescedWrite(
+                "flush task ended without a result".to_string(),
+            ))
         })
     }
+
+    /// Flush `namespace`'s coalescing batch now, if one is waiting, and wait
+    /// for it to land or fail. Its failure is reported to its own writers.
+    async fn flush_pending(&self, namespace: &str) {
+        let Some(coalescer) = &self.coalescer else {
+            return;
+        };
+        let Some(pending) = coalescer.pending.get(namespace).map(|p| p.clone()) else {
+            return;
+        };
+        let rx = {
+            let mut batch = pending.batch.lock().unwrap_or_else(|e| e.into_inner());
+            if !batch.flush_scheduled {
+                return;
+            }
+            let (tx, rx) = oneshot::channel();
+            batch.waiters.push(tx);
+            rx
+        };
+        pending.full.notify_one();
+        let _ = rx.await;
+    }
+
+    /// Queue a write in the background and return at once, for clients that
+    /// don't need durability on acknowledgement. The write goes through
+    /// [`submit`](Self::submit), so it is coalesced like any other.
+    ///
+    /// It counts as in flight from this call, so [`shutdown`](Self::shutdown)
+    /// waits for it. `permit` (an upsert limiter slot) is held until the
+    /// write lands, which bounds the queue. A failed write can't be reported
+    /// to the client; it is logged and counted in
+    /// `zeppelin_async_upsert_failures_total`.
+    pub fn submit_detached(
+        self: &Arc<Self>,
+        namespace: &str,
+        vectors: Vec<VectorEntry>,
+        deletes: Vec<VectorId>,
+        permit: Option<OwnedSemaphorePermit>,
+    ) {
+        let in_flight = self.in_flight.enter();
+        let pending = crate::metrics::ASYNC_UPSERTS_PENDING.with_label_values(&[namespace]);
+        pending.inc();
+        let writer = self.clone();
+        let namespace = namespace.to_string();
+        tokio::spawn(async move {
+            let _in_flight = in_flight;
+            let _permit = permit;
+            let count = vectors.len() + deletes.len();
+            if let Err(e) = writer.submit(&namespace, vectors, deletes).await {
+                crate::metrics::ASYNC_UPSERT_FAILURES_TOTAL
+                    .with_label_values(&[&namespace])
+                    .inc();
+                error!(namespace = %namespace, count, error = %e, "async WAL write failed");
+            }
+            pending.dec();
+        });
+    }
+
+    /// Flush every buffered write now and wait until all in-flight writes
+    /// have reached the manifest (or failed). Called on graceful shutdown so
+    /// no acknowledged or buffered write is lost. Writes submitted after this
+    /// are flushed without waiting for the coalescing window.
+    pub async fn shutdown(&self) {
+        if let Some(coalescer) = &self.coalescer {
+            coalescer.closed.store(true, Ordering::SeqCst);
+            for pending in coalescer.pending.iter() {
+                pending.full.notify_one();
+            }
+        }
+        loop {
+            let idle = self.in_flight.idle.notified();
+            let count = self.in_flight.count.load(Ordering::SeqCst);
+            if count == 0 {
+                break;
+            }
+            debug!(in_flight = count, "waiting for WAL writes before shutdown");
+            idle.await;
+        }
+    }
+}
+
+/// Write one fragment and add it to the manifest, holding the namespace lock.
+/// Uses CAS for the manifest update; see [`WalWriter::append_with_lease`].
+/// Returns the fragment and the committed manifest's `next_sequence`.
+///
+/// `fragment` is built once the lock is held, so fragment IDs (ULIDs) rise
+/// in manifest order within this process.
+async fn write_fragment(
+    store: &ZeppelinStore,
+    layout: WalLayout,
+    lock: &Mutex<()>,
+    namespace: &str,
+    fragment: impl FnOnce() -> Result<WalFragment>,
+    fencing_token: Option<u64>,
+) -> Result<(WalFragment, u64)> {
+    let _guard = lock.lock().await;
+    write_fragment_locked(store, layout, namespace, fragment()?, None, fencing_token).await
+}
+
+/// [`write_fragment`] for a caller already holding the namespace lock and
+/// having built the fragment under it. `payload_hash` goes into the
+/// fragment's manifest entry.
+async fn write_fragment_locked(
+    store: &ZeppelinStore,
+    layout: WalLayout,
+    namespace: &str,
+    fragment: WalFragment,
+    payload_hash: Option<u64>,
+    fencing_token: Option<u64>,
+) -> Result<(WalFragment, u64)> {
+    crate::metrics::WAL_APPENDS_TOTAL
+        .with_label_values(&[namespace])
+        .inc();
+
+    // Write the fragment to S3
+    let key = WalFragment::s3_key(namespace, &fragment.id);
+    let data = fragment.to_bytes_with_layout(layout)?;
+    store.put(&key, data).await?;
+
+    debug!(
+        fragment_id = %fragment.id,
+        vectors = fragment.vectors.len(),
+        deletes = fragment.deletes.len(),
+        patches = fragment.patches.len(),
+        filter_patches = fragment.filter_patches.len(),
+        "wrote WAL fragment"
+    );
+
+    // CAS retry loop for manifest update
+    for attempt in 0..MAX_CAS_RETRIES {
+        let (mut manifest, version) = match Manifest::read_versioned(store, namespace).await? {
+            Some(pair) => pair,
+            None => (Manifest::default(), ManifestVersion(None)),
+        };
+
+        // Layer 1: Fencing check — reject zombie writers.
+        if let Some(token) = fencing_token {
+            if manifest.fencing_token > token {
+                return Err(ZeppelinError::FencingTokenStale {
+                    namespace: namespace.to_string(),
+                    our_token: token,
+                    manifest_token: manifest.fencing_token,
+                });
+            }
+            manifest.fencing_token = token;
+        }
+
+        manifest.add_fragment(FragmentRef {
+            id: fragment.id,
+            vector_count: fragment.vectors.len(),
+            delete_count: fragment.deletes.len(),
+            sequence_number: 0, // assigned by add_fragment
+            idempotency_key: fragment.idempotency_key.clone(),
+            payload_hash,
+        });
+
+        // Layer 2: CAS — catches TOCTOU gap between fencing check and write.
+        match manifest.write_conditional(store, namespace, &version).await {
+            Ok(()) => {
+                debug!(
+                    fragment_count = manifest.fragments.len(),
+                    attempt, "updated manifest"
+                );
+                return Ok((fragment, manifest.next_sequence));
+            }
+            Err(ZeppelinError::ManifestConflict { .. }) => {
+                warn!(
+                    attempt,
+                    namespace, "manifest CAS conflict in writer, retrying"
+                );
+                continue;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(ZeppelinError::ManifestConflict {
+        namespace: namespace.to_string(),
+    })
+}
+
+/// Error reported to every write in a failed coalesced flush. Manifest
+/// conflicts keep their variant so clients still see them as retryable.
+fn shared_error(e: &ZeppelinError) -> ZeppelinError {
+    match e {
+        ZeppelinError::ManifestConflict { namespace } => ZeppelinError::ManifestConflict {
+            namespace: namespace.clone(),
+        },
+        other => ZeppelinError::CoalescedWrite(other.to_string()),
+    }
 }
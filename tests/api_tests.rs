@@ -1,7 +1,16 @@
 mod common;
 
-use common::server::{api_ns, cleanup_ns, start_test_server};
-use common::vectors::random_vectors;
+use common::server::{
+    api_ns, cleanup_ns, start_test_server, start_test_server_with_compactor,
+    start_test_server_with_config, start_test_server_with_store,
+    start_test_server_with_store_and_config,
+};
+use common::vectors::{clustered_vectors, random_vectors, simple_attributes, with_attributes};
+
+use zeppelin::config::{ApiKeyConfig, Config, JsonCase, Role, RoleGrant};
+use zeppelin::storage::ZeppelinStore;
+use zeppelin::types::{AttributeValue, VectorEntry};
+use zeppelin::wal::Manifest;
 
 #[tokio::test]
 async fn test_health_check() {
@@ -16,6 +25,165 @@ async fn test_health_check() {
     harness.cleanup().await;
 }
 
+#[tokio::test]
+async fn test_admin_config_redacts_secrets() {
+    let mut config = Config::load(None).unwrap();
+    config.indexing.default_nprobe = 7;
+    config.storage.s3_access_key_id = Some("AKIATESTKEY".to_string());
+    config.storage.s3_secret_access_key = Some("super-secret".to_string());
+
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(Some(config)).await;
+
+    let resp = reqwest::get(format!("{base_url}/v1/admin/config"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["indexing"]["default_nprobe"], 7);
+    assert_eq!(body["storage"]["s3_access_key_id"], "<redacted>");
+    assert_eq!(body["storage"]["s3_secret_access_key"], "<redacted>");
+    let raw = body.to_string();
+    assert!(!raw.contains("AKIATESTKEY"));
+    assert!(!raw.contains("super-secret"));
+
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_admin_invalidate_namespace_cache() {
+    let (base_url, harness, cache, _dir) = start_test_server_with_config(None).await;
+    let ns = api_ns(&harness, "api-inval");
+    let other = api_ns(&harness, "api-inval-other");
+
+    let data = bytes::Bytes::from_static(b"cluster");
+    cache.put(&format!("{ns}/segments/a"), &data).await.unwrap();
+    cache.put(&format!("{ns}/segments/b"), &data).await.unwrap();
+    cache
+        .put(&format!("{other}/segments/a"), &data)
+        .await
+        .unwrap();
+
+    let resp = reqwest::Client::new()
+        .post(format!(
+            "{base_url}/v1/admin/namespaces/{ns}/cache/invalidate"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["invalidated"], 2);
+
+    assert!(cache.get(&format!("{ns}/segments/a")).await.is_none());
+    assert!(cache.get(&format!("{ns}/segments/b")).await.is_none());
+    assert!(cache.get(&format!("{other}/segments/a")).await.is_some());
+
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_cache_pinned_flag_toggles_and_unpins() {
+    let (base_url, harness, cache, _dir) = start_test_server_with_config(None).await;
+    let ns = api_ns(&harness, "api-pinned");
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 4, "cache_pinned": true}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["cache_pinned"], true);
+
+    let key = format!("{ns}/segments/seg/centroids.bin");
+    cache.pin(&key).await;
+
+    let resp = client
+        .patch(format!("{base_url}/v1/namespaces/{ns}"))
+        .json(&serde_json::json!({"cache_pinned": false}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["cache_pinned"], false);
+    assert!(!cache.is_pinned(&key).await);
+
+    let resp = reqwest::get(format!("{base_url}/v1/namespaces/{ns}"))
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["cache_pinned"], false);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_camel_case_responses() {
+    let mut config = Config::load(None).unwrap();
+    config.server.json_case = JsonCase::Camel;
+
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(Some(config)).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-camel");
+
+    // Requests accept camelCase keys.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 4,
+            "distanceMetric": "euclidean",
+            "compaction": {"maxWalFragmentsBeforeCompact": 5},
+            "namedVectors": {"title_vec": {"dimensions": 2, "distanceMetric": "dot_product"}}
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["distanceMetric"], "euclidean");
+    assert!(body["vectorCount"].is_number());
+    assert!(body.get("distance_metric").is_none());
+    // Nested DTOs are renamed too; user-chosen field names are not.
+    assert_eq!(body["compaction"]["maxWalFragmentsBeforeCompact"], 5);
+    assert_eq!(
+        body["namedVectors"]["title_vec"]["distanceMetric"],
+        "dot_product"
+    );
+
+    let vectors = random_vectors(5, 4);
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors }))
+        .send()
+        .await
+        .unwrap();
+
+    // ...and snake_case keys alike.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({
+            "vector": vectors[0].values,
+            "top_k": 3
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert!(body["scannedFragments"].as_u64().unwrap() >= 1);
+    assert!(body.get("scanned_fragments").is_none());
+    assert_eq!(body["results"].as_array().unwrap().len(), 3);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
 #[tokio::test]
 async fn test_namespace_crud() {
     let (base_url, harness) = start_test_server().await;
@@ -58,6 +226,14 @@ async fn test_namespace_crud() {
     let body: Vec<serde_json::Value> = resp.json().await.unwrap();
     assert!(body.iter().any(|n| n["name"] == ns));
 
+    // Head
+    let resp = client
+        .head(format!("{base_url}/v1/namespaces/{ns}"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
     // Delete
     let resp = client
         .delete(format!("{base_url}/v1/namespaces/{ns}"))
@@ -74,10 +250,85 @@ async fn test_namespace_crud() {
         .unwrap();
     assert_eq!(resp.status(), 404);
 
+    let resp = client
+        .head(format!("{base_url}/v1/namespaces/{ns}"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+
     // DELETE handler already cleaned up S3 objects
     harness.cleanup().await;
 }
 
+#[tokio::test]
+async fn test_list_namespaces_paginates_in_name_order() {
+    let (base_url, harness) = start_test_server().await;
+    let client = reqwest::Client::new();
+
+    // Created out of order on purpose.
+    let created: Vec<String> = ["page-d", "page-a", "page-e", "page-c", "page-b"]
+        .iter()
+        .map(|suffix| api_ns(&harness, suffix))
+        .collect();
+    for ns in &created {
+        let resp = client
+            .post(format!("{base_url}/v1/namespaces"))
+            .json(&serde_json::json!({"name": ns, "dimensions": 4}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 201);
+    }
+
+    let mut listed: Vec<String> = Vec::new();
+    let mut cursor: Option<String> = None;
+    let mut pages = 0;
+    loop {
+        let mut url = format!("{base_url}/v1/namespaces?limit=2");
+        if let Some(ref c) = cursor {
+            url.push_str(&format!("&cursor={c}"));
+        }
+        let resp = client.get(url).send().await.unwrap();
+        assert_eq!(resp.status(), 200);
+        cursor = resp
+            .headers()
+            .get("x-next-cursor")
+            .map(|v| v.to_str().unwrap().to_string());
+        let page: Vec<serde_json::Value> = resp.json().await.unwrap();
+        assert!(page.len() <= 2);
+        listed.extend(page.iter().map(|n| n["name"].as_str().unwrap().to_string()));
+        pages += 1;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert!(pages >= 3, "5 namespaces at limit=2 need at least 3 pages");
+
+    let mut sorted = listed.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(
+        listed, sorted,
+        "pages must be in name order without duplicates"
+    );
+    for ns in &created {
+        assert!(listed.contains(ns), "{ns} missing from paginated listing");
+    }
+
+    let resp = client
+        .get(format!("{base_url}/v1/namespaces?limit=0"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    for ns in &created {
+        cleanup_ns(&harness.store, ns).await;
+    }
+    harness.cleanup().await;
+}
+
 #[tokio::test]
 async fn test_duplicate_create_409() {
     let (base_url, harness) = start_test_server().await;
@@ -144,204 +395,665 @@ async fn test_vector_upsert() {
 }
 
 #[tokio::test]
-async fn test_dimension_mismatch_400() {
+async fn test_unnormalized_cosine_upsert_warns() {
     let (base_url, harness) = start_test_server().await;
     let client = reqwest::Client::new();
-    let ns = api_ns(&harness, "api-dim");
+    let ns = api_ns(&harness, "api-unnormalized");
 
-    // Create namespace with dim=16
     client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 4, "distance_metric": "cosine"}))
+        .send()
+        .await
+        .unwrap();
+    let warnings = || {
+        zeppelin::metrics::UNNORMALIZED_COSINE_UPSERTS_TOTAL
+            .with_label_values(&[&ns])
+            .get()
+    };
+
+    let upsert = |scale: f32| {
+        let vectors: Vec<_> = (0..10)
+            .map(|i| {
+                serde_json::json!({
+                    "id": format!("v{i}"),
+                    "values": [scale, 0.0, 0.0, 0.0],
+                })
+            })
+            .collect();
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&serde_json::json!({ "vectors": vectors }))
+            .send()
+    };
+
+    assert_eq!(upsert(1.0).await.unwrap().status(), 200);
+    assert_eq!(warnings(), 0);
+
+    assert_eq!(upsert(5.0).await.unwrap().status(), 200);
+    assert_eq!(warnings(), 1);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_text_only_upsert_does_not_warn_unnormalized() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store).await;
+    let client = reqwest::Client::new();
+    let ns = "api-text-only-norm";
+
+    let resp = client
         .post(format!("{base_url}/v1/namespaces"))
         .json(&serde_json::json!({
             "name": ns,
-            "dimensions": 16,
+            "dimensions": 0,
+            "distance_metric": "cosine",
+            "full_text_search": {"content": {}},
         }))
         .send()
         .await
         .unwrap();
+    assert_eq!(resp.status(), 201, "{}", resp.text().await.unwrap());
 
-    // Upsert with wrong dimension (32 instead of 16)
-    let vectors = random_vectors(1, 32);
+    // Documents carry no values, which would read as zero-norm vectors.
     let resp = client
         .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
-        .json(&serde_json::json!({ "vectors": vectors }))
+        .json(&serde_json::json!({"vectors": [
+            {"id": "doc1", "attributes": {"content": "rust"}},
+            {"id": "doc2", "attributes": {"content": "pasta"}},
+        ]}))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 400);
+    assert_eq!(resp.status(), 200, "{}", resp.text().await.unwrap());
+    let resp = client
+        .put(format!("{base_url}/v1/namespaces/{ns}/vectors/doc3"))
+        .json(&serde_json::json!({"attributes": {"content": "tokio"}}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200, "{}", resp.text().await.unwrap());
 
-    cleanup_ns(&harness.store, &ns).await;
-    harness.cleanup().await;
+    assert_eq!(
+        zeppelin::metrics::UNNORMALIZED_COSINE_UPSERTS_TOTAL
+            .with_label_values(&[ns])
+            .get(),
+        0
+    );
 }
 
 #[tokio::test]
-async fn test_query_basic_wal_scan() {
+async fn test_put_single_vector() {
     let (base_url, harness) = start_test_server().await;
     let client = reqwest::Client::new();
-    let ns = api_ns(&harness, "api-query");
+    let ns = api_ns(&harness, "api-put");
 
-    // Create namespace
     client
         .post(format!("{base_url}/v1/namespaces"))
         .json(&serde_json::json!({
             "name": ns,
-            "dimensions": 8,
+            "dimensions": 4,
         }))
         .send()
         .await
         .unwrap();
 
-    // Upsert 10 vectors
-    let vectors = random_vectors(10, 8);
-    let query_vec = vectors[0].values.clone();
+    // PUT a vector by ID
+    let resp = client
+        .put(format!("{base_url}/v1/namespaces/{ns}/vectors/doc-1"))
+        .json(&serde_json::json!({
+            "values": [1.0, 0.0, 0.0, 0.0],
+            "attributes": {"color": "red"},
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["id"], "doc-1");
+    assert_eq!(body["upserted"], 1);
 
-    client
-        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
-        .json(&serde_json::json!({ "vectors": vectors }))
+    // Wrong dimension is rejected
+    let resp = client
+        .put(format!("{base_url}/v1/namespaces/{ns}/vectors/doc-2"))
+        .json(&serde_json::json!({ "values": [1.0, 0.0] }))
         .send()
         .await
         .unwrap();
+    assert_eq!(resp.status(), 400);
 
-    // Query with the first vector — it should be the top result (distance ~0)
+    // Query it back
     let resp = client
         .post(format!("{base_url}/v1/namespaces/{ns}/query"))
         .json(&serde_json::json!({
-            "vector": query_vec,
+            "vector": [1.0, 0.0, 0.0, 0.0],
             "top_k": 5,
         }))
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 200);
-
     let body: serde_json::Value = resp.json().await.unwrap();
     let results = body["results"].as_array().unwrap();
-    assert!(!results.is_empty());
-    assert_eq!(results[0]["id"], "vec_0");
-    assert!(body["scanned_fragments"].as_u64().unwrap() > 0);
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["id"], "doc-1");
+    assert_eq!(results[0]["attributes"]["color"], "red");
 
     cleanup_ns(&harness.store, &ns).await;
     harness.cleanup().await;
 }
 
 #[tokio::test]
-async fn test_query_with_filter() {
+async fn test_repeated_delete_is_noop() {
     let (base_url, harness) = start_test_server().await;
     let client = reqwest::Client::new();
-    let ns = api_ns(&harness, "api-filter");
+    let ns = api_ns(&harness, "api-del-noop");
 
-    // Create namespace
     client
         .post(format!("{base_url}/v1/namespaces"))
-        .json(&serde_json::json!({
-            "name": ns,
-            "dimensions": 4,
-        }))
+        .json(&serde_json::json!({ "name": ns, "dimensions": 4 }))
         .send()
         .await
         .unwrap();
-
-    // Upsert vectors with attributes
-    let vectors = serde_json::json!({
-        "vectors": [
-            {"id": "v1", "values": [1.0, 0.0, 0.0, 0.0], "attributes": {"category": "a"}},
-            {"id": "v2", "values": [0.9, 0.1, 0.0, 0.0], "attributes": {"category": "b"}},
-            {"id": "v3", "values": [0.8, 0.2, 0.0, 0.0], "attributes": {"category": "a"}},
-        ]
-    });
+    let vectors = random_vectors(2, 4);
     client
         .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
-        .json(&vectors)
+        .json(&serde_json::json!({ "vectors": vectors }))
         .send()
         .await
         .unwrap();
 
-    // Query with filter for category=a
-    let resp = client
-        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
-        .json(&serde_json::json!({
-            "vector": [1.0, 0.0, 0.0, 0.0],
-            "top_k": 10,
-            "filter": {"op": "eq", "field": "category", "value": "a"},
-        }))
+    let delete = |ids: serde_json::Value| {
+        client
+            .delete(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&serde_json::json!({ "ids": ids }))
+            .send()
+    };
+    for _ in 0..100 {
+        let resp = delete(serde_json::json!(["vec_0"])).await.unwrap();
+        assert_eq!(resp.status(), 200);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["deleted"], 1);
+    }
+
+    let manifest = Manifest::read(&harness.store, &ns).await.unwrap().unwrap();
+    let tombstones: usize = manifest.fragments.iter().map(|f| f.delete_count).sum();
+    assert_eq!(tombstones, 1, "repeated deletes wrote redundant tombstones");
+
+    // A re-upserted ID gets a fresh tombstone.
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": [vectors[0]] }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-
-    let body: serde_json::Value = resp.json().await.unwrap();
-    let results = body["results"].as_array().unwrap();
-    // Should only contain v1 and v3 (category=a)
-    assert_eq!(results.len(), 2);
-    let ids: Vec<&str> = results.iter().map(|r| r["id"].as_str().unwrap()).collect();
-    assert!(ids.contains(&"v1"));
-    assert!(ids.contains(&"v3"));
+    delete(serde_json::json!(["vec_0", "vec_1"])).await.unwrap();
+    let manifest = Manifest::read(&harness.store, &ns).await.unwrap().unwrap();
+    let tombstones: usize = manifest.fragments.iter().map(|f| f.delete_count).sum();
+    assert_eq!(tombstones, 3);
 
     cleanup_ns(&harness.store, &ns).await;
     harness.cleanup().await;
 }
 
 #[tokio::test]
-async fn test_query_empty_namespace() {
+async fn test_dimension_mismatch_400() {
     let (base_url, harness) = start_test_server().await;
     let client = reqwest::Client::new();
-    let ns = api_ns(&harness, "api-empty");
+    let ns = api_ns(&harness, "api-dim");
 
-    // Create namespace
+    // Create namespace with dim=16
     client
         .post(format!("{base_url}/v1/namespaces"))
         .json(&serde_json::json!({
             "name": ns,
-            "dimensions": 4,
+            "dimensions": 16,
         }))
         .send()
         .await
         .unwrap();
 
-    // Query empty namespace
+    // Upsert with wrong dimension (32 instead of 16)
+    let vectors = random_vectors(1, 32);
     let resp = client
-        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
-        .json(&serde_json::json!({
-            "vector": [1.0, 0.0, 0.0, 0.0],
-            "top_k": 5,
-        }))
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors }))
         .send()
         .await
         .unwrap();
-    assert_eq!(resp.status(), 200);
-
-    let body: serde_json::Value = resp.json().await.unwrap();
-    let results = body["results"].as_array().unwrap();
-    assert!(results.is_empty());
+    assert_eq!(resp.status(), 400);
 
     cleanup_ns(&harness.store, &ns).await;
     harness.cleanup().await;
 }
 
 #[tokio::test]
-async fn test_query_dimension_mismatch() {
+async fn test_query_basic_wal_scan() {
     let (base_url, harness) = start_test_server().await;
     let client = reqwest::Client::new();
-    let ns = api_ns(&harness, "api-qdim");
+    let ns = api_ns(&harness, "api-query");
 
-    // Create namespace with dim=4
+    // Create namespace
     client
         .post(format!("{base_url}/v1/namespaces"))
         .json(&serde_json::json!({
             "name": ns,
-            "dimensions": 4,
+            "dimensions": 8,
         }))
         .send()
         .await
         .unwrap();
 
-    // Query with wrong dimension
-    let resp = client
-        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
-        .json(&serde_json::json!({
-            "vector": [1.0, 0.0],
-            "top_k": 5,
-        }))
+    // Upsert 10 vectors
+    let vectors = random_vectors(10, 8);
+    let query_vec = vectors[0].values.clone();
+
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors }))
+        .send()
+        .await
+        .unwrap();
+
+    // Query with the first vector — it should be the top result (distance ~0)
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({
+            "vector": query_vec,
+            "top_k": 5,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let results = body["results"].as_array().unwrap();
+    assert!(!results.is_empty());
+    assert_eq!(results[0]["id"], "vec_0");
+    assert!(body["scanned_fragments"].as_u64().unwrap() > 0);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_query_wal_only_skips_segment() {
+    let mut config = Config::load(None).unwrap();
+    config.indexing.default_num_centroids = 2;
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(Some(config)).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-wal-only");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+
+    // seg_* vectors end up in the segment, wal_* stay in the WAL.
+    for (prefix, compact) in [("seg", true), ("wal", false)] {
+        let mut vectors = random_vectors(10, 8);
+        for v in &mut vectors {
+            v.id = v.id.replace("vec", prefix);
+        }
+        let resp = client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&serde_json::json!({ "vectors": vectors }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        if compact {
+            let resp = client
+                .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), 200);
+        }
+    }
+
+    let query = |extra: serde_json::Value| {
+        let mut body = serde_json::json!({"vector": vec![0.5f32; 8], "top_k": 20});
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+    };
+
+    let body: serde_json::Value = query(serde_json::json!({}))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["results"].as_array().unwrap().len(), 20);
+
+    for consistency in ["strong", "eventual"] {
+        let resp = query(serde_json::json!({"wal_only": true, "consistency": consistency}))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        let results = body["results"].as_array().unwrap();
+        assert_eq!(results.len(), 10);
+        assert!(results
+            .iter()
+            .all(|r| r["id"].as_str().unwrap().starts_with("wal_")));
+        assert_eq!(body["scanned_segments"], 0);
+        assert!(body["scanned_fragments"].as_u64().unwrap() > 0);
+    }
+
+    let resp = query(serde_json::json!({"wal_only": true, "rank_by": ["text", "BM25", "x"]}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_query_score_decimals_rounds_without_reordering() {
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(None).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-score-decimals");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": random_vectors(20, 8) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let query = |extra: serde_json::Value| {
+        let mut body = serde_json::json!({"vector": vec![0.5f32; 8], "top_k": 20});
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+    };
+
+    let full: serde_json::Value = query(serde_json::json!({}))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let rounded: serde_json::Value = query(serde_json::json!({"score_decimals": 2}))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let full = full["results"].as_array().unwrap();
+    let rounded = rounded["results"].as_array().unwrap();
+    assert_eq!(rounded.len(), full.len());
+
+    for (f, r) in full.iter().zip(rounded) {
+        assert_eq!(f["id"], r["id"], "ordering must not change");
+        let score = r["score"].as_f64().unwrap();
+        let text = r["score"].to_string();
+        let decimals = text.split_once('.').map_or(0, |(_, frac)| frac.len());
+        assert!(decimals <= 2, "score {text} has more than 2 decimals");
+        assert!((score - f["score"].as_f64().unwrap()).abs() <= 0.005 + 1e-6);
+    }
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_query_then_order_by_resorts_retrieved_results() {
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(None).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-then-order-by");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    let vectors = with_attributes(random_vectors(30, 8), simple_attributes);
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let query = |extra: serde_json::Value| {
+        let mut body = serde_json::json!({"vector": vec![0.5f32; 8], "top_k": 10});
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+    };
+
+    let ranked: serde_json::Value = query(serde_json::json!({}))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let resorted: serde_json::Value =
+        query(serde_json::json!({"then_order_by": ["score", "desc"]}))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+    let ranked = ranked["results"].as_array().unwrap();
+    let resorted = resorted["results"].as_array().unwrap();
+    assert_eq!(resorted.len(), 10);
+
+    // Same survivors as plain retrieval, now ordered by the attribute.
+    let ids = |results: &[serde_json::Value]| {
+        let mut ids: Vec<String> = results
+            .iter()
+            .map(|r| r["id"].as_str().unwrap().to_string())
+            .collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(ids(ranked), ids(resorted));
+    let values: Vec<i64> = resorted
+        .iter()
+        .map(|r| r["attributes"]["score"].as_i64().unwrap())
+        .collect();
+    assert!(
+        values.windows(2).all(|w| w[0] >= w[1]),
+        "expected descending attribute order, got {values:?}"
+    );
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_query_include_rank_annotates_final_order() {
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(None).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-include-rank");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    let vectors = with_attributes(random_vectors(30, 8), simple_attributes);
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let query = |body: serde_json::Value| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+    };
+
+    // Off by default.
+    let plain: serde_json::Value =
+        query(serde_json::json!({"vector": vec![0.5f32; 8], "top_k": 5}))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+    assert!(plain["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|r| r.get("rank").is_none()));
+
+    // Ranks follow the final order, after `then_order_by`.
+    let ranked: serde_json::Value = query(serde_json::json!({
+        "vector": vec![0.5f32; 8],
+        "top_k": 10,
+        "include_rank": true,
+        "then_order_by": ["score", "asc"],
+    }))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    let results = ranked["results"].as_array().unwrap();
+    assert_eq!(results.len(), 10);
+    let ranks: Vec<u64> = results
+        .iter()
+        .map(|r| r["rank"].as_u64().unwrap())
+        .collect();
+    assert_eq!(ranks, (0..10).collect::<Vec<u64>>());
+    let values: Vec<i64> = results
+        .iter()
+        .map(|r| r["attributes"]["score"].as_i64().unwrap())
+        .collect();
+    assert!(values.windows(2).all(|w| w[0] <= w[1]), "{values:?}");
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_query_explain_reports_plan() {
+    let mut config = Config::load(None).unwrap();
+    config.indexing.default_num_centroids = 4;
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(Some(config)).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-explain");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    let vectors = with_attributes(random_vectors(100, 8), simple_attributes);
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": random_vectors(5, 8) }))
+        .send()
+        .await
+        .unwrap();
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query/explain"))
+        .json(&serde_json::json!({
+            "vector": vectors[0].values,
+            "top_k": 5,
+            "nprobe": 2,
+            "filter": {"op": "and", "filters": [
+                {"op": "eq", "field": "category", "value": "a"},
+                {"op": "range", "field": "score", "gte": 10.0},
+                {"op": "contains_all_tokens", "field": "title", "tokens": ["x"]},
+            ]},
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let plan: serde_json::Value = resp.json().await.unwrap();
+
+    assert_eq!(plan["consistency"], "strong");
+    assert_eq!(plan["wal"], "scan");
+    assert_eq!(plan["wal_fragments"], 1);
+    let segment = &plan["segment"];
+    assert_eq!(segment["cluster_count"], 4);
+    assert_eq!(segment["requested_nprobe"], 2);
+    assert_eq!(segment["nprobe"], 2);
+    let probed = segment["probed_clusters"].as_array().unwrap().len();
+    let pruned = segment["pruned_clusters"].as_array().unwrap().len();
+    assert_eq!(probed + pruned, 2);
+
+    let clauses = plan["filter"].as_array().unwrap();
+    assert_eq!(clauses.len(), 3);
+    assert_eq!(clauses[0]["field"], "category");
+    assert_eq!(clauses[0]["bitmap_prefilter"], true);
+    assert_eq!(clauses[0]["range_pruning"], false);
+    assert_eq!(clauses[1]["field"], "score");
+    assert_eq!(clauses[1]["op"], "range");
+    assert_eq!(clauses[1]["range_pruning"], true);
+    assert_eq!(clauses[2]["bitmap_prefilter"], false);
+    assert_eq!(clauses[2]["range_pruning"], false);
+
+    // Eventual reads skip the WAL; a non-vector query can't be explained.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query/explain"))
+        .json(&serde_json::json!({
+            "vector": vectors[0].values,
+            "consistency": "eventual",
+        }))
+        .send()
+        .await
+        .unwrap();
+    let plan: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(plan["wal"], "skip");
+    assert_eq!(plan["wal_fragments"], 0);
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query/explain"))
+        .json(&serde_json::json!({"vectors": [vectors[0].values]}))
         .send()
         .await
         .unwrap();
@@ -350,3 +1062,3484 @@ async fn test_query_dimension_mismatch() {
     cleanup_ns(&harness.store, &ns).await;
     harness.cleanup().await;
 }
+
+#[tokio::test]
+async fn test_query_with_filter() {
+    let (base_url, harness) = start_test_server().await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-filter");
+
+    // Create namespace
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 4,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // Upsert vectors with attributes
+    let vectors = serde_json::json!({
+        "vectors": [
+            {"id": "v1", "values": [1.0, 0.0, 0.0, 0.0], "attributes": {"category": "a"}},
+            {"id": "v2", "values": [0.9, 0.1, 0.0, 0.0], "attributes": {"category": "b"}},
+            {"id": "v3", "values": [0.8, 0.2, 0.0, 0.0], "attributes": {"category": "a"}},
+        ]
+    });
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&vectors)
+        .send()
+        .await
+        .unwrap();
+
+    // Query with filter for category=a
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({
+            "vector": [1.0, 0.0, 0.0, 0.0],
+            "top_k": 10,
+            "filter": {"op": "eq", "field": "category", "value": "a"},
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let results = body["results"].as_array().unwrap();
+    // Should only contain v1 and v3 (category=a)
+    assert_eq!(results.len(), 2);
+    let ids: Vec<&str> = results.iter().map(|r| r["id"].as_str().unwrap()).collect();
+    assert!(ids.contains(&"v1"));
+    assert!(ids.contains(&"v3"));
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_query_empty_namespace() {
+    let (base_url, harness) = start_test_server().await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-empty");
+
+    // Create namespace
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 4,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // Query empty namespace
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({
+            "vector": [1.0, 0.0, 0.0, 0.0],
+            "top_k": 5,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let results = body["results"].as_array().unwrap();
+    assert!(results.is_empty());
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_query_dimension_mismatch() {
+    let (base_url, harness) = start_test_server().await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-qdim");
+
+    // Create namespace with dim=4
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 4,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // Query with wrong dimension
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({
+            "vector": [1.0, 0.0],
+            "top_k": 5,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_query_missing_namespace_404_before_validation() {
+    let (base_url, harness) = start_test_server().await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-qmissing");
+
+    // top_k = 0 and a missing vector/rank_by would each be a 400 on an
+    // existing namespace; the missing namespace must win.
+    for body in [
+        serde_json::json!({ "vector": [1.0, 0.0], "top_k": 0 }),
+        serde_json::json!({ "top_k": 5 }),
+    ] {
+        let resp = client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 404);
+    }
+
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_compact_endpoint_reports_recall() {
+    let mut config = Config::load(None).unwrap();
+    config.indexing.default_num_centroids = 8;
+    config.indexing.default_nprobe = 4;
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(Some(config)).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-compact-verify");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 32,
+            "distance_metric": "euclidean",
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let (vectors, _) = clustered_vectors(8, 50, 32, 0.05);
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .json(&serde_json::json!({ "verify": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["vectors_compacted"], 400);
+    let recall = body["recall_estimate"].as_f64().unwrap();
+    assert!(recall >= 0.9, "recall estimate too low: {recall}");
+
+    // Without a body nothing is left to compact and no estimate is returned.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["fragments_removed"], 0);
+    assert!(body.get("recall_estimate").is_none());
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_namespace_storage_usage_is_opt_in() {
+    let mut config = Config::load(None).unwrap();
+    config.indexing.default_num_centroids = 4;
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(Some(config)).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-storage-usage");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    for _ in 0..2 {
+        let resp = client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&serde_json::json!({ "vectors": random_vectors(20, 8) }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+
+    let usage = || async {
+        let resp = client
+            .get(format!(
+                "{base_url}/v1/namespaces/{ns}?include_storage=true"
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        (
+            body["s3_object_count"].as_u64().unwrap(),
+            body["s3_total_bytes"].as_u64().unwrap(),
+        )
+    };
+
+    // meta.json, the manifest and one WAL fragment per upsert.
+    let (count, bytes) = usage().await;
+    assert_eq!(count, 4);
+    assert!(bytes > 0);
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // The segment adds centroids plus per-cluster objects; compacted WAL
+    // fragments are still counted until they are garbage collected.
+    let manifest = Manifest::read(&harness.store, &ns).await.unwrap().unwrap();
+    let objects_under = |dir: &'static str| {
+        let prefix = format!("{ns}/{dir}/");
+        let store = harness.store.clone();
+        async move { store.list_prefix(&prefix).await.unwrap().len() as u64 }
+    };
+    let segment_objects = objects_under("segments").await;
+    let wal_objects = objects_under("wal").await;
+    assert!(segment_objects > manifest.segments[0].cluster_count as u64);
+    let (count, _) = usage().await;
+    assert_eq!(count, 2 + segment_objects + wal_objects);
+
+    // Without the parameter the listing is skipped.
+    let body: serde_json::Value = client
+        .get(format!("{base_url}/v1/namespaces/{ns}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(body.get("s3_object_count").is_none());
+    assert!(body.get("s3_total_bytes").is_none());
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_namespace_index_parameters() {
+    let mut config = Config::load(None).unwrap();
+    config.indexing.default_num_centroids = 4;
+    config.indexing.default_nprobe = 16;
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(Some(config)).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-index-params");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 8,
+            "distance_metric": "euclidean",
+            "index_type": "ivf_sq",
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let index_params = || async {
+        let resp = client
+            .get(format!("{base_url}/v1/namespaces/{ns}/index"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        resp.json::<serde_json::Value>().await.unwrap()
+    };
+
+    // Before compaction the namespace config decides the quantization.
+    let body = index_params().await;
+    assert!(body["segment_id"].is_null());
+    assert_eq!(body["cluster_count"], 0);
+    assert_eq!(body["quantization"], "scalar");
+    assert_eq!(body["distance_metric"], "euclidean");
+
+    let (vectors, _) = clustered_vectors(4, 20, 8, 0.05);
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors }))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let manifest = Manifest::read(&harness.store, &ns).await.unwrap().unwrap();
+    let segment = &manifest.segments[0];
+    let body = index_params().await;
+    assert_eq!(body["segment_id"], segment.id.as_str());
+    assert_eq!(body["cluster_count"], segment.cluster_count);
+    assert_eq!(body["vector_count"], 80);
+    assert_eq!(body["quantization"], "scalar");
+    // The default nprobe of 16 is capped at the segment's cluster count.
+    assert_eq!(body["recommended_nprobe"], segment.cluster_count);
+
+    let resp = client
+        .get(format!("{base_url}/v1/namespaces/missing-ns/index"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_soft_deadline_returns_partial_results() {
+    let mut config = Config::load(None).unwrap();
+    config.indexing.default_num_centroids = 16;
+    config.query.soft_deadline_ms = Some(0);
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(Some(config)).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-soft-deadline");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 16,
+            "distance_metric": "euclidean",
+        }))
+        .send()
+        .await
+        .unwrap();
+    let (vectors, centroids) = clustered_vectors(16, 20, 16, 0.05);
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors }))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // Probing all 16 clusters with an already-expired deadline scans only
+    // the closest one.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({
+            "vector": centroids[0],
+            "top_k": 100,
+            "nprobe": 16,
+            "consistency": "eventual",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["warnings"], serde_json::json!(["partial_results"]));
+    let results = body["results"].as_array().unwrap();
+    assert!(!results.is_empty());
+    assert!(
+        results.len() < 100,
+        "expected a partial scan, got {}",
+        results.len()
+    );
+    assert!(results[0]["id"].as_str().unwrap().starts_with("cluster_0_"));
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_multi_vector_maxsim_query() {
+    let (base_url, harness) = start_test_server().await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-maxsim");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 4,
+            "distance_metric": "euclidean",
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // "both" is moderately close to each query vector; "a" and "b" are each
+    // an exact match for one query vector and far from the other.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": [
+            {"id": "a", "values": [1.0, 0.0, 0.0, 0.0]},
+            {"id": "b", "values": [0.0, 1.0, 0.0, 0.0]},
+            {"id": "both", "values": [0.6, 0.6, 0.0, 0.0]},
+            {"id": "far", "values": [0.0, 0.0, 5.0, 5.0]},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // A single-vector query prefers the exact match.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({ "vector": [1.0, 0.0, 0.0, 0.0], "top_k": 3 }))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["results"][0]["id"], "a");
+
+    // Aggregated over both query vectors, "both" wins.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({
+            "vectors": [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0]],
+            "top_k": 3,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0]["id"], "both");
+
+    // Heavy weight on the first query vector brings "a" back to the top.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({
+            "vectors": [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0]],
+            "weights": [10.0, 1.0],
+            "top_k": 3,
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["results"][0]["id"], "a");
+
+    // vector and vectors are mutually exclusive.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({
+            "vector": [1.0, 0.0, 0.0, 0.0],
+            "vectors": [[1.0, 0.0, 0.0, 0.0]],
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_small_upserts_are_coalesced_into_few_fragments() {
+    let mut config = Config::load(None).unwrap();
+    config.server.max_concurrent_upserts_per_ns = 1000;
+    config.wal.coalesce_window_ms = Some(50);
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(Some(config)).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-coalesce");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({ "name": ns, "dimensions": 8 }))
+        .send()
+        .await
+        .unwrap();
+
+    let writes = 64;
+    let vectors = random_vectors(writes, 8);
+    let requests = vectors.iter().map(|v| {
+        client
+            .put(format!("{base_url}/v1/namespaces/{ns}/vectors/{}", v.id))
+            .json(&serde_json::json!({ "values": v.values }))
+            .send()
+    });
+    for resp in futures::future::join_all(requests).await {
+        assert_eq!(resp.unwrap().status(), 200);
+    }
+
+    let manifest = Manifest::read(&harness.store, &ns).await.unwrap().unwrap();
+    assert!(
+        manifest.fragments.len() <= writes / 8,
+        "{} fragments for {writes} writes",
+        manifest.fragments.len()
+    );
+
+    let query = |vector: &Vec<f32>| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&serde_json::json!({
+                "vector": vector,
+                "top_k": writes,
+                "consistency": "strong",
+            }))
+            .send()
+    };
+
+    // Every acknowledged write is visible to a strong read straight away.
+    let body: serde_json::Value = query(&vectors[0].values)
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["results"].as_array().unwrap().len(), writes);
+
+    // A delete coalesced after an upsert of the same ID still wins.
+    let id = &vectors[1].id;
+    let (put, delete) = tokio::join!(
+        client
+            .put(format!("{base_url}/v1/namespaces/{ns}/vectors/{id}"))
+            .json(&serde_json::json!({ "values": vectors[1].values }))
+            .send(),
+        async {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            client
+                .delete(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+                .json(&serde_json::json!({ "ids": [id] }))
+                .send()
+                .await
+        }
+    );
+    assert_eq!(put.unwrap().status(), 200);
+    assert_eq!(delete.unwrap().status(), 200);
+    let body: serde_json::Value = query(&vectors[1].values)
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), writes - 1);
+    assert!(results.iter().all(|r| r["id"] != id.as_str()));
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_async_ack_upsert_returns_202_and_lands() {
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(None).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-async-ack");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({ "name": ns, "dimensions": 8 }))
+        .send()
+        .await
+        .unwrap();
+
+    let vectors = random_vectors(20, 8);
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors, "async_ack": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 202);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["upserted"], 20);
+
+    // Not necessarily visible yet, but it lands shortly after.
+    let mut found = 0;
+    for _ in 0..100 {
+        let body: serde_json::Value = client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&serde_json::json!({
+                "vector": vectors[0].values,
+                "top_k": 20,
+                "consistency": "strong",
+            }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        found = body["results"].as_array().unwrap().len();
+        if found == 20 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert_eq!(found, 20, "async-acked vectors never became queryable");
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_upserts_over_limit_get_429() {
+    let mut config = Config::load(None).unwrap();
+    config.server.max_concurrent_upserts_per_ns = 1;
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(Some(config)).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-upsert-limit");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({ "name": ns, "dimensions": 16 }))
+        .send()
+        .await
+        .unwrap();
+
+    let body = serde_json::json!({ "vectors": random_vectors(500, 16) });
+    let requests = (0..16).map(|_| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&body)
+            .send()
+    });
+    let statuses: Vec<u16> = futures::future::join_all(requests)
+        .await
+        .into_iter()
+        .map(|resp| resp.unwrap().status().as_u16())
+        .collect();
+
+    assert!(
+        statuses.iter().all(|s| *s == 200 || *s == 429),
+        "{statuses:?}"
+    );
+    assert!(statuses.contains(&200), "{statuses:?}");
+    assert!(statuses.contains(&429), "{statuses:?}");
+
+    // Once the burst is over the namespace accepts upserts again.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_compact_all_compacts_namespaces_over_threshold() {
+    let mut config = Config::load(None).unwrap();
+    config.compaction.max_wal_fragments_before_compact = 2;
+    config.indexing.default_num_centroids = 4;
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(Some(config)).await;
+    let client = reqwest::Client::new();
+    let over = [
+        api_ns(&harness, "compact-all-a"),
+        api_ns(&harness, "compact-all-b"),
+    ];
+    let under = api_ns(&harness, "compact-all-under");
+    let broken = api_ns(&harness, "compact-all-broken");
+
+    for ns in over.iter().chain([&under, &broken]) {
+        let resp = client
+            .post(format!("{base_url}/v1/namespaces"))
+            .json(&serde_json::json!({ "name": ns, "dimensions": 8 }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 201);
+    }
+    for ns in &over {
+        for _ in 0..2 {
+            let resp = client
+                .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+                .json(&serde_json::json!({ "vectors": random_vectors(20, 8) }))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), 200);
+        }
+    }
+    client
+        .post(format!("{base_url}/v1/namespaces/{under}/vectors"))
+        .json(&serde_json::json!({ "vectors": random_vectors(5, 8) }))
+        .send()
+        .await
+        .unwrap();
+    // An unreadable manifest fails this namespace without stopping the rest.
+    harness
+        .store
+        .put(&Manifest::s3_key(&broken), "not json".into())
+        .await
+        .unwrap();
+
+    let resp = client
+        .post(format!("{base_url}/v1/admin/compact-all"))
+        .json(&serde_json::json!({ "prefix": harness.prefix }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["namespaces_checked"], 4, "got: {body}");
+
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), 3, "got: {body}");
+    let entry = |ns: &str| {
+        results
+            .iter()
+            .find(|r| r["namespace"] == ns)
+            .unwrap_or_else(|| panic!("no result for {ns}: {body}"))
+    };
+    for ns in &over {
+        let r = entry(ns);
+        // Both batches reuse the same 20 IDs.
+        assert_eq!(r["vectors_compacted"], 20);
+        assert!(r["segment_id"].is_string());
+        assert!(r.get("error").is_none());
+    }
+    assert!(entry(&broken)["error"].is_string());
+    assert!(results.iter().all(|r| r["namespace"] != under.as_str()));
+
+    for ns in over.iter().chain([&under, &broken]) {
+        cleanup_ns(&harness.store, ns).await;
+    }
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_admin_verify_reports_missing_segment_artifact() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store.clone()).await;
+    let client = reqwest::Client::new();
+    let ns = "api-verify";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": random_vectors(60, 8) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let verify = || async {
+        let resp = client
+            .post(format!("{base_url}/v1/admin/namespaces/{ns}/verify"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        resp.json::<serde_json::Value>().await.unwrap()
+    };
+    let report = verify().await;
+    assert_eq!(report["segment_ids_read"], 60, "got: {report}");
+    assert_eq!(report["inconsistencies"], serde_json::json!([]));
+
+    let manifest = Manifest::read(&store, ns).await.unwrap().unwrap();
+    let seg_id = manifest.active_segment.unwrap();
+    let missing = format!("{ns}/segments/{seg_id}/cluster_0.bin");
+    store.delete(&missing).await.unwrap();
+    let stray = format!("{ns}/wal/{}.wal", ulid::Ulid::new());
+    store.put(&stray, "stray".into()).await.unwrap();
+
+    let report = verify().await;
+    let issues = report["inconsistencies"].as_array().unwrap();
+    assert!(
+        issues
+            .iter()
+            .any(|i| i["kind"] == "missing_artifact" && i["key"] == missing.as_str()),
+        "got: {report}"
+    );
+    assert!(
+        issues.iter().any(|i| i["kind"] == "vector_count_mismatch"),
+        "got: {report}"
+    );
+    assert!(
+        issues.iter().any(|i| i["kind"] == "orphaned_fragment"
+            && i["key"] == stray.as_str()
+            && i["compacted"] == false),
+        "got: {report}"
+    );
+
+    let resp = client
+        .post(format!(
+            "{base_url}/v1/admin/namespaces/{ns}-missing/verify"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_query_fans_out_across_namespaces() {
+    let (base_url, harness) = start_test_server().await;
+    let client = reqwest::Client::new();
+    let ns_a = api_ns(&harness, "fanout-a");
+    let ns_b = api_ns(&harness, "fanout-b");
+    let ns_wide = api_ns(&harness, "fanout-wide");
+
+    for (ns, dims) in [(&ns_a, 4), (&ns_b, 4), (&ns_wide, 8)] {
+        client
+            .post(format!("{base_url}/v1/namespaces"))
+            .json(&serde_json::json!({
+                "name": ns,
+                "dimensions": dims,
+                "distance_metric": "euclidean",
+            }))
+            .send()
+            .await
+            .unwrap();
+    }
+    for (ns, vectors) in [
+        (
+            &ns_a,
+            serde_json::json!([
+                {"id": "a1", "values": [5.0, 5.0, 5.0, 5.0]},
+                {"id": "a2", "values": [0.5, 0.0, 0.0, 0.0]},
+            ]),
+        ),
+        (
+            &ns_b,
+            serde_json::json!([
+                {"id": "b1", "values": [0.0, 0.0, 0.0, 0.1]},
+                {"id": "b2", "values": [9.0, 9.0, 9.0, 9.0]},
+            ]),
+        ),
+    ] {
+        let resp = client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&serde_json::json!({ "vectors": vectors }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+
+    let resp = client
+        .post(format!("{base_url}/v1/query"))
+        .json(&serde_json::json!({
+            "namespaces": [ns_a, ns_b],
+            "vector": [0.0, 0.0, 0.0, 0.0],
+            "top_k": 3,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let results = body["results"].as_array().unwrap();
+    let tagged: Vec<(&str, &str)> = results
+        .iter()
+        .map(|r| (r["namespace"].as_str().unwrap(), r["id"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        tagged,
+        [
+            (ns_b.as_str(), "b1"),
+            (ns_a.as_str(), "a2"),
+            (ns_a.as_str(), "a1")
+        ]
+    );
+
+    // Namespaces with different dimensions can't be ranked together.
+    let resp = client
+        .post(format!("{base_url}/v1/query"))
+        .json(&serde_json::json!({
+            "namespaces": [ns_a, ns_wide],
+            "vector": [0.0, 0.0, 0.0, 0.0],
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert!(
+        body["error"].as_str().unwrap().contains("does not match"),
+        "got: {body}"
+    );
+
+    for ns in [&ns_a, &ns_b, &ns_wide] {
+        cleanup_ns(&harness.store, ns).await;
+    }
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_query_no_cache_rereads_segment_from_storage() {
+    let mut config = Config::load(None).unwrap();
+    config.indexing.default_num_centroids = 2;
+    let (base_url, harness, cache, _dir, compactor) =
+        start_test_server_with_compactor(Some(config)).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-no-cache");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": random_vectors(20, 8) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    compactor.compact(&ns).await.unwrap();
+
+    let query = |no_cache: bool, header: Option<&'static str>| {
+        let mut req = client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&serde_json::json!({
+                "vector": vec![0.5f32; 8],
+                "top_k": 5,
+                "nprobe": 2,
+                "no_cache": no_cache,
+            }));
+        if let Some(value) = header {
+            req = req.header("cache-control", value);
+        }
+        async move {
+            let resp = req.send().await.unwrap();
+            assert_eq!(resp.status(), 200);
+            let body: serde_json::Value = resp.json().await.unwrap();
+            body["results"].as_array().unwrap().len()
+        }
+    };
+
+    // Probing every cluster caches all of their blobs.
+    assert_eq!(query(false, None).await, 5);
+    let segment_id = Manifest::read(&harness.store, &ns)
+        .await
+        .unwrap()
+        .unwrap()
+        .active_segment
+        .unwrap();
+    let cluster_keys: Vec<String> = harness
+        .store
+        .list_prefix(&format!("{ns}/segments/{segment_id}/"))
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|k| k.contains("/cluster_"))
+        .collect();
+    assert!(!cluster_keys.is_empty());
+    for key in &cluster_keys {
+        assert!(cache.contains(key).await, "{key} not cached");
+    }
+
+    // With the blobs gone from storage, only cached reads still find
+    // vectors: missing clusters are skipped.
+    for key in &cluster_keys {
+        harness.store.delete(key).await.unwrap();
+    }
+    assert_eq!(query(false, None).await, 5);
+    assert_eq!(query(true, None).await, 0);
+    assert_eq!(query(false, Some("max-age=0, no-cache")).await, 0);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_query_best_result_first_for_each_metric() {
+    let (base_url, harness, _cache, _dir, compactor) = start_test_server_with_compactor(None).await;
+    let client = reqwest::Client::new();
+
+    // For query [1, 0]: "near" is closest under every metric, and has the
+    // largest dot product.
+    let vectors = serde_json::json!([
+        {"id": "near", "values": [1.5, 0.1]},
+        {"id": "mid", "values": [1.0, 1.0]},
+        {"id": "far", "values": [-1.0, 0.5]},
+    ]);
+    for metric in ["cosine", "euclidean", "dot_product"] {
+        let ns = api_ns(
+            &harness,
+            &format!("api-metric-{}", metric.replace('_', "-")),
+        );
+        client
+            .post(format!("{base_url}/v1/namespaces"))
+            .json(&serde_json::json!({"name": ns, "dimensions": 2, "distance_metric": metric}))
+            .send()
+            .await
+            .unwrap();
+        let resp = client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&serde_json::json!({ "vectors": vectors }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+
+        // First from the WAL scan, then from the compacted segment.
+        for consistency in ["strong", "eventual"] {
+            if consistency == "eventual" {
+                compactor.compact(&ns).await.unwrap();
+            }
+            let body: serde_json::Value = client
+                .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+                .json(&serde_json::json!({
+                    "vector": [1.0, 0.0],
+                    "top_k": 3,
+                    "consistency": consistency,
+                }))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            let results = body["results"].as_array().unwrap();
+            let ids: Vec<&str> = results.iter().map(|r| r["id"].as_str().unwrap()).collect();
+            assert_eq!(ids, ["near", "mid", "far"], "{metric} {consistency}");
+            if metric == "dot_product" {
+                let best = results[0]["score"].as_f64().unwrap();
+                assert!((best - 1.5).abs() < 1e-5, "dot product score {best}");
+            }
+        }
+
+        cleanup_ns(&harness.store, &ns).await;
+    }
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_count_by_groups_live_vectors() {
+    let mut config = Config::load(None).unwrap();
+    config.indexing.default_num_centroids = 2;
+    let (base_url, harness, _cache, _dir, compactor) =
+        start_test_server_with_compactor(Some(config)).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-count-by");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    let vectors = with_attributes(random_vectors(30, 8), simple_attributes);
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    compactor.compact(&ns).await.unwrap();
+
+    // WAL writes on top of the segment: vec_0 ("a") is deleted and vec_1
+    // ("b") moves to "c".
+    client
+        .delete(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({"ids": ["vec_0"]}))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .put(format!("{base_url}/v1/namespaces/{ns}/vectors/vec_1"))
+        .json(&serde_json::json!({
+            "values": vec![0.5f32; 8],
+            "attributes": {"category": "c", "score": 1},
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let count_by = |body: serde_json::Value| {
+        let req = client
+            .post(format!("{base_url}/v1/namespaces/{ns}/count-by"))
+            .json(&body);
+        async move {
+            let resp = req.send().await.unwrap();
+            let status = resp.status();
+            (status, resp.json::<serde_json::Value>().await.unwrap())
+        }
+    };
+
+    let (status, body) = count_by(serde_json::json!({"field": "category"})).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["counts"], serde_json::json!({"a": 9, "b": 9, "c": 11}));
+    assert_eq!(body["truncated"], false);
+
+    let (_, body) = count_by(serde_json::json!({
+        "field": "category",
+        "filter": {"op": "range", "field": "score", "gte": 15},
+    }))
+    .await;
+    assert_eq!(body["counts"], serde_json::json!({"a": 5, "b": 5, "c": 5}));
+
+    let (_, body) = count_by(serde_json::json!({"field": "category", "limit": 1})).await;
+    assert_eq!(body["counts"], serde_json::json!({"c": 11}));
+    assert_eq!(body["truncated"], true);
+
+    let (status, _) = count_by(serde_json::json!({"field": ""})).await;
+    assert_eq!(status, 400);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_tombstones_listed_until_compaction() {
+    let (base_url, harness, _cache, _dir, compactor) = start_test_server_with_compactor(None).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "api-tombstones");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": random_vectors(10, 8) }))
+        .send()
+        .await
+        .unwrap();
+    client
+        .delete(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({"ids": ["vec_7", "vec_3", "vec_1"]}))
+        .send()
+        .await
+        .unwrap();
+    // Re-upserted after its delete, so no longer a tombstone.
+    client
+        .put(format!("{base_url}/v1/namespaces/{ns}/vectors/vec_7"))
+        .json(&serde_json::json!({"values": vec![0.5f32; 8]}))
+        .send()
+        .await
+        .unwrap();
+
+    let list = |query: &'static str| {
+        let req = client.get(format!("{base_url}/v1/namespaces/{ns}/tombstones{query}"));
+        async move {
+            let resp = req.send().await.unwrap();
+            assert_eq!(resp.status(), 200);
+            resp.json::<serde_json::Value>().await.unwrap()
+        }
+    };
+
+    let body = list("").await;
+    assert_eq!(body["ids"], serde_json::json!(["vec_1", "vec_3"]));
+    assert!(body.get("next_cursor").is_none());
+
+    let body = list("?limit=1").await;
+    assert_eq!(body["ids"], serde_json::json!(["vec_1"]));
+    assert_eq!(body["next_cursor"], "vec_1");
+    let body = list("?limit=1&cursor=vec_1").await;
+    assert_eq!(body["ids"], serde_json::json!(["vec_3"]));
+    assert!(body.get("next_cursor").is_none());
+
+    let resp = client
+        .get(format!("{base_url}/v1/namespaces/{ns}/tombstones?limit=0"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    compactor.compact(&ns).await.unwrap();
+    let body = list("").await;
+    assert_eq!(body["ids"], serde_json::json!([]));
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_get_vector_by_id() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store.clone()).await;
+    let client = reqwest::Client::new();
+    let ns = "api-get-vector";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    let vectors = with_attributes(random_vectors(60, 8), simple_attributes);
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors }))
+        .send()
+        .await
+        .unwrap();
+
+    let get = |id: &str| {
+        client
+            .get(format!("{base_url}/v1/namespaces/{ns}/vectors/{id}"))
+            .send()
+    };
+    let check = |body: VectorEntry| {
+        assert_eq!(body.id, vectors[7].id);
+        assert_eq!(body.values, vectors[7].values);
+        assert_eq!(body.attributes, vectors[7].attributes);
+    };
+
+    // From the WAL, then from the compacted segment.
+    let resp = get("vec_7").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    check(resp.json().await.unwrap());
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = get("vec_7").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    check(resp.json().await.unwrap());
+
+    // A delete in the WAL hides the compacted copy.
+    client
+        .delete(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "ids": ["vec_7"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(get("vec_7").await.unwrap().status(), 404);
+    assert_eq!(get("missing").await.unwrap().status(), 404);
+
+    let resp = client
+        .get(format!(
+            "{base_url}/v1/namespaces/{ns}-missing/vectors/vec_0"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_vector_ids_named_like_verb_routes() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store.clone()).await;
+    let client = reqwest::Client::new();
+    let ns = "api-verb-ids";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 4}))
+        .send()
+        .await
+        .unwrap();
+
+    // Every verb route's name works as a vector ID on the single-vector
+    // routes.
+    let ids = ["fetch", "count-by", "delete_by_filter", "update_by_filter"];
+    for id in ids {
+        let url = format!("{base_url}/v1/namespaces/{ns}/vectors/{id}");
+        let resp = client
+            .put(&url)
+            .json(&serde_json::json!({"values": [1.0, 0.0, 0.0, 0.0]}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200, "PUT {id}");
+        let resp = client
+            .patch(&url)
+            .json(&serde_json::json!({"attributes": {"name": id}}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200, "PATCH {id}");
+        let resp = client.get(&url).send().await.unwrap();
+        assert_eq!(resp.status(), 200, "GET {id}");
+        let body: VectorEntry = resp.json().await.unwrap();
+        assert_eq!(body.id, id);
+    }
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/fetch"))
+        .json(&serde_json::json!({ "ids": ids }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["vectors"].as_array().unwrap().len(), ids.len());
+}
+
+#[tokio::test]
+async fn test_fetch_vectors_by_ids() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store.clone()).await;
+    let client = reqwest::Client::new();
+    let ns = "api-fetch-vectors";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    let vectors = with_attributes(random_vectors(80, 8), simple_attributes);
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors }))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // One WAL rewrite and one WAL delete on top of the segment.
+    let rewritten = VectorEntry {
+        id: "vec_3".to_string(),
+        values: vec![0.25; 8],
+        attributes: None,
+        sparse: None,
+        named_vectors: None,
+        token_vectors: None,
+        version: None,
+    };
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": [rewritten] }))
+        .send()
+        .await
+        .unwrap();
+    client
+        .delete(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "ids": ["vec_5"] }))
+        .send()
+        .await
+        .unwrap();
+
+    let fetch = |ids: serde_json::Value| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/fetch"))
+            .json(&serde_json::json!({ "ids": ids }))
+            .send()
+    };
+    let resp = fetch(serde_json::json!([
+        "vec_70", "vec_3", "missing", "vec_5", "vec_0", "vec_70"
+    ]))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let got: Vec<VectorEntry> = serde_json::from_value(body["vectors"].clone()).unwrap();
+    let ids: Vec<&str> = got.iter().map(|v| v.id.as_str()).collect();
+    assert_eq!(ids, ["vec_70", "vec_3", "vec_0"]);
+    assert_eq!(got[0].values, vectors[70].values);
+    assert_eq!(got[0].attributes, vectors[70].attributes);
+    assert_eq!(got[1].values, rewritten.values);
+    assert_eq!(got[1].attributes, None);
+    assert_eq!(got[2].attributes, vectors[0].attributes);
+
+    assert_eq!(fetch(serde_json::json!([])).await.unwrap().status(), 400);
+}
+
+#[tokio::test]
+async fn test_list_vectors_paginates_in_id_order() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store.clone()).await;
+    let client = reqwest::Client::new();
+    let ns = "api-list-vectors";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    let vectors = random_vectors(50, 8);
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors }))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // WAL writes on top of the segment: new IDs, a rewrite and deletes.
+    let mut extra: Vec<VectorEntry> = random_vectors(5, 8)
+        .into_iter()
+        .map(|mut v| {
+            v.id = format!("new_{}", v.id);
+            v
+        })
+        .collect();
+    extra.push(VectorEntry {
+        id: "vec_10".to_string(),
+        values: vec![0.5; 8],
+        attributes: None,
+        sparse: None,
+        named_vectors: None,
+        token_vectors: None,
+        version: None,
+    });
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": extra }))
+        .send()
+        .await
+        .unwrap();
+    client
+        .delete(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "ids": ["vec_0", "new_vec_1"] }))
+        .send()
+        .await
+        .unwrap();
+
+    let mut listed: Vec<VectorEntry> = Vec::new();
+    let mut cursor: Option<String> = None;
+    let mut pages = 0;
+    loop {
+        let mut req = client
+            .get(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .query(&[("limit", "7")]);
+        if let Some(c) = &cursor {
+            req = req.query(&[("cursor", c)]);
+        }
+        let resp = req.send().await.unwrap();
+        assert_eq!(resp.status(), 200);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        let page: Vec<VectorEntry> = serde_json::from_value(body["vectors"].clone()).unwrap();
+        assert!(page.len() <= 7);
+        listed.extend(page);
+        pages += 1;
+        match body["next_cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+
+    let mut expected: Vec<String> = vectors
+        .iter()
+        .chain(&extra)
+        .map(|v| v.id.clone())
+        .filter(|id| id != "vec_0" && id != "new_vec_1")
+        .collect();
+    expected.sort();
+    expected.dedup();
+    let ids: Vec<&String> = listed.iter().map(|v| &v.id).collect();
+    assert_eq!(ids, expected.iter().collect::<Vec<_>>());
+    assert_eq!(pages, expected.len().div_ceil(7));
+    let rewritten = listed.iter().find(|v| v.id == "vec_10").unwrap();
+    assert_eq!(rewritten.values, vec![0.5; 8]);
+    let from_segment = listed.iter().find(|v| v.id == "vec_20").unwrap();
+    assert_eq!(from_segment.values, vectors[20].values);
+
+    let resp = client
+        .get(format!("{base_url}/v1/namespaces/{ns}/vectors?limit=0"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_delete_by_filter() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store.clone()).await;
+    let client = reqwest::Client::new();
+    let ns = "api-delete-by-filter";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    // 30 segment vectors, ten per category.
+    let vectors = with_attributes(random_vectors(30, 8), simple_attributes);
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors }))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // In the WAL: vec_1 moves into category "a", vec_0 leaves it, and one
+    // "a" vector is already deleted.
+    let moved = |id: &str, category: &str| VectorEntry {
+        id: id.to_string(),
+        values: vec![0.5; 8],
+        attributes: Some(std::collections::HashMap::from([(
+            "category".to_string(),
+            AttributeValue::String(category.to_string()),
+        )])),
+        sparse: None,
+        named_vectors: None,
+        token_vectors: None,
+        version: None,
+    };
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": [moved("vec_1", "a"), moved("vec_0", "b")] }))
+        .send()
+        .await
+        .unwrap();
+    client
+        .delete(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "ids": ["vec_3"] }))
+        .send()
+        .await
+        .unwrap();
+
+    let delete = |filter: serde_json::Value| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/delete_by_filter"))
+            .json(&serde_json::json!({ "filter": filter }))
+            .send()
+    };
+    let resp = delete(serde_json::json!({"op": "eq", "field": "category", "value": "a"}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    // vec_6..vec_27 step 3 (8 vectors) plus vec_1.
+    assert_eq!(body["deleted"], 9);
+
+    let resp = client
+        .get(format!("{base_url}/v1/namespaces/{ns}/vectors?limit=1000"))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let remaining: Vec<VectorEntry> = serde_json::from_value(body["vectors"].clone()).unwrap();
+    assert_eq!(remaining.len(), 20);
+    assert!(remaining.iter().all(|v| {
+        v.attributes.as_ref().unwrap()["category"] != AttributeValue::String("a".into())
+    }));
+    assert!(remaining.iter().any(|v| v.id == "vec_0"));
+
+    // Nothing left to match.
+    let body: serde_json::Value =
+        delete(serde_json::json!({"op": "eq", "field": "category", "value": "a"}))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+    assert_eq!(body["deleted"], 0);
+
+    let resp = delete(serde_json::json!({"op": "bogus", "field": "category"}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_delete_by_filter_splits_batches_at_max_batch_size() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let mut config = Config::load(None).unwrap();
+    config.server.max_batch_size = 10;
+    let (base_url, _dir) = start_test_server_with_store_and_config(store.clone(), config).await;
+    let client = reqwest::Client::new();
+    let ns = "api-delete-by-filter-batches";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    let vectors = with_attributes(random_vectors(25, 8), simple_attributes);
+    for batch in vectors.chunks(10) {
+        let resp = client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&serde_json::json!({ "vectors": batch }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/delete_by_filter"))
+        .json(&serde_json::json!({"filter": {"op": "range", "field": "score", "gte": 0}}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["deleted"], 25);
+
+    // 25 matches over a limit of 10: three delete fragments, none larger
+    // than the limit.
+    let manifest = Manifest::read(&store, ns).await.unwrap().unwrap();
+    let deletes: Vec<usize> = manifest
+        .fragments
+        .iter()
+        .map(|f| f.delete_count)
+        .filter(|&n| n > 0)
+        .collect();
+    assert_eq!(deletes.iter().sum::<usize>(), 25);
+    assert_eq!(deletes.len(), 3);
+    assert!(deletes.iter().all(|&n| n <= 10), "{deletes:?}");
+
+    let body: serde_json::Value = client
+        .get(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["vectors"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn test_patch_vector_attributes() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store.clone()).await;
+    let client = reqwest::Client::new();
+    let ns = "api-patch-vector";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    let vectors = with_attributes(random_vectors(30, 8), simple_attributes);
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vectors }))
+        .send()
+        .await
+        .unwrap();
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+
+    let patch = |id: &str, attributes: serde_json::Value| {
+        client
+            .patch(format!("{base_url}/v1/namespaces/{ns}/vectors/{id}"))
+            .json(&serde_json::json!({ "attributes": attributes }))
+            .send()
+    };
+    let get = |id: &str| {
+        let req = client
+            .get(format!("{base_url}/v1/namespaces/{ns}/vectors/{id}"))
+            .send();
+        async move { req.await.unwrap().json::<VectorEntry>().await.unwrap() }
+    };
+    let check = |got: VectorEntry, i: usize, category: &str, extra: Option<&str>| {
+        assert_eq!(got.values, vectors[i].values);
+        let attrs = got.attributes.unwrap();
+        assert_eq!(
+            attrs["category"],
+            AttributeValue::String(category.to_string())
+        );
+        assert_eq!(attrs["score"], AttributeValue::Integer(i as i64));
+        assert_eq!(
+            attrs.get("tag"),
+            extra
+                .map(|t| AttributeValue::String(t.to_string()))
+                .as_ref()
+        );
+    };
+
+    // Patches over a compacted vector, then over the patched version.
+    let resp = patch("vec_4", serde_json::json!({"category": "z"}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["patched"], 1);
+    patch("vec_4", serde_json::json!({"tag": "new"}))
+        .await
+        .unwrap();
+    check(get("vec_4").await, 4, "z", Some("new"));
+
+    // Filters see the merged attributes.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({
+            "vector": vectors[0].values,
+            "top_k": 10,
+            "filter": {"op": "eq", "field": "category", "value": "z"},
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["id"], "vec_4");
+
+    // A patch on a deleted or unknown ID is a no-op.
+    client
+        .delete(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "ids": ["vec_5"] }))
+        .send()
+        .await
+        .unwrap();
+    let resp = patch("vec_5", serde_json::json!({"tag": "gone"}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    patch("missing", serde_json::json!({"tag": "gone"}))
+        .await
+        .unwrap();
+
+    // Compaction folds the patches into the segment.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    check(get("vec_4").await, 4, "z", Some("new"));
+    check(get("vec_6").await, 6, "a", None);
+    for id in ["vec_5", "missing"] {
+        let resp = client
+            .get(format!("{base_url}/v1/namespaces/{ns}/vectors/{id}"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 404);
+    }
+
+    let resp = patch("vec_6", serde_json::json!({})).await.unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_update_by_filter() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store.clone()).await;
+    let client = reqwest::Client::new();
+    let ns = "api-update-by-filter";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    let vectors = with_attributes(random_vectors(32, 8), simple_attributes);
+    let upsert = |batch: &[VectorEntry]| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&serde_json::json!({ "vectors": batch }))
+            .send()
+    };
+    // 0..30 compacted, 30 in the WAL before the update, 31 after it.
+    upsert(&vectors[..30]).await.unwrap();
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    upsert(&vectors[30..31]).await.unwrap();
+
+    let update = |body: serde_json::Value| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/update_by_filter"))
+            .json(&body)
+            .send()
+    };
+    let resp = update(serde_json::json!({
+        "filter": {"op": "eq", "field": "category", "value": "a"},
+        "attributes": {"status": "archived"},
+    }))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 202);
+    upsert(&vectors[31..]).await.unwrap();
+
+    let status = |id: String| {
+        let req = client
+            .get(format!("{base_url}/v1/namespaces/{ns}/vectors/{id}"))
+            .send();
+        async move {
+            let v: VectorEntry = req.await.unwrap().json().await.unwrap();
+            v.attributes.unwrap().get("status").cloned()
+        }
+    };
+    let check = || async {
+        let archived = Some(AttributeValue::String("archived".to_string()));
+        for i in 0..32 {
+            let want = if i % 3 == 0 && i != 31 {
+                &archived
+            } else {
+                &None
+            };
+            assert_eq!(&status(format!("vec_{i}")).await, want, "vec_{i}");
+        }
+    };
+    check().await;
+
+    // Compaction folds the update into the segment.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    check().await;
+
+    let resp = update(serde_json::json!({
+        "filter": {"op": "bogus", "field": "category"},
+        "attributes": {"status": "x"},
+    }))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 400);
+    let resp = update(serde_json::json!({
+        "filter": {"op": "eq", "field": "category", "value": "a"},
+        "attributes": {},
+    }))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_conditional_upsert() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store.clone()).await;
+    let client = reqwest::Client::new();
+    let ns = "api-conditional-upsert";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 4}))
+        .send()
+        .await
+        .unwrap();
+    let upsert = |vectors: serde_json::Value, condition: serde_json::Value| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&serde_json::json!({ "vectors": vectors, "condition": condition }))
+            .send()
+    };
+    let etag = |id: &str| {
+        let req = client
+            .get(format!("{base_url}/v1/namespaces/{ns}/vectors/{id}"))
+            .send();
+        async move {
+            let resp = req.await.unwrap();
+            assert_eq!(resp.status(), 200);
+            resp.headers()["etag"].to_str().unwrap().to_string()
+        }
+    };
+
+    // Create-only: the first write lands, a retry conflicts.
+    let batch = serde_json::json!([
+        {"id": "a", "values": [1.0, 0.0, 0.0, 0.0]},
+        {"id": "b", "values": [0.0, 1.0, 0.0, 0.0]},
+    ]);
+    let resp = upsert(batch.clone(), "if_not_exists".into()).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = upsert(batch, "if_not_exists".into()).await.unwrap();
+    assert_eq!(resp.status(), 409);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let error = body["error"].as_str().unwrap();
+    assert!(
+        error.contains("\"a\"") && error.contains("\"b\""),
+        "{error}"
+    );
+
+    // Compare-and-set against the ETag, also once served from a segment.
+    let before = etag("a").await;
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(etag("a").await, before);
+    let put = |body: serde_json::Value, header: (&'static str, String)| {
+        client
+            .put(format!("{base_url}/v1/namespaces/{ns}/vectors/a"))
+            .header(header.0, header.1)
+            .json(&body)
+            .send()
+    };
+    let v2 = serde_json::json!({"values": [0.5, 0.5, 0.0, 0.0]});
+    let resp = put(v2.clone(), ("if-match", before.clone())).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let after = etag("a").await;
+    assert_ne!(after, before);
+    // The stale version no longer matches.
+    let resp = put(v2, ("if-match", before.clone())).await.unwrap();
+    assert_eq!(resp.status(), 409);
+    let resp = upsert(
+        serde_json::json!([{"id": "a", "values": [0.0, 0.0, 1.0, 0.0]}]),
+        serde_json::json!({"if_version_matches": {"a": after.trim_matches('"')}}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // Writing the original content back is a new version: the first ETag
+    // stays stale.
+    let resp = client
+        .put(format!("{base_url}/v1/namespaces/{ns}/vectors/a"))
+        .json(&serde_json::json!({"values": [1.0, 0.0, 0.0, 0.0]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_ne!(etag("a").await, before);
+    let resp = put(
+        serde_json::json!({"values": [0.0, 1.0, 1.0, 0.0]}),
+        ("if-match", before.clone()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 409);
+
+    // If-None-Match: * creates only new IDs.
+    let v = serde_json::json!({"values": [0.0, 0.0, 0.0, 1.0]});
+    let resp = put(v.clone(), ("if-none-match", "*".into())).await.unwrap();
+    assert_eq!(resp.status(), 409);
+    let resp = client
+        .put(format!("{base_url}/v1/namespaces/{ns}/vectors/c"))
+        .header("if-none-match", "*")
+        .json(&v)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({
+            "vectors": [{"id": "d", "values": [1.0, 0.0, 0.0, 0.0]}],
+            "condition": "if_not_exists",
+            "async_ack": true,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_idempotency_key_dedupes_retries() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store.clone()).await;
+    let client = reqwest::Client::new();
+    let ns = "api-idempotency-key";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+        .send()
+        .await
+        .unwrap();
+    let vectors = random_vectors(5, 8);
+    let upsert = |key: &str, body: serde_json::Value| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .header("idempotency-key", key)
+            .json(&body)
+            .send()
+    };
+    let replayed = |resp: &reqwest::Response| resp.headers().contains_key("idempotent-replayed");
+
+    let body = serde_json::json!({ "vectors": vectors });
+    let resp = upsert("load-1", body.clone()).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(!replayed(&resp));
+    let resp = upsert("load-1", body.clone()).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(replayed(&resp));
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["upserted"], 5);
+
+    let delete = |key: &'static str| {
+        client
+            .delete(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .header("idempotency-key", key)
+            .json(&serde_json::json!({ "ids": ["vec_0", "vec_1"] }))
+            .send()
+    };
+    let resp = delete("del-1").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(!replayed(&resp));
+    // The retry's IDs are all tombstoned by now; it still replays.
+    let resp = delete("del-1").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(replayed(&resp));
+
+    // Reusing a key for a different payload is rejected, not replayed.
+    let resp = upsert(
+        "load-1",
+        serde_json::json!({ "vectors": random_vectors(2, 8) }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 422);
+
+    // One fragment per distinct key.
+    let manifest = Manifest::read(&store, ns).await.unwrap().unwrap();
+    assert_eq!(manifest.fragments.len(), 2);
+
+    let resp = upsert("", serde_json::json!({ "vectors": vectors }))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let resp = upsert(
+        "load-2",
+        serde_json::json!({ "vectors": vectors, "async_ack": true }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_api_key_roles() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let key = |key: &str, prefix: &str, role: Role| ApiKeyConfig {
+        name: Some(key.to_string()),
+        key: key.to_string(),
+        grants: vec![RoleGrant {
+            prefix: prefix.to_string(),
+            role,
+        }],
+    };
+    let mut config = Config::load(None).unwrap();
+    config.auth.api_keys = vec![
+        key("admin-key", "", Role::Admin),
+        key("writer-key", "analytics-", Role::Write),
+        key("reader-key", "analytics-", Role::Read),
+    ];
+    let (base_url, _dir) = start_test_server_with_store_and_config(store, config).await;
+    let client = reqwest::Client::new();
+    let ns = "analytics-events";
+    let probe = vec![0.1f32; 8];
+
+    // Health is public; everything else needs a known key.
+    let resp = client
+        .get(format!("{base_url}/healthz"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = client
+        .get(format!("{base_url}/v1/namespaces"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+    assert_eq!(resp.headers()["www-authenticate"], "Bearer");
+    let resp = client
+        .get(format!("{base_url}/v1/namespaces"))
+        .bearer_auth("wrong-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+
+    // Only admins create namespaces.
+    let create = |key: &'static str, name: &'static str| {
+        client
+            .post(format!("{base_url}/v1/namespaces"))
+            .bearer_auth(key)
+            .json(&serde_json::json!({"name": name, "dimensions": 8}))
+            .send()
+    };
+    assert_eq!(create("writer-key", ns).await.unwrap().status(), 403);
+    assert_eq!(create("admin-key", ns).await.unwrap().status(), 201);
+    assert_eq!(create("admin-key", "billing").await.unwrap().status(), 201);
+
+    let upsert = |key: &'static str| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .bearer_auth(key)
+            .json(&serde_json::json!({ "vectors": random_vectors(3, 8) }))
+            .send()
+    };
+    let resp = upsert("reader-key").await.unwrap();
+    assert_eq!(resp.status(), 403);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert!(body["error"].as_str().unwrap().contains("write"), "{body}");
+    assert_eq!(upsert("writer-key").await.unwrap().status(), 200);
+
+    // Readers can query, including across namespaces, but only their own.
+    let query = |key: &'static str| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .bearer_auth(key)
+            .json(&serde_json::json!({"vector": probe, "top_k": 3, "consistency": "strong"}))
+            .send()
+    };
+    let resp = query("reader-key").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["results"].as_array().unwrap().len(), 3);
+    let resp = client
+        .post(format!("{base_url}/v1/query"))
+        .bearer_auth("reader-key")
+        .json(&serde_json::json!({"namespaces": [ns, "billing"], "vector": probe, "top_k": 3}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    let resp = client
+        .get(format!("{base_url}/v1/namespaces/billing"))
+        .bearer_auth("reader-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    // Listing hides namespaces the key has no role on.
+    let resp = client
+        .get(format!("{base_url}/v1/namespaces"))
+        .bearer_auth("reader-key")
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let names: Vec<&str> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|n| n["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec![ns]);
+
+    // Writers can't delete or compact namespaces, or reach admin endpoints.
+    let resp = client
+        .delete(format!("{base_url}/v1/namespaces/{ns}"))
+        .bearer_auth("writer-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    let resp = client
+        .get(format!("{base_url}/v1/admin/config"))
+        .bearer_auth("writer-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    // The config endpoint never returns keys.
+    let resp = client
+        .get(format!("{base_url}/v1/admin/config"))
+        .bearer_auth("admin-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    for key in body["auth"]["api_keys"].as_array().unwrap() {
+        assert_eq!(key["key"], "<redacted>");
+    }
+    let resp = client
+        .delete(format!("{base_url}/v1/namespaces/{ns}"))
+        .bearer_auth("admin-key")
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+}
+
+#[tokio::test]
+async fn test_write_rate_limit_per_namespace() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let mut config = Config::load(None).unwrap();
+    config.server.write_rate_limit = zeppelin::config::RateLimit {
+        qps: 0.01,
+        burst: 2,
+    };
+    let (base_url, _dir) = start_test_server_with_store_and_config(store, config).await;
+    let client = reqwest::Client::new();
+
+    for ns in ["api-rate-a", "api-rate-b"] {
+        let resp = client
+            .post(format!("{base_url}/v1/namespaces"))
+            .json(&serde_json::json!({"name": ns, "dimensions": 8}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 201);
+    }
+    let upsert = |ns: &'static str| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&serde_json::json!({ "vectors": random_vectors(2, 8) }))
+            .send()
+    };
+    assert_eq!(upsert("api-rate-a").await.unwrap().status(), 200);
+    assert_eq!(upsert("api-rate-a").await.unwrap().status(), 200);
+    let resp = upsert("api-rate-a").await.unwrap();
+    assert_eq!(resp.status(), 429);
+    let retry_after: u64 = resp.headers()["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=100).contains(&retry_after), "{retry_after}");
+
+    // Other namespaces and reads are unaffected.
+    assert_eq!(upsert("api-rate-b").await.unwrap().status(), 200);
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/api-rate-a/query"))
+        .json(&serde_json::json!({"vector": vec![0.1f32; 8], "top_k": 1}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_sparse_vector_query() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store).await;
+    let client = reqwest::Client::new();
+    let ns = "api-sparse-query";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 4}))
+        .send()
+        .await
+        .unwrap();
+    let doc = |id: &str, indices: &[u32], values: &[f32], lang: &str| {
+        serde_json::json!({
+            "id": id,
+            "values": [0.1, 0.2, 0.3, 0.4],
+            "attributes": {"lang": lang},
+            "sparse": {"indices": indices, "values": values},
+        })
+    };
+    let upsert = |vectors: serde_json::Value| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&serde_json::json!({ "vectors": vectors }))
+            .send()
+    };
+    let resp = upsert(serde_json::json!([
+        doc("a", &[1, 7], &[1.0, 2.0], "en"),
+        doc("b", &[7, 9], &[0.5, 3.0], "de"),
+        doc("c", &[100], &[5.0], "en"),
+        {"id": "dense", "values": [0.1, 0.2, 0.3, 0.4]},
+    ]))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let query = |extra: serde_json::Value| {
+        let mut body = serde_json::json!({
+            "rank_by": ["sparse", "DotProduct", {"indices": [7, 9], "values": [1.0, 1.0]}],
+            "top_k": 10,
+            "consistency": "strong",
+        });
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+    };
+    let ranked = |body: serde_json::Value| -> Vec<(String, f64)> {
+        body["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                (
+                    r["id"].as_str().unwrap().to_string(),
+                    r["score"].as_f64().unwrap(),
+                )
+            })
+            .collect()
+    };
+    let expected = vec![("b".to_string(), 3.5), ("a".to_string(), 2.0)];
+
+    let resp = query(serde_json::json!({})).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(ranked(resp.json().await.unwrap()), expected);
+
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+
+    // Served from the segment's sparse index, with filters.
+    let resp = query(serde_json::json!({"consistency": "eventual"}))
+        .await
+        .unwrap();
+    assert_eq!(ranked(resp.json().await.unwrap()), expected);
+    let resp = query(serde_json::json!({
+        "filter": {"op": "eq", "field": "lang", "value": "en"},
+    }))
+    .await
+    .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(ranked(body.clone()), vec![("a".to_string(), 2.0)]);
+    assert_eq!(body["results"][0]["attributes"]["lang"], "en");
+
+    // The compacted vector keeps its sparse representation, and a WAL
+    // rewrite without one shadows the segment's.
+    let resp = client
+        .get(format!("{base_url}/v1/namespaces/{ns}/vectors/b"))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(
+        body["sparse"],
+        serde_json::json!({"indices": [7, 9], "values": [0.5, 3.0]})
+    );
+    upsert(serde_json::json!([{"id": "b", "values": [0.1, 0.2, 0.3, 0.4]}]))
+        .await
+        .unwrap();
+    let resp = query(serde_json::json!({})).await.unwrap();
+    assert_eq!(
+        ranked(resp.json().await.unwrap()),
+        vec![("a".to_string(), 2.0)]
+    );
+
+    for (body, status) in [
+        (
+            serde_json::json!([doc("bad", &[9, 7], &[1.0, 1.0], "en")]),
+            400,
+        ),
+        (
+            serde_json::json!([{"id": "bad", "values": [0.1, 0.2, 0.3, 0.4], "sparse": {"indices": [1], "values": []}}]),
+            400,
+        ),
+    ] {
+        assert_eq!(upsert(body).await.unwrap().status(), status);
+    }
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({
+            "rank_by": ["Sum", [["sparse", "DotProduct", {"indices": [7], "values": [1.0]}]]],
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_hybrid_query_fuses_vector_and_bm25() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store).await;
+    let client = reqwest::Client::new();
+    let ns = "api-hybrid-query";
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 2,
+            "full_text_search": {"content": {}},
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    // "near" is closest to the query vector and "both" is runner-up, while
+    // "both" matches the query text best. Fusion should put "both" first.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({"vectors": [
+            {"id": "near", "values": [1.0, 0.0], "attributes": {"content": "unrelated words"}},
+            {"id": "both", "values": [0.9, 0.1], "attributes": {"content": "rust database"}},
+            {"id": "text", "values": [0.0, 1.0], "attributes": {"content": "rust"}},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let query = |extra: serde_json::Value| {
+        let mut body = serde_json::json!({
+            "vector": [1.0, 0.0],
+            "rank_by": ["content", "BM25", "rust database"],
+            "top_k": 3,
+            "consistency": "strong",
+        });
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+    };
+    let ids = |body: &serde_json::Value| -> Vec<String> {
+        body["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let resp = query(serde_json::json!({})).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(ids(&body)[0], "both");
+    assert_eq!(body["results"][0]["attributes"]["content"], "rust database");
+
+    let resp = query(serde_json::json!({"fusion": {"method": "weighted", "vector_weight": 1.0}}))
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(ids(&body)[0], "near");
+
+    for bad in [
+        serde_json::json!({"fusion": {"method": "weighted", "vector_weight": 2.0}}),
+        serde_json::json!({"vectors": [[1.0, 0.0]]}),
+    ] {
+        assert_eq!(query(bad).await.unwrap().status(), 400);
+    }
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({"vector": [1.0, 0.0], "fusion": {"method": "rrf"}}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_named_vector_query() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store).await;
+    let client = reqwest::Client::new();
+    let ns = "api-named-vectors";
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 2,
+            "named_vectors": {"title": {"dimensions": 3, "distance_metric": "euclidean"}},
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["named_vectors"]["title"]["dimensions"], 3);
+
+    let upsert = |vectors: serde_json::Value| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&serde_json::json!({ "vectors": vectors }))
+            .send()
+    };
+    // The primary vectors rank the documents the other way round.
+    let resp = upsert(serde_json::json!([
+        {"id": "a", "values": [0.0, 1.0], "named_vectors": {"title": [1.0, 0.0, 0.0]}},
+        {"id": "b", "values": [1.0, 0.0], "named_vectors": {"title": [0.0, 0.0, 1.0]}},
+        {"id": "plain", "values": [1.0, 0.0]},
+    ]))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let query = |consistency: &str| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&serde_json::json!({
+                "vector": [1.0, 0.0, 0.0],
+                "vector_field": "title",
+                "consistency": consistency,
+            }))
+            .send()
+    };
+    let ids = |body: serde_json::Value| -> Vec<String> {
+        body["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+    let resp = query("strong").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(ids(resp.json().await.unwrap()), vec!["a", "b"]);
+
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+
+    // Served from the segment, which keeps each document's named vectors.
+    let resp = query("eventual").await.unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(ids(body.clone()), vec!["a", "b"]);
+    assert_eq!(body["scanned_segments"], 1);
+    let resp = client
+        .get(format!("{base_url}/v1/namespaces/{ns}/vectors/b"))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(
+        body["named_vectors"],
+        serde_json::json!({"title": [0.0, 0.0, 1.0]})
+    );
+
+    for vectors in [
+        serde_json::json!([{"id": "bad", "values": [1.0, 0.0], "named_vectors": {"title": [1.0]}}]),
+        serde_json::json!([{"id": "bad", "values": [1.0, 0.0], "named_vectors": {"image": [1.0]}}]),
+    ] {
+        assert_eq!(upsert(vectors).await.unwrap().status(), 400);
+    }
+    for body in [
+        serde_json::json!({"vector": [1.0, 0.0, 0.0], "vector_field": "image"}),
+        serde_json::json!({"vector": [1.0, 0.0], "vector_field": "title"}),
+        serde_json::json!({"vectors": [[1.0, 0.0]], "vector_field": "title"}),
+    ] {
+        let resp = client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 400, "{body}");
+    }
+}
+
+#[tokio::test]
+async fn test_token_vectors_maxsim_query() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store).await;
+    let client = reqwest::Client::new();
+    let ns = "api-token-vectors";
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 2, "token_dimensions": 2}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let upsert = |vectors: serde_json::Value| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&serde_json::json!({ "vectors": vectors }))
+            .send()
+    };
+    let resp = upsert(serde_json::json!([
+        {"id": "a", "values": [1.0, 0.0], "attributes": {"lang": "en"},
+         "token_vectors": [[1.0, 0.0], [0.0, 1.0]]},
+        {"id": "b", "values": [1.0, 0.0], "attributes": {"lang": "de"},
+         "token_vectors": [[3.0, 0.0]]},
+        {"id": "plain", "values": [1.0, 0.0]},
+    ]))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let query = |extra: serde_json::Value| {
+        let mut body = serde_json::json!({
+            "token_vectors": [[1.0, 0.0], [0.0, 1.0]],
+            "consistency": "strong",
+        });
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+    };
+    let ranked = |body: serde_json::Value| -> Vec<(String, f64)> {
+        body["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                (
+                    r["id"].as_str().unwrap().to_string(),
+                    r["score"].as_f64().unwrap(),
+                )
+            })
+            .collect()
+    };
+    // b: 3 + 0, a: 1 + 1.
+    let expected = vec![("b".to_string(), 3.0), ("a".to_string(), 2.0)];
+
+    let resp = query(serde_json::json!({})).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(ranked(resp.json().await.unwrap()), expected);
+
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+
+    // Served from the segment's token matrices, with filters.
+    let resp = query(serde_json::json!({"consistency": "eventual"}))
+        .await
+        .unwrap();
+    assert_eq!(ranked(resp.json().await.unwrap()), expected);
+    let resp = query(serde_json::json!({
+        "filter": {"op": "eq", "field": "lang", "value": "en"},
+    }))
+    .await
+    .unwrap();
+    assert_eq!(
+        ranked(resp.json().await.unwrap()),
+        vec![("a".to_string(), 2.0)]
+    );
+    let resp = client
+        .get(format!("{base_url}/v1/namespaces/{ns}/vectors/a"))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(
+        body["token_vectors"],
+        serde_json::json!([[1.0, 0.0], [0.0, 1.0]])
+    );
+
+    let resp = upsert(serde_json::json!([
+        {"id": "bad", "values": [1.0, 0.0], "token_vectors": [[1.0, 0.0, 0.0]]},
+    ]))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 400);
+    for extra in [
+        serde_json::json!({"token_vectors": [[1.0]]}),
+        serde_json::json!({"token_vectors": []}),
+        serde_json::json!({"vector": [1.0, 0.0]}),
+    ] {
+        assert_eq!(query(extra.clone()).await.unwrap().status(), 400, "{extra}");
+    }
+}
+
+#[tokio::test]
+async fn test_query_diversify_reranks_with_mmr() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store).await;
+    let client = reqwest::Client::new();
+    let ns = "api-diversify";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 2, "distance_metric": "euclidean"}))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({"vectors": [
+            {"id": "a", "values": [1.0, 0.0]},
+            {"id": "a2", "values": [1.0, 0.01]},
+            {"id": "b", "values": [0.0, 1.0]},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let query = |extra: serde_json::Value| {
+        let mut body = serde_json::json!({"vector": [1.0, 0.0], "top_k": 2});
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+    };
+    let ids = |body: serde_json::Value| -> Vec<String> {
+        body["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let resp = query(serde_json::json!({})).await.unwrap();
+    assert_eq!(ids(resp.json().await.unwrap()), vec!["a", "a2"]);
+    let resp = query(serde_json::json!({"diversify": {"lambda": 0.3}}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(ids(resp.json().await.unwrap()), vec!["a", "b"]);
+
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    let resp = query(serde_json::json!({"diversify": {"lambda": 0.3}, "consistency": "eventual"}))
+        .await
+        .unwrap();
+    assert_eq!(ids(resp.json().await.unwrap()), vec!["a", "b"]);
+
+    for extra in [
+        serde_json::json!({"diversify": {"lambda": 2.0}}),
+        serde_json::json!({"diversify": {}, "vector": null, "vectors": [[1.0, 0.0]]}),
+    ] {
+        assert_eq!(query(extra.clone()).await.unwrap().status(), 400, "{extra}");
+    }
+}
+
+#[tokio::test]
+async fn test_query_group_by_caps_hits_per_value() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store).await;
+    let client = reqwest::Client::new();
+    let ns = "api-group-by";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 2, "distance_metric": "euclidean"}))
+        .send()
+        .await
+        .unwrap();
+    // Three chunks of doc "a" are nearest, then one of doc "b".
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({"vectors": [
+            {"id": "a1", "values": [1.0, 0.0], "attributes": {"doc_id": "a"}},
+            {"id": "a2", "values": [1.0, 0.1], "attributes": {"doc_id": "a"}},
+            {"id": "a3", "values": [1.0, 0.2], "attributes": {"doc_id": "a"}},
+            {"id": "b1", "values": [1.0, 0.5], "attributes": {"doc_id": "b"}},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let query = |extra: serde_json::Value| {
+        let mut body = serde_json::json!({"vector": [1.0, 0.0], "top_k": 3});
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+    };
+    let ids = |body: serde_json::Value| -> Vec<String> {
+        body["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let resp = query(serde_json::json!({})).await.unwrap();
+    assert_eq!(ids(resp.json().await.unwrap()), vec!["a1", "a2", "a3"]);
+    let group_by = serde_json::json!({"group_by": {"field": "doc_id", "group_size": 2}});
+    let resp = query(group_by.clone()).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(ids(resp.json().await.unwrap()), vec!["a1", "a2", "b1"]);
+
+    // Grouping reads segment attributes even when they aren't returned.
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    let resp = query(serde_json::json!({
+        "group_by": {"field": "doc_id"},
+        "include_attributes": false,
+        "consistency": "eventual",
+    }))
+    .await
+    .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(ids(body.clone()), vec!["a1", "b1"]);
+    assert!(body["results"][0].get("attributes").is_none());
+
+    for extra in [
+        serde_json::json!({"group_by": {"field": "doc_id", "group_size": 0}}),
+        serde_json::json!({"group_by": {"field": "doc_id"}, "vector": null, "vectors": [[1.0, 0.0]]}),
+    ] {
+        assert_eq!(query(extra.clone()).await.unwrap().status(), 400, "{extra}");
+    }
+}
+
+#[tokio::test]
+async fn test_query_max_distance_drops_far_results() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store).await;
+    let client = reqwest::Client::new();
+    let ns = "api-max-distance";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 2, "distance_metric": "euclidean"}))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({"vectors": [
+            {"id": "near", "values": [1.0, 0.0]},
+            {"id": "close", "values": [1.0, 0.3]},
+            {"id": "far", "values": [1.0, 1.0]},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let query = |extra: serde_json::Value| {
+        let mut body = serde_json::json!({"vector": [1.0, 0.0], "top_k": 10});
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+    };
+    let ids = |body: serde_json::Value| -> Vec<String> {
+        body["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    // WAL scan.
+    let resp = query(serde_json::json!({"max_distance": 0.5}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(ids(resp.json().await.unwrap()), vec!["near", "close"]);
+
+    // Segment search applies the same cutoff.
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    let resp = query(serde_json::json!({"maxDistance": 0.5, "consistency": "eventual"}))
+        .await
+        .unwrap();
+    assert_eq!(ids(resp.json().await.unwrap()), vec!["near", "close"]);
+
+    for extra in [
+        serde_json::json!({"min_score": 0.5}),
+        serde_json::json!({"min_score": 0.5, "max_distance": 0.5}),
+        serde_json::json!({"max_distance": 0.5, "vector": null, "vectors": [[1.0, 0.0]]}),
+    ] {
+        assert_eq!(query(extra.clone()).await.unwrap().status(), 400, "{extra}");
+    }
+}
+
+#[tokio::test]
+async fn test_query_include_vectors_returns_values() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store).await;
+    let client = reqwest::Client::new();
+    let ns = "api-include-vectors";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 2, "distance_metric": "euclidean"}))
+        .send()
+        .await
+        .unwrap();
+    let upsert = |id: &str, values: [f32; 2]| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&serde_json::json!({"vectors": [{"id": id, "values": values}]}))
+            .send()
+    };
+    assert_eq!(upsert("seg", [1.0, 0.0]).await.unwrap().status(), 200);
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(upsert("wal", [0.0, 1.0]).await.unwrap().status(), 200);
+
+    let query = |extra: serde_json::Value| {
+        let mut body = serde_json::json!({"vector": [1.0, 0.0], "top_k": 10});
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+    };
+
+    let resp = query(serde_json::json!({"include_vectors": true}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["id"], "seg");
+    assert_eq!(results[0]["values"], serde_json::json!([1.0, 0.0]));
+    assert_eq!(results[1]["id"], "wal");
+    assert_eq!(results[1]["values"], serde_json::json!([0.0, 1.0]));
+
+    let resp = query(serde_json::json!({})).await.unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert!(body["results"][0].get("values").is_none());
+
+    for extra in [
+        serde_json::json!({"include_vectors": true, "ids_only": true}),
+        serde_json::json!({"include_vectors": true, "vector": null, "vectors": [[1.0, 0.0]]}),
+    ] {
+        assert_eq!(query(extra.clone()).await.unwrap().status(), 400, "{extra}");
+    }
+}
+
+#[tokio::test]
+async fn test_query_attribute_projection() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store).await;
+    let client = reqwest::Client::new();
+    let ns = "api-attr-projection";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 2}))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({"vectors": [{
+            "id": "a",
+            "values": [1.0, 0.0],
+            "attributes": {"title": "A", "url": "https://a", "body": "long text"},
+        }]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let query = |extra: serde_json::Value| {
+        let mut body = serde_json::json!({"vector": [1.0, 0.0], "top_k": 1});
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+    };
+    let attributes = |body: serde_json::Value| body["results"][0]["attributes"].clone();
+
+    let resp = query(serde_json::json!({"include_attributes": ["title", "url"]}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        attributes(resp.json().await.unwrap()),
+        serde_json::json!({"title": "A", "url": "https://a"})
+    );
+
+    let resp = query(serde_json::json!({"excludeAttributes": ["body"]}))
+        .await
+        .unwrap();
+    assert_eq!(
+        attributes(resp.json().await.unwrap()),
+        serde_json::json!({"title": "A", "url": "https://a"})
+    );
+
+    let resp = query(serde_json::json!({
+        "include_attributes": ["title", "body"],
+        "exclude_attributes": ["body"],
+    }))
+    .await
+    .unwrap();
+    assert_eq!(
+        attributes(resp.json().await.unwrap()),
+        serde_json::json!({"title": "A"})
+    );
+
+    let resp = query(serde_json::json!({"include_attributes": false}))
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert!(body["results"][0].get("attributes").is_none());
+}
+
+#[tokio::test]
+async fn test_query_batch() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store).await;
+    let client = reqwest::Client::new();
+    let ns = "api-query-batch";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 2, "distance_metric": "euclidean"}))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({"vectors": [
+            {"id": "x", "values": [1.0, 0.0]},
+            {"id": "y", "values": [0.0, 1.0]},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+        .send()
+        .await
+        .unwrap();
+
+    let batch = |queries: serde_json::Value| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query_batch"))
+            .json(&serde_json::json!({"queries": queries}))
+            .send()
+    };
+
+    let resp = batch(serde_json::json!([
+        {"vector": [1.0, 0.0], "top_k": 1},
+        {"vector": [0.0, 1.0], "top_k": 1, "consistency": "eventual"},
+        {"vector": [0.0, 1.0], "top_k": 2, "ids_only": true},
+    ]))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let responses = body["responses"].as_array().unwrap();
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[0]["results"][0]["id"], "x");
+    assert_eq!(responses[1]["results"][0]["id"], "y");
+    assert_eq!(
+        responses[2]["results"],
+        serde_json::json!([{"id": "y", "score": 0.0}, {"id": "x", "score": 2.0}])
+    );
+
+    let resp = batch(serde_json::json!([
+        {"vector": [1.0, 0.0]},
+        {"vector": [1.0, 0.0], "top_k": 0},
+    ]))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert!(body["error"].as_str().unwrap().contains("queries[1]: "));
+
+    let too_many = vec![serde_json::json!({"vector": [1.0, 0.0]}); 65];
+    for queries in [serde_json::json!([]), serde_json::json!(too_many)] {
+        assert_eq!(batch(queries).await.unwrap().status(), 400);
+    }
+
+    // Under camel case each response is renamed like a single query's.
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let mut config = Config::load(None).unwrap();
+    config.server.json_case = JsonCase::Camel;
+    let (base_url, _dir) = start_test_server_with_store_and_config(store, config).await;
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 2}))
+        .send()
+        .await
+        .unwrap();
+    client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({"vectors": [
+            {"id": "x", "values": [1.0, 0.0]},
+            {"id": "y", "values": [0.0, 1.0]},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query_batch"))
+        .json(&serde_json::json!({"queries": [{"vector": [1.0, 0.0], "top_k": 1}]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let response = &body["responses"][0];
+    assert_eq!(response["scannedFragments"], 1);
+    assert!(response["nextCursor"].is_string());
+    assert!(response.get("scanned_fragments").is_none());
+    assert!(response.get("next_cursor").is_none());
+}
+
+#[tokio::test]
+async fn test_query_by_document_id() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store).await;
+    let client = reqwest::Client::new();
+    let ns = "api-query-by-id";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 2, "distance_metric": "euclidean"}))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({"vectors": [
+            {"id": "a", "values": [1.0, 0.0]},
+            {"id": "b", "values": [1.0, 0.2]},
+            {"id": "c", "values": [1.0, 0.5]},
+            {"id": "d", "values": [0.0, 1.0]},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let query = |body: serde_json::Value| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+    };
+
+    let resp = query(serde_json::json!({"query": {"id": "a"}, "top_k": 2}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let ids: Vec<&str> = body["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, vec!["b", "c"]);
+
+    let resp = query(serde_json::json!({"query": {"id": "missing"}}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+    let resp = query(serde_json::json!({"query": {"id": "a"}, "vector": [1.0, 0.0]}))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_query_by_document_id_at_max_top_k() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let mut config = Config::load(None).unwrap();
+    config.server.max_top_k = 2;
+    let (base_url, _dir) = start_test_server_with_store_and_config(store, config).await;
+    let client = reqwest::Client::new();
+    let ns = "api-query-by-id-max";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 2, "distance_metric": "euclidean"}))
+        .send()
+        .await
+        .unwrap();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({"vectors": [
+            {"id": "a", "values": [1.0, 0.0]},
+            {"id": "b", "values": [1.0, 0.2]},
+            {"id": "c", "values": [1.0, 0.5]},
+            {"id": "d", "values": [0.0, 1.0]},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let page = |cursor: Option<String>| {
+        let mut body = serde_json::json!({"query": {"id": "a"}, "top_k": 2});
+        if let Some(cursor) = cursor {
+            body["cursor"] = serde_json::json!(cursor);
+        }
+        let client = client.clone();
+        let url = format!("{base_url}/v1/namespaces/{ns}/query");
+        async move {
+            let resp = client.post(url).json(&body).send().await.unwrap();
+            assert_eq!(resp.status(), 200);
+            let body: serde_json::Value = resp.json().await.unwrap();
+            let ids: Vec<String> = body["results"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|r| r["id"].as_str().unwrap().to_string())
+                .collect();
+            (ids, body["next_cursor"].as_str().map(str::to_string))
+        }
+    };
+
+    // Dropping the source document still leaves a full page at the cap.
+    let (ids, cursor) = page(None).await;
+    assert_eq!(ids, vec!["b", "c"]);
+    assert!(cursor.is_some());
+    let (ids, cursor) = page(cursor).await;
+    assert_eq!(ids, vec!["d"]);
+    assert!(cursor.is_none());
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({"query": {"id": "a"}, "top_k": 3}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_query_aggregations() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store).await;
+    let client = reqwest::Client::new();
+    let ns = "api-aggregations";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 2}))
+        .send()
+        .await
+        .unwrap();
+    let doc = |id: &str, brand: &str, price: f64| {
+        serde_json::json!({
+            "id": id,
+            "values": [1.0, 0.0],
+            "attributes": {"brand": brand, "price": price, "in_stock": true},
+        })
+    };
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({"vectors": [
+            doc("1", "acme", 10.0),
+            doc("2", "acme", 30.0),
+            doc("3", "globex", 20.0),
+            {"id": "4", "values": [1.0, 0.0], "attributes": {"brand": "acme", "price": 99.0}},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let query = |extra: serde_json::Value| {
+        let mut body = serde_json::json!({"vector": [1.0, 0.0], "top_k": 1});
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+    };
+
+    // Aggregations cover every filtered vector, not just the top_k.
+    let resp = query(serde_json::json!({
+        "filter": {"op": "eq", "field": "in_stock", "value": true},
+        "aggregations": {
+            "brands": {"terms": {"field": "brand"}},
+            "cheapest": {"min": {"field": "price"}},
+            "priciest": {"max": {"field": "price"}},
+            "mean": {"avg": {"field": "price"}},
+            "none": {"avg": {"field": "missing"}},
+        },
+    }))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["results"].as_array().unwrap().len(), 1);
+    assert_eq!(
+        body["aggregations"],
+        serde_json::json!({
+            "brands": {"counts": {"acme": 2, "globex": 1}, "truncated": false},
+            "cheapest": {"value": 10.0, "count": 3},
+            "priciest": {"value": 30.0, "count": 3},
+            "mean": {"value": 20.0, "count": 3},
+            "none": {"value": null, "count": 0},
+        })
+    );
+
+    let resp = query(serde_json::json!({})).await.unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert!(body.get("aggregations").is_none());
+
+    for aggregations in [
+        serde_json::json!({"a": {"terms": {"field": "brand", "size": 0}}}),
+        serde_json::json!({"a": {"sum": {"field": "price"}}}),
+        serde_json::json!({"a": {"min": {"field": ""}}}),
+    ] {
+        let resp = query(serde_json::json!({"aggregations": aggregations.clone()}))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 400, "{aggregations}");
+    }
+}
+
+#[tokio::test]
+async fn test_query_cursor_pagination() {
+    let store = ZeppelinStore::new(std::sync::Arc::new(object_store::memory::InMemory::new()));
+    let (base_url, _dir) = start_test_server_with_store(store).await;
+    let client = reqwest::Client::new();
+    let ns = "api-query-cursor";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 2, "distance_metric": "euclidean"}))
+        .send()
+        .await
+        .unwrap();
+    // "b" and "c" tie on distance, so the cursor has to break ties by ID.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({"vectors": [
+            {"id": "a", "values": [1.0, 0.0]},
+            {"id": "c", "values": [1.0, 0.5]},
+            {"id": "b", "values": [1.0, 0.5]},
+            {"id": "d", "values": [1.0, 1.0]},
+            {"id": "e", "values": [1.0, 2.0]},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let page = |cursor: Option<String>, consistency: &'static str| {
+        let client = client.clone();
+        let url = format!("{base_url}/v1/namespaces/{ns}/query");
+        async move {
+            let mut body = serde_json::json!({
+                "vector": [1.0, 0.0],
+                "top_k": 2,
+                "consistency": consistency,
+                "include_rank": true,
+            });
+            if let Some(cursor) = cursor {
+                body["cursor"] = serde_json::json!(cursor);
+            }
+            let resp = client.post(url).json(&body).send().await.unwrap();
+            assert_eq!(resp.status(), 200);
+            let body: serde_json::Value = resp.json().await.unwrap();
+            let results = body["results"].as_array().unwrap();
+            let ids: Vec<String> = results
+                .iter()
+                .map(|r| r["id"].as_str().unwrap().to_string())
+                .collect();
+            let ranks: Vec<u64> = results
+                .iter()
+                .map(|r| r["rank"].as_u64().unwrap())
+                .collect();
+            (ids, ranks, body["next_cursor"].as_str().map(String::from))
+        }
+    };
+
+    // Page through the WAL, then through the compacted segment. Ranks
+    // count on from the previous page.
+    for consistency in ["strong", "eventual"] {
+        let (ids, ranks, cursor) = page(None, consistency).await;
+        assert_eq!(ids, vec!["a", "b"], "{consistency}");
+        assert_eq!(ranks, vec![0, 1], "{consistency}");
+        let (ids, ranks, cursor) = page(cursor, consistency).await;
+        assert_eq!(ids, vec!["c", "d"], "{consistency}");
+        assert_eq!(ranks, vec![2, 3], "{consistency}");
+        let (ids, ranks, cursor) = page(cursor, consistency).await;
+        assert_eq!(ids, vec!["e"], "{consistency}");
+        assert_eq!(ranks, vec![4], "{consistency}");
+        assert!(cursor.is_none(), "{consistency}");
+
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/compact"))
+            .send()
+            .await
+            .unwrap();
+    }
+
+    for body in [
+        serde_json::json!({"vector": [1.0, 0.0], "cursor": "not-a-cursor"}),
+        serde_json::json!({"vector": [1.0, 0.0], "cursor": "3f8000002:a", "diversify": {"lambda": 0.5}}),
+        serde_json::json!({"vectors": [[1.0, 0.0]], "cursor": "3f8000002:a"}),
+    ] {
+        let resp = client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 400, "{body}");
+    }
+}
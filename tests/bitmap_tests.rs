@@ -60,6 +60,10 @@ fn status_vectors(prefix: &str, n: usize, dims: usize) -> Vec<VectorEntry> {
                 attrs.insert("priority".to_string(), AttributeValue::Integer(i as i64));
                 attrs
             }),
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         })
         .collect()
 }
@@ -87,6 +91,10 @@ fn tagged_vectors(prefix: &str, n: usize, dims: usize) -> Vec<VectorEntry> {
                 attrs.insert("priority".to_string(), AttributeValue::Integer(i as i64));
                 attrs
             }),
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         })
         .collect()
 }
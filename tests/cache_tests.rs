@@ -269,3 +269,56 @@ async fn test_cache_concurrent_get_or_fetch() {
         Some(Bytes::from("shared_value"))
     );
 }
+
+#[tokio::test]
+async fn test_exists_with_cache() {
+    use zeppelin::cache::exists_with_cache;
+    use zeppelin::storage::ZeppelinStore;
+
+    let dir = TempDir::new().unwrap();
+    let cache = test_cache(dir.path(), 1024 * 1024);
+    let store = ZeppelinStore::new(Arc::new(object_store::memory::InMemory::new()));
+
+    // Cached but absent from the store: answered from the cache index.
+    cache.put("cached", &Bytes::from("v")).await.unwrap();
+    assert!(exists_with_cache(Some(&cache), &store, "cached")
+        .await
+        .unwrap());
+    // The index alone answers; the cached file is never read.
+    std::fs::remove_file(dir.path().join("cached")).unwrap();
+    assert!(exists_with_cache(Some(&cache), &store, "cached")
+        .await
+        .unwrap());
+
+    // Cache miss falls back to the store and does not populate the cache.
+    store.put("stored", Bytes::from("v")).await.unwrap();
+    assert!(exists_with_cache(Some(&cache), &store, "stored")
+        .await
+        .unwrap());
+    assert!(!cache.contains("stored").await);
+    assert!(exists_with_cache(None, &store, "stored").await.unwrap());
+
+    assert!(!exists_with_cache(Some(&cache), &store, "missing")
+        .await
+        .unwrap());
+}
+
+#[tokio::test]
+async fn test_cache_get_or_fetch_fetches_once_per_miss() {
+    let dir = TempDir::new().unwrap();
+    let cache = test_cache(dir.path(), 1024 * 1024);
+    let fetches = std::sync::atomic::AtomicUsize::new(0);
+    let fetch = || async {
+        fetches.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Ok(Bytes::from("v"))
+    };
+
+    cache.get_or_fetch("k", fetch).await.unwrap();
+    cache.get_or_fetch("k", fetch).await.unwrap();
+    assert_eq!(fetches.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    // A cached file that vanished is a single miss and a single fetch.
+    std::fs::remove_file(dir.path().join("k")).unwrap();
+    cache.get_or_fetch("k", fetch).await.unwrap();
+    assert_eq!(fetches.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
@@ -36,6 +36,7 @@ impl TestHarness {
                 gcs_service_account_path: None,
                 azure_account: None,
                 azure_access_key: None,
+                read_after_write_retries: 3,
             },
             "minio" => StorageConfig {
                 backend: "s3".to_string(),
@@ -55,6 +56,7 @@ impl TestHarness {
                 gcs_service_account_path: None,
                 azure_account: None,
                 azure_access_key: None,
+                read_after_write_retries: 3,
             },
             other => panic!("unsupported TEST_BACKEND: {other}"),
         };
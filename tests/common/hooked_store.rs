@@ -0,0 +1,139 @@
+//! In-memory object store with per-method hooks, for tests that inject
+//! failures or watch which objects a component touches.
+
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use object_store::memory::InMemory;
+use object_store::path::Path;
+use object_store::{
+    GetOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, ObjectStore, PutMultipartOpts,
+    PutOptions, PutPayload, PutResult,
+};
+
+/// Runs before the call it hooks, with the backing store and the call's
+/// path. An error is returned in place of the call's result.
+type Hook = Box<
+    dyn for<'a> Fn(&'a InMemory, &'a Path) -> BoxFuture<'a, object_store::Result<()>> + Send + Sync,
+>;
+
+/// Runs at the start of every recursive listing, with its prefix.
+type ListHook = Box<dyn Fn(Option<&Path>) + Send + Sync>;
+
+/// [`InMemory`] store that runs an optional hook before each get, put or
+/// recursive listing. Every other method passes straight through.
+#[derive(Default)]
+pub struct HookedStore {
+    inner: InMemory,
+    on_get: Option<Hook>,
+    on_put: Option<Hook>,
+    on_list: Option<ListHook>,
+}
+
+impl HookedStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `hook` before every read.
+    pub fn on_get<F>(mut self, hook: F) -> Self
+    where
+        F: for<'a> Fn(&'a InMemory, &'a Path) -> BoxFuture<'a, object_store::Result<()>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.on_get = Some(Box::new(hook));
+        self
+    }
+
+    /// Run `hook` before every write.
+    pub fn on_put<F>(mut self, hook: F) -> Self
+    where
+        F: for<'a> Fn(&'a InMemory, &'a Path) -> BoxFuture<'a, object_store::Result<()>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.on_put = Some(Box::new(hook));
+        self
+    }
+
+    /// Run `hook` on every recursive listing. Delimiter listings don't
+    /// trigger it.
+    pub fn on_list<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(Option<&Path>) + Send + Sync + 'static,
+    {
+        self.on_list = Some(Box::new(hook));
+        self
+    }
+}
+
+impl std::fmt::Display for HookedStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HookedStore")
+    }
+}
+
+impl std::fmt::Debug for HookedStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HookedStore")
+    }
+}
+
+#[async_trait::async_trait]
+impl ObjectStore for HookedStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        if let Some(hook) = &self.on_put {
+            hook(&self.inner, location).await?;
+        }
+        self.inner.put_opts(location, payload, opts).await
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &Path,
+        opts: PutMultipartOpts,
+    ) -> object_store::Result<Box<dyn MultipartUpload>> {
+        self.inner.put_multipart_opts(location, opts).await
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        if let Some(hook) = &self.on_get {
+            hook(&self.inner, location).await?;
+        }
+        self.inner.get_opts(location, options).await
+    }
+
+    async fn delete(&self, location: &Path) -> object_store::Result<()> {
+        self.inner.delete(location).await
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+        if let Some(hook) = &self.on_list {
+            hook(prefix);
+        }
+        self.inner.list(prefix)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> object_store::Result<ListResult> {
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
@@ -3,6 +3,8 @@ pub mod assertions;
 #[allow(dead_code)]
 pub mod harness;
 #[allow(dead_code)]
+pub mod hooked_store;
+#[allow(dead_code)]
 pub mod server;
 #[allow(dead_code)]
 pub mod vectors;
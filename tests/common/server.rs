@@ -9,6 +9,7 @@ use zeppelin::compaction::background::compaction_loop;
 use zeppelin::compaction::Compactor;
 use zeppelin::config::Config;
 use zeppelin::namespace::NamespaceManager;
+use zeppelin::server::limits::UpsertLimiter;
 use zeppelin::server::routes::build_router;
 use zeppelin::server::AppState;
 use zeppelin::storage::ZeppelinStore;
@@ -40,8 +41,11 @@ pub async fn start_test_server_with_config(
     let state = AppState {
         store: harness.store.clone(),
         namespace_manager: Arc::new(NamespaceManager::new(harness.store.clone())),
-        wal_writer: Arc::new(WalWriter::new(harness.store.clone())),
+        wal_writer: Arc::new(WalWriter::from_config(harness.store.clone(), &config.wal)),
         wal_reader: Arc::new(WalReader::new(harness.store.clone())),
+        upsert_limiter: Arc::new(UpsertLimiter::new(
+            config.server.max_concurrent_upserts_per_ns,
+        )),
         config: Arc::new(config),
         compactor,
         cache: cache.clone(),
@@ -90,8 +94,11 @@ pub async fn start_test_server_with_compactor(
     let state = AppState {
         store: harness.store.clone(),
         namespace_manager: Arc::new(NamespaceManager::new(harness.store.clone())),
-        wal_writer: Arc::new(WalWriter::new(harness.store.clone())),
+        wal_writer: Arc::new(WalWriter::from_config(harness.store.clone(), &config.wal)),
         wal_reader: Arc::new(WalReader::new(harness.store.clone())),
+        upsert_limiter: Arc::new(UpsertLimiter::new(
+            config.server.max_concurrent_upserts_per_ns,
+        )),
         config: Arc::new(config),
         compactor: compactor.clone(),
         cache: cache.clone(),
@@ -153,8 +160,11 @@ pub async fn start_test_server_with_compaction(
     let state = AppState {
         store: harness.store.clone(),
         namespace_manager,
-        wal_writer: Arc::new(WalWriter::new(harness.store.clone())),
+        wal_writer: Arc::new(WalWriter::from_config(harness.store.clone(), &config.wal)),
         wal_reader: Arc::new(WalReader::new(harness.store.clone())),
+        upsert_limiter: Arc::new(UpsertLimiter::new(
+            config.server.max_concurrent_upserts_per_ns,
+        )),
         config: Arc::new(config),
         compactor,
         cache: cache.clone(),
@@ -172,6 +182,55 @@ pub async fn start_test_server_with_compaction(
     (base_url, harness, cache, cache_dir, shutdown_tx)
 }
 
+/// Start a test server with default config on top of `store`, e.g. one that
+/// injects faults. Returns (base_url, _cache_dir).
+pub async fn start_test_server_with_store(store: ZeppelinStore) -> (String, tempfile::TempDir) {
+    start_test_server_with_store_and_config(store, Config::load(None).unwrap()).await
+}
+
+/// Like [`start_test_server_with_store`], with a caller-supplied config.
+pub async fn start_test_server_with_store_and_config(
+    store: ZeppelinStore,
+    config: Config,
+) -> (String, tempfile::TempDir) {
+    zeppelin::metrics::init();
+
+    let cache_dir = tempfile::TempDir::new().unwrap();
+    let cache = Arc::new(
+        DiskCache::new_with_max_bytes(cache_dir.path().to_path_buf(), 100 * 1024 * 1024).unwrap(),
+    );
+    let compactor = Arc::new(Compactor::new(
+        store.clone(),
+        WalReader::new(store.clone()),
+        config.compaction.clone(),
+        config.indexing.clone(),
+    ));
+
+    let state = AppState {
+        store: store.clone(),
+        namespace_manager: Arc::new(NamespaceManager::new(store.clone())),
+        wal_writer: Arc::new(WalWriter::from_config(store.clone(), &config.wal)),
+        wal_reader: Arc::new(WalReader::new(store)),
+        upsert_limiter: Arc::new(UpsertLimiter::new(
+            config.server.max_concurrent_upserts_per_ns,
+        )),
+        config: Arc::new(config),
+        compactor,
+        cache,
+    };
+
+    let app = build_router(state);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let base_url = format!("http://{addr}");
+
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    (base_url, cache_dir)
+}
+
 /// Start a test server with default config, returning (base_url, harness).
 pub async fn start_test_server() -> (String, TestHarness) {
     let (url, harness, _cache, _dir) = start_test_server_with_config(None).await;
@@ -11,6 +11,10 @@ pub fn random_vectors(n: usize, dims: usize) -> Vec<VectorEntry> {
             id: format!("vec_{i}"),
             values: (0..dims).map(|_| rng.gen_range(-1.0..1.0)).collect(),
             attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         })
         .collect()
 }
@@ -49,6 +53,10 @@ pub fn clustered_vectors(
                 id: format!("cluster_{ci}_vec_{vi}"),
                 values,
                 attributes: None,
+                sparse: None,
+                named_vectors: None,
+                token_vectors: None,
+                version: None,
             });
         }
     }
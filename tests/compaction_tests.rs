@@ -1,13 +1,25 @@
 mod common;
 
 use common::harness::TestHarness;
-use common::vectors::{random_vectors, simple_attributes, with_attributes};
+use common::hooked_store::HookedStore;
+use common::server::start_test_server_with_store;
+use common::vectors::{clustered_vectors, random_vectors, simple_attributes, with_attributes};
+use object_store::ObjectStore;
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 use zeppelin::compaction::Compactor;
-use zeppelin::config::{CompactionConfig, IndexingConfig};
+use zeppelin::config::{ClusterBy, CompactionConfig, CompactionOverrides, IndexingConfig};
+use zeppelin::index::distance::compute_distance;
 use zeppelin::index::ivf_flat::build::build_ivf_flat;
+use zeppelin::index::quantization::QuantizationType;
+use zeppelin::namespace::manager::NamespaceMetadata;
+use zeppelin::namespace::NamespaceManager;
 use zeppelin::query::execute_query;
-use zeppelin::types::{AttributeValue, ConsistencyLevel, DistanceMetric, Filter, VectorEntry};
+use zeppelin::types::{
+    AttributeValue, ConsistencyLevel, DistanceMetric, Filter, IndexType, VectorEntry,
+};
 use zeppelin::wal::fragment::WalFragment;
 use zeppelin::wal::manifest::{Manifest, SegmentRef};
 use zeppelin::wal::{WalReader, WalWriter};
@@ -87,6 +99,10 @@ async fn test_compact_multiple_fragments() {
             id: format!("a_{i}"),
             values: random_vectors(1, 16)[0].values.clone(),
             attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         })
         .collect();
     let vecs2: Vec<VectorEntry> = (0..30)
@@ -94,6 +110,10 @@ async fn test_compact_multiple_fragments() {
             id: format!("b_{i}"),
             values: random_vectors(1, 16)[0].values.clone(),
             attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         })
         .collect();
     let vecs3: Vec<VectorEntry> = (0..50)
@@ -101,6 +121,10 @@ async fn test_compact_multiple_fragments() {
             id: format!("c_{i}"),
             values: random_vectors(1, 16)[0].values.clone(),
             attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         })
         .collect();
 
@@ -190,6 +214,10 @@ async fn test_compact_deduplication() {
                 id: "dup".to_string(),
                 values: v1,
                 attributes: None,
+                sparse: None,
+                named_vectors: None,
+                token_vectors: None,
+                version: None,
             }],
             vec![],
         )
@@ -206,6 +234,10 @@ async fn test_compact_deduplication() {
                 id: "dup".to_string(),
                 values: v2.clone(),
                 attributes: None,
+                sparse: None,
+                named_vectors: None,
+                token_vectors: None,
+                version: None,
             }],
             vec![],
         )
@@ -400,8 +432,16 @@ async fn test_compact_with_existing_segment() {
         cluster_count: 4,
         quantization: Default::default(),
         hierarchical: false,
+        vamana: false,
         bitmap_fields: Vec::new(),
         fts_fields: Vec::new(),
+        kmeans_metric: None,
+        text_only: false,
+        sparse: false,
+        named_vectors: false,
+        token_vectors: false,
+        versions: false,
+        tombstones: Default::default(),
     });
     manifest.write(store, &ns).await.unwrap();
 
@@ -412,6 +452,10 @@ async fn test_compact_with_existing_segment() {
             id: format!("new_vec_{i}"),
             values: random_vectors(1, 16)[0].values.clone(),
             attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         })
         .collect();
     writer.append(&ns, new_vecs, vec![]).await.unwrap();
@@ -531,6 +575,71 @@ async fn test_query_after_compaction() {
     harness.cleanup().await;
 }
 
+#[tokio::test]
+async fn test_eventual_with_deletes_hides_wal_tombstones() {
+    let harness = TestHarness::new().await;
+    let ns = harness.key("compact-eventual-deletes");
+    let store = &harness.store;
+    let writer = WalWriter::new(store.clone());
+    let wal_reader = WalReader::new(store.clone());
+
+    let manifest = Manifest::new();
+    manifest.write(store, &ns).await.unwrap();
+
+    let vecs = random_vectors(100, 16);
+    let query_vec = vecs[0].values.clone();
+    writer.append(&ns, vecs, vec![]).await.unwrap();
+    test_compactor(store).compact(&ns).await.unwrap();
+
+    // After compaction: one upsert-only fragment with a copy of the query
+    // vector, then one fragment deleting the segment-resident vec_0.
+    let newcomer = VectorEntry {
+        id: "newcomer".to_string(),
+        values: query_vec.clone(),
+        attributes: None,
+        sparse: None,
+        named_vectors: None,
+        token_vectors: None,
+        version: None,
+    };
+    writer.append(&ns, vec![newcomer], vec![]).await.unwrap();
+    writer
+        .append(&ns, vec![], vec!["vec_0".to_string()])
+        .await
+        .unwrap();
+
+    let query = |consistency| {
+        execute_query(
+            store,
+            &wal_reader,
+            &ns,
+            &query_vec,
+            5,
+            4,
+            None,
+            consistency,
+            DistanceMetric::Cosine,
+            3,
+            None,
+        )
+    };
+
+    // Plain eventual still sees the deleted vector.
+    let eventual = query(ConsistencyLevel::Eventual).await.unwrap();
+    assert_eq!(eventual.results[0].id, "vec_0");
+
+    let result = query(ConsistencyLevel::EventualWithDeletes).await.unwrap();
+    let ids: Vec<&str> = result.results.iter().map(|r| r.id.as_str()).collect();
+    assert!(!ids.contains(&"vec_0"), "deleted vector returned: {ids:?}");
+    assert!(!ids.contains(&"newcomer"), "WAL upsert returned: {ids:?}");
+    assert_eq!(ids.len(), 5);
+    // Only the fragment carrying deletes is read.
+    assert_eq!(result.scanned_fragments, 1);
+    assert_eq!(result.scanned_segments, 1);
+
+    harness.cleanup().await;
+}
+
 #[tokio::test]
 async fn test_compact_empty_namespace() {
     let harness = TestHarness::new().await;
@@ -592,6 +701,90 @@ async fn test_compact_trigger_by_fragment_count() {
     harness.cleanup().await;
 }
 
+#[tokio::test]
+async fn test_compact_all_honors_namespace_overrides() {
+    let harness = TestHarness::new().await;
+    let store = &harness.store;
+    let writer = WalWriter::new(store.clone());
+    let manager = NamespaceManager::new(store.clone());
+    let compactor = test_compactor(store); // max_wal_fragments_before_compact: 3
+
+    // eager compacts at 2 fragments, lazy only at 10, aged on fragment age.
+    let eager = format!("{}-override-eager", harness.prefix);
+    let lazy = format!("{}-override-lazy", harness.prefix);
+    let aged = format!("{}-override-aged", harness.prefix);
+    let overrides = [
+        (
+            &eager,
+            CompactionOverrides {
+                max_wal_fragments_before_compact: Some(2),
+                ..Default::default()
+            },
+            2,
+        ),
+        (
+            &lazy,
+            CompactionOverrides {
+                max_wal_fragments_before_compact: Some(10),
+                ..Default::default()
+            },
+            5,
+        ),
+        (
+            &aged,
+            CompactionOverrides {
+                max_fragment_age_secs: Some(0),
+                ..Default::default()
+            },
+            1,
+        ),
+    ];
+    for (ns, compaction, fragments) in &overrides {
+        manager
+            .create(ns, 16, DistanceMetric::Euclidean)
+            .await
+            .unwrap();
+        manager
+            .update_metadata(ns, |meta| meta.compaction = compaction.clone())
+            .await
+            .unwrap();
+        for _ in 0..*fragments {
+            writer
+                .append(ns, random_vectors(10, 16), vec![])
+                .await
+                .unwrap();
+        }
+    }
+
+    let mut namespaces = Vec::new();
+    for (ns, _, _) in &overrides {
+        namespaces.push(manager.get(ns).await.unwrap());
+    }
+    let results = compactor.compact_all(&namespaces).await;
+    let mut compacted: Vec<&str> = results.iter().map(|r| r.namespace.as_str()).collect();
+    compacted.sort();
+    let mut expected = vec![aged.as_str(), eager.as_str()];
+    expected.sort();
+    assert_eq!(compacted, expected);
+
+    for (ns, _, fragments) in &overrides {
+        let manifest = Manifest::read(store, ns).await.unwrap().unwrap();
+        if *ns == &lazy {
+            // 5 fragments: over the global threshold, under its own.
+            assert_eq!(manifest.fragments.len(), *fragments);
+            assert!(manifest.segments.is_empty());
+        } else {
+            assert!(manifest.fragments.is_empty());
+            assert_eq!(manifest.segments.len(), 1);
+        }
+    }
+
+    for (ns, _, _) in &overrides {
+        manager.delete(ns).await.unwrap();
+    }
+    harness.cleanup().await;
+}
+
 #[tokio::test]
 async fn test_compact_attributes_preserved() {
     let harness = TestHarness::new().await;
@@ -647,3 +840,1292 @@ async fn test_compact_attributes_preserved() {
 
     harness.cleanup().await;
 }
+
+#[tokio::test]
+async fn test_presence_filters_match_vectors_without_attributes() {
+    let store = zeppelin::storage::ZeppelinStore::new(std::sync::Arc::new(
+        object_store::memory::InMemory::new(),
+    ));
+    let ns = "presence-filters";
+
+    // vec_0..4 are tagged, vec_5..7 have other attributes, vec_8..11 none.
+    let mut vecs = random_vectors(12, 8);
+    for (i, v) in vecs.iter_mut().enumerate() {
+        v.attributes = match i {
+            0..=4 => Some([("tag".to_string(), AttributeValue::Integer(i as i64))].into()),
+            5..=7 => Some([("other".to_string(), AttributeValue::Bool(true))].into()),
+            _ => None,
+        };
+    }
+    let query = vecs[0].values.clone();
+    Manifest::new().write(&store, ns).await.unwrap();
+    WalWriter::new(store.clone())
+        .append(ns, vecs, vec![])
+        .await
+        .unwrap();
+
+    let wal_reader = WalReader::new(store.clone());
+    let matched = |filter: Filter, consistency: ConsistencyLevel| {
+        let (store, wal_reader, query) = (&store, &wal_reader, &query);
+        async move {
+            let result = execute_query(
+                store,
+                wal_reader,
+                ns,
+                query,
+                20,
+                16,
+                Some(&filter),
+                consistency,
+                DistanceMetric::Euclidean,
+                3,
+                None,
+            )
+            .await
+            .unwrap();
+            let mut ids: Vec<String> = result.results.into_iter().map(|r| r.id).collect();
+            ids.sort_by_key(|id| id[4..].parse::<usize>().unwrap());
+            ids
+        }
+    };
+    let ids = |range: std::ops::Range<usize>| -> Vec<String> {
+        range.map(|i| format!("vec_{i}")).collect()
+    };
+    let exists = || Filter::Exists {
+        field: "tag".to_string(),
+    };
+    let not_exists = || Filter::NotExists {
+        field: "tag".to_string(),
+    };
+
+    // WAL scan.
+    assert_eq!(matched(exists(), ConsistencyLevel::Strong).await, ids(0..5));
+    assert_eq!(
+        matched(not_exists(), ConsistencyLevel::Strong).await,
+        ids(5..12)
+    );
+
+    // Compacted segment scan.
+    test_compactor(&store).compact(ns).await.unwrap();
+    assert_eq!(
+        matched(exists(), ConsistencyLevel::Eventual).await,
+        ids(0..5)
+    );
+    assert_eq!(
+        matched(not_exists(), ConsistencyLevel::Eventual).await,
+        ids(5..12)
+    );
+}
+
+#[tokio::test]
+async fn test_compact_dedup_epsilon_collapses_near_duplicates() {
+    let harness = TestHarness::new().await;
+    let ns = harness.key("compact-dedup-eps");
+    let store = &harness.store;
+    let writer = WalWriter::new(store.clone());
+    let wal_reader = WalReader::new(store.clone());
+
+    let manifest = Manifest::new();
+    manifest.write(store, &ns).await.unwrap();
+
+    // 40 originals, then near-identical copies of the first 10 under new IDs.
+    let originals = random_vectors(40, 16);
+    let copies: Vec<VectorEntry> = originals[..10]
+        .iter()
+        .enumerate()
+        .map(|(i, v)| VectorEntry {
+            id: format!("dup_{i}"),
+            values: v.values.iter().map(|x| x + 1e-4).collect(),
+            attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
+        })
+        .collect();
+    let query_vec = originals[3].values.clone();
+    writer.append(&ns, originals, vec![]).await.unwrap();
+    writer.append(&ns, copies, vec![]).await.unwrap();
+
+    let compactor = Compactor::new(
+        store.clone(),
+        WalReader::new(store.clone()),
+        CompactionConfig {
+            dedup_epsilon: Some(0.01),
+            ..Default::default()
+        },
+        IndexingConfig {
+            default_num_centroids: 4,
+            ..Default::default()
+        },
+    );
+    let result = compactor.compact(&ns).await.unwrap();
+    assert_eq!(result.vectors_compacted, 40);
+
+    // The later copy survives; the original it shadowed is gone.
+    let result = execute_query(
+        store,
+        &wal_reader,
+        &ns,
+        &query_vec,
+        40,
+        4,
+        None,
+        ConsistencyLevel::Eventual,
+        DistanceMetric::Euclidean,
+        3,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.results[0].id, "dup_3");
+    assert!(!result.results.iter().any(|r| r.id == "vec_3"));
+
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_compact_dedup_epsilon_keeps_reupserted_vector() {
+    let store = zeppelin::storage::ZeppelinStore::new(std::sync::Arc::new(
+        object_store::memory::InMemory::new(),
+    ));
+    let ns = "dedup-reupsert";
+    Manifest::new().write(&store, ns).await.unwrap();
+    let writer = WalWriter::new(store.clone());
+    let wal_reader = WalReader::new(store.clone());
+
+    let entry = |id: &str, values: Vec<f32>| VectorEntry {
+        id: id.to_string(),
+        values,
+        attributes: None,
+        sparse: None,
+        named_vectors: None,
+        token_vectors: None,
+        version: None,
+    };
+    // Writes a, b, b, a: "a" is the latest even though both IDs were
+    // written twice.
+    writer
+        .append(ns, random_vectors(20, 4), vec![])
+        .await
+        .unwrap();
+    for (id, values) in [
+        ("a", vec![1.0, 0.0, 0.0, 0.0]),
+        ("b", vec![1.0, 0.0, 0.0, 1e-4]),
+        ("b", vec![1.0, 0.0, 0.0, 1e-4]),
+        ("a", vec![1.0, 0.0, 0.0, 0.0]),
+    ] {
+        writer
+            .append(ns, vec![entry(id, values)], vec![])
+            .await
+            .unwrap();
+    }
+
+    let compactor = Compactor::new(
+        store.clone(),
+        WalReader::new(store.clone()),
+        CompactionConfig {
+            dedup_epsilon: Some(0.01),
+            ..Default::default()
+        },
+        IndexingConfig {
+            default_num_centroids: 4,
+            ..Default::default()
+        },
+    );
+    let result = compactor.compact(ns).await.unwrap();
+    assert_eq!(result.vectors_compacted, 21);
+
+    let result = execute_query(
+        &store,
+        &wal_reader,
+        ns,
+        &[1.0, 0.0, 0.0, 0.0],
+        21,
+        4,
+        None,
+        ConsistencyLevel::Eventual,
+        DistanceMetric::Euclidean,
+        3,
+        None,
+    )
+    .await
+    .unwrap();
+    let ids: Vec<&str> = result.results.iter().map(|r| r.id.as_str()).collect();
+    assert!(ids.contains(&"a"), "{ids:?}");
+    assert!(!ids.contains(&"b"), "{ids:?}");
+}
+
+#[tokio::test]
+async fn test_compact_cluster_by_attribute() {
+    let store = zeppelin::storage::ZeppelinStore::new(std::sync::Arc::new(
+        object_store::memory::InMemory::new(),
+    ));
+    let vecs = with_attributes(random_vectors(100, 16), simple_attributes);
+    let query_vec = vecs[42].values.clone();
+    let filter = Filter::Range {
+        field: "score".to_string(),
+        gte: Some(40.0),
+        lte: None,
+        gt: None,
+        lt: Some(50.0),
+    };
+
+    // Clusters a range filter on `score` leaves to scan out of all 10,
+    // once their attribute stats have pruned the rest.
+    let probed = |cluster_by: ClusterBy, ns: &'static str| {
+        let (store, vecs, query_vec, filter) = (&store, vecs.clone(), &query_vec, &filter);
+        async move {
+            Manifest::new().write(store, ns).await.unwrap();
+            WalWriter::new(store.clone())
+                .append(ns, vecs, vec![])
+                .await
+                .unwrap();
+            let compactor = Compactor::new(
+                store.clone(),
+                WalReader::new(store.clone()),
+                CompactionConfig {
+                    cluster_by,
+                    ..Default::default()
+                },
+                IndexingConfig {
+                    default_num_centroids: 10,
+                    ..Default::default()
+                },
+            );
+            let result = compactor.compact(ns).await.unwrap();
+            assert_eq!(result.vectors_compacted, 100);
+
+            let plan = zeppelin::query::explain_query(
+                store,
+                ns,
+                query_vec,
+                20,
+                10,
+                Some(filter),
+                ConsistencyLevel::Eventual,
+                DistanceMetric::Euclidean,
+                None,
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+            let segment = plan.segment.unwrap();
+            assert_eq!(segment.cluster_count, 10);
+            assert_eq!(
+                segment.probed_clusters.len() + segment.pruned_clusters.len(),
+                10
+            );
+            segment.probed_clusters.len()
+        }
+    };
+
+    // Ten consecutive scores sit in one or two score-ordered clusters, but
+    // spread over most k-means clusters.
+    let by_attribute = probed(ClusterBy::Attribute("score".to_string()), "by-attr").await;
+    let by_vector = probed(ClusterBy::Vector, "by-vector").await;
+    assert!(by_attribute <= 2, "probed {by_attribute} clusters");
+    assert!(
+        by_vector > by_attribute + 2,
+        "k-means probed {by_vector} clusters, attribute clustering {by_attribute}"
+    );
+
+    // Range filter on the clustering attribute returns exactly the range.
+    let wal_reader = WalReader::new(store.clone());
+    let result = execute_query(
+        &store,
+        &wal_reader,
+        "by-attr",
+        &query_vec,
+        20,
+        10,
+        Some(&filter),
+        ConsistencyLevel::Eventual,
+        DistanceMetric::Euclidean,
+        3,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.results.len(), 10);
+    assert_eq!(result.results[0].id, "vec_42");
+}
+
+#[tokio::test]
+async fn test_compact_ivf_pq_namespace() {
+    let harness = TestHarness::new().await;
+    let ns = format!("{}-ivf-pq", harness.prefix);
+    let store = &harness.store;
+    let writer = WalWriter::new(store.clone());
+    let wal_reader = WalReader::new(store.clone());
+
+    let manager = NamespaceManager::new(store.clone());
+    let mut meta = NamespaceMetadata::new(&ns, 16, DistanceMetric::Euclidean);
+    meta.index_type = IndexType::IvfPq;
+    let meta = manager.create_with_options(meta).await.unwrap();
+    assert_eq!(meta.index_type, IndexType::IvfPq);
+
+    let (vectors, centroids) = clustered_vectors(4, 50, 16, 0.05);
+    writer.append(&ns, vectors.clone(), vec![]).await.unwrap();
+
+    // Server-wide config is unquantized; the namespace type selects PQ.
+    let compactor = Compactor::new(
+        store.clone(),
+        WalReader::new(store.clone()),
+        CompactionConfig::default(),
+        IndexingConfig {
+            default_num_centroids: 4,
+            kmeans_max_iterations: 10,
+            pq_m: 4,
+            ..Default::default()
+        },
+    );
+    compactor.compact(&ns).await.unwrap();
+
+    let manifest = Manifest::read(store, &ns).await.unwrap().unwrap();
+    assert_eq!(manifest.segments.len(), 1);
+    assert_eq!(manifest.segments[0].quantization, QuantizationType::Product);
+    assert!(!manifest.segments[0].hierarchical);
+
+    let query = &centroids[0];
+    let result = execute_query(
+        store,
+        &wal_reader,
+        &ns,
+        query,
+        10,
+        4,
+        None,
+        ConsistencyLevel::Eventual,
+        DistanceMetric::Euclidean,
+        3,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let mut distances: Vec<(&str, f32)> = vectors
+        .iter()
+        .map(|v| {
+            (
+                v.id.as_str(),
+                compute_distance(query, &v.values, DistanceMetric::Euclidean),
+            )
+        })
+        .collect();
+    distances.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    let ground_truth: Vec<&str> = distances.iter().take(10).map(|(id, _)| *id).collect();
+    assert_recall_at_k(&result.results, &ground_truth, 10, 0.8);
+
+    harness.cleanup().await;
+}
+
+/// In-memory store that, while `swaps` is non-zero, fails reads of segment
+/// centroids as if compaction had just replaced the segment, and rewrites
+/// the namespace manifest (bumping its ETag) to mimic the concurrent swap.
+fn manifest_swapping_store(swaps: Arc<AtomicUsize>) -> HookedStore {
+    HookedStore::new().on_get(move |inner, location| {
+        let swaps = swaps.clone();
+        Box::pin(async move {
+            let path = location.as_ref();
+            if path.ends_with("centroids.bin")
+                && swaps
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                    .is_ok()
+            {
+                let ns = path.split("/segments/").next().unwrap();
+                let manifest_path = object_store::path::Path::from(Manifest::s3_key(ns));
+                let bytes = inner.get(&manifest_path).await?.bytes().await?;
+                inner.put(&manifest_path, bytes.into()).await?;
+                return Err(object_store::Error::NotFound {
+                    path: path.to_string(),
+                    source: "segment replaced by compaction".into(),
+                });
+            }
+            Ok(())
+        })
+    })
+}
+
+#[tokio::test]
+async fn test_query_retries_when_manifest_swapped() {
+    let swaps = Arc::new(AtomicUsize::new(0));
+    let store =
+        zeppelin::storage::ZeppelinStore::new(Arc::new(manifest_swapping_store(swaps.clone())));
+    let ns = "manifest-swap";
+
+    Manifest::new().write(&store, ns).await.unwrap();
+    let vecs = random_vectors(50, 16);
+    let query = vecs[0].values.clone();
+    WalWriter::new(store.clone())
+        .append(ns, vecs, vec![])
+        .await
+        .unwrap();
+    test_compactor(&store).compact(ns).await.unwrap();
+
+    let wal_reader = WalReader::new(store.clone());
+    let run = || {
+        execute_query(
+            &store,
+            &wal_reader,
+            ns,
+            &query,
+            5,
+            4,
+            None,
+            ConsistencyLevel::Eventual,
+            DistanceMetric::Euclidean,
+            3,
+            None,
+        )
+    };
+
+    // One swap mid-query: the retry on the new manifest succeeds.
+    swaps.store(1, Ordering::SeqCst);
+    let result = run().await.unwrap();
+    assert_eq!(result.results.len(), 5);
+    assert_eq!(swaps.load(Ordering::SeqCst), 0);
+
+    // The manifest keeps changing: transient 503.
+    swaps.store(10, Ordering::SeqCst);
+    let err = run().await.unwrap_err();
+    assert!(
+        matches!(err, zeppelin::error::ZeppelinError::IndexUpdating { .. }),
+        "expected IndexUpdating, got: {err}"
+    );
+    assert_eq!(err.status_code(), 503);
+
+    // A failure without a manifest change is returned as-is.
+    swaps.store(0, Ordering::SeqCst);
+    store
+        .delete(&format!(
+            "{ns}/segments/{}/centroids.bin",
+            Manifest::read(&store, ns)
+                .await
+                .unwrap()
+                .unwrap()
+                .active_segment
+                .unwrap()
+        ))
+        .await
+        .unwrap();
+    let err = run().await.unwrap_err();
+    assert_eq!(err.status_code(), 404);
+}
+
+/// In-memory store that records the path of every object read.
+fn read_recording_store(reads: Arc<Mutex<Vec<String>>>) -> HookedStore {
+    HookedStore::new().on_get(move |_, location| {
+        reads.lock().unwrap().push(location.to_string());
+        Box::pin(async { Ok(()) })
+    })
+}
+
+#[tokio::test]
+async fn test_unfiltered_query_without_attributes_skips_attr_reads() {
+    let reads = Arc::new(Mutex::new(Vec::new()));
+    let store =
+        zeppelin::storage::ZeppelinStore::new(Arc::new(read_recording_store(reads.clone())));
+    let ns = "skip-attrs";
+
+    Manifest::new().write(&store, ns).await.unwrap();
+    let vecs = with_attributes(random_vectors(50, 16), simple_attributes);
+    let query = vecs[0].values.clone();
+    WalWriter::new(store.clone())
+        .append(ns, vecs, vec![])
+        .await
+        .unwrap();
+    test_compactor(&store).compact(ns).await.unwrap();
+
+    let wal_reader = WalReader::new(store.clone());
+    let run = |skip_attributes: bool| {
+        let options = zeppelin::query::QueryOptions {
+            skip_attributes,
+            ..Default::default()
+        };
+        let (store, wal_reader, query) = (&store, &wal_reader, &query);
+        async move {
+            zeppelin::query::execute_query_with_options(
+                store,
+                wal_reader,
+                ns,
+                query,
+                5,
+                4,
+                None,
+                ConsistencyLevel::Eventual,
+                DistanceMetric::Euclidean,
+                3,
+                None,
+                &options,
+            )
+            .await
+            .unwrap()
+        }
+    };
+    let attr_reads = || {
+        reads
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|path| path.contains("/attrs_"))
+            .count()
+    };
+
+    reads.lock().unwrap().clear();
+    let result = run(true).await;
+    assert_eq!(result.results.len(), 5);
+    assert!(result.results.iter().all(|r| r.attributes.is_none()));
+    assert_eq!(attr_reads(), 0, "attribute objects were fetched");
+
+    // Default behavior still enriches results with attributes.
+    let result = run(false).await;
+    assert!(result.results.iter().all(|r| r.attributes.is_some()));
+    assert!(attr_reads() > 0);
+}
+
+#[tokio::test]
+async fn test_ids_only_query_returns_id_and_score_without_attr_reads() {
+    let reads = Arc::new(Mutex::new(Vec::new()));
+    let store =
+        zeppelin::storage::ZeppelinStore::new(Arc::new(read_recording_store(reads.clone())));
+    let (base_url, _dir) = start_test_server_with_store(store.clone()).await;
+    let client = reqwest::Client::new();
+    let ns = "ids-only";
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({"name": ns, "dimensions": 16}))
+        .send()
+        .await
+        .unwrap();
+    let vecs = with_attributes(random_vectors(50, 16), simple_attributes);
+    let query = vecs[0].values.clone();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({ "vectors": vecs }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    test_compactor(&store).compact(ns).await.unwrap();
+
+    reads.lock().unwrap().clear();
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({"vector": query, "top_k": 5, "ids_only": true}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), 5);
+    assert_eq!(results[0]["id"], "vec_0");
+    for r in results {
+        let keys: Vec<&String> = r.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["id", "score"]);
+    }
+    let attr_reads = reads
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|path| path.contains("/attrs_"))
+        .count();
+    assert_eq!(attr_reads, 0, "attribute objects were fetched");
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({
+            "vector": query,
+            "ids_only": true,
+            "include_rank": true,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_attribute_scan_reads_id_maps_and_fails_on_sidecar_errors() {
+    let reads = Arc::new(Mutex::new(Vec::new()));
+    let fail_attrs = Arc::new(AtomicBool::new(false));
+    let backend = {
+        let (reads, fail_attrs) = (reads.clone(), fail_attrs.clone());
+        HookedStore::new().on_get(move |_, location| {
+            reads.lock().unwrap().push(location.to_string());
+            let fail = location.as_ref().contains("/attrs_") && fail_attrs.load(Ordering::SeqCst);
+            Box::pin(async move {
+                if fail {
+                    return Err(object_store::Error::Generic {
+                        store: "HookedStore",
+                        source: "injected attribute read failure".into(),
+                    });
+                }
+                Ok(())
+            })
+        })
+    };
+    let store = zeppelin::storage::ZeppelinStore::new(Arc::new(backend));
+    let ns = "attr-scan";
+
+    Manifest::new().write(&store, ns).await.unwrap();
+    WalWriter::new(store.clone())
+        .append(
+            ns,
+            with_attributes(random_vectors(50, 16), simple_attributes),
+            vec![],
+        )
+        .await
+        .unwrap();
+    test_compactor(&store).compact(ns).await.unwrap();
+    let wal_reader = WalReader::new(store.clone());
+
+    // IDs come from the segment's ID map, not the vector-bearing clusters.
+    reads.lock().unwrap().clear();
+    let resp = zeppelin::query::count_by(&store, &wal_reader, ns, "category", None, 10)
+        .await
+        .unwrap();
+    assert_eq!(resp.counts.values().sum::<u64>(), 50);
+    let cluster_reads = reads
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|path| path.contains("/cluster_"))
+        .count();
+    assert_eq!(cluster_reads, 0, "cluster blobs were fetched");
+
+    // A failed sidecar read is an error, not a partition without attributes.
+    fail_attrs.store(true, Ordering::SeqCst);
+    let result = zeppelin::query::count_by(&store, &wal_reader, ns, "category", None, 10).await;
+    assert!(result.is_err(), "expected read failure, got: {result:?}");
+}
+
+#[tokio::test]
+async fn test_filter_patch_expansion_reads_no_cluster_blobs() {
+    let reads = Arc::new(Mutex::new(Vec::new()));
+    let store =
+        zeppelin::storage::ZeppelinStore::new(Arc::new(read_recording_store(reads.clone())));
+    let ns = "filter-patch-scan";
+
+    Manifest::new().write(&store, ns).await.unwrap();
+    let writer = WalWriter::new(store.clone());
+    writer
+        .append(
+            ns,
+            with_attributes(random_vectors(50, 16), simple_attributes),
+            vec![],
+        )
+        .await
+        .unwrap();
+    test_compactor(&store).compact(ns).await.unwrap();
+    writer
+        .append_patches(
+            ns,
+            vec![],
+            vec![zeppelin::wal::FilterPatch {
+                filter: Filter::Eq {
+                    field: "category".to_string(),
+                    value: AttributeValue::String("z".to_string()),
+                },
+                attributes: [("tag".to_string(), AttributeValue::String("x".to_string()))].into(),
+            }],
+        )
+        .await
+        .unwrap();
+
+    // Expanding the uncompacted filter patch scans every live vector's
+    // attributes, but only through ID maps and attribute sidecars. The
+    // patch matches nothing, so no base versions are fetched either.
+    reads.lock().unwrap().clear();
+    let wal_reader = WalReader::new(store.clone());
+    let resp = zeppelin::query::count_by(&store, &wal_reader, ns, "tag", None, 10)
+        .await
+        .unwrap();
+    assert!(resp.counts.is_empty());
+    let cluster_reads = reads
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|path| path.contains("/cluster_"))
+        .count();
+    assert_eq!(cluster_reads, 0, "cluster blobs were fetched");
+}
+
+#[tokio::test]
+async fn test_compaction_fails_when_namespace_metadata_unreadable() {
+    let fail_meta = Arc::new(AtomicBool::new(false));
+    let backend = {
+        let fail_meta = fail_meta.clone();
+        HookedStore::new().on_get(move |_, location| {
+            let fail = location.as_ref().ends_with("meta.json") && fail_meta.load(Ordering::SeqCst);
+            Box::pin(async move {
+                if fail {
+                    return Err(object_store::Error::Generic {
+                        store: "HookedStore",
+                        source: "injected metadata read failure".into(),
+                    });
+                }
+                Ok(())
+            })
+        })
+    };
+    let store = zeppelin::storage::ZeppelinStore::new(Arc::new(backend));
+    let ns = "meta-unreadable";
+
+    NamespaceManager::new(store.clone())
+        .create(ns, 16, DistanceMetric::Cosine)
+        .await
+        .unwrap();
+    WalWriter::new(store.clone())
+        .append(ns, random_vectors(20, 16), vec![])
+        .await
+        .unwrap();
+
+    // Compacting with the server defaults instead would silently drop the
+    // namespace's own index settings.
+    fail_meta.store(true, Ordering::SeqCst);
+    assert!(test_compactor(&store).compact(ns).await.is_err());
+    let manifest = Manifest::read(&store, ns).await.unwrap().unwrap();
+    assert!(manifest.active_segment.is_none());
+
+    fail_meta.store(false, Ordering::SeqCst);
+    test_compactor(&store).compact(ns).await.unwrap();
+    let manifest = Manifest::read(&store, ns).await.unwrap().unwrap();
+    assert!(manifest.active_segment.is_some());
+}
+
+#[tokio::test]
+async fn test_pinned_namespace_centroids_survive_eviction() {
+    let harness = TestHarness::new().await;
+    let store = &harness.store;
+    let hot = harness.key("pinned-hot");
+    let cold = harness.key("pinned-cold");
+
+    let vecs = random_vectors(20, 4);
+    let query = vecs[0].values.clone();
+    for ns in [&hot, &cold] {
+        Manifest::new().write(store, ns).await.unwrap();
+        WalWriter::new(store.clone())
+            .append(ns, vecs.clone(), vec![])
+            .await
+            .unwrap();
+        test_compactor(store).compact(ns).await.unwrap();
+    }
+
+    let cache_dir = tempfile::TempDir::new().unwrap();
+    let cache = std::sync::Arc::new(
+        zeppelin::cache::DiskCache::new_with_max_bytes(cache_dir.path().to_path_buf(), 4096)
+            .unwrap(),
+    );
+    let wal_reader = WalReader::new(store.clone());
+    for (ns, cache_pinned) in [(&hot, true), (&cold, false)] {
+        let options = zeppelin::query::QueryOptions {
+            cache_pinned,
+            ..Default::default()
+        };
+        zeppelin::query::execute_query_with_options(
+            store,
+            &wal_reader,
+            ns,
+            &query,
+            5,
+            4,
+            None,
+            ConsistencyLevel::Eventual,
+            DistanceMetric::Euclidean,
+            3,
+            Some(&cache),
+            &options,
+        )
+        .await
+        .unwrap();
+    }
+
+    let segment_id = |ns: &str| {
+        let ns = ns.to_string();
+        async move {
+            Manifest::read(store, &ns)
+                .await
+                .unwrap()
+                .unwrap()
+                .active_segment
+                .unwrap()
+        }
+    };
+    let hot_centroids = format!("{hot}/segments/{}/centroids.bin", segment_id(&hot).await);
+    let cold_seg = segment_id(&cold).await;
+    let cold_clusters: Vec<String> = (0..4)
+        .map(|i| format!("{cold}/segments/{cold_seg}/cluster_{i}.bin"))
+        .collect();
+    assert!(cache.is_pinned(&hot_centroids).await);
+    assert!(cache.contains(&hot_centroids).await);
+    let mut cold_cached = 0;
+    for key in &cold_clusters {
+        cold_cached += cache.contains(key).await as usize;
+    }
+    assert!(cold_cached > 0, "cold namespace data was never cached");
+
+    // Fill the cache so every unpinned entry has to go.
+    cache
+        .put("filler", &bytes::Bytes::from(vec![0u8; 4000]))
+        .await
+        .unwrap();
+
+    assert!(cache.contains(&hot_centroids).await);
+    for key in &cold_clusters {
+        assert!(!cache.contains(key).await, "{key} survived eviction");
+    }
+}
+
+#[tokio::test]
+async fn test_dedupe_by_write_time_prefers_newer_segment() {
+    let harness = TestHarness::new().await;
+    let store = &harness.store;
+    let ns = harness.key("dedupe-write-time");
+    Manifest::new().write(store, &ns).await.unwrap();
+
+    let versioned = |version: &str, values: Vec<f32>| VectorEntry {
+        id: "x".to_string(),
+        values,
+        attributes: Some(std::collections::HashMap::from([(
+            "version".to_string(),
+            AttributeValue::String(version.to_string()),
+        )])),
+        sparse: None,
+        named_vectors: None,
+        token_vectors: None,
+        version: None,
+    };
+
+    // A writer builds its fragment first but commits it only after a
+    // compaction has folded a newer write of the same ID into the segment.
+    let stale = WalFragment::new(vec![versioned("stale", vec![0.0; 4])], vec![]);
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    let mut vecs = random_vectors(20, 4);
+    vecs.push(versioned("segment", vec![1.0; 4]));
+    WalWriter::new(store.clone())
+        .append(&ns, vecs, vec![])
+        .await
+        .unwrap();
+    test_compactor(store).compact(&ns).await.unwrap();
+
+    store
+        .put(
+            &WalFragment::s3_key(&ns, &stale.id),
+            stale.to_bytes().unwrap(),
+        )
+        .await
+        .unwrap();
+    let mut manifest = Manifest::read(store, &ns).await.unwrap().unwrap();
+    assert!(stale.id < manifest.compaction_watermark.unwrap());
+    manifest.add_fragment(zeppelin::wal::manifest::FragmentRef {
+        id: stale.id,
+        vector_count: 1,
+        delete_count: 0,
+        sequence_number: 0,
+        idempotency_key: None,
+        payload_hash: None,
+    });
+    manifest.write(store, &ns).await.unwrap();
+
+    let wal_reader = WalReader::new(store.clone());
+    let version_of_x = |dedupe_by_write_time: bool| {
+        let options = zeppelin::query::QueryOptions {
+            dedupe_by_write_time,
+            ..Default::default()
+        };
+        let (wal_reader, ns) = (&wal_reader, &ns);
+        async move {
+            let response = zeppelin::query::execute_query_with_options(
+                store,
+                wal_reader,
+                ns,
+                &[1.0; 4],
+                50,
+                4,
+                None,
+                ConsistencyLevel::Strong,
+                DistanceMetric::Euclidean,
+                3,
+                None,
+                &options,
+            )
+            .await
+            .unwrap();
+            let xs: Vec<_> = response.results.iter().filter(|r| r.id == "x").collect();
+            assert_eq!(xs.len(), 1, "x returned more than once");
+            xs[0].attributes.as_ref().unwrap()["version"].clone()
+        }
+    };
+
+    // By default the WAL is authoritative, even when it is older.
+    assert_eq!(
+        version_of_x(false).await,
+        AttributeValue::String("stale".to_string())
+    );
+    assert_eq!(
+        version_of_x(true).await,
+        AttributeValue::String("segment".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_tiered_compaction_merges_full_tier_and_drops_tombstones() {
+    let store = zeppelin::storage::ZeppelinStore::new(std::sync::Arc::new(
+        object_store::memory::InMemory::new(),
+    ));
+    let ns = "tiered";
+    Manifest::new().write(&store, ns).await.unwrap();
+    let writer = WalWriter::new(store.clone());
+    let compactor = Compactor::new(
+        store.clone(),
+        WalReader::new(store.clone()),
+        CompactionConfig {
+            merge_factor: Some(3),
+            ..Default::default()
+        },
+        IndexingConfig {
+            default_num_centroids: 4,
+            kmeans_max_iterations: 10,
+            ..Default::default()
+        },
+    );
+    let batch = |prefix: &str| -> Vec<VectorEntry> {
+        random_vectors(30, 8)
+            .into_iter()
+            .map(|mut v| {
+                v.id = format!("{prefix}_{}", v.id);
+                v
+            })
+            .collect()
+    };
+    let query_all = || {
+        let wal_reader = WalReader::new(store.clone());
+        let store = store.clone();
+        async move {
+            execute_query(
+                &store,
+                &wal_reader,
+                ns,
+                &[0.0; 8],
+                200,
+                4,
+                None,
+                ConsistencyLevel::Eventual,
+                DistanceMetric::Euclidean,
+                3,
+                None,
+            )
+            .await
+            .unwrap()
+        }
+    };
+
+    writer.append(ns, batch("a"), vec![]).await.unwrap();
+    let first = compactor.compact(ns).await.unwrap();
+    assert_eq!(first.segments_merged, 0);
+
+    // The second batch rewrites a_vec_0 and deletes a_vec_1: both are
+    // tombstoned in the first segment, which stays live.
+    let mut second = batch("b");
+    second.push(VectorEntry {
+        id: "a_vec_0".to_string(),
+        values: vec![0.5; 8],
+        attributes: None,
+        sparse: None,
+        named_vectors: None,
+        token_vectors: None,
+        version: None,
+    });
+    writer
+        .append(ns, second, vec!["a_vec_1".to_string()])
+        .await
+        .unwrap();
+    let result = compactor.compact(ns).await.unwrap();
+    assert_eq!(result.vectors_compacted, 31);
+    assert_eq!(result.segments_merged, 0);
+    assert!(result.old_segment_removed.is_none());
+
+    let manifest = Manifest::read(&store, ns).await.unwrap().unwrap();
+    assert_eq!(manifest.live_segments().len(), 2);
+    assert_eq!(
+        manifest.tier_segments,
+        vec![first.segment_id.clone().unwrap()]
+    );
+    let older = manifest.live_segments()[0];
+    assert_eq!(
+        older.tombstones.iter().collect::<Vec<_>>(),
+        vec!["a_vec_0", "a_vec_1"]
+    );
+
+    let response = query_all().await;
+    assert_eq!(response.scanned_segments, 2);
+    assert_eq!(response.results.len(), 59);
+    assert!(response.results.iter().all(|r| r.id != "a_vec_1"));
+    assert_eq!(
+        response
+            .results
+            .iter()
+            .filter(|r| r.id == "a_vec_0")
+            .count(),
+        1
+    );
+    let report = zeppelin::compaction::verify::verify_namespace(&store, ns)
+        .await
+        .unwrap();
+    assert!(report.is_consistent(), "{:?}", report.inconsistencies);
+
+    // A third segment of similar size fills the tier: all three merge.
+    writer.append(ns, batch("c"), vec![]).await.unwrap();
+    let result = compactor.compact(ns).await.unwrap();
+    assert_eq!(result.segments_merged, 3);
+
+    let manifest = Manifest::read(&store, ns).await.unwrap().unwrap();
+    assert_eq!(manifest.segments.len(), 1);
+    assert!(manifest.tier_segments.is_empty());
+    let merged = manifest.live_segments()[0];
+    assert_eq!(merged.vector_count, 89);
+    assert!(merged.tombstones.is_empty());
+
+    let response = query_all().await;
+    assert_eq!(response.scanned_segments, 1);
+    assert_eq!(response.results.len(), 89);
+    assert!(response.results.iter().all(|r| r.id != "a_vec_1"));
+}
+
+#[tokio::test]
+async fn test_get_vector_prefers_wal_and_skips_tombstoned_segments() {
+    let store = zeppelin::storage::ZeppelinStore::new(std::sync::Arc::new(
+        object_store::memory::InMemory::new(),
+    ));
+    let ns = "get-vector";
+    Manifest::new().write(&store, ns).await.unwrap();
+    let writer = WalWriter::new(store.clone());
+    let compactor = Compactor::new(
+        store.clone(),
+        WalReader::new(store.clone()),
+        CompactionConfig {
+            merge_factor: Some(4),
+            ..Default::default()
+        },
+        IndexingConfig {
+            default_num_centroids: 4,
+            kmeans_max_iterations: 10,
+            ..Default::default()
+        },
+    );
+    // Values and attributes of the latest version, if any.
+    type Found = Option<(
+        Vec<f32>,
+        Option<std::collections::HashMap<String, AttributeValue>>,
+    )>;
+    let get = |id: &'static str| {
+        let store = store.clone();
+        async move {
+            zeppelin::query::get_vector(&store, &WalReader::new(store.clone()), ns, id)
+                .await
+                .unwrap()
+                .map(|v| {
+                    assert_eq!(v.id, id);
+                    (v.values, v.attributes)
+                })
+        }
+    };
+    let found = |v: &VectorEntry| -> Found { Some((v.values.clone(), v.attributes.clone())) };
+
+    let vectors = with_attributes(random_vectors(40, 8), simple_attributes);
+    writer.append(ns, vectors.clone(), vec![]).await.unwrap();
+    assert_eq!(get("vec_2").await, found(&vectors[2]));
+
+    // Served from a segment once compacted.
+    compactor.compact(ns).await.unwrap();
+    assert_eq!(get("vec_2").await, found(&vectors[2]));
+    assert!(get("missing").await.is_none());
+
+    // Uncompacted rewrites and deletes shadow the segment.
+    let rewritten = VectorEntry {
+        id: "vec_0".to_string(),
+        values: vec![0.5; 8],
+        attributes: None,
+        sparse: None,
+        named_vectors: None,
+        token_vectors: None,
+        version: None,
+    };
+    writer
+        .append(ns, vec![rewritten.clone()], vec!["vec_1".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(get("vec_0").await, found(&rewritten));
+    assert!(get("vec_1").await.is_none());
+
+    // Tiered compaction leaves the old copies tombstoned in the older segment.
+    compactor.compact(ns).await.unwrap();
+    let manifest = Manifest::read(&store, ns).await.unwrap().unwrap();
+    assert_eq!(manifest.live_segments().len(), 2);
+    assert_eq!(get("vec_0").await, found(&rewritten));
+    assert!(get("vec_1").await.is_none());
+    assert_eq!(get("vec_3").await, found(&vectors[3]));
+}
+
+#[tokio::test]
+async fn test_compaction_writes_segment_id_map() {
+    use zeppelin::index::id_map::{segment_id_map_key, SegmentIdMap};
+
+    let store = zeppelin::storage::ZeppelinStore::new(std::sync::Arc::new(
+        object_store::memory::InMemory::new(),
+    ));
+    let ns = "id-map";
+    Manifest::new().write(&store, ns).await.unwrap();
+    let vectors = random_vectors(60, 8);
+    WalWriter::new(store.clone())
+        .append(ns, vectors.clone(), vec![])
+        .await
+        .unwrap();
+    let result = test_compactor(&store).compact(ns).await.unwrap();
+    let seg_id = result.segment_id.unwrap();
+    let manifest = Manifest::read(&store, ns).await.unwrap().unwrap();
+    let cluster_count = manifest.live_segments()[0].cluster_count;
+    assert!(cluster_count > 1);
+
+    let data = store.get(&segment_id_map_key(ns, &seg_id)).await.unwrap();
+    let id_map = SegmentIdMap::from_bytes(&data).unwrap();
+    let mut ids: Vec<&str> = vectors.iter().map(|v| v.id.as_str()).collect();
+    ids.sort_unstable();
+    assert_eq!(id_map.ids(), ids);
+    assert!(ids
+        .iter()
+        .all(|id| id_map.partition_of(id).is_some_and(|p| p < cluster_count)));
+
+    // Fetches from the compacted segment go through the map.
+    let wanted: Vec<String> = vec![vectors[59].id.clone(), vectors[0].id.clone()];
+    let fetched =
+        zeppelin::query::fetch_vectors(&store, &WalReader::new(store.clone()), ns, &wanted)
+            .await
+            .unwrap();
+    assert_eq!(fetched.len(), 2);
+    assert_eq!(fetched[0].values, vectors[59].values);
+    assert_eq!(fetched[1].values, vectors[0].values);
+}
+
+#[tokio::test]
+async fn test_list_vectors_across_tier_segments() {
+    let store = zeppelin::storage::ZeppelinStore::new(std::sync::Arc::new(
+        object_store::memory::InMemory::new(),
+    ));
+    let ns = "list-tiered";
+    Manifest::new().write(&store, ns).await.unwrap();
+    let writer = WalWriter::new(store.clone());
+    let compactor = Compactor::new(
+        store.clone(),
+        WalReader::new(store.clone()),
+        CompactionConfig {
+            merge_factor: Some(4),
+            ..Default::default()
+        },
+        IndexingConfig {
+            default_num_centroids: 4,
+            kmeans_max_iterations: 10,
+            ..Default::default()
+        },
+    );
+
+    let first = random_vectors(30, 8);
+    writer.append(ns, first.clone(), vec![]).await.unwrap();
+    compactor.compact(ns).await.unwrap();
+    let second: Vec<VectorEntry> = random_vectors(30, 8)
+        .into_iter()
+        .map(|mut v| {
+            v.id = format!("b_{}", v.id);
+            v
+        })
+        .collect();
+    writer
+        .append(ns, second.clone(), vec!["vec_3".to_string()])
+        .await
+        .unwrap();
+    compactor.compact(ns).await.unwrap();
+    let manifest = Manifest::read(&store, ns).await.unwrap().unwrap();
+    assert_eq!(manifest.live_segments().len(), 2);
+
+    let wal_reader = WalReader::new(store.clone());
+    let mut ids = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = zeppelin::query::list_vectors(&store, &wal_reader, ns, cursor.as_deref(), 11)
+            .await
+            .unwrap();
+        ids.extend(page.vectors.into_iter().map(|v| v.id));
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+
+    let mut expected: Vec<String> = first
+        .iter()
+        .chain(&second)
+        .map(|v| v.id.clone())
+        .filter(|id| id != "vec_3")
+        .collect();
+    expected.sort();
+    assert_eq!(ids, expected);
+}
+
+#[tokio::test]
+async fn test_multi_vector_query_scores_candidates_exactly() {
+    let store = zeppelin::storage::ZeppelinStore::new(std::sync::Arc::new(
+        object_store::memory::InMemory::new(),
+    ));
+    let ns = "multi-vector-exact";
+
+    let entry = |id: &str, values: Vec<f32>| VectorEntry {
+        id: id.to_string(),
+        values,
+        attributes: None,
+        sparse: None,
+        named_vectors: None,
+        token_vectors: None,
+        version: None,
+    };
+    let vecs = vec![
+        entry("a", vec![1.0, 0.0]),
+        entry("b", vec![0.0, 1.0]),
+        entry("far", vec![5.0, 5.0]),
+    ];
+    Manifest::new().write(&store, ns).await.unwrap();
+    WalWriter::new(store.clone())
+        .append(ns, vecs, vec![])
+        .await
+        .unwrap();
+
+    // Each query's single-result search returns only its own match, so "a"
+    // is missing from the second list and "b" from the first.
+    let queries = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+    let result = zeppelin::query::execute_multi_vector_query(
+        &store,
+        &WalReader::new(store.clone()),
+        ns,
+        &queries,
+        Some(&[2.0, 1.0]),
+        1,
+        16,
+        None,
+        ConsistencyLevel::Strong,
+        DistanceMetric::Euclidean,
+        1,
+        None,
+        &Default::default(),
+    )
+    .await
+    .unwrap();
+
+    let exact = 2.0 * compute_distance(&queries[0], &[1.0, 0.0], DistanceMetric::Euclidean)
+        + compute_distance(&queries[1], &[1.0, 0.0], DistanceMetric::Euclidean);
+    assert_eq!(result.results.len(), 1);
+    assert_eq!(result.results[0].id, "a");
+    assert!((result.results[0].score - exact).abs() < 1e-5);
+    assert!(result.results[0].values.is_none());
+}
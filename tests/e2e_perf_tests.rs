@@ -25,6 +25,10 @@ fn prefixed_vectors(prefix: &str, n: usize, dims: usize) -> Vec<VectorEntry> {
             id: format!("{prefix}_vec_{i}"),
             values: (0..dims).map(|_| rng.gen_range(-1.0..1.0)).collect(),
             attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         })
         .collect()
 }
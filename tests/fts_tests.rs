@@ -39,6 +39,10 @@ fn content_doc(id: &str, text: &str) -> VectorEntry {
         id: id.to_string(),
         values: vec![0.1, 0.2, 0.3, 0.4],
         attributes: Some(attrs),
+        sparse: None,
+        named_vectors: None,
+        token_vectors: None,
+        version: None,
     }
 }
 
@@ -57,6 +61,10 @@ fn title_content_doc(id: &str, title: &str, content: &str) -> VectorEntry {
         id: id.to_string(),
         values: vec![0.1, 0.2, 0.3, 0.4],
         attributes: Some(attrs),
+        sparse: None,
+        named_vectors: None,
+        token_vectors: None,
+        version: None,
     }
 }
 
@@ -1073,3 +1081,225 @@ async fn test_fts_field_not_configured_error() {
     cleanup_ns(&harness.store, &ns).await;
     harness.cleanup().await;
 }
+
+// ---------------------------------------------------------------------------
+// Test 13: rebuild_fts indexes a new field without touching vector clusters
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_fts_rebuild_indexes_new_field() {
+    let config = fts_test_config();
+    let (base_url, harness, _cache, _dir, compactor) =
+        start_test_server_with_compactor(Some(config)).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "fts-rebuild");
+
+    create_fts_namespace(
+        &client,
+        &base_url,
+        &ns,
+        serde_json::json!({
+            "title": {"language": "english", "stemming": true},
+            "content": {"language": "english", "stemming": true, "remove_stopwords": true}
+        }),
+    )
+    .await;
+
+    let docs: Vec<VectorEntry> = (0..8)
+        .map(|i| {
+            let mut doc = title_content_doc(
+                &format!("doc{i}"),
+                if i % 2 == 0 {
+                    "Rust guide"
+                } else {
+                    "Pasta recipe"
+                },
+                "some shared body text",
+            );
+            doc.values = vec![i as f32, 1.0, 0.5, 0.25];
+            doc
+        })
+        .collect();
+    upsert_docs(&client, &base_url, &ns, &docs).await;
+
+    // The segment is first built with only "content" indexed.
+    compactor
+        .compact_with_fts(&ns, None, &content_fts_configs())
+        .await
+        .unwrap();
+    let manifest = zeppelin::wal::Manifest::read(&harness.store, &ns)
+        .await
+        .unwrap()
+        .unwrap();
+    let segment_id = manifest.active_segment.clone().unwrap();
+
+    let title_query = serde_json::json!({
+        "rank_by": ["title", "BM25", "rust"],
+        "top_k": 10,
+        "consistency": "eventual",
+    });
+    let vector_query = serde_json::json!({
+        "vector": [3.0, 1.0, 0.5, 0.25],
+        "top_k": 8,
+        "consistency": "eventual",
+    });
+    let body = bm25_query(&client, &base_url, &ns, title_query.clone()).await;
+    assert!(result_ids(&body).is_empty());
+    let vector_before =
+        result_ids(&bm25_query(&client, &base_url, &ns, vector_query.clone()).await);
+
+    let mut fts_configs = content_fts_configs();
+    fts_configs.insert(
+        "title".to_string(),
+        FtsFieldConfig {
+            stemming: true,
+            ..Default::default()
+        },
+    );
+    let mut fields = compactor
+        .rebuild_fts(&ns, &fts_configs)
+        .await
+        .unwrap()
+        .unwrap();
+    fields.sort();
+    assert_eq!(fields, vec!["content", "title"]);
+
+    let manifest = zeppelin::wal::Manifest::read(&harness.store, &ns)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(manifest.active_segment.as_ref(), Some(&segment_id));
+    let seg_ref = manifest
+        .segments
+        .iter()
+        .find(|s| s.id == segment_id)
+        .unwrap();
+    assert!(seg_ref.fts_fields.contains(&"title".to_string()));
+
+    let mut ids = result_ids(&bm25_query(&client, &base_url, &ns, title_query).await);
+    ids.sort();
+    assert_eq!(ids, vec!["doc0", "doc2", "doc4", "doc6"]);
+    let vector_after = result_ids(&bm25_query(&client, &base_url, &ns, vector_query).await);
+    assert_eq!(vector_after, vector_before);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+// ---------------------------------------------------------------------------
+// Test 14: Text-only namespace — BM25 segments without vector artifacts
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_fts_text_only_namespace() {
+    // FTS indexes are built for text-only segments even with fts_index off.
+    let mut config = fts_test_config();
+    config.indexing.fts_index = false;
+    let (base_url, harness, _cache, _dir, compactor) =
+        start_test_server_with_compactor(Some(config)).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "fts-text-only");
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 0,
+            "full_text_search": {"content": {"language": "english", "stemming": true}},
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201, "{}", resp.text().await.unwrap());
+
+    let doc = |id: &str, text: &str| serde_json::json!({"id": id, "attributes": {"content": text}});
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({"vectors": [
+            doc("doc1", "Rust programming language is fast"),
+            doc("doc2", "Cooking Italian pasta at home"),
+            doc("doc3", "Rust ownership prevents data races"),
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200, "{}", resp.text().await.unwrap());
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({"vector": [], "top_k": 5}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let fts_configs = content_fts_configs();
+    let result = compactor
+        .compact_with_fts(&ns, None, &fts_configs)
+        .await
+        .unwrap();
+    assert_eq!(result.vectors_compacted, 3);
+
+    // A second compaction merges new documents into the text-only segment.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({"vectors": [doc("doc4", "Learning Rust in a weekend")]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let result = compactor
+        .compact_with_fts(&ns, None, &fts_configs)
+        .await
+        .unwrap();
+    assert_eq!(result.vectors_compacted, 4);
+
+    let manifest = zeppelin::wal::Manifest::read(&harness.store, &ns)
+        .await
+        .unwrap()
+        .unwrap();
+    let segment_id = manifest.active_segment.clone().unwrap();
+    let seg_ref = manifest
+        .segments
+        .iter()
+        .find(|s| s.id == segment_id)
+        .unwrap();
+    assert!(seg_ref.text_only);
+    assert_eq!(seg_ref.kmeans_metric, None);
+    assert_eq!(seg_ref.fts_fields, vec!["content".to_string()]);
+
+    let keys = harness
+        .store
+        .list_prefix(&format!("{ns}/segments/{segment_id}/"))
+        .await
+        .unwrap();
+    assert!(
+        keys.iter().any(|k| k.ends_with("/docs_0.bin")),
+        "missing docs blob: {keys:?}"
+    );
+    assert!(
+        !keys
+            .iter()
+            .any(|k| k.ends_with("/centroids.bin") || k.contains("/cluster_")),
+        "text-only segment wrote vector artifacts: {keys:?}"
+    );
+
+    let body = bm25_query(
+        &client,
+        &base_url,
+        &ns,
+        serde_json::json!({
+            "rank_by": ["content", "BM25", "rust"],
+            "top_k": 10,
+            "consistency": "eventual",
+        }),
+    )
+    .await;
+    let mut ids = result_ids(&body);
+    ids.sort();
+    assert_eq!(ids, vec!["doc1", "doc3", "doc4"]);
+    assert_eq!(body["scanned_segments"].as_u64().unwrap(), 1);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
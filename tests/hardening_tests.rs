@@ -1,6 +1,10 @@
 mod common;
 
-use common::server::{api_ns, cleanup_ns, start_test_server, start_test_server_with_config};
+use common::hooked_store::HookedStore;
+use common::server::{
+    api_ns, cleanup_ns, start_test_server, start_test_server_with_config,
+    start_test_server_with_store,
+};
 use common::vectors::random_vectors;
 
 use zeppelin::config::Config;
@@ -393,3 +397,40 @@ async fn test_cache_populated_after_segment_query() {
     cleanup_ns(&harness.store, &ns).await;
     harness.cleanup().await;
 }
+
+// --- Test 11: Handler panic returns a 500 JSON body ---
+
+#[tokio::test]
+async fn test_handler_panic_returns_500_json() {
+    // Reads under `poisoned/` panic, standing in for an unexpected `unwrap`
+    // deep in a handler.
+    let backend = HookedStore::new().on_get(|_, location| {
+        if location.as_ref().starts_with("poisoned/") {
+            panic!("injected read panic for {location}");
+        }
+        Box::pin(async { Ok(()) })
+    });
+    let store = zeppelin::storage::ZeppelinStore::new(std::sync::Arc::new(backend));
+    let (base_url, _dir) = start_test_server_with_store(store).await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .get(format!("{base_url}/v1/namespaces/poisoned"))
+        .header("x-request-id", "panic-test")
+        .send()
+        .await
+        .expect("panic should produce a response, not a dropped connection");
+    assert_eq!(resp.status(), 500);
+    assert_eq!(resp.headers()["x-request-id"], "panic-test");
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "internal error");
+    assert_eq!(body["code"], "panic");
+
+    // The server keeps serving afterwards.
+    let resp = client
+        .get(format!("{base_url}/v1/namespaces/healthy"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
@@ -137,6 +137,10 @@ async fn test_build_hierarchical_errors() {
         id: "z0".into(),
         values: vec![],
         attributes: None,
+        sparse: None,
+        named_vectors: None,
+        token_vectors: None,
+        version: None,
     }];
     let result = build_hierarchical(&zero_dim_vecs, &config, &harness.store, &ns, "seg_err2").await;
     assert!(result.is_err());
@@ -156,11 +160,19 @@ async fn test_build_hierarchical_errors() {
             id: "m0".into(),
             values: vec![1.0, 2.0, 3.0],
             attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         },
         VectorEntry {
             id: "m1".into(),
             values: vec![1.0, 2.0],
             attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         },
     ];
     let result =
@@ -427,6 +439,10 @@ async fn test_compact_hierarchical() {
             id: format!("frag1_{i}"),
             values: v.values.clone(),
             attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         })
         .collect();
     let frag2_vecs: Vec<VectorEntry> = all_vecs[50..]
@@ -436,6 +452,10 @@ async fn test_compact_hierarchical() {
             id: format!("frag2_{i}"),
             values: v.values.clone(),
             attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         })
         .collect();
 
@@ -220,6 +220,147 @@ async fn test_ivf_flat_search_recall() {
     harness.cleanup().await;
 }
 
+#[tokio::test]
+async fn test_ivf_flat_cosine_kmeans_improves_recall() {
+    use rand::{Rng, SeedableRng};
+
+    let harness = TestHarness::new().await;
+    let ns = harness.key("idx-cosine-kmeans");
+
+    // Tight direction clusters at widely varying norms: cosine neighbours
+    // share a direction, but Euclidean k-means splits clusters by norm.
+    let (mut vectors, _) = clustered_vectors(8, 40, 16, 0.05);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+    for v in &mut vectors {
+        let scale = rng.gen_range(0.1f32..20.0);
+        v.values.iter_mut().for_each(|x| *x *= scale);
+    }
+
+    let mut recalls = Vec::new();
+    for metric in [DistanceMetric::Euclidean, DistanceMetric::Cosine] {
+        let config = IndexingConfig {
+            default_num_centroids: 8,
+            kmeans_max_iterations: 50,
+            kmeans_convergence_epsilon: 1e-6,
+            kmeans_metric: Some(metric),
+            ..Default::default()
+        };
+        let segment_id = format!("seg_{metric}");
+        let index = IvfFlatIndex::build(&vectors, &config, &harness.store, &ns, &segment_id)
+            .await
+            .unwrap();
+
+        let mut hits = 0;
+        let queries: Vec<_> = vectors.iter().step_by(16).collect();
+        for query in &queries {
+            let results = index
+                .search(
+                    &query.values,
+                    10,
+                    1,
+                    None,
+                    DistanceMetric::Cosine,
+                    &harness.store,
+                )
+                .await
+                .unwrap();
+            let mut truth: Vec<(&str, f32)> = vectors
+                .iter()
+                .map(|v| (v.id.as_str(), cosine_distance(&query.values, &v.values)))
+                .collect();
+            truth.sort_by(|a, b| a.1.total_cmp(&b.1));
+            hits += truth
+                .iter()
+                .take(10)
+                .filter(|(id, _)| results.iter().any(|r| r.id == *id))
+                .count();
+        }
+        recalls.push(hits as f64 / (queries.len() * 10) as f64);
+    }
+
+    let (euclidean, cosine) = (recalls[0], recalls[1]);
+    assert!(cosine >= 0.9, "cosine-trained recall too low: {cosine}");
+    assert!(
+        cosine > euclidean,
+        "cosine-trained recall {cosine} should beat Euclidean-trained {euclidean}"
+    );
+
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_cosine_scores_are_exact_cosine_distances() {
+    use rand::{Rng, SeedableRng};
+    use zeppelin::index::quantization::QuantizationType;
+
+    let harness = TestHarness::new().await;
+    let ns = harness.key("idx-cosine-scores");
+
+    let (mut vectors, _) = clustered_vectors(8, 40, 16, 0.1);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+    for v in &mut vectors {
+        let scale = rng.gen_range(0.1f32..20.0);
+        v.values.iter_mut().for_each(|x| *x *= scale);
+    }
+
+    let naive_cosine = |a: &[f32], b: &[f32]| {
+        let dot: f64 = a.iter().zip(b).map(|(x, y)| *x as f64 * *y as f64).sum();
+        let na: f64 = a.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+        let nb: f64 = b.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+        (1.0 - dot / (na * nb)) as f32
+    };
+    let by_id: HashMap<&str, &[f32]> = vectors
+        .iter()
+        .map(|v| (v.id.as_str(), v.values.as_slice()))
+        .collect();
+
+    // Spherical k-means trains on normalized copies; quantized indexes score
+    // approximately first. Reported scores must still be cosine distances.
+    for quantization in [
+        QuantizationType::None,
+        QuantizationType::Scalar,
+        QuantizationType::Product,
+    ] {
+        let config = IndexingConfig {
+            default_num_centroids: 8,
+            kmeans_max_iterations: 20,
+            kmeans_metric: Some(DistanceMetric::Cosine),
+            quantization,
+            ..Default::default()
+        };
+        let segment_id = format!("seg_{quantization:?}");
+        let index = IvfFlatIndex::build(&vectors, &config, &harness.store, &ns, &segment_id)
+            .await
+            .unwrap();
+
+        for query in vectors.iter().step_by(40) {
+            let results = index
+                .search(
+                    &query.values,
+                    10,
+                    8,
+                    None,
+                    DistanceMetric::Cosine,
+                    &harness.store,
+                )
+                .await
+                .unwrap();
+            assert!(!results.is_empty());
+            for r in &results {
+                let expected = naive_cosine(&query.values, by_id[r.id.as_str()]);
+                assert!(
+                    (r.score - expected).abs() < 1e-4,
+                    "{quantization:?}: score {} for {} != cosine distance {expected}",
+                    r.score,
+                    r.id
+                );
+            }
+        }
+    }
+
+    harness.cleanup().await;
+}
+
 #[tokio::test]
 async fn test_ivf_flat_load_from_s3() {
     let harness = TestHarness::new().await;
@@ -291,6 +432,72 @@ async fn test_ivf_flat_search_with_filter() {
     harness.cleanup().await;
 }
 
+#[tokio::test]
+async fn test_range_filter_prunes_clusters_by_attr_stats() {
+    let harness = TestHarness::new().await;
+    let ns = harness.key("idx-attr-stats");
+
+    // Scores increase with the generated cluster, so `score < 40` lives
+    // entirely in the first cluster.
+    let (vectors, _) = clustered_vectors(4, 40, 16, 0.05);
+    let vectors = with_attributes(vectors, simple_attributes);
+    let config = IndexingConfig {
+        default_num_centroids: 4,
+        kmeans_max_iterations: 25,
+        bitmap_index: false,
+        ..Default::default()
+    };
+    let index = IvfFlatIndex::build(&vectors, &config, &harness.store, &ns, "seg_stats")
+        .await
+        .unwrap();
+    assert_s3_object_exists(
+        &harness.store,
+        &zeppelin::index::ivf_flat::stats::attr_stats_key(&ns, "seg_stats"),
+    )
+    .await;
+
+    let filter = Filter::Range {
+        field: "score".to_string(),
+        gte: None,
+        lte: None,
+        gt: None,
+        lt: Some(40.0),
+    };
+    let pruned = || {
+        zeppelin::metrics::CLUSTERS_PRUNED_TOTAL
+            .with_label_values(&[&ns])
+            .get()
+    };
+    let before = pruned();
+    let results = index
+        .search(
+            &vectors[100].values,
+            10,
+            4,
+            Some(&filter),
+            DistanceMetric::Euclidean,
+            &harness.store,
+        )
+        .await
+        .unwrap();
+
+    assert!(
+        pruned() > before,
+        "expected clusters outside the range to be skipped"
+    );
+    assert_eq!(results.len(), 10);
+    for r in &results {
+        let attrs = r.attributes.as_ref().unwrap();
+        assert!(
+            evaluate_filter(&filter, attrs),
+            "{} escaped the filter",
+            r.id
+        );
+    }
+
+    harness.cleanup().await;
+}
+
 #[tokio::test]
 async fn test_ivf_flat_dimension_mismatch() {
     let harness = TestHarness::new().await;
@@ -51,6 +51,10 @@ fn make_vectors(prefix: &str, count: usize, dims: usize) -> Vec<VectorEntry> {
                 id: format!("{prefix}_{i}"),
                 values,
                 attributes: None,
+                sparse: None,
+                named_vectors: None,
+                token_vectors: None,
+                version: None,
             }
         })
         .collect()
@@ -253,6 +257,10 @@ async fn test_sequence_numbers_override_ulid_ordering() {
             id: "vec_0".into(),
             values: vec_a.clone(),
             attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         }],
         vec![],
     );
@@ -266,6 +274,10 @@ async fn test_sequence_numbers_override_ulid_ordering() {
             id: "vec_0".into(),
             values: vec_b.clone(),
             attributes: None,
+            sparse: None,
+            named_vectors: None,
+            token_vectors: None,
+            version: None,
         }],
         vec![],
     );
@@ -285,12 +297,16 @@ async fn test_sequence_numbers_override_ulid_ordering() {
         vector_count: 1,
         delete_count: 0,
         sequence_number: 0, // overwritten by add_fragment
+        idempotency_key: None,
+        payload_hash: None,
     });
     manifest.add_fragment(FragmentRef {
         id: frag_a.id,
         vector_count: 1,
         delete_count: 0,
         sequence_number: 0, // overwritten by add_fragment
+        idempotency_key: None,
+        payload_hash: None,
     });
     manifest.write(store, &ns).await.unwrap();
 
@@ -343,6 +359,10 @@ async fn test_intra_fragment_overlap_rejected() {
         id: "doomed_v1".into(),
         values: vec![999.0; 16],
         attributes: None,
+        sparse: None,
+        named_vectors: None,
+        token_vectors: None,
+        version: None,
     }];
     let deletes = vec!["doomed_v1".to_string()];
 
@@ -369,6 +389,10 @@ async fn test_intra_fragment_overlap_rejected() {
         id: "keep_me".into(),
         values: vec![1.0; 16],
         attributes: None,
+        sparse: None,
+        named_vectors: None,
+        token_vectors: None,
+        version: None,
     }];
     let good_deletes = vec!["delete_me".to_string()];
     let good_result = WalFragment::try_new(good_vectors, good_deletes);
@@ -388,12 +388,16 @@ async fn test_tla_toctou_fencing_gap_cas_catches_zombie() {
         vector_count: 3,
         delete_count: 0,
         sequence_number: 0,
+        idempotency_key: None,
+        payload_hash: None,
     });
     manifest.add_fragment(FragmentRef {
         id: frag2.id,
         vector_count: 3,
         delete_count: 0,
         sequence_number: 0,
+        idempotency_key: None,
+        payload_hash: None,
     });
     manifest.write(store, &ns).await.unwrap();
 
@@ -462,6 +466,8 @@ async fn test_tla_toctou_fencing_gap_cas_catches_zombie() {
         vector_count: 3,
         delete_count: 0,
         sequence_number: 0,
+        idempotency_key: None,
+        payload_hash: None,
     });
     w2_snap.fencing_token = w2_token;
     w2_snap
@@ -477,6 +483,8 @@ async fn test_tla_toctou_fencing_gap_cas_catches_zombie() {
         vector_count: 3,
         delete_count: 0,
         sequence_number: 0,
+        idempotency_key: None,
+        payload_hash: None,
     });
     w1_modified.fencing_token = w1_token;
     let w1_cas_result = w1_modified.write_conditional(store, &ns, &w1_version).await;
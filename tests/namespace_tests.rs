@@ -2,6 +2,10 @@ mod common;
 
 use common::assertions::{assert_s3_object_exists, assert_s3_object_not_exists};
 use common::harness::TestHarness;
+use common::hooked_store::HookedStore;
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use zeppelin::error::ZeppelinError;
 use zeppelin::namespace::manager::NamespaceMetadata;
@@ -146,6 +150,32 @@ async fn test_delete_namespace() {
     harness.cleanup().await;
 }
 
+#[tokio::test]
+async fn test_namespace_exists() {
+    let harness = TestHarness::new().await;
+    let name = ns(&harness, "ns-exists");
+
+    let manager = NamespaceManager::new(harness.store.clone());
+    assert!(!manager.exists(&name).await.unwrap());
+
+    manager
+        .create(&name, 16, DistanceMetric::Cosine)
+        .await
+        .unwrap();
+    assert!(manager.exists(&name).await.unwrap());
+
+    // A fresh manager has an empty registry and must fall back to S3.
+    let fresh = NamespaceManager::new(harness.store.clone());
+    assert!(fresh.exists(&name).await.unwrap());
+
+    manager.delete(&name).await.unwrap();
+    assert!(!manager.exists(&name).await.unwrap());
+    assert!(!fresh.exists(&name).await.unwrap());
+
+    cleanup_ns(&harness.store, &name).await;
+    harness.cleanup().await;
+}
+
 #[tokio::test]
 async fn test_duplicate_create() {
     let harness = TestHarness::new().await;
@@ -256,3 +286,144 @@ async fn test_create_namespace_valid_name_formats() {
 
     harness.cleanup().await;
 }
+
+/// In-memory object store that fails manifest writes while `fail_manifest`
+/// is set, and counts `meta.json` writes.
+fn failing_manifest_store(
+    fail_manifest: Arc<AtomicBool>,
+    meta_puts: Arc<AtomicUsize>,
+) -> HookedStore {
+    HookedStore::new().on_put(move |_, location| {
+        if location.as_ref().ends_with("meta.json") {
+            meta_puts.fetch_add(1, Ordering::SeqCst);
+        }
+        let fail =
+            location.as_ref().ends_with("manifest.json") && fail_manifest.load(Ordering::SeqCst);
+        Box::pin(async move {
+            if fail {
+                return Err(object_store::Error::Generic {
+                    store: "FailingManifestStore",
+                    source: "injected manifest write failure".into(),
+                });
+            }
+            Ok(())
+        })
+    })
+}
+
+#[tokio::test]
+async fn test_create_namespace_rolls_back_on_manifest_failure() {
+    let fail_manifest = Arc::new(AtomicBool::new(true));
+    let store = zeppelin::storage::ZeppelinStore::new(Arc::new(failing_manifest_store(
+        fail_manifest.clone(),
+        Default::default(),
+    )));
+    let manager = NamespaceManager::new(store.clone());
+    let name = "ns-rollback";
+
+    let result = manager.create(name, 16, DistanceMetric::Cosine).await;
+    assert!(
+        result.is_err(),
+        "expected injected failure, got: {result:?}"
+    );
+
+    assert!(!manager.exists(name).await.unwrap());
+    assert!(store
+        .list_prefix(&format!("{name}/"))
+        .await
+        .unwrap()
+        .is_empty());
+
+    fail_manifest.store(false, Ordering::SeqCst);
+    let meta = manager
+        .create(name, 16, DistanceMetric::Cosine)
+        .await
+        .unwrap();
+    assert_eq!(meta.dimensions, 16);
+    assert!(manager.exists(name).await.unwrap());
+    assert!(store.exists(&Manifest::s3_key(name)).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_create_with_options_writes_metadata_once() {
+    let meta_puts = Arc::new(AtomicUsize::new(0));
+    let store = zeppelin::storage::ZeppelinStore::new(Arc::new(failing_manifest_store(
+        Default::default(),
+        meta_puts.clone(),
+    )));
+    let name = "ns-full-create";
+
+    let mut meta = NamespaceMetadata::new(name, 16, DistanceMetric::Cosine);
+    meta.cache_pinned = true;
+    meta.token_dimensions = 8;
+    meta.named_vectors.insert(
+        "image".to_string(),
+        zeppelin::types::NamedVectorConfig {
+            dimensions: 4,
+            distance_metric: DistanceMetric::Euclidean,
+        },
+    );
+    NamespaceManager::new(store.clone())
+        .create_with_options(meta)
+        .await
+        .unwrap();
+    assert_eq!(meta_puts.load(Ordering::SeqCst), 1);
+
+    // Read back through a fresh manager, from storage.
+    let stored = NamespaceManager::new(store).get(name).await.unwrap();
+    assert!(stored.cache_pinned);
+    assert_eq!(stored.token_dimensions, 8);
+    assert_eq!(stored.named_vectors["image"].dimensions, 4);
+}
+
+#[tokio::test]
+async fn test_list_page_reads_names_without_listing_objects() {
+    let full_listings = Arc::new(AtomicUsize::new(0));
+    let backend = {
+        let full_listings = full_listings.clone();
+        HookedStore::new().on_list(move |_| {
+            full_listings.fetch_add(1, Ordering::SeqCst);
+        })
+    };
+    let store = zeppelin::storage::ZeppelinStore::new(Arc::new(backend));
+    let manager = NamespaceManager::new(store.clone());
+    for name in ["ns-a", "ns-b", "ns-c", "other"] {
+        manager
+            .create(name, 4, DistanceMetric::Cosine)
+            .await
+            .unwrap();
+    }
+    // Objects under a namespace, and a directory that isn't one.
+    for i in 0..50 {
+        store
+            .put(
+                &format!("ns-b/wal/{i}.wal"),
+                bytes::Bytes::from_static(b"x"),
+            )
+            .await
+            .unwrap();
+    }
+    store
+        .put("ns-bb/leftover.bin", bytes::Bytes::from_static(b"x"))
+        .await
+        .unwrap();
+
+    // A fresh manager reads metadata from storage, not its registry.
+    let manager = NamespaceManager::new(store.clone());
+    let names = |page: &[NamespaceMetadata]| -> Vec<String> {
+        page.iter().map(|m| m.name.clone()).collect()
+    };
+    let (page, next) = manager.list_page(Some("ns-"), None, 2).await.unwrap();
+    assert_eq!(names(&page), ["ns-a", "ns-b"]);
+    assert_eq!(next.as_deref(), Some("ns-b"));
+    let (page, next) = manager
+        .list_page(Some("ns-"), next.as_deref(), 2)
+        .await
+        .unwrap();
+    assert_eq!(names(&page), ["ns-c"]);
+    assert_eq!(next, None);
+    let all = manager.list(None).await.unwrap();
+    assert_eq!(names(&all), ["ns-a", "ns-b", "ns-c", "other"]);
+
+    assert_eq!(full_listings.load(Ordering::SeqCst), 0);
+}
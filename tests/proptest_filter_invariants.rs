@@ -0,0 +1,231 @@
+//! Property-based tests for the production filter engine.
+//!
+//! Unlike `proptest_filter_eval.rs`, which checks a mirrored copy of the
+//! evaluator, these generate arbitrary filter trees over the full `Filter`
+//! surface and run them through `zeppelin::index::filter` directly. The
+//! engine must be total (no panics, including on NaN/infinite bounds, nested
+//! objects and arbitrary text) and satisfy the boolean identities below.
+
+use proptest::prelude::*;
+use std::collections::HashMap;
+
+use zeppelin::index::filter::{evaluate_filter, evaluate_filter_rows};
+use zeppelin::types::{AttributeValue, Filter};
+
+type Attrs = HashMap<String, AttributeValue>;
+
+// ---- Strategies ----
+
+fn arb_field_name() -> impl Strategy<Value = String> {
+    prop::sample::select(vec![
+        "color".to_string(),
+        "size".to_string(),
+        "tags".to_string(),
+        "body".to_string(),
+        "meta.author".to_string(),
+        "meta.deep.level".to_string(),
+        "missing".to_string(),
+    ])
+}
+
+fn arb_float() -> impl Strategy<Value = f64> {
+    prop_oneof![
+        8 => -100.0f64..100.0,
+        1 => Just(f64::NAN),
+        1 => prop::sample::select(vec![f64::INFINITY, f64::NEG_INFINITY, 0.0, -0.0]),
+    ]
+}
+
+fn arb_text() -> impl Strategy<Value = String> {
+    prop_oneof![
+        prop::collection::vec(
+            prop::sample::select(vec!["red", "blue", "quick", "fox", "the", "a"]),
+            0..6,
+        )
+        .prop_map(|words| words.join(" ")),
+        any::<String>(),
+    ]
+}
+
+fn arb_scalar() -> impl Strategy<Value = AttributeValue> {
+    prop_oneof![
+        arb_text().prop_map(AttributeValue::String),
+        (-100i64..100).prop_map(AttributeValue::Integer),
+        arb_float().prop_map(AttributeValue::Float),
+        any::<bool>().prop_map(AttributeValue::Bool),
+        prop::collection::vec(arb_text(), 0..4).prop_map(AttributeValue::StringList),
+        prop::collection::vec(-100i64..100, 0..4).prop_map(AttributeValue::IntegerList),
+        prop::collection::vec(arb_float(), 0..4).prop_map(AttributeValue::FloatList),
+    ]
+}
+
+fn arb_attr_value() -> impl Strategy<Value = AttributeValue> {
+    arb_scalar().prop_recursive(2, 8, 3, |inner| {
+        prop::collection::hash_map(
+            prop::sample::select(vec![
+                "author".to_string(),
+                "deep".to_string(),
+                "level".to_string(),
+            ]),
+            inner,
+            0..3,
+        )
+        .prop_map(AttributeValue::Object)
+    })
+}
+
+fn arb_attributes() -> impl Strategy<Value = Attrs> {
+    prop::collection::hash_map(
+        prop::sample::select(vec![
+            "color".to_string(),
+            "size".to_string(),
+            "tags".to_string(),
+            "body".to_string(),
+            "meta".to_string(),
+            "meta.author".to_string(),
+        ]),
+        arb_attr_value(),
+        0..6,
+    )
+}
+
+fn arb_filter_leaf() -> impl Strategy<Value = Filter> {
+    prop_oneof![
+        (arb_field_name(), arb_scalar()).prop_map(|(field, value)| Filter::Eq { field, value }),
+        (arb_field_name(), arb_scalar()).prop_map(|(field, value)| Filter::NotEq { field, value }),
+        (
+            arb_field_name(),
+            proptest::option::of(arb_float()),
+            proptest::option::of(arb_float()),
+            proptest::option::of(arb_float()),
+            proptest::option::of(arb_float()),
+        )
+            .prop_map(|(field, gte, lte, gt, lt)| Filter::Range {
+                field,
+                gte,
+                lte,
+                gt,
+                lt,
+            }),
+        (arb_field_name(), prop::collection::vec(arb_scalar(), 0..4))
+            .prop_map(|(field, values)| Filter::In { field, values }),
+        (arb_field_name(), prop::collection::vec(arb_scalar(), 0..4))
+            .prop_map(|(field, values)| Filter::NotIn { field, values }),
+        (arb_field_name(), arb_scalar())
+            .prop_map(|(field, value)| Filter::Contains { field, value }),
+        (arb_field_name(), prop::collection::vec(arb_scalar(), 0..4))
+            .prop_map(|(field, values)| Filter::ContainsAny { field, values }),
+        (arb_field_name(), arb_text())
+            .prop_map(|(field, prefix)| Filter::StartsWith { field, prefix }),
+        (arb_field_name(), arb_text()).prop_map(|(field, pattern)| Filter::Glob { field, pattern }),
+        (arb_field_name(), prop::collection::vec(arb_text(), 0..3))
+            .prop_map(|(field, tokens)| Filter::ContainsAllTokens { field, tokens }),
+        (arb_field_name(), prop::collection::vec(arb_text(), 0..3))
+            .prop_map(|(field, tokens)| Filter::ContainsTokenSequence { field, tokens }),
+    ]
+}
+
+fn arb_filter() -> impl Strategy<Value = Filter> {
+    arb_filter_leaf().prop_recursive(3, 16, 3, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..=3).prop_map(|filters| Filter::And { filters }),
+            prop::collection::vec(inner.clone(), 0..=3).prop_map(|filters| Filter::Or { filters }),
+            inner.prop_map(|f| Filter::Not {
+                filter: Box::new(f)
+            }),
+        ]
+    })
+}
+
+fn not(f: Filter) -> Filter {
+    Filter::Not {
+        filter: Box::new(f),
+    }
+}
+
+// ---- Property Tests ----
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    /// Not(f) is the complement of f; Not(Not(f)) == f.
+    #[test]
+    fn double_negation(filter in arb_filter(), attrs in arb_attributes()) {
+        let base = evaluate_filter(&filter, &attrs);
+        prop_assert_eq!(evaluate_filter(&not(filter.clone()), &attrs), !base);
+        prop_assert_eq!(evaluate_filter(&not(not(filter)), &attrs), base);
+    }
+
+    /// And([f]) == f and Or([f]) == f.
+    #[test]
+    fn singleton_combinators(filter in arb_filter(), attrs in arb_attributes()) {
+        let base = evaluate_filter(&filter, &attrs);
+        let and = Filter::And { filters: vec![filter.clone()] };
+        let or = Filter::Or { filters: vec![filter] };
+        prop_assert_eq!(evaluate_filter(&and, &attrs), base);
+        prop_assert_eq!(evaluate_filter(&or, &attrs), base);
+    }
+
+    /// Empty And is vacuously true, empty Or is false.
+    #[test]
+    fn empty_combinators(attrs in arb_attributes()) {
+        let and = Filter::And { filters: vec![] };
+        let or = Filter::Or { filters: vec![] };
+        prop_assert!(evaluate_filter(&and, &attrs));
+        prop_assert!(!evaluate_filter(&or, &attrs));
+    }
+
+    /// De Morgan: Not(And([a, b])) == Or([Not(a), Not(b)]).
+    #[test]
+    fn de_morgan(a in arb_filter(), b in arb_filter(), attrs in arb_attributes()) {
+        let lhs = not(Filter::And { filters: vec![a.clone(), b.clone()] });
+        let rhs = Filter::Or { filters: vec![not(a), not(b)] };
+        prop_assert_eq!(evaluate_filter(&lhs, &attrs), evaluate_filter(&rhs, &attrs));
+    }
+
+    /// And/Or results do not depend on operand order.
+    #[test]
+    fn combinators_commute(a in arb_filter(), b in arb_filter(), attrs in arb_attributes()) {
+        let ab = vec![a.clone(), b.clone()];
+        let ba = vec![b, a];
+        prop_assert_eq!(
+            evaluate_filter(&Filter::And { filters: ab.clone() }, &attrs),
+            evaluate_filter(&Filter::And { filters: ba.clone() }, &attrs)
+        );
+        prop_assert_eq!(
+            evaluate_filter(&Filter::Or { filters: ab }, &attrs),
+            evaluate_filter(&Filter::Or { filters: ba }, &attrs)
+        );
+    }
+
+    /// NotEq/NotIn are the complements of Eq/In.
+    #[test]
+    fn negated_leaves_complement(
+        field in arb_field_name(),
+        value in arb_scalar(),
+        values in prop::collection::vec(arb_scalar(), 0..4),
+        attrs in arb_attributes(),
+    ) {
+        let eq = Filter::Eq { field: field.clone(), value: value.clone() };
+        let not_eq = Filter::NotEq { field: field.clone(), value };
+        prop_assert_eq!(evaluate_filter(&not_eq, &attrs), !evaluate_filter(&eq, &attrs));
+
+        let in_ = Filter::In { field: field.clone(), values: values.clone() };
+        let not_in = Filter::NotIn { field, values };
+        prop_assert_eq!(evaluate_filter(&not_in, &attrs), !evaluate_filter(&in_, &attrs));
+    }
+
+    /// The columnar row evaluator agrees with per-row evaluation.
+    #[test]
+    fn rows_match_per_row_evaluation(
+        filter in arb_filter(),
+        rows in prop::collection::vec(proptest::option::of(arb_attributes()), 0..8),
+    ) {
+        let selection = evaluate_filter_rows(&filter, &rows);
+        prop_assert_eq!(selection.len(), rows.len());
+        for (row, selected) in rows.iter().zip(selection) {
+            let expected = row.as_ref().is_some_and(|attrs| evaluate_filter(&filter, attrs));
+            prop_assert_eq!(selected, expected);
+        }
+    }
+}
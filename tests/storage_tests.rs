@@ -353,3 +353,48 @@ fn test_s3_build_error() {
         Err(other) => panic!("expected Config error or Ok, got: {other}"),
     }
 }
+
+/// Test list_prefix_stream yields every key under a large prefix one at a time,
+/// and delete_prefix drains it across multiple delete batches.
+#[tokio::test]
+async fn test_list_prefix_stream_large_prefix() {
+    use futures::StreamExt;
+    use std::collections::HashSet;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = StorageConfig {
+        backend: "local".to_string(),
+        bucket: dir.path().to_str().unwrap().to_string(),
+        ..Default::default()
+    };
+    let store = ZeppelinStore::from_config(&config).unwrap();
+
+    let n = 2500;
+    for i in 0..n {
+        store
+            .put(&format!("big/obj_{i:05}.bin"), Bytes::from("x"))
+            .await
+            .unwrap();
+    }
+    store.put("other/obj.bin", Bytes::from("x")).await.unwrap();
+
+    // Consume the stream incrementally — no Vec of all keys is built.
+    let mut seen = HashSet::new();
+    let mut stream = store.list_prefix_stream("big/");
+    while let Some(key) = stream.next().await {
+        let key = key.expect("stream item should be Ok");
+        assert!(key.starts_with("big/"), "unexpected key {key}");
+        assert!(seen.insert(key), "key yielded twice");
+    }
+    drop(stream);
+    assert_eq!(seen.len(), n);
+    for i in 0..n {
+        assert!(seen.contains(&format!("big/obj_{i:05}.bin")));
+    }
+
+    // delete_prefix consumes the stream in batches and removes everything.
+    let deleted = store.delete_prefix("big/").await.unwrap();
+    assert_eq!(deleted, n);
+    assert!(store.list_prefix("big/").await.unwrap().is_empty());
+    assert!(store.exists("other/obj.bin").await.unwrap());
+}
@@ -1,6 +1,8 @@
 mod common;
 
-use common::server::{api_ns, cleanup_ns, start_test_server};
+use common::server::{api_ns, cleanup_ns, start_test_server, start_test_server_with_config};
+
+use zeppelin::config::{Config, VectorIdCharset};
 
 // --- Test 1: Namespace name with slash rejected ---
 
@@ -278,3 +280,363 @@ async fn test_vector_id_at_max_length_accepted() {
     cleanup_ns(&harness.store, &ns).await;
     harness.cleanup().await;
 }
+
+// --- Test 11: Unknown filter op rejected with supported ops ---
+
+#[tokio::test]
+async fn test_unknown_filter_op_rejected() {
+    let (base_url, harness) = start_test_server().await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "val-filter-op");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 4,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+        .json(&serde_json::json!({
+            "vector": [1.0, 0.0, 0.0, 0.0],
+            "filter": {"op": "gte", "field": "score", "value": 5},
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let error_msg = body["error"].as_str().unwrap();
+    assert!(
+        error_msg.contains("unknown filter op 'gte'"),
+        "got: {error_msg}"
+    );
+    assert!(
+        error_msg.contains("range") && error_msg.contains("not_in"),
+        "got: {error_msg}"
+    );
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+// --- Test 12: Empty values rejected ---
+
+#[tokio::test]
+async fn test_vector_empty_values_rejected() {
+    let (base_url, harness) = start_test_server().await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "val-empty-values");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 4,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({
+            "vectors": [{"id": "v1", "values": []}]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let error_msg = body["error"].as_str().unwrap();
+    assert!(
+        error_msg.contains("vector 'v1' has empty values"),
+        "got: {error_msg}"
+    );
+
+    let resp = client
+        .put(format!("{base_url}/v1/namespaces/{ns}/vectors/v2"))
+        .json(&serde_json::json!({"values": []}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let error_msg = body["error"].as_str().unwrap();
+    assert!(
+        error_msg.contains("vector 'v2' has empty values"),
+        "got: {error_msg}"
+    );
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+// --- Test 13: Non-JSON body rejected with 415 ---
+
+#[tokio::test]
+async fn test_upsert_non_json_body_rejected() {
+    let (base_url, harness) = start_test_server().await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "val-non-json");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 4,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("id=v1&values=1,0,0,0")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 415);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let error_msg = body["error"].as_str().unwrap();
+    assert!(
+        error_msg.contains("application/json")
+            && error_msg.contains("application/x-www-form-urlencoded"),
+        "got: {error_msg}"
+    );
+
+    // No content type at all is also a 415.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .body(r#"{"vectors": []}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 415);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+// --- Test 14: Malformed JSON rejected with 400 and location ---
+
+#[tokio::test]
+async fn test_upsert_malformed_json_rejected() {
+    let (base_url, harness) = start_test_server().await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "val-bad-json");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 4,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .header("content-type", "application/json")
+        .body("{\"vectors\": [{\"id\": \"v1\",\n \"values\": [1.0, 0.0,]}]}")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let error_msg = body["error"].as_str().unwrap();
+    assert!(error_msg.contains("JSON"), "got: {error_msg}");
+    assert!(error_msg.contains("line 2 column"), "got: {error_msg}");
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+// --- Test 15: Vector ID policy from config ---
+
+#[tokio::test]
+async fn test_vector_id_policy_rejects_offending_ids() {
+    let mut config = Config::load(None).unwrap();
+    config.server.max_vector_id_length = 16;
+    config.server.vector_id_charset = VectorIdCharset::UrlSafe;
+    let (base_url, harness, _cache, _dir) = start_test_server_with_config(Some(config)).await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "val-vid-policy");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 4,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let upsert = |id: String| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+            .json(&serde_json::json!({
+                "vectors": [{"id": id, "values": [1.0, 0.0, 0.0, 0.0]}]
+            }))
+            .send()
+    };
+
+    let resp = upsert("x".repeat(17)).await.unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let error_msg = body["error"].as_str().unwrap();
+    assert!(
+        error_msg.contains("exceeds maximum of 16"),
+        "got: {error_msg}"
+    );
+
+    let resp = upsert("docs/42".to_string()).await.unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let error_msg = body["error"].as_str().unwrap();
+    assert!(
+        error_msg.contains("'/'") && error_msg.contains("url_safe"),
+        "got: {error_msg}"
+    );
+
+    let resp = upsert("doc-42_v1.0~a".to_string()).await.unwrap();
+    assert_eq!(resp.status(), 200);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+// --- Test 16: Control characters rejected by default ---
+
+#[tokio::test]
+async fn test_vector_id_control_chars_rejected_by_default() {
+    let (base_url, harness) = start_test_server().await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "val-vid-ctrl");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 4,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({
+            "vectors": [{"id": "line\nbreak", "values": [1.0, 0.0, 0.0, 0.0]}]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let error_msg = body["error"].as_str().unwrap();
+    assert!(error_msg.contains("printable"), "got: {error_msg}");
+
+    // Non-ASCII printable IDs stay allowed.
+    let resp = client
+        .post(format!("{base_url}/v1/namespaces/{ns}/vectors"))
+        .json(&serde_json::json!({
+            "vectors": [{"id": "café/ünïcode id", "values": [1.0, 0.0, 0.0, 0.0]}]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+// --- Test 17: Non-numeric query vector rejected with a clear message ---
+
+#[tokio::test]
+async fn test_query_vector_of_strings_rejected() {
+    let (base_url, harness) = start_test_server().await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "val-query-vec-type");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 2,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    for body in [
+        serde_json::json!({"vector": ["a", "b"], "top_k": 5}),
+        serde_json::json!({"vector": "a,b", "top_k": 5}),
+    ] {
+        let resp = client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 400);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        let error_msg = body["error"].as_str().unwrap();
+        assert!(
+            error_msg.contains("vector must be an array of numbers"),
+            "got: {error_msg}"
+        );
+    }
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
+
+// --- Test 18: probe_gap_ratio below 1 rejected ---
+
+#[tokio::test]
+async fn test_query_probe_gap_ratio_below_one_rejected() {
+    let (base_url, harness) = start_test_server().await;
+    let client = reqwest::Client::new();
+    let ns = api_ns(&harness, "val-probe-gap");
+
+    client
+        .post(format!("{base_url}/v1/namespaces"))
+        .json(&serde_json::json!({
+            "name": ns,
+            "dimensions": 2,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let query = |ratio: f32| {
+        client
+            .post(format!("{base_url}/v1/namespaces/{ns}/query"))
+            .json(&serde_json::json!({
+                "vector": [0.1, 0.2],
+                "top_k": 5,
+                "probe_gap_ratio": ratio,
+            }))
+            .send()
+    };
+
+    let resp = query(0.5).await.unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let error_msg = body["error"].as_str().unwrap();
+    assert!(error_msg.contains("probe_gap_ratio"), "got: {error_msg}");
+
+    assert_eq!(query(1.2).await.unwrap().status(), 200);
+
+    cleanup_ns(&harness.store, &ns).await;
+    harness.cleanup().await;
+}
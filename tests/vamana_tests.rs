@@ -0,0 +1,237 @@
+mod common;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use common::assertions::{assert_recall_at_k, assert_s3_object_exists};
+use common::hooked_store::HookedStore;
+use common::vectors::{clustered_vectors, simple_attributes, with_attributes};
+
+use zeppelin::compaction::Compactor;
+use zeppelin::config::{CompactionConfig, IndexingConfig};
+use zeppelin::index::distance::compute_distance;
+use zeppelin::index::traits::VectorIndex;
+use zeppelin::index::vamana::{graph_block_key, vamana_meta_key};
+use zeppelin::index::VamanaIndex;
+use zeppelin::query::execute_query;
+use zeppelin::storage::ZeppelinStore;
+use zeppelin::types::{AttributeValue, ConsistencyLevel, DistanceMetric, Filter, VectorEntry};
+use zeppelin::wal::manifest::Manifest;
+use zeppelin::wal::{WalReader, WalWriter};
+
+/// Small graph and small blocks so a few hundred vectors span many blocks.
+fn vamana_test_config() -> IndexingConfig {
+    IndexingConfig {
+        vamana: true,
+        vamana_max_degree: 12,
+        vamana_search_list_size: 32,
+        vamana_block_size_bytes: 2048,
+        ..Default::default()
+    }
+}
+
+fn memory_store() -> ZeppelinStore {
+    ZeppelinStore::new(Arc::new(object_store::memory::InMemory::new()))
+}
+
+fn ground_truth<'a>(vectors: &'a [VectorEntry], query: &[f32], k: usize) -> Vec<&'a str> {
+    let mut distances: Vec<(&str, f32)> = vectors
+        .iter()
+        .map(|v| {
+            (
+                v.id.as_str(),
+                compute_distance(query, &v.values, DistanceMetric::Euclidean),
+            )
+        })
+        .collect();
+    distances.sort_by(|a, b| a.1.total_cmp(&b.1));
+    distances.iter().take(k).map(|(id, _)| *id).collect()
+}
+
+#[tokio::test]
+async fn test_build_vamana_writes_fixed_size_blocks() {
+    let store = memory_store();
+    let (vectors, _) = clustered_vectors(4, 100, 16, 0.1);
+
+    let index = VamanaIndex::build(&vectors, &vamana_test_config(), &store, "ns", "seg")
+        .await
+        .unwrap();
+
+    assert_eq!(index.vector_count(), 400);
+    assert_eq!(index.dimension(), 16);
+    // 4 + 4 * 12 + 4 * 16 = 116-byte records, 17 per 2 KiB block.
+    assert_eq!(index.meta().nodes_per_block, 17);
+    assert_eq!(index.num_blocks(), 400usize.div_ceil(17));
+
+    assert_s3_object_exists(&store, &vamana_meta_key("ns", "seg")).await;
+    let full = store.get(&graph_block_key("ns", "seg", 0)).await.unwrap();
+    let next = store.get(&graph_block_key("ns", "seg", 1)).await.unwrap();
+    assert_eq!(full.len(), next.len());
+    assert!(full.len() <= 2048);
+}
+
+#[tokio::test]
+async fn test_search_vamana_recall() {
+    let store = memory_store();
+    let (vectors, centroids) = clustered_vectors(8, 60, 16, 0.2);
+    let index = VamanaIndex::build(&vectors, &vamana_test_config(), &store, "ns", "seg")
+        .await
+        .unwrap();
+
+    for query in centroids
+        .iter()
+        .chain([&vectors[7].values, &vectors[301].values])
+    {
+        let results = index
+            .search(query, 10, 4, None, DistanceMetric::Euclidean, &store)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 10);
+        assert_recall_at_k(&results, &ground_truth(&vectors, query, 10), 10, 0.9);
+    }
+}
+
+#[tokio::test]
+async fn test_search_vamana_with_filter() {
+    let store = memory_store();
+    let (vectors, centroids) = clustered_vectors(4, 50, 16, 0.1);
+    let vectors = with_attributes(vectors, simple_attributes);
+    let index = VamanaIndex::build(&vectors, &vamana_test_config(), &store, "ns", "seg")
+        .await
+        .unwrap();
+
+    let filter = Filter::Eq {
+        field: "category".to_string(),
+        value: AttributeValue::String("a".to_string()),
+    };
+    let results = index
+        .search(
+            &centroids[0],
+            5,
+            4,
+            Some(&filter),
+            DistanceMetric::Euclidean,
+            &store,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(results.len(), 5);
+    for r in &results {
+        let attrs = r
+            .attributes
+            .as_ref()
+            .expect("filtered results carry attributes");
+        assert_eq!(
+            attrs.get("category"),
+            Some(&AttributeValue::String("a".to_string()))
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_search_vamana_fails_on_unreadable_attributes() {
+    let fail_attrs = Arc::new(AtomicBool::new(false));
+    let backend = {
+        let fail_attrs = fail_attrs.clone();
+        HookedStore::new().on_get(move |_, location| {
+            let fail = location.as_ref().contains("/attrs_") && fail_attrs.load(Ordering::SeqCst);
+            Box::pin(async move {
+                if fail {
+                    return Err(object_store::Error::Generic {
+                        store: "HookedStore",
+                        source: "injected attribute read failure".into(),
+                    });
+                }
+                Ok(())
+            })
+        })
+    };
+    let store = ZeppelinStore::new(Arc::new(backend));
+    let (vectors, centroids) = clustered_vectors(4, 50, 16, 0.1);
+    let vectors = with_attributes(vectors, simple_attributes);
+    let index = VamanaIndex::build(&vectors, &vamana_test_config(), &store, "ns", "seg")
+        .await
+        .unwrap();
+
+    // A filter can't be applied without attributes; the read error is
+    // returned instead of an empty result.
+    fail_attrs.store(true, Ordering::SeqCst);
+    let filter = Filter::Eq {
+        field: "category".to_string(),
+        value: AttributeValue::String("a".to_string()),
+    };
+    let result = index
+        .search(
+            &centroids[0],
+            5,
+            4,
+            Some(&filter),
+            DistanceMetric::Euclidean,
+            &store,
+        )
+        .await;
+    assert!(result.is_err(), "expected read failure, got: {result:?}");
+}
+
+#[tokio::test]
+async fn test_compact_vamana_and_query() {
+    let store = memory_store();
+    let ns = "vamana-compact";
+    Manifest::new().write(&store, ns).await.unwrap();
+    let writer = WalWriter::new(store.clone());
+
+    let (vectors, centroids) = clustered_vectors(4, 50, 16, 0.1);
+    writer
+        .append(ns, vectors[..120].to_vec(), vec![])
+        .await
+        .unwrap();
+
+    let compactor = Compactor::new(
+        store.clone(),
+        WalReader::new(store.clone()),
+        CompactionConfig::default(),
+        vamana_test_config(),
+    );
+    let result = compactor.compact(ns).await.unwrap();
+    assert_eq!(result.vectors_compacted, 120);
+
+    let manifest = Manifest::read(&store, ns).await.unwrap().unwrap();
+    let seg = manifest.segments.last().unwrap();
+    assert!(seg.vamana, "segment should be a Vamana graph");
+    assert!(!seg.hierarchical);
+    assert!(seg.cluster_count > 1);
+    assert_s3_object_exists(&store, &vamana_meta_key(ns, &seg.id)).await;
+
+    // The second compaction merges the graph segment with new writes.
+    writer
+        .append(ns, vectors[120..].to_vec(), vec![vectors[0].id.clone()])
+        .await
+        .unwrap();
+    let result = compactor.compact(ns).await.unwrap();
+    assert_eq!(result.vectors_compacted, 199);
+
+    let response = execute_query(
+        &store,
+        &WalReader::new(store.clone()),
+        ns,
+        &centroids[2],
+        10,
+        4,
+        None,
+        ConsistencyLevel::Eventual,
+        DistanceMetric::Euclidean,
+        3,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.scanned_segments, 1);
+    let live: Vec<VectorEntry> = vectors[1..].to_vec();
+    assert_recall_at_k(
+        &response.results,
+        &ground_truth(&live, &centroids[2], 10),
+        10,
+        0.9,
+    );
+}
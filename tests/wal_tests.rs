@@ -1,12 +1,16 @@
 mod common;
 
 use common::harness::TestHarness;
-use common::vectors::random_vectors;
+use common::vectors::{random_vectors, simple_attributes, with_attributes};
 
 use std::sync::Arc;
 
+use zeppelin::config::WalConfig;
 use zeppelin::error::ZeppelinError;
-use zeppelin::wal::{Manifest, WalFragment, WalReader, WalWriter};
+use zeppelin::types::{AttributeValue, Filter, SparseVector};
+use zeppelin::wal::{
+    AttributePatch, FilterPatch, Manifest, WalFragment, WalLayout, WalReader, WalWriter,
+};
 
 #[tokio::test]
 async fn test_fragment_serialize_deserialize_roundtrip() {
@@ -44,6 +48,354 @@ async fn test_fragment_checksum_corruption() {
     }
 }
 
+#[tokio::test]
+async fn test_columnar_fragment_roundtrip() {
+    let mut vectors = with_attributes(random_vectors(200, 64), simple_attributes);
+    vectors[3].attributes = None;
+    let deletes = vec!["del_1".to_string(), "del_2".to_string()];
+
+    let fragment = WalFragment::new(vectors.clone(), deletes.clone());
+    let row = fragment.to_bytes().unwrap();
+    let columnar = fragment.to_bytes_with_layout(WalLayout::Columnar).unwrap();
+    assert!(columnar.starts_with(b"ZWC1"));
+    assert!(columnar.len() < row.len() / 2);
+
+    let restored = WalFragment::from_bytes(&columnar).unwrap();
+    assert_eq!(restored.id, fragment.id);
+    assert_eq!(restored.checksum, fragment.checksum);
+    assert_eq!(restored.deletes, deletes);
+    assert_eq!(restored.vectors.len(), vectors.len());
+    for (got, want) in restored.vectors.iter().zip(&vectors) {
+        assert_eq!(got.id, want.id);
+        assert_eq!(got.values, want.values);
+        assert_eq!(got.attributes, want.attributes);
+    }
+}
+
+#[tokio::test]
+async fn test_fragment_with_sparse_vectors_roundtrip() {
+    let mut vectors = random_vectors(3, 8);
+    let plain = WalFragment::new(vectors.clone(), vec![]);
+    vectors[1].sparse = Some(SparseVector {
+        indices: vec![2, 40],
+        values: vec![0.5, 1.5],
+    });
+    let fragment = WalFragment::new(vectors, vec![]);
+    assert_ne!(fragment.checksum, plain.checksum);
+
+    // Columnar falls back to row, which carries the sparse vectors.
+    let bytes = fragment.to_bytes_with_layout(WalLayout::Columnar).unwrap();
+    assert!(bytes.starts_with(b"{"));
+    let restored = WalFragment::from_bytes(&bytes).unwrap();
+    assert_eq!(restored.vectors[1].sparse, fragment.vectors[1].sparse);
+    assert!(restored.vectors[0].sparse.is_none());
+
+    let mut tampered = restored;
+    tampered.vectors[1].sparse.as_mut().unwrap().values[0] = 9.0;
+    assert!(tampered.validate_checksum().is_err());
+}
+
+#[tokio::test]
+async fn test_fragment_with_named_vectors_roundtrip() {
+    let mut vectors = random_vectors(3, 8);
+    let plain = WalFragment::new(vectors.clone(), vec![]);
+    vectors[2].named_vectors = Some([("title".to_string(), vec![0.25, 0.75])].into());
+    let fragment = WalFragment::new(vectors, vec![]);
+    assert_ne!(fragment.checksum, plain.checksum);
+
+    let bytes = fragment.to_bytes_with_layout(WalLayout::Columnar).unwrap();
+    assert!(bytes.starts_with(b"{"));
+    let restored = WalFragment::from_bytes(&bytes).unwrap();
+    assert_eq!(
+        restored.vectors[2].named_vectors,
+        fragment.vectors[2].named_vectors
+    );
+
+    let mut tampered = restored;
+    tampered.vectors[2]
+        .named_vectors
+        .as_mut()
+        .unwrap()
+        .get_mut("title")
+        .unwrap()[0] = 9.0;
+    assert!(tampered.validate_checksum().is_err());
+}
+
+#[tokio::test]
+async fn test_fragment_with_patches_roundtrip() {
+    let vectors = random_vectors(3, 8);
+    let patches = vec![AttributePatch {
+        id: "vec_1".to_string(),
+        attributes: simple_attributes(7),
+    }];
+    let plain = WalFragment::new(vectors.clone(), vec![]);
+    let fragment = WalFragment::try_new_with_patches(
+        vectors,
+        vec!["del_1".to_string()],
+        patches.clone(),
+        Vec::new(),
+    )
+    .unwrap();
+    assert_ne!(fragment.checksum, plain.checksum);
+    assert_eq!(fragment.operation_count(), 5);
+
+    // Columnar falls back to row, which carries the patches.
+    let bytes = fragment.to_bytes_with_layout(WalLayout::Columnar).unwrap();
+    assert!(bytes.starts_with(b"{"));
+    let restored = WalFragment::from_bytes(&bytes).unwrap();
+    assert_eq!(restored.patches, patches);
+
+    // Fragments without patches don't serialize the field.
+    let plain_json = String::from_utf8(plain.to_bytes().unwrap().to_vec()).unwrap();
+    assert!(!plain_json.contains("patches"));
+
+    let mut tampered = restored;
+    tampered.patches[0]
+        .attributes
+        .insert("category".to_string(), AttributeValue::String("x".into()));
+    assert!(tampered.validate_checksum().is_err());
+
+    let filter: Filter = serde_json::from_value(serde_json::json!({
+        "op": "and",
+        "filters": [
+            {"op": "eq", "field": "category", "value": "a"},
+            {"op": "range", "field": "score", "gte": 3},
+        ],
+    }))
+    .unwrap();
+    let by_filter = WalFragment::try_new_with_patches(
+        vec![],
+        vec![],
+        vec![],
+        vec![FilterPatch {
+            filter,
+            attributes: simple_attributes(2),
+        }],
+    )
+    .unwrap();
+    let restored = WalFragment::from_bytes(&by_filter.to_bytes().unwrap()).unwrap();
+    assert_eq!(restored.filter_patches.len(), 1);
+    assert_eq!(restored.checksum, by_filter.checksum);
+}
+
+#[tokio::test]
+async fn test_empty_values_fragment_checksum_is_deterministic() {
+    // The upsert path rejects empty values, but the WAL layer must still
+    // checksum and round-trip such entries without tripping over them.
+    let vectors = vec![zeppelin::types::VectorEntry {
+        id: "empty".to_string(),
+        values: vec![],
+        attributes: None,
+        sparse: None,
+        named_vectors: None,
+        token_vectors: None,
+        version: None,
+    }];
+    let a = WalFragment::new(vectors.clone(), vec![]);
+    let b = WalFragment::new(vectors, vec![]);
+    assert_eq!(a.checksum, b.checksum);
+
+    for layout in [WalLayout::Row, WalLayout::Columnar] {
+        let bytes = a.to_bytes_with_layout(layout).unwrap();
+        let restored = WalFragment::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.checksum, a.checksum);
+        assert!(restored.vectors[0].values.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_columnar_fragment_checksum_corruption() {
+    let fragment = WalFragment::new(random_vectors(3, 16), vec![]);
+    let mut bytes = fragment
+        .to_bytes_with_layout(WalLayout::Columnar)
+        .unwrap()
+        .to_vec();
+
+    // Flip a byte inside the values matrix; the structure stays parseable.
+    let len = bytes.len();
+    bytes[len - 20] ^= 0xFF;
+    match WalFragment::from_bytes(&bytes) {
+        Err(ZeppelinError::ChecksumMismatch { .. }) => {}
+        other => panic!("expected ChecksumMismatch, got: {other:?}"),
+    }
+
+    // Truncation is reported, not panicked on.
+    assert!(WalFragment::from_bytes(&bytes[..len / 2]).is_err());
+}
+
+#[tokio::test]
+async fn test_wal_writer_append_idempotent() {
+    let store =
+        zeppelin::storage::ZeppelinStore::new(Arc::new(object_store::memory::InMemory::new()));
+    let ns = "wal-idempotent";
+    Manifest::new().write(&store, ns).await.unwrap();
+    let writer = WalWriter::new_with_layout(store.clone(), WalLayout::Columnar);
+
+    let vectors = random_vectors(4, 8);
+    let hash = WalFragment::payload_hash(&vectors, &[]);
+    assert!(!writer
+        .append_idempotent(ns, vectors.clone(), vec![], "req-1", hash)
+        .await
+        .unwrap());
+    // A retry is not written again; another key is.
+    assert!(writer
+        .append_idempotent(ns, vectors.clone(), vec![], "req-1", hash)
+        .await
+        .unwrap());
+    let deletes = vec!["vec_0".to_string()];
+    let delete_hash = WalFragment::payload_hash(&[], &deletes);
+    assert!(!writer
+        .append_idempotent(ns, vec![], deletes, "req-2", delete_hash)
+        .await
+        .unwrap());
+    // The same key with another payload is an error, and writes nothing.
+    let other = random_vectors(1, 8);
+    let other_hash = WalFragment::payload_hash(&other, &[]);
+    let err = writer
+        .append_idempotent(ns, other, vec![], "req-1", other_hash)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, ZeppelinError::IdempotencyKeyReused { .. }),
+        "{err}"
+    );
+
+    let manifest = Manifest::read(&store, ns).await.unwrap().unwrap();
+    let keys: Vec<_> = manifest
+        .fragments
+        .iter()
+        .map(|f| f.idempotency_key.as_deref())
+        .collect();
+    assert_eq!(keys, [Some("req-1"), Some("req-2")]);
+
+    // The key is stored in the fragment (as row, despite the layout) and
+    // covered by its checksum.
+    let fragments = WalReader::new(store.clone())
+        .read_uncompacted_fragments(ns)
+        .await
+        .unwrap();
+    assert_eq!(fragments[0].idempotency_key.as_deref(), Some("req-1"));
+    let mut tampered = fragments[0].clone();
+    tampered.idempotency_key = Some("req-3".to_string());
+    assert!(tampered.validate_checksum().is_err());
+}
+
+#[tokio::test]
+async fn test_wal_writer_append_deletes_if_skips_tombstoned() {
+    let store =
+        zeppelin::storage::ZeppelinStore::new(Arc::new(object_store::memory::InMemory::new()));
+    let ns = "wal-deletes-if";
+    Manifest::new().write(&store, ns).await.unwrap();
+    let writer = WalWriter::new(store.clone());
+    let reader = WalReader::new(store.clone());
+    writer
+        .append(ns, random_vectors(4, 8), vec![])
+        .await
+        .unwrap();
+
+    // Deletes only the IDs not already tombstoned, as of this writer's
+    // last commit.
+    let delete = |ids: &[&str], key: Option<(&'static str, u64)>| {
+        let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+        let (writer, reader) = (&writer, &reader);
+        async move {
+            writer
+                .append_deletes_if(ns, key, || async {
+                    let min_sequence = writer.committed_sequence(ns).unwrap_or(0);
+                    let tombstoned = reader.tombstoned_ids(ns, &ids, min_sequence, 0).await?;
+                    Ok(ids
+                        .iter()
+                        .filter(|id| !tombstoned.contains(*id))
+                        .cloned()
+                        .collect())
+                })
+                .await
+                .unwrap()
+        }
+    };
+    assert_eq!(delete(&["vec_0", "vec_1"], None).await, Some(2));
+    assert_eq!(delete(&["vec_0", "vec_2"], None).await, Some(1));
+    assert_eq!(delete(&["vec_1"], None).await, Some(0));
+    let manifest = Manifest::read(&store, ns).await.unwrap().unwrap();
+    assert_eq!(manifest.fragments.len(), 3);
+
+    // A keyed retry is a replay, even though its IDs are now tombstoned.
+    let ids = vec!["vec_3".to_string()];
+    let hash = WalFragment::payload_hash(&[], &ids);
+    assert_eq!(delete(&["vec_3"], Some(("req-1", hash))).await, Some(1));
+    assert_eq!(delete(&["vec_3"], Some(("req-1", hash))).await, None);
+    let manifest = Manifest::read(&store, ns).await.unwrap().unwrap();
+    assert_eq!(manifest.fragments.len(), 4);
+}
+
+#[tokio::test]
+async fn test_wal_writer_append_deletes_if_lands_after_coalesced_writes() {
+    let store =
+        zeppelin::storage::ZeppelinStore::new(Arc::new(object_store::memory::InMemory::new()));
+    let ns = "wal-deletes-if-coalesced";
+    Manifest::new().write(&store, ns).await.unwrap();
+    // A window long enough that only the delete can flush the batch.
+    let config = WalConfig {
+        coalesce_window_ms: Some(60_000),
+        ..Default::default()
+    };
+    let writer = Arc::new(WalWriter::from_config(store.clone(), &config));
+
+    let upsert = {
+        let writer = writer.clone();
+        tokio::spawn(async move { writer.submit(ns, random_vectors(1, 4), vec![]).await })
+    };
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let deleted = writer
+        .append_deletes_if(ns, None, || async { Ok(vec!["vec_0".to_string()]) })
+        .await
+        .unwrap();
+    assert_eq!(deleted, Some(1));
+    upsert.await.unwrap().unwrap();
+
+    // The held upsert was flushed first, so the delete wins.
+    let tombstones = WalReader::new(store.clone()).tombstones(ns).await.unwrap();
+    assert!(tombstones.contains("vec_0"));
+}
+
+#[tokio::test]
+async fn test_wal_writer_columnar_layout() {
+    let harness = TestHarness::new().await;
+    let ns = harness.key("wal-columnar");
+
+    let manifest = Manifest::new();
+    manifest.write(&harness.store, &ns).await.unwrap();
+
+    // Row and columnar fragments can coexist in one namespace.
+    let row_writer = WalWriter::new(harness.store.clone());
+    let f1 = row_writer
+        .append(&ns, random_vectors(2, 8), vec![])
+        .await
+        .unwrap();
+    let columnar_writer = WalWriter::new_with_layout(harness.store.clone(), WalLayout::Columnar);
+    let f2 = columnar_writer
+        .append(&ns, random_vectors(3, 8), vec!["del_1".to_string()])
+        .await
+        .unwrap();
+
+    let raw = harness
+        .store
+        .get(&WalFragment::s3_key(&ns, &f2.id))
+        .await
+        .unwrap();
+    assert!(raw.starts_with(b"ZWC1"));
+
+    let reader = WalReader::new(harness.store.clone());
+    let fragments = reader.read_uncompacted_fragments(&ns).await.unwrap();
+    assert_eq!(fragments.len(), 2);
+    assert_eq!(fragments[0].id, f1.id);
+    assert_eq!(fragments[1].id, f2.id);
+    assert_eq!(fragments[1].vectors.len(), 3);
+    assert_eq!(fragments[1].deletes, vec!["del_1".to_string()]);
+
+    harness.cleanup().await;
+}
+
 #[tokio::test]
 async fn test_wal_writer_append_single_fragment() {
     let harness = TestHarness::new().await;
@@ -196,6 +548,10 @@ async fn test_wal_writer_concurrent_appends() {
                 id: format!("concurrent_{i}"),
                 values: vec![i as f32; 4],
                 attributes: None,
+                sparse: None,
+                named_vectors: None,
+                token_vectors: None,
+                version: None,
             }];
             writer.append(&ns, vectors, vec![]).await.unwrap();
         }));
@@ -220,6 +576,116 @@ async fn test_wal_writer_concurrent_appends() {
     harness.cleanup().await;
 }
 
+#[tokio::test]
+async fn test_wal_writer_shutdown_flushes_buffered_writes() {
+    let harness = TestHarness::new().await;
+    let ns = harness.key("wal-shutdown-flush");
+
+    let manifest = Manifest::new();
+    manifest.write(&harness.store, &ns).await.unwrap();
+
+    // A window long enough that only shutdown can flush the batch.
+    let config = WalConfig {
+        coalesce_window_ms: Some(60_000),
+        ..Default::default()
+    };
+    let writer = Arc::new(WalWriter::from_config(harness.store.clone(), &config));
+
+    let mut handles = vec![];
+    for i in 0..10 {
+        let writer = writer.clone();
+        let ns = ns.clone();
+        handles.push(tokio::spawn(async move {
+            let vectors = vec![zeppelin::types::VectorEntry {
+                id: format!("buffered_{i}"),
+                values: vec![i as f32; 4],
+                attributes: None,
+                sparse: None,
+                named_vectors: None,
+                token_vectors: None,
+                version: None,
+            }];
+            writer.submit(&ns, vectors, vec![]).await
+        }));
+    }
+
+    // Let every write reach the buffer; none is flushed yet.
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let manifest = Manifest::read(&harness.store, &ns).await.unwrap().unwrap();
+    assert!(manifest.fragments.is_empty());
+
+    tokio::time::timeout(std::time::Duration::from_secs(10), writer.shutdown())
+        .await
+        .expect("shutdown should not wait for the coalescing window");
+
+    for handle in handles {
+        handle.await.unwrap().unwrap();
+    }
+
+    let reader = WalReader::new(harness.store.clone());
+    let fragments = reader.read_uncompacted_fragments(&ns).await.unwrap();
+    assert_eq!(fragments.len(), 1);
+    let mut ids: Vec<_> = fragments[0].vectors.iter().map(|v| v.id.clone()).collect();
+    ids.sort();
+    let mut expected: Vec<_> = (0..10).map(|i| format!("buffered_{i}")).collect();
+    expected.sort();
+    assert_eq!(ids, expected);
+
+    harness.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_coalesced_delete_and_upsert_of_same_id() {
+    let store =
+        zeppelin::storage::ZeppelinStore::new(Arc::new(object_store::memory::InMemory::new()));
+    let config = WalConfig {
+        coalesce_window_ms: Some(200),
+        ..Default::default()
+    };
+    let writer = Arc::new(WalWriter::from_config(store.clone(), &config));
+    let entry = |id: &str| {
+        let mut v = random_vectors(1, 4).remove(0);
+        v.id = id.to_string();
+        v
+    };
+
+    // Each namespace gets one batch; the later write of `a` must win.
+    for (ns, upsert_last) in [("delete-then-upsert", true), ("upsert-then-delete", false)] {
+        Manifest::new().write(&store, ns).await.unwrap();
+        let (first, second) = if upsert_last {
+            ((vec![], vec!["a".to_string()]), (vec![entry("a")], vec![]))
+        } else {
+            ((vec![entry("a")], vec![]), (vec![], vec!["a".to_string()]))
+        };
+        let submit = |(vectors, deletes): (Vec<_>, Vec<String>)| {
+            let writer = writer.clone();
+            tokio::spawn(async move { writer.submit(ns, vectors, deletes).await })
+        };
+        let first = submit(first);
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        let second = submit(second);
+        let other = submit((vec![entry("b")], vec![]));
+        for handle in [first, second, other] {
+            handle.await.unwrap().unwrap();
+        }
+
+        let fragments = WalReader::new(store.clone())
+            .read_uncompacted_fragments(ns)
+            .await
+            .unwrap();
+        assert_eq!(fragments.len(), 1, "{ns}");
+        let mut ids: Vec<_> = fragments[0].vectors.iter().map(|v| v.id.as_str()).collect();
+        ids.sort();
+        if upsert_last {
+            assert_eq!(ids, vec!["a", "b"], "{ns}");
+            assert!(fragments[0].deletes.is_empty(), "{ns}");
+        } else {
+            assert_eq!(ids, vec!["b"], "{ns}");
+            assert_eq!(fragments[0].deletes, vec!["a".to_string()], "{ns}");
+        }
+    }
+}
+
 #[tokio::test]
 async fn test_wal_writer_sequential_consistency() {
     let harness = TestHarness::new().await;
@@ -256,3 +722,64 @@ async fn test_wal_writer_sequential_consistency() {
 
     harness.cleanup().await;
 }
+
+#[tokio::test]
+async fn test_strong_query_retries_stale_manifest_read() {
+    let harness = TestHarness::new().await;
+    let ns = harness.key("wal-stale-manifest");
+
+    let writer = WalWriter::new(harness.store.clone());
+    writer
+        .append(&ns, random_vectors(5, 4), vec![])
+        .await
+        .unwrap();
+    let stale = Manifest::read(&harness.store, &ns).await.unwrap().unwrap();
+
+    let mut fresh_vector = random_vectors(1, 4);
+    fresh_vector[0].id = "fresh".to_string();
+    writer.append(&ns, fresh_vector, vec![]).await.unwrap();
+    let fresh = Manifest::read(&harness.store, &ns).await.unwrap().unwrap();
+    assert_eq!(writer.committed_sequence(&ns), Some(fresh.next_sequence));
+
+    // Simulate a backend still serving the pre-overwrite manifest, which
+    // only catches up a little later.
+    stale.write(&harness.store, &ns).await.unwrap();
+    let store = harness.store.clone();
+    let restore_ns = ns.clone();
+    let restore = tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(120)).await;
+        fresh.write(&store, &restore_ns).await.unwrap();
+    });
+
+    let reader = WalReader::new(harness.store.clone());
+    let options = zeppelin::query::QueryOptions {
+        min_manifest_sequence: writer.committed_sequence(&ns),
+        manifest_read_retries: 5,
+        ..Default::default()
+    };
+    let response = zeppelin::query::execute_query_with_options(
+        &harness.store,
+        &reader,
+        &ns,
+        &[0.0; 4],
+        10,
+        1,
+        None,
+        zeppelin::types::ConsistencyLevel::Strong,
+        zeppelin::types::DistanceMetric::Euclidean,
+        1,
+        None,
+        &options,
+    )
+    .await
+    .unwrap();
+    restore.await.unwrap();
+
+    assert_eq!(response.scanned_fragments, 2);
+    assert!(
+        response.results.iter().any(|r| r.id == "fresh"),
+        "retry should surface the fragment missing from the stale manifest"
+    );
+
+    harness.cleanup().await;
+}